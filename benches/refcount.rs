@@ -0,0 +1,95 @@
+//! Compares the throughput of atomic and local (`#[refcount(local)]`) reference counts.
+//!
+//! Run with `cargo bench --features production --bench refcount`.
+
+use com::interfaces::IUnknown;
+use std::time::{Duration, Instant};
+
+com::interfaces! {
+    #[uuid("8f0c7a5e-2b4d-4c61-a3e9-5d7b1f2e6c01")]
+    pub unsafe interface IBench : IUnknown {}
+}
+
+com::class! {
+    #[no_class_factory]
+    pub class AtomicClass : IBench {}
+
+    impl IBench for AtomicClass {}
+}
+
+com::class! {
+    #[no_class_factory]
+    #[refcount(local)]
+    pub class LocalClass : IBench {}
+
+    impl IBench for LocalClass {}
+}
+
+const ITERATIONS: u32 = 10_000_000;
+
+/// Keep the optimizer from removing the computation of `value`
+///
+/// `std::hint::black_box` requires a newer Rust version than the crate supports.
+fn black_box<T>(value: T) -> T {
+    unsafe {
+        let result = std::ptr::read_volatile(&value);
+        std::mem::forget(value);
+        result
+    }
+}
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    // Warm up
+    for _ in 0..ITERATIONS / 10 {
+        f();
+    }
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    let per_iteration = elapsed.as_nanos() as f64 / ITERATIONS as f64;
+    println!(
+        "{:<40} {:>10.2?} total {:>8.2} ns/iter",
+        name,
+        Duration::from_nanos(elapsed.as_nanos() as u64),
+        per_iteration
+    );
+}
+
+fn add_ref_release(interface: &IBench) {
+    unsafe {
+        black_box(interface.AddRef());
+        black_box(interface.Release());
+    }
+}
+
+fn main() {
+    let atomic = AtomicClass::allocate();
+    let local = LocalClass::allocate();
+
+    let atomic_interface = atomic.query_interface::<IBench>().unwrap();
+    let local_interface = local.query_interface::<IBench>().unwrap();
+
+    bench("atomic: AddRef + Release", || {
+        add_ref_release(black_box(&atomic_interface))
+    });
+    bench("local: AddRef + Release", || {
+        add_ref_release(black_box(&local_interface))
+    });
+
+    bench("atomic: interface clone + drop", || {
+        drop(black_box(atomic_interface.clone()))
+    });
+    bench("local: interface clone + drop", || {
+        drop(black_box(local_interface.clone()))
+    });
+
+    bench("atomic: ClassAllocation clone + drop", || {
+        drop(black_box(atomic.clone()))
+    });
+    bench("local: ClassAllocation clone + drop", || {
+        drop(black_box(local.clone()))
+    });
+}
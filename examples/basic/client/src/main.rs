@@ -13,12 +13,12 @@ fn run() {
     use interface::{Food, IAnimal, ICat, IDomesticAnimal, IExample, CLSID_CAT_CLASS};
     // Initialize the COM apartment
     init_apartment(ApartmentType::SingleThreaded)
-        .unwrap_or_else(|hr| panic!("Failed to initialize COM Library{:x}", hr));
+        .unwrap_or_else(|e| panic!("Failed to initialize COM Library: {}", e));
     println!("Initialized apartment");
 
     // Get a `BritishShortHairCat` class factory
     let factory = get_class_object::<IClassFactory>(&CLSID_CAT_CLASS)
-        .unwrap_or_else(|hr| panic!("Failed to get cat class object 0x{:x}", hr));
+        .unwrap_or_else(|e| panic!("Failed to get cat class object: {}", e));
     println!("Got cat class object");
 
     // Get an instance of a `BritishShortHairCat` as the `IUnknown` interface
@@ -71,7 +71,7 @@ fn run() {
 
     // Get another instance of `BritishShortHairCat` from the factory
     let cat = create_instance::<ICat>(&CLSID_CAT_CLASS)
-        .unwrap_or_else(|hr| panic!("Failed to get a cat {:x}", hr));
+        .unwrap_or_else(|e| panic!("Failed to get a cat: {}", e));
     println!("Got another cat");
     unsafe { cat.Eat(&food) };
 
@@ -10,14 +10,9 @@ pub use icat_class::ICatClass;
 pub use idomesticanimal::IDomesticAnimal;
 pub use iexample::IExample;
 
-use com::sys::IID;
+use com::CLSID;
 
-pub const CLSID_CAT_CLASS: IID = IID {
-    data1: 0xC5F45CBC,
-    data2: 0x4439,
-    data3: 0x418C,
-    data4: [0xA9, 0xF9, 0x05, 0xAC, 0x67, 0x52, 0x5E, 0x43],
-};
+pub const CLSID_CAT_CLASS: CLSID = com::guid!("C5F45CBC-4439-418C-A9F9-05AC67525E43");
 
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -2,7 +2,7 @@ use std::process::Command;
 
 fn main() {
     let mut child_proc = Command::new("cmd")
-        .args(&["/C", "cargo build --all --release"])
+        .args(["/C", "cargo build --all --release"])
         .spawn()
         .expect("Something went wrong!");
 
@@ -13,7 +13,7 @@ fn main() {
 
     if cfg!(windows) {
         let mut child_proc = Command::new("cmd")
-            .args(&["/C", "regsvr32 /s ../../target/release/server.dll"])
+            .args(["/C", "regsvr32 /s ../../target/release/server.dll"])
             .spawn()
             .expect("Something went wrong!");
         if !child_proc.wait().unwrap().success() {
@@ -22,7 +22,7 @@ fn main() {
         }
 
         let mut child_proc = Command::new("cmd")
-            .args(&["/C", "cargo run --release --package client"])
+            .args(["/C", "cargo run --release --package client"])
             .spawn()
             .expect("Something went wrong!");
         if !child_proc.wait().unwrap().success() {
@@ -422,9 +422,8 @@ fn get_dxgi_factory(device: &ID3D11Device) -> IDXGIFactory2 {
 fn create_render_target(factory: &ID2D1Factory1, device: &mut ID3D11Device) -> ID2D1DeviceContext {
     let dxdevice = device.query_interface::<IDXGIDevice>();
 
-    let mut d2device = None;
     let target = unsafe {
-        HR!(factory.CreateDevice(&dxdevice, &mut d2device));
+        let d2device = factory.CreateDevice(&dxdevice).unwrap();
         let mut target = None;
 
         HR!(d2device
@@ -525,25 +524,14 @@ impl DeviceIndependentResources {
         }
         let style = style.unwrap();
 
-        let class_id = com::CLSID {
-            data1: 0x4C1FC63A,
-            data2: 0x695C,
-            data3: 0x47E8,
-            data4: [0xA3, 0x39, 0x1A, 0x19, 0x4B, 0xE3, 0xD0, 0xB8],
-        };
+        let class_id = com::guid!("4C1FC63A-695C-47E8-A339-1A194BE3D0B8");
         let animation_manager =
             com::runtime::create_instance::<IUIAnimationManager>(&class_id).unwrap();
 
         let mut animation_frequency = winnt::LARGE_INTEGER::default();
         let mut animation_variable = None;
 
-        let class_id = com::CLSID {
-            // 1D6322AD-AA85-4EF5-A828-86D71067D145
-            data1: 0x1D6322AD,
-            data2: 0xAA85,
-            data3: 0x4EF5,
-            data4: [0xA8, 0x28, 0x86, 0xD7, 0x10, 0x67, 0xD1, 0x45],
-        };
+        let class_id = com::guid!("1D6322AD-AA85-4EF5-A828-86D71067D145");
         let library: IUIAnimationTransitionLibrary =
             com::runtime::create_instance(&class_id).unwrap();
         let mut transition = None;
@@ -690,7 +678,7 @@ interfaces! {
         fn CreateDevice(
             &self,
             dxgi_device: Option<IDXGIDevice>,
-            d2d_device: *mut Option<ID2D1Device>,
+            #[retval] d2d_device: *mut Option<ID2D1Device>,
         ) -> HRESULT;
         fn CreateStrokeStyle(
             &self,
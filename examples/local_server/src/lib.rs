@@ -0,0 +1,194 @@
+//! Interfaces and classes hosted by the `local_server` binary, which clients in
+//! other processes call through `com::remote`
+
+use com::interfaces::IUnknown;
+use com::marshal::Marshal;
+use com::sys::{E_INVALIDARG, E_POINTER, HRESULT, NOERROR};
+use com::{BStr, Interface, Variant, CLSID};
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+pub const CLSID_CALCULATOR: CLSID = com::guid!("6c3c2f6e-4b0a-4f43-9a57-1b0d6f3c9e01");
+
+/// A point passed by `ICalculator::Midpoint`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Marshal)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+com::interfaces! {
+    #[uuid("6c3c2f6e-4b0a-4f43-9a57-1b0d6f3c9e02")]
+    #[remote]
+    pub unsafe interface ICalculator: IUnknown {
+        pub fn Add(&self, a: i32, b: i32, #[retval] sum: *mut i32) -> HRESULT;
+        pub fn Divide(&self, a: i32, b: i32, quotient: *mut i32, remainder: *mut i32) -> HRESULT;
+        pub fn Scale(&self, value: f64, factor: *const f64, #[retval] result: *mut f64) -> HRESULT;
+        pub fn CreateCounter(&self, #[retval] counter: *mut Option<ICounter>) -> HRESULT;
+        pub fn IsLastCounter(&self, counter: ICounter, #[retval] last: *mut bool) -> HRESULT;
+        pub fn Apply(&self, callback: ICallback, value: i32, #[retval] result: *mut i32) -> HRESULT;
+        pub fn LiveObjects(&self, #[retval] count: *mut u32) -> HRESULT;
+        pub fn Sum(&self, count: u32, #[size_is(count)] values: *const i32, #[retval] sum: *mut i32) -> HRESULT;
+        pub fn Squares(&self, #[size_is(count)] squares: *mut i32, count: u32) -> HRESULT;
+        pub fn Midpoint(&self, a: *const Point, b: *const Point, #[retval] midpoint: *mut Point) -> HRESULT;
+        pub fn Greet(&self, name: BStr, #[retval] greeting: *mut BStr) -> HRESULT;
+        pub fn Echo(&self, value: Variant, #[retval] echo: *mut Variant) -> HRESULT;
+    }
+
+    #[uuid("6c3c2f6e-4b0a-4f43-9a57-1b0d6f3c9e03")]
+    #[remote]
+    pub unsafe interface ICounter: IUnknown {
+        pub fn Increment(&self, #[retval] value: *mut u32) -> HRESULT;
+    }
+
+    #[uuid("6c3c2f6e-4b0a-4f43-9a57-1b0d6f3c9e04")]
+    #[remote]
+    pub unsafe interface INamed: IUnknown {
+        pub fn Name(&self, #[retval] name: *mut u64) -> HRESULT;
+    }
+
+    #[uuid("6c3c2f6e-4b0a-4f43-9a57-1b0d6f3c9e05")]
+    #[remote]
+    pub unsafe interface ICallback: IUnknown {
+        pub fn Call(&self, value: i32, #[retval] result: *mut i32) -> HRESULT;
+    }
+}
+
+/// The number of objects alive in the server
+static LIVE_OBJECTS: AtomicU32 = AtomicU32::new(0);
+
+/// A field counting the objects alive in the server
+#[derive(Debug)]
+pub struct Tracker(());
+
+impl Default for Tracker {
+    fn default() -> Self {
+        LIVE_OBJECTS.fetch_add(1, Ordering::SeqCst);
+        Tracker(())
+    }
+}
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        LIVE_OBJECTS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The address identifying an object
+fn identity<I: Interface>(interface: &I) -> usize {
+    let unknown = interface.as_iunknown().query_interface::<IUnknown>();
+    unknown.map_or(0, |unknown| unknown.as_raw().as_ptr() as usize)
+}
+
+com::class! {
+    pub class Calculator: ICalculator {
+        tracker: Tracker,
+        last_counter: Cell<usize>,
+    }
+
+    impl ICalculator for Calculator {
+        fn Add(&self, a: i32, b: i32) -> Result<i32, HRESULT> {
+            Ok(a.wrapping_add(b))
+        }
+
+        fn Divide(&self, a: i32, b: i32, quotient: *mut i32, remainder: *mut i32) -> HRESULT {
+            if b == 0 {
+                return E_INVALIDARG;
+            }
+            if quotient.is_null() || remainder.is_null() {
+                return E_POINTER;
+            }
+            unsafe {
+                *quotient = a / b;
+                *remainder = a % b;
+            }
+            NOERROR
+        }
+
+        fn Scale(&self, value: f64, factor: *const f64) -> Result<f64, HRESULT> {
+            Ok(value * unsafe { factor.as_ref() }.copied().unwrap_or(1.0))
+        }
+
+        fn CreateCounter(&self) -> Result<Option<ICounter>, HRESULT> {
+            let counter = Counter::allocate(Tracker::default(), Cell::new(0))
+                .query_interface::<ICounter>();
+            self.last_counter.set(counter.as_ref().map_or(0, identity));
+            Ok(counter)
+        }
+
+        fn IsLastCounter(&self, counter: ICounter) -> Result<bool, HRESULT> {
+            Ok(identity(&counter) == self.last_counter.get())
+        }
+
+        fn Apply(&self, callback: ICallback, value: i32) -> Result<i32, com::Error> {
+            unsafe { callback.Call(value) }
+        }
+
+        fn LiveObjects(&self) -> Result<u32, HRESULT> {
+            Ok(LIVE_OBJECTS.load(Ordering::SeqCst))
+        }
+
+        fn Sum(&self, count: u32, values: *const i32) -> Result<i32, HRESULT> {
+            if count == 0 {
+                return Ok(0);
+            }
+            let values = unsafe { std::slice::from_raw_parts(values, count as usize) };
+            Ok(values.iter().fold(0i32, |sum, value| sum.wrapping_add(*value)))
+        }
+
+        fn Squares(&self, squares: *mut i32, count: u32) -> HRESULT {
+            for i in 0..count {
+                unsafe { *squares.add(i as usize) = (i * i) as i32 };
+            }
+            NOERROR
+        }
+
+        fn Midpoint(&self, a: *const Point, b: *const Point) -> Result<Point, HRESULT> {
+            match unsafe { (a.as_ref(), b.as_ref()) } {
+                (Some(a), Some(b)) => Ok(Point {
+                    x: (a.x + b.x) / 2.0,
+                    y: (a.y + b.y) / 2.0,
+                }),
+                _ => Err(E_POINTER),
+            }
+        }
+
+        fn Greet(&self, name: BStr) -> Result<BStr, HRESULT> {
+            Ok(BStr::from(format!("Hello, {}!", name)))
+        }
+
+        fn Echo(&self, value: Variant) -> Result<Variant, HRESULT> {
+            Ok(value)
+        }
+    }
+}
+
+com::class! {
+    pub class Counter: ICounter, INamed {
+        tracker: Tracker,
+        count: Cell<u32>,
+    }
+
+    impl ICounter for Counter {
+        fn Increment(&self) -> Result<u32, HRESULT> {
+            self.count.set(self.count.get() + 1);
+            Ok(self.count.get())
+        }
+    }
+
+    impl INamed for Counter {
+        fn Name(&self) -> Result<u64, HRESULT> {
+            Ok(0xC0047E5)
+        }
+    }
+}
+
+/// Register the interfaces called by clients
+pub fn register_interfaces() {
+    com::remote::register::<ICalculator>();
+    com::remote::register::<ICounter>();
+    com::remote::register::<INamed>();
+    com::remote::register::<ICallback>();
+}
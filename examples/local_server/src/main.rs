@@ -0,0 +1,31 @@
+//! Host the classes of `local_server` for clients in other processes
+//!
+//! Usage: `local_server ADDRESS`, where the address is the path of a Unix domain
+//! socket, or the name of a named pipe on Windows. `ready` is printed once clients
+//! can connect.
+
+use com::production::ClassRegistry;
+use com::remote::LocalServer;
+use local_server::{register_interfaces, Calculator, CLSID_CALCULATOR};
+
+fn main() -> std::io::Result<()> {
+    let address = match std::env::args().nth(1) {
+        Some(address) => address,
+        None => {
+            eprintln!("usage: local_server ADDRESS");
+            std::process::exit(1);
+        }
+    };
+
+    register_interfaces();
+    let mut classes = ClassRegistry::new();
+    classes.register::<Calculator>(CLSID_CALCULATOR);
+
+    #[cfg(unix)]
+    let listener = std::os::unix::net::UnixListener::bind(&address)?;
+    #[cfg(windows)]
+    let listener = com::remote::NamedPipeListener::bind(&address)?;
+
+    println!("ready");
+    LocalServer::new(classes).run(listener)
+}
@@ -0,0 +1,312 @@
+//! Call the classes of the `local_server` binary from another process
+#![cfg(unix)]
+
+use std::io::{BufRead, BufReader};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use com::remote::Connection;
+use com::sys::{
+    CLASS_E_CLASSNOTAVAILABLE, E_INVALIDARG, E_POINTER, RPC_E_DISCONNECTED, RPC_E_VERSION_MISMATCH,
+    S_OK,
+};
+use com::{BStr, Error, HResult, Interface, Variant, VariantValue};
+use local_server::{ICalculator, ICallback, ICounter, INamed, Point, CLSID_CALCULATOR};
+
+/// A server process, which is killed when the test ends
+struct Server {
+    child: Child,
+    path: PathBuf,
+}
+
+impl Server {
+    fn start() -> Server {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "com-rs-local-server-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = std::fs::remove_file(&path);
+        let mut child = Command::new(env!("CARGO_BIN_EXE_local_server"))
+            .arg(&path)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        assert_eq!(line.trim(), "ready");
+        Server { child, path }
+    }
+
+    fn connect(&self) -> Connection {
+        Connection::new(UnixStream::connect(&self.path).unwrap())
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn create_calculator(connection: &Connection) -> ICalculator {
+    connection
+        .create_instance::<ICalculator>(&CLSID_CALCULATOR)
+        .unwrap()
+}
+
+com::class! {
+    pub class Doubler: ICallback {
+        calls: std::cell::Cell<u32>,
+    }
+
+    impl ICallback for Doubler {
+        fn Call(&self, value: i32) -> Result<i32, com::sys::HRESULT> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(value * 2)
+        }
+    }
+}
+
+com::class! {
+    pub class Refuser: ICallback {}
+
+    impl ICallback for Refuser {
+        fn Call(&self, value: i32) -> Result<i32, Error> {
+            Err(Error::with_description(
+                HResult(E_INVALIDARG),
+                format!("{} is not accepted", value),
+            ))
+        }
+    }
+}
+
+#[test]
+fn parameters() {
+    let server = Server::start();
+    let connection = server.connect();
+    let calculator = create_calculator(&connection);
+
+    assert_eq!(unsafe { calculator.Add(2, 3) }, Ok(5));
+
+    let (mut quotient, mut remainder) = (0, 0);
+    let hr = unsafe { calculator.Divide(17, 5, &mut quotient, &mut remainder) };
+    assert_eq!(hr, S_OK);
+    assert_eq!((quotient, remainder), (3, 2));
+
+    assert_eq!(unsafe { calculator.Scale(1.5, &2.0) }, Ok(3.0));
+    assert_eq!(unsafe { calculator.Scale(1.5, std::ptr::null()) }, Ok(1.5));
+}
+
+#[test]
+fn arrays() {
+    let server = Server::start();
+    let connection = server.connect();
+    let calculator = create_calculator(&connection);
+
+    let values = [1, 2, 3, 4];
+    assert_eq!(unsafe { calculator.Sum(4, values.as_ptr()) }, Ok(10));
+    assert_eq!(unsafe { calculator.Sum(2, values.as_ptr()) }, Ok(3));
+    assert_eq!(unsafe { calculator.Sum(0, std::ptr::null()) }, Ok(0));
+    let error = unsafe { calculator.Sum(1, std::ptr::null()) }.unwrap_err();
+    assert_eq!(error.code(), HResult(E_POINTER));
+
+    let mut squares = [-1; 5];
+    assert_eq!(unsafe { calculator.Squares(squares.as_mut_ptr(), 4) }, S_OK);
+    assert_eq!(squares, [0, 1, 4, 9, -1]);
+    assert_eq!(unsafe { calculator.Squares(std::ptr::null_mut(), 0) }, S_OK);
+}
+
+#[test]
+fn structs_and_strings() {
+    let server = Server::start();
+    let connection = server.connect();
+    let calculator = create_calculator(&connection);
+
+    let a = Point { x: 1.0, y: 2.0 };
+    let b = Point { x: 3.0, y: -2.0 };
+    assert_eq!(
+        unsafe { calculator.Midpoint(&a, &b) },
+        Ok(Point { x: 2.0, y: 0.0 })
+    );
+    let error = unsafe { calculator.Midpoint(&a, std::ptr::null()) }.unwrap_err();
+    assert_eq!(error.code(), HResult(E_POINTER));
+
+    let greeting = unsafe { calculator.Greet(BStr::from("wörld 🦀")) }.unwrap();
+    assert_eq!(greeting, "Hello, wörld 🦀!");
+    assert_eq!(
+        unsafe { calculator.Greet(BStr::new()) }.unwrap(),
+        "Hello, !"
+    );
+
+    for value in [
+        Variant::new(),
+        Variant::from(42),
+        Variant::from(-1.5),
+        Variant::from(true),
+        Variant::from("text"),
+    ] {
+        let echo = unsafe { calculator.Echo(&value) }.unwrap();
+        assert_eq!(echo, value);
+    }
+
+    // Objects in variants are passed as references
+    let counter = unsafe { calculator.CreateCounter() }.unwrap().unwrap();
+    let value = Variant::from(counter.as_iunknown().clone());
+    match unsafe { calculator.Echo(&value) }.unwrap().into_value() {
+        VariantValue::Unknown(Some(object)) => {
+            assert_eq!(object.query_interface::<ICounter>(), Some(counter))
+        }
+        value => panic!("unexpected value {:?}", value),
+    }
+}
+
+#[test]
+fn errors() {
+    let server = Server::start();
+    let connection = server.connect();
+    let calculator = create_calculator(&connection);
+
+    let (mut quotient, mut remainder) = (-1, -1);
+    let hr = unsafe { calculator.Divide(1, 0, &mut quotient, &mut remainder) };
+    assert_eq!(hr, E_INVALIDARG);
+    // Out parameters are only written by successful calls
+    assert_eq!((quotient, remainder), (-1, -1));
+
+    let unknown = com::guid!("6c3c2f6e-4b0a-4f43-9a57-1b0d6f3c9eff");
+    let error = connection
+        .create_instance::<ICalculator>(&unknown)
+        .unwrap_err();
+    assert_eq!(error.code(), HResult(CLASS_E_CLASSNOTAVAILABLE));
+
+    // The error information of failed calls is passed back to the caller, here from
+    // the callback to the server, and from the server to the client
+    let refuser = Refuser::allocate().query_interface::<ICallback>().unwrap();
+    let error = unsafe { calculator.Apply(&refuser, 3) }.unwrap_err();
+    assert_eq!(error.code(), HResult(E_INVALIDARG));
+    assert_eq!(error.description(), Some("3 is not accepted"));
+
+    // Failed calls without error information clear it
+    Error::with_description(HResult(E_INVALIDARG), "stale").set_error_info();
+    let hr = unsafe { calculator.Divide(1, 0, &mut quotient, &mut remainder) };
+    assert!(Error::from_error_info(HResult(hr)).info().is_none());
+}
+
+#[test]
+fn interfaces() {
+    let server = Server::start();
+    let connection = server.connect();
+    let calculator = create_calculator(&connection);
+
+    let counter = unsafe { calculator.CreateCounter() }.unwrap().unwrap();
+    assert_eq!(unsafe { counter.Increment() }, Ok(1));
+    assert_eq!(unsafe { counter.Increment() }, Ok(2));
+
+    // Objects passed back to the server are the original objects
+    assert_eq!(unsafe { calculator.IsLastCounter(&counter) }, Ok(true));
+    let other = unsafe { calculator.CreateCounter() }.unwrap().unwrap();
+    assert_eq!(unsafe { calculator.IsLastCounter(&counter) }, Ok(false));
+    assert_eq!(unsafe { calculator.IsLastCounter(&other) }, Ok(true));
+
+    // Query the object in the server for another interface
+    com::remote::register::<INamed>();
+    let named = counter.query_interface::<INamed>().unwrap();
+    assert_eq!(unsafe { named.Name() }, Ok(0xC0047E5));
+    assert!(counter.query_interface::<ICallback>().is_none());
+    let back = named.query_interface::<ICounter>().unwrap();
+    assert_eq!(back, counter);
+    assert_eq!(unsafe { back.Increment() }, Ok(3));
+}
+
+#[test]
+fn callbacks() {
+    let server = Server::start();
+    let connection = server.connect();
+    let calculator = create_calculator(&connection);
+
+    let doubler = Doubler::allocate(Default::default());
+    let callback = doubler.query_interface::<ICallback>().unwrap();
+    assert_eq!(unsafe { calculator.Apply(&callback, 21) }, Ok(42));
+    assert_eq!(unsafe { calculator.Apply(&callback, -4) }, Ok(-8));
+    assert_eq!(doubler.calls.get(), 2);
+}
+
+#[test]
+fn release() {
+    let server = Server::start();
+    let connection = server.connect();
+    let calculator = create_calculator(&connection);
+    assert_eq!(unsafe { calculator.LiveObjects() }, Ok(1));
+
+    let counter = unsafe { calculator.CreateCounter() }.unwrap().unwrap();
+    let clone = counter.clone();
+    assert_eq!(unsafe { calculator.LiveObjects() }, Ok(2));
+    drop(counter);
+    assert_eq!(unsafe { calculator.LiveObjects() }, Ok(2));
+    drop(clone);
+    assert_eq!(unsafe { calculator.LiveObjects() }, Ok(1));
+
+    // Objects are released when their connection is closed
+    let other = server.connect();
+    let _counter = unsafe { create_calculator(&other).CreateCounter() }.unwrap();
+    other.close();
+    wait_for_live_objects(&calculator, 1);
+}
+
+/// Wait for other connections to release their objects
+fn wait_for_live_objects(calculator: &ICalculator, count: u32) {
+    for _ in 0..500 {
+        if unsafe { calculator.LiveObjects() } == Ok(count) {
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    panic!("{:?} objects are alive", unsafe {
+        calculator.LiveObjects()
+    });
+}
+
+#[test]
+fn version_mismatch() {
+    use std::io::{Read, Write};
+
+    let (client, mut server) = UnixStream::pair().unwrap();
+    let connection = Connection::new(client);
+
+    // The connection sends its version first
+    let mut hello = [0; 9];
+    server.read_exact(&mut hello).unwrap();
+    assert_eq!(hello[..4], 5u32.to_le_bytes());
+    assert_eq!(hello[4], 0);
+    assert_eq!(hello[5..], com::marshal::VERSION.to_le_bytes());
+
+    let mut other = vec![5, 0, 0, 0, 0];
+    other.extend_from_slice(&(com::marshal::VERSION + 1).to_le_bytes());
+    server.write_all(&other).unwrap();
+    let error = connection
+        .create_instance::<ICalculator>(&CLSID_CALCULATOR)
+        .unwrap_err();
+    assert_eq!(error.code(), HResult(RPC_E_VERSION_MISMATCH));
+    assert!(!connection.is_connected());
+}
+
+#[test]
+fn disconnect() {
+    let mut server = Server::start();
+    let connection = server.connect();
+    let calculator = create_calculator(&connection);
+    assert_eq!(unsafe { calculator.Add(1, 1) }, Ok(2));
+
+    server.child.kill().unwrap();
+    server.child.wait().unwrap();
+    let error = unsafe { calculator.Add(1, 1) }.unwrap_err();
+    assert_eq!(error.code(), HResult(RPC_E_DISCONNECTED));
+    assert!(!connection.is_connected());
+}
@@ -63,6 +63,7 @@ impl Environment {
         com::runtime::init_apartment(com::runtime::ApartmentType::SingleThreaded)
             .expect("Failed to initialize COM.");
 
+        #[allow(clippy::arc_with_non_send_sync)]
         let environment = Arc::new(Mutex::new(None));
 
         // create a handler that will store created environments in our local variable
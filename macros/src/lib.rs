@@ -17,3 +17,11 @@ pub fn class(input: TokenStream) -> TokenStream {
     let class = syn::parse_macro_input!(input as Class);
     class.to_tokens().into()
 }
+
+#[proc_macro_derive(Marshal)]
+pub fn derive_marshal(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    com_macros_support::marshal::expand_derive(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
@@ -0,0 +1,54 @@
+//! Generate a C/C++ header from the `interfaces!` declarations in Rust source files
+//!
+//! Usage: `com-header [-o OUTPUT] INPUT...`
+//!
+//! The header is written to standard output unless an output file is given.
+use std::path::PathBuf;
+use std::process::exit;
+
+const USAGE: &str = "usage: com-header [-o OUTPUT] INPUT...";
+
+fn main() {
+    let mut output = None;
+    let mut inputs = Vec::new();
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-o") | Some("--output") => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => fail(USAGE),
+            },
+            Some("-h") | Some("--help") => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+    if inputs.is_empty() {
+        fail(USAGE);
+    }
+
+    let mut interfaces = Vec::new();
+    for input in &inputs {
+        let source = std::fs::read_to_string(input)
+            .unwrap_or_else(|e| fail(&format!("{}: {}", input.display(), e)));
+        match com_macros_support::header::parse_source(&source) {
+            Ok(parsed) => interfaces.extend(parsed),
+            Err(e) => fail(&format!("{}: {}", input.display(), e)),
+        }
+    }
+
+    let header =
+        com_macros_support::header::generate(&interfaces).unwrap_or_else(|e| fail(&e.to_string()));
+    match output {
+        Some(output) => std::fs::write(&output, header)
+            .unwrap_or_else(|e| fail(&format!("{}: {}", output.display(), e))),
+        None => print!("{}", header),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("com-header: {}", message);
+    exit(1)
+}
@@ -0,0 +1,48 @@
+//! Generate Rust `interfaces!` declarations from an IDL file
+//!
+//! Usage: `com-idl [-o OUTPUT] INPUT`
+//!
+//! The Rust source is written to standard output unless an output file is given.
+//! The IDL files imported by the input are looked up in its directory.
+use std::path::PathBuf;
+use std::process::exit;
+
+const USAGE: &str = "usage: com-idl [-o OUTPUT] INPUT";
+
+fn main() {
+    let mut output = None;
+    let mut input = None;
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-o") | Some("--output") => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => fail(USAGE),
+            },
+            Some("-h") | Some("--help") => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => fail(USAGE),
+        }
+    }
+    let input = input.unwrap_or_else(|| fail(USAGE));
+
+    let source = std::fs::read_to_string(&input)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", input.display(), e)));
+    let directory = input.parent().map(PathBuf::from).unwrap_or_default();
+    let read_import = |file: &str| std::fs::read_to_string(directory.join(file)).ok();
+    let rust = com_macros_support::idl::convert_with_imports(&source, read_import)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", input.display(), e)));
+    match output {
+        Some(output) => std::fs::write(&output, rust)
+            .unwrap_or_else(|e| fail(&format!("{}: {}", output.display(), e))),
+        None => print!("{}", rust),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("com-idl: {}", message);
+    exit(1)
+}
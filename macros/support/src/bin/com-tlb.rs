@@ -0,0 +1,45 @@
+//! Generate Rust `interfaces!` declarations from a type library (`.tlb` file)
+//!
+//! Usage: `com-tlb [-o OUTPUT] INPUT`
+//!
+//! The Rust source is written to standard output unless an output file is given.
+use std::path::PathBuf;
+use std::process::exit;
+
+const USAGE: &str = "usage: com-tlb [-o OUTPUT] INPUT";
+
+fn main() {
+    let mut output = None;
+    let mut input = None;
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-o") | Some("--output") => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => fail(USAGE),
+            },
+            Some("-h") | Some("--help") => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => fail(USAGE),
+        }
+    }
+    let input = input.unwrap_or_else(|| fail(USAGE));
+
+    let bytes =
+        std::fs::read(&input).unwrap_or_else(|e| fail(&format!("{}: {}", input.display(), e)));
+    let rust = com_macros_support::tlb::convert(&bytes)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", input.display(), e)));
+    match output {
+        Some(output) => std::fs::write(&output, rust)
+            .unwrap_or_else(|e| fail(&format!("{}: {}", output.display(), e))),
+        None => print!("{}", rust),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("com-tlb: {}", message);
+    exit(1)
+}
@@ -0,0 +1,97 @@
+//! Build a type library (`.tlb` file) from the `interfaces!` and `class!`
+//! declarations in Rust source files
+//!
+//! Usage: `com-typelib --name NAME --uuid UUID [--version MAJOR.MINOR]
+//! [--helpstring TEXT] [--win32] [--idl] [-o OUTPUT] INPUT...`
+//!
+//! With `--idl`, the MIDL source of the type library is written instead. The output
+//! is written to standard output unless an output file is given.
+use com_macros_support::tlb::{build, Declarations, Library, SysKind};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::exit;
+
+const USAGE: &str = "usage: com-typelib --name NAME --uuid UUID [--version MAJOR.MINOR] \
+                     [--helpstring TEXT] [--win32] [--idl] [-o OUTPUT] INPUT...";
+
+fn main() {
+    let mut output = None;
+    let mut inputs = Vec::new();
+    let mut name = None;
+    let mut uuid = None;
+    let mut version = (1, 0);
+    let mut doc_string = None;
+    let mut syskind = SysKind::Win64;
+    let mut idl = false;
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || match args.next().map(|v| v.into_string()) {
+            Some(Ok(value)) => value,
+            _ => fail(USAGE),
+        };
+        match arg.to_str() {
+            Some("-o") | Some("--output") => output = Some(PathBuf::from(value())),
+            Some("--name") => name = Some(value()),
+            Some("--uuid") => uuid = Some(value()),
+            Some("--version") => version = parse_version(&value()),
+            Some("--helpstring") => doc_string = Some(value()),
+            Some("--win32") => syskind = SysKind::Win32,
+            Some("--idl") => idl = true,
+            Some("-h") | Some("--help") => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+    let (name, uuid) = match (name, uuid) {
+        (Some(name), Some(uuid)) if !inputs.is_empty() => (name, uuid),
+        _ => fail(USAGE),
+    };
+
+    let guid = uuid.parse().unwrap_or_else(|e| fail(&format!("{}", e)));
+    let mut library = Library::new(name, guid);
+    library.major_version = version.0;
+    library.minor_version = version.1;
+    library.doc_string = doc_string;
+    library.syskind = syskind;
+
+    let mut declarations = Declarations::default();
+    for input in &inputs {
+        let source = std::fs::read_to_string(input)
+            .unwrap_or_else(|e| fail(&format!("{}: {}", input.display(), e)));
+        if let Err(e) = declarations.parse_source(&source) {
+            fail(&format!("{}: {}", input.display(), e));
+        }
+    }
+    let lib = build(&library, &declarations).unwrap_or_else(|e| fail(&e.to_string()));
+    let bytes = if idl {
+        lib.to_idl()
+            .unwrap_or_else(|e| fail(&e.to_string()))
+            .into_bytes()
+    } else {
+        lib.to_bytes()
+    };
+    match output {
+        Some(output) => std::fs::write(&output, bytes)
+            .unwrap_or_else(|e| fail(&format!("{}: {}", output.display(), e))),
+        None => std::io::stdout()
+            .write_all(&bytes)
+            .unwrap_or_else(|e| fail(&e.to_string())),
+    }
+}
+
+/// Parse a version such as `1.0`
+fn parse_version(version: &str) -> (u16, u16) {
+    let mut parts = version.splitn(2, '.').map(str::parse::<u16>);
+    match (parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor))) => (major, minor),
+        (Some(Ok(major)), None) => (major, 0),
+        _ => fail(&format!("invalid version `{}`", version)),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("com-typelib: {}", message);
+    exit(1)
+}
@@ -14,6 +14,18 @@ pub struct Class {
     pub methods: HashMap<syn::Path, Vec<InterfaceMethod>>,
     pub fields: Vec<syn::Field>,
     pub impl_debug: bool,
+    /// Whether the class can be aggregated by another COM object
+    pub aggregatable: bool,
+    /// The fields marked `#[aggregate]` holding inner objects aggregated by this class
+    pub aggregates: Vec<Ident>,
+    /// Whether the class supports weak references through `IWeakReferenceSource`
+    pub weak_ref: bool,
+    /// Whether the class uses a non-atomic reference count (`#[refcount(local)]`)
+    pub local_refcount: bool,
+    /// Whether the class implements `IDispatch` on top of its other interfaces
+    pub dispatch: bool,
+    /// What the methods of the class do when they panic (`#[on_panic(..)]`)
+    pub on_panic: OnPanic,
 }
 
 #[derive(Debug)]
@@ -44,7 +56,7 @@ impl Class {
 
     /// Creates static items containing the vtables for each top-level interface.
     fn gen_vtable_static_items(&self) -> TokenStream {
-        self.interfaces
+        let mut items: TokenStream = self.interfaces
             .iter()
             .enumerate()
             .map(move |(index,  interface)| {
@@ -55,7 +67,32 @@ impl Class {
                     #[allow(non_upper_case_globals)]
                     static #vtable_item_ident: <#interface_name as ::com::Interface>::VTable = #interface_tokens;
                 }
-            }).collect()
+            }).collect();
+
+        if self.aggregatable {
+            // The non-delegating `IUnknown` directly follows the interface chains
+            let offset = self.interfaces.len();
+            let iunknown = super::iunknown_impl::IUnknownAbi::new(
+                self.name.clone(),
+                offset,
+                super::iunknown_impl::Delegation::NonDelegating,
+                self.on_panic.clone(),
+            );
+            let vtable_item_ident = self.non_delegating_vtable_static_item_ident();
+            let vtable_tokens = iunknown.to_vtable_tokens();
+            items.extend(quote! {
+                #[allow(non_upper_case_globals)]
+                static #vtable_item_ident: <::com::interfaces::IUnknown as ::com::Interface>::VTable = #vtable_tokens;
+            });
+        }
+
+        items
+    }
+
+    /// Returns the `Ident` for the static item that contains the vtable for the
+    /// non-delegating `IUnknown` of aggregatable classes.
+    pub fn non_delegating_vtable_static_item_ident(&self) -> Ident {
+        quote::format_ident!("{}__NON_DELEGATING_VTABLE", self.name)
     }
 
     /// Generates `From` impls for the interfaces implemented by this class.
@@ -83,7 +120,7 @@ impl Class {
         for (index, interface) in self.interfaces.iter().enumerate() {
             let class_name = &self.name;
             let chain_ident = interface.chain_ident(index);
-            let ref_count_ident = crate::utils::ref_count_ident();
+            let add_ref = self.delegating_add_ref_tokens(&quote!(class));
 
             for interface_path in interface
                 .iter_chain()
@@ -94,7 +131,7 @@ impl Class {
                     impl<'a> ::core::convert::From<&'a #class_name> for #interface_path {
                         fn from(class: &'a #class_name) -> Self {
                             unsafe {
-                                ::com::refcounting::addref(&class.#ref_count_ident);
+                                #add_ref;
                                 ::core::mem::transmute(&class.#chain_ident)
                             }
                         }
@@ -106,6 +143,53 @@ impl Class {
         output
     }
 
+    /// An expression incrementing the reference count of `this` on behalf of an
+    /// interface pointer.
+    ///
+    /// For aggregated objects, interface pointers are counted by the controlling
+    /// (outer) object.
+    pub fn delegating_add_ref_tokens(&self, this: &TokenStream) -> TokenStream {
+        let ref_count_ident = crate::utils::ref_count_ident();
+        if !self.aggregatable {
+            return quote! {
+                ::com::refcounting::addref(&#this.#ref_count_ident)
+            };
+        }
+
+        let outer_unknown_ident = crate::utils::outer_unknown_ident();
+        quote! {
+            match &#this.#outer_unknown_ident {
+                ::core::option::Option::Some(outer) => outer.AddRef(),
+                ::core::option::Option::None => ::com::refcounting::addref(&#this.#ref_count_ident),
+            }
+        }
+    }
+
+    /// The type of the reference count field
+    ///
+    /// Classes supporting weak references keep their reference count in a separate
+    /// control block which outlives the object.
+    pub fn ref_count_type_tokens(&self) -> TokenStream {
+        if self.weak_ref {
+            quote!(::com::production::WeakRefCount)
+        } else if self.local_refcount {
+            quote!(::com::refcounting::LocalRefCount)
+        } else {
+            quote!(::core::sync::atomic::AtomicU32)
+        }
+    }
+
+    /// An expression initializing the reference count field to one
+    pub fn ref_count_init_tokens(&self) -> TokenStream {
+        if self.weak_ref {
+            quote!(::com::production::WeakRefCount::new())
+        } else if self.local_refcount {
+            quote!(::com::refcounting::LocalRefCount::new(1))
+        } else {
+            quote!(::core::sync::atomic::AtomicU32::new(1))
+        }
+    }
+
     /// Get the paths of all interfaces including parent interfaces
     fn interfaces_paths<'a>(&'a self) -> HashSet<&'a syn::Path> {
         fn get_interface<'a>(interface: &'a Interface, result: &mut HashSet<&'a syn::Path>) {
@@ -126,6 +210,10 @@ impl Class {
         input: syn::parse::ParseStream,
         docs: Vec<syn::Attribute>,
         has_class_factory: bool,
+        aggregatable: bool,
+        weak_ref: bool,
+        local_refcount: bool,
+        dispatch: bool,
     ) -> syn::Result<Self> {
         let mut interfaces: Vec<Interface> = Vec::new();
         let visibility = input.parse::<syn::Visibility>()?;
@@ -145,7 +233,7 @@ impl Class {
             }
             interfaces.push(interface);
 
-            let mut current = interfaces.last_mut().unwrap();
+            let current = interfaces.last_mut().unwrap();
             fn parse_parens(buffer: &ParseBuffer, current: &mut Interface) -> syn::Result<()> {
                 while buffer.peek(syn::token::Paren) {
                     let contents;
@@ -161,12 +249,37 @@ impl Class {
                 Ok(())
             }
 
-            parse_parens(input, &mut current)?;
+            parse_parens(input, current)?;
 
             if !input.peek(syn::token::Brace) {
                 let _ = input.parse::<syn::Token!(,)>()?;
             }
         }
+        if weak_ref {
+            // `IWeakReferenceSource` is implemented by the generated code
+            let path = weak_reference_source_path();
+            if let Some(i) = interfaces
+                .iter()
+                .find(|i| i.iter_chain().any(is_weak_reference_source))
+            {
+                return Err(syn::Error::new(
+                    i.path.span(),
+                    "IWeakReferenceSource is implemented automatically for #[weak_ref] classes",
+                ));
+            }
+            interfaces.push(Interface { path, parent: None });
+        }
+        if dispatch
+            && !interfaces
+                .iter()
+                .any(|i| i.iter_chain().any(super::dispatch::is_dispatch))
+        {
+            // Dual interfaces derive from `IDispatch`, otherwise it is implemented
+            // as a separate interface
+            let path = super::dispatch::dispatch_path();
+            interfaces.push(Interface { path, parent: None });
+        }
+
         let fields;
         syn::braced!(fields in input);
         let fields =
@@ -174,7 +287,18 @@ impl Class {
                 &fields,
                 syn::Field::parse_named,
             )?;
-        let fields = fields.into_iter().collect();
+        let mut aggregates = Vec::new();
+        let fields = fields
+            .into_iter()
+            .map(|mut f| {
+                let len = f.attrs.len();
+                f.attrs.retain(|a| !a.path.is_ident("aggregate"));
+                if f.attrs.len() != len {
+                    aggregates.push(f.ident.clone().unwrap());
+                }
+                f
+            })
+            .collect();
 
         Ok(Class {
             name,
@@ -185,6 +309,12 @@ impl Class {
             methods: HashMap::new(),
             fields,
             impl_debug: false,
+            aggregatable,
+            aggregates,
+            weak_ref,
+            local_refcount,
+            dispatch,
+            on_panic: OnPanic::default(),
         })
     }
 
@@ -194,7 +324,8 @@ impl Class {
     /// ```rust,ignore
     /// pub struct ClassName {
     ///     // ..interface vpointers..
-    ///     // ..ref count..
+    ///     // ..non-delegating IUnknown vpointer and outer IUnknown (aggregatable classes only)..
+    ///     // ..ref count (see `Class::ref_count_type_tokens`)..
     ///     // ..user defined fields..
     /// }
     /// ```
@@ -211,6 +342,17 @@ impl Class {
             }
         });
         let ref_count_ident = crate::utils::ref_count_ident();
+        let ref_count_type = self.ref_count_type_tokens();
+        let aggregation_fields = if self.aggregatable {
+            let non_delegating_unknown_ident = crate::utils::non_delegating_unknown_ident();
+            let outer_unknown_ident = crate::utils::outer_unknown_ident();
+            quote! {
+                #non_delegating_unknown_ident: &'static <::com::interfaces::IUnknown as ::com::Interface>::VTable,
+                #outer_unknown_ident: ::core::option::Option<::core::mem::ManuallyDrop<::com::interfaces::IUnknown>>,
+            }
+        } else {
+            TokenStream::new()
+        };
 
         let user_fields = &self.fields;
         let docs = &self.docs;
@@ -223,8 +365,8 @@ impl Class {
         });
 
         let iunknown = super::iunknown_impl::IUnknown::new();
-        let add_ref = iunknown.to_add_ref_tokens();
-        let query_interface = iunknown.to_query_interface_tokens(interfaces);
+        let add_ref = iunknown.to_add_ref_tokens(self);
+        let query_interface = iunknown.to_query_interface_tokens(self);
         let constructor = super::class_constructor::generate(self);
         let debug = self.debug();
         let safe_query_interface = self.safe_query_interface();
@@ -243,7 +385,8 @@ impl Class {
             #[allow(non_snake_case)]
             #vis struct #name {
                 #(#interface_fields)*
-                #ref_count_ident: ::core::sync::atomic::AtomicU32,
+                #aggregation_fields
+                #ref_count_ident: #ref_count_type,
                 #(#user_fields),*
             }
             impl #name {
@@ -266,10 +409,12 @@ impl Class {
             quote! { () }
         };
         let ref_count_ident = crate::utils::ref_count_ident();
+        let ref_count_type = self.ref_count_type_tokens();
 
         quote! {
             unsafe impl com::production::Class for #name {
                 type Factory = #factory;
+                type RefCount = #ref_count_type;
 
                 unsafe fn dec_ref_count(&self) -> u32 {
                     ::com::refcounting::release(&self.#ref_count_ident)
@@ -307,10 +452,14 @@ impl Class {
     }
 
     fn safe_query_interface(&self) -> TokenStream {
+        let ref_count_ident = crate::utils::ref_count_ident();
         quote! {
             pub fn query_interface<T: ::com::Interface>(self: &::core::pin::Pin<::com::alloc::boxed::Box<Self>>) -> Option<T> {
                 let mut result = None;
                 let hr = unsafe { self.QueryInterface(&T::IID, &mut result as *mut _ as _) };
+                unsafe {
+                    ::com::refcounting::trace_query_interface(&**self as *const Self, &self.#ref_count_ident, ::core::option::Option::None, &T::IID, hr);
+                }
 
                 if ::com::sys::FAILED(hr) {
                     return None;
@@ -331,11 +480,26 @@ impl syn::parse::Parse for Class {
             let attributes = input.call(syn::Attribute::parse_outer)?;
             let mut docs = Vec::with_capacity(attributes.len());
             let mut has_class_factory = true;
+            let mut aggregatable = false;
+            let mut weak_ref = false;
+            let mut local_refcount = false;
+            let mut dispatch = false;
+            let mut on_panic = OnPanic::default();
             for attr in attributes {
                 if attr.path.is_ident("doc") {
                     docs.push(attr)
                 } else if attr.path.is_ident("no_class_factory") {
                     has_class_factory = false;
+                } else if attr.path.is_ident("aggregatable") {
+                    aggregatable = true;
+                } else if attr.path.is_ident("weak_ref") {
+                    weak_ref = true;
+                } else if attr.path.is_ident("dispatch") {
+                    dispatch = true;
+                } else if attr.path.is_ident("refcount") {
+                    local_refcount = parse_refcount(&attr)?;
+                } else if attr.path.is_ident("on_panic") {
+                    on_panic = parse_on_panic(&attr)?;
                 } else if attr.path.is_ident("derive") {
                     parse_derive_debug(&attr)?;
                     impl_debug = true;
@@ -343,9 +507,31 @@ impl syn::parse::Parse for Class {
                     return Err(syn::Error::new(attr.path.span(), "Unrecognized attribute"));
                 }
             }
+            if weak_ref && aggregatable {
+                return Err(syn::Error::new(
+                    input.span(),
+                    "#[weak_ref] classes cannot be #[aggregatable]",
+                ));
+            }
+            if weak_ref && local_refcount {
+                return Err(syn::Error::new(
+                    input.span(),
+                    "#[weak_ref] classes cannot use #[refcount(local)]",
+                ));
+            }
 
             if !input.peek(syn::Token!(impl)) {
-                class = Some(Self::parse_class(input, docs, has_class_factory)?);
+                let mut c = Self::parse_class(
+                    input,
+                    docs,
+                    has_class_factory,
+                    aggregatable,
+                    weak_ref,
+                    local_refcount,
+                    dispatch,
+                )?;
+                c.on_panic = on_panic;
+                class = Some(c);
             } else {
                 let item = input.parse::<syn::ItemImpl>()?;
                 // TODO: ensure that class idents line up
@@ -378,8 +564,29 @@ impl syn::parse::Parse for Class {
                 }
             }
         }
+        let mut dispatch = None;
         let mut class = match class {
-            Some(c) => {
+            Some(mut c) => {
+                if c.weak_ref {
+                    methods.insert(weak_reference_source_path(), vec![get_weak_reference()]);
+                }
+                if c.dispatch {
+                    let path = c
+                        .interfaces
+                        .iter()
+                        .flat_map(|i| i.iter_chain())
+                        .find(|p| super::dispatch::is_dispatch(p))
+                        .unwrap()
+                        .clone();
+                    if methods.contains_key(&path) {
+                        return Err(syn::Error::new(
+                            path.span(),
+                            "IDispatch is implemented automatically for #[dispatch] classes",
+                        ));
+                    }
+                    methods.insert(path.clone(), super::dispatch::placeholder_methods());
+                    dispatch = Some(path);
+                }
                 let mut interface_paths = c.interfaces_paths();
                 for i in methods.keys() {
                     if !interface_paths.remove(i) {
@@ -392,6 +599,20 @@ impl syn::parse::Parse for Class {
                 if let Some(i) = interface_paths.into_iter().next() {
                     return Err(syn::Error::new(i.span(), "impl for interface is missing"));
                 }
+                let supported = error_info_interfaces(&c, &methods);
+                if !supported.is_empty()
+                    && !c
+                        .interfaces
+                        .iter()
+                        .any(|i| i.iter_chain().any(is_support_error_info))
+                {
+                    let path = support_error_info_path();
+                    c.interfaces.push(Interface {
+                        path: path.clone(),
+                        parent: None,
+                    });
+                    methods.insert(path, vec![interface_supports_error_info(&supported)]);
+                }
                 c
             }
             None => {
@@ -401,10 +622,115 @@ impl syn::parse::Parse for Class {
         class.impl_debug = impl_debug;
         class.methods = methods;
         find_method_name_collisions(&mut class);
+        if let Some(path) = dispatch {
+            super::dispatch::generate(&mut class, &path);
+        }
         Ok(class)
     }
 }
 
+fn weak_reference_source_path() -> syn::Path {
+    syn::parse_quote!(::com::interfaces::IWeakReferenceSource)
+}
+
+pub(super) fn is_weak_reference_source(path: &syn::Path) -> bool {
+    path.segments.last().unwrap().ident == "IWeakReferenceSource"
+}
+
+/// The implementation of `IWeakReferenceSource::GetWeakReference` for `#[weak_ref]` classes
+fn get_weak_reference() -> InterfaceMethod {
+    let ref_count_ident = crate::utils::ref_count_ident();
+    let item: syn::ImplItemMethod = syn::parse_quote! {
+        fn GetWeakReference(&self) -> ::core::result::Result<::com::interfaces::IWeakReference, ::com::Error> {
+            ::core::result::Result::Ok(self.#ref_count_ident.weak_reference())
+        }
+    };
+    InterfaceMethod {
+        original_ident: item.sig.ident.clone(),
+        item,
+    }
+}
+
+fn support_error_info_path() -> syn::Path {
+    syn::parse_quote!(::com::interfaces::ISupportErrorInfo)
+}
+
+pub(super) fn is_support_error_info(path: &syn::Path) -> bool {
+    path.segments.last().unwrap().ident == "ISupportErrorInfo"
+}
+
+/// The interfaces of `class` with methods returning a `Result<T, com::Error>`, in
+/// declaration order
+///
+/// The shims of these methods set the error information of the thread when they
+/// fail, so the class implements `ISupportErrorInfo` for them.
+fn error_info_interfaces(
+    class: &Class,
+    methods: &HashMap<syn::Path, Vec<InterfaceMethod>>,
+) -> Vec<syn::Path> {
+    let mut interfaces = Vec::new();
+    for path in class.interfaces.iter().flat_map(|i| i.iter_chain()) {
+        if is_weak_reference_source(path) {
+            continue;
+        }
+        let returns_error = methods
+            .get(path)
+            .into_iter()
+            .flatten()
+            .any(|m| returns_com_error(&m.item.sig.output));
+        if returns_error && !interfaces.contains(path) {
+            interfaces.push(path.clone());
+        }
+    }
+    interfaces
+}
+
+/// Whether a method implementation returns a `Result<T, Error>`
+fn returns_com_error(ret: &syn::ReturnType) -> bool {
+    let ty = match ret {
+        syn::ReturnType::Type(_, ty) => ty,
+        syn::ReturnType::Default => return false,
+    };
+    let segment = match &**ty {
+        syn::Type::Path(p) => p.path.segments.last(),
+        _ => None,
+    };
+    let args = match segment {
+        Some(s) if s.ident == "Result" => &s.arguments,
+        _ => return false,
+    };
+    match args {
+        syn::PathArguments::AngleBracketed(args) => matches!(
+            args.args.iter().nth(1),
+            Some(syn::GenericArgument::Type(syn::Type::Path(p)))
+                if p.path.segments.last().map(|s| s.ident == "Error") == Some(true)
+        ),
+        _ => false,
+    }
+}
+
+/// The implementation of `ISupportErrorInfo::InterfaceSupportsErrorInfo` for
+/// classes with methods returning a `com::Error`
+fn interface_supports_error_info(interfaces: &[syn::Path]) -> InterfaceMethod {
+    let item: syn::ImplItemMethod = syn::parse_quote! {
+        unsafe fn InterfaceSupportsErrorInfo(&self, riid: *const ::com::sys::IID) -> ::com::sys::HRESULT {
+            if riid.is_null() {
+                return ::com::sys::E_POINTER;
+            }
+            let riid = &*riid;
+            if #(riid == &<#interfaces as ::com::Interface>::IID)||* {
+                ::com::sys::S_OK
+            } else {
+                ::com::sys::S_FALSE
+            }
+        }
+    };
+    InterfaceMethod {
+        original_ident: item.sig.ident.clone(),
+        item,
+    }
+}
+
 /// Resolve name collisions among methods defined on different interfaces, by
 /// renaming some methods with a disambiguating suffix.
 ///
@@ -505,7 +831,7 @@ fn find_method_name_collisions(class: &mut Class) {
             // just in case.
             if method_name_count.contains_key(&new_ident) {
                 loop {
-                    assert!(collision_counter < std::u32::MAX);
+                    assert!(collision_counter < u32::MAX);
                     new_ident = Ident::new(
                         &format!("{}__{:04}", new_ident_string, collision_counter),
                         old_ident.span(),
@@ -564,6 +890,97 @@ fn parse_derive_debug(attr: &syn::Attribute) -> syn::Result<()> {
     }
 }
 
+/// Parses `#[refcount(atomic)]` and `#[refcount(local)]`, returning whether the
+/// reference count is local.
+fn parse_refcount(attr: &syn::Attribute) -> syn::Result<bool> {
+    if let Ok(syn::Meta::List(l)) = attr.parse_meta() {
+        if let (1, Some(syn::NestedMeta::Meta(syn::Meta::Path(p)))) =
+            (l.nested.len(), l.nested.iter().next())
+        {
+            if p.is_ident("local") {
+                return Ok(true);
+            } else if p.is_ident("atomic") {
+                return Ok(false);
+            }
+        }
+    }
+    Err(syn::Error::new(
+        attr.tokens.span(),
+        "Expected #[refcount(atomic)] or #[refcount(local)]",
+    ))
+}
+
+/// Parses `#[on_panic(abort)]` and `#[on_panic(<HRESULT>)]`
+fn parse_on_panic(attr: &syn::Attribute) -> syn::Result<OnPanic> {
+    match attr.parse_args::<syn::Expr>() {
+        Ok(syn::Expr::Path(p)) if p.path.is_ident("abort") => Ok(OnPanic::Abort),
+        Ok(hr) => Ok(OnPanic::Return(Box::new(hr))),
+        Err(_) => Err(syn::Error::new(
+            attr.tokens.span(),
+            "Expected #[on_panic(abort)] or #[on_panic(<HRESULT>)]",
+        )),
+    }
+}
+
+/// What the `extern "system"` functions of a class do when a method panics
+///
+/// Unwinding into a caller that is not Rust code is undefined behavior, so the
+/// panic is always caught.
+#[derive(Clone, Debug)]
+pub enum OnPanic {
+    /// Return the `HRESULT` from methods returning one, and abort the process
+    /// from other methods
+    Return(Box<syn::Expr>),
+    /// Abort the process
+    Abort,
+}
+
+impl Default for OnPanic {
+    fn default() -> Self {
+        OnPanic::Return(Box::new(syn::parse_quote!(::com::sys::E_UNEXPECTED)))
+    }
+}
+
+impl OnPanic {
+    /// Wraps the `body` of the function implementing the method `name` of `class`
+    ///
+    /// `returns_hresult` is whether the method returns an `HRESULT` or an
+    /// `HResult`, which can report the panic.
+    pub fn guard(
+        &self,
+        class: &Ident,
+        name: &str,
+        returns_hresult: bool,
+        body: TokenStream,
+    ) -> TokenStream {
+        match self {
+            OnPanic::Return(hr) if returns_hresult => quote! {
+                ::com::unwind::catch(#hr, || { #body })
+            },
+            _ => {
+                let name = format!("{}::{}", class, name);
+                quote! {
+                    ::com::unwind::abort_on_panic(#name, || { #body })
+                }
+            }
+        }
+    }
+}
+
+/// Whether a method implementation returns an `HRESULT` or an `HResult`
+fn returns_hresult(ret: &syn::ReturnType) -> bool {
+    match ret {
+        syn::ReturnType::Type(_, ty) => match &**ty {
+            syn::Type::Path(p) => matches!(
+                p.path.segments.last(),
+                Some(s) if s.ident == "HRESULT" || s.ident == "HResult"
+            ),
+            _ => false,
+        },
+        syn::ReturnType::Default => false,
+    }
+}
+
 mod keywords {
     syn::custom_keyword!(class);
     syn::custom_keyword!(factory);
@@ -604,7 +1021,7 @@ impl Interface {
                 let pat = &p.pat;
                 let typ = &p.ty;
                 quote! {
-                    let #pat = <#typ as ::com::AbiTransferable>::from_abi(#pat);
+                    let #pat = <#typ as ::com::AbiTransferable>::from_in_param(#pat);
                 }
             });
             let params = params.map(|p| {
@@ -614,14 +1031,53 @@ impl Interface {
                     #pat: <#typ as ::com::AbiTransferable>::Abi
                 }
             });
-            let ret = &m.item.sig.output;
-            let method = quote! {
-                #[allow(non_snake_case)]
-                unsafe extern "system" fn #name(this: ::core::ptr::NonNull<::core::ptr::NonNull<#vtable_ident>>, #(#params),*) #ret {
-                    let this = this.as_ptr().sub(#offset);
-                    let this = ::core::mem::ManuallyDrop::new(::com::production::ClassAllocation::from_raw(this as *mut _ as *mut #class_name));
-                    #(#translation)*
-                    #class_name::#name(&this, #(#args),*)
+            let method = match retval_type(&m.item.sig.output) {
+                // Methods returning a `Result` implement methods with an `[out, retval]`
+                // parameter. The shim writes the `Ok` value to that parameter.
+                Some(retval) => {
+                    let body = class.on_panic.guard(class_name, &original_name.to_string(), true, quote! {
+                        if __retval.is_null() {
+                            return ::com::sys::E_POINTER;
+                        }
+                        let this = this.as_ptr().sub(#offset);
+                        let this = ::core::mem::ManuallyDrop::new(::com::production::ClassAllocation::from_raw(this as *mut _ as *mut #class_name));
+                        #(#translation)*
+                        match #class_name::#name(&this, #(#args),*) {
+                            ::core::result::Result::Ok(value) => {
+                                ::core::ptr::write(__retval, value);
+                                ::com::sys::S_OK
+                            }
+                            ::core::result::Result::Err(e) => ::com::error_info::report(e),
+                        }
+                    });
+                    // Fails to compile unless the interface declares the method with a
+                    // `#[retval]` parameter, rather than by a mismatch of the shim and
+                    // the vtable
+                    let interface = &self.path;
+                    let marker = crate::utils::retval_marker_ident(original_name);
+                    let marker = quote::quote_spanned!(m.item.sig.output.span()=> #interface::#marker);
+                    quote! {
+                        const _: () = #marker;
+                        #[allow(non_snake_case)]
+                        unsafe extern "system" fn #name(this: ::core::ptr::NonNull<::core::ptr::NonNull<#vtable_ident>>, #(#params,)* __retval: *mut #retval) -> ::com::sys::HRESULT {
+                            #body
+                        }
+                    }
+                },
+                None => {
+                    let ret = &m.item.sig.output;
+                    let body = class.on_panic.guard(class_name, &original_name.to_string(), returns_hresult(ret), quote! {
+                        let this = this.as_ptr().sub(#offset);
+                        let this = ::core::mem::ManuallyDrop::new(::com::production::ClassAllocation::from_raw(this as *mut _ as *mut #class_name));
+                        #(#translation)*
+                        #class_name::#name(&this, #(#args),*)
+                    });
+                    quote! {
+                        #[allow(non_snake_case)]
+                        unsafe extern "system" fn #name(this: ::core::ptr::NonNull<::core::ptr::NonNull<#vtable_ident>>, #(#params),*) #ret {
+                            #body
+                        }
+                    }
                 }
             };
             let field_name = Ident::new(&crate::utils::snake_to_camel(&original_name.to_string()), proc_macro2::Span::call_site());
@@ -676,24 +1132,18 @@ impl Interface {
     ///
     /// `offset` is the index of the interface chain, not an offset in bytes.
     fn iunknown_tokens(class: &Class, offset: usize) -> TokenStream {
-        let iunknown = super::iunknown_impl::IUnknownAbi::new(class.name.clone(), offset);
-        let add_ref = iunknown.to_add_ref_tokens();
-        let release = iunknown.to_release_tokens();
-        let query_interface = iunknown.to_query_interface_tokens();
-        quote! {
-            {
-                // See https://github.com/rust-lang/rust/issues/86935
-                type IUknownVTable = <::com::interfaces::IUnknown as ::com::Interface>::VTable;
-                #add_ref
-                #release
-                #query_interface
-                IUknownVTable {
-                    AddRef,
-                    Release,
-                    QueryInterface,
-                }
-            }
-        }
+        let delegation = if class.aggregatable {
+            super::iunknown_impl::Delegation::Delegating
+        } else {
+            super::iunknown_impl::Delegation::None
+        };
+        let iunknown = super::iunknown_impl::IUnknownAbi::new(
+            class.name.clone(),
+            offset,
+            delegation,
+            class.on_panic.clone(),
+        );
+        iunknown.to_vtable_tokens()
     }
 
     pub fn iter_chain(&self) -> IterChain<'_> {
@@ -703,6 +1153,31 @@ impl Interface {
     }
 }
 
+/// If a method implementation returns `Result<T, E>`, returns `T`.
+///
+/// Such methods implement interface methods that have a `#[retval]` parameter. The
+/// shims generated for them check that the interface declares the parameter.
+pub(super) fn retval_type(ret: &syn::ReturnType) -> Option<&syn::Type> {
+    let ty = match ret {
+        syn::ReturnType::Type(_, ty) => ty,
+        syn::ReturnType::Default => return None,
+    };
+    let segment = match &**ty {
+        syn::Type::Path(p) => p.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Result" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 pub struct IterChain<'a> {
     next_interface: Option<&'a Interface>,
 }
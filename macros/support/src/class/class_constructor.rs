@@ -16,6 +16,7 @@ pub fn generate(class: &Class) -> TokenStream {
     });
 
     let ref_count_ident = crate::utils::ref_count_ident();
+    let ref_count_init = class.ref_count_init_tokens();
 
     // Generate the vptr field idents needed in the instantiation syntax of the COM struct.
     let interface_fields = class
@@ -30,6 +31,44 @@ pub fn generate(class: &Class) -> TokenStream {
             }
         });
 
+    if !class.aggregatable {
+        // Weak references resolve to the first interface chain of the object
+        let set_weak_ref_object = if class.weak_ref {
+            let first_chain_ident = class.interfaces[0].chain_ident(0);
+            quote! {
+                unsafe {
+                    instance.#ref_count_ident.set_object(&instance.#first_chain_ident as *const _ as *const ::core::ffi::c_void);
+                }
+            }
+        } else {
+            TokenStream::new()
+        };
+
+        return quote! {
+            /// Allocate the class casting it to the supplied interface
+            ///
+            /// This allocates the class on the heap and pins it. This is because COM classes
+            /// must have a stable location in memory. Once a COM class is instantiated somewhere
+            /// it must stay there.
+            #vis fn allocate(#(#parameters),*) -> ::com::production::ClassAllocation<Self> {
+                let instance = #name {
+                    #(#interface_fields)*
+                    #ref_count_ident: #ref_count_init,
+                    #(#user_fields),*
+                };
+                let instance = ::com::alloc::boxed::Box::pin(instance);
+                #set_weak_ref_object
+                ::com::production::ClassAllocation::new(instance)
+            }
+        };
+    }
+
+    let interface_fields = interface_fields.collect::<Vec<_>>();
+    let user_fields = user_fields.collect::<Vec<_>>();
+    let non_delegating_unknown_ident = crate::utils::non_delegating_unknown_ident();
+    let non_delegating_vtable_static_item = class.non_delegating_vtable_static_item_ident();
+    let outer_unknown_ident = crate::utils::outer_unknown_ident();
+
     quote! {
         /// Allocate the class casting it to the supplied interface
         ///
@@ -39,11 +78,39 @@ pub fn generate(class: &Class) -> TokenStream {
         #vis fn allocate(#(#parameters),*) -> ::com::production::ClassAllocation<Self> {
             let instance = #name {
                 #(#interface_fields)*
-                #ref_count_ident: ::core::sync::atomic::AtomicU32::new(1),
+                #non_delegating_unknown_ident: &#non_delegating_vtable_static_item,
+                #outer_unknown_ident: ::core::option::Option::None,
+                #ref_count_ident: #ref_count_init,
                 #(#user_fields),*
             };
             let instance = ::com::alloc::boxed::Box::pin(instance);
             ::com::production::ClassAllocation::new(instance)
         }
+
+        /// Allocate the class as the inner object of an aggregate
+        ///
+        /// All interfaces of the new object delegate their `IUnknown` methods to `outer`.
+        /// The returned non-delegating `IUnknown` controls the lifetime of the new object.
+        ///
+        /// # Safety
+        ///
+        /// `outer` must be the controlling `IUnknown` of the aggregate. The new object does
+        /// not hold a reference to `outer`, so `outer` must outlive it. The returned
+        /// `IUnknown` must not be handed out to anyone but the controlling object.
+        #vis unsafe fn allocate_aggregated(outer: &::com::interfaces::IUnknown, #(#parameters),*) -> ::com::interfaces::IUnknown {
+            let instance = #name {
+                #(#interface_fields)*
+                #non_delegating_unknown_ident: &#non_delegating_vtable_static_item,
+                #outer_unknown_ident: ::core::option::Option::Some(::core::mem::ManuallyDrop::new(::core::mem::transmute_copy(outer))),
+                #ref_count_ident: #ref_count_init,
+                #(#user_fields),*
+            };
+            let instance = ::com::alloc::boxed::Box::pin(instance);
+            let instance = ::com::production::ClassAllocation::new(instance);
+            let unknown = &instance.#non_delegating_unknown_ident as *const _ as *mut _;
+            // The reference held by `instance` is transferred to the returned `IUnknown`
+            ::core::mem::forget(instance);
+            <::com::interfaces::IUnknown as ::com::AbiTransferable>::from_abi(::core::ptr::NonNull::new_unchecked(unknown))
+        }
     }
 }
@@ -9,10 +9,34 @@ pub fn generate(class: &Class) -> TokenStream {
 
     let class_factory_ident = crate::utils::class_factory_ident(&class.name);
     let class_name = &class.name;
-    let user_fields = class.fields.iter().map(|f| {
-        let ty = &f.ty;
-        quote! { <#ty as ::core::default::Default>::default() }
-    });
+    let user_fields = class
+        .fields
+        .iter()
+        .map(|f| {
+            let ty = &f.ty;
+            quote! { <#ty as ::core::default::Default>::default() }
+        })
+        .collect::<Vec<_>>();
+    let aggregation = if class.aggregatable {
+        quote! {
+            // When aggregating, the controlling object must ask for the
+            // non-delegating `IUnknown`.
+            if *riid != ::com::interfaces::iunknown::IID_IUNKNOWN {
+                *ppv = ::core::ptr::null_mut();
+                return ::com::sys::CLASS_E_NOAGGREGATION;
+            }
+            let outer = ::core::mem::ManuallyDrop::new(
+                <::com::interfaces::IUnknown as ::com::AbiTransferable>::from_abi(::core::ptr::NonNull::new_unchecked(aggr))
+            );
+            let unknown = #class_name::allocate_aggregated(&outer, #(#user_fields),*);
+            *ppv = ::com::AbiTransferable::into_abi(unknown).as_ptr() as *mut ::core::ffi::c_void;
+            return ::com::sys::NOERROR;
+        }
+    } else {
+        quote! {
+            return ::com::sys::CLASS_E_NOAGGREGATION;
+        }
+    };
     quote! {
         ::com::class! {
             #[no_class_factory]
@@ -27,7 +51,7 @@ pub fn generate(class: &Class) -> TokenStream {
                 ) -> ::com::sys::HRESULT {
                     assert!(!riid.is_null(), "iid passed to CreateInstance was null");
                     if !aggr.is_null() {
-                        return ::com::sys::CLASS_E_NOAGGREGATION;
+                        #aggregation
                     }
 
                     let instance = #class_name::allocate(#(#user_fields),*);
@@ -39,5 +63,12 @@ pub fn generate(class: &Class) -> TokenStream {
                 }
             }
         }
+
+        impl ::com::production::ClassFactory for #class_factory_ident {
+            fn create() -> ::com::interfaces::IClassFactory {
+                let factory = #class_factory_ident::allocate();
+                ::com::interfaces::IClassFactory::from(&**factory)
+            }
+        }
     }
 }
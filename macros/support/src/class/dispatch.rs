@@ -0,0 +1,297 @@
+//! The `IDispatch` implementation generated for `#[dispatch]` classes
+//!
+//! The methods of the other interfaces implemented by the class are exposed as
+//! dispatch members, numbered from 1 in declaration order. Arguments are converted
+//! from `Variant` with `TryFrom<&Variant>`. Methods returning an `HRESULT`,
+//! `HResult` or `Result` report their failures as the `HRESULT` of `Invoke`, and
+//! the other return values and the `Ok` values of `Result`s are converted into the
+//! `Variant` result with `From`.
+//!
+//! Methods taking or returning pointers, references or functions cannot be called
+//! through `IDispatch`, and neither can methods with other types lacking these
+//! conversions, such as interfaces which are not in an `Option`, GUIDs or structs.
+//! They keep their `DISPID`, but invoking them fails with `DISP_E_MEMBERNOTFOUND`.
+//! The conversions are chosen by the types of the generated code, as described in
+//! `com::production::dispatch`.
+use super::class::{retval_type, Class, InterfaceMethod};
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+use std::collections::HashSet;
+
+pub fn dispatch_path() -> syn::Path {
+    syn::parse_quote!(::com::interfaces::IDispatch)
+}
+
+pub fn is_dispatch(path: &syn::Path) -> bool {
+    path.segments.last().unwrap().ident == "IDispatch"
+}
+
+/// Placeholders for the `IDispatch` methods, which take part in resolving method name
+/// collisions before their bodies are generated by [`generate`].
+pub fn placeholder_methods() -> Vec<InterfaceMethod> {
+    let items: Vec<syn::ImplItemMethod> = vec![
+        syn::parse_quote! {
+            fn GetTypeInfoCount(&self, count: *mut u32) -> ::com::sys::HRESULT {
+                ::com::sys::E_NOTIMPL
+            }
+        },
+        syn::parse_quote! {
+            fn GetTypeInfo(&self, index: u32, lcid: u32, type_info: *mut *mut ::core::ffi::c_void) -> ::com::sys::HRESULT {
+                ::com::sys::E_NOTIMPL
+            }
+        },
+        syn::parse_quote! {
+            fn GetIDsOfNames(
+                &self,
+                riid: *const ::com::sys::GUID,
+                names: *const *const u16,
+                count: u32,
+                lcid: u32,
+                dispids: *mut ::com::sys::DISPID,
+            ) -> ::com::sys::HRESULT {
+                ::com::sys::E_NOTIMPL
+            }
+        },
+        syn::parse_quote! {
+            fn Invoke(
+                &self,
+                dispid: ::com::sys::DISPID,
+                riid: *const ::com::sys::GUID,
+                lcid: u32,
+                flags: u16,
+                params: *mut ::com::sys::DISPPARAMS,
+                result: *mut ::com::sys::VARIANT,
+                excep_info: *mut ::com::sys::EXCEPINFO,
+                arg_err: *mut u32,
+            ) -> ::com::sys::HRESULT {
+                ::com::sys::E_NOTIMPL
+            }
+        },
+    ];
+    items
+        .into_iter()
+        .map(|mut item| {
+            // Not all parameters are used by the generated bodies
+            item.attrs.push(syn::parse_quote!(
+                #[allow(unused_variables, clippy::too_many_arguments)]
+            ));
+            InterfaceMethod {
+                original_ident: item.sig.ident.clone(),
+                item,
+            }
+        })
+        .collect()
+}
+
+/// The methods exposed through `IDispatch`, in `DISPID` order
+///
+/// Interfaces are visited in declaration order, from the base of each chain. When
+/// several methods have the same name (ignoring case), the first one is exposed.
+fn members<'a>(class: &'a Class, dispatch: &syn::Path) -> Vec<&'a InterfaceMethod> {
+    let mut names = HashSet::new();
+    let mut members = Vec::new();
+    for interface in &class.interfaces {
+        let chain = interface.iter_chain().collect::<Vec<_>>();
+        for path in chain.into_iter().rev() {
+            if path == dispatch
+                || super::class::is_weak_reference_source(path)
+                || super::class::is_support_error_info(path)
+            {
+                continue;
+            }
+            for method in class.methods.get(path).into_iter().flatten() {
+                if names.insert(method.original_ident.to_string().to_lowercase()) {
+                    members.push(method);
+                }
+            }
+        }
+    }
+    members
+}
+
+/// Fill in the bodies of the `IDispatch` methods of `class`
+pub fn generate(class: &mut Class, dispatch: &syn::Path) {
+    let members = members(class, dispatch);
+    let names = members
+        .iter()
+        .map(|m| m.original_ident.to_string())
+        .collect::<Vec<_>>();
+    let arms = members
+        .iter()
+        .enumerate()
+        .filter_map(|(index, m)| invoke_arm(index as i32 + 1, m))
+        .collect::<Vec<_>>();
+
+    let bodies = [
+        (
+            "GetTypeInfoCount",
+            quote! {
+                unsafe { ::com::production::dispatch::get_type_info_count(count) }
+            },
+        ),
+        (
+            "GetTypeInfo",
+            quote! {
+                unsafe { ::com::production::dispatch::get_type_info(type_info) }
+            },
+        ),
+        (
+            "GetIDsOfNames",
+            quote! {
+                unsafe {
+                    ::com::production::dispatch::get_ids_of_names(&[#(#names),*], names, count, dispids)
+                }
+            },
+        ),
+        (
+            "Invoke",
+            quote! {
+                #[allow(unused_imports)]
+                use ::com::production::dispatch::{
+                    FromVariant as _, FromVariantFallback as _, IntoVariant as _,
+                    IntoVariantFallback as _,
+                };
+                unsafe {
+                    match dispid {
+                        #(#arms)*
+                        _ => ::com::sys::DISP_E_MEMBERNOTFOUND,
+                    }
+                }
+            },
+        ),
+    ];
+
+    for method in class.methods.get_mut(dispatch).unwrap() {
+        let (_, body) = bodies
+            .iter()
+            .find(|(name, _)| method.original_ident == name)
+            .unwrap();
+        method.item.block = syn::parse_quote!({ #body });
+    }
+}
+
+/// Whether values of type `ty` can be converted from and to a `Variant`
+fn is_dispatchable(ty: &syn::Type) -> bool {
+    !matches!(
+        ty,
+        syn::Type::Ptr(_) | syn::Type::Reference(_) | syn::Type::BareFn(_)
+    )
+}
+
+/// The last identifier of the path of `ty`, if it is a path
+fn type_ident(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(p) => p.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    }
+}
+
+fn is_unit(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Tuple(t) if t.elems.is_empty())
+}
+
+/// The arm of the `match` in `Invoke` calling `method`, or `None` if it cannot be
+/// called through `IDispatch`
+fn invoke_arm(dispid: i32, method: &InterfaceMethod) -> Option<TokenStream> {
+    let name = &method.item.sig.ident;
+    let types = method
+        .item
+        .sig
+        .inputs
+        .iter()
+        .filter_map(|p| match p {
+            syn::FnArg::Receiver(_) => None,
+            syn::FnArg::Typed(p) => Some(&p.ty),
+        })
+        .collect::<Vec<_>>();
+    if !types.iter().all(|ty| is_dispatchable(ty)) {
+        return None;
+    }
+    let count = types.len();
+    let args = (0..count)
+        .map(|i| format_ident!("__arg{}", i))
+        .collect::<Vec<Ident>>();
+    let converts = (0..count)
+        .map(|i| format_ident!("__from_variant{}", i))
+        .collect::<Vec<Ident>>();
+    let indices = 0..count;
+
+    let call = quote!(Self::#name(self, #(#args),*));
+    let ret = match &method.item.sig.output {
+        syn::ReturnType::Default => None,
+        syn::ReturnType::Type(_, ty) if is_unit(ty) => None,
+        syn::ReturnType::Type(_, ty) => Some(&**ty),
+    };
+    // The type converted into the result, if any
+    let mut result_type = None;
+    let call = match ret {
+        None => quote! {
+            #call;
+            ::com::sys::S_OK
+        },
+        Some(ty) if !is_dispatchable(ty) => return None,
+        Some(ty) if type_ident(ty).as_deref() == Some("HRESULT") => call,
+        Some(ty) if type_ident(ty).as_deref() == Some("HResult") => quote! {
+            ::com::HResult::from(#call).0
+        },
+        Some(ty) => match retval_type(&method.item.sig.output) {
+            Some(ty) if !is_dispatchable(ty) => return None,
+            Some(ty) if is_unit(ty) => quote! {
+                match #call {
+                    ::core::result::Result::Ok(()) => ::com::sys::S_OK,
+                    ::core::result::Result::Err(e) => ::com::HResult::from(e).0,
+                }
+            },
+            Some(ty) => {
+                result_type = Some(ty);
+                quote! {
+                    match #call {
+                        ::core::result::Result::Ok(value) => {
+                            ::com::production::dispatch::set_result(result, __into_variant(value));
+                            ::com::sys::S_OK
+                        }
+                        ::core::result::Result::Err(e) => ::com::HResult::from(e).0,
+                    }
+                }
+            }
+            None => {
+                result_type = Some(ty);
+                quote! {
+                    ::com::production::dispatch::set_result(result, __into_variant(#call));
+                    ::com::sys::S_OK
+                }
+            }
+        },
+    };
+    let into_variant = result_type.map(|ty| {
+        quote! {
+            let __into_variant = match (&::com::production::dispatch::Convert::<#ty>(::core::marker::PhantomData)).result_fn() {
+                ::core::result::Result::Ok(convert) => convert,
+                ::core::result::Result::Err(hr) => return hr,
+            };
+        }
+    });
+
+    Some(quote! {
+        #dispid => {
+            #(
+                let #converts = match (&::com::production::dispatch::Convert::<#types>(::core::marker::PhantomData)).argument_fn() {
+                    ::core::result::Result::Ok(convert) => convert,
+                    ::core::result::Result::Err(hr) => return hr,
+                };
+            )*
+            #into_variant
+            let args = match ::com::production::dispatch::arguments(flags, params, #count) {
+                ::core::result::Result::Ok(args) => args,
+                ::core::result::Result::Err(hr) => return hr,
+            };
+            #(
+                let #args = match #converts(args, #indices, arg_err) {
+                    ::core::result::Result::Ok(arg) => arg,
+                    ::core::result::Result::Err(hr) => return hr,
+                };
+            )*
+            #call
+        }
+    })
+}
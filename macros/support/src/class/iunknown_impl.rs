@@ -2,26 +2,89 @@ use proc_macro2::TokenStream;
 use quote::quote;
 use syn::Ident;
 
-use super::class::Interface;
+use super::class::{Class, OnPanic};
+
+/// How an `IUnknown` implementation relates to COM aggregation
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Delegation {
+    /// The class is not aggregatable
+    None,
+    /// An interface chain of an aggregatable class, which forwards to the
+    /// controlling (outer) `IUnknown` when the object is aggregated
+    Delegating,
+    /// The non-delegating `IUnknown` of an aggregatable class
+    NonDelegating,
+}
 
 pub struct IUnknownAbi {
     class_name: Ident,
     offset: usize,
+    delegation: Delegation,
+    on_panic: OnPanic,
 }
 
 impl IUnknownAbi {
-    pub fn new(class_name: Ident, offset: usize) -> Self {
-        Self { class_name, offset }
+    pub fn new(
+        class_name: Ident,
+        offset: usize,
+        delegation: Delegation,
+        on_panic: OnPanic,
+    ) -> Self {
+        Self {
+            class_name,
+            offset,
+            delegation,
+            on_panic,
+        }
+    }
+
+    /// An initialized `IUnknown` VTable
+    pub fn to_vtable_tokens(&self) -> TokenStream {
+        let add_ref = self.to_add_ref_tokens();
+        let release = self.to_release_tokens();
+        let query_interface = self.to_query_interface_tokens();
+        quote! {
+            {
+                // See https://github.com/rust-lang/rust/issues/86935
+                type IUknownVTable = <::com::interfaces::IUnknown as ::com::Interface>::VTable;
+                #add_ref
+                #release
+                #query_interface
+                IUknownVTable {
+                    AddRef,
+                    Release,
+                    QueryInterface,
+                }
+            }
+        }
     }
 
     pub fn to_add_ref_tokens(&self) -> TokenStream {
         let this_ptr = this_ptr_type();
         let munge = self.borrowed_pointer_munging();
+        let body = match self.delegation {
+            Delegation::NonDelegating => {
+                let ref_count_ident = crate::utils::ref_count_ident();
+                quote! { ::com::refcounting::addref(&munged.#ref_count_ident) }
+            }
+            _ => quote! { munged.AddRef() },
+        };
+        let trace = self.trace_tokens(quote!(AddRef));
+        let body = self.on_panic.guard(
+            &self.class_name,
+            "AddRef",
+            false,
+            quote! {
+                #munge
+                let new_ref_count = #body;
+                #trace
+                new_ref_count
+            },
+        );
 
         quote! {
             unsafe extern "system" fn AddRef(this: #this_ptr) -> u32 {
-                #munge
-                munged.AddRef()
+                #body
             }
         }
     }
@@ -30,16 +93,41 @@ impl IUnknownAbi {
         let this_ptr = this_ptr_type();
         let munge = self.borrowed_pointer_munging();
         let ref_count_ident = crate::utils::ref_count_ident();
+        let delegate = if self.delegation == Delegation::Delegating {
+            let outer_unknown_ident = crate::utils::outer_unknown_ident();
+            let trace = self.trace_tokens(quote!(Release));
+            quote! {
+                if let ::core::option::Option::Some(outer) = &munged.#outer_unknown_ident {
+                    let new_ref_count = outer.Release();
+                    #trace
+                    return new_ref_count;
+                }
+            }
+        } else {
+            TokenStream::new()
+        };
+        let object = self.object_tokens();
+        let chain = self.chain_tokens();
 
-        quote! {
-            unsafe extern "system" fn Release(this: #this_ptr) -> u32 {
+        let body = self.on_panic.guard(
+            &self.class_name,
+            "Release",
+            false,
+            quote! {
                 #munge
-                let new_ref_count = ::com::refcounting::release(&munged.#ref_count_ident);
+                #delegate
+                let new_ref_count = ::com::refcounting::release_object(#object, &munged.#ref_count_ident, #chain);
                 if new_ref_count == 0 {
                     // The last reference has been dropped.
                     munged.drop_inner();
                 }
                 new_ref_count
+            },
+        );
+
+        quote! {
+            unsafe extern "system" fn Release(this: #this_ptr) -> u32 {
+                #body
             }
         }
     }
@@ -47,6 +135,24 @@ impl IUnknownAbi {
     pub fn to_query_interface_tokens(&self) -> TokenStream {
         let this_ptr = this_ptr_type();
         let munge = self.borrowed_pointer_munging();
+        let body = match self.delegation {
+            Delegation::NonDelegating => quote! { munged.NonDelegatingQueryInterface(riid, ppv) },
+            _ => quote! { munged.QueryInterface(riid, ppv) },
+        };
+        let object = self.object_tokens();
+        let chain = self.chain_tokens();
+        let ref_count_ident = crate::utils::ref_count_ident();
+        let body = self.on_panic.guard(
+            &self.class_name,
+            "QueryInterface",
+            true,
+            quote! {
+                #munge
+                let hr = #body;
+                ::com::refcounting::trace_query_interface(#object, &munged.#ref_count_ident, #chain, riid, hr);
+                hr
+            },
+        );
 
         quote! {
             unsafe extern "system" fn QueryInterface(
@@ -54,12 +160,37 @@ impl IUnknownAbi {
                 riid: *const ::com::sys::IID,
                 ppv: *mut *mut ::core::ffi::c_void
             ) -> ::com::sys::HRESULT {
-                #munge
-                munged.QueryInterface(riid, ppv)
+                #body
             }
         }
     }
 
+    /// A pointer to the object, which is still valid as a value once the object
+    /// is freed
+    fn object_tokens(&self) -> TokenStream {
+        let offset = self.offset;
+        let class_name = &self.class_name;
+        quote!(this.as_ptr().sub(#offset) as *const #class_name)
+    }
+
+    /// The index of the interface chain, if this is not the non-delegating `IUnknown`
+    fn chain_tokens(&self) -> TokenStream {
+        let offset = self.offset;
+        match self.delegation {
+            Delegation::NonDelegating => quote!(::core::option::Option::None),
+            _ => quote!(::core::option::Option::Some(#offset)),
+        }
+    }
+
+    /// Reports the call of `operation` which returned `new_ref_count`
+    fn trace_tokens(&self, operation: TokenStream) -> TokenStream {
+        let object = self.object_tokens();
+        let chain = self.chain_tokens();
+        quote! {
+            ::com::refcounting::trace(#object, #chain, ::com::refcounting::Operation::#operation, new_ref_count);
+        }
+    }
+
     fn owned_pointer_munging(&self) -> TokenStream {
         let offset = self.offset;
         let class_name = &self.class_name;
@@ -87,49 +218,106 @@ impl IUnknown {
         Self
     }
 
-    pub fn to_add_ref_tokens(&self) -> TokenStream {
-        let ref_count_ident = crate::utils::ref_count_ident();
+    pub fn to_add_ref_tokens(&self, class: &Class) -> TokenStream {
+        let add_ref = class.delegating_add_ref_tokens(&quote!(self));
         quote! {
             pub unsafe fn AddRef(self: &::core::pin::Pin<::com::alloc::boxed::Box<Self>>) -> u32 {
-                ::com::refcounting::addref(&self.#ref_count_ident)
+                #add_ref
             }
         }
     }
 
-    pub fn to_query_interface_tokens(&self, interfaces: &[Interface]) -> TokenStream {
-        // Generate match arms for implemented interfaces
-        let base_match_arms = Self::gen_base_match_arms(interfaces);
+    pub fn to_query_interface_tokens(&self, class: &Class) -> TokenStream {
+        let body = Self::gen_query_interface_body(class);
+        if !class.aggregatable {
+            return quote! {
+                // We don't want this inlined into every interface chain. QueryInterface
+                // can generate a lot of code.
+                #[inline(never)]
+                pub unsafe fn QueryInterface(
+                    self: &::core::pin::Pin<::com::alloc::boxed::Box<Self>>,
+                    riid: *const ::com::sys::IID,
+                    ppv: *mut *mut ::core::ffi::c_void
+                ) -> ::com::sys::HRESULT {
+                    #body
+                }
+            };
+        }
 
+        let outer_unknown_ident = crate::utils::outer_unknown_ident();
+        let non_delegating_unknown_ident = crate::utils::non_delegating_unknown_ident();
+        let ref_count_ident = crate::utils::ref_count_ident();
         quote! {
-            // We don't want this inlined into every interface chain. QueryInterface
-            // can generate a lot of code.
+            /// Queries the controlling `IUnknown` if the object is aggregated, and the
+            /// object itself otherwise.
             #[inline(never)]
             pub unsafe fn QueryInterface(
                 self: &::core::pin::Pin<::com::alloc::boxed::Box<Self>>,
                 riid: *const ::com::sys::IID,
                 ppv: *mut *mut ::core::ffi::c_void
             ) -> ::com::sys::HRESULT {
-                let riid = &*riid;
-
-                // Use 'pv' for definite assignment analysis, to guarantee
-                // that we always assign *ppv.
-                let pv: *const ::core::ffi::c_void =
-                    #base_match_arms {
-                        *ppv = ::core::ptr::null_mut::<::core::ffi::c_void>();
-                        return ::com::sys::E_NOINTERFACE;
-                    };
+                if let ::core::option::Option::Some(outer) = &self.#outer_unknown_ident {
+                    return outer.QueryInterface(riid, ppv);
+                }
+                #body
+            }
 
-                *ppv = pv as *mut ::core::ffi::c_void;
+            /// The `QueryInterface` of the non-delegating `IUnknown`, which is only
+            /// handed out to the controlling object of an aggregate.
+            #[inline(never)]
+            pub unsafe fn NonDelegatingQueryInterface(
+                self: &::core::pin::Pin<::com::alloc::boxed::Box<Self>>,
+                riid: *const ::com::sys::IID,
+                ppv: *mut *mut ::core::ffi::c_void
+            ) -> ::com::sys::HRESULT {
+                if &*riid == &::com::interfaces::iunknown::IID_IUNKNOWN {
+                    *ppv = &self.#non_delegating_unknown_ident as *const _ as *mut ::core::ffi::c_void;
+                    ::com::refcounting::addref(&self.#ref_count_ident);
+                    return ::com::sys::NOERROR;
+                }
+                #body
+            }
+        }
+    }
 
-                self.AddRef();
-                ::com::sys::NOERROR
+    fn gen_query_interface_body(class: &Class) -> TokenStream {
+        // Generate match arms for implemented interfaces
+        let base_match_arms = Self::gen_base_match_arms(class);
+        let aggregates = class.aggregates.iter().map(|field| {
+            quote! {
+                if let ::core::option::Option::Some(inner) = self.#field.get() {
+                    let hr = inner.QueryInterface(riid, ppv);
+                    if !::com::sys::FAILED(hr) {
+                        return hr;
+                    }
+                }
             }
+        });
+
+        quote! {
+            let riid = &*riid;
+
+            // Use 'pv' for definite assignment analysis, to guarantee
+            // that we always assign *ppv.
+            let pv: *const ::core::ffi::c_void =
+                #base_match_arms {
+                    // Interfaces which are not implemented by the class itself may
+                    // be implemented by the objects it aggregates.
+                    #(#aggregates)*
+                    *ppv = ::core::ptr::null_mut::<::core::ffi::c_void>();
+                    return ::com::sys::E_NOINTERFACE;
+                };
+
+            *ppv = pv as *mut ::core::ffi::c_void;
+
+            self.AddRef();
+            ::com::sys::NOERROR
         }
     }
 
-    fn gen_base_match_arms(interfaces: &[Interface]) -> TokenStream {
+    fn gen_base_match_arms(class: &Class) -> TokenStream {
         // Generate match arms for implemented interfaces
-        interfaces.iter().enumerate().map(|(index, interface)| {
+        class.interfaces.iter().enumerate().map(|(index, interface)| {
             let interface_path = &interface.path;
             let interface_field_ident = interface.chain_ident(index);
             let or_iunknown_clause =
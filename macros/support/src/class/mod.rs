@@ -2,6 +2,7 @@
 mod class;
 mod class_constructor;
 mod class_factory;
+mod dispatch;
 mod iunknown_impl;
 #[cfg(test)]
 mod tests;
@@ -1,3 +1,6 @@
+// `cfg(disabled)` is used below to keep known-failing tests from being compiled.
+#![allow(unexpected_cfgs)]
+
 use crate::test_utils::is_verbose_testing;
 use crate::test_utils::rustfmt;
 use crate::Class;
@@ -236,3 +239,329 @@ fn from_impls() {
         impl IZap for Server {}
     });
 }
+
+#[test]
+fn retval_method() {
+    let class = parse_class_ok(quote! {
+        class Server: IFoo {}
+        impl IFoo for Server {
+            fn get(&self, flags: u32) -> Result<u32, HRESULT> {
+                Ok(flags)
+            }
+        }
+    });
+    let tokens = class.to_tokens().to_string();
+    assert!(tokens.contains("__retval : * mut u32"));
+    assert!(tokens.contains("const _ : () = IFoo :: __get_has_retval ;"));
+}
+
+#[test]
+fn aggregatable() {
+    let class = parse_class_ok(quote! {
+        #[aggregatable]
+        pub class Simple: IFoo {}
+        impl IFoo for Simple {}
+    });
+    assert!(class.aggregatable);
+    let tokens = class.to_tokens().to_string();
+    assert!(tokens.contains("Simple__NON_DELEGATING_VTABLE"));
+    assert!(tokens.contains("fn allocate_aggregated"));
+}
+
+#[test]
+fn aggregate_field() {
+    let class = parse_class_ok(quote! {
+        pub class Outer: IFoo {
+            #[aggregate]
+            inner: com::production::Aggregate,
+            other: u32,
+        }
+        impl IFoo for Outer {}
+    });
+    assert!(!class.aggregatable);
+    assert_eq!(class.aggregates.len(), 1);
+    assert!(class.aggregates[0] == "inner");
+}
+
+#[test]
+fn weak_ref() {
+    let class = parse_class_ok(quote! {
+        #[weak_ref]
+        pub class Simple: IFoo {}
+        impl IFoo for Simple {}
+    });
+    assert!(class.weak_ref);
+    assert_eq!(class.interfaces.len(), 2);
+    let tokens = class.to_tokens().to_string();
+    assert!(tokens.contains("WeakRefCount"));
+    assert!(tokens.contains("Simple__IWeakReferenceSource_VTABLE"));
+}
+
+#[test]
+fn err_weak_ref_declared_source() {
+    parse_class_err(
+        quote! {
+            #[weak_ref]
+            pub class Simple: IFoo, IWeakReferenceSource {}
+            impl IFoo for Simple {}
+            impl IWeakReferenceSource for Simple {}
+        },
+        "IWeakReferenceSource is implemented automatically",
+    );
+}
+
+#[test]
+fn err_weak_ref_aggregatable() {
+    parse_class_err(
+        quote! {
+            #[weak_ref]
+            #[aggregatable]
+            pub class Simple: IFoo {}
+            impl IFoo for Simple {}
+        },
+        "#[weak_ref] classes cannot be #[aggregatable]",
+    );
+}
+
+#[test]
+fn refcount_local() {
+    let class = parse_class_ok(quote! {
+        #[refcount(local)]
+        pub class Simple: IFoo {}
+        impl IFoo for Simple {}
+    });
+    assert!(class.local_refcount);
+    let tokens = class.to_tokens().to_string();
+    assert!(tokens.contains("LocalRefCount"));
+}
+
+#[test]
+fn refcount_atomic() {
+    let class = parse_class_ok(quote! {
+        #[refcount(atomic)]
+        pub class Simple: IFoo {}
+        impl IFoo for Simple {}
+    });
+    assert!(!class.local_refcount);
+}
+
+#[test]
+fn err_refcount_unrecognized() {
+    parse_class_err(
+        quote! {
+            #[refcount(shared)]
+            pub class Simple: IFoo {}
+            impl IFoo for Simple {}
+        },
+        "Expected #[refcount(atomic)] or #[refcount(local)]",
+    );
+}
+
+#[test]
+fn on_panic_default() {
+    let class = parse_class_ok(quote! {
+        pub class Simple: IFoo {}
+        impl IFoo for Simple {
+            fn Run(&self) -> HRESULT {}
+            fn Count(&self) -> u32 {}
+        }
+    });
+    let tokens = class.to_tokens().to_string();
+    assert!(tokens.contains(":: com :: unwind :: catch (:: com :: sys :: E_UNEXPECTED"));
+    assert!(tokens.contains("abort_on_panic (\"Simple::Count\""));
+    assert!(tokens.contains("abort_on_panic (\"Simple::AddRef\""));
+    assert!(!tokens.contains("abort_on_panic (\"Simple::Run\""));
+}
+
+#[test]
+fn on_panic_hresult() {
+    let class = parse_class_ok(quote! {
+        #[on_panic(com::sys::E_FAIL)]
+        pub class Simple: IFoo {}
+        impl IFoo for Simple {
+            fn Get(&self) -> Result<u32, HRESULT> {}
+        }
+    });
+    let tokens = class.to_tokens().to_string();
+    assert!(tokens.contains(":: com :: unwind :: catch (com :: sys :: E_FAIL"));
+    assert!(!tokens.contains("E_UNEXPECTED"));
+}
+
+#[test]
+fn on_panic_abort() {
+    let class = parse_class_ok(quote! {
+        #[on_panic(abort)]
+        pub class Simple: IFoo {}
+        impl IFoo for Simple {
+            fn Run(&self) -> HRESULT {}
+        }
+    });
+    let tokens = class.to_tokens().to_string();
+    assert!(tokens.contains("abort_on_panic (\"Simple::Run\""));
+    assert!(tokens.contains("abort_on_panic (\"Simple::QueryInterface\""));
+    assert!(!tokens.contains(":: com :: unwind :: catch"));
+}
+
+#[test]
+fn err_on_panic_unrecognized() {
+    parse_class_err(
+        quote! {
+            #[on_panic]
+            pub class Simple: IFoo {}
+            impl IFoo for Simple {}
+        },
+        "Expected #[on_panic(abort)] or #[on_panic(<HRESULT>)]",
+    );
+}
+
+#[test]
+fn dispatch() {
+    let class = parse_class_ok(quote! {
+        #[dispatch]
+        pub class Simple: IFoo {}
+        impl IFoo for Simple {
+            fn Add(&self, a: i32, b: i32) -> Result<i32, com::Error> {
+                Ok(a + b)
+            }
+        }
+    });
+    assert!(class.dispatch);
+    // IFoo, IDispatch and ISupportErrorInfo
+    assert_eq!(class.interfaces.len(), 3);
+    let tokens = class.to_tokens().to_string();
+    assert!(tokens.contains("Simple__IDispatch_VTABLE"));
+    assert!(tokens.contains("get_ids_of_names (& [\"Add\"]"));
+    assert!(
+        tokens.contains("Convert :: < i32 > (:: core :: marker :: PhantomData)) . argument_fn ()")
+    );
+}
+
+#[test]
+fn dispatch_return_values() {
+    let class = parse_class_ok(quote! {
+        #[dispatch]
+        pub class Simple: IFoo {}
+        impl IFoo for Simple {
+            fn Legs(&self) -> u32 {
+                4
+            }
+            fn Ping(&self) -> HResult {
+                HResult(S_OK)
+            }
+            fn Fill(&self, buffer: *mut u8) -> HRESULT {
+                S_OK
+            }
+        }
+    });
+    let tokens = class.to_tokens().to_string();
+    // Values are returned in the result, and `HResult`s by `Invoke`
+    assert!(
+        tokens.contains("Convert :: < u32 > (:: core :: marker :: PhantomData)) . result_fn ()")
+    );
+    assert!(tokens.contains("set_result (result , __into_variant (Self :: Legs (self ,)))"));
+    assert!(tokens.contains(":: com :: HResult :: from (Self :: Ping (self ,)) . 0"));
+    // Methods taking pointers keep their DISPID, but are not invoked
+    assert!(tokens.contains("get_ids_of_names (& [\"Legs\" , \"Ping\" , \"Fill\"]"));
+    assert!(!tokens.contains("Self :: Fill"));
+}
+
+#[test]
+fn dispatch_dual_interface() {
+    let class = parse_class_ok(quote! {
+        #[dispatch]
+        pub class Simple: IFoo(IDispatch) {}
+        impl IFoo for Simple {}
+    });
+    assert_eq!(class.interfaces.len(), 1);
+    let dispatch: syn::Path = syn::parse_quote!(IDispatch);
+    assert_eq!(class.methods[&dispatch].len(), 4);
+}
+
+#[test]
+fn dispatch_method_name_collision() {
+    let class = parse_class_ok(quote! {
+        #[dispatch]
+        pub class Simple: IFoo {}
+        impl IFoo for Simple {
+            fn Invoke(&self) -> HRESULT {
+                S_OK
+            }
+        }
+    });
+    // The generated `Invoke` calls the renamed method of `IFoo`
+    let tokens = class.to_tokens().to_string();
+    assert!(tokens.contains("Self :: IFoo__Invoke (self ,)"));
+}
+
+#[test]
+fn err_dispatch_implemented() {
+    parse_class_err(
+        quote! {
+            #[dispatch]
+            pub class Simple: IFoo(IDispatch) {}
+            impl IFoo for Simple {}
+            impl IDispatch for Simple {}
+        },
+        "IDispatch is implemented automatically for #[dispatch] classes",
+    );
+}
+
+#[test]
+fn support_error_info() {
+    let class = parse_class_ok(quote! {
+        pub class Simple: IFoo, IBar {}
+        impl IFoo for Simple {
+            fn Get(&self) -> Result<u32, com::Error> {}
+        }
+        impl IBar for Simple {
+            fn Run(&self) -> HRESULT {}
+        }
+    });
+    assert_eq!(class.interfaces.len(), 3);
+    let tokens = class.to_tokens().to_string();
+    assert!(tokens.contains("Simple__ISupportErrorInfo_VTABLE"));
+    assert!(tokens.contains("riid == & < IFoo as :: com :: Interface > :: IID"));
+    assert!(!tokens.contains("< IBar as :: com :: Interface > :: IID"));
+}
+
+#[test]
+fn support_error_info_hresult_only() {
+    let class = parse_class_ok(quote! {
+        pub class Simple: IFoo {}
+        impl IFoo for Simple {
+            fn Get(&self) -> Result<u32, HRESULT> {}
+        }
+    });
+    assert_eq!(class.interfaces.len(), 1);
+    assert!(!class.to_tokens().to_string().contains("ISupportErrorInfo"));
+}
+
+#[test]
+fn support_error_info_declared() {
+    let class = parse_class_ok(quote! {
+        pub class Simple: IFoo, ISupportErrorInfo {}
+        impl IFoo for Simple {
+            fn Get(&self) -> Result<u32, com::Error> {}
+        }
+        impl ISupportErrorInfo for Simple {
+            fn InterfaceSupportsErrorInfo(&self, riid: *const IID) -> HRESULT {}
+        }
+    });
+    assert_eq!(class.interfaces.len(), 2);
+    assert!(!class.to_tokens().to_string().contains("S_FALSE"));
+}
+
+#[test]
+fn refcount_tracing() {
+    let class = parse_class_ok(quote! {
+        #[aggregatable]
+        pub class Simple: IFoo {}
+        impl IFoo for Simple {}
+    });
+    let tokens = class.to_tokens().to_string();
+    assert!(tokens.contains(
+        "refcounting :: trace (this . as_ptr () . sub (0usize) as * const Simple , :: core :: option :: Option :: Some (0usize) , :: com :: refcounting :: Operation :: AddRef"
+    ));
+    assert!(tokens.contains("refcounting :: release_object (this . as_ptr () . sub (1usize) as * const Simple , & munged . __refcnt , :: core :: option :: Option :: None)"));
+    assert!(tokens.contains("trace_query_interface"));
+}
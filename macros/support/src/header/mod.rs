@@ -0,0 +1,295 @@
+//! Generation of C/C++ headers from `interfaces!` declarations
+//!
+//! The generated header declares each interface the way MIDL does: a
+//! `MIDL_INTERFACE` struct for C++, a vtable struct and an `interface` struct for C
+//! with the same layout as the vtable generated by `interfaces!`, and a
+//! `DEFINE_GUID` line for its IID.
+#[cfg(test)]
+mod tests;
+mod types;
+
+use crate::interface::{Interface, InterfaceMethod, Interfaces};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Interfaces declared in the Windows SDK headers, which can be used as parents
+/// without being part of the input
+const SDK_INTERFACES: &str = r#"
+    #[uuid("00000000-0000-0000-C000-000000000046")]
+    pub unsafe interface IUnknown {
+        fn QueryInterface(&self, riid: *const IID, ppvObject: *mut *mut c_void) -> HRESULT;
+        fn AddRef(&self) -> u32;
+        fn Release(&self) -> u32;
+    }
+
+    #[uuid("00000001-0000-0000-C000-000000000046")]
+    pub unsafe interface IClassFactory: IUnknown {
+        fn CreateInstance(
+            &self,
+            pUnkOuter: Option<IUnknown>,
+            riid: *const IID,
+            ppvObject: *mut *mut c_void,
+        ) -> HRESULT;
+        fn LockServer(&self, fLock: BOOL) -> HRESULT;
+    }
+
+    #[uuid("00020400-0000-0000-C000-000000000046")]
+    pub unsafe interface IDispatch: IUnknown {
+        fn GetTypeInfoCount(&self, pctinfo: *mut u32) -> HRESULT;
+        fn GetTypeInfo(&self, iTInfo: u32, lcid: LCID, ppTInfo: *mut *mut ITypeInfo) -> HRESULT;
+        fn GetIDsOfNames(
+            &self,
+            riid: *const IID,
+            rgszNames: *mut LPOLESTR,
+            cNames: u32,
+            lcid: LCID,
+            rgDispId: *mut DISPID,
+        ) -> HRESULT;
+        fn Invoke(
+            &self,
+            dispIdMember: DISPID,
+            riid: *const IID,
+            lcid: LCID,
+            wFlags: u16,
+            pDispParams: *mut DISPPARAMS,
+            pVarResult: *mut VARIANT,
+            pExcepInfo: *mut EXCEPINFO,
+            puArgErr: *mut u32,
+        ) -> HRESULT;
+    }
+"#;
+
+/// Collect the interfaces declared with `interfaces!` in a Rust source file
+///
+/// Invocations inside inline modules are included. Invocations are recognized by the
+/// last segment of the macro path, so both `interfaces!` and `com::interfaces!` work.
+pub fn parse_source(source: &str) -> syn::Result<Vec<Interface>> {
+    let file = syn::parse_file(source)?;
+    let mut interfaces = Vec::new();
+    collect_interfaces(&file.items, &mut interfaces)?;
+    Ok(interfaces)
+}
+
+fn collect_interfaces(items: &[syn::Item], interfaces: &mut Vec<Interface>) -> syn::Result<()> {
+    for item in items {
+        match item {
+            syn::Item::Macro(item) if is_interfaces_macro(&item.mac.path) => {
+                let parsed: Interfaces = syn::parse2(item.mac.tokens.clone())?;
+                interfaces.extend(parsed.inner);
+            }
+            syn::Item::Mod(syn::ItemMod {
+                content: Some((_, items)),
+                ..
+            }) => collect_interfaces(items, interfaces)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn is_interfaces_macro(path: &syn::Path) -> bool {
+    path.segments
+        .last()
+        .map(|s| s.ident == "interfaces")
+        .unwrap_or(false)
+}
+
+/// Generate a C/C++ header declaring `interfaces`
+///
+/// The parent of each interface must either be part of `interfaces` or be one of
+/// `IUnknown`, `IClassFactory` and `IDispatch`, since the C vtable structs list the
+/// methods of all the parents of an interface.
+pub fn generate(interfaces: &[Interface]) -> syn::Result<String> {
+    let sdk: Interfaces = syn::parse_str(SDK_INTERFACES)?;
+    let mut known: HashMap<String, &Interface> =
+        sdk.inner.iter().map(|i| (i.name.to_string(), i)).collect();
+    for interface in interfaces {
+        if known.contains_key(&interface.name.to_string()) {
+            return Err(syn::Error::new(
+                interface.name.span(),
+                format!("interface `{}` is declared more than once", interface.name),
+            ));
+        }
+        known.insert(interface.name.to_string(), interface);
+    }
+
+    let names = interfaces
+        .iter()
+        .map(|i| i.name.to_string())
+        .chain(sdk.inner.iter().map(|i| i.name.to_string()))
+        .collect::<Vec<_>>();
+    let types = types::TypeMapper::new(&names);
+
+    let mut out = String::new();
+    out.push_str("/* Generated from `interfaces!` declarations. Do not edit. */\n\n");
+    out.push_str("#pragma once\n\n");
+    out.push_str("#include <stdint.h>\n");
+    out.push_str("#include <objbase.h>\n");
+    out.push_str("#include <oaidl.h>\n");
+
+    if !interfaces.is_empty() {
+        out.push_str("\n/* Forward declarations */\n");
+        for interface in interfaces {
+            writeln!(out, "typedef interface {0} {0};", interface.name).unwrap();
+        }
+    }
+
+    for interface in interfaces {
+        let chain = chain(interface, &known)?;
+        out.push('\n');
+        write_interface(&mut out, interface, &chain, &types)?;
+    }
+    Ok(out)
+}
+
+/// The interfaces whose methods make up the vtable of `interface`, base first
+fn chain<'a>(
+    interface: &'a Interface,
+    known: &HashMap<String, &'a Interface>,
+) -> syn::Result<Vec<&'a Interface>> {
+    let mut chain = vec![interface];
+    let mut current = interface;
+    while let Some(parent) = &current.parent {
+        let name = parent.segments.last().unwrap().ident.to_string();
+        current = match known.get(&name) {
+            Some(parent) if chain.iter().all(|i| i.name != parent.name) => parent,
+            Some(_) => {
+                return Err(syn::Error::new(
+                    interface.name.span(),
+                    format!("interface `{}` inherits from itself", interface.name),
+                ))
+            }
+            None => {
+                return Err(syn::Error::new(
+                    interface.name.span(),
+                    format!(
+                        "parent interface `{}` of `{}` is not declared in the input",
+                        name, interface.name
+                    ),
+                ))
+            }
+        };
+        chain.push(current);
+    }
+    chain.reverse();
+    Ok(chain)
+}
+
+fn write_interface(
+    out: &mut String,
+    interface: &Interface,
+    chain: &[&Interface],
+    types: &types::TypeMapper,
+) -> syn::Result<()> {
+    let name = &interface.name;
+    let iid = &interface.iid;
+
+    writeln!(out, "/* {} */", name).unwrap();
+    writeln!(
+        out,
+        "DEFINE_GUID(IID_{}, {});\n",
+        name,
+        iid.to_c_fields().join(", ")
+    )
+    .unwrap();
+
+    // C++
+    out.push_str("#if defined(__cplusplus) && !defined(CINTERFACE)\n\n");
+    writeln!(out, "MIDL_INTERFACE(\"{}\")", iid.to_registry_string()).unwrap();
+    match &interface.parent {
+        Some(parent) => writeln!(
+            out,
+            "{} : public {}",
+            name,
+            parent.segments.last().unwrap().ident
+        )
+        .unwrap(),
+        None => writeln!(out, "{}", name).unwrap(),
+    }
+    out.push_str("{\npublic:\n");
+    for method in &interface.methods {
+        let params = params(method, types)?;
+        let params = if params.is_empty() {
+            "void".to_owned()
+        } else {
+            params.join(", ")
+        };
+        writeln!(
+            out,
+            "    virtual {} STDMETHODCALLTYPE {}({}) = 0;",
+            return_type(method, types)?,
+            method_name(method),
+            params
+        )
+        .unwrap();
+    }
+    out.push_str("};\n\n");
+
+    // C
+    out.push_str("#else /* C style interface */\n\n");
+    writeln!(
+        out,
+        "typedef struct {}Vtbl\n{{\n    BEGIN_INTERFACE\n",
+        name
+    )
+    .unwrap();
+    for parent in chain {
+        if !parent.methods.is_empty() {
+            writeln!(out, "    /* {} */", parent.name).unwrap();
+        }
+        for method in &parent.methods {
+            let params = std::iter::once(format!("{} *This", name))
+                .chain(params(method, types)?)
+                .collect::<Vec<_>>();
+            writeln!(
+                out,
+                "    {} (STDMETHODCALLTYPE *{})({});",
+                return_type(method, types)?,
+                method_name(method),
+                params.join(", ")
+            )
+            .unwrap();
+        }
+        if !parent.methods.is_empty() {
+            out.push('\n');
+        }
+    }
+    writeln!(out, "    END_INTERFACE\n}} {}Vtbl;\n", name).unwrap();
+    writeln!(
+        out,
+        "interface {}\n{{\n    CONST_VTBL struct {}Vtbl *lpVtbl;\n}};\n",
+        name, name
+    )
+    .unwrap();
+    out.push_str("#endif\n");
+    Ok(())
+}
+
+/// The name of the vtable entry of `method`, as generated by `vtable::generate`
+fn method_name(method: &InterfaceMethod) -> String {
+    crate::utils::snake_to_camel(&method.name.to_string())
+}
+
+fn return_type(method: &InterfaceMethod, types: &types::TypeMapper) -> syn::Result<String> {
+    if method.retval().is_some() {
+        return Ok("HRESULT".to_owned());
+    }
+    match &method.ret {
+        syn::ReturnType::Default => Ok("void".to_owned()),
+        syn::ReturnType::Type(_, ty) => types.c_type(ty),
+    }
+}
+
+fn params(method: &InterfaceMethod, types: &types::TypeMapper) -> syn::Result<Vec<String>> {
+    method
+        .args
+        .iter()
+        .map(|arg| {
+            let ty = types.c_type(&arg.ty)?;
+            Ok(match &*arg.pat {
+                syn::Pat::Ident(pat) => types::declaration(&ty, &pat.ident.to_string()),
+                _ => ty,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,102 @@
+//! Golden-file tests for the generated headers
+//!
+//! The expected headers live in `tests/headers`.
+use super::{generate, parse_source};
+use crate::test_utils::assert_golden;
+use std::path::Path;
+
+fn header(inputs: &[&str]) -> String {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut interfaces = Vec::new();
+    for input in inputs {
+        let source = std::fs::read_to_string(root.join(input)).unwrap();
+        interfaces.extend(parse_source(&source).unwrap());
+    }
+    generate(&interfaces).unwrap()
+}
+
+fn generate_err(source: &str, expected_error: &str) {
+    let interfaces = parse_source(source).unwrap();
+    match generate(&interfaces) {
+        Ok(_) => panic!("Expected header generation to fail.\nInput: {}", source),
+        Err(e) => {
+            let e_string = e.to_string();
+            if !e_string.contains(expected_error) {
+                panic!(
+                    "Did not find expected error string.\nActual error: {:?}\nExpected error: {:?}",
+                    e_string, expected_error
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn basic_example() {
+    let header = header(&[
+        "../../examples/basic/interface/src/ianimal.rs",
+        "../../examples/basic/interface/src/icat.rs",
+        "../../examples/basic/interface/src/idomesticanimal.rs",
+        "../../examples/basic/interface/src/iexample.rs",
+        "../../examples/basic/interface/src/icat_class.rs",
+    ]);
+    assert_golden(&header, "tests/headers/basic.h");
+}
+
+#[test]
+fn types() {
+    let header = header(&["tests/headers/types.rs"]);
+    assert_golden(&header, "tests/headers/types.h");
+}
+
+#[test]
+fn no_interfaces() {
+    let header = generate(&parse_source("fn main() {}").unwrap()).unwrap();
+    assert!(header.contains("#pragma once"));
+    assert!(!header.contains("typedef interface"));
+}
+
+#[test]
+fn err_unknown_parent() {
+    generate_err(
+        r#"
+        interfaces! {
+            #[uuid("12345678-1234-1234-1234-12345678ABCD")]
+            pub unsafe interface IFoo: IBar {}
+        }
+        "#,
+        "parent interface `IBar` of `IFoo` is not declared in the input",
+    );
+}
+
+#[test]
+fn err_declared_twice() {
+    generate_err(
+        r#"
+        interfaces! {
+            #[uuid("12345678-1234-1234-1234-12345678ABCD")]
+            pub unsafe interface IFoo: IUnknown {}
+        }
+        interfaces! {
+            #[uuid("12345678-1234-1234-1234-12345678ABCE")]
+            pub unsafe interface IFoo: IUnknown {}
+        }
+        "#,
+        "interface `IFoo` is declared more than once",
+    );
+}
+
+#[test]
+fn err_unsupported_type() {
+    generate_err(
+        r#"
+        interfaces! {
+            #[uuid("12345678-1234-1234-1234-12345678ABCD")]
+            pub unsafe interface IFoo: IUnknown {
+                fn Foo(&self, values: [u8; 4]) -> HRESULT;
+            }
+        }
+        "#,
+        "type is not supported in C headers",
+    );
+}
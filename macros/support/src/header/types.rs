@@ -0,0 +1,100 @@
+use std::collections::HashSet;
+use syn::spanned::Spanned;
+use syn::Type;
+
+/// Maps the Rust types used in `interfaces!` declarations to C types
+///
+/// The types which `com` passes through the ABI as something else (interfaces,
+/// `Option`s of interfaces and the owned OLE Automation types) are mapped to the C
+/// type of their ABI. Other paths are kept as is, so that type aliases such as
+/// `HRESULT` and `BOOL` map to their Windows SDK declarations.
+pub struct TypeMapper {
+    interfaces: HashSet<String>,
+}
+
+impl TypeMapper {
+    pub fn new(interfaces: &[String]) -> Self {
+        Self {
+            interfaces: interfaces.iter().cloned().collect(),
+        }
+    }
+
+    /// The C type of `ty`
+    pub fn c_type(&self, ty: &Type) -> syn::Result<String> {
+        match ty {
+            Type::Path(path) if path.qself.is_none() => {
+                let segment = path.path.segments.last().unwrap();
+                let name = segment.ident.to_string();
+                if name == "Option" {
+                    return self.option_type(ty, &segment.arguments);
+                }
+                if self.interfaces.contains(&name) {
+                    return Ok(format!("{} *", name));
+                }
+                Ok(match name.as_str() {
+                    "bool" => "boolean",
+                    "i8" => "int8_t",
+                    "u8" => "uint8_t",
+                    "i16" => "int16_t",
+                    "u16" => "uint16_t",
+                    "i32" => "int32_t",
+                    "u32" => "uint32_t",
+                    "i64" => "int64_t",
+                    "u64" => "uint64_t",
+                    "f32" => "float",
+                    "f64" => "double",
+                    "usize" => "size_t",
+                    "isize" => "ptrdiff_t",
+                    "c_void" => "void",
+                    "HResult" => "HRESULT",
+                    "BStr" => "BSTR",
+                    "Variant" => "VARIANT",
+                    "PropVariant" => "PROPVARIANT",
+                    "SafeArray" => "SAFEARRAY *",
+                    _ => return Ok(name),
+                }
+                .to_owned())
+            }
+            Type::Ptr(ptr) => {
+                let elem = self.c_type(&ptr.elem)?;
+                Ok(match (ptr.const_token.is_some(), elem.ends_with('*')) {
+                    (false, true) => format!("{}*", elem),
+                    (false, false) => format!("{} *", elem),
+                    (true, true) => format!("{}const *", elem),
+                    (true, false) => format!("const {} *", elem),
+                })
+            }
+            Type::Paren(paren) => self.c_type(&paren.elem),
+            Type::Group(group) => self.c_type(&group.elem),
+            _ => Err(unsupported(ty)),
+        }
+    }
+
+    fn option_type(&self, ty: &Type, arguments: &syn::PathArguments) -> syn::Result<String> {
+        if let syn::PathArguments::AngleBracketed(arguments) = arguments {
+            if let Some(syn::GenericArgument::Type(Type::Path(inner))) = arguments.args.first() {
+                let name = inner.path.segments.last().unwrap().ident.to_string();
+                if self.interfaces.contains(&name) {
+                    return Ok(format!("{} *", name));
+                }
+            }
+        }
+        Err(unsupported(ty))
+    }
+}
+
+/// A declaration of `name` with the C type `ty`
+pub fn declaration(ty: &str, name: &str) -> String {
+    if ty.ends_with('*') {
+        format!("{}{}", ty, name)
+    } else {
+        format!("{} {}", ty, name)
+    }
+}
+
+fn unsupported(ty: &Type) -> syn::Error {
+    syn::Error::new(
+        ty.span(),
+        "type is not supported in C headers: only paths, pointers and `Option`s of interfaces are",
+    )
+}
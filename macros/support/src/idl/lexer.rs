@@ -0,0 +1,119 @@
+use super::Error;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    Ident(String),
+    /// A number literal, without any `L`/`U` suffix
+    Number(String),
+    Str(String),
+    Punct(char),
+}
+
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+    /// Byte offsets of the token in the source
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Split IDL source into tokens, skipping comments and preprocessor directives
+pub fn tokenize(source: &str) -> Result<Vec<Token>, Error> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut line_start = true;
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        match c {
+            b'\n' => {
+                line += 1;
+                line_start = true;
+                i += 1;
+                continue;
+            }
+            c if c.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b'#' if line_start => {
+                // Preprocessor directives, including their continuation lines
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    if bytes[i] == b'\\' && bytes.get(i + 1) == Some(&b'\n') {
+                        line += 1;
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let start_line = line;
+                i += 2;
+                loop {
+                    match bytes.get(i) {
+                        Some(b'*') if bytes.get(i + 1) == Some(&b'/') => break,
+                        Some(b'\n') => line += 1,
+                        Some(_) => {}
+                        None => return Err(Error::new(start_line, "unterminated comment")),
+                    }
+                    i += 1;
+                }
+                i += 2;
+                continue;
+            }
+            _ => {}
+        }
+        line_start = false;
+
+        let start = i;
+        let kind = if c == b'_' || c.is_ascii_alphabetic() {
+            while i < bytes.len() && (bytes[i] == b'_' || bytes[i].is_ascii_alphanumeric()) {
+                i += 1;
+            }
+            TokenKind::Ident(source[start..i].to_owned())
+        } else if c.is_ascii_digit() {
+            while i < bytes.len() && (bytes[i] == b'.' || bytes[i].is_ascii_alphanumeric()) {
+                i += 1;
+            }
+            let number = source[start..i].trim_end_matches(['l', 'L', 'u', 'U']);
+            TokenKind::Number(number.to_owned())
+        } else if c == b'"' {
+            i += 1;
+            while i < bytes.len() && bytes[i] != b'"' {
+                match bytes[i] {
+                    b'\\' => i += 1,
+                    b'\n' => return Err(Error::new(line, "unterminated string")),
+                    _ => {}
+                }
+                i += 1;
+            }
+            if i == bytes.len() {
+                return Err(Error::new(line, "unterminated string"));
+            }
+            i += 1;
+            TokenKind::Str(source[start + 1..i - 1].to_owned())
+        } else if c.is_ascii() {
+            i += 1;
+            TokenKind::Punct(c as char)
+        } else {
+            let c = source[i..].chars().next().unwrap();
+            return Err(Error::new(line, format!("unexpected character `{}`", c)));
+        };
+        tokens.push(Token {
+            kind,
+            line,
+            start,
+            end: i,
+        });
+    }
+    Ok(tokens)
+}
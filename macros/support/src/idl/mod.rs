@@ -0,0 +1,94 @@
+//! Conversion of MIDL interface definitions to `interfaces!` declarations
+//!
+//! A practical subset of MIDL is supported: `import`, `interface` declarations with
+//! their `uuid`, parent and methods, `[out, retval]` parameters, `struct`, `union`
+//! and `enum` declarations, `typedef`s, `const`s and the CLSIDs of `coclass`es.
+//! Preprocessor directives, `cpp_quote`, `dispinterface` and `module` are ignored.
+//!
+//! IDL types are mapped to the corresponding Rust types, or to the types of
+//! `com::sys` and `com::interfaces` when they exist. Other names are kept, and are
+//! expected to be declared by the generated code for imported IDL files, which is
+//! used from the `super::<file name>` module. Pointers to the interfaces declared or
+//! forward-declared in the file, declared by the imported files which are read with
+//! [`convert_with_imports`], or declared by the Windows SDK become `Option<IFoo>`.
+//! Enums become `i32` constants.
+pub(crate) mod lexer;
+pub(crate) mod parser;
+pub(crate) mod rust;
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashSet;
+use std::fmt;
+
+/// Convert the source of an IDL file to Rust source using `com::interfaces!`
+///
+/// The imported files are not read, so their interfaces are not known. Use
+/// [`convert_with_imports`] for files importing interfaces from other IDL files.
+pub fn convert(source: &str) -> Result<String, Error> {
+    convert_with_imports(source, |_| None)
+}
+
+/// Convert the source of an IDL file like [`convert`], reading the files it imports
+/// with `read_import` to know the interfaces they declare
+///
+/// `read_import` is called with the name of each imported file other than those of
+/// the Windows SDK, including the files imported by imported files. It returns
+/// `None` for the files which cannot be found.
+pub fn convert_with_imports(
+    source: &str,
+    mut read_import: impl FnMut(&str) -> Option<String>,
+) -> Result<String, Error> {
+    let idl = parse(source)?;
+    let mut imported = HashSet::new();
+    let mut read = HashSet::new();
+    let mut pending = idl.imports().map(str::to_owned).collect::<Vec<_>>();
+    while let Some(file) = pending.pop() {
+        if rust::is_sdk_import(&file) || !read.insert(file.clone()) {
+            continue;
+        }
+        let source = match read_import(&file) {
+            Some(source) => source,
+            None => continue,
+        };
+        let import = parse(&source)
+            .map_err(|e| Error::new(e.line, format!("in {}: {}", file, e.message)))?;
+        imported.extend(import.interface_names().map(str::to_owned));
+        pending.extend(import.imports().map(str::to_owned));
+    }
+    rust::generate(&idl, &imported, "Generated from IDL by com-idl")
+}
+
+fn parse(source: &str) -> Result<parser::Idl, Error> {
+    let tokens = lexer::tokenize(source)?;
+    parser::parse(source, tokens)
+}
+
+/// An error in an IDL file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    line: usize,
+    message: String,
+}
+
+impl Error {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+
+    /// The line of the IDL file where the error occurred, starting at 1
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
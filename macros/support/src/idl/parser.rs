@@ -0,0 +1,711 @@
+use super::lexer::{Token, TokenKind};
+use super::Error;
+
+/// The declarations of an IDL file which are converted to Rust
+#[derive(Debug, Default)]
+pub struct Idl {
+    pub items: Vec<Item>,
+}
+
+impl Idl {
+    /// The names of the interfaces declared or forward-declared in the file
+    pub fn interface_names(&self) -> impl Iterator<Item = &str> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Interface(i) => Some(i.name.as_str()),
+            Item::ForwardInterface(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// The files imported by the file
+    pub fn imports(&self) -> impl Iterator<Item = &str> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Import(file) => Some(file.as_str()),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum Item {
+    /// `import "file.idl";`
+    Import(String),
+    /// `interface IFoo;`
+    ForwardInterface(String),
+    Interface(Interface),
+    /// A `struct` or `union`
+    Struct(Struct),
+    Enum(Enum),
+    /// `typedef Type Name;`
+    Typedef(String, Type),
+    /// `const Type NAME = value;`
+    Const(String, Type, Expr),
+    /// A `coclass` with its CLSID
+    Coclass(String, String),
+}
+
+#[derive(Debug)]
+pub struct Interface {
+    pub name: String,
+    /// The line of the declaration, for error messages
+    pub line: usize,
+    pub uuid: String,
+    pub parent: Option<String>,
+    pub methods: Vec<Method>,
+}
+
+#[derive(Debug)]
+pub struct Method {
+    /// The name of the vtable entry, with the `get_`/`put_`/`putref_` prefixes of
+    /// properties
+    pub name: String,
+    pub ret: Type,
+    pub params: Vec<Param>,
+}
+
+#[derive(Debug)]
+pub struct Param {
+    pub name: Option<String>,
+    pub ty: Type,
+    pub retval: bool,
+    /// The argument of a `size_is(...)` attribute, such as the name of the parameter
+    /// holding the number of elements
+    pub size_is: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Struct {
+    pub name: String,
+    pub is_union: bool,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug)]
+pub struct Field {
+    pub name: String,
+    pub ty: Type,
+    /// The lengths of the array dimensions of the field, outermost first
+    pub dims: Vec<Expr>,
+}
+
+#[derive(Debug)]
+pub struct Enum {
+    pub name: String,
+    pub variants: Vec<(String, Option<Expr>)>,
+}
+
+/// A C type
+#[derive(Clone, Debug, PartialEq)]
+pub struct Type {
+    /// The name of the base type, e.g. `unsigned long` or `IUnknown`
+    pub name: String,
+    /// Whether the base type is `const`
+    pub is_const: bool,
+    /// One entry per `*`, telling whether that pointer is itself `const`
+    pub pointers: Vec<bool>,
+}
+
+impl Type {
+    /// Whether the `level`th pointer (starting at 0) points to a `const` value
+    pub fn points_to_const(&self, level: usize) -> bool {
+        match level {
+            0 => self.is_const,
+            _ => self.pointers[level - 1],
+        }
+    }
+}
+
+/// A constant expression, as a sequence of tokens
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr(pub Vec<TokenKind>);
+
+/// An attribute such as `uuid(...)` or `in`, with the source text of its arguments
+struct Attribute {
+    name: String,
+    args: Option<String>,
+}
+
+fn find_attribute<'a>(attributes: &'a [Attribute], name: &str) -> Option<&'a Attribute> {
+    attributes.iter().find(|a| a.name == name)
+}
+
+pub fn parse(source: &str, tokens: Vec<Token>) -> Result<Idl, Error> {
+    let mut parser = Parser {
+        source,
+        tokens,
+        pos: 0,
+        items: Vec::new(),
+    };
+    while !parser.at_end() {
+        parser.item()?;
+    }
+    Ok(Idl {
+        items: parser.items,
+    })
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    items: Vec<Item>,
+}
+
+impl<'a> Parser<'a> {
+    fn at_end(&self) -> bool {
+        self.pos == self.tokens.len()
+    }
+
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|t| &t.kind)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&TokenKind> {
+        self.tokens.get(self.pos + offset).map(|t| &t.kind)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map(|t| t.line)
+            .unwrap_or(1)
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, Error> {
+        Err(Error::new(self.line(), message))
+    }
+
+    fn next(&mut self) -> Result<TokenKind, Error> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                Ok(token.kind.clone())
+            }
+            None => self.error("unexpected end of file"),
+        }
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.peek() == Some(&TokenKind::Punct(c))
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(TokenKind::Ident(i)) if i == keyword)
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        let found = self.is_punct(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<(), Error> {
+        if !self.eat_punct(c) {
+            return self.error(format!("expected `{}`", c));
+        }
+        Ok(())
+    }
+
+    fn ident(&mut self) -> Result<String, Error> {
+        match self.peek() {
+            Some(TokenKind::Ident(i)) => {
+                let i = i.clone();
+                self.pos += 1;
+                Ok(i)
+            }
+            _ => self.error("expected an identifier"),
+        }
+    }
+
+    /// Skip a balanced group starting at the current `(`, `[` or `{`, returning the
+    /// source text between the delimiters
+    fn skip_group(&mut self) -> Result<String, Error> {
+        let open = self.pos;
+        let mut depth = 0;
+        loop {
+            match self.next()? {
+                TokenKind::Punct('(') | TokenKind::Punct('[') | TokenKind::Punct('{') => depth += 1,
+                TokenKind::Punct(')') | TokenKind::Punct(']') | TokenKind::Punct('}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let start = self.tokens[open].end;
+        let end = self.tokens[self.pos - 1].start;
+        Ok(self.source[start..end].trim().to_owned())
+    }
+
+    /// Parse `[attr, attr(args), ...]` if present
+    fn attributes(&mut self) -> Result<Vec<Attribute>, Error> {
+        let mut attributes = Vec::new();
+        if !self.eat_punct('[') {
+            return Ok(attributes);
+        }
+        loop {
+            if self.eat_punct(']') {
+                break;
+            }
+            let name = self.ident()?;
+            let args = if self.is_punct('(') {
+                Some(self.skip_group()?)
+            } else {
+                None
+            };
+            attributes.push(Attribute { name, args });
+            if !self.eat_punct(',') {
+                self.expect_punct(']')?;
+                break;
+            }
+        }
+        Ok(attributes)
+    }
+
+    fn item(&mut self) -> Result<(), Error> {
+        if self.eat_punct(';') {
+            return Ok(());
+        }
+        if self.eat_keyword("import") {
+            loop {
+                match self.next()? {
+                    TokenKind::Str(file) => self.items.push(Item::Import(file)),
+                    _ => return self.error("expected a file name"),
+                }
+                if !self.eat_punct(',') {
+                    break;
+                }
+            }
+            return self.expect_punct(';');
+        }
+        if self.eat_keyword("cpp_quote")
+            || self.eat_keyword("midl_pragma")
+            || self.eat_keyword("importlib")
+        {
+            // midl_pragma warning(...)
+            self.eat_keyword("warning");
+            self.skip_group()?;
+            self.eat_punct(';');
+            return Ok(());
+        }
+
+        let attributes = self.attributes()?;
+        let keyword = match self.peek() {
+            Some(TokenKind::Ident(keyword)) => keyword.clone(),
+            _ => return self.error("expected a declaration"),
+        };
+        match keyword.as_str() {
+            "interface" => {
+                self.pos += 1;
+                self.interface(&attributes)
+            }
+            "library" => {
+                self.pos += 1;
+                self.ident()?;
+                self.expect_punct('{')?;
+                while !self.eat_punct('}') {
+                    self.item()?;
+                }
+                Ok(())
+            }
+            "coclass" => {
+                self.pos += 1;
+                let name = self.ident()?;
+                if self.is_punct('{') {
+                    self.skip_group()?;
+                }
+                if let Some(uuid) = find_attribute(&attributes, "uuid") {
+                    self.items.push(Item::Coclass(name, uuid_arg(uuid)));
+                }
+                Ok(())
+            }
+            "dispinterface" | "module" => {
+                self.pos += 1;
+                self.ident()?;
+                if self.is_punct('{') {
+                    self.skip_group()?;
+                }
+                Ok(())
+            }
+            _ => self.declaration(),
+        }
+    }
+
+    fn interface(&mut self, attributes: &[Attribute]) -> Result<(), Error> {
+        let line = self.line();
+        let name = self.ident()?;
+        if self.eat_punct(';') {
+            self.items.push(Item::ForwardInterface(name));
+            return Ok(());
+        }
+        let uuid = match find_attribute(attributes, "uuid") {
+            Some(uuid) => uuid_arg(uuid),
+            None => return self.error(format!("interface `{}` has no uuid attribute", name)),
+        };
+        let parent = if self.eat_punct(':') {
+            Some(self.ident()?)
+        } else {
+            None
+        };
+        self.expect_punct('{')?;
+        let mut methods = Vec::new();
+        while !self.eat_punct('}') {
+            if self.eat_punct(';') {
+                continue;
+            }
+            if self.is_keyword("cpp_quote") || self.is_keyword("midl_pragma") {
+                self.pos += 1;
+                self.eat_keyword("warning");
+                self.skip_group()?;
+                continue;
+            }
+            if ["typedef", "struct", "union", "enum", "const"]
+                .iter()
+                .any(|k| self.is_keyword(k))
+            {
+                self.declaration()?;
+                continue;
+            }
+            let (method, is_remote) = self.method()?;
+            if !is_remote {
+                methods.push(method);
+            }
+        }
+        self.items.push(Item::Interface(Interface {
+            name,
+            line,
+            uuid,
+            parent,
+            methods,
+        }));
+        Ok(())
+    }
+
+    /// A method, and whether it is the `[call_as]` remote version of a `[local]`
+    /// method, which is not part of the vtable
+    fn method(&mut self) -> Result<(Method, bool), Error> {
+        let attributes = self.attributes()?;
+        let ret = self.ty()?;
+        // Calling conventions are implied
+        while self.is_keyword("STDMETHODCALLTYPE") || self.is_keyword("__stdcall") {
+            self.pos += 1;
+        }
+        let mut name = self.ident()?;
+        for (attribute, prefix) in &[
+            ("propget", "get_"),
+            ("propput", "put_"),
+            ("propputref", "putref_"),
+        ] {
+            if find_attribute(&attributes, attribute).is_some() {
+                name = format!("{}{}", prefix, name);
+            }
+        }
+        self.expect_punct('(')?;
+        let mut params = Vec::new();
+        let is_void = self.is_keyword("void") && self.peek_at(1) == Some(&TokenKind::Punct(')'));
+        if is_void {
+            self.pos += 1;
+        }
+        while !self.eat_punct(')') {
+            params.push(self.param()?);
+            if !self.eat_punct(',') {
+                self.expect_punct(')')?;
+                break;
+            }
+        }
+        self.expect_punct(';')?;
+        let is_remote = find_attribute(&attributes, "call_as").is_some();
+        Ok((Method { name, ret, params }, is_remote))
+    }
+
+    fn param(&mut self) -> Result<Param, Error> {
+        let attributes = self.attributes()?;
+        let mut ty = self.ty()?;
+        let name = match self.peek() {
+            Some(TokenKind::Ident(_)) => Some(self.ident()?),
+            _ => None,
+        };
+        // Array parameters are pointers
+        while self.is_punct('[') {
+            self.skip_group()?;
+            ty.pointers.push(false);
+        }
+        Ok(Param {
+            name,
+            ty,
+            retval: find_attribute(&attributes, "retval").is_some(),
+            size_is: find_attribute(&attributes, "size_is").and_then(|a| a.args.clone()),
+        })
+    }
+
+    /// A C type, without the declarator name
+    fn ty(&mut self) -> Result<Type, Error> {
+        let mut is_const = false;
+        let mut words = Vec::new();
+        loop {
+            match self.peek() {
+                Some(TokenKind::Ident(i)) if i == "const" => {
+                    is_const = true;
+                    self.pos += 1;
+                }
+                Some(TokenKind::Ident(i)) if i == "struct" || i == "enum" || i == "union" => {
+                    // The tag is used as the type name
+                    self.pos += 1;
+                }
+                Some(TokenKind::Ident(i))
+                    if matches!(
+                        i.as_str(),
+                        "unsigned"
+                            | "signed"
+                            | "long"
+                            | "short"
+                            | "int"
+                            | "char"
+                            | "small"
+                            | "hyper"
+                            | "__int64"
+                    ) =>
+                {
+                    words.push(i.clone());
+                    self.pos += 1;
+                }
+                Some(TokenKind::Ident(_)) if words.is_empty() => {
+                    words.push(self.ident()?);
+                }
+                _ => break,
+            }
+        }
+        if words.is_empty() {
+            return self.error("expected a type");
+        }
+        let mut pointers = Vec::new();
+        // `SAFEARRAY(type)` is a pointer to a SAFEARRAY of that type
+        if words == ["SAFEARRAY"] && self.is_punct('(') {
+            self.skip_group()?;
+            pointers.push(false);
+        }
+        while self.eat_punct('*') {
+            pointers.push(self.eat_keyword("const"));
+        }
+        Ok(Type {
+            name: normalize_type_name(&words),
+            is_const,
+            pointers,
+        })
+    }
+
+    /// `typedef`, `struct`, `union`, `enum` and `const` declarations
+    fn declaration(&mut self) -> Result<(), Error> {
+        if self.eat_keyword("const") {
+            let ty = self.ty()?;
+            let name = self.ident()?;
+            self.expect_punct('=')?;
+            let value = self.expr(&[';'])?;
+            self.expect_punct(';')?;
+            self.items.push(Item::Const(name, ty, value));
+            return Ok(());
+        }
+
+        let is_typedef = self.eat_keyword("typedef");
+        if is_typedef {
+            // Attributes such as `[v1_enum]` or `[public]`
+            self.attributes()?;
+        }
+        let is_aggregate = ["struct", "union", "enum"]
+            .iter()
+            .any(|k| self.is_keyword(k))
+            && (self.peek_at(1) == Some(&TokenKind::Punct('{'))
+                || self.peek_at(2) == Some(&TokenKind::Punct('{')));
+        if !is_aggregate {
+            if !is_typedef {
+                return self.error("expected a declaration");
+            }
+            let base = self.ty()?;
+            return self.typedef_names(base);
+        }
+
+        let keyword = self.ident()?;
+        let tag = match self.peek() {
+            Some(TokenKind::Ident(_)) => Some(self.ident()?),
+            _ => None,
+        };
+        self.expect_punct('{')?;
+        let aggregate = if keyword == "enum" {
+            Aggregate::Enum(self.enum_variants()?)
+        } else {
+            Aggregate::Struct(keyword == "union", self.fields()?)
+        };
+
+        if !is_typedef {
+            let name = match tag {
+                Some(tag) => tag,
+                None => return self.error(format!("anonymous {} declaration", keyword)),
+            };
+            self.push_aggregate(name, aggregate);
+            return self.expect_punct(';');
+        }
+
+        // The first declarator names the type; others are aliases or pointers to it
+        let name = self.ident()?;
+        self.push_aggregate(name.clone(), aggregate);
+        if let Some(tag) = tag.filter(|tag| *tag != name) {
+            self.items.push(Item::Typedef(tag, plain_type(&name)));
+        }
+        if self.eat_punct(',') {
+            self.typedef_names(plain_type(&name))
+        } else {
+            self.expect_punct(';')
+        }
+    }
+
+    /// The declarators of a `typedef`, e.g. `FOO, *PFOO;`
+    fn typedef_names(&mut self, base: Type) -> Result<(), Error> {
+        loop {
+            let mut ty = base.clone();
+            while self.eat_punct('*') {
+                ty.pointers.push(self.eat_keyword("const"));
+            }
+            let name = self.ident()?;
+            if self.is_punct('[') || self.is_punct('(') {
+                return self.error(format!("unsupported typedef `{}`", name));
+            }
+            if ty.pointers.is_empty() && ty.name == name {
+                // typedef struct FOO FOO;
+            } else {
+                self.items.push(Item::Typedef(name, ty));
+            }
+            if !self.eat_punct(',') {
+                break;
+            }
+        }
+        self.expect_punct(';')
+    }
+
+    fn push_aggregate(&mut self, name: String, aggregate: Aggregate) {
+        self.items.push(match aggregate {
+            Aggregate::Enum(variants) => Item::Enum(Enum { name, variants }),
+            Aggregate::Struct(is_union, fields) => Item::Struct(Struct {
+                name,
+                is_union,
+                fields,
+            }),
+        });
+    }
+
+    fn enum_variants(&mut self) -> Result<Vec<(String, Option<Expr>)>, Error> {
+        let mut variants = Vec::new();
+        while !self.eat_punct('}') {
+            let name = self.ident()?;
+            let value = if self.eat_punct('=') {
+                Some(self.expr(&[',', '}'])?)
+            } else {
+                None
+            };
+            variants.push((name, value));
+            if !self.eat_punct(',') {
+                self.expect_punct('}')?;
+                break;
+            }
+        }
+        Ok(variants)
+    }
+
+    fn fields(&mut self) -> Result<Vec<Field>, Error> {
+        let mut fields = Vec::new();
+        while !self.eat_punct('}') {
+            self.attributes()?;
+            let base = self.ty()?;
+            loop {
+                let mut ty = base.clone();
+                while self.eat_punct('*') {
+                    ty.pointers.push(self.eat_keyword("const"));
+                }
+                let name = self.ident()?;
+                let mut dims = Vec::new();
+                while self.eat_punct('[') {
+                    dims.push(self.expr(&[']'])?);
+                    self.expect_punct(']')?;
+                }
+                fields.push(Field { name, ty, dims });
+                if !self.eat_punct(',') {
+                    break;
+                }
+            }
+            self.expect_punct(';')?;
+        }
+        Ok(fields)
+    }
+
+    /// The tokens of a constant expression, up to one of `terminators` outside of
+    /// parentheses
+    fn expr(&mut self, terminators: &[char]) -> Result<Expr, Error> {
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+        loop {
+            match self.peek() {
+                Some(TokenKind::Punct(c)) if depth == 0 && terminators.contains(c) => break,
+                Some(TokenKind::Punct('(')) => depth += 1,
+                Some(TokenKind::Punct(')')) => depth -= 1,
+                None => return self.error("unexpected end of file"),
+                _ => {}
+            }
+            tokens.push(self.next()?);
+        }
+        if tokens.is_empty() {
+            return self.error("expected an expression");
+        }
+        Ok(Expr(tokens))
+    }
+}
+
+enum Aggregate {
+    Enum(Vec<(String, Option<Expr>)>),
+    Struct(bool, Vec<Field>),
+}
+
+fn plain_type(name: &str) -> Type {
+    Type {
+        name: name.to_owned(),
+        is_const: false,
+        pointers: Vec::new(),
+    }
+}
+
+/// The argument of a `uuid(...)` attribute, which may be quoted
+fn uuid_arg(attribute: &Attribute) -> String {
+    attribute
+        .args
+        .as_deref()
+        .unwrap_or_default()
+        .trim_matches('"')
+        .to_owned()
+}
+
+/// Normalize multi-word C type names, e.g. `long int` to `long`
+fn normalize_type_name(words: &[String]) -> String {
+    let words = words
+        .iter()
+        .map(String::as_str)
+        .filter(|w| *w != "signed")
+        .collect::<Vec<_>>();
+    let words = match words.as_slice() {
+        [] => vec!["int"],
+        ["unsigned"] => vec!["unsigned", "int"],
+        [rest @ .., "int"] if !rest.is_empty() && rest != ["unsigned"] => rest.to_vec(),
+        _ => words,
+    };
+    words.join(" ")
+}
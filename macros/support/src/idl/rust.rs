@@ -0,0 +1,588 @@
+use super::lexer::TokenKind;
+use super::parser::{Enum, Expr, Idl, Interface, Item, Method, Param, Struct, Type};
+use super::Error;
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write;
+
+/// The interfaces exported by `com::interfaces`
+const COM_INTERFACES: &[&str] = &["IUnknown", "IClassFactory", "IDispatch"];
+
+/// The types exported by `com::sys`
+const SYS_TYPES: &[&str] = &[
+    "HRESULT",
+    "BOOL",
+    "BSTR",
+    "HKEY",
+    "GUID",
+    "VARIANT",
+    "PROPVARIANT",
+    "VARIANT_BOOL",
+    "VARTYPE",
+    "DISPID",
+    "DISPPARAMS",
+    "EXCEPINFO",
+    "SAFEARRAY",
+    "SAFEARRAYBOUND",
+];
+
+/// Interfaces declared by the IDL files of the Windows SDK in [`SDK_IMPORTS`], which
+/// are not exported by `com::interfaces` and have to be provided by the user
+const SDK_INTERFACES: &[&str] = &[
+    "IAdviseSink",
+    "IBindCtx",
+    "IClassFactory2",
+    "IConnectionPoint",
+    "IConnectionPointContainer",
+    "IDataObject",
+    "IDropSource",
+    "IDropTarget",
+    "IEnumConnectionPoints",
+    "IEnumConnections",
+    "IEnumFORMATETC",
+    "IEnumMoniker",
+    "IEnumString",
+    "IEnumUnknown",
+    "IEnumVARIANT",
+    "IErrorInfo",
+    "IMalloc",
+    "IMarshal",
+    "IMoniker",
+    "IOleClientSite",
+    "IOleObject",
+    "IOleWindow",
+    "IPersist",
+    "IPersistFile",
+    "IPersistStream",
+    "IPersistStreamInit",
+    "IPropertySetStorage",
+    "IPropertyStorage",
+    "IRecordInfo",
+    "IRunningObjectTable",
+    "ISequentialStream",
+    "IServiceProvider",
+    "IStorage",
+    "IStream",
+    "ISupportErrorInfo",
+    "ITypeComp",
+    "ITypeInfo",
+    "ITypeLib",
+];
+
+/// IDL files of the Windows SDK, whose declarations are provided by `com` or have to
+/// be provided by the user
+const SDK_IMPORTS: &[&str] = &[
+    "unknwn.idl",
+    "wtypes.idl",
+    "wtypesbase.idl",
+    "objidl.idl",
+    "objidlbase.idl",
+    "oaidl.idl",
+    "ocidl.idl",
+    "oleidl.idl",
+    "propidl.idl",
+    "servprov.idl",
+];
+
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Whether `file` is an IDL file of the Windows SDK
+pub fn is_sdk_import(file: &str) -> bool {
+    SDK_IMPORTS.contains(&file.to_lowercase().as_str())
+}
+
+/// Generate the Rust source for `idl`, starting with a comment made of `origin`
+///
+/// `imported` holds the names of the interfaces declared by the files `idl` imports.
+pub fn generate(idl: &Idl, imported: &HashSet<String>, origin: &str) -> Result<String, Error> {
+    let mut generator = Generator {
+        interfaces: idl
+            .interface_names()
+            .map(str::to_owned)
+            .chain(imported.iter().cloned())
+            .collect(),
+        non_copy: HashSet::new(),
+        com_interfaces: BTreeSet::new(),
+        sys: BTreeSet::new(),
+        c_void: false,
+    };
+
+    let mut body = String::new();
+    let mut modules = Vec::new();
+    for item in &idl.items {
+        match item {
+            Item::Import(file) => {
+                if !is_sdk_import(file) {
+                    modules.push(module_name(file));
+                }
+            }
+            Item::Struct(s) => generator.structure(&mut body, s),
+            Item::Enum(e) => generator.enumeration(&mut body, e),
+            Item::Typedef(name, ty) => {
+                let ty = generator.ty(ty);
+                writeln!(body, "\n{}pub type {} = {};", allow_type(name), name, ty).unwrap();
+            }
+            Item::Const(name, ty, value) => {
+                let ty = generator.ty(ty);
+                let value = expr(value, ty == "i32");
+                writeln!(
+                    body,
+                    "\n{}pub const {}: {} = {};",
+                    allow_const(name),
+                    name,
+                    ty,
+                    value
+                )
+                .unwrap();
+            }
+            Item::Coclass(name, uuid) => {
+                writeln!(
+                    body,
+                    "\n{}pub const CLSID_{}: com::CLSID = com::guid!(\"{}\");",
+                    allow_const(name),
+                    name,
+                    uuid
+                )
+                .unwrap();
+            }
+            Item::Interface(_) | Item::ForwardInterface(_) => {}
+        }
+    }
+
+    let interfaces = idl
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Interface(i) if i.name != "IUnknown" => Some(i),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if !interfaces.is_empty() {
+        body.push_str("\ninterfaces! {\n");
+        for (index, interface) in interfaces.into_iter().enumerate() {
+            if index > 0 {
+                body.push('\n');
+            }
+            generator.interface(&mut body, interface)?;
+        }
+        body.push_str("}\n");
+    }
+
+    let mut out = String::new();
+    writeln!(out, "// {}. Do not edit.\n", origin).unwrap();
+    let mut uses = Vec::new();
+    if body.contains("interfaces! {") {
+        uses.push("use com::interfaces;".to_owned());
+    }
+    if !generator.com_interfaces.is_empty() {
+        uses.push(use_list("com::interfaces", &generator.com_interfaces));
+    }
+    if !generator.sys.is_empty() {
+        uses.push(use_list("com::sys", &generator.sys));
+    }
+    if generator.c_void {
+        uses.push("use core::ffi::c_void;".to_owned());
+    }
+    for module in modules {
+        uses.push(format!("use super::{}::*;", module));
+    }
+    for line in uses {
+        writeln!(out, "{}", line).unwrap();
+    }
+    out.push_str(&body);
+    Ok(out)
+}
+
+struct Generator {
+    /// The names of the interfaces declared, forward-declared or imported by the IDL
+    /// file
+    interfaces: HashSet<String>,
+    /// The structs which contain interface pointers and are therefore not `Copy`
+    non_copy: HashSet<String>,
+    /// The `com::interfaces` and `com::sys` items used by the generated code
+    com_interfaces: BTreeSet<String>,
+    sys: BTreeSet<String>,
+    c_void: bool,
+}
+
+impl Generator {
+    fn is_interface(&self, name: &str) -> bool {
+        self.interfaces.contains(name)
+            || COM_INTERFACES.contains(&name)
+            || SDK_INTERFACES.contains(&name)
+    }
+
+    fn use_name(&mut self, name: &str) -> String {
+        if COM_INTERFACES.contains(&name) {
+            self.com_interfaces.insert(name.to_owned());
+        } else if SYS_TYPES.contains(&name) {
+            self.sys.insert(name.to_owned());
+        }
+        name.to_owned()
+    }
+
+    /// The Rust type of a C type without pointers
+    fn base_type(&mut self, name: &str) -> String {
+        let primitive = match name {
+            "boolean" | "BOOLEAN" | "byte" | "BYTE" | "UCHAR" | "unsigned char" | "UINT8" => "u8",
+            "char" | "CHAR" | "small" | "INT8" => "i8",
+            "short" | "SHORT" | "INT16" => "i16",
+            "unsigned short" | "USHORT" | "WORD" | "UINT16" | "WCHAR" | "wchar_t" | "OLECHAR" => {
+                "u16"
+            }
+            "int" | "INT" | "long" | "LONG" | "INT32" | "LONG32" => "i32",
+            "unsigned int" | "unsigned long" | "UINT" | "ULONG" | "DWORD" | "UINT32"
+            | "ULONG32" | "DWORD32" | "LCID" => "u32",
+            "hyper" | "__int64" | "long long" | "LONGLONG" | "INT64" | "LONG64" => "i64",
+            "unsigned hyper" | "unsigned __int64" | "unsigned long long" | "ULONGLONG"
+            | "UINT64" | "ULONG64" | "DWORD64" => "u64",
+            "float" | "FLOAT" => "f32",
+            "double" | "DOUBLE" | "DATE" => "f64",
+            "SIZE_T" | "UINT_PTR" | "ULONG_PTR" | "DWORD_PTR" => "usize",
+            "SSIZE_T" | "INT_PTR" | "LONG_PTR" => "isize",
+            "LPWSTR" | "LPOLESTR" => "*mut u16",
+            "LPCWSTR" | "LPCOLESTR" => "*const u16",
+            "LPSTR" => "*mut u8",
+            "LPCSTR" => "*const u8",
+            _ => "",
+        };
+        if !primitive.is_empty() {
+            return primitive.to_owned();
+        }
+        match name {
+            "void" => {
+                self.c_void = true;
+                "c_void".to_owned()
+            }
+            "LPVOID" | "PVOID" => {
+                self.c_void = true;
+                "*mut c_void".to_owned()
+            }
+            "LPCVOID" => {
+                self.c_void = true;
+                "*const c_void".to_owned()
+            }
+            "IID" | "CLSID" => self.use_name("GUID"),
+            "REFIID" | "REFGUID" | "REFCLSID" => format!("*const {}", self.use_name("GUID")),
+            "VARIANTARG" => self.use_name("VARIANT"),
+            "MEMBERID" => self.use_name("DISPID"),
+            _ => self.use_name(name),
+        }
+    }
+
+    /// The Rust type of a C type
+    fn ty(&mut self, ty: &Type) -> String {
+        let (mut rust, first) = if self.is_interface(&ty.name) && !ty.pointers.is_empty() {
+            (format!("Option<{}>", self.use_name(&ty.name)), 1)
+        } else {
+            (self.base_type(&ty.name), 0)
+        };
+        for level in first..ty.pointers.len() {
+            let kind = if ty.points_to_const(level) {
+                "const"
+            } else {
+                "mut"
+            };
+            rust = format!("*{} {}", kind, rust);
+        }
+        rust
+    }
+
+    fn interface(&mut self, out: &mut String, interface: &Interface) -> Result<(), Error> {
+        let parent = match &interface.parent {
+            Some(parent) => self.use_name(parent),
+            None => {
+                return Err(Error::new(
+                    interface.line,
+                    format!(
+                        "interface `{}` does not inherit from another interface",
+                        interface.name
+                    ),
+                ))
+            }
+        };
+        writeln!(out, "    #[uuid(\"{}\")]", interface.uuid.to_uppercase()).unwrap();
+        write!(
+            out,
+            "    pub unsafe interface {}: {} {{",
+            interface.name, parent
+        )
+        .unwrap();
+        if interface.methods.is_empty() {
+            out.push_str("}\n");
+            return Ok(());
+        }
+        out.push('\n');
+        for method in &interface.methods {
+            self.method(out, method);
+        }
+        out.push_str("    }\n");
+        Ok(())
+    }
+
+    fn method(&mut self, out: &mut String, method: &Method) {
+        let returns_void = method.ret.name == "void" && method.ret.pointers.is_empty();
+        let ret = if returns_void {
+            String::new()
+        } else {
+            format!(" -> {}", self.ty(&method.ret))
+        };
+        let mut params = vec!["&self".to_owned()];
+        for (index, param) in method.params.iter().enumerate() {
+            let name = match &param.name {
+                Some(name) => identifier(name),
+                None => format!("arg{}", index),
+            };
+            let ty = self.ty(&param.ty);
+            // `interfaces!` only supports `[retval]` on the last parameter of methods
+            // returning `HRESULT`
+            let is_retval = param.retval
+                && index == method.params.len() - 1
+                && ret == " -> HRESULT"
+                && ty.starts_with("*mut ");
+            let attribute = if is_retval {
+                "#[retval] ".to_owned()
+            } else {
+                match size_is(method, param, &ty) {
+                    Some(len) => format!("#[size_is({})] ", identifier(len)),
+                    None => String::new(),
+                }
+            };
+            params.push(format!("{}{}: {}", attribute, name, ty));
+        }
+
+        let single_line = format!(
+            "        pub fn {}({}){};",
+            method.name,
+            params.join(", "),
+            ret
+        );
+        if single_line.len() <= 100 {
+            writeln!(out, "{}", single_line).unwrap();
+        } else {
+            writeln!(out, "        pub fn {}(", method.name).unwrap();
+            for param in params {
+                writeln!(out, "            {},", param).unwrap();
+            }
+            writeln!(out, "        ){};", ret).unwrap();
+        }
+    }
+
+    fn structure(&mut self, out: &mut String, s: &Struct) {
+        let mut fields = Vec::new();
+        let mut is_copy = true;
+        for field in &s.fields {
+            let is_interface = self.is_interface(&field.ty.name) && field.ty.pointers.len() == 1;
+            let mut ty = self.ty(&field.ty);
+            if is_interface || self.non_copy.contains(&field.ty.name) {
+                is_copy = false;
+                if is_interface || s.is_union {
+                    // Interface pointers in C structs are not owned
+                    ty = format!("::core::mem::ManuallyDrop<{}>", ty);
+                }
+            }
+            for dim in field.dims.iter().rev() {
+                ty = format!("[{}; {}]", ty, length(dim));
+            }
+            fields.push((identifier(&field.name), ty));
+        }
+        if !is_copy {
+            self.non_copy.insert(s.name.clone());
+        }
+
+        out.push('\n');
+        out.push_str("#[repr(C)]\n");
+        if is_copy {
+            out.push_str("#[derive(Clone, Copy)]\n");
+        }
+        out.push_str(allow_type(&s.name));
+        if fields
+            .iter()
+            .any(|(name, _)| name.chars().any(char::is_uppercase))
+        {
+            out.push_str("#[allow(non_snake_case)]\n");
+        }
+        let keyword = if s.is_union { "union" } else { "struct" };
+        writeln!(out, "pub {} {} {{", keyword, s.name).unwrap();
+        for (name, ty) in fields {
+            writeln!(out, "    pub {}: {},", name, ty).unwrap();
+        }
+        out.push_str("}\n");
+    }
+
+    fn enumeration(&mut self, out: &mut String, e: &Enum) {
+        writeln!(out, "\n{}pub type {} = i32;", allow_type(&e.name), e.name).unwrap();
+        let mut previous: Option<&str> = None;
+        for (name, value) in &e.variants {
+            let value = match (value, previous) {
+                (Some(value), _) => expr(value, true),
+                (None, Some(previous)) => format!("{} + 1", previous),
+                (None, None) => "0".to_owned(),
+            };
+            writeln!(
+                out,
+                "{}pub const {}: {} = {};",
+                allow_const(name),
+                name,
+                e.name,
+                value
+            )
+            .unwrap();
+            previous = Some(name);
+        }
+    }
+}
+
+fn use_list(path: &str, names: &BTreeSet<String>) -> String {
+    if names.len() == 1 {
+        format!("use {}::{};", path, names.iter().next().unwrap())
+    } else {
+        let names = names.iter().cloned().collect::<Vec<_>>();
+        format!("use {}::{{{}}};", path, names.join(", "))
+    }
+}
+
+/// The name of the Rust module generated for an imported IDL file
+fn module_name(file: &str) -> String {
+    let stem = file.rsplit(['/', '\\']).next().unwrap();
+    let stem = stem.strip_suffix(".idl").unwrap_or(stem);
+    stem.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The length parameter of a `size_is` array, which `interfaces!` supports for
+/// pointers to single values and lengths passed by value
+fn size_is<'m>(method: &'m Method, param: &Param, ty: &str) -> Option<&'m str> {
+    let len = param.size_is.as_deref()?;
+    if param.ty.pointers.len() != 1 || !ty.starts_with('*') {
+        return None;
+    }
+    let len_param = method
+        .params
+        .iter()
+        .find(|p| p.name.as_deref() == Some(len))?;
+    if !len_param.ty.pointers.is_empty() {
+        return None;
+    }
+    len_param.name.as_deref()
+}
+
+/// A Rust identifier for a C identifier, which may be a Rust keyword
+fn identifier(name: &str) -> String {
+    if RUST_KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+fn allow_type(name: &str) -> &'static str {
+    if name.contains('_') || name.starts_with(char::is_lowercase) {
+        "#[allow(non_camel_case_types)]\n"
+    } else {
+        ""
+    }
+}
+
+fn allow_const(name: &str) -> &'static str {
+    if name.chars().any(char::is_lowercase) {
+        "#[allow(non_upper_case_globals)]\n"
+    } else {
+        ""
+    }
+}
+
+/// The length of an array dimension
+fn length(dim: &Expr) -> String {
+    match dim.0.as_slice() {
+        [TokenKind::Number(n)] => number(n, false),
+        _ => format!("({}) as usize", expr(dim, false)),
+    }
+}
+
+/// A Rust expression for a C constant expression
+///
+/// When `is_i32` is set, number literals which only fit in a `u32` are reinterpreted,
+/// as C does for enum values such as `0x80000000`.
+fn expr(expr: &Expr, is_i32: bool) -> String {
+    if let [TokenKind::Number(n)] = expr.0.as_slice() {
+        return number(n, is_i32);
+    }
+    let mut out = String::new();
+    // Whether the next token follows the previous one without a space
+    let mut joined = true;
+    // Whether the previous token ends an operand, making a following `-` binary
+    let mut after_operand = false;
+    let mut tokens = expr.0.iter().peekable();
+    while let Some(token) = tokens.next() {
+        let text = match token {
+            TokenKind::Ident(i) => i.clone(),
+            TokenKind::Number(n) => match number(n, is_i32) {
+                n if n.contains(" as ") => format!("({})", n),
+                n => n,
+            },
+            TokenKind::Str(s) => format!("{:?}", s),
+            TokenKind::Punct('~') => "!".to_owned(),
+            TokenKind::Punct(c @ '<') | TokenKind::Punct(c @ '>')
+                if tokens.peek() == Some(&&TokenKind::Punct(*c)) =>
+            {
+                tokens.next();
+                format!("{}{}", c, c)
+            }
+            TokenKind::Punct(c) => c.to_string(),
+        };
+        let is_unary =
+            matches!(token, TokenKind::Punct('-') | TokenKind::Punct('~')) && !after_operand;
+        if !joined && *token != TokenKind::Punct(')') {
+            out.push(' ');
+        }
+        out.push_str(&text);
+        joined = is_unary || *token == TokenKind::Punct('(');
+        after_operand = !matches!(token, TokenKind::Punct(c) if *c != ')');
+    }
+    out
+}
+
+/// A number literal, reinterpreted as an `i32` if it only fits in a `u32` and
+/// `is_i32` is set
+fn number(n: &str, is_i32: bool) -> String {
+    // C octal literals such as `0755` would be decimal in Rust
+    let literal = match octal_digits(n) {
+        Some(digits) => format!("0o{}", digits),
+        None => n.to_owned(),
+    };
+    match parse_number(n) {
+        Some(value) if is_i32 && value > i32::MAX as u64 && value <= u32::MAX as u64 => {
+            format!("{}u32 as i32", literal)
+        }
+        _ => literal,
+    }
+}
+
+/// The digits of a C octal literal, which starts with a `0`
+fn octal_digits(n: &str) -> Option<&str> {
+    n.strip_prefix('0')
+        .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn parse_number(n: &str) -> Option<u64> {
+    if let Some(digits) = octal_digits(n) {
+        return u64::from_str_radix(digits, 8).ok();
+    }
+    match n.strip_prefix("0x").or_else(|| n.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => n.parse().ok(),
+    }
+}
@@ -0,0 +1,385 @@
+use super::{convert, convert_with_imports};
+use crate::test_utils::assert_golden;
+
+fn convert_ok(idl: &str) -> String {
+    match convert(idl) {
+        Ok(rust) => rust,
+        Err(e) => panic!("Expected IDL to convert.\nIDL: {}\nError: {}", idl, e),
+    }
+}
+
+fn convert_err(idl: &str, expected_error: &str) {
+    match convert(idl) {
+        Ok(rust) => panic!("Expected IDL to fail to convert.\nOutput: {}", rust),
+        Err(e) => {
+            let e_string = e.to_string();
+            if !e_string.contains(expected_error) {
+                panic!(
+                    "Did not find expected error string.\nActual error: {:?}\nExpected error: {:?}",
+                    e_string, expected_error
+                );
+            }
+        }
+    }
+}
+
+/// Check that the output is accepted by `interfaces!` and that the C header
+/// generated from it declares `vtable_entries`, completing the round trip from IDL
+/// back to C
+fn assert_round_trip(rust: &str, vtable_entries: &[&str]) {
+    let interfaces = crate::header::parse_source(rust)
+        .unwrap_or_else(|e| panic!("Output is not accepted by interfaces!: {}\n{}", e, rust));
+    let header = crate::header::generate(&interfaces).unwrap();
+    for entry in vtable_entries {
+        assert!(
+            header.contains(entry),
+            "Header does not contain {:?}:\n{}",
+            entry,
+            header
+        );
+    }
+}
+
+#[test]
+fn clock() {
+    let idl = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/idl/clock.idl"))
+        .unwrap();
+    let rust = convert_ok(&idl);
+    assert_golden(&rust, "tests/idl/clock.rs");
+    assert_round_trip(
+        &rust,
+        &[
+            "HRESULT (STDMETHODCALLTYPE *CreateDevice)(ID2D1Factory *This, IDXGIDevice *dxgiDevice, ID2D1Device **d2dDevice);",
+            "void (STDMETHODCALLTYPE *GetDesktopDpi)(ID2D1Factory *This, float *dpiX, float *dpiY);",
+            "HRESULT (STDMETHODCALLTYPE *CreateStrokeStyle)(ID2D1Factory *This, const D2D1_COLOR_F *color, const float *dashes, uint32_t count);",
+            "uint64_t (STDMETHODCALLTYPE *GetMaximumTextureMemory)(ID2D1Device *This);",
+            "HRESULT (STDMETHODCALLTYPE *Invoke)(IClock *This,",
+            "HRESULT (STDMETHODCALLTYPE *GetTime)(IClock *This, double *time);",
+            "HRESULT (STDMETHODCALLTYPE *PutTime)(IClock *This, double time);",
+            "HRESULT (STDMETHODCALLTYPE *Name)(IClock *This, const GUID *riid, const uint16_t *prefix, BSTR *name);",
+        ],
+    );
+}
+
+#[test]
+fn interface() {
+    let rust = convert_ok(
+        r#"
+        import "unknwn.idl";
+
+        [object, uuid(eff8970e-c50f-45e0-9284-291ce5a6f771), pointer_default(unique)]
+        interface IAnimal : IUnknown
+        {
+            HRESULT Eat([in] IUnknown *food);
+            HRESULT Name([out, retval] BSTR *name);
+            ULONG Legs(void);
+        }
+        "#,
+    );
+    assert_eq!(
+        rust,
+        r#"// Generated from IDL by com-idl. Do not edit.
+
+use com::interfaces;
+use com::interfaces::IUnknown;
+use com::sys::{BSTR, HRESULT};
+
+interfaces! {
+    #[uuid("EFF8970E-C50F-45E0-9284-291CE5A6F771")]
+    pub unsafe interface IAnimal: IUnknown {
+        pub fn Eat(&self, food: Option<IUnknown>) -> HRESULT;
+        pub fn Name(&self, #[retval] name: *mut BSTR) -> HRESULT;
+        pub fn Legs(&self) -> u32;
+    }
+}
+"#
+    );
+    assert_round_trip(
+        &rust,
+        &["uint32_t (STDMETHODCALLTYPE *Legs)(IAnimal *This);"],
+    );
+}
+
+#[test]
+fn retval_requires_last_hresult_method() {
+    let rust = convert_ok(
+        r#"
+        [object, uuid(eff8970e-c50f-45e0-9284-291ce5a6f771)]
+        interface IFoo : IUnknown
+        {
+            HRESULT NotLast([out, retval] LONG *value, [in] LONG flags);
+            LONG NotHresult([out, retval] LONG *value);
+        }
+        "#,
+    );
+    assert!(rust.contains("pub fn NotLast(&self, value: *mut i32, flags: i32) -> HRESULT;"));
+    assert!(rust.contains("pub fn NotHresult(&self, value: *mut i32) -> i32;"));
+}
+
+#[test]
+fn size_is() {
+    let rust = convert_ok(
+        r#"
+        [object, uuid(eff8970e-c50f-45e0-9284-291ce5a6f771)]
+        interface IFoo : IUnknown
+        {
+            HRESULT Write([in] ULONG count, [in, size_is(count)] const BYTE *bytes);
+            HRESULT Read([out, size_is(count)] LONG *values, [in] ULONG count);
+            HRESULT Indirect([in] ULONG *count, [in, size_is(*count)] const BYTE *bytes);
+        }
+        "#,
+    );
+    assert!(rust.contains(
+        "pub fn Write(&self, count: u32, #[size_is(count)] bytes: *const u8) -> HRESULT;"
+    ));
+    assert!(rust.contains(
+        "pub fn Read(&self, #[size_is(count)] values: *mut i32, count: u32) -> HRESULT;"
+    ));
+    assert!(rust.contains("pub fn Indirect(&self, count: *mut u32, bytes: *const u8) -> HRESULT;"));
+}
+
+#[test]
+fn pointers() {
+    let rust = convert_ok(
+        r#"
+        [object, uuid(eff8970e-c50f-45e0-9284-291ce5a6f771)]
+        interface IFoo : IUnknown
+        {
+            HRESULT Pointers(
+                [in] const void *a,
+                [out] void **b,
+                [in] const WCHAR * const *c,
+                [out] IUnknown **d,
+                [in] IUnknown * const *e,
+                [in] REFIID riid,
+                [in] LPCWSTR name,
+                [out] SAFEARRAY(BSTR) *names);
+        }
+        "#,
+    );
+    assert!(rust.contains("a: *const c_void"));
+    assert!(rust.contains("b: *mut *mut c_void"));
+    assert!(rust.contains("c: *const *const u16"));
+    assert!(rust.contains("d: *mut Option<IUnknown>"));
+    assert!(rust.contains("e: *const Option<IUnknown>"));
+    assert!(rust.contains("riid: *const GUID"));
+    assert!(rust.contains("name: *const u16"));
+    assert!(rust.contains("names: *mut *mut SAFEARRAY"));
+    assert!(rust.contains("use core::ffi::c_void;"));
+}
+
+#[test]
+fn c_types() {
+    let rust = convert_ok(
+        r#"
+        typedef struct FOO
+        {
+            unsigned char a;
+            signed char b;
+            short c;
+            unsigned short d;
+            long int e;
+            unsigned f;
+            unsigned long g;
+            hyper h;
+            unsigned __int64 i;
+            boolean j;
+            double k;
+            SIZE_T l;
+            VARIANT_BOOL m;
+            struct BAR *n;
+        } FOO;
+        "#,
+    );
+    for field in &[
+        "a: u8",
+        "b: i8",
+        "c: i16",
+        "d: u16",
+        "e: i32",
+        "f: u32",
+        "g: u32",
+        "h: i64",
+        "i: u64",
+        "j: u8",
+        "k: f64",
+        "l: usize",
+        "m: VARIANT_BOOL",
+        "n: *mut BAR",
+    ] {
+        assert!(rust.contains(field), "missing {}:\n{}", field, rust);
+    }
+}
+
+#[test]
+fn structs() {
+    let rust = convert_ok(
+        r#"
+        typedef struct tagPOINT { LONG x, y; } POINT, *LPPOINT;
+        typedef union VALUE { LONG i; IUnknown *unknown; } VALUE;
+        struct HOLDER { IUnknown *unknown; DWORD cookie; };
+        typedef struct NESTED { struct HOLDER holder; } NESTED;
+        "#,
+    );
+    assert!(rust.contains(
+        "#[repr(C)]\n#[derive(Clone, Copy)]\npub struct POINT {\n    pub x: i32,\n    pub y: i32,\n}"
+    ));
+    assert!(rust.contains("#[allow(non_camel_case_types)]\npub type tagPOINT = POINT;"));
+    assert!(rust.contains("pub type LPPOINT = *mut POINT;"));
+    // Interface pointers in structs are not owned
+    assert!(rust.contains("pub union VALUE {\n    pub i: i32,\n    pub unknown: ::core::mem::ManuallyDrop<Option<IUnknown>>,\n}"));
+    assert!(rust.contains("#[repr(C)]\npub struct HOLDER {"));
+    assert!(rust.contains("#[repr(C)]\npub struct NESTED {\n    pub holder: HOLDER,\n}"));
+}
+
+#[test]
+fn enums() {
+    let rust = convert_ok(
+        r#"
+        typedef [v1_enum] enum COLOR
+        {
+            COLOR_RED,
+            COLOR_GREEN,
+            COLOR_BLUE = 0x10,
+            COLOR_ALPHA,
+            COLOR_MASK = ~(COLOR_RED | 0x80000000),
+            COLOR_MIN = -2,
+            COLOR_ALL = 0xFFFFFFFF
+        } COLOR;
+        "#,
+    );
+    assert!(rust.contains(
+        "pub type COLOR = i32;
+pub const COLOR_RED: COLOR = 0;
+pub const COLOR_GREEN: COLOR = COLOR_RED + 1;
+pub const COLOR_BLUE: COLOR = 0x10;
+pub const COLOR_ALPHA: COLOR = COLOR_BLUE + 1;
+pub const COLOR_MASK: COLOR = !(COLOR_RED | (0x80000000u32 as i32));
+pub const COLOR_MIN: COLOR = -2;
+pub const COLOR_ALL: COLOR = 0xFFFFFFFFu32 as i32;"
+    ));
+}
+
+#[test]
+fn imports() {
+    let rust = convert_ok(
+        r#"
+        import "oaidl.idl", "ocidl.idl";
+        import "shared/types.idl";
+        const DWORD SIZE = 4;
+        "#,
+    );
+    assert!(rust.contains("use super::types::*;"));
+    assert!(!rust.contains("oaidl"));
+    assert!(rust.contains("pub const SIZE: u32 = 4;"));
+}
+
+#[test]
+fn imported_interfaces() {
+    let idl = r#"
+        import "objidl.idl";
+        import "animals.idl";
+        [object, uuid(0c3b5d7e-9f2a-4e61-8d4c-1b7a6e5f3d20)]
+        interface IZoo : IUnknown
+        {
+            HRESULT Add([in] IAnimal *animal, [in] IBird *bird);
+            HRESULT Save([in] IStream *stream);
+            HRESULT Feed([in] IFood *food);
+        }
+        "#;
+    let rust = convert_with_imports(idl, |file| match file {
+        "animals.idl" => Some(
+            r#"
+            import "birds.idl";
+            interface IAnimal;
+            "#
+            .to_owned(),
+        ),
+        "birds.idl" => Some("interface IBird;".to_owned()),
+        _ => None,
+    })
+    .unwrap();
+    assert!(rust
+        .contains("pub fn Add(&self, animal: Option<IAnimal>, bird: Option<IBird>) -> HRESULT;"));
+    assert!(rust.contains("pub fn Save(&self, stream: Option<IStream>) -> HRESULT;"));
+    // Names are not guessed to be interfaces
+    assert!(rust.contains("pub fn Feed(&self, food: *mut IFood) -> HRESULT;"));
+    let rust = convert_ok(idl);
+    assert!(rust.contains("pub fn Add(&self, animal: *mut IAnimal, bird: *mut IBird) -> HRESULT;"));
+
+    let e = convert_with_imports(idl, |_| Some("interface".to_owned())).unwrap_err();
+    assert!(e.to_string().contains("in animals.idl: "), "{}", e);
+}
+
+#[test]
+fn octal_literals() {
+    let rust = convert_ok(
+        r#"
+        const DWORD MODE = 0755;
+        const DWORD ZERO = 0;
+        typedef enum FLAGS { FLAGS_HIGH = 020000000000, FLAGS_LOW = FLAGS_HIGH | 017 } FLAGS;
+        typedef struct BUFFER { BYTE data[010]; } BUFFER;
+        "#,
+    );
+    assert!(rust.contains("pub const MODE: u32 = 0o755;"));
+    assert!(rust.contains("pub const ZERO: u32 = 0;"));
+    assert!(rust.contains("pub const FLAGS_HIGH: FLAGS = 0o20000000000u32 as i32;"));
+    assert!(rust.contains("pub const FLAGS_LOW: FLAGS = FLAGS_HIGH | 0o17;"));
+    assert!(rust.contains("pub data: [u8; 0o10],"));
+}
+
+#[test]
+fn rust_keywords() {
+    let rust = convert_ok(
+        r#"
+        [object, uuid(eff8970e-c50f-45e0-9284-291ce5a6f771)]
+        interface IFoo : IUnknown
+        {
+            HRESULT Foo([in] LONG type, [in] LONG ref, [in] LONG);
+        }
+        "#,
+    );
+    assert!(rust.contains("pub fn Foo(&self, type_: i32, ref_: i32, arg2: i32) -> HRESULT;"));
+}
+
+#[test]
+fn ignored_declarations() {
+    let rust = convert_ok(
+        r##"
+        #include "foo.h"
+        #define FOO \
+            1
+        cpp_quote("#define BAR 2")
+        midl_pragma warning(disable: 2111)
+        /* interface IBar : IUnknown { HRESULT Bar(); } */
+        [uuid(eff8970e-c50f-45e0-9284-291ce5a6f771)]
+        dispinterface DFoo { properties: methods: };
+        "##,
+    );
+    assert!(!rust.contains("interfaces!"));
+}
+
+#[test]
+fn err_missing_uuid() {
+    convert_err(
+        "[object] interface IFoo : IUnknown {}",
+        "line 1: interface `IFoo` has no uuid attribute",
+    );
+}
+
+#[test]
+fn err_no_parent() {
+    convert_err(
+        "\n[uuid(eff8970e-c50f-45e0-9284-291ce5a6f771)]\ninterface IFoo {}",
+        "line 3: interface `IFoo` does not inherit from another interface",
+    );
+}
+
+#[test]
+fn err_syntax() {
+    convert_err(
+        "[object, uuid(eff8970e-c50f-45e0-9284-291ce5a6f771)]\ninterface IFoo : IUnknown\n{\n    HRESULT Foo(LONG a\n}",
+        "line 5: expected `)`",
+    );
+    convert_err("/* unterminated", "line 1: unterminated comment");
+}
@@ -21,6 +21,25 @@ impl IID {
         Ok(Self { parts })
     }
 
+    /// The IID in its registry format, e.g. `00000000-0000-0000-C000-000000000046`
+    pub fn to_registry_string(&self) -> String {
+        self.parts.join("-").to_uppercase()
+    }
+
+    /// The fields of the IID as C hex literals, in the order taken by `DEFINE_GUID`
+    pub fn to_c_fields(&self) -> Vec<String> {
+        let mut fields = vec![
+            format!("0x{}", self.parts[0].to_lowercase()),
+            format!("0x{}", self.parts[1].to_lowercase()),
+            format!("0x{}", self.parts[2].to_lowercase()),
+        ];
+        let bytes = self.parts[3].to_lowercase() + &self.parts[4].to_lowercase();
+        for i in (0..bytes.len()).step_by(2) {
+            fields.push(format!("0x{}", &bytes[i..i + 2]));
+        }
+        fields
+    }
+
     pub fn to_tokens(&self, interface_ident: &Ident) -> HelperTokenStream {
         let iid_ident = ident(interface_ident);
         let data1 = hex_lit(&self.parts[0]);
@@ -11,7 +11,13 @@ pub struct Interface {
     pub name: Ident,
     pub parent: Option<Path>,
     pub methods: Vec<InterfaceMethod>,
-    docs: Vec<Attribute>,
+    pub docs: Vec<Attribute>,
+    /// Whether the interface is marked `#[remote]`, so that it can be called from
+    /// other processes
+    pub remote: bool,
+    /// The `#[mock]` attribute of the interface, if a mock class is generated for
+    /// it
+    pub mock: Option<proc_macro2::Span>,
 }
 
 impl Interface {
@@ -45,6 +51,20 @@ impl Interface {
         let interface_name = &self.name;
 
         let methods = self.methods.iter().map(|m| m.to_tokens());
+        // `class!` refers to these when a method implementation returns a `Result`,
+        // which only implements methods with a `#[retval]` parameter
+        let retval_markers = self
+            .methods
+            .iter()
+            .filter(|m| m.retval().is_some())
+            .map(|m| {
+                let marker = crate::utils::retval_marker_ident(&m.name);
+                quote! {
+                    #[doc(hidden)]
+                    #[allow(non_upper_case_globals)]
+                    pub const #marker: () = ();
+                }
+            });
 
         let deref = self.deref_impl();
         let drop = self.drop_impl();
@@ -53,6 +73,7 @@ impl Interface {
         quote! {
             impl #interface_name {
                 #(#methods)*
+                #(#retval_markers)*
             }
             #deref
             #drop
@@ -112,11 +133,32 @@ impl syn::parse::Parse for Interface {
         let attributes = input.call(Attribute::parse_outer)?;
         let mut iid = None;
         let mut docs = Vec::new();
+        let mut safe = false;
+        let mut remote = false;
+        let mut mock = None;
         for attr in attributes.into_iter() {
             let path = &attr.path;
             let tokens = &attr.tokens;
             if path.is_ident("doc") {
                 docs.push(attr);
+            } else if is_safe_attribute(&attr)? {
+                safe = true;
+            } else if path.is_ident("remote") {
+                if !attr.tokens.is_empty() {
+                    return Err(syn::Error::new(
+                        attr.tokens.span(),
+                        "#[remote] does not take arguments",
+                    ));
+                }
+                remote = true;
+            } else if path.is_ident("mock") {
+                if !attr.tokens.is_empty() {
+                    return Err(syn::Error::new(
+                        attr.tokens.span(),
+                        "#[mock] does not take arguments",
+                    ));
+                }
+                mock = Some(attr.path.span());
             } else if path.is_ident("uuid") {
                 let iid_str: ParenthsizedStr = syn::parse2(tokens.clone())?;
 
@@ -156,7 +198,15 @@ impl syn::parse::Parse for Interface {
         syn::braced!(content in input);
         let mut methods = Vec::new();
         while !content.is_empty() {
-            methods.push(content.parse::<InterfaceMethod>()?);
+            let mut method = content.parse::<InterfaceMethod>()?;
+            if safe && !method.safe {
+                method.check_safe()?;
+                method.safe = true;
+            }
+            if remote {
+                super::remote::check(&method)?;
+            }
+            methods.push(method);
         }
         Ok(Self {
             iid,
@@ -165,6 +215,8 @@ impl syn::parse::Parse for Interface {
             name,
             parent,
             docs,
+            remote,
+            mock,
         })
     }
 }
@@ -195,12 +247,21 @@ pub struct InterfaceMethod {
     pub args: Vec<InterfaceMethodArg>,
     pub ret: syn::ReturnType,
     pub docs: Vec<syn::Attribute>,
+    /// Whether the generated wrapper is a safe function, because the method or
+    /// its interface is marked `#[safe]`
+    pub safe: bool,
 }
 
 pub struct InterfaceMethodArg {
     pub ty: Box<syn::Type>,
     pub pat: Box<syn::Pat>,
     pub pass_through: bool,
+    /// Whether this is an `[out, retval]` parameter which is returned from the
+    /// generated wrapper instead of being passed in by the caller
+    pub retval: bool,
+    /// The parameter holding the number of elements of an array passed as a pointer,
+    /// from a `#[size_is(len)]` attribute
+    pub size_is: Option<Ident>,
 }
 
 macro_rules! bail {
@@ -227,9 +288,14 @@ macro_rules! expected_token {
 
 impl syn::parse::Parse for InterfaceMethod {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let docs = input.call(Attribute::parse_outer)?;
+        let mut docs = input.call(Attribute::parse_outer)?;
         let visibility = input.parse::<syn::Visibility>()?;
         let method = input.parse::<syn::TraitItemMethod>()?;
+        let mut safe = false;
+        for attr in &docs {
+            safe |= is_safe_attribute(attr)?;
+        }
+        docs.retain(|a| !a.path.is_ident("safe"));
         unexpected_token!(docs.iter().find(|a| !a.path.is_ident("doc")), "attribute");
         unexpected_token!(method.default, "default method implementation");
         let sig = method.sig;
@@ -257,27 +323,220 @@ impl syn::parse::Parse for InterfaceMethod {
                     .fuse();
                 let pass_through = filter.next().is_some();
 
+                unexpected_token!(filter.next(), "function attribute");
+
+                let mut filter = p.attrs.iter().filter(|a| a.path.is_ident("retval")).fuse();
+                let retval = filter.next().is_some();
+
+                unexpected_token!(filter.next(), "function attribute");
+                if retval && pass_through {
+                    bail!(p.pat, "#[retval] parameters cannot be #[pass_through]");
+                }
+
+                let mut filter = p.attrs.iter().filter(|a| a.path.is_ident("size_is")).fuse();
+                let size_is = filter.next().map(|a| a.parse_args::<Ident>()).transpose()?;
+
                 unexpected_token!(filter.next(), "function attribute");
                 Ok(InterfaceMethodArg {
                     ty: p.ty,
                     pat: p.pat,
                     pass_through,
+                    retval,
+                    size_is,
                 })
             })
             .collect::<Result<Vec<InterfaceMethodArg>, syn::Error>>()?;
 
         let ret = sig.output;
-        Ok(InterfaceMethod {
+        if let Some((last, rest)) = args.split_last() {
+            unexpected_token!(
+                rest.iter().find(|a| a.retval).map(|a| &a.pat),
+                "#[retval] attribute (only the last parameter can be #[retval])"
+            );
+            if last.retval {
+                if retval_type(&last.ty).is_none() {
+                    bail!(last.ty, "#[retval] parameters must be `*mut T` pointers");
+                }
+                if !returns_hresult(&ret) {
+                    bail!(
+                        ret,
+                        "methods with a #[retval] parameter must return `HRESULT` or `HResult`"
+                    );
+                }
+            }
+        }
+        check_size_is(&args)?;
+        let method = InterfaceMethod {
             name: sig.ident,
             visibility,
             args,
             ret,
             docs,
-        })
+            safe,
+        };
+        if safe {
+            method.check_safe()?;
+        }
+        Ok(method)
+    }
+}
+
+/// Check that the `#[size_is(len)]` parameters are pointers to arrays whose length is
+/// another parameter passed by value
+fn check_size_is(args: &[InterfaceMethodArg]) -> syn::Result<()> {
+    for arg in args {
+        let len = match &arg.size_is {
+            Some(len) => len,
+            None => continue,
+        };
+        if arg.retval || !matches!(&*arg.ty, syn::Type::Ptr(_)) {
+            return Err(syn::Error::new_spanned(
+                &arg.ty,
+                "#[size_is] parameters must be `*const T` or `*mut T` pointers, and cannot be #[retval]",
+            ));
+        }
+        let len_arg = args.iter().find(|a| arg_ident(a) == Some(len));
+        match len_arg {
+            Some(len_arg) if !len_arg.retval && !matches!(&*len_arg.ty, syn::Type::Ptr(_)) => {}
+            Some(_) => {
+                bail!(
+                    len,
+                    "the length of a #[size_is] array must be passed by value"
+                );
+            }
+            None => {
+                bail!(len, "no parameter named `{}`", len);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The name of a parameter, if it is a plain identifier
+pub fn arg_ident(arg: &InterfaceMethodArg) -> Option<&Ident> {
+    match &*arg.pat {
+        syn::Pat::Ident(p) => Some(&p.ident),
+        _ => None,
+    }
+}
+
+/// Whether `attr` is the `#[safe]` attribute, which takes no arguments
+fn is_safe_attribute(attr: &Attribute) -> syn::Result<bool> {
+    if !attr.path.is_ident("safe") {
+        return Ok(false);
+    }
+    if !attr.tokens.is_empty() {
+        bail!(attr.tokens, "#[safe] does not take arguments");
+    }
+    Ok(true)
+}
+
+/// The type aliases of `com::sys` and Windows which are raw pointers, and the raw
+/// structs which contain raw pointers
+const POINTER_TYPES: &[&str] = &[
+    "BSTR",
+    "LPWSTR",
+    "LPCWSTR",
+    "PWSTR",
+    "PCWSTR",
+    "LPSTR",
+    "LPCSTR",
+    "PSTR",
+    "PCSTR",
+    "LPVOID",
+    "LPCVOID",
+    "PVOID",
+    "HANDLE",
+    "HKEY",
+    "HWND",
+    "HINSTANCE",
+    "HMODULE",
+    "HGLOBAL",
+    "VARIANT",
+    "PROPVARIANT",
+    "DISPPARAMS",
+    "EXCEPINFO",
+    "SAFEARRAY",
+    "MSG",
+];
+
+/// Whether a raw pointer appears anywhere in `ty`
+///
+/// Only the pointers which are visible in the syntax, and the aliases and structs of
+/// [`POINTER_TYPES`], can be found.
+fn contains_pointer(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Ptr(_) => true,
+        syn::Type::Reference(r) => contains_pointer(&r.elem),
+        syn::Type::Array(a) => contains_pointer(&a.elem),
+        syn::Type::Slice(s) => contains_pointer(&s.elem),
+        syn::Type::Group(g) => contains_pointer(&g.elem),
+        syn::Type::Paren(p) => contains_pointer(&p.elem),
+        syn::Type::Tuple(t) => t.elems.iter().any(contains_pointer),
+        syn::Type::Path(p) => p.path.segments.iter().any(|s| {
+            POINTER_TYPES.contains(&s.ident.to_string().as_str())
+                || match &s.arguments {
+                    syn::PathArguments::AngleBracketed(a) => a.args.iter().any(|a| match a {
+                        syn::GenericArgument::Type(ty) => contains_pointer(ty),
+                        _ => false,
+                    }),
+                    _ => false,
+                }
+        }),
+        _ => false,
+    }
+}
+
+/// The pointee type of a `#[retval]` parameter
+pub fn retval_type(ty: &syn::Type) -> Option<&syn::Type> {
+    match ty {
+        syn::Type::Ptr(p) if p.mutability.is_some() => Some(&p.elem),
+        _ => None,
+    }
+}
+
+pub fn returns_hresult(ret: &syn::ReturnType) -> bool {
+    match ret {
+        syn::ReturnType::Type(_, ty) => match &**ty {
+            syn::Type::Path(p) => p
+                .path
+                .segments
+                .last()
+                .map(|s| s.ident == "HRESULT" || s.ident == "HResult")
+                .unwrap_or(false),
+            _ => false,
+        },
+        syn::ReturnType::Default => false,
     }
 }
 
 impl InterfaceMethod {
+    /// The `#[retval]` parameter of the method, if it has one
+    pub fn retval(&self) -> Option<&InterfaceMethodArg> {
+        self.args.last().filter(|a| a.retval)
+    }
+
+    /// Check that the parameters of a `#[safe]` method cannot be used to pass
+    /// dangling or invalid pointers. The `#[retval]` parameter is written by the
+    /// generated wrapper, so it is allowed to be a pointer.
+    fn check_safe(&self) -> syn::Result<()> {
+        for arg in self.args.iter().filter(|a| !a.retval) {
+            if arg.pass_through {
+                bail!(
+                    arg.pat,
+                    "#[safe] methods cannot have #[pass_through] parameters"
+                );
+            }
+            if contains_pointer(&arg.ty) {
+                return Err(syn::Error::new_spanned(
+                    &arg.ty,
+                    "#[safe] methods cannot have raw pointer parameters",
+                ));
+            }
+        }
+        Ok(())
+    }
+
     fn to_tokens(&self) -> TokenStream {
         let inner_method_ident =
             format_ident!("{}", crate::utils::snake_to_camel(&self.name.to_string()));
@@ -296,6 +555,10 @@ impl InterfaceMethod {
         for (index, arg) in self.args.iter().enumerate() {
             let pat = &arg.pat;
             let ty = &arg.ty;
+            if arg.retval {
+                params.push(quote! { #pat.as_mut_ptr() });
+                continue;
+            }
             if arg.pass_through {
                 args.push(quote! { #pat: #ty });
             } else {
@@ -315,15 +578,46 @@ impl InterfaceMethod {
 
         let docs = &self.docs;
         let vis = &self.visibility;
-        return quote! {
+        let (return_type, body) = match self.retval() {
+            Some(retval) => {
+                let pat = &retval.pat;
+                let ty = retval_type(&retval.ty).unwrap();
+                let return_type = quote! { -> ::core::result::Result<#ty, ::com::Error> };
+                let body = quote! {
+                    #(#into)*
+                    let mut #pat = ::core::mem::MaybeUninit::<#ty>::uninit();
+                    let #interface_ptr_ident = <Self as ::com::AbiTransferable>::get_abi(self);
+                    let hr = ::com::HResult::from((#interface_ptr_ident.as_ref().as_ref().#inner_method_ident)(#(#params),*));
+                    if hr.is_err() {
+                        return Err(::com::Error::from_failed_call(self, hr));
+                    }
+                    Ok(#pat.assume_init())
+                };
+                (return_type, body)
+            }
+            None => {
+                let body = quote! {
+                    #(#into)*
+                    let #interface_ptr_ident = <Self as ::com::AbiTransferable>::get_abi(self);
+                    (#interface_ptr_ident.as_ref().as_ref().#inner_method_ident)(#(#params),*)
+                };
+                (return_type.to_token_stream(), body)
+            }
+        };
+        // The parameters of `#[safe]` methods have been checked to not contain raw
+        // pointers, so the wrapper can be called without an `unsafe` block
+        let (unsafety, body) = if self.safe {
+            (quote! {}, quote! { unsafe { #body } })
+        } else {
+            (quote! { unsafe }, body)
+        };
+        quote! {
             #[allow(non_snake_case)]
-            #[allow(clippy::from_over_into)]
+            #[allow(clippy::from_over_into, clippy::too_many_arguments)]
             #(#docs)*
-            #vis unsafe fn #outer_method_ident<#(#generics),*>(&self, #(#args),*) #return_type {
-                #(#into)*
-                let #interface_ptr_ident = <Self as ::com::AbiTransferable>::get_abi(self);
-                (#interface_ptr_ident.as_ref().as_ref().#inner_method_ident)(#(#params),*)
+            #vis #unsafety fn #outer_method_ident<#(#generics),*>(&self, #(#args),*) #return_type {
+                #body
             }
-        };
+        }
     }
 }
@@ -17,6 +17,7 @@ impl syn::parse::Parse for Interfaces {
             }
             interfaces.push(interface);
         }
+        super::mock::check(&interfaces)?;
         Ok(Self {
             inner: interfaces,
             parents,
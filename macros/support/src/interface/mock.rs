@@ -0,0 +1,288 @@
+use super::interface::retval_type;
+use super::{Interface, InterfaceMethod};
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{Ident, LitStr, Path};
+
+/// The interfaces implemented by the mock of an interface
+struct Chain<'a> {
+    /// The interface and its bases, down to the one deriving from `IUnknown` or
+    /// `IDispatch`
+    interfaces: Vec<&'a Interface>,
+    /// The path of `IDispatch`, if the chain derives from it
+    dispatch: Option<&'a Path>,
+}
+
+/// Find the bases of the mocked `interface` among the interfaces `declared` in the
+/// same `interfaces!` block
+fn chain<'a>(interface: &'a Interface, declared: &'a [Interface]) -> syn::Result<Chain<'a>> {
+    let span = interface.mock.unwrap_or_else(|| interface.name.span());
+    let mut interfaces = vec![interface];
+    let mut current = interface;
+    loop {
+        let parent = match &current.parent {
+            Some(parent) => parent,
+            None => return Err(syn::Error::new(span, "IUnknown cannot be mocked")),
+        };
+        let ident = &parent.segments.last().unwrap().ident;
+        if ident == "IUnknown" {
+            return Ok(Chain {
+                interfaces,
+                dispatch: None,
+            });
+        }
+        if ident == "IDispatch" {
+            return Ok(Chain {
+                interfaces,
+                dispatch: Some(parent),
+            });
+        }
+        match declared.iter().find(|i| parent.is_ident(&i.name)) {
+            Some(base) if !interfaces.iter().any(|i| i.name == base.name) => {
+                interfaces.push(base);
+                current = base;
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    span,
+                    format!(
+                        "#[mock] requires the base interfaces of {} to be declared in the same interfaces! block, unless they are IUnknown or IDispatch",
+                        interface.name
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+/// Check that a mock class can be generated for each `#[mock]` interface of an
+/// `interfaces!` block
+pub fn check(declared: &[Interface]) -> syn::Result<()> {
+    for interface in declared.iter().filter(|i| i.mock.is_some()) {
+        chain(interface, declared)?;
+    }
+    Ok(())
+}
+
+/// A method of the mock class
+struct MockMethod<'a> {
+    /// The interface declaring the method
+    interface: &'a Interface,
+    method: &'a InterfaceMethod,
+    /// The field holding the expectation of the method
+    field: Ident,
+    /// The parameters of the closure, which are the parameters of the method
+    /// except the `#[retval]` parameter
+    params: Vec<(Ident, &'a syn::Type)>,
+    /// The return type of the closure
+    ret: TokenStream,
+}
+
+impl<'a> MockMethod<'a> {
+    fn new(interface: &'a Interface, method: &'a InterfaceMethod) -> Self {
+        let field = format_ident!("{}", crate::utils::camel_to_snake(&method.name.to_string()));
+        let params = method
+            .args
+            .iter()
+            .filter(|a| !a.retval)
+            .enumerate()
+            .map(|(index, a)| (format_ident!("__{}", index), &*a.ty))
+            .collect();
+        let ret = match (method.retval(), &method.ret) {
+            (Some(retval), _) => {
+                let ty = retval_type(&retval.ty).unwrap();
+                quote! { ::core::result::Result<#ty, ::com::Error> }
+            }
+            (None, syn::ReturnType::Type(_, ty)) => quote! { #ty },
+            (None, syn::ReturnType::Default) => quote! { () },
+        };
+        Self {
+            interface,
+            method,
+            field,
+            params,
+            ret,
+        }
+    }
+
+    fn expectation_type(&self) -> TokenStream {
+        let tys = self.params.iter().map(|(_, ty)| ty);
+        let ret = &self.ret;
+        quote! { ::com::mock::Expectation<dyn FnMut(#(#tys),*) -> #ret> }
+    }
+
+    /// The value returned by a failed call of a method which does not return an
+    /// `HRESULT`, or `None` for the methods which panic
+    fn fallback(&self) -> Option<TokenStream> {
+        if self.method.retval().is_some() {
+            return None;
+        }
+        let ty = match &self.method.ret {
+            syn::ReturnType::Default => return Some(quote! { ::core::option::Option::Some(()) }),
+            syn::ReturnType::Type(_, ty) => &**ty,
+        };
+        match ty {
+            syn::Type::Path(p) => {
+                let ident = &p.path.segments.last().unwrap().ident;
+                if ident == "HRESULT" || ident == "HResult" {
+                    return None;
+                }
+            }
+            syn::Type::Ptr(p) if p.mutability.is_some() => {
+                return Some(quote! { ::core::option::Option::Some(::core::ptr::null_mut()) })
+            }
+            syn::Type::Ptr(_) => {
+                return Some(quote! { ::core::option::Option::Some(::core::ptr::null()) })
+            }
+            _ => {}
+        }
+        Some(quote! {{
+            #[allow(unused_imports)]
+            use ::com::mock::{DefaultFallback as _, NoFallback as _};
+            (&::com::mock::Fallback::<#ty>(::core::marker::PhantomData)).fallback()
+        }})
+    }
+
+    /// The implementation of the interface method, calling the closure
+    fn to_impl_tokens(&self) -> TokenStream {
+        let name = &self.method.name;
+        let field = &self.field;
+        let ret = &self.ret;
+        let params = self.params.iter().map(|(ident, ty)| quote! { #ident: #ty });
+        let args = self.params.iter().map(|(ident, _)| ident);
+        let call = match self.fallback() {
+            Some(fallback) => quote! { self.#field.call_or(move |f| f(#(#args),*), #fallback) },
+            None => quote! { self.#field.call(move |f| f(#(#args),*)) },
+        };
+        quote! {
+            fn #name(&self, #(#params),*) -> #ret {
+                #call
+            }
+        }
+    }
+
+    /// The `expect_` method setting the closure
+    fn to_expect_tokens(&self, vis: &syn::Visibility) -> TokenStream {
+        let interface = self.interface;
+        let field = &self.field;
+        let expect = format_ident!("expect_{}", field);
+        let tys = self.params.iter().map(|(_, ty)| ty);
+        let ret = &self.ret;
+        let expectation = self.expectation_type();
+        let doc = format!(
+            "Set the closure called by `{}::{}`",
+            interface.name, self.method.name
+        );
+        quote! {
+            #[doc = #doc]
+            #vis fn #expect(&self, returning: impl FnMut(#(#tys),*) -> #ret + 'static) -> &#expectation {
+                self.#field.returning(::com::alloc::boxed::Box::new(returning))
+            }
+        }
+    }
+}
+
+/// Generate a class implementing `interface` and its bases with closures, for use
+/// in tests
+///
+/// `declared` holds the interfaces of the `interfaces!` block, which were checked by
+/// [`check`].
+pub fn generate(interface: &Interface, declared: &[Interface]) -> TokenStream {
+    let chain = match chain(interface, declared) {
+        Ok(chain) => chain,
+        Err(e) => return e.to_compile_error(),
+    };
+    let name = &interface.name;
+    let vis = &interface.visibility;
+    let mock = format_ident!("Mock{}", name);
+    // The methods in vtable order, starting with those of the base interface
+    let methods: Vec<_> = chain
+        .interfaces
+        .iter()
+        .rev()
+        .flat_map(|i| i.methods.iter().map(move |m| MockMethod::new(i, m)))
+        .collect();
+
+    let fields = methods.iter().map(|m| {
+        let field = &m.field;
+        let ty = m.expectation_type();
+        quote! { #field: #ty, }
+    });
+    let impls = chain.interfaces.iter().rev().map(|i| {
+        let impls = methods
+            .iter()
+            .filter(|m| m.interface.name == i.name)
+            .map(|m| m.to_impl_tokens());
+        let interface = &i.name;
+        quote! {
+            impl #interface for #mock {
+                #(#impls)*
+            }
+        }
+    });
+    let expects = methods.iter().map(|m| m.to_expect_tokens(vis));
+    let expectations = methods.iter().map(|m| {
+        let method = LitStr::new(
+            &format!("{}::{}", m.interface.name, m.method.name),
+            Span::call_site(),
+        );
+        quote! { ::com::mock::Expectation::new(#method) }
+    });
+    // The bases of the interface, as in `class IFoo(IBar(IDispatch))`
+    let bases = chain.interfaces[1..]
+        .iter()
+        .map(|i| {
+            let name = &i.name;
+            quote!(#name)
+        })
+        .chain(chain.dispatch.map(|p| quote!(#p)))
+        .rev()
+        .fold(None, |inner: Option<TokenStream>, base| match inner {
+            Some(inner) => Some(quote!(#base(#inner))),
+            None => Some(base),
+        })
+        .map(|bases| quote!((#bases)));
+    let dispatch = chain.dispatch.map(|_| quote!(#[dispatch]));
+    let field_names = methods.iter().map(|m| &m.field);
+    let interface_doc = format!("Get the `{}` interface of the mock", name);
+    let doc = format!(
+        "A mock implementation of [`{}`], whose methods call the closures set with its `expect_` methods",
+        name
+    );
+
+    quote! {
+        ::com::class! {
+            #[doc = #doc]
+            #[no_class_factory]
+            #[refcount(local)]
+            #dispatch
+            #vis class #mock: #name #bases {
+                #(#fields)*
+            }
+
+            #(#impls)*
+        }
+
+        impl #mock {
+            /// Allocate a mock whose methods have no closure
+            #vis fn new() -> ::com::mock::Mock<Self> {
+                ::com::mock::Mock::new(Self::allocate(#(#expectations),*))
+            }
+
+            #[doc = #interface_doc]
+            #vis fn interface(self: &::core::pin::Pin<::com::alloc::boxed::Box<Self>>) -> #name {
+                self.query_interface::<#name>().unwrap()
+            }
+
+            #(#expects)*
+        }
+
+        impl ::com::mock::Verify for #mock {
+            fn failures(&self) -> ::com::alloc::vec::Vec<::com::alloc::string::String> {
+                let mut failures = ::com::alloc::vec::Vec::new();
+                #(self.#field_names.verify(&mut failures);)*
+                failures
+            }
+        }
+    }
+}
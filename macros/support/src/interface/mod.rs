@@ -3,6 +3,8 @@ mod iid;
 mod interface;
 mod interface_impl;
 mod interfaces;
+mod mock;
+mod remote;
 mod vptr;
 pub mod vtable;
 
@@ -17,12 +19,18 @@ use std::iter::FromIterator;
 // Expansion entry point
 pub fn expand_interfacess(interfaces: Interfaces) -> TokenStream {
     let mut out: Vec<TokenStream> = Vec::new();
-    for interface in interfaces.inner {
+    for interface in &interfaces.inner {
         out.push(interface.to_struct_tokens());
-        out.push(vtable::generate(&interface).unwrap_or_else(|e| e.to_compile_error()));
-        out.push(vptr::generate(&interface));
-        out.push(interface_impl::generate(&interface));
+        out.push(vtable::generate(interface).unwrap_or_else(|e| e.to_compile_error()));
+        out.push(vptr::generate(interface));
+        out.push(interface_impl::generate(interface));
         out.push(interface.to_iid_tokens());
+        if interface.remote {
+            out.push(remote::generate(interface));
+        }
+        if interface.mock.is_some() {
+            out.push(mock::generate(interface, &interfaces.inner));
+        }
     }
     out.extend(convert_impls(interfaces.parents));
 
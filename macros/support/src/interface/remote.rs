@@ -0,0 +1,252 @@
+use super::interface::{arg_ident, returns_hresult, InterfaceMethodArg};
+use super::{vptr, vtable, Interface, InterfaceMethod};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::Type;
+
+/// How a parameter of a `#[remote]` method is passed to the other process
+enum Direction<'a> {
+    /// A value which is marshalled as is
+    In(&'a Type),
+    /// A `*const T` which may be null
+    InPtr(&'a Type),
+    /// A `*mut T` which is written when the call succeeds
+    Out(&'a Type),
+    /// A `#[size_is]` `*const T` array, with the index of its length parameter
+    InArray(&'a Type, usize),
+    /// A `#[size_is]` `*mut T` array which is written when the call succeeds, with the
+    /// index of its length parameter
+    OutArray(&'a Type, usize),
+}
+
+fn direction<'a>(arg: &'a InterfaceMethodArg, args: &[InterfaceMethodArg]) -> Direction<'a> {
+    let len = arg.size_is.as_ref().map(|len| {
+        args.iter()
+            .position(|a| arg_ident(a) == Some(len))
+            .expect("the length parameter of #[size_is] arrays has been checked")
+    });
+    match (&*arg.ty, len) {
+        (Type::Ptr(p), Some(len)) if p.mutability.is_some() => Direction::OutArray(&p.elem, len),
+        (Type::Ptr(p), Some(len)) => Direction::InArray(&p.elem, len),
+        (Type::Ptr(p), None) if p.mutability.is_some() => Direction::Out(&p.elem),
+        (Type::Ptr(p), None) => Direction::InPtr(&p.elem),
+        (ty, _) => Direction::In(ty),
+    }
+}
+
+/// Check that the methods of a `#[remote]` interface can be called from another
+/// process
+pub fn check(method: &InterfaceMethod) -> syn::Result<()> {
+    if !returns_hresult(&method.ret) {
+        return Err(syn::Error::new(
+            method.name.span(),
+            "methods of #[remote] interfaces must return `HRESULT` or `HResult`",
+        ));
+    }
+    for arg in &method.args {
+        if arg.pass_through {
+            return Err(syn::Error::new(
+                arg.pat.span(),
+                "methods of #[remote] interfaces cannot have #[pass_through] parameters",
+            ));
+        }
+        let valid = match &*arg.ty {
+            Type::Path(_) => true,
+            Type::Ptr(p) => matches!(&*p.elem, Type::Path(_)),
+            _ => false,
+        };
+        if !valid {
+            return Err(syn::Error::new_spanned(
+                &arg.ty,
+                "parameters of #[remote] methods must be values, `*const T` or `*mut T`",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Generate the proxy and the stub of a `#[remote]` interface
+pub fn generate(interface: &Interface) -> TokenStream {
+    let interface_ident = &interface.name;
+    let vtable_ident = vtable::ident(&interface_ident.to_string());
+    let vptr_ident = vptr::ident(interface_ident);
+
+    let mut proxies = Vec::new();
+    let mut fields = Vec::new();
+    let mut stubs = Vec::new();
+    for (index, method) in interface.methods.iter().enumerate() {
+        let index = index as u32;
+        let field = format_ident!("{}", crate::utils::snake_to_camel(&method.name.to_string()));
+        let proxy = format_ident!("{}", field);
+        let return_type = match method.retval() {
+            Some(_) => quote!(::com::sys::HRESULT),
+            None => match &method.ret {
+                syn::ReturnType::Type(_, ty) => quote!(#ty),
+                syn::ReturnType::Default => unreachable!(),
+            },
+        };
+
+        let mut params = Vec::new();
+        let mut null_checks = Vec::new();
+        let mut marshal_args = Vec::new();
+        let mut unmarshal_results = Vec::new();
+        let mut unmarshal_args = Vec::new();
+        let mut arrays = Vec::new();
+        let mut call_args = Vec::new();
+        let mut marshal_results = Vec::new();
+        for (i, arg) in method.args.iter().enumerate() {
+            let name = format_ident!("__{}", i);
+            let ty = &arg.ty;
+            params.push(quote!(#name: <#ty as ::com::AbiTransferable>::Abi));
+            match direction(arg, &method.args) {
+                Direction::In(ty) => {
+                    marshal_args.push(quote! {
+                        ::com::remote::proxy::marshal_in::<#ty>(#name, args)?;
+                    });
+                    unmarshal_args.push(quote! {
+                        let #name = <#ty as ::com::marshal::Marshal>::unmarshal(args)?;
+                    });
+                    call_args.push(quote!(::com::AbiTransferable::get_abi(&#name)));
+                }
+                Direction::InPtr(ty) => {
+                    marshal_args.push(quote! {
+                        ::com::remote::proxy::marshal_in_ptr::<#ty>(#name, args)?;
+                    });
+                    unmarshal_args.push(quote! {
+                        let #name = ::com::remote::proxy::unmarshal_in_ptr::<#ty>(args)?;
+                    });
+                    call_args.push(quote!(::com::remote::proxy::in_ptr(&#name)));
+                }
+                Direction::Out(ty) => {
+                    null_checks.push(quote! {
+                        if #name.is_null() {
+                            return ::core::convert::From::from(::com::sys::E_POINTER);
+                        }
+                    });
+                    unmarshal_results.push(quote! {
+                        ::com::remote::proxy::unmarshal_out::<#ty>(#name, results)?;
+                    });
+                    unmarshal_args.push(quote! {
+                        let mut #name = ::core::mem::MaybeUninit::<#ty>::zeroed();
+                    });
+                    call_args.push(quote!(#name.as_mut_ptr()));
+                    marshal_results.push(quote! {
+                        ::com::remote::proxy::marshal_out(#name, results)?;
+                    });
+                }
+                Direction::InArray(ty, len) => {
+                    let len = format_ident!("__{}", len);
+                    marshal_args.push(quote! {
+                        ::com::remote::proxy::marshal_in_array::<#ty, _>(#name, #len, args)?;
+                    });
+                    unmarshal_args.push(quote! {
+                        let #name = ::com::remote::proxy::unmarshal_in_array::<#ty>(args)?;
+                    });
+                    arrays.push(quote! {
+                        ::com::remote::proxy::check_in_array(#name.as_slice(), #len)?;
+                    });
+                    call_args.push(quote!(#name.as_ptr()));
+                }
+                Direction::OutArray(ty, len) => {
+                    let len = format_ident!("__{}", len);
+                    marshal_args.push(quote! {
+                        ::com::remote::proxy::check_out_array(#name, #len)?;
+                    });
+                    unmarshal_results.push(quote! {
+                        ::com::remote::proxy::unmarshal_out_array::<#ty, _>(#name, #len, results)?;
+                    });
+                    // The length may be read after the array
+                    arrays.push(quote! {
+                        let mut #name = ::com::remote::proxy::out_array::<#ty, _>(#len)?;
+                    });
+                    call_args.push(quote!(#name.as_mut_ptr().cast::<#ty>()));
+                    marshal_results.push(quote! {
+                        ::com::remote::proxy::marshal_out_array(#name, results)?;
+                    });
+                }
+            }
+        }
+
+        proxies.push(quote! {
+            #[allow(non_snake_case)]
+            unsafe extern "system" fn #proxy(
+                this: ::core::ptr::NonNull<#vptr_ident>,
+                #(#params),*
+            ) -> #return_type {
+                #(#null_checks)*
+                let hr = ::com::remote::proxy::call(
+                    this,
+                    &<#interface_ident as ::com::Interface>::IID,
+                    #index,
+                    |args| {
+                        #(#marshal_args)*
+                        Ok(())
+                    },
+                    |results| {
+                        #(#unmarshal_results)*
+                        Ok(())
+                    },
+                );
+                ::core::convert::From::from(hr)
+            }
+        });
+        fields.push(quote!(#field: #proxy,));
+        stubs.push(quote! {
+            #index => {
+                #(#unmarshal_args)*
+                #(#arrays)*
+                let hr = ::com::HResult::from((this.as_ref().as_ref().#field)(this, #(#call_args),*)).0;
+                if !::com::sys::FAILED(hr) {
+                    #(#marshal_results)*
+                }
+                Ok(hr)
+            }
+        });
+    }
+
+    let parent = interface
+        .parent
+        .as_ref()
+        .expect("IUnknown is implemented by com::remote");
+    let (args, results, this) = if interface.methods.is_empty() {
+        (quote!(_args), quote!(_results), quote!())
+    } else {
+        let this = quote!(let this = <Self as ::com::AbiTransferable>::get_abi(self););
+        (quote!(args), quote!(results), this)
+    };
+    quote! {
+        unsafe impl ::com::remote::Remote for #interface_ident {
+            const PROXY_VTABLE: #vtable_ident = {
+                #(#proxies)*
+                #vtable_ident {
+                    parent: <#parent as ::com::remote::Remote>::PROXY_VTABLE,
+                    #(#fields)*
+                }
+            };
+
+            fn proxy_vtable() -> &'static #vtable_ident {
+                static VTABLE: #vtable_ident = <#interface_ident as ::com::remote::Remote>::PROXY_VTABLE;
+                &VTABLE
+            }
+
+            fn register() {
+                ::com::remote::register_interface::<Self>();
+                <#parent as ::com::remote::Remote>::register();
+            }
+
+            unsafe fn invoke(
+                &self,
+                index: u32,
+                #args: &mut ::com::marshal::Decoder<'_>,
+                #results: &mut ::com::marshal::Encoder<'_>,
+            ) -> ::core::result::Result<::com::sys::HRESULT, ::com::Error> {
+                #this
+                match index {
+                    #(#stubs)*
+                    _ => Err(::com::HResult(::com::sys::RPC_E_INVALIDMETHOD).into()),
+                }
+            }
+        }
+    }
+}
@@ -63,7 +63,16 @@ fn gen_vtable_function_signature(
     method: &InterfaceMethod,
 ) -> syn::Result<TokenStream> {
     let params = gen_raw_params(interface_ident, method)?;
-    let return_type = &method.ret;
+    // `HRESULT` and `HResult` are ABI-identical. Methods with a `#[retval]` parameter
+    // always use `HRESULT` so that `class!` can generate shims for them without having
+    // to know which of the two was used in the declaration.
+    let return_type = match method.retval() {
+        Some(_) => quote!(-> ::com::sys::HRESULT),
+        None => {
+            let ret = &method.ret;
+            quote!(#ret)
+        }
+    };
 
     Ok(quote!(
         unsafe extern "system" fn(#params) #return_type
@@ -2,9 +2,13 @@
 #![allow(clippy::upper_case_acronyms)]
 
 pub mod class;
+pub mod header;
+pub mod idl;
 pub mod interface;
+pub mod marshal;
 #[cfg(test)]
 mod test_utils;
+pub mod tlb;
 mod utils;
 
 pub use class::Class;
@@ -0,0 +1,89 @@
+//! `#[derive(Marshal)]` for `#[repr(C)]` structs
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, GenericParam};
+
+/// Implement `com::marshal::Marshal` by writing the fields of the struct in order
+pub fn expand_derive(mut input: DeriveInput) -> syn::Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(data) => {
+            return Err(syn::Error::new(
+                data.enum_token.span(),
+                "#[derive(Marshal)] is only supported for structs",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span(),
+                "#[derive(Marshal)] is only supported for structs",
+            ))
+        }
+    };
+    if !is_repr_c(&input)? {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "#[derive(Marshal)] requires the struct to be #[repr(C)]",
+        ));
+    }
+
+    let members = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let index = syn::Index::from(index);
+                quote!(#index)
+            }
+        })
+        .collect::<Vec<_>>();
+    let types = fields.iter().map(|f| &f.ty);
+
+    for param in &mut input.generics.params {
+        if let GenericParam::Type(param) = param {
+            param
+                .bounds
+                .push(syn::parse_quote!(::com::marshal::Marshal));
+        }
+    }
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::com::marshal::Marshal for #name #ty_generics #where_clause {
+            fn marshal(
+                &self,
+                encoder: &mut ::com::marshal::Encoder<'_>,
+            ) -> ::core::result::Result<(), ::com::Error> {
+                #(::com::marshal::Marshal::marshal(&self.#members, encoder)?;)*
+                ::core::result::Result::Ok(())
+            }
+
+            fn unmarshal(
+                decoder: &mut ::com::marshal::Decoder<'_>,
+            ) -> ::core::result::Result<Self, ::com::Error> {
+                ::core::result::Result::Ok(Self {
+                    #(#members: <#types as ::com::marshal::Marshal>::unmarshal(decoder)?,)*
+                })
+            }
+        }
+    })
+}
+
+/// Whether the struct has a `#[repr(C)]` attribute, possibly with other hints such
+/// as `#[repr(C, align(8))]`
+fn is_repr_c(input: &DeriveInput) -> syn::Result<bool> {
+    for attr in input.attrs.iter().filter(|a| a.path.is_ident("repr")) {
+        if let syn::Meta::List(list) = attr.parse_meta()? {
+            let is_c = list.nested.iter().any(|meta| match meta {
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) => path.is_ident("C"),
+                _ => false,
+            });
+            if is_c {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
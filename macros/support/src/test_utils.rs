@@ -1,7 +1,32 @@
 pub(crate) mod rustfmt;
 
+use std::path::Path;
+
 pub(crate) fn is_verbose_testing() -> bool {
     std::env::var("COM_RS_TEST_VERBOSE")
         .map(|value| matches!(value.as_str(), "1" | "true"))
         .unwrap_or_default()
 }
+
+/// Compare generated output with the golden file at `path`, relative to the crate
+/// root
+///
+/// Set `COM_RS_GOLDEN=overwrite` to regenerate the golden files after an intended
+/// change in the output.
+pub(crate) fn assert_golden(actual: &str, path: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+    if std::env::var("COM_RS_GOLDEN").as_deref() == Ok("overwrite") {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+        .replace("\r\n", "\n");
+    if actual != expected {
+        panic!(
+            "Generated output does not match {}.\nActual:\n{}",
+            path.display(),
+            actual
+        );
+    }
+}
@@ -0,0 +1,655 @@
+//! Building type libraries from `interfaces!` and `class!` declarations
+//!
+//! Interfaces become type infos in declaration order, followed by a coclass for
+//! each class which is given a CLSID by `inproc_dll_module!`. Interfaces deriving
+//! from `IDispatch` are dual interfaces, whose `DISPID`s are those assigned by
+//! `#[dispatch]` classes. `IUnknown` and `IDispatch` are imported from
+//! `stdole2.tlb`.
+use super::{
+    FuncDesc, FuncKind, Guid, ImplType, ImportedLib, InvokeKind, ParamDesc, SysKind, TypeDesc,
+    TypeInfo, TypeKind, TypeLib, TypeRef, TYPEFLAG_FDUAL,
+};
+use crate::class::Class;
+use crate::interface::{Interface, InterfaceMethod, Interfaces};
+use proc_macro2::Span;
+use std::collections::HashMap;
+use std::str::FromStr;
+use syn::parse::{ParseStream, Parser};
+use syn::spanned::Spanned;
+
+const TYPEFLAG_FCANCREATE: u32 = 0x2;
+const TYPEFLAG_FOLEAUTOMATION: u32 = 0x100;
+const TYPEFLAG_FDISPATCHABLE: u32 = 0x1000;
+const IMPLTYPEFLAG_FDEFAULT: u32 = 0x1;
+const PARAMFLAG_FIN: u16 = 0x1;
+const PARAMFLAG_FOUT: u16 = 0x2;
+const PARAMFLAG_FRETVAL: u16 = 0x8;
+const CC_STDCALL: u8 = 4;
+/// The first member id MIDL assigns to the methods of an interface
+const MEMBERID_BASE: i32 = 0x6000_0000;
+
+const STDOLE: &str = "stdole2.tlb";
+const LIBID_STDOLE: &str = "00020430-0000-0000-C000-000000000046";
+/// The interfaces imported from `stdole2.tlb`, with their IID and number of methods
+const STDOLE_INTERFACES: &[(&str, &str, usize)] = &[
+    ("IUnknown", "00000000-0000-0000-C000-000000000046", 3),
+    ("IDispatch", "00020400-0000-0000-C000-000000000046", 7),
+];
+/// Interfaces implemented by the code generated by `class!`, which are not listed in
+/// coclasses
+const IMPLICIT_INTERFACES: &[&str] = &[
+    "IUnknown",
+    "IDispatch",
+    "IWeakReferenceSource",
+    "ISupportErrorInfo",
+];
+
+const VT_I2: u16 = 2;
+const VT_I4: u16 = 3;
+const VT_R4: u16 = 4;
+const VT_R8: u16 = 5;
+const VT_DATE: u16 = 7;
+const VT_BSTR: u16 = 8;
+const VT_DISPATCH: u16 = 9;
+const VT_ERROR: u16 = 10;
+const VT_BOOL: u16 = 11;
+const VT_VARIANT: u16 = 12;
+const VT_UNKNOWN: u16 = 13;
+const VT_I1: u16 = 16;
+const VT_UI1: u16 = 17;
+const VT_UI2: u16 = 18;
+const VT_UI4: u16 = 19;
+const VT_I8: u16 = 20;
+const VT_UI8: u16 = 21;
+const VT_INT: u16 = 22;
+const VT_UINT: u16 = 23;
+const VT_VOID: u16 = 24;
+const VT_HRESULT: u16 = 25;
+const VT_LPSTR: u16 = 30;
+const VT_LPWSTR: u16 = 31;
+const VT_INT_PTR: u16 = 37;
+const VT_UINT_PTR: u16 = 38;
+
+/// The attributes of a type library built from Rust declarations
+#[derive(Debug, Clone)]
+pub struct Library {
+    pub name: String,
+    pub guid: Guid,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub doc_string: Option<String>,
+    pub syskind: SysKind,
+}
+
+impl Library {
+    /// A library for 64-bit Windows with version 1.0
+    pub fn new(name: impl Into<String>, guid: Guid) -> Self {
+        Self {
+            name: name.into(),
+            guid,
+            major_version: 1,
+            minor_version: 0,
+            doc_string: None,
+            syskind: SysKind::Win64,
+        }
+    }
+}
+
+/// The declarations of a COM server, collected from its Rust source files
+#[derive(Default)]
+pub struct Declarations {
+    interfaces: Vec<Interface>,
+    classes: Vec<Class>,
+    /// The values of the constants declared with `guid!`, by name
+    guids: HashMap<String, Guid>,
+    /// The CLSID constants and classes listed by `inproc_dll_module!`
+    modules: Vec<(syn::Ident, syn::Ident)>,
+}
+
+impl Declarations {
+    /// Collect the `interfaces!`, `class!` and `inproc_dll_module!` invocations and
+    /// the `guid!` constants of a Rust source file, including those in inline modules
+    ///
+    /// Invocations are recognized by the last segment of the macro path.
+    pub fn parse_source(&mut self, source: &str) -> syn::Result<()> {
+        let file = syn::parse_file(source)?;
+        self.collect(&file.items)
+    }
+
+    fn collect(&mut self, items: &[syn::Item]) -> syn::Result<()> {
+        for item in items {
+            match item {
+                syn::Item::Macro(item) => {
+                    let tokens = item.mac.tokens.clone();
+                    match macro_name(&item.mac.path).as_str() {
+                        "interfaces" => {
+                            let parsed: Interfaces = syn::parse2(tokens)?;
+                            self.interfaces.extend(parsed.inner);
+                        }
+                        "class" => self.classes.push(syn::parse2(tokens)?),
+                        "inproc_dll_module" => {
+                            self.modules.extend(parse_module.parse2(tokens)?);
+                        }
+                        _ => {}
+                    }
+                }
+                syn::Item::Const(item) => {
+                    if let Some(guid) = guid_macro(&item.expr)? {
+                        self.guids.insert(item.ident.to_string(), guid);
+                    }
+                }
+                syn::Item::Mod(syn::ItemMod {
+                    content: Some((_, items)),
+                    ..
+                }) => self.collect(items)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn macro_name(path: &syn::Path) -> String {
+    path.segments
+        .last()
+        .map(|s| s.ident.to_string())
+        .unwrap_or_default()
+}
+
+/// The entries of `inproc_dll_module!`, e.g. `(CLSID_CAT, Cat), (CLSID_DOG, Dog)`
+fn parse_module(input: ParseStream) -> syn::Result<Vec<(syn::Ident, syn::Ident)>> {
+    let mut entries = Vec::new();
+    while !input.is_empty() {
+        let entry;
+        syn::parenthesized!(entry in input);
+        let clsid: syn::Ident = entry.parse()?;
+        entry.parse::<syn::Token![,]>()?;
+        let class: syn::Path = entry.parse()?;
+        entries.push((clsid, class.segments.last().unwrap().ident.clone()));
+        if !input.is_empty() {
+            input.parse::<syn::Token![,]>()?;
+        }
+    }
+    Ok(entries)
+}
+
+/// The GUID of a `guid!("...")` expression
+fn guid_macro(expr: &syn::Expr) -> syn::Result<Option<Guid>> {
+    let mac = match expr {
+        syn::Expr::Macro(expr) if macro_name(&expr.mac.path) == "guid" => &expr.mac,
+        _ => return Ok(None),
+    };
+    let lit: syn::LitStr = mac.parse_body()?;
+    parse_guid(&lit.value(), lit.span()).map(Some)
+}
+
+fn parse_guid(s: &str, span: Span) -> syn::Result<Guid> {
+    Guid::from_str(s).map_err(|e| syn::Error::new(span, e.to_string()))
+}
+
+/// Build a type library describing the declarations of a COM server
+///
+/// Classes which are not listed by `inproc_dll_module!` have no CLSID and are
+/// not described. The types of parameters must be ones which have a `VARTYPE`:
+/// integers, floating point numbers, `HRESULT`, `BStr`, `Variant`, `SafeArray<T>`,
+/// interfaces and pointers to those.
+pub fn build(library: &Library, declarations: &Declarations) -> syn::Result<TypeLib> {
+    let builder = Builder::new(library, declarations)?;
+    let mut type_infos = declarations
+        .interfaces
+        .iter()
+        .map(|i| builder.interface(i))
+        .collect::<syn::Result<Vec<_>>>()?;
+    builder.assign_dispids(&mut type_infos)?;
+    for (clsid, class) in &declarations.modules {
+        let guid = match declarations.guids.get(&clsid.to_string()) {
+            Some(guid) => *guid,
+            None => {
+                return Err(syn::Error::new(
+                    clsid.span(),
+                    format!("CLSID `{}` is not declared with `guid!`", clsid),
+                ))
+            }
+        };
+        match declarations.classes.iter().find(|c| c.name == *class) {
+            Some(class) => type_infos.push(builder.coclass(class, guid)?),
+            None => {
+                return Err(syn::Error::new(
+                    class.span(),
+                    format!("class `{}` is not declared with `class!`", class),
+                ))
+            }
+        }
+    }
+
+    Ok(TypeLib {
+        name: library.name.clone(),
+        doc_string: library.doc_string.clone(),
+        guid: Some(library.guid),
+        major_version: library.major_version,
+        minor_version: library.minor_version,
+        lcid: 0,
+        syskind: library.syskind,
+        imports: vec![ImportedLib {
+            file: STDOLE.to_owned(),
+            guid: Some(Guid::from_str(LIBID_STDOLE).unwrap()),
+            major_version: 2,
+            minor_version: 0,
+            lcid: 0,
+        }],
+        type_infos,
+    })
+}
+
+struct Builder<'a> {
+    declarations: &'a Declarations,
+    pointer_size: usize,
+    /// The index of each interface, by name
+    indices: HashMap<String, usize>,
+}
+
+/// Where an interface stands in its chain of parents
+struct Chain<'a> {
+    /// The declared interfaces of the chain, base first, including the interface
+    interfaces: Vec<&'a Interface>,
+    /// The interface imported from `stdole2.tlb` at the base of the chain
+    base: Option<&'static (&'static str, &'static str, usize)>,
+}
+
+impl Chain<'_> {
+    fn is_dual(&self) -> bool {
+        matches!(self.base, Some((name, _, _)) if *name == "IDispatch")
+    }
+
+    /// The number of methods of the parents of the interface
+    fn parent_methods(&self) -> usize {
+        let base = self.base.map(|(_, _, methods)| *methods).unwrap_or(0);
+        let parents = &self.interfaces[..self.interfaces.len() - 1];
+        base + parents.iter().map(|i| i.methods.len()).sum::<usize>()
+    }
+}
+
+impl<'a> Builder<'a> {
+    fn new(library: &Library, declarations: &'a Declarations) -> syn::Result<Self> {
+        let mut indices = HashMap::new();
+        for (index, interface) in declarations.interfaces.iter().enumerate() {
+            if indices.insert(interface.name.to_string(), index).is_some() {
+                return Err(syn::Error::new(
+                    interface.name.span(),
+                    format!("interface `{}` is declared more than once", interface.name),
+                ));
+            }
+        }
+        Ok(Self {
+            declarations,
+            pointer_size: library.syskind.pointer_size(),
+            indices,
+        })
+    }
+
+    fn chain(&self, interface: &'a Interface) -> syn::Result<Chain<'a>> {
+        let mut interfaces = vec![interface];
+        let mut current = interface;
+        while let Some(parent) = &current.parent {
+            let name = parent.segments.last().unwrap().ident.to_string();
+            if let Some(base) = STDOLE_INTERFACES.iter().find(|(n, _, _)| *n == name) {
+                interfaces.reverse();
+                return Ok(Chain {
+                    interfaces,
+                    base: Some(base),
+                });
+            }
+            current = match self.indices.get(&name) {
+                Some(index) => &self.declarations.interfaces[*index],
+                None => {
+                    return Err(syn::Error::new(
+                        parent.span(),
+                        format!(
+                            "parent interface `{}` of `{}` is not declared in the input",
+                            name, interface.name
+                        ),
+                    ))
+                }
+            };
+            if interfaces.iter().any(|i| i.name == current.name) {
+                return Err(syn::Error::new(
+                    interface.name.span(),
+                    format!("interface `{}` inherits from itself", interface.name),
+                ));
+            }
+            interfaces.push(current);
+        }
+        interfaces.reverse();
+        Ok(Chain {
+            interfaces,
+            base: None,
+        })
+    }
+
+    fn interface(&self, interface: &Interface) -> syn::Result<TypeInfo> {
+        let chain = self.chain(interface)?;
+        let parent_methods = chain.parent_methods();
+        let dual = chain.is_dual();
+        let level = (chain.interfaces.len() - 1 + chain.base.is_some() as usize) as i32;
+
+        let impl_types = match &interface.parent {
+            Some(parent) => vec![ImplType {
+                reference: self.interface_ref(parent)?,
+                flags: 0,
+            }],
+            None => Vec::new(),
+        };
+        let funcs = interface
+            .methods
+            .iter()
+            .enumerate()
+            .map(|(index, method)| {
+                Ok(FuncDesc {
+                    memid: MEMBERID_BASE | level << 16 | index as i32,
+                    name: crate::utils::snake_to_camel(&method.name.to_string()),
+                    doc_string: doc_string(&method.docs),
+                    kind: if dual {
+                        FuncKind::Dispatch
+                    } else {
+                        FuncKind::PureVirtual
+                    },
+                    invoke_kind: InvokeKind::Func,
+                    call_conv: CC_STDCALL,
+                    flags: 0,
+                    vtable_offset: (self.pointer_size * (parent_methods + index)) as i16,
+                    ret: match &method.ret {
+                        syn::ReturnType::Default => TypeDesc::Base(VT_VOID),
+                        syn::ReturnType::Type(_, ty) => self.type_desc(ty)?,
+                    },
+                    params: self.params(method)?,
+                })
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+
+        Ok(TypeInfo {
+            name: interface.name.to_string(),
+            doc_string: doc_string(&interface.docs),
+            guid: Some(parse_guid(
+                &interface.iid.to_registry_string(),
+                interface.name.span(),
+            )?),
+            kind: if dual {
+                TypeKind::Dispatch
+            } else {
+                TypeKind::Interface
+            },
+            flags: if dual {
+                TYPEFLAG_FDUAL | TYPEFLAG_FOLEAUTOMATION | TYPEFLAG_FDISPATCHABLE
+            } else {
+                0
+            },
+            major_version: 0,
+            minor_version: 0,
+            size: self.pointer_size as u32,
+            alignment: self.pointer_size as u16,
+            vtable_size: (self.pointer_size * (parent_methods + funcs.len())) as u16,
+            impl_types,
+            alias: None,
+            funcs,
+            vars: Vec::new(),
+        })
+    }
+
+    fn params(&self, method: &InterfaceMethod) -> syn::Result<Vec<ParamDesc>> {
+        method
+            .args
+            .iter()
+            .map(|arg| {
+                let flags = match &*arg.ty {
+                    _ if arg.retval => PARAMFLAG_FOUT | PARAMFLAG_FRETVAL,
+                    syn::Type::Ptr(ptr) if ptr.mutability.is_some() => PARAMFLAG_FOUT,
+                    _ => PARAMFLAG_FIN,
+                };
+                Ok(ParamDesc {
+                    name: match &*arg.pat {
+                        syn::Pat::Ident(pat) => Some(pat.ident.to_string()),
+                        _ => None,
+                    },
+                    ty: self.type_desc(&arg.ty)?,
+                    flags,
+                })
+            })
+            .collect()
+    }
+
+    /// The `DISPID`s of the methods of dual interfaces are those assigned by
+    /// `#[dispatch]` classes, which number the methods of all their interfaces
+    fn assign_dispids(&self, type_infos: &mut [TypeInfo]) -> syn::Result<()> {
+        // The class which assigned the DISPIDs of each dual interface
+        let mut assigned: HashMap<usize, &syn::Ident> = HashMap::new();
+        for class in self.declarations.classes.iter().filter(|c| c.dispatch) {
+            let mut dispids = HashMap::new();
+            let mut names = std::collections::HashSet::new();
+            let mut dispid = 1;
+            for interface in &class.interfaces {
+                let chain = interface.iter_chain().collect::<Vec<_>>();
+                for path in chain.into_iter().rev() {
+                    if IMPLICIT_INTERFACES.contains(&last_ident(path).as_str()) {
+                        continue;
+                    }
+                    let index = self.indices.get(&last_ident(path)).copied();
+                    for method in class.methods.get(path).into_iter().flatten() {
+                        let name = method.original_ident.to_string();
+                        if !names.insert(name.to_lowercase()) {
+                            continue;
+                        }
+                        if let Some(index) = index {
+                            dispids.insert((index, name), dispid);
+                        }
+                        dispid += 1;
+                    }
+                }
+            }
+
+            for (index, info) in type_infos.iter_mut().enumerate() {
+                if !info.is_dual() || !dispids.keys().any(|(i, _)| *i == index) {
+                    continue;
+                }
+                let interface = &self.declarations.interfaces[index];
+                let mut changed = false;
+                for (func, method) in info.funcs.iter_mut().zip(&interface.methods) {
+                    if let Some(dispid) = dispids.get(&(index, method.name.to_string())) {
+                        changed |= func.memid != *dispid;
+                        func.memid = *dispid;
+                    }
+                }
+                match assigned.get(&index) {
+                    Some(other) if changed => {
+                        return Err(syn::Error::new(
+                            class.name.span(),
+                            format!(
+                                "the DISPIDs of `{}` differ between the #[dispatch] classes `{}` and `{}`",
+                                info.name, other, class.name
+                            ),
+                        ))
+                    }
+                    _ => {
+                        assigned.insert(index, &class.name);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn coclass(&self, class: &Class, guid: Guid) -> syn::Result<TypeInfo> {
+        let mut impl_types = Vec::new();
+        for interface in &class.interfaces {
+            let name = last_ident(&interface.path);
+            if IMPLICIT_INTERFACES.contains(&name.as_str()) {
+                continue;
+            }
+            let index = self.indices.get(&name).ok_or_else(|| {
+                syn::Error::new(
+                    interface.path.span(),
+                    format!(
+                        "interface `{}` of class `{}` is not declared in the input",
+                        name, class.name
+                    ),
+                )
+            })?;
+            impl_types.push(ImplType {
+                reference: TypeRef::Local(*index),
+                flags: if impl_types.is_empty() {
+                    IMPLTYPEFLAG_FDEFAULT
+                } else {
+                    0
+                },
+            });
+        }
+        Ok(TypeInfo {
+            name: class.name.to_string(),
+            doc_string: doc_string(&class.docs),
+            guid: Some(guid),
+            kind: TypeKind::Coclass,
+            flags: if class.has_class_factory {
+                TYPEFLAG_FCANCREATE
+            } else {
+                0
+            },
+            major_version: 0,
+            minor_version: 0,
+            size: 0,
+            alignment: 4,
+            vtable_size: 0,
+            impl_types,
+            alias: None,
+            funcs: Vec::new(),
+            vars: Vec::new(),
+        })
+    }
+
+    fn interface_ref(&self, path: &syn::Path) -> syn::Result<TypeRef> {
+        let name = last_ident(path);
+        if let Some(index) = self.indices.get(&name) {
+            return Ok(TypeRef::Local(*index));
+        }
+        match STDOLE_INTERFACES.iter().find(|(n, _, _)| *n == name) {
+            Some((_, iid, _)) => Ok(TypeRef::Imported {
+                guid: Guid::from_str(iid).unwrap(),
+                file: STDOLE.to_owned(),
+            }),
+            None => Err(syn::Error::new(
+                path.span(),
+                format!("interface `{}` is not declared in the input", name),
+            )),
+        }
+    }
+
+    /// The type description of a Rust type
+    fn type_desc(&self, ty: &syn::Type) -> syn::Result<TypeDesc> {
+        let path = match ty {
+            syn::Type::Ptr(ptr) => return Ok(TypeDesc::Ptr(Box::new(self.type_desc(&ptr.elem)?))),
+            syn::Type::Paren(paren) => return self.type_desc(&paren.elem),
+            syn::Type::Group(group) => return self.type_desc(&group.elem),
+            syn::Type::Path(path) if path.qself.is_none() => &path.path,
+            _ => return Err(unsupported(ty)),
+        };
+        let segment = path.segments.last().unwrap();
+        let name = segment.ident.to_string();
+        let argument = match &segment.arguments {
+            syn::PathArguments::AngleBracketed(arguments) => match arguments.args.first() {
+                Some(syn::GenericArgument::Type(argument)) => Some(argument),
+                _ => None,
+            },
+            _ => None,
+        };
+        match (name.as_str(), argument) {
+            // Interfaces are passed as pointers, whether or not they are optional
+            ("Option", Some(argument)) => match argument {
+                syn::Type::Path(inner) if self.is_interface(&inner.path) => {
+                    self.type_desc(argument)
+                }
+                _ => Err(unsupported(ty)),
+            },
+            ("SafeArray", Some(element)) => {
+                Ok(TypeDesc::SafeArray(Box::new(self.type_desc(element)?)))
+            }
+            ("IUnknown", None) => Ok(TypeDesc::Base(VT_UNKNOWN)),
+            ("IDispatch", None) => Ok(TypeDesc::Base(VT_DISPATCH)),
+            (_, None) if self.indices.contains_key(&name) => Ok(TypeDesc::Ptr(Box::new(
+                TypeDesc::UserDefined(TypeRef::Local(self.indices[&name])),
+            ))),
+            (_, None) => base_type(&name)
+                .map(TypeDesc::Base)
+                .ok_or_else(|| unsupported(ty)),
+            _ => Err(unsupported(ty)),
+        }
+    }
+
+    fn is_interface(&self, path: &syn::Path) -> bool {
+        let name = last_ident(path);
+        self.indices.contains_key(&name) || name == "IUnknown" || name == "IDispatch"
+    }
+}
+
+fn last_ident(path: &syn::Path) -> String {
+    path.segments.last().unwrap().ident.to_string()
+}
+
+/// The `VARTYPE` of a Rust type or of one of the Windows type aliases
+fn base_type(name: &str) -> Option<u16> {
+    Some(match name {
+        "bool" | "u8" | "BYTE" | "UCHAR" => VT_UI1,
+        "i8" | "CHAR" => VT_I1,
+        "i16" | "SHORT" => VT_I2,
+        "u16" | "USHORT" | "WORD" => VT_UI2,
+        "i32" | "LONG" | "BOOL" | "DISPID" => VT_I4,
+        "u32" | "ULONG" | "DWORD" | "LCID" => VT_UI4,
+        "i64" | "LONGLONG" => VT_I8,
+        "u64" | "ULONGLONG" => VT_UI8,
+        "INT" => VT_INT,
+        "UINT" => VT_UINT,
+        "isize" | "INT_PTR" => VT_INT_PTR,
+        "usize" | "UINT_PTR" | "SIZE_T" => VT_UINT_PTR,
+        "f32" | "FLOAT" => VT_R4,
+        "f64" | "DOUBLE" => VT_R8,
+        "DATE" => VT_DATE,
+        "c_void" => VT_VOID,
+        "HRESULT" | "HResult" => VT_HRESULT,
+        "SCODE" => VT_ERROR,
+        "BStr" | "BSTR" => VT_BSTR,
+        "Variant" | "VARIANT" => VT_VARIANT,
+        "VARIANT_BOOL" => VT_BOOL,
+        "LPSTR" => VT_LPSTR,
+        "LPWSTR" | "LPOLESTR" => VT_LPWSTR,
+        _ => return None,
+    })
+}
+
+/// The first paragraph of the documentation of an item, on one line
+fn doc_string(attributes: &[syn::Attribute]) -> Option<String> {
+    let mut lines = Vec::new();
+    for attribute in attributes.iter().filter(|a| a.path.is_ident("doc")) {
+        if let Ok(syn::Meta::NameValue(syn::MetaNameValue {
+            lit: syn::Lit::Str(lit),
+            ..
+        })) = attribute.parse_meta()
+        {
+            lines.push(lit.value().trim().to_owned());
+        }
+    }
+    let paragraph = lines
+        .into_iter()
+        .skip_while(|line| line.is_empty())
+        .take_while(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    if paragraph.is_empty() {
+        None
+    } else {
+        Some(paragraph.join(" "))
+    }
+}
+
+fn unsupported(ty: &syn::Type) -> syn::Error {
+    syn::Error::new(
+        ty.span(),
+        "type cannot be described in a type library: only integers, floating point numbers, \
+         HRESULT, BStr, Variant, SafeArray, interfaces and pointers to those are",
+    )
+}
@@ -0,0 +1,244 @@
+//! Conversion of type libraries to Rust, through the declarations of the IDL
+//! converter
+use super::{Error, FuncDesc, InvokeKind, TypeDesc, TypeInfo, TypeKind, TypeLib, TypeRef};
+use super::{Value, VarKind};
+use crate::idl::lexer::TokenKind;
+use crate::idl::parser::{Enum, Expr, Field, Idl, Interface, Item, Method, Param, Struct, Type};
+use std::collections::HashSet;
+
+/// The interfaces of `com::interfaces` which type libraries import, by IID
+const COM_INTERFACES: &[(&str, &str)] = &[
+    ("00000000-0000-0000-C000-000000000046", "IUnknown"),
+    ("00000001-0000-0000-C000-000000000046", "IClassFactory"),
+    ("00020400-0000-0000-C000-000000000046", "IDispatch"),
+];
+
+/// Generate Rust source using `com::interfaces!` for the declarations of `lib`
+pub fn generate(lib: &TypeLib) -> Result<String, Error> {
+    let mut idl = Idl::default();
+    // Declare the interfaces first, so that they are known when converting types
+    for info in &lib.type_infos {
+        if has_vtable(info) {
+            idl.items.push(Item::ForwardInterface(info.name.clone()));
+        }
+    }
+    for info in &lib.type_infos {
+        if let Some(item) = item(lib, info)? {
+            idl.items.push(item);
+        }
+    }
+    let origin = format!("Generated from the type library {} by com-tlb", lib.name);
+    // The interfaces of the imported type libraries are those of `com::interfaces`
+    crate::idl::rust::generate(&idl, &HashSet::new(), &origin)
+        .map_err(|e| Error::new(None, e.to_string()))
+}
+
+/// Whether the type info is an interface or the dispinterface of a dual interface
+pub(super) fn has_vtable(info: &TypeInfo) -> bool {
+    info.kind == TypeKind::Interface || (info.kind == TypeKind::Dispatch && info.is_dual())
+}
+
+fn item(lib: &TypeLib, info: &TypeInfo) -> Result<Option<Item>, Error> {
+    let item = match info.kind {
+        TypeKind::Enum => Item::Enum(Enum {
+            name: info.name.clone(),
+            variants: info
+                .vars
+                .iter()
+                .filter(|v| v.kind == VarKind::Const)
+                .map(|v| Ok((v.name.clone(), Some(value(info, v.value.as_ref())?))))
+                .collect::<Result<_, Error>>()?,
+        }),
+        TypeKind::Record | TypeKind::Union => {
+            let mut fields = Vec::new();
+            for var in info.vars.iter().filter(|v| v.kind == VarKind::PerInstance) {
+                let (ty, dims) = match &var.ty {
+                    TypeDesc::CArray(element, dims) => (element.as_ref(), dims.clone()),
+                    ty => (ty, Vec::new()),
+                };
+                fields.push(Field {
+                    name: var.name.clone(),
+                    ty: c_type(lib, ty)?,
+                    dims: dims
+                        .iter()
+                        .map(|n| Expr(vec![TokenKind::Number(n.to_string())]))
+                        .collect(),
+                });
+            }
+            Item::Struct(Struct {
+                name: info.name.clone(),
+                is_union: info.kind == TypeKind::Union,
+                fields,
+            })
+        }
+        TypeKind::Alias => match &info.alias {
+            Some(alias) => Item::Typedef(info.name.clone(), c_type(lib, alias)?),
+            None => return Ok(None),
+        },
+        TypeKind::Coclass => match &info.guid {
+            Some(guid) => Item::Coclass(info.name.clone(), guid.to_string()),
+            None => return Ok(None),
+        },
+        _ if has_vtable(info) => Item::Interface(interface(lib, info)?),
+        TypeKind::Module | TypeKind::Dispatch | TypeKind::Interface => return Ok(None),
+    };
+    Ok(Some(item))
+}
+
+fn interface(lib: &TypeLib, info: &TypeInfo) -> Result<Interface, Error> {
+    let guid = info
+        .guid
+        .as_ref()
+        .ok_or_else(|| Error::new(None, format!("interface `{}` has no GUID", info.name)))?;
+    let parent = match info.ref_type_of_impl_type(0) {
+        Some(parent) => Some(ref_name(lib, parent)?),
+        None => None,
+    };
+    let mut funcs = info.funcs.iter().collect::<Vec<_>>();
+    funcs.sort_by_key(|f| f.vtable_offset);
+    Ok(Interface {
+        name: info.name.clone(),
+        line: 0,
+        uuid: guid.to_string(),
+        parent,
+        methods: funcs
+            .into_iter()
+            .map(|f| method(lib, f))
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+fn method(lib: &TypeLib, func: &FuncDesc) -> Result<Method, Error> {
+    let prefix = match func.invoke_kind {
+        InvokeKind::Func => "",
+        InvokeKind::PropertyGet => "get_",
+        InvokeKind::PropertyPut => "put_",
+        InvokeKind::PropertyPutRef => "putref_",
+    };
+    Ok(Method {
+        name: format!("{}{}", prefix, func.name),
+        ret: c_type(lib, &func.ret)?,
+        params: func
+            .params
+            .iter()
+            .map(|p| {
+                Ok(Param {
+                    name: p.name.clone(),
+                    ty: c_type(lib, &p.ty)?,
+                    retval: p.is_retval(),
+                    // Type libraries do not record array lengths
+                    size_is: None,
+                })
+            })
+            .collect::<Result<_, Error>>()?,
+    })
+}
+
+/// The C type of a type description, as understood by the IDL converter
+fn c_type(lib: &TypeLib, ty: &TypeDesc) -> Result<Type, Error> {
+    let (name, pointers) = match ty {
+        TypeDesc::Base(vt) => match base_type(*vt) {
+            Some(name) => (name.to_owned(), 0),
+            None if *vt == 9 => ("IDispatch".to_owned(), 1),
+            None if *vt == 13 => ("IUnknown".to_owned(), 1),
+            None => return Err(Error::new(None, format!("VARTYPE {} is not supported", vt))),
+        },
+        TypeDesc::Ptr(ty) | TypeDesc::CArray(ty, _) => {
+            let mut ty = c_type(lib, ty)?;
+            ty.pointers.push(false);
+            return Ok(ty);
+        }
+        TypeDesc::SafeArray(_) => ("SAFEARRAY".to_owned(), 1),
+        TypeDesc::UserDefined(reference) => (ref_name(lib, reference)?, 0),
+    };
+    Ok(Type {
+        name,
+        is_const: false,
+        pointers: vec![false; pointers],
+    })
+}
+
+/// The IDL name of a `VARTYPE` which is not a pointer
+pub(super) fn base_type(vt: u16) -> Option<&'static str> {
+    Some(match vt {
+        2 => "SHORT",
+        3 => "LONG",
+        4 => "FLOAT",
+        5 => "DOUBLE",
+        6 | 20 => "LONGLONG",
+        7 => "DATE",
+        8 => "BSTR",
+        10 => "LONG",
+        11 => "VARIANT_BOOL",
+        12 => "VARIANT",
+        16 => "CHAR",
+        17 => "BYTE",
+        18 => "USHORT",
+        19 => "ULONG",
+        21 => "ULONGLONG",
+        22 => "INT",
+        23 => "UINT",
+        24 => "void",
+        25 => "HRESULT",
+        30 => "LPSTR",
+        31 => "LPWSTR",
+        37 => "INT_PTR",
+        38 => "UINT_PTR",
+        _ => return None,
+    })
+}
+
+/// The name of a referenced type info. A coclass stands for its default interface.
+pub(super) fn ref_name(lib: &TypeLib, reference: &TypeRef) -> Result<String, Error> {
+    let reference = match lib.ref_type_info(reference) {
+        Some(info) if info.kind == TypeKind::Coclass => {
+            default_interface(info).unwrap_or(reference)
+        }
+        _ => reference,
+    };
+    match reference {
+        TypeRef::Local(index) => Ok(lib.type_infos[*index].name.clone()),
+        TypeRef::Imported { guid, file } => {
+            let guid = guid.to_string();
+            COM_INTERFACES
+                .iter()
+                .find(|(iid, _)| *iid == guid)
+                .map(|(_, name)| (*name).to_owned())
+                .ok_or_else(|| {
+                    Error::new(
+                        None,
+                        format!(
+                            "type {{{}}} imported from `{}` is not supported",
+                            guid, file
+                        ),
+                    )
+                })
+        }
+    }
+}
+
+/// The default interface of a coclass
+fn default_interface(coclass: &TypeInfo) -> Option<&TypeRef> {
+    const IMPLTYPEFLAG_FDEFAULT: u32 = 0x1;
+    const IMPLTYPEFLAG_FSOURCE: u32 = 0x2;
+    coclass
+        .impl_types
+        .iter()
+        .find(|i| i.flags & (IMPLTYPEFLAG_FDEFAULT | IMPLTYPEFLAG_FSOURCE) == IMPLTYPEFLAG_FDEFAULT)
+        .map(|i| &i.reference)
+}
+
+/// The value of an enum variant as an expression
+fn value(info: &TypeInfo, value: Option<&Value>) -> Result<Expr, Error> {
+    match value {
+        Some(Value::Int(n)) if *n < 0 => Ok(Expr(vec![
+            TokenKind::Punct('-'),
+            TokenKind::Number(n.unsigned_abs().to_string()),
+        ])),
+        Some(Value::Int(n)) => Ok(Expr(vec![TokenKind::Number(n.to_string())])),
+        _ => Err(Error::new(
+            None,
+            format!("enum `{}` has a value which is not an integer", info.name),
+        )),
+    }
+}
@@ -0,0 +1,344 @@
+//! Generation of MIDL source describing a type library
+//!
+//! The type infos are declared in a `library` block in their order in the type
+//! library, after forward declarations of the interfaces. Types are named as they
+//! are by the Rust conversion, so that converting the generated IDL with
+//! `idl::convert` gives the same declarations as converting the type library.
+use super::convert::{base_type, has_vtable, ref_name};
+use super::{Error, FuncDesc, InvokeKind, ParamDesc, TypeDesc, TypeInfo, TypeKind, TypeLib};
+use super::{Value, VarDesc, VarKind};
+use std::fmt::Write;
+
+const TYPEFLAG_FCANCREATE: u32 = 0x2;
+const TYPEFLAG_FOLEAUTOMATION: u32 = 0x100;
+const IMPLTYPEFLAG_FDEFAULT: u32 = 0x1;
+const IMPLTYPEFLAG_FSOURCE: u32 = 0x2;
+/// The `PARAMFLAG_*` values and the corresponding attributes, in the order MIDL
+/// expects them
+const PARAM_ATTRIBUTES: &[(u16, &str)] = &[
+    (0x1, "in"),
+    (0x2, "out"),
+    (0x4, "lcid"),
+    (0x8, "retval"),
+    (0x10, "optional"),
+];
+
+pub fn generate(lib: &TypeLib) -> Result<String, Error> {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "/* Generated from the type library {}. Do not edit. */\n",
+        lib.name
+    )
+    .unwrap();
+    // The declarations of the OLE Automation types and of `IDispatch`
+    out.push_str("import \"oaidl.idl\";\n\n");
+
+    let mut attributes = Vec::new();
+    if let Some(guid) = &lib.guid {
+        attributes.push(format!("uuid({})", guid));
+    }
+    attributes.push(format!(
+        "version({}.{})",
+        lib.major_version, lib.minor_version
+    ));
+    if lib.lcid != 0 {
+        attributes.push(format!("lcid({:#06x})", lib.lcid));
+    }
+    attributes.extend(help_string(lib.doc_string.as_deref()));
+    writeln!(out, "[{}]\nlibrary {}\n{{", attributes.join(", "), lib.name).unwrap();
+    for import in &lib.imports {
+        writeln!(out, "    importlib(\"{}\");", escape(&import.file)).unwrap();
+    }
+
+    let interfaces = lib.type_infos.iter().filter(|i| has_vtable(i));
+    let mut forward = interfaces.map(|i| i.name.as_str()).peekable();
+    if forward.peek().is_some() {
+        out.push('\n');
+        for name in forward {
+            writeln!(out, "    interface {};", name).unwrap();
+        }
+    }
+
+    for info in &lib.type_infos {
+        out.push('\n');
+        type_info(&mut out, lib, info)?;
+    }
+    out.push_str("};\n");
+    Ok(out)
+}
+
+fn type_info(out: &mut String, lib: &TypeLib, info: &TypeInfo) -> Result<(), Error> {
+    let mut attributes = Vec::new();
+    if let Some(guid) = &info.guid {
+        attributes.push(format!("uuid({})", guid));
+    }
+    attributes.extend(help_string(info.doc_string.as_deref()));
+    match info.kind {
+        TypeKind::Enum => {
+            writeln!(
+                out,
+                "    typedef{} enum {}\n    {{",
+                typedef_attributes(&attributes),
+                info.name
+            )
+            .unwrap();
+            let variants = info.vars.iter().filter(|v| v.kind == VarKind::Const);
+            let variants = variants.collect::<Vec<_>>();
+            for (index, var) in variants.iter().enumerate() {
+                let separator = if index + 1 < variants.len() { "," } else { "" };
+                writeln!(
+                    out,
+                    "        {} = {}{}",
+                    var.name,
+                    value(info, var)?,
+                    separator
+                )
+                .unwrap();
+            }
+            writeln!(out, "    }} {};", info.name).unwrap();
+        }
+        TypeKind::Record | TypeKind::Union => {
+            let keyword = if info.kind == TypeKind::Union {
+                "union"
+            } else {
+                "struct"
+            };
+            writeln!(
+                out,
+                "    typedef{} {} {}\n    {{",
+                typedef_attributes(&attributes),
+                keyword,
+                info.name
+            )
+            .unwrap();
+            for var in info.vars.iter().filter(|v| v.kind == VarKind::PerInstance) {
+                let (ty, dims) = match &var.ty {
+                    TypeDesc::CArray(element, dims) => (element.as_ref(), dims.as_slice()),
+                    ty => (ty, &[][..]),
+                };
+                let dims = dims.iter().map(|n| format!("[{}]", n)).collect::<String>();
+                let attributes = help_string(var.doc_string.as_deref())
+                    .map(|a| format!("[{}] ", a))
+                    .unwrap_or_default();
+                writeln!(
+                    out,
+                    "        {}{}{};",
+                    attributes,
+                    declaration(lib, ty, &var.name)?,
+                    dims
+                )
+                .unwrap();
+            }
+            writeln!(out, "    }} {};", info.name).unwrap();
+        }
+        TypeKind::Alias => {
+            let alias = info
+                .alias
+                .as_ref()
+                .ok_or_else(|| Error::new(None, format!("alias `{}` has no type", info.name)))?;
+            writeln!(
+                out,
+                "    typedef{} {};",
+                typedef_attributes(&attributes),
+                declaration(lib, alias, &info.name)?
+            )
+            .unwrap();
+        }
+        TypeKind::Dispatch if !info.is_dual() => {
+            writeln!(
+                out,
+                "    [{}]\n    dispinterface {}\n    {{",
+                attributes.join(", "),
+                info.name
+            )
+            .unwrap();
+            out.push_str("    properties:\n");
+            for var in &info.vars {
+                writeln!(
+                    out,
+                    "        [id({})] {};",
+                    var.memid,
+                    declaration(lib, &var.ty, &var.name)?
+                )
+                .unwrap();
+            }
+            out.push_str("    methods:\n");
+            for func in &info.funcs {
+                method(out, lib, func, true)?;
+            }
+            out.push_str("    };\n");
+        }
+        TypeKind::Interface | TypeKind::Dispatch => {
+            attributes.insert(0, "object".to_owned());
+            if info.is_dual() {
+                attributes.push("dual".to_owned());
+            }
+            if info.flags & TYPEFLAG_FOLEAUTOMATION != 0 {
+                attributes.push("oleautomation".to_owned());
+            }
+            writeln!(out, "    [{}]", attributes.join(", ")).unwrap();
+            match info.ref_type_of_impl_type(0) {
+                Some(parent) => writeln!(
+                    out,
+                    "    interface {} : {}",
+                    info.name,
+                    ref_name(lib, parent)?
+                ),
+                None => writeln!(out, "    interface {}", info.name),
+            }
+            .unwrap();
+            out.push_str("    {\n");
+            let mut funcs = info.funcs.iter().collect::<Vec<_>>();
+            funcs.sort_by_key(|f| f.vtable_offset);
+            for func in funcs {
+                method(out, lib, func, info.kind == TypeKind::Dispatch)?;
+            }
+            out.push_str("    };\n");
+        }
+        TypeKind::Coclass => {
+            if info.flags & TYPEFLAG_FCANCREATE == 0 {
+                attributes.push("noncreatable".to_owned());
+            }
+            writeln!(
+                out,
+                "    [{}]\n    coclass {}\n    {{",
+                attributes.join(", "),
+                info.name
+            )
+            .unwrap();
+            for impl_type in &info.impl_types {
+                let mut attributes = Vec::new();
+                if impl_type.flags & IMPLTYPEFLAG_FDEFAULT != 0 {
+                    attributes.push("default");
+                }
+                if impl_type.flags & IMPLTYPEFLAG_FSOURCE != 0 {
+                    attributes.push("source");
+                }
+                let attributes = if attributes.is_empty() {
+                    String::new()
+                } else {
+                    format!("[{}] ", attributes.join(", "))
+                };
+                let name = ref_name(lib, &impl_type.reference)?;
+                writeln!(out, "        {}interface {};", attributes, name).unwrap();
+            }
+            out.push_str("    };\n");
+        }
+        TypeKind::Module => {
+            return Err(Error::new(
+                None,
+                format!("module `{}` cannot be declared in IDL", info.name),
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn method(out: &mut String, lib: &TypeLib, func: &FuncDesc, with_id: bool) -> Result<(), Error> {
+    let mut attributes = Vec::new();
+    if with_id {
+        attributes.push(format!("id({})", func.memid));
+    }
+    match func.invoke_kind {
+        InvokeKind::Func => {}
+        InvokeKind::PropertyGet => attributes.push("propget".to_owned()),
+        InvokeKind::PropertyPut => attributes.push("propput".to_owned()),
+        InvokeKind::PropertyPutRef => attributes.push("propputref".to_owned()),
+    }
+    attributes.extend(help_string(func.doc_string.as_deref()));
+    let params = func
+        .params
+        .iter()
+        .map(|p| param(lib, p))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let params = if params.is_empty() {
+        "void".to_owned()
+    } else {
+        params.join(", ")
+    };
+    out.push_str("        ");
+    if !attributes.is_empty() {
+        write!(out, "[{}] ", attributes.join(", ")).unwrap();
+    }
+    writeln!(
+        out,
+        "{}({});",
+        declaration(lib, &func.ret, &func.name)?,
+        params
+    )
+    .unwrap();
+    Ok(())
+}
+
+fn param(lib: &TypeLib, param: &ParamDesc) -> Result<String, Error> {
+    let attributes = PARAM_ATTRIBUTES
+        .iter()
+        .filter(|(flag, _)| param.flags & flag != 0)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>();
+    let declaration = declaration(lib, &param.ty, param.name.as_deref().unwrap_or_default())?;
+    if attributes.is_empty() {
+        Ok(declaration)
+    } else {
+        Ok(format!("[{}] {}", attributes.join(", "), declaration))
+    }
+}
+
+/// A C declaration of `name`, e.g. `IAlarm **alarm`
+fn declaration(lib: &TypeLib, ty: &TypeDesc, name: &str) -> Result<String, Error> {
+    let (base, pointers) = idl_type(lib, ty)?;
+    let declaration = format!("{} {}{}", base, "*".repeat(pointers), name);
+    Ok(declaration.trim_end().to_owned())
+}
+
+/// The name of the base type of `ty` and the number of pointers to it
+fn idl_type(lib: &TypeLib, ty: &TypeDesc) -> Result<(String, usize), Error> {
+    Ok(match ty {
+        TypeDesc::Base(vt) => match base_type(*vt) {
+            Some(name) => (name.to_owned(), 0),
+            None if *vt == 9 => ("IDispatch".to_owned(), 1),
+            None if *vt == 13 => ("IUnknown".to_owned(), 1),
+            None => return Err(Error::new(None, format!("VARTYPE {} is not supported", vt))),
+        },
+        // Array parameters are pointers
+        TypeDesc::Ptr(ty) | TypeDesc::CArray(ty, _) => {
+            let (base, pointers) = idl_type(lib, ty)?;
+            (base, pointers + 1)
+        }
+        TypeDesc::SafeArray(element) => {
+            let (base, pointers) = idl_type(lib, element)?;
+            let element = format!("{} {}", base, "*".repeat(pointers));
+            (format!("SAFEARRAY({})", element.trim_end()), 0)
+        }
+        TypeDesc::UserDefined(reference) => (ref_name(lib, reference)?, 0),
+    })
+}
+
+/// The attributes of a `typedef`, which follow the keyword
+fn typedef_attributes(attributes: &[String]) -> String {
+    if attributes.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", attributes.join(", "))
+    }
+}
+
+fn help_string(doc: Option<&str>) -> Option<String> {
+    doc.map(|doc| format!("helpstring(\"{}\")", escape(doc)))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The value of an enum constant
+fn value(info: &TypeInfo, var: &VarDesc) -> Result<i64, Error> {
+    match var.value {
+        Some(Value::Int(n)) => Ok(n),
+        _ => Err(Error::new(
+            None,
+            format!("enum `{}` has a value which is not an integer", info.name),
+        )),
+    }
+}
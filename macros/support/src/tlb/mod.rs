@@ -0,0 +1,425 @@
+//! Reading and writing of type libraries (`.tlb` files), and conversion from and to
+//! `interfaces!` declarations
+//!
+//! [`TypeLib::parse`] reads the MSFT format written by MIDL and `ICreateTypeLib2`
+//! from a byte slice, without any Windows API. The result is a view of the type
+//! library following `ITypeLib` and `ITypeInfo`: the type infos with their kind,
+//! GUID, functions (with their vtable offsets), variables and implemented interfaces.
+//!
+//! [`generate`] produces the same Rust source as [`crate::idl::convert`] would for the
+//! IDL the type library was compiled from: `interfaces!` declarations for interfaces
+//! and dual interfaces, records, enums, aliases and the CLSIDs of coclasses. Pure
+//! dispinterfaces and modules have no vtable and are skipped.
+//!
+//! Types imported from other type libraries are only known by their GUID. The
+//! interfaces of `com::interfaces` are recognized, other imported types cannot be
+//! converted.
+//!
+//! Going the other way, [`build()`] describes the interfaces and classes declared
+//! with `interfaces!` and `class!` as a [`TypeLib`], which [`TypeLib::to_bytes`]
+//! writes in the MSFT format and [`TypeLib::to_idl`] declares in MIDL, for the
+//! consumers of a COM server which need its type library.
+mod build;
+mod convert;
+mod midl;
+mod reader;
+#[cfg(test)]
+mod tests;
+mod writer;
+
+pub use build::{build, Declarations, Library};
+pub use convert::generate;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Convert the bytes of a type library to Rust source using `com::interfaces!`
+pub fn convert(bytes: &[u8]) -> Result<String, Error> {
+    generate(&TypeLib::parse(bytes)?)
+}
+
+/// A type library, the equivalent of `ITypeLib`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeLib {
+    pub name: String,
+    pub doc_string: Option<String>,
+    pub guid: Option<Guid>,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub lcid: u32,
+    pub syskind: SysKind,
+    /// The type libraries which type infos are imported from
+    pub imports: Vec<ImportedLib>,
+    pub type_infos: Vec<TypeInfo>,
+}
+
+impl TypeLib {
+    /// Read a type library in the MSFT format
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        reader::read(bytes)
+    }
+
+    /// Write the type library in the MSFT format
+    ///
+    /// The name hash table is left empty, which `LoadTypeLib` does not rely on, but
+    /// `ITypeLib::IsName` and `FindName` may not find names in the written file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        writer::write(self)
+    }
+
+    /// The MIDL source of a `library` block declaring the contents of the type
+    /// library, which MIDL compiles back into an equivalent type library
+    pub fn to_idl(&self) -> Result<String, Error> {
+        midl::generate(self)
+    }
+
+    /// The number of type infos, as returned by `ITypeLib::GetTypeInfoCount`
+    pub fn type_info_count(&self) -> usize {
+        self.type_infos.len()
+    }
+
+    pub fn type_info(&self, index: usize) -> Option<&TypeInfo> {
+        self.type_infos.get(index)
+    }
+
+    pub fn type_info_of_guid(&self, guid: &Guid) -> Option<&TypeInfo> {
+        self.type_infos
+            .iter()
+            .find(|t| t.guid.as_ref() == Some(guid))
+    }
+
+    /// The type info with the given name, compared case-insensitively as
+    /// `ITypeLib::FindName` does
+    pub fn find_name(&self, name: &str) -> Option<&TypeInfo> {
+        self.type_infos
+            .iter()
+            .find(|t| t.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The type info referenced by `reference`, if it is declared in this library
+    pub fn ref_type_info(&self, reference: &TypeRef) -> Option<&TypeInfo> {
+        match reference {
+            TypeRef::Local(index) => self.type_infos.get(*index),
+            TypeRef::Imported { .. } => None,
+        }
+    }
+}
+
+/// A type library which type infos are imported from
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedLib {
+    /// The file name of the type library, e.g. `stdole2.tlb`
+    pub file: String,
+    pub guid: Option<Guid>,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub lcid: u32,
+}
+
+/// A type description, the equivalent of `ITypeInfo`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeInfo {
+    pub name: String,
+    pub doc_string: Option<String>,
+    pub guid: Option<Guid>,
+    pub kind: TypeKind,
+    /// The `TYPEFLAGS` of the type info
+    pub flags: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+    /// The size of an instance, in bytes
+    pub size: u32,
+    pub alignment: u16,
+    /// The size of the vtable of an interface, in bytes
+    pub vtable_size: u16,
+    /// The interfaces implemented by a coclass, or the parent of an interface. For
+    /// dispinterfaces, this is the interface the vtable part of a dual interface
+    /// inherits from.
+    pub impl_types: Vec<ImplType>,
+    /// The aliased type of an alias
+    pub alias: Option<TypeDesc>,
+    pub funcs: Vec<FuncDesc>,
+    pub vars: Vec<VarDesc>,
+}
+
+impl TypeInfo {
+    pub fn is_dual(&self) -> bool {
+        self.flags & TYPEFLAG_FDUAL != 0
+    }
+
+    pub fn func_desc(&self, index: usize) -> Option<&FuncDesc> {
+        self.funcs.get(index)
+    }
+
+    pub fn var_desc(&self, index: usize) -> Option<&VarDesc> {
+        self.vars.get(index)
+    }
+
+    pub fn ref_type_of_impl_type(&self, index: usize) -> Option<&TypeRef> {
+        self.impl_types.get(index).map(|i| &i.reference)
+    }
+
+    /// The name of the member with the given id followed by the names of its
+    /// parameters, as returned by `ITypeInfo::GetNames`
+    pub fn names(&self, memid: i32) -> Vec<&str> {
+        if let Some(func) = self.funcs.iter().find(|f| f.memid == memid) {
+            let mut names = vec![func.name.as_str()];
+            names.extend(func.params.iter().map_while(|p| p.name.as_deref()));
+            return names;
+        }
+        self.vars
+            .iter()
+            .filter(|v| v.memid == memid)
+            .map(|v| v.name.as_str())
+            .take(1)
+            .collect()
+    }
+
+    /// The id of the member with the given name, compared case-insensitively as
+    /// `ITypeInfo::GetIDsOfNames` does
+    pub fn id_of_name(&self, name: &str) -> Option<i32> {
+        let funcs = self.funcs.iter().map(|f| (&f.name, f.memid));
+        let vars = self.vars.iter().map(|v| (&v.name, v.memid));
+        funcs
+            .chain(vars)
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, memid)| memid)
+    }
+}
+
+/// `TYPEFLAG_FDUAL`
+pub const TYPEFLAG_FDUAL: u32 = 0x40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeKind {
+    Enum,
+    Record,
+    Module,
+    Interface,
+    Dispatch,
+    Coclass,
+    Alias,
+    Union,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SysKind {
+    Win16,
+    Win32,
+    Mac,
+    Win64,
+}
+
+impl SysKind {
+    /// The size of pointers, and therefore of vtable entries, in bytes
+    pub fn pointer_size(self) -> usize {
+        match self {
+            SysKind::Win16 => 2,
+            SysKind::Win32 | SysKind::Mac => 4,
+            SysKind::Win64 => 8,
+        }
+    }
+}
+
+/// An interface implemented by a type info
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImplType {
+    pub reference: TypeRef,
+    /// The `IMPLTYPEFLAGS` of the interface in a coclass
+    pub flags: u32,
+}
+
+/// A reference to a type info, the equivalent of `HREFTYPE`
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeRef {
+    /// The type info with the given index in this library
+    Local(usize),
+    /// A type info of another type library, which is only known by its GUID
+    Imported { guid: Guid, file: String },
+}
+
+/// The type of a function, parameter or variable, the equivalent of `TYPEDESC`
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeDesc {
+    /// A type which is not constructed from another one, identified by its `VARTYPE`
+    Base(u16),
+    Ptr(Box<TypeDesc>),
+    SafeArray(Box<TypeDesc>),
+    /// A C array, with the number of elements of each dimension
+    CArray(Box<TypeDesc>, Vec<u32>),
+    UserDefined(TypeRef),
+}
+
+/// A function, the equivalent of `FUNCDESC`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncDesc {
+    pub memid: i32,
+    pub name: String,
+    pub doc_string: Option<String>,
+    pub kind: FuncKind,
+    pub invoke_kind: InvokeKind,
+    /// The `CALLCONV` of the function
+    pub call_conv: u8,
+    /// The `FUNCFLAGS` of the function
+    pub flags: u16,
+    /// The offset of the function in the vtable, in bytes
+    pub vtable_offset: i16,
+    pub ret: TypeDesc,
+    pub params: Vec<ParamDesc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuncKind {
+    Virtual,
+    PureVirtual,
+    NonVirtual,
+    Static,
+    Dispatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvokeKind {
+    Func,
+    PropertyGet,
+    PropertyPut,
+    PropertyPutRef,
+}
+
+/// A parameter, the equivalent of `ELEMDESC`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamDesc {
+    pub name: Option<String>,
+    pub ty: TypeDesc,
+    /// The `PARAMFLAGS` of the parameter
+    pub flags: u16,
+}
+
+impl ParamDesc {
+    pub fn is_in(&self) -> bool {
+        self.flags & 0x1 != 0
+    }
+
+    pub fn is_out(&self) -> bool {
+        self.flags & 0x2 != 0
+    }
+
+    pub fn is_retval(&self) -> bool {
+        self.flags & 0x8 != 0
+    }
+}
+
+/// A variable, the equivalent of `VARDESC`
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarDesc {
+    pub memid: i32,
+    pub name: String,
+    pub doc_string: Option<String>,
+    pub kind: VarKind,
+    pub ty: TypeDesc,
+    /// The offset of the field in its record, for `VarKind::PerInstance`
+    pub offset: u32,
+    /// The value of a constant, for `VarKind::Const`
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarKind {
+    PerInstance,
+    Static,
+    Const,
+    Dispatch,
+}
+
+/// The value of a constant
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+/// A GUID, as stored in type libraries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Guid {
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8; 8],
+}
+
+impl fmt::Display for Guid {
+    /// Format the GUID in its registry format, e.g. `00000000-0000-0000-C000-000000000046`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-",
+            self.data1, self.data2, self.data3, self.data4[0], self.data4[1]
+        )?;
+        for byte in &self.data4[2..] {
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Guid {
+    type Err = Error;
+
+    /// Parse a GUID in its registry format, with or without braces
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::new(None, format!("invalid GUID `{}`", s));
+        let hex = s
+            .strip_prefix('{')
+            .and_then(|s| s.strip_suffix('}'))
+            .unwrap_or(s);
+        let parts = hex.split('-').collect::<Vec<_>>();
+        let lengths = parts.iter().map(|p| p.len()).collect::<Vec<_>>();
+        if lengths != [8, 4, 4, 4, 12] || !hex.chars().all(|c| c == '-' || c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let data4 = format!("{}{}", parts[3], parts[4]);
+        let mut guid = Guid {
+            data1: u32::from_str_radix(parts[0], 16).map_err(|_| invalid())?,
+            data2: u16::from_str_radix(parts[1], 16).map_err(|_| invalid())?,
+            data3: u16::from_str_radix(parts[2], 16).map_err(|_| invalid())?,
+            data4: [0; 8],
+        };
+        for (i, byte) in guid.data4.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&data4[2 * i..2 * i + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(guid)
+    }
+}
+
+/// An error in a type library
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    offset: Option<usize>,
+    message: String,
+}
+
+impl Error {
+    fn new(offset: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            offset,
+            message: message.into(),
+        }
+    }
+
+    /// The offset in the type library of the data which could not be read, for
+    /// errors in the format of the type library
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "offset {:#x}: {}", offset, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,589 @@
+//! Parsing of the MSFT type library format
+//!
+//! A type library starts with a header, the offsets of its type infos and a
+//! directory of segments holding the type infos, GUIDs, names, strings, type
+//! descriptions and references to other type libraries. The functions and variables
+//! of each type info are stored after the segments.
+use super::{
+    Error, FuncDesc, FuncKind, Guid, ImplType, ImportedLib, InvokeKind, ParamDesc, SysKind,
+    TypeDesc, TypeInfo, TypeKind, TypeLib, TypeRef, Value, VarDesc, VarKind,
+};
+use std::convert::TryFrom;
+
+const MAGIC: [u8; 8] = *b"MSFT\x02\x00\x01\x00";
+const HEADER_SIZE: usize = 0x54;
+/// The `varflags` bit telling that the header is followed by a help string DLL
+const HELPDLLFLAG: i32 = 0x100;
+const TYPEINFO_SIZE: usize = 0x64;
+/// The flag of imported type infos which are referenced by GUID
+const IMPINFO_OFFSET_IS_GUID: i32 = 0x10000;
+/// The maximum nesting of type descriptions, which guards against cycles
+const MAX_TYPE_DEPTH: usize = 32;
+
+const VT_I2: u16 = 2;
+const VT_I4: u16 = 3;
+const VT_R4: u16 = 4;
+const VT_R8: u16 = 5;
+const VT_CY: u16 = 6;
+const VT_DATE: u16 = 7;
+const VT_BSTR: u16 = 8;
+const VT_ERROR: u16 = 10;
+const VT_BOOL: u16 = 11;
+const VT_I1: u16 = 16;
+const VT_UI1: u16 = 17;
+const VT_UI2: u16 = 18;
+const VT_UI4: u16 = 19;
+const VT_I8: u16 = 20;
+const VT_UI8: u16 = 21;
+const VT_INT: u16 = 22;
+const VT_UINT: u16 = 23;
+const VT_HRESULT: u16 = 25;
+const VT_PTR: u16 = 26;
+const VT_SAFEARRAY: u16 = 27;
+const VT_CARRAY: u16 = 28;
+const VT_USERDEFINED: u16 = 29;
+const VT_TYPEMASK: i32 = 0xfff;
+
+/// The segments of the directory, in order
+#[derive(Clone, Copy)]
+enum Seg {
+    TypeInfo,
+    ImpInfo,
+    ImpFiles,
+    RefTab,
+    GuidTab = 5,
+    NameTab = 7,
+    StringTab,
+    TypeDesc,
+    ArrayDesc,
+    CustData,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Segment {
+    offset: usize,
+    length: usize,
+}
+
+pub fn read(bytes: &[u8]) -> Result<TypeLib, Error> {
+    if bytes.get(..MAGIC.len()) != Some(&MAGIC[..]) {
+        return Err(Error::new(Some(0), "not a type library in the MSFT format"));
+    }
+    let reader = Reader::new(bytes)?;
+    let header = |offset| reader.i32(offset);
+
+    let count = reader.count(0x20)?;
+    let dispatch = header(0x4c)?;
+    let mut lib = TypeLib {
+        name: reader.name(header(0x38)?)?,
+        doc_string: reader.string(header(0x24)?)?,
+        guid: reader.guid(header(0x08)?)?,
+        major_version: header(0x18)? as u16,
+        minor_version: (header(0x18)? >> 16) as u16,
+        lcid: header(0x0c)? as u32,
+        syskind: match header(0x14)? & 0xf {
+            0 => SysKind::Win16,
+            1 => SysKind::Win32,
+            2 => SysKind::Mac,
+            3 => SysKind::Win64,
+            kind => return Err(Error::new(Some(0x14), format!("unknown SYSKIND {}", kind))),
+        },
+        imports: reader.imports()?,
+        type_infos: Vec::with_capacity(count),
+    };
+    for index in 0..count {
+        lib.type_infos
+            .push(reader.type_info(index, count, dispatch)?);
+    }
+    Ok(lib)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    segments: [Segment; 15],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        let mut reader = Reader {
+            bytes,
+            segments: [Segment::default(); 15],
+        };
+        let mut directory = HEADER_SIZE + 4 * reader.count(0x20)?;
+        if reader.i32(0x14)? & HELPDLLFLAG != 0 {
+            directory += 4;
+        }
+        for index in 0..reader.segments.len() {
+            let entry = directory + 16 * index;
+            if reader.i32(entry + 12)? != 0xf {
+                return Err(Error::new(Some(entry), "invalid segment directory"));
+            }
+            let (offset, length) = (reader.i32(entry)?, reader.i32(entry + 4)?);
+            if offset < 0 || length <= 0 {
+                continue;
+            }
+            // Both are below 2^31, so that their sum fits in a `usize`
+            let (offset, length) = (offset as usize, length as usize);
+            if offset + length > bytes.len() {
+                return Err(Error::new(
+                    Some(entry),
+                    "segment extends past the end of the file",
+                ));
+            }
+            reader.segments[index] = Segment { offset, length };
+        }
+        Ok(reader)
+    }
+
+    fn bytes(&self, offset: usize, length: usize) -> Result<&'a [u8], Error> {
+        offset
+            .checked_add(length)
+            .and_then(|end| self.bytes.get(offset..end))
+            .ok_or_else(|| Error::new(Some(offset), "unexpected end of file"))
+    }
+
+    fn i32(&self, offset: usize) -> Result<i32, Error> {
+        let bytes = self.bytes(offset, 4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn i16(&self, offset: usize) -> Result<i16, Error> {
+        let bytes = self.bytes(offset, 2)?;
+        Ok(i16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn count(&self, offset: usize) -> Result<usize, Error> {
+        match self.i32(offset)? {
+            count if count >= 0 => Ok(count as usize),
+            _ => Err(Error::new(Some(offset), "negative count")),
+        }
+    }
+
+    /// The absolute offset of an entry of `size` bytes in a segment
+    fn entry(&self, seg: Seg, offset: i32, size: usize) -> Result<usize, Error> {
+        let segment = self.segments[seg as usize];
+        match usize::try_from(offset) {
+            Ok(offset) if offset + size <= segment.length => Ok(segment.offset + offset),
+            _ => Err(Error::new(
+                None,
+                format!("invalid offset {:#x} in the {} segment", offset, seg.name()),
+            )),
+        }
+    }
+
+    /// A name of the name table
+    fn name(&self, offset: i32) -> Result<String, Error> {
+        let entry = self.entry(Seg::NameTab, offset, 12)?;
+        let length = (self.i32(entry + 8)? & 0xff) as usize;
+        let bytes = self.bytes(entry + 12, length)?;
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    /// A string of the string table, such as a help string
+    fn string(&self, offset: i32) -> Result<Option<String>, Error> {
+        if offset < 0 {
+            return Ok(None);
+        }
+        let entry = self.entry(Seg::StringTab, offset, 2)?;
+        let length = self.i16(entry)? as u16 as usize;
+        let bytes = self.bytes(entry + 2, length)?;
+        Ok(Some(String::from_utf8_lossy(bytes).into_owned()))
+    }
+
+    fn guid(&self, offset: i32) -> Result<Option<Guid>, Error> {
+        if offset < 0 {
+            return Ok(None);
+        }
+        let bytes = self.bytes(self.entry(Seg::GuidTab, offset, 16)?, 16)?;
+        let mut data4 = [0; 8];
+        data4.copy_from_slice(&bytes[8..]);
+        Ok(Some(Guid {
+            data1: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            data2: u16::from_le_bytes([bytes[4], bytes[5]]),
+            data3: u16::from_le_bytes([bytes[6], bytes[7]]),
+            data4,
+        }))
+    }
+
+    /// The type libraries listed in the import file segment
+    fn imports(&self) -> Result<Vec<ImportedLib>, Error> {
+        let segment = self.segments[Seg::ImpFiles as usize];
+        let mut imports = Vec::new();
+        let mut offset = 0;
+        while offset < segment.length {
+            let entry = self.entry(Seg::ImpFiles, offset as i32, 14)?;
+            let (file, length) = self.imported_file(entry)?;
+            let version = self.i32(entry + 8)?;
+            imports.push(ImportedLib {
+                file,
+                guid: self.guid(self.i32(entry)?)?,
+                major_version: version as u16,
+                minor_version: (version >> 16) as u16,
+                lcid: self.i32(entry + 4)? as u32,
+            });
+            // Entries are padded to 4 bytes
+            offset += (14 + length + 3) & !3;
+        }
+        Ok(imports)
+    }
+
+    /// The file name of an entry of the import file segment, and its length
+    fn imported_file(&self, entry: usize) -> Result<(String, usize), Error> {
+        let length = (self.i16(entry + 12)? as u16 >> 2) as usize;
+        let file = String::from_utf8_lossy(self.bytes(entry + 14, length)?).into_owned();
+        Ok((file, length))
+    }
+
+    fn type_info(&self, index: usize, count: usize, dispatch: i32) -> Result<TypeInfo, Error> {
+        let base = self.entry(Seg::TypeInfo, (index * TYPEINFO_SIZE) as i32, TYPEINFO_SIZE)?;
+        let field = |offset| self.i32(base + offset);
+
+        let kind = match field(0x00)? & 0xf {
+            0 => TypeKind::Enum,
+            1 => TypeKind::Record,
+            2 => TypeKind::Module,
+            3 => TypeKind::Interface,
+            4 => TypeKind::Dispatch,
+            5 => TypeKind::Coclass,
+            6 => TypeKind::Alias,
+            7 => TypeKind::Union,
+            kind => return Err(Error::new(Some(base), format!("unknown TYPEKIND {}", kind))),
+        };
+        let elements = field(0x18)?;
+        let (func_count, var_count) = (elements as u16 as usize, (elements >> 16) as u16 as usize);
+        let impl_count = self.i16(base + 0x4c)?.max(0) as usize;
+        let datatype1 = field(0x54)?;
+
+        let impl_types = match kind {
+            TypeKind::Coclass => self.impl_types(datatype1, impl_count, count)?,
+            TypeKind::Dispatch => {
+                let parent = if datatype1 != -1 { datatype1 } else { dispatch };
+                match parent {
+                    -1 => Vec::new(),
+                    parent => vec![ImplType {
+                        reference: self.type_ref(parent, count)?,
+                        flags: 0,
+                    }],
+                }
+            }
+            TypeKind::Interface if impl_count > 0 => vec![ImplType {
+                reference: self.type_ref(datatype1, count)?,
+                flags: 0,
+            }],
+            _ => Vec::new(),
+        };
+        let alias = match kind {
+            TypeKind::Alias => Some(self.type_desc(datatype1, count, 0)?),
+            _ => None,
+        };
+
+        let mut info = TypeInfo {
+            name: self.name(field(0x34)?)?,
+            doc_string: self.string(field(0x3c)?)?,
+            guid: self.guid(field(0x2c)?)?,
+            kind,
+            flags: field(0x30)? as u32,
+            major_version: field(0x38)? as u16,
+            minor_version: (field(0x38)? >> 16) as u16,
+            size: field(0x50)? as u32,
+            alignment: ((field(0x00)? >> 11) & 0x1f) as u16,
+            vtable_size: self.i16(base + 0x4e)? as u16,
+            impl_types,
+            alias,
+            funcs: Vec::with_capacity(func_count),
+            vars: Vec::with_capacity(var_count),
+        };
+        if func_count + var_count > 0 {
+            let offset = usize::try_from(field(0x04)?)
+                .map_err(|_| Error::new(Some(base + 4), "invalid member offset"))?;
+            let members = Members {
+                records: offset + 4,
+                arrays: offset + 4 + self.count(offset)?,
+                count: func_count + var_count,
+            };
+            for index in 0..func_count {
+                info.funcs.push(self.func(&members, index, count)?);
+            }
+            for index in func_count..func_count + var_count {
+                info.vars.push(self.var(&members, index, count)?);
+            }
+        }
+        Ok(info)
+    }
+
+    /// The interfaces of a coclass, which are chained in the reference table
+    fn impl_types(
+        &self,
+        first: i32,
+        impl_count: usize,
+        count: usize,
+    ) -> Result<Vec<ImplType>, Error> {
+        let mut impl_types = Vec::with_capacity(impl_count);
+        let mut offset = first;
+        while offset != -1 && impl_types.len() < impl_count {
+            let entry = self.entry(Seg::RefTab, offset, 16)?;
+            impl_types.push(ImplType {
+                reference: self.type_ref(self.i32(entry)?, count)?,
+                flags: self.i32(entry + 4)? as u32,
+            });
+            offset = self.i32(entry + 12)?;
+        }
+        Ok(impl_types)
+    }
+
+    /// Resolve an `HREFTYPE`, which is either the offset of a type info of this
+    /// library or the offset of an imported type info plus one
+    fn type_ref(&self, href: i32, count: usize) -> Result<TypeRef, Error> {
+        if href & 3 == 0 {
+            let index = usize::try_from(href).unwrap_or(usize::MAX) / TYPEINFO_SIZE;
+            if index >= count {
+                return Err(Error::new(
+                    None,
+                    format!("invalid type reference {:#x}", href),
+                ));
+            }
+            return Ok(TypeRef::Local(index));
+        }
+
+        let impinfo = self.entry(Seg::ImpInfo, href & !3, 12)?;
+        if self.i32(impinfo)? & IMPINFO_OFFSET_IS_GUID == 0 {
+            return Err(Error::new(
+                Some(impinfo),
+                "imported type infos must be referenced by GUID",
+            ));
+        }
+        let impfile = self.entry(Seg::ImpFiles, self.i32(impinfo + 4)?, 14)?;
+        let (file, _) = self.imported_file(impfile)?;
+        match self.guid(self.i32(impinfo + 8)?)? {
+            Some(guid) => Ok(TypeRef::Imported { guid, file }),
+            None => Err(Error::new(
+                Some(impinfo + 8),
+                "imported type info has no GUID",
+            )),
+        }
+    }
+
+    /// Decode a type, which is either a base type or the offset of a type description
+    fn type_desc(&self, encoded: i32, count: usize, depth: usize) -> Result<TypeDesc, Error> {
+        if encoded < 0 {
+            return Ok(TypeDesc::Base((encoded & VT_TYPEMASK) as u16));
+        }
+        if depth == MAX_TYPE_DEPTH {
+            return Err(Error::new(None, "type descriptions are nested too deeply"));
+        }
+        let entry = self.entry(Seg::TypeDesc, encoded, 8)?;
+        let vt = (self.i16(entry)? as i32 & VT_TYPEMASK) as u16;
+        let target = self.i32(entry + 4)?;
+        Ok(match vt {
+            VT_PTR => TypeDesc::Ptr(Box::new(self.type_desc(target, count, depth + 1)?)),
+            VT_SAFEARRAY => {
+                TypeDesc::SafeArray(Box::new(self.type_desc(target, count, depth + 1)?))
+            }
+            VT_CARRAY => {
+                let array = self.entry(Seg::ArrayDesc, target, 8)?;
+                let element = self.type_desc(self.i32(array)?, count, depth + 1)?;
+                let dims = self.i16(array + 4)?.max(0) as usize;
+                let mut lengths = Vec::with_capacity(dims);
+                for dim in 0..dims {
+                    lengths.push(self.i32(array + 8 + 8 * dim)? as u32);
+                }
+                TypeDesc::CArray(Box::new(element), lengths)
+            }
+            VT_USERDEFINED => TypeDesc::UserDefined(self.type_ref(target, count)?),
+            vt => TypeDesc::Base(vt),
+        })
+    }
+
+    fn func(&self, members: &Members, index: usize, count: usize) -> Result<FuncDesc, Error> {
+        let record = self.record(members, index)?;
+        let length = (self.i32(record)? & 0xffff) as usize;
+        let flags = self.i32(record + 16)?;
+        let param_count = self.i16(record + 20)?.max(0) as usize;
+        if length < 24 + 12 * param_count {
+            return Err(Error::new(Some(record), "function record is too short"));
+        }
+        // Optional attributes follow the fixed fields, the help string being second
+        let mut attributes = (length - 24 - 12 * param_count) / 4;
+        if flags & 0x1000 != 0 {
+            // Offsets of the default values of the parameters
+            attributes = attributes.saturating_sub(param_count);
+        }
+        let doc_string = match attributes {
+            0 | 1 => None,
+            _ => self.string(self.i32(record + 28)?)?,
+        };
+
+        let mut params = Vec::with_capacity(param_count);
+        for param in 0..param_count {
+            let offset = record + length - 12 * (param_count - param);
+            let name = match self.i32(offset + 4)? {
+                -1 => None,
+                name => Some(self.name(name)?),
+            };
+            params.push(ParamDesc {
+                name,
+                ty: self.type_desc(self.i32(offset)?, count, 0)?,
+                flags: self.i32(offset + 8)? as u16,
+            });
+        }
+
+        Ok(FuncDesc {
+            memid: self.i32(members.id(index))?,
+            name: self.name(self.i32(members.name(index))?)?,
+            doc_string,
+            kind: match flags & 0x7 {
+                0 => FuncKind::Virtual,
+                1 => FuncKind::PureVirtual,
+                2 => FuncKind::NonVirtual,
+                3 => FuncKind::Static,
+                _ => FuncKind::Dispatch,
+            },
+            invoke_kind: match (flags >> 3) & 0xf {
+                2 => InvokeKind::PropertyGet,
+                4 => InvokeKind::PropertyPut,
+                8 => InvokeKind::PropertyPutRef,
+                _ => InvokeKind::Func,
+            },
+            call_conv: ((flags >> 8) & 0xf) as u8,
+            flags: self.i32(record + 8)? as u16,
+            vtable_offset: self.i16(record + 12)?,
+            ret: self.type_desc(self.i32(record + 4)?, count, 0)?,
+            params,
+        })
+    }
+
+    fn var(&self, members: &Members, index: usize, count: usize) -> Result<VarDesc, Error> {
+        let record = self.record(members, index)?;
+        let length = (self.i32(record)? & 0xff) as usize;
+        if length < 20 {
+            return Err(Error::new(Some(record), "variable record is too short"));
+        }
+        let kind = match self.i16(record + 12)? {
+            0 => VarKind::PerInstance,
+            1 => VarKind::Static,
+            2 => VarKind::Const,
+            _ => VarKind::Dispatch,
+        };
+        let offset_or_value = self.i32(record + 16)?;
+        let doc_string = match length {
+            0..=24 => None,
+            _ => self.string(self.i32(record + 24)?)?,
+        };
+        Ok(VarDesc {
+            memid: self.i32(members.id(index))?,
+            name: self.name(self.i32(members.name(index))?)?,
+            doc_string,
+            kind,
+            ty: self.type_desc(self.i32(record + 4)?, count, 0)?,
+            offset: match kind {
+                VarKind::PerInstance => offset_or_value as u32,
+                _ => 0,
+            },
+            value: match kind {
+                VarKind::Const => Some(self.value(offset_or_value)?),
+                _ => None,
+            },
+        })
+    }
+
+    /// The absolute offset of the record of a function or variable
+    fn record(&self, members: &Members, index: usize) -> Result<usize, Error> {
+        let offset = self.i32(members.record_offset(index))?;
+        usize::try_from(offset)
+            .ok()
+            .map(|offset| members.records + offset)
+            .filter(|&record| record < members.arrays)
+            .ok_or_else(|| Error::new(Some(members.record_offset(index)), "invalid record offset"))
+    }
+
+    /// The value of a constant, which is either packed in `encoded` or stored in the
+    /// custom data segment
+    fn value(&self, encoded: i32) -> Result<Value, Error> {
+        if encoded < 0 {
+            let vt = (encoded & 0x7c00_0000) >> 26;
+            let value = (encoded & 0x03ff_ffff) as i64;
+            return Ok(match vt as u16 {
+                VT_R4 | VT_R8 | VT_DATE => Value::Float(value as f64),
+                _ => Value::Int(value),
+            });
+        }
+        let entry = self.entry(Seg::CustData, encoded, 2)?;
+        let data = entry + 2;
+        Ok(match self.i16(entry)? as u16 {
+            VT_I1 => Value::Int(self.bytes(data, 1)?[0] as i8 as i64),
+            VT_UI1 => Value::Int(self.bytes(data, 1)?[0] as i64),
+            VT_I2 | VT_BOOL => Value::Int(self.i16(data)? as i64),
+            VT_UI2 => Value::Int(self.i16(data)? as u16 as i64),
+            VT_I4 | VT_INT | VT_ERROR | VT_HRESULT => Value::Int(self.i32(data)? as i64),
+            VT_UI4 | VT_UINT => Value::Int(self.i32(data)? as u32 as i64),
+            VT_I8 | VT_UI8 | VT_CY => {
+                let bytes = self.bytes(data, 8)?;
+                let mut le = [0; 8];
+                le.copy_from_slice(bytes);
+                Value::Int(i64::from_le_bytes(le))
+            }
+            VT_R4 => Value::Float(f32::from_bits(self.i32(data)? as u32) as f64),
+            VT_R8 | VT_DATE => {
+                let bytes = self.bytes(data, 8)?;
+                let mut le = [0; 8];
+                le.copy_from_slice(bytes);
+                Value::Float(f64::from_le_bytes(le))
+            }
+            VT_BSTR => {
+                let length = self.count(data)?;
+                let bytes = self.bytes(data + 4, length)?;
+                Value::Str(String::from_utf8_lossy(bytes).into_owned())
+            }
+            vt => {
+                return Err(Error::new(
+                    Some(entry),
+                    format!("unsupported constant of VARTYPE {}", vt),
+                ))
+            }
+        })
+    }
+}
+
+impl Seg {
+    fn name(self) -> &'static str {
+        match self {
+            Seg::TypeInfo => "type info",
+            Seg::ImpInfo => "import info",
+            Seg::ImpFiles => "import file",
+            Seg::RefTab => "reference",
+            Seg::GuidTab => "GUID",
+            Seg::NameTab => "name",
+            Seg::StringTab => "string",
+            Seg::TypeDesc => "type description",
+            Seg::ArrayDesc => "array description",
+            Seg::CustData => "custom data",
+        }
+    }
+}
+
+/// The functions and variables of a type info
+///
+/// They start with the total length of their records, followed by the records and
+/// three arrays with one entry per member: the member ids, the offsets of their
+/// names and the offsets of their records.
+struct Members {
+    /// The absolute offset of the first record
+    records: usize,
+    /// The absolute offset of the first array, which ends the records
+    arrays: usize,
+    count: usize,
+}
+
+impl Members {
+    fn id(&self, index: usize) -> usize {
+        self.arrays + 4 * index
+    }
+
+    fn name(&self, index: usize) -> usize {
+        self.arrays + 4 * (self.count + index)
+    }
+
+    fn record_offset(&self, index: usize) -> usize {
+        self.arrays + 4 * (2 * self.count + index)
+    }
+}
@@ -0,0 +1,545 @@
+//! Tests reading the type libraries of `tests/tlb`, which are built by
+//! `tests/tlb/make_fixtures.py`, and building type libraries from the declarations
+//! of `tests/tlb/server.rs`
+//!
+//! MIDL only runs on Windows, so the type library it compiles from
+//! `tests/tlb/clock.idl` is not checked in. The `midl` job of the CI workflow builds
+//! it and runs the ignored [`midl_clock`] test, which compares it with the fixture.
+use super::{
+    build, convert, Declarations, FuncKind, Guid, InvokeKind, Library, SysKind, TypeDesc, TypeKind,
+    TypeLib, TypeRef, Value, VarKind,
+};
+use crate::test_utils::assert_golden;
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(format!("{}/tests/tlb/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+fn clock() -> TypeLib {
+    TypeLib::parse(&fixture("clock.tlb")).unwrap()
+}
+
+fn library_of(sources: &[&str]) -> syn::Result<TypeLib> {
+    let mut declarations = Declarations::default();
+    for source in sources {
+        declarations.parse_source(source)?;
+    }
+    let guid = "5b0b1b8e-7d5e-4c1c-9c0c-4ad4a3b0b1c3".parse().unwrap();
+    let mut library = Library::new("ServerLib", guid);
+    library.doc_string = Some("Server library".to_owned());
+    build(&library, &declarations)
+}
+
+fn server() -> TypeLib {
+    let source = String::from_utf8(fixture("server.rs")).unwrap();
+    library_of(&[&source]).unwrap()
+}
+
+fn build_err(source: &str, expected_error: &str) {
+    match library_of(&[source]) {
+        Ok(lib) => panic!("Expected type library to fail to build.\nOutput: {:?}", lib),
+        Err(e) => {
+            let e_string = e.to_string();
+            if !e_string.contains(expected_error) {
+                panic!(
+                    "Did not find expected error string.\nActual error: {:?}\nExpected error: {:?}",
+                    e_string, expected_error
+                );
+            }
+        }
+    }
+}
+
+fn parse_err(bytes: &[u8], expected_error: &str) {
+    match TypeLib::parse(bytes) {
+        Ok(lib) => panic!("Expected type library to fail to parse.\nOutput: {:?}", lib),
+        Err(e) => {
+            let e_string = e.to_string();
+            if !e_string.contains(expected_error) {
+                panic!(
+                    "Did not find expected error string.\nActual error: {:?}\nExpected error: {:?}",
+                    e_string, expected_error
+                );
+            }
+        }
+    }
+}
+
+/// Overwrite the 32-bit integer at `offset` of a copy of `bytes`
+fn patch(bytes: &[u8], offset: usize, value: i32) -> Vec<u8> {
+    let mut bytes = bytes.to_vec();
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn library() {
+    let lib = clock();
+    assert_eq!(lib.name, "ClockLib");
+    assert_eq!(lib.doc_string.as_deref(), Some("Clock library"));
+    assert_eq!(
+        lib.guid.unwrap().to_string(),
+        "BB12D362-DAEE-4B9A-AA1D-14BA401CFA1F"
+    );
+    assert_eq!((lib.major_version, lib.minor_version), (1, 0));
+    assert_eq!(lib.lcid, 0x409);
+    assert_eq!(lib.syskind, SysKind::Win64);
+    let imports = lib.imports.iter().map(|i| &i.file).collect::<Vec<_>>();
+    assert_eq!(imports, ["stdole2.tlb"]);
+    assert_eq!(lib.imports[0].major_version, 2);
+    let kinds = lib.type_infos.iter().map(|t| t.kind).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            TypeKind::Enum,
+            TypeKind::Record,
+            TypeKind::Record,
+            TypeKind::Alias,
+            TypeKind::Interface,
+            TypeKind::Dispatch,
+            TypeKind::Coclass,
+        ]
+    );
+    assert_eq!(lib.type_info_count(), 7);
+    assert_eq!(lib.find_name("iclock").unwrap().name, "IClock");
+    let clsid = Guid {
+        data1: 0xc5f45cbc,
+        data2: 0x4439,
+        data3: 0x418c,
+        data4: [0xa9, 0xf9, 0x05, 0xac, 0x67, 0x52, 0x5e, 0x43],
+    };
+    assert_eq!(lib.type_info_of_guid(&clsid).unwrap().name, "Clock");
+}
+
+#[test]
+fn interface() {
+    let lib = clock();
+    let alarm = lib.find_name("IAlarm").unwrap();
+    assert_eq!(alarm.doc_string.as_deref(), Some("An alarm"));
+    assert_eq!(alarm.vtable_size, 64);
+    match alarm.ref_type_of_impl_type(0).unwrap() {
+        TypeRef::Imported { guid, file } => {
+            assert_eq!(guid.to_string(), "00000000-0000-0000-C000-000000000046");
+            assert_eq!(file, "stdole2.tlb");
+        }
+        reference => panic!("IAlarm inherits from {:?}", reference),
+    }
+
+    let offsets = alarm
+        .funcs
+        .iter()
+        .map(|f| (f.name.as_str(), f.vtable_offset))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        offsets,
+        [
+            ("Ring", 24),
+            ("GetTime", 32),
+            ("Next", 40),
+            ("Flags", 48),
+            ("Reset", 56)
+        ]
+    );
+    let get_time = alarm.func_desc(1).unwrap();
+    assert_eq!(get_time.kind, FuncKind::PureVirtual);
+    assert_eq!(
+        get_time.doc_string.as_deref(),
+        Some("The time of the alarm")
+    );
+    assert_eq!(get_time.ret, TypeDesc::Base(25));
+    let time = &get_time.params[0];
+    assert!(time.is_out() && time.is_retval() && !time.is_in());
+    assert_eq!(time.ty, TypeDesc::Ptr(Box::new(TypeDesc::Base(7))));
+    assert_eq!(
+        alarm.func_desc(3).unwrap().params[0].ty,
+        TypeDesc::Ptr(Box::new(TypeDesc::UserDefined(TypeRef::Local(0))))
+    );
+    assert_eq!(
+        alarm.names(0x60010002),
+        ["Next", "count", "values", "fetched"]
+    );
+    assert_eq!(alarm.id_of_name("reset"), Some(0x60010004));
+}
+
+#[test]
+fn dual_interface() {
+    let lib = clock();
+    let clock = lib.find_name("IClock").unwrap();
+    assert!(clock.is_dual());
+    assert_eq!(clock.vtable_size, 13 * 8);
+    let get = clock.func_desc(0).unwrap();
+    let put = clock.func_desc(1).unwrap();
+    assert_eq!((get.memid, get.invoke_kind), (1, InvokeKind::PropertyGet));
+    assert_eq!((put.memid, put.invoke_kind), (1, InvokeKind::PropertyPut));
+    assert_eq!(get.kind, FuncKind::Dispatch);
+    assert_eq!(
+        get.vtable_offset as usize / lib.syskind.pointer_size(),
+        7,
+        "the first method follows those of IDispatch"
+    );
+    let alarms = &clock.func_desc(4).unwrap().params[0].ty;
+    let alarm = TypeDesc::Ptr(Box::new(TypeDesc::UserDefined(TypeRef::Local(4))));
+    assert_eq!(
+        *alarms,
+        TypeDesc::Ptr(Box::new(TypeDesc::SafeArray(Box::new(alarm))))
+    );
+}
+
+#[test]
+fn records_and_enums() {
+    let lib = clock();
+    let flags = lib.type_info(0).unwrap();
+    let values = flags
+        .vars
+        .iter()
+        .map(|v| (v.kind, v.value.clone().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        values,
+        [
+            (VarKind::Const, Value::Int(0)),
+            (VarKind::Const, Value::Int(1)),
+            (VarKind::Const, Value::Int(2)),
+            (VarKind::Const, Value::Int(-1)),
+        ]
+    );
+
+    let alarm = lib.find_name("ALARM").unwrap();
+    assert_eq!((alarm.size, alarm.alignment), (40, 8));
+    let offsets = alarm.vars.iter().map(|v| v.offset).collect::<Vec<_>>();
+    assert_eq!(offsets, [0, 8, 16]);
+    assert_eq!(
+        alarm.var_desc(2).unwrap().ty,
+        TypeDesc::CArray(Box::new(TypeDesc::Base(4)), vec![2, 3])
+    );
+    assert_eq!(
+        lib.find_name("CLOCK_ID").unwrap().alias,
+        Some(TypeDesc::Base(3))
+    );
+}
+
+#[test]
+fn coclass() {
+    let lib = clock();
+    let clock = lib.find_name("Clock").unwrap();
+    let interfaces = clock
+        .impl_types
+        .iter()
+        .map(|i| {
+            (
+                lib.ref_type_info(&i.reference).unwrap().name.as_str(),
+                i.flags,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(interfaces, [("IClock", 1), ("IAlarm", 0)]);
+}
+
+#[test]
+fn generate_rust() {
+    let rust = convert(&fixture("clock.tlb")).unwrap();
+    assert_golden(&rust, "tests/tlb/clock.rs");
+    // The declarations are accepted by `interfaces!`
+    let interfaces = crate::header::parse_source(&rust).unwrap();
+    let header = crate::header::generate(&interfaces).unwrap();
+    assert!(header.contains("HRESULT (STDMETHODCALLTYPE *GetTime)(IAlarm *This, double *time);"));
+}
+
+/// The type library compiled by MIDL from `tests/tlb/clock.idl`, whose path is set
+/// in `COM_RS_MIDL_TLB`, has the same contents as `clock.tlb`
+///
+/// MIDL may order the type infos differently, so they are matched by name.
+#[test]
+#[ignore]
+fn midl_clock() {
+    let path = std::env::var("COM_RS_MIDL_TLB").expect("COM_RS_MIDL_TLB is not set");
+    let bytes = std::fs::read(path).unwrap();
+    let midl = TypeLib::parse(&bytes).unwrap();
+    let lib = clock();
+    assert_eq!(midl.name, lib.name);
+    assert_eq!(midl.guid, lib.guid);
+    assert_eq!(midl.syskind, lib.syskind);
+    assert_eq!(midl.type_info_count(), lib.type_info_count());
+    for expected in &lib.type_infos {
+        let actual = midl
+            .type_infos
+            .iter()
+            .find(|t| t.name == expected.name && t.kind == expected.kind)
+            .unwrap_or_else(|| panic!("{} is missing", expected.name));
+        let layout = |t: &super::TypeInfo| {
+            let funcs = t
+                .funcs
+                .iter()
+                .map(|f| (f.name.clone(), f.memid, f.invoke_kind, f.vtable_offset))
+                .collect::<Vec<_>>();
+            let vars = t
+                .vars
+                .iter()
+                .map(|v| (v.name.clone(), v.kind, v.offset, v.value.clone()))
+                .collect::<Vec<_>>();
+            (
+                t.guid,
+                t.flags,
+                t.size,
+                t.alignment,
+                t.vtable_size,
+                funcs,
+                vars,
+            )
+        };
+        assert_eq!(layout(actual), layout(expected), "{}", expected.name);
+    }
+    // The declarations are the same, in any order
+    let lines = |rust: String| {
+        let mut lines = rust.lines().skip(1).map(str::to_owned).collect::<Vec<_>>();
+        lines.sort();
+        lines
+    };
+    assert_eq!(
+        lines(convert(&bytes).unwrap()),
+        lines(convert(&fixture("clock.tlb")).unwrap())
+    );
+}
+
+#[test]
+fn err_not_a_type_library() {
+    parse_err(
+        b"SLTG\x01\x00\x00\x00",
+        "offset 0x0: not a type library in the MSFT format",
+    );
+}
+
+#[test]
+fn err_truncated() {
+    let bytes = fixture("clock.tlb");
+    parse_err(&bytes[..0x60], "unexpected end of file");
+    parse_err(&bytes[..0x400], "segment extends past the end of the file");
+    // The member data of the type infos follows the segments
+    parse_err(&bytes[..bytes.len() - 4], "unexpected end of file");
+}
+
+#[test]
+fn err_invalid_offsets() {
+    let bytes = fixture("clock.tlb");
+    // The name of the library
+    parse_err(
+        &patch(&bytes, 0x38, 0x7fff_0000),
+        "invalid offset 0x7fff0000 in the name segment",
+    );
+    // The IDispatch reference of the dual interface
+    parse_err(
+        &patch(&bytes, 0x4c, 0x6400),
+        "invalid type reference 0x6400",
+    );
+}
+
+#[test]
+fn err_unknown_import() {
+    let mut lib = clock();
+    let alarm = lib
+        .type_infos
+        .iter_mut()
+        .find(|t| t.name == "IAlarm")
+        .unwrap();
+    if let TypeRef::Imported { guid, .. } = &mut alarm.impl_types[0].reference {
+        guid.data1 = 0x1234;
+    }
+    let e = super::generate(&lib).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "type {00001234-0000-0000-C000-000000000046} imported from `stdole2.tlb` is not supported"
+    );
+}
+
+#[test]
+fn guid_from_str() {
+    let guid = "{bb12d362-daee-4b9a-aa1d-14ba401cfa1f}"
+        .parse::<Guid>()
+        .unwrap();
+    assert_eq!(guid.to_string(), "BB12D362-DAEE-4B9A-AA1D-14BA401CFA1F");
+    assert_eq!(
+        "BB12D362-DAEE-4B9A-AA1D-14BA401CFA1F"
+            .parse::<Guid>()
+            .unwrap(),
+        guid
+    );
+    let e = "BB12D362-DAEE-4B9A-AA1D".parse::<Guid>().unwrap_err();
+    assert_eq!(e.to_string(), "invalid GUID `BB12D362-DAEE-4B9A-AA1D`");
+    assert!("BB12D362-DAEE-4B9A-AA1D-14BA401CFA1G"
+        .parse::<Guid>()
+        .is_err());
+}
+
+#[test]
+fn write_round_trip() {
+    let lib = clock();
+    let bytes = lib.to_bytes();
+    assert_eq!(TypeLib::parse(&bytes).unwrap(), lib);
+    // Names, strings and type descriptions are shared
+    assert!(bytes.len() <= fixture("clock.tlb").len());
+}
+
+#[test]
+fn generate_idl() {
+    let idl = clock().to_idl().unwrap();
+    assert_golden(&idl, "tests/tlb/clock.idl");
+    // Converting the IDL gives the same declarations as converting the type library
+    let from_idl = crate::idl::convert(&idl).unwrap();
+    let from_tlb = convert(&fixture("clock.tlb")).unwrap();
+    let declarations = |rust: &str| rust.lines().skip(1).collect::<Vec<_>>().join("\n");
+    assert_eq!(declarations(&from_idl), declarations(&from_tlb));
+}
+
+#[test]
+fn build_interfaces() {
+    let lib = server();
+    let names = lib
+        .type_infos
+        .iter()
+        .map(|t| t.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["IAnimal", "ICat", "IScript", "ScriptedCat"]);
+
+    let animal = lib.find_name("IAnimal").unwrap();
+    assert_eq!(animal.kind, TypeKind::Interface);
+    assert_eq!(animal.doc_string.as_deref(), Some("An animal"));
+    assert_eq!(
+        animal.func_desc(0).unwrap().doc_string.as_deref(),
+        Some("Make the animal eat")
+    );
+    let cat = lib.find_name("ICat").unwrap();
+    assert_eq!(cat.ref_type_of_impl_type(0), Some(&TypeRef::Local(0)));
+    assert_eq!(cat.vtable_size, 7 * 8);
+    let ignore = cat.func_desc(0).unwrap();
+    assert_eq!((ignore.memid, ignore.vtable_offset), (0x60020000, 5 * 8));
+    let flags = ignore.params.iter().map(|p| p.flags).collect::<Vec<_>>();
+    assert_eq!(flags, [1, 2], "[in] count, [out] ignored");
+    let friend = &cat.func_desc(1).unwrap().params[0];
+    assert!(friend.is_out() && friend.is_retval());
+    assert_eq!(
+        friend.ty,
+        TypeDesc::Ptr(Box::new(TypeDesc::Ptr(Box::new(TypeDesc::UserDefined(
+            TypeRef::Local(1)
+        )))))
+    );
+}
+
+#[test]
+fn build_dual_interface() {
+    let lib = server();
+    let script = lib.find_name("IScript").unwrap();
+    assert!(script.is_dual());
+    assert_eq!(script.kind, TypeKind::Dispatch);
+    match script.ref_type_of_impl_type(0).unwrap() {
+        TypeRef::Imported { guid, file } => {
+            assert_eq!(guid.to_string(), "00020400-0000-0000-C000-000000000046");
+            assert_eq!(file, "stdole2.tlb");
+        }
+        reference => panic!("IScript inherits from {:?}", reference),
+    }
+    // The DISPIDs follow the methods of IAnimal and ICat, as numbered by #[dispatch]
+    let run = script.func_desc(0).unwrap();
+    assert_eq!(
+        (run.memid, run.kind, run.vtable_offset),
+        (5, FuncKind::Dispatch, 7 * 8)
+    );
+    assert_eq!(
+        run.params[1].ty,
+        TypeDesc::SafeArray(Box::new(TypeDesc::Base(12)))
+    );
+    assert_eq!(script.id_of_name("lines"), Some(6));
+}
+
+#[test]
+fn build_coclass() {
+    let lib = server();
+    let cat = lib.find_name("ScriptedCat").unwrap();
+    assert_eq!(
+        cat.guid.unwrap().to_string(),
+        "C5F45CBC-4439-418C-A9F9-05AC67525E43"
+    );
+    assert_eq!(cat.doc_string.as_deref(), Some("A cat which runs scripts"));
+    let interfaces = cat
+        .impl_types
+        .iter()
+        .map(|i| {
+            (
+                lib.ref_type_info(&i.reference).unwrap().name.as_str(),
+                i.flags,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(interfaces, [("ICat", 1), ("IScript", 0)]);
+}
+
+#[test]
+fn build_type_library() {
+    let lib = server();
+    assert_golden(&lib.to_idl().unwrap(), "tests/tlb/server.idl");
+    let bytes = lib.to_bytes();
+    assert_eq!(TypeLib::parse(&bytes).unwrap(), lib);
+    // The type library describes the same interfaces as the source
+    let rust = convert(&bytes).unwrap();
+    let interfaces = crate::header::parse_source(&rust).unwrap();
+    let names = interfaces
+        .iter()
+        .map(|i| i.name.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["IAnimal", "ICat", "IScript"]);
+    assert!(rust.contains("args: *mut SAFEARRAY,"));
+    assert!(rust.contains("#[retval] result: *mut VARIANT"));
+}
+
+#[test]
+fn err_build_unsupported_type() {
+    build_err(
+        r#"
+        com::interfaces! {
+            #[uuid("eff8970e-c50f-45e0-9284-291ce5a6f771")]
+            pub unsafe interface IFoo: IUnknown {
+                fn Foo(&self, name: &str) -> HRESULT;
+            }
+        }
+        "#,
+        "type cannot be described in a type library",
+    );
+}
+
+#[test]
+fn err_build_undeclared_parent() {
+    build_err(
+        r#"
+        com::interfaces! {
+            #[uuid("eff8970e-c50f-45e0-9284-291ce5a6f771")]
+            pub unsafe interface IFoo: IBar {
+                fn Foo(&self) -> HRESULT;
+            }
+        }
+        "#,
+        "parent interface `IBar` of `IFoo` is not declared in the input",
+    );
+}
+
+#[test]
+fn err_build_undeclared_clsid() {
+    build_err(
+        r#"
+        com::interfaces! {
+            #[uuid("eff8970e-c50f-45e0-9284-291ce5a6f771")]
+            pub unsafe interface IFoo: IUnknown {
+                fn Foo(&self) -> HRESULT;
+            }
+        }
+        com::class! {
+            pub class Foo: IFoo {}
+            impl IFoo for Foo {
+                fn Foo(&self) -> HRESULT {
+                    S_OK
+                }
+            }
+        }
+        com::inproc_dll_module![(CLSID_FOO, Foo),];
+        "#,
+        "CLSID `CLSID_FOO` is not declared with `guid!`",
+    );
+}
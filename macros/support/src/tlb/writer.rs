@@ -0,0 +1,562 @@
+//! Writing of the MSFT type library format
+//!
+//! The layout is the one understood by the reader: the header, the offsets of the
+//! type infos and the segment directory, followed by the segments and the member
+//! data of each type info. Names, strings and type descriptions are shared between
+//! the type infos which use them.
+use super::{
+    FuncKind, Guid, InvokeKind, TypeDesc, TypeInfo, TypeKind, TypeLib, TypeRef, Value, VarKind,
+};
+use std::collections::HashMap;
+
+const MAGIC: [u8; 8] = *b"MSFT\x02\x00\x01\x00";
+const HEADER_SIZE: usize = 0x54;
+const TYPEINFO_SIZE: usize = 0x64;
+const SEGMENT_COUNT: usize = 15;
+/// The flag of imported type infos which are referenced by GUID
+const IMPINFO_OFFSET_IS_GUID: i32 = 0x10000;
+/// The `varflags` bits of the header besides the `SYSKIND`
+const VARFLAGS: i32 = 0x10;
+/// `LIBFLAG_FHASDISKIMAGE`
+const LIBFLAGS: i32 = 0x8;
+/// The hreftype of the GUID of the library itself
+const LIBRARY_HREFTYPE: i32 = -2;
+const IID_IDISPATCH: Guid = Guid {
+    data1: 0x0002_0400,
+    data2: 0,
+    data3: 0,
+    data4: [0xc0, 0, 0, 0, 0, 0, 0, 0x46],
+};
+
+const VT_I4: u16 = 3;
+const VT_R8: u16 = 5;
+const VT_BSTR: u16 = 8;
+const VT_I8: u16 = 20;
+const VT_PTR: u16 = 26;
+const VT_SAFEARRAY: u16 = 27;
+const VT_CARRAY: u16 = 28;
+const VT_USERDEFINED: u16 = 29;
+
+pub fn write(lib: &TypeLib) -> Vec<u8> {
+    let mut writer = Writer {
+        guid_hash: [-1; 32],
+        dispatch: -1,
+        ..Writer::default()
+    };
+    let lib_guid = match &lib.guid {
+        Some(guid) => writer.guid(guid, LIBRARY_HREFTYPE),
+        None => -1,
+    };
+    let lib_name = writer.name(&lib.name, -1);
+    let lib_doc = writer.optional_string(lib.doc_string.as_deref());
+    for import in &lib.imports {
+        writer.impfile(lib, &import.file);
+    }
+
+    let infos = lib
+        .type_infos
+        .iter()
+        .enumerate()
+        .map(|(index, info)| writer.type_info(lib, index, info))
+        .collect::<Vec<_>>();
+
+    let mut segments: [Vec<u8>; SEGMENT_COUNT] = Default::default();
+    segments[1] = std::mem::take(&mut writer.impinfos);
+    segments[2] = std::mem::take(&mut writer.impfiles);
+    segments[3] = std::mem::take(&mut writer.refs);
+    segments[4] = writer
+        .guid_hash
+        .iter()
+        .flat_map(|h| h.to_le_bytes())
+        .collect();
+    segments[5] = std::mem::take(&mut writer.guids);
+    // The name hash table is left empty
+    segments[6] = (-1i32).to_le_bytes().repeat(128);
+    segments[7] = std::mem::take(&mut writer.names);
+    segments[8] = std::mem::take(&mut writer.strings);
+    segments[9] = std::mem::take(&mut writer.typedescs);
+    segments[10] = std::mem::take(&mut writer.arraydescs);
+    segments[11] = std::mem::take(&mut writer.custdata);
+    segments[0] = vec![0; TYPEINFO_SIZE * infos.len()];
+
+    let mut position = HEADER_SIZE + 4 * infos.len() + 16 * SEGMENT_COUNT;
+    let mut directory = Vec::with_capacity(SEGMENT_COUNT);
+    for segment in &segments {
+        if segment.is_empty() {
+            directory.push((-1, 0));
+        } else {
+            directory.push((position as i32, segment.len() as i32));
+            position += segment.len();
+        }
+    }
+
+    // The member data follows the segments
+    let mut members = Vec::new();
+    let mut member_offsets = Vec::with_capacity(infos.len());
+    for info in &infos {
+        member_offsets.push(position + members.len());
+        members.extend_from_slice(&info.members);
+    }
+    let end = (position + members.len()) as i32;
+    for (index, info) in infos.iter().enumerate() {
+        let memoffset = if info.members.is_empty() {
+            end
+        } else {
+            member_offsets[index] as i32
+        };
+        let record = &mut segments[0][index * TYPEINFO_SIZE..(index + 1) * TYPEINFO_SIZE];
+        record.copy_from_slice(&info.record(memoffset));
+    }
+
+    let mut out = Vec::with_capacity(end as usize);
+    out.extend_from_slice(&MAGIC);
+    let header = [
+        lib_guid,
+        lib.lcid as i32,
+        0,
+        lib.syskind as i32 | VARFLAGS,
+        version(lib.major_version, lib.minor_version),
+        LIBFLAGS,
+        infos.len() as i32,
+        lib_doc,
+        0,
+        0,
+        writer.name_offsets.len() as i32,
+        segments[7].len() as i32,
+        lib_name,
+        -1,
+        -1,
+        0x20,
+        0x80,
+        writer.dispatch,
+        (segments[1].len() / 12) as i32,
+    ];
+    put(&mut out, &header);
+    let offsets = (0..infos.len())
+        .map(|index| (index * TYPEINFO_SIZE) as i32)
+        .collect::<Vec<_>>();
+    put(&mut out, &offsets);
+    for (offset, length) in directory {
+        put(&mut out, &[offset, length, -1, 0xf]);
+    }
+    for segment in &segments {
+        out.extend_from_slice(segment);
+    }
+    out.extend_from_slice(&members);
+    out
+}
+
+fn put(out: &mut Vec<u8>, values: &[i32]) {
+    for value in values {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn put_i16(out: &mut Vec<u8>, value: i16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Pad `data` to a multiple of 4 bytes
+fn pad(data: &mut Vec<u8>, filler: u8) {
+    let len = (data.len() + 3) & !3;
+    data.resize(len, filler);
+}
+
+fn version(major: u16, minor: u16) -> i32 {
+    (major as u32 | (minor as u32) << 16) as i32
+}
+
+/// The encoding of a base type
+fn base(vt: u16) -> i32 {
+    (0x8000_0000 | (vt as u32) << 16 | vt as u32) as i32
+}
+
+/// The segments being built, and the offsets of the entries which are shared
+#[derive(Default)]
+struct Writer {
+    impinfos: Vec<u8>,
+    impfiles: Vec<u8>,
+    refs: Vec<u8>,
+    guid_hash: [i32; 32],
+    guids: Vec<u8>,
+    names: Vec<u8>,
+    strings: Vec<u8>,
+    typedescs: Vec<u8>,
+    arraydescs: Vec<u8>,
+    custdata: Vec<u8>,
+    name_offsets: HashMap<String, i32>,
+    string_offsets: HashMap<String, i32>,
+    typedesc_offsets: HashMap<[u8; 8], i32>,
+    impfile_offsets: HashMap<String, i32>,
+    impinfo_offsets: HashMap<(String, Guid), i32>,
+    /// The hreftype of `IDispatch`, which dispinterfaces derive from
+    dispatch: i32,
+}
+
+/// A type info, whose record is written once the offset of its member data is known
+struct TypeInfoRecord {
+    kind: i32,
+    funcs: i32,
+    vars: i32,
+    guid: i32,
+    flags: i32,
+    name: i32,
+    version: i32,
+    doc: i32,
+    impl_count: i16,
+    vtable_size: i16,
+    size: i32,
+    datatype1: i32,
+    members: Vec<u8>,
+}
+
+impl TypeInfoRecord {
+    fn record(&self, memoffset: i32) -> Vec<u8> {
+        let count = self.funcs + self.vars;
+        let mut record = Vec::with_capacity(TYPEINFO_SIZE);
+        put(
+            &mut record,
+            &[
+                self.kind,
+                memoffset,
+                count * 0x40,
+                if count == 0 { -1 } else { (count - 1) * 0x38 },
+                3,
+                0,
+                self.funcs | self.vars << 16,
+                0,
+                0,
+                0,
+                0,
+                self.guid,
+                self.flags,
+                self.name,
+                self.version,
+                self.doc,
+                0,
+                0,
+                -1,
+            ],
+        );
+        put_i16(&mut record, self.impl_count);
+        put_i16(&mut record, self.vtable_size);
+        put(&mut record, &[self.size, self.datatype1, 0, 0, -1]);
+        record
+    }
+}
+
+impl Writer {
+    fn guid(&mut self, guid: &Guid, hreftype: i32) -> i32 {
+        let mut bytes = Vec::with_capacity(16);
+        bytes.extend_from_slice(&guid.data1.to_le_bytes());
+        bytes.extend_from_slice(&guid.data2.to_le_bytes());
+        bytes.extend_from_slice(&guid.data3.to_le_bytes());
+        bytes.extend_from_slice(&guid.data4);
+        let bucket = bytes.chunks(2).fold(0, |hash, word| {
+            hash ^ u16::from_le_bytes([word[0], word[1]])
+        }) as usize
+            & 0x1f;
+        let offset = self.guids.len() as i32;
+        self.guids.extend_from_slice(&bytes);
+        put(&mut self.guids, &[hreftype, self.guid_hash[bucket]]);
+        self.guid_hash[bucket] = offset;
+        offset
+    }
+
+    /// The offset of a name, whose hreftype is that of the first type info using it
+    fn name(&mut self, name: &str, hreftype: i32) -> i32 {
+        if let Some(offset) = self.name_offsets.get(name) {
+            return *offset;
+        }
+        let offset = self.names.len() as i32;
+        put(&mut self.names, &[hreftype, -1, name.len() as i32 & 0xff]);
+        self.names.extend_from_slice(name.as_bytes());
+        pad(&mut self.names, 0x57);
+        self.name_offsets.insert(name.to_owned(), offset);
+        offset
+    }
+
+    fn optional_string(&mut self, string: Option<&str>) -> i32 {
+        let string = match string {
+            Some(string) => string,
+            None => return -1,
+        };
+        if let Some(offset) = self.string_offsets.get(string) {
+            return *offset;
+        }
+        let offset = self.strings.len() as i32;
+        put_i16(&mut self.strings, string.len() as i16);
+        self.strings.extend_from_slice(string.as_bytes());
+        pad(&mut self.strings, 0x57);
+        self.string_offsets.insert(string.to_owned(), offset);
+        offset
+    }
+
+    /// The offset of the entry of an imported type library
+    fn impfile(&mut self, lib: &TypeLib, file: &str) -> i32 {
+        if let Some(offset) = self.impfile_offsets.get(file) {
+            return *offset;
+        }
+        let offset = self.impfiles.len() as i32;
+        let import = lib.imports.iter().find(|i| i.file == file);
+        let guid = match import.and_then(|i| i.guid.as_ref()) {
+            Some(guid) => self.guid(guid, offset | 2),
+            None => -1,
+        };
+        let (lcid, major, minor) = import
+            .map(|i| (i.lcid, i.major_version, i.minor_version))
+            .unwrap_or_default();
+        put(
+            &mut self.impfiles,
+            &[guid, lcid as i32, version(major, minor)],
+        );
+        put_i16(&mut self.impfiles, ((file.len() << 2) | 1) as i16);
+        self.impfiles.extend_from_slice(file.as_bytes());
+        pad(&mut self.impfiles, 0x57);
+        self.impfile_offsets.insert(file.to_owned(), offset);
+        offset
+    }
+
+    /// The hreftype of a type info of this library or of an imported one
+    fn type_ref(&mut self, lib: &TypeLib, reference: &TypeRef) -> i32 {
+        let (guid, file) = match reference {
+            TypeRef::Local(index) => return (index * TYPEINFO_SIZE) as i32,
+            TypeRef::Imported { guid, file } => (guid, file),
+        };
+        let key = (file.clone(), *guid);
+        if let Some(offset) = self.impinfo_offsets.get(&key) {
+            return offset | 1;
+        }
+        let impfile = self.impfile(lib, file);
+        let offset = self.impinfos.len() as i32;
+        let guid = self.guid(guid, offset | 1);
+        // Imported type infos are assumed to be interfaces
+        let flags = (TypeKind::Interface as i32) << 24 | IMPINFO_OFFSET_IS_GUID;
+        put(&mut self.impinfos, &[flags, impfile, guid]);
+        self.impinfo_offsets.insert(key, offset);
+        offset | 1
+    }
+
+    fn type_desc(&mut self, lib: &TypeLib, ty: &TypeDesc) -> i32 {
+        let (vt, target) = match ty {
+            TypeDesc::Base(vt) => return base(*vt),
+            TypeDesc::Ptr(ty) => (VT_PTR, self.type_desc(lib, ty)),
+            TypeDesc::SafeArray(ty) => (VT_SAFEARRAY, self.type_desc(lib, ty)),
+            TypeDesc::CArray(ty, dims) => {
+                let element = self.type_desc(lib, ty);
+                let offset = self.arraydescs.len() as i32;
+                put(&mut self.arraydescs, &[element]);
+                put_i16(&mut self.arraydescs, dims.len() as i16);
+                put_i16(&mut self.arraydescs, 0);
+                for dim in dims {
+                    put(&mut self.arraydescs, &[*dim as i32, 0]);
+                }
+                (VT_CARRAY, offset)
+            }
+            TypeDesc::UserDefined(reference) => (VT_USERDEFINED, self.type_ref(lib, reference)),
+        };
+        let mut entry = [0; 8];
+        entry[..2].copy_from_slice(&vt.to_le_bytes());
+        entry[2..4].copy_from_slice(&0x7ffe_i16.to_le_bytes());
+        entry[4..].copy_from_slice(&target.to_le_bytes());
+        if let Some(offset) = self.typedesc_offsets.get(&entry) {
+            return *offset;
+        }
+        let offset = self.typedescs.len() as i32;
+        self.typedescs.extend_from_slice(&entry);
+        self.typedesc_offsets.insert(entry, offset);
+        offset
+    }
+
+    /// The encoding of a constant, which is packed when it is a small positive
+    /// integer and stored in the custom data segment otherwise
+    fn value(&mut self, value: &Value) -> i32 {
+        let offset = self.custdata.len() as i32;
+        match value {
+            Value::Int(n) if (0..0x400_0000).contains(n) => {
+                return (0x8000_0000 | (VT_I4 as u32) << 26 | *n as u32) as i32
+            }
+            Value::Int(n) if *n as i32 as i64 == *n => {
+                put_i16(&mut self.custdata, VT_I4 as i16);
+                put(&mut self.custdata, &[*n as i32]);
+            }
+            Value::Int(n) => {
+                put_i16(&mut self.custdata, VT_I8 as i16);
+                self.custdata.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::Float(f) => {
+                put_i16(&mut self.custdata, VT_R8 as i16);
+                self.custdata.extend_from_slice(&f.to_le_bytes());
+            }
+            Value::Str(s) => {
+                put_i16(&mut self.custdata, VT_BSTR as i16);
+                put(&mut self.custdata, &[s.len() as i32]);
+                self.custdata.extend_from_slice(s.as_bytes());
+            }
+        }
+        pad(&mut self.custdata, 0);
+        offset
+    }
+
+    fn type_info(&mut self, lib: &TypeLib, index: usize, info: &TypeInfo) -> TypeInfoRecord {
+        let hreftype = (index * TYPEINFO_SIZE) as i32;
+        let name = self.name(&info.name, hreftype);
+        let guid = match &info.guid {
+            Some(guid) => self.guid(guid, hreftype),
+            None => -1,
+        };
+        let doc = self.optional_string(info.doc_string.as_deref());
+
+        let (impl_count, datatype1) = match info.kind {
+            TypeKind::Coclass if !info.impl_types.is_empty() => {
+                let first = self.refs.len() as i32;
+                for (i, impl_type) in info.impl_types.iter().enumerate() {
+                    let href = self.type_ref(lib, &impl_type.reference);
+                    let next = if i + 1 < info.impl_types.len() {
+                        first + 16 * (i as i32 + 1)
+                    } else {
+                        -1
+                    };
+                    put(&mut self.refs, &[href, impl_type.flags as i32, -1, next]);
+                }
+                (info.impl_types.len() as i16, first)
+            }
+            TypeKind::Interface | TypeKind::Dispatch if !info.impl_types.is_empty() => {
+                let reference = &info.impl_types[0].reference;
+                let href = self.type_ref(lib, reference);
+                match reference {
+                    // Dispinterfaces derive from `IDispatch` unless told otherwise
+                    TypeRef::Imported { guid, .. }
+                        if info.kind == TypeKind::Dispatch && *guid == IID_IDISPATCH =>
+                    {
+                        self.dispatch = href;
+                        (1, -1)
+                    }
+                    _ => (1, href),
+                }
+            }
+            TypeKind::Alias => match &info.alias {
+                Some(alias) => (0, self.type_desc(lib, alias)),
+                None => (0, -1),
+            },
+            _ => (0, -1),
+        };
+
+        TypeInfoRecord {
+            kind: info.kind as i32 | (info.alignment as i32) << 11,
+            funcs: info.funcs.len() as i32,
+            vars: info.vars.len() as i32,
+            guid,
+            flags: info.flags as i32,
+            name,
+            version: version(info.major_version, info.minor_version),
+            doc,
+            impl_count,
+            vtable_size: info.vtable_size as i16,
+            size: info.size as i32,
+            datatype1,
+            members: self.members(lib, hreftype, info),
+        }
+    }
+
+    /// The member data of a type info: the length of the records, the records and
+    /// the arrays of member ids, names and record offsets
+    fn members(&mut self, lib: &TypeLib, hreftype: i32, info: &TypeInfo) -> Vec<u8> {
+        if info.funcs.is_empty() && info.vars.is_empty() {
+            return Vec::new();
+        }
+        let mut records = Vec::new();
+        let mut ids = Vec::new();
+        let mut names = Vec::new();
+        let mut offsets = Vec::new();
+
+        for (index, func) in info.funcs.iter().enumerate() {
+            let mut attributes = Vec::new();
+            if let Some(doc) = &func.doc_string {
+                // The help context comes first
+                attributes = vec![0, self.optional_string(Some(doc))];
+            }
+            let mut params = Vec::new();
+            for param in &func.params {
+                let name = match &param.name {
+                    Some(name) => self.name(name, -1),
+                    None => -1,
+                };
+                let ty = self.type_desc(lib, &param.ty);
+                put(&mut params, &[ty, name, param.flags as i32]);
+            }
+            let length = 24 + 4 * attributes.len() + params.len();
+            let flags = func_kind(func.kind)
+                | invoke_kind(func.invoke_kind) << 3
+                | (func.call_conv as i32) << 8;
+
+            offsets.push(records.len() as i32);
+            let ret = self.type_desc(lib, &func.ret);
+            put(
+                &mut records,
+                &[length as i32 | (index as i32) << 16, ret, func.flags as i32],
+            );
+            put_i16(&mut records, func.vtable_offset);
+            put_i16(&mut records, 0x40);
+            put(&mut records, &[flags]);
+            put_i16(&mut records, func.params.len() as i16);
+            put_i16(&mut records, 0);
+            put(&mut records, &attributes);
+            records.extend_from_slice(&params);
+            ids.push(func.memid);
+            names.push(self.name(&func.name, hreftype));
+        }
+
+        for (index, var) in info.vars.iter().enumerate() {
+            let doc = self.optional_string(var.doc_string.as_deref());
+            let length = if doc == -1 { 20 } else { 28 };
+            let (kind, offset_or_value) = match var.kind {
+                VarKind::PerInstance => (0, var.offset as i32),
+                VarKind::Static => (1, 0),
+                VarKind::Const => match &var.value {
+                    Some(value) => (2, self.value(value)),
+                    None => (2, self.value(&Value::Int(0))),
+                },
+                VarKind::Dispatch => (3, 0),
+            };
+            offsets.push(records.len() as i32);
+            let ty = self.type_desc(lib, &var.ty);
+            put(&mut records, &[length | (index as i32) << 16, ty, 0]);
+            put_i16(&mut records, kind);
+            put_i16(&mut records, 0x24);
+            put(&mut records, &[offset_or_value]);
+            if doc != -1 {
+                put(&mut records, &[0, doc]);
+            }
+            ids.push(var.memid);
+            names.push(self.name(&var.name, hreftype));
+        }
+
+        let mut data = Vec::new();
+        put(&mut data, &[records.len() as i32]);
+        data.extend_from_slice(&records);
+        put(&mut data, &ids);
+        put(&mut data, &names);
+        put(&mut data, &offsets);
+        data
+    }
+}
+
+fn func_kind(kind: FuncKind) -> i32 {
+    match kind {
+        FuncKind::Virtual => 0,
+        FuncKind::PureVirtual => 1,
+        FuncKind::NonVirtual => 2,
+        FuncKind::Static => 3,
+        FuncKind::Dispatch => 4,
+    }
+}
+
+fn invoke_kind(kind: InvokeKind) -> i32 {
+    match kind {
+        InvokeKind::Func => 1,
+        InvokeKind::PropertyGet => 2,
+        InvokeKind::PropertyPut => 4,
+        InvokeKind::PropertyPutRef => 8,
+    }
+}
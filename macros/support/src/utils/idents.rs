@@ -8,3 +8,17 @@ pub fn class_factory_ident(class_ident: &Ident) -> Ident {
 pub fn ref_count_ident() -> Ident {
     format_ident!("__refcnt")
 }
+
+pub fn non_delegating_unknown_ident() -> Ident {
+    format_ident!("__non_delegating_unknown")
+}
+
+pub fn outer_unknown_ident() -> Ident {
+    format_ident!("__outer_unknown")
+}
+
+/// The hidden constant which `interfaces!` declares on an interface for each of its
+/// methods with a `#[retval]` parameter
+pub fn retval_marker_ident(method: &Ident) -> Ident {
+    format_ident!("__{}_has_retval", method)
+}
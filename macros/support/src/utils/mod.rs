@@ -31,7 +31,7 @@ pub fn camel_to_snake(input: &str) -> String {
             new.push_str(&c.to_lowercase().to_string());
         } else {
             seen_lowercase = true;
-            new.push_str(&c.to_string())
+            new.push(c)
         }
     }
 
@@ -0,0 +1,200 @@
+/* Generated from `interfaces!` declarations. Do not edit. */
+
+#pragma once
+
+#include <stdint.h>
+#include <objbase.h>
+#include <oaidl.h>
+
+/* Forward declarations */
+typedef interface IAnimal IAnimal;
+typedef interface ICat ICat;
+typedef interface IDomesticAnimal IDomesticAnimal;
+typedef interface IExample IExample;
+typedef interface ICatClass ICatClass;
+
+/* IAnimal */
+DEFINE_GUID(IID_IAnimal, 0xeff8970e, 0xc50f, 0x45e0, 0x92, 0x84, 0x29, 0x1c, 0xe5, 0xa6, 0xf7, 0x71);
+
+#if defined(__cplusplus) && !defined(CINTERFACE)
+
+MIDL_INTERFACE("EFF8970E-C50F-45E0-9284-291CE5A6F771")
+IAnimal : public IUnknown
+{
+public:
+    virtual HRESULT STDMETHODCALLTYPE Eat(const Food *food) = 0;
+    virtual size_t STDMETHODCALLTYPE Happiness(void) = 0;
+};
+
+#else /* C style interface */
+
+typedef struct IAnimalVtbl
+{
+    BEGIN_INTERFACE
+
+    /* IUnknown */
+    HRESULT (STDMETHODCALLTYPE *QueryInterface)(IAnimal *This, const IID *riid, void **ppvObject);
+    uint32_t (STDMETHODCALLTYPE *AddRef)(IAnimal *This);
+    uint32_t (STDMETHODCALLTYPE *Release)(IAnimal *This);
+
+    /* IAnimal */
+    HRESULT (STDMETHODCALLTYPE *Eat)(IAnimal *This, const Food *food);
+    size_t (STDMETHODCALLTYPE *Happiness)(IAnimal *This);
+
+    END_INTERFACE
+} IAnimalVtbl;
+
+interface IAnimal
+{
+    CONST_VTBL struct IAnimalVtbl *lpVtbl;
+};
+
+#endif
+
+/* ICat */
+DEFINE_GUID(IID_ICat, 0xf5353c58, 0xcfd9, 0x4204, 0x8d, 0x92, 0xd2, 0x74, 0xc7, 0x57, 0x8b, 0x53);
+
+#if defined(__cplusplus) && !defined(CINTERFACE)
+
+MIDL_INTERFACE("F5353C58-CFD9-4204-8D92-D274C7578B53")
+ICat : public IAnimal
+{
+public:
+    virtual HRESULT STDMETHODCALLTYPE IgnoreHumans(void) = 0;
+};
+
+#else /* C style interface */
+
+typedef struct ICatVtbl
+{
+    BEGIN_INTERFACE
+
+    /* IUnknown */
+    HRESULT (STDMETHODCALLTYPE *QueryInterface)(ICat *This, const IID *riid, void **ppvObject);
+    uint32_t (STDMETHODCALLTYPE *AddRef)(ICat *This);
+    uint32_t (STDMETHODCALLTYPE *Release)(ICat *This);
+
+    /* IAnimal */
+    HRESULT (STDMETHODCALLTYPE *Eat)(ICat *This, const Food *food);
+    size_t (STDMETHODCALLTYPE *Happiness)(ICat *This);
+
+    /* ICat */
+    HRESULT (STDMETHODCALLTYPE *IgnoreHumans)(ICat *This);
+
+    END_INTERFACE
+} ICatVtbl;
+
+interface ICat
+{
+    CONST_VTBL struct ICatVtbl *lpVtbl;
+};
+
+#endif
+
+/* IDomesticAnimal */
+DEFINE_GUID(IID_IDomesticAnimal, 0xc22425df, 0xefb2, 0x4b85, 0x93, 0x3e, 0x9c, 0xf7, 0xb2, 0x34, 0x59, 0xe8);
+
+#if defined(__cplusplus) && !defined(CINTERFACE)
+
+MIDL_INTERFACE("C22425DF-EFB2-4B85-933E-9CF7B23459E8")
+IDomesticAnimal : public IAnimal
+{
+public:
+    virtual HRESULT STDMETHODCALLTYPE Train(void) = 0;
+};
+
+#else /* C style interface */
+
+typedef struct IDomesticAnimalVtbl
+{
+    BEGIN_INTERFACE
+
+    /* IUnknown */
+    HRESULT (STDMETHODCALLTYPE *QueryInterface)(IDomesticAnimal *This, const IID *riid, void **ppvObject);
+    uint32_t (STDMETHODCALLTYPE *AddRef)(IDomesticAnimal *This);
+    uint32_t (STDMETHODCALLTYPE *Release)(IDomesticAnimal *This);
+
+    /* IAnimal */
+    HRESULT (STDMETHODCALLTYPE *Eat)(IDomesticAnimal *This, const Food *food);
+    size_t (STDMETHODCALLTYPE *Happiness)(IDomesticAnimal *This);
+
+    /* IDomesticAnimal */
+    HRESULT (STDMETHODCALLTYPE *Train)(IDomesticAnimal *This);
+
+    END_INTERFACE
+} IDomesticAnimalVtbl;
+
+interface IDomesticAnimal
+{
+    CONST_VTBL struct IDomesticAnimalVtbl *lpVtbl;
+};
+
+#endif
+
+/* IExample */
+DEFINE_GUID(IID_IExample, 0xc5f45cbc, 0x4439, 0x418c, 0xa9, 0xf9, 0x05, 0xac, 0x67, 0x52, 0x5e, 0x43);
+
+#if defined(__cplusplus) && !defined(CINTERFACE)
+
+MIDL_INTERFACE("C5F45CBC-4439-418C-A9F9-05AC67525E43")
+IExample : public IUnknown
+{
+public:
+};
+
+#else /* C style interface */
+
+typedef struct IExampleVtbl
+{
+    BEGIN_INTERFACE
+
+    /* IUnknown */
+    HRESULT (STDMETHODCALLTYPE *QueryInterface)(IExample *This, const IID *riid, void **ppvObject);
+    uint32_t (STDMETHODCALLTYPE *AddRef)(IExample *This);
+    uint32_t (STDMETHODCALLTYPE *Release)(IExample *This);
+
+    END_INTERFACE
+} IExampleVtbl;
+
+interface IExample
+{
+    CONST_VTBL struct IExampleVtbl *lpVtbl;
+};
+
+#endif
+
+/* ICatClass */
+DEFINE_GUID(IID_ICatClass, 0xf5353c58, 0xcfd9, 0x4204, 0x8d, 0x92, 0xd2, 0x74, 0xc7, 0x57, 0x8b, 0x53);
+
+#if defined(__cplusplus) && !defined(CINTERFACE)
+
+MIDL_INTERFACE("F5353C58-CFD9-4204-8D92-D274C7578B53")
+ICatClass : public IClassFactory
+{
+public:
+};
+
+#else /* C style interface */
+
+typedef struct ICatClassVtbl
+{
+    BEGIN_INTERFACE
+
+    /* IUnknown */
+    HRESULT (STDMETHODCALLTYPE *QueryInterface)(ICatClass *This, const IID *riid, void **ppvObject);
+    uint32_t (STDMETHODCALLTYPE *AddRef)(ICatClass *This);
+    uint32_t (STDMETHODCALLTYPE *Release)(ICatClass *This);
+
+    /* IClassFactory */
+    HRESULT (STDMETHODCALLTYPE *CreateInstance)(ICatClass *This, IUnknown *pUnkOuter, const IID *riid, void **ppvObject);
+    HRESULT (STDMETHODCALLTYPE *LockServer)(ICatClass *This, BOOL fLock);
+
+    END_INTERFACE
+} ICatClassVtbl;
+
+interface ICatClass
+{
+    CONST_VTBL struct ICatClassVtbl *lpVtbl;
+};
+
+#endif
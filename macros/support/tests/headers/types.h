@@ -0,0 +1,101 @@
+/* Generated from `interfaces!` declarations. Do not edit. */
+
+#pragma once
+
+#include <stdint.h>
+#include <objbase.h>
+#include <oaidl.h>
+
+/* Forward declarations */
+typedef interface ITypes ITypes;
+typedef interface IScriptable IScriptable;
+
+/* ITypes */
+DEFINE_GUID(IID_ITypes, 0x2b1f3c52, 0x7e41, 0x4d0a, 0x9a, 0x3b, 0x6c, 0x1e, 0x0f, 0x7d, 0x8a, 0x21);
+
+#if defined(__cplusplus) && !defined(CINTERFACE)
+
+MIDL_INTERFACE("2B1F3C52-7E41-4D0A-9A3B-6C1E0F7D8A21")
+ITypes : public IUnknown
+{
+public:
+    virtual HRESULT STDMETHODCALLTYPE Primitives(boolean a, int8_t b, uint8_t c, int16_t d, uint16_t e, int32_t f, uint32_t g, int64_t h, uint64_t i) = 0;
+    virtual double STDMETHODCALLTYPE Floats(float a, double b, size_t c, ptrdiff_t d) = 0;
+    virtual HRESULT STDMETHODCALLTYPE Pointers(void *a, void **b, const GUID *c, const uint16_t *const *d) = 0;
+    virtual HRESULT STDMETHODCALLTYPE Automation(BSTR a, VARIANT b, SAFEARRAY *c, BOOL d) = 0;
+    virtual HRESULT STDMETHODCALLTYPE Interfaces(IUnknown *a, ITypes *b, IUnknown **c) = 0;
+    virtual HRESULT STDMETHODCALLTYPE Retval(BSTR *value) = 0;
+    virtual void STDMETHODCALLTYPE NoReturn(void) = 0;
+};
+
+#else /* C style interface */
+
+typedef struct ITypesVtbl
+{
+    BEGIN_INTERFACE
+
+    /* IUnknown */
+    HRESULT (STDMETHODCALLTYPE *QueryInterface)(ITypes *This, const IID *riid, void **ppvObject);
+    uint32_t (STDMETHODCALLTYPE *AddRef)(ITypes *This);
+    uint32_t (STDMETHODCALLTYPE *Release)(ITypes *This);
+
+    /* ITypes */
+    HRESULT (STDMETHODCALLTYPE *Primitives)(ITypes *This, boolean a, int8_t b, uint8_t c, int16_t d, uint16_t e, int32_t f, uint32_t g, int64_t h, uint64_t i);
+    double (STDMETHODCALLTYPE *Floats)(ITypes *This, float a, double b, size_t c, ptrdiff_t d);
+    HRESULT (STDMETHODCALLTYPE *Pointers)(ITypes *This, void *a, void **b, const GUID *c, const uint16_t *const *d);
+    HRESULT (STDMETHODCALLTYPE *Automation)(ITypes *This, BSTR a, VARIANT b, SAFEARRAY *c, BOOL d);
+    HRESULT (STDMETHODCALLTYPE *Interfaces)(ITypes *This, IUnknown *a, ITypes *b, IUnknown **c);
+    HRESULT (STDMETHODCALLTYPE *Retval)(ITypes *This, BSTR *value);
+    void (STDMETHODCALLTYPE *NoReturn)(ITypes *This);
+
+    END_INTERFACE
+} ITypesVtbl;
+
+interface ITypes
+{
+    CONST_VTBL struct ITypesVtbl *lpVtbl;
+};
+
+#endif
+
+/* IScriptable */
+DEFINE_GUID(IID_IScriptable, 0x6a0d9e47, 0x31c8, 0x4f25, 0xb7, 0xe2, 0x0d, 0x4c, 0x9b, 0x1a, 0x5f, 0x36);
+
+#if defined(__cplusplus) && !defined(CINTERFACE)
+
+MIDL_INTERFACE("6A0D9E47-31C8-4F25-B7E2-0D4C9B1A5F36")
+IScriptable : public IDispatch
+{
+public:
+    virtual HRESULT STDMETHODCALLTYPE Run(BSTR script, VARIANT *result) = 0;
+};
+
+#else /* C style interface */
+
+typedef struct IScriptableVtbl
+{
+    BEGIN_INTERFACE
+
+    /* IUnknown */
+    HRESULT (STDMETHODCALLTYPE *QueryInterface)(IScriptable *This, const IID *riid, void **ppvObject);
+    uint32_t (STDMETHODCALLTYPE *AddRef)(IScriptable *This);
+    uint32_t (STDMETHODCALLTYPE *Release)(IScriptable *This);
+
+    /* IDispatch */
+    HRESULT (STDMETHODCALLTYPE *GetTypeInfoCount)(IScriptable *This, uint32_t *pctinfo);
+    HRESULT (STDMETHODCALLTYPE *GetTypeInfo)(IScriptable *This, uint32_t iTInfo, LCID lcid, ITypeInfo **ppTInfo);
+    HRESULT (STDMETHODCALLTYPE *GetIDsOfNames)(IScriptable *This, const IID *riid, LPOLESTR *rgszNames, uint32_t cNames, LCID lcid, DISPID *rgDispId);
+    HRESULT (STDMETHODCALLTYPE *Invoke)(IScriptable *This, DISPID dispIdMember, const IID *riid, LCID lcid, uint16_t wFlags, DISPPARAMS *pDispParams, VARIANT *pVarResult, EXCEPINFO *pExcepInfo, uint32_t *puArgErr);
+
+    /* IScriptable */
+    HRESULT (STDMETHODCALLTYPE *Run)(IScriptable *This, BSTR script, VARIANT *result);
+
+    END_INTERFACE
+} IScriptableVtbl;
+
+interface IScriptable
+{
+    CONST_VTBL struct IScriptableVtbl *lpVtbl;
+};
+
+#endif
@@ -0,0 +1,26 @@
+use com::sys::{BOOL, GUID, HRESULT};
+use com::{BStr, SafeArray, Variant};
+use core::ffi::c_void;
+
+com::interfaces! {
+    /// Exercises the mapping of Rust types to C types
+    #[uuid("2B1F3C52-7E41-4D0A-9A3B-6C1E0F7D8A21")]
+    pub unsafe interface ITypes: IUnknown {
+        fn Primitives(&self, a: bool, b: i8, c: u8, d: i16, e: u16, f: i32, g: u32, h: i64, i: u64) -> HRESULT;
+        fn Floats(&self, a: f32, b: f64, c: usize, d: isize) -> f64;
+        fn Pointers(&self, a: *mut c_void, b: *mut *mut c_void, c: *const GUID, d: *const *const u16) -> HRESULT;
+        fn Automation(&self, a: BStr, b: Variant, c: SafeArray<i32>, #[pass_through] d: BOOL) -> HRESULT;
+        fn Interfaces(&self, a: IUnknown, b: Option<ITypes>, c: *mut Option<IUnknown>) -> HRESULT;
+        fn Retval(&self, #[retval] value: *mut BStr) -> com::HResult;
+        fn NoReturn(&self);
+    }
+}
+
+mod nested {
+    interfaces! {
+        #[uuid("6A0D9E47-31C8-4F25-B7E2-0D4C9B1A5F36")]
+        pub unsafe interface IScriptable: IDispatch {
+            fn Run(&self, script: BStr, #[retval] result: *mut Variant) -> HRESULT;
+        }
+    }
+}
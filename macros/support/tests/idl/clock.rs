@@ -0,0 +1,103 @@
+// Generated from IDL by com-idl. Do not edit.
+
+use com::interfaces;
+use com::interfaces::{IDispatch, IUnknown};
+use com::sys::{BSTR, GUID, HRESULT, VARIANT, VARIANT_BOOL};
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub struct D2D1_COLOR_F {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub struct D2D_MATRIX_3X2_F {
+    pub matrix: [[f32; 2]; 3],
+}
+
+#[allow(non_camel_case_types)]
+pub type _D2D_MATRIX_3X2_F = D2D_MATRIX_3X2_F;
+
+#[allow(non_camel_case_types)]
+pub type PD2D_MATRIX_3X2_F = *mut D2D_MATRIX_3X2_F;
+
+#[repr(C)]
+pub struct ALARM {
+    pub time: f64,
+    pub callback: ::core::mem::ManuallyDrop<Option<IUnknown>>,
+}
+
+#[allow(non_camel_case_types)]
+pub type D2D1_UNIT_MODE = i32;
+pub const D2D1_UNIT_MODE_DIPS: D2D1_UNIT_MODE = 0;
+pub const D2D1_UNIT_MODE_PIXELS: D2D1_UNIT_MODE = 1;
+pub const D2D1_UNIT_MODE_FORCE_DWORD: D2D1_UNIT_MODE = 0xffffffffu32 as i32;
+
+#[allow(non_camel_case_types)]
+pub type CLOCK_FLAGS = i32;
+pub const CLOCK_FLAGS_NONE: CLOCK_FLAGS = 0;
+pub const CLOCK_FLAGS_SECONDS: CLOCK_FLAGS = 1 << 0;
+pub const CLOCK_FLAGS_DATE: CLOCK_FLAGS = 1 << 1;
+pub const CLOCK_FLAGS_ALL: CLOCK_FLAGS = CLOCK_FLAGS_SECONDS | CLOCK_FLAGS_DATE;
+pub const CLOCK_FLAGS_INVALID: CLOCK_FLAGS = -1;
+
+pub const MAX_ALARMS: u32 = 16;
+
+#[allow(non_upper_case_globals)]
+pub const CLSID_Clock: com::CLSID = com::guid!("C5F45CBC-4439-418C-A9F9-05AC67525E43");
+
+interfaces! {
+    #[uuid("54EC77FA-1377-44E6-8C32-88FD5F44C84C")]
+    pub unsafe interface IDXGIDevice: IUnknown {
+        pub fn GetGPUThreadPriority(&self, priority: *mut i32) -> HRESULT;
+    }
+
+    #[uuid("06152247-6F50-465A-9245-118BFD3B6007")]
+    pub unsafe interface ID2D1Factory: IUnknown {
+        pub fn ReloadSystemMetrics(&self) -> HRESULT;
+        pub fn GetDesktopDpi(&self, dpiX: *mut f32, dpiY: *mut f32);
+        pub fn CreateDevice(
+            &self,
+            dxgiDevice: Option<IDXGIDevice>,
+            #[retval] d2dDevice: *mut Option<ID2D1Device>,
+        ) -> HRESULT;
+        pub fn CreateStrokeStyle(
+            &self,
+            color: *const D2D1_COLOR_F,
+            #[size_is(count)] dashes: *const f32,
+            count: u32,
+        ) -> HRESULT;
+        pub fn GetUnitMode(&self) -> D2D1_UNIT_MODE;
+    }
+
+    #[uuid("47DD575D-AC05-4CDD-8049-9B02CD16F44C")]
+    pub unsafe interface ID2D1Device: IUnknown {
+        pub fn SetMaximumTextureMemory(&self, maximumInBytes: u64);
+        pub fn GetMaximumTextureMemory(&self) -> u64;
+    }
+
+    #[uuid("50C83A1C-E072-4C48-87B0-3630FA36A6D0")]
+    pub unsafe interface IClock: IDispatch {
+        pub fn get_Time(&self, #[retval] time: *mut f64) -> HRESULT;
+        pub fn put_Time(&self, time: f64) -> HRESULT;
+        pub fn SetAlarm(
+            &self,
+            alarm: *const ALARM,
+            repeat: VARIANT_BOOL,
+            #[retval] id: *mut i32,
+        ) -> HRESULT;
+        pub fn Name(
+            &self,
+            riid: *const GUID,
+            prefix: *const u16,
+            #[retval] name: *mut BSTR,
+        ) -> HRESULT;
+        pub fn Next(&self, count: u32, values: *mut VARIANT, fetched: *mut u32) -> HRESULT;
+    }
+}
@@ -0,0 +1,61 @@
+// Generated from the type library ClockLib by com-tlb. Do not edit.
+
+use com::interfaces;
+use com::interfaces::{IDispatch, IUnknown};
+use com::sys::{BSTR, HRESULT, SAFEARRAY, VARIANT, VARIANT_BOOL};
+
+#[allow(non_camel_case_types)]
+pub type CLOCK_FLAGS = i32;
+pub const CLOCK_FLAGS_NONE: CLOCK_FLAGS = 0;
+pub const CLOCK_FLAGS_SECONDS: CLOCK_FLAGS = 1;
+pub const CLOCK_FLAGS_DATE: CLOCK_FLAGS = 2;
+pub const CLOCK_FLAGS_INVALID: CLOCK_FLAGS = -1;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub struct D2D1_COLOR_F {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+#[repr(C)]
+pub struct ALARM {
+    pub time: f64,
+    pub callback: ::core::mem::ManuallyDrop<Option<IUnknown>>,
+    pub digits: [[f32; 3]; 2],
+}
+
+#[allow(non_camel_case_types)]
+pub type CLOCK_ID = i32;
+
+#[allow(non_upper_case_globals)]
+pub const CLSID_Clock: com::CLSID = com::guid!("C5F45CBC-4439-418C-A9F9-05AC67525E43");
+
+interfaces! {
+    #[uuid("4A1BD7A8-61C4-4D3B-9F5E-2F1C0D9A7E13")]
+    pub unsafe interface IAlarm: IUnknown {
+        pub fn Ring(&self, count: i32) -> HRESULT;
+        pub fn GetTime(&self, #[retval] time: *mut f64) -> HRESULT;
+        pub fn Next(&self, count: u32, values: *mut VARIANT, fetched: *mut u32) -> HRESULT;
+        pub fn Flags(&self, #[retval] flags: *mut CLOCK_FLAGS) -> HRESULT;
+        pub fn Reset(&self);
+    }
+
+    #[uuid("50C83A1C-E072-4C48-87B0-3630FA36A6D0")]
+    pub unsafe interface IClock: IDispatch {
+        pub fn get_Time(&self, #[retval] time: *mut f64) -> HRESULT;
+        pub fn put_Time(&self, time: f64) -> HRESULT;
+        pub fn SetAlarm(
+            &self,
+            alarm: *mut ALARM,
+            repeat: VARIANT_BOOL,
+            #[retval] id: *mut CLOCK_ID,
+        ) -> HRESULT;
+        pub fn Name(&self, prefix: BSTR, #[retval] name: *mut BSTR) -> HRESULT;
+        pub fn Alarms(&self, #[retval] alarms: *mut *mut SAFEARRAY) -> HRESULT;
+        pub fn Alarm(&self, index: i32, #[retval] alarm: *mut Option<IAlarm>) -> HRESULT;
+    }
+}
@@ -0,0 +1,412 @@
+#!/usr/bin/env python3
+"""Build the MSFT type library fixtures used by the tests of com_macros_support::tlb
+
+MIDL is not available on Linux, so the fixtures are laid out by hand following the
+MSFT format as MIDL writes it: header, type info offsets, segment directory and
+segments, followed by the member data of each type info. The name hash table is
+left empty, which readers do not rely on.
+
+To check the layout against MIDL, the `midl` job of the CI workflow compiles
+clock.idl on Windows, runs the ignored `midl_clock` test on the result and uploads
+it as the `midl-clock-tlb` artifact.
+
+clock.tlb describes the following library (SYS_WIN64):
+
+    [uuid(bb12d362-daee-4b9a-aa1d-14ba401cfa1f), version(1.0), helpstring("Clock library")]
+    library ClockLib
+    {
+        importlib("stdole2.tlb");
+
+        typedef enum CLOCK_FLAGS { CLOCK_FLAGS_NONE = 0, CLOCK_FLAGS_SECONDS = 1,
+                                   CLOCK_FLAGS_DATE = 2, CLOCK_FLAGS_INVALID = -1 } CLOCK_FLAGS;
+        typedef struct D2D1_COLOR_F { float r; float g; float b; float a; } D2D1_COLOR_F;
+        typedef struct ALARM { DATE time; IUnknown *callback; float digits[2][3]; } ALARM;
+        typedef long CLOCK_ID;
+
+        [object, uuid(4a1bd7a8-61c4-4d3b-9f5e-2f1c0d9a7e13), helpstring("An alarm")]
+        interface IAlarm : IUnknown {
+            HRESULT Ring([in] long count);
+            [helpstring("The time of the alarm")] HRESULT GetTime([out, retval] DATE *time);
+            HRESULT Next([in] ULONG count, [out] VARIANT *values, [out] ULONG *fetched);
+            HRESULT Flags([out, retval] CLOCK_FLAGS *flags);
+            void Reset();
+        };
+
+        [object, uuid(50c83a1c-e072-4c48-87b0-3630fa36a6d0), dual, oleautomation]
+        interface IClock : IDispatch {
+            [id(1), propget] HRESULT Time([out, retval] DATE *time);
+            [id(1), propput] HRESULT Time([in] DATE time);
+            [id(2)] HRESULT SetAlarm([in] ALARM *alarm, [in] VARIANT_BOOL repeat, [out, retval] CLOCK_ID *id);
+            [id(3)] HRESULT Name([in] BSTR prefix, [out, retval] BSTR *name);
+            [id(4)] HRESULT Alarms([out, retval] SAFEARRAY(IAlarm *) *alarms);
+            [id(5)] HRESULT Alarm([in] long index, [out, retval] IAlarm **alarm);
+        };
+
+        [uuid(c5f45cbc-4439-418c-a9f9-05ac67525e43)]
+        coclass Clock { [default] interface IClock; interface IAlarm; };
+    };
+
+Run this script from its directory to regenerate the fixtures.
+"""
+import struct
+import uuid
+
+# VARENUM
+VT_EMPTY, VT_I2, VT_I4, VT_R4, VT_R8, VT_DATE, VT_BSTR = 0, 2, 3, 4, 5, 7, 8
+VT_DISPATCH, VT_ERROR, VT_BOOL, VT_VARIANT, VT_UNKNOWN = 9, 10, 11, 12, 13
+VT_UI4, VT_VOID, VT_HRESULT = 19, 24, 25
+VT_PTR, VT_SAFEARRAY, VT_CARRAY, VT_USERDEFINED = 26, 27, 28, 29
+
+TKIND_ENUM, TKIND_RECORD, TKIND_INTERFACE, TKIND_DISPATCH = 0, 1, 3, 4
+TKIND_COCLASS, TKIND_ALIAS = 5, 6
+
+TYPEFLAG_FCANCREATE, TYPEFLAG_FDUAL = 0x2, 0x40
+TYPEFLAG_FOLEAUTOMATION, TYPEFLAG_FDISPATCHABLE = 0x100, 0x1000
+
+FUNC_PUREVIRTUAL, FUNC_DISPATCH = 1, 4
+INVOKE_FUNC, INVOKE_PROPERTYGET, INVOKE_PROPERTYPUT = 1, 2, 4
+CC_STDCALL = 4
+PARAMFLAG_FIN, PARAMFLAG_FOUT, PARAMFLAG_FRETVAL = 1, 2, 8
+VAR_PERINSTANCE, VAR_CONST = 0, 2
+
+SYS_WIN64 = 3
+TYPEINFO_SIZE = 0x64
+
+
+def base(vt):
+    """The encoding of a base type"""
+    return struct.unpack("<i", struct.pack("<I", 0x80000000 | (vt << 16) | vt))[0]
+
+
+def guid_bytes(text):
+    return uuid.UUID(text).bytes_le
+
+
+def pad(data, filler=b"\x57"):
+    while len(data) % 4:
+        data += filler
+    return data
+
+
+class Segment:
+    def __init__(self):
+        self.data = b""
+
+    def add(self, data):
+        offset = len(self.data)
+        self.data += data
+        return offset
+
+
+class TypeLib:
+    def __init__(self):
+        self.guids = Segment()
+        self.guid_hash = [-1] * 32
+        self.names = Segment()
+        self.strings = Segment()
+        self.typedescs = Segment()
+        self.arraydescs = Segment()
+        self.custdata = Segment()
+        self.impinfos = Segment()
+        self.impfiles = Segment()
+        self.refs = Segment()
+        self.typeinfos = []
+        self.name_offsets = {}
+        self.string_offsets = {}
+        self.impfile_offsets = {}
+
+    def guid(self, text, hreftype):
+        data = guid_bytes(text)
+        words = struct.unpack("<8h", data)
+        bucket = 0
+        for word in words:
+            bucket ^= word
+        bucket &= 0x1F
+        offset = self.guids.add(data + struct.pack("<ii", hreftype, self.guid_hash[bucket]))
+        self.guid_hash[bucket] = offset
+        return offset
+
+    def name(self, text, hreftype=-1):
+        if text not in self.name_offsets:
+            raw = text.encode()
+            self.name_offsets[text] = self.names.add(
+                pad(struct.pack("<iii", hreftype, -1, len(raw)) + raw)
+            )
+        return self.name_offsets[text]
+
+    def string(self, text):
+        if text not in self.string_offsets:
+            raw = text.encode()
+            self.string_offsets[text] = self.strings.add(pad(struct.pack("<h", len(raw)) + raw))
+        return self.string_offsets[text]
+
+    def typedesc(self, vt, target):
+        """A pointer, safe array, array or user defined type"""
+        return self.typedescs.add(struct.pack("<hhi", vt, 0x7FFE, target))
+
+    def ptr(self, target):
+        return self.typedesc(VT_PTR, target)
+
+    def carray(self, element, dims):
+        offset = self.arraydescs.add(
+            struct.pack("<ihh", element, len(dims), 0)
+            + b"".join(struct.pack("<ii", n, 0) for n in dims)
+        )
+        return self.typedesc(VT_CARRAY, offset)
+
+    def value(self, value):
+        """The encoding of a constant"""
+        if 0 <= value < 0x4000000:
+            return struct.unpack("<i", struct.pack("<I", 0x80000000 | (VT_I4 << 26) | value))[0]
+        return self.custdata.add(pad(struct.pack("<hi", VT_I4, value), b"\x00"))
+
+    def import_type(self, file_guid, filename, type_guid, kind):
+        if filename not in self.impfile_offsets:
+            offset = len(self.impfiles.data)
+            self.impfile_offsets[filename] = self.impfiles.add(
+                pad(
+                    struct.pack("<iii", self.guid(file_guid, offset | 2), 0, 2)
+                    + struct.pack("<h", (len(filename) << 2) | 1)
+                    + filename.encode()
+                )
+            )
+        impfile = self.impfile_offsets[filename]
+        offset = self.impinfos.add(struct.pack("<iii", (kind << 24) | 0x10000, impfile, 0))
+        guid = self.guid(type_guid, offset | 1)
+        self.impinfos.data = (
+            self.impinfos.data[: offset + 8] + struct.pack("<i", guid) + self.impinfos.data[offset + 12 :]
+        )
+        return offset | 1
+
+
+def func(name, memid, vft, ret, params, invkind=INVOKE_FUNC, funckind=FUNC_PUREVIRTUAL, doc=None):
+    return dict(name=name, memid=memid, vft=vft, ret=ret, params=params,
+                invkind=invkind, funckind=funckind, doc=doc)
+
+
+def var(name, memid, ty, kind, value):
+    return dict(name=name, memid=memid, ty=ty, kind=kind, value=value)
+
+
+def member_data(lib, index, funcs, vars):
+    """The member data of a type info: records, then ids, names and record offsets"""
+    records = b""
+    offsets = []
+    for i, f in enumerate(funcs):
+        attrs = []
+        if f["doc"] is not None:
+            attrs = [0, lib.string(f["doc"])]
+        params = b"".join(
+            struct.pack("<iii", ty, lib.name(pname) if pname else -1, flags)
+            for (pname, ty, flags) in f["params"]
+        )
+        size = 24 + 4 * len(attrs) + len(params)
+        fkccic = f["funckind"] | (f["invkind"] << 3) | (CC_STDCALL << 8)
+        record = struct.pack(
+            "<iiihhihh", size | (i << 16), f["ret"], 0, f["vft"], 0x40, fkccic, len(f["params"]), 0
+        )
+        record += b"".join(struct.pack("<i", a) for a in attrs) + params
+        offsets.append(len(records))
+        records += record
+    for i, v in enumerate(vars):
+        record = struct.pack("<iiihhi", 20 | (i << 16), v["ty"], 0, v["kind"], 0x24, v["value"])
+        offsets.append(len(records))
+        records += record
+    members = funcs + vars
+    ids = b"".join(struct.pack("<i", m["memid"]) for m in members)
+    names = b"".join(struct.pack("<i", lib.name(m["name"], index * TYPEINFO_SIZE)) for m in members)
+    return struct.pack("<i", len(records)) + records + ids + names + b"".join(
+        struct.pack("<i", o) for o in offsets
+    )
+
+
+def typeinfo(lib, kind, name, guid=None, flags=0, doc=None, funcs=(), vars=(), impls=(),
+             datatype1=-1, size=0, align=4, vft=0):
+    index = len(lib.typeinfos)
+    info = dict(
+        kind=kind, align=align, name=lib.name(name, index * TYPEINFO_SIZE),
+        guid=lib.guid(guid, index * TYPEINFO_SIZE) if guid else -1, flags=flags,
+        doc=lib.string(doc) if doc else -1, funcs=len(funcs), vars=len(vars),
+        data=member_data(lib, index, list(funcs), list(vars)) if funcs or vars else b"",
+        impls=len(impls), datatype1=datatype1, size=size, vft=vft,
+    )
+    if impls:
+        # Reference records of a coclass, chained in order
+        first = len(lib.refs.data)
+        for i, (href, implflags) in enumerate(impls):
+            following = first + 16 * (i + 1) if i + 1 < len(impls) else -1
+            lib.refs.add(struct.pack("<iiii", href, implflags, -1, following))
+        info["datatype1"] = first
+    lib.typeinfos.append(info)
+    return index * TYPEINFO_SIZE
+
+
+def write(lib, path, libname, libguid, doc):
+    lib_guid = lib.guid(libguid, -2)
+    lib_name = lib.name(libname)
+    lib_doc = lib.string(doc)
+    segments = [
+        None,  # type infos, filled below
+        lib.impinfos.data,
+        lib.impfiles.data,
+        lib.refs.data,
+        b"".join(struct.pack("<i", h) for h in lib.guid_hash),
+        lib.guids.data,
+        struct.pack("<i", -1) * 128,
+        lib.names.data,
+        lib.strings.data,
+        lib.typedescs.data,
+        lib.arraydescs.data,
+        lib.custdata.data,
+        b"",
+        b"",
+        b"",
+    ]
+    count = len(lib.typeinfos)
+    header_size = 0x54 + 4 * count + 15 * 16
+    segments[0] = b"\x00" * (TYPEINFO_SIZE * count)
+    position = header_size
+    directory = []
+    for data in segments:
+        if data:
+            directory.append((position, len(data)))
+            position += len(data)
+        else:
+            directory.append((-1, 0))
+
+    # Member data follows the segments
+    memoffsets = []
+    member_blob = b""
+    for info in lib.typeinfos:
+        if info["data"]:
+            memoffsets.append(position + len(member_blob))
+            member_blob += info["data"]
+        else:
+            memoffsets.append(None)
+    end = position + len(member_blob)
+
+    tab = b""
+    for info, memoffset in zip(lib.typeinfos, memoffsets):
+        n = info["funcs"] + info["vars"]
+        tab += struct.pack(
+            "<iiiiiiiiiiiiiiiiiiihhiiiii",
+            info["kind"] | (info["align"] << 11),
+            memoffset if memoffset is not None else end,
+            n * 0x40,
+            (n - 1) * 0x38 if n else -1,
+            3,
+            0,
+            info["funcs"] | (info["vars"] << 16),
+            0, 0, 0, 0,
+            info["guid"],
+            info["flags"],
+            info["name"],
+            1,  # version 1.0
+            info["doc"],
+            0, 0, -1,
+            info["impls"],
+            info["vft"],
+            info["size"],
+            info["datatype1"],
+            0,
+            0,
+            -1,
+        )
+    segments[0] = tab
+
+    header = struct.pack(
+        "<iiiiiiiiiiiiiiiiiiiii",
+        0x5446534D, 0x00010002, lib_guid, 0x409, 0, SYS_WIN64 | 0x10, 1, 0x8,
+        count, lib_doc, 0, 0, len(lib.name_offsets), len(lib.names.data), lib_name, -1, -1, 0x20, 0x80,
+        lib.dispatch, len(lib.impinfos.data) // 12,
+    )
+    offsets = b"".join(struct.pack("<i", i * TYPEINFO_SIZE) for i in range(count))
+    directory_data = b"".join(struct.pack("<iiii", o, n, -1, 0x0F) for (o, n) in directory)
+    with open(path, "wb") as f:
+        f.write(header + offsets + directory_data + b"".join(segments) + member_blob)
+
+
+def clock():
+    lib = TypeLib()
+    stdole = "00020430-0000-0000-c000-000000000046"
+    iunknown = lib.import_type(stdole, "stdole2.tlb", "00000000-0000-0000-c000-000000000046", TKIND_INTERFACE)
+    idispatch = lib.import_type(stdole, "stdole2.tlb", "00020400-0000-0000-c000-000000000046", TKIND_INTERFACE)
+    lib.dispatch = idispatch
+
+    flags = typeinfo(lib, TKIND_ENUM, "CLOCK_FLAGS", size=4, vars=[
+        var("CLOCK_FLAGS_NONE", 0x40000000, base(VT_I4), VAR_CONST, lib.value(0)),
+        var("CLOCK_FLAGS_SECONDS", 0x40000001, base(VT_I4), VAR_CONST, lib.value(1)),
+        var("CLOCK_FLAGS_DATE", 0x40000002, base(VT_I4), VAR_CONST, lib.value(2)),
+        var("CLOCK_FLAGS_INVALID", 0x40000003, base(VT_I4), VAR_CONST, lib.value(-1)),
+    ])
+    typeinfo(lib, TKIND_RECORD, "D2D1_COLOR_F", size=16, vars=[
+        var(n, 0x40000000 + i, base(VT_R4), VAR_PERINSTANCE, 4 * i) for i, n in enumerate("rgba")
+    ])
+    alarm_struct = typeinfo(lib, TKIND_RECORD, "ALARM", size=40, align=8, vars=[
+        var("time", 0x40000000, base(VT_DATE), VAR_PERINSTANCE, 0),
+        var("callback", 0x40000001, base(VT_UNKNOWN), VAR_PERINSTANCE, 8),
+        var("digits", 0x40000002, lib.carray(base(VT_R4), [2, 3]), VAR_PERINSTANCE, 16),
+    ])
+    clock_id = typeinfo(lib, TKIND_ALIAS, "CLOCK_ID", size=4, datatype1=base(VT_I4))
+
+    hresult = base(VT_HRESULT)
+    alarm = typeinfo(
+        lib, TKIND_INTERFACE, "IAlarm", "4a1bd7a8-61c4-4d3b-9f5e-2f1c0d9a7e13",
+        doc="An alarm", datatype1=iunknown, impls=(), vft=8 * 8, align=8, funcs=[
+            func("Ring", 0x60010000, 24, hresult, [("count", base(VT_I4), PARAMFLAG_FIN)]),
+            func("GetTime", 0x60010001, 32, hresult,
+                 [("time", lib.ptr(base(VT_DATE)), PARAMFLAG_FOUT | PARAMFLAG_FRETVAL)],
+                 doc="The time of the alarm"),
+            func("Next", 0x60010002, 40, hresult, [
+                ("count", base(VT_UI4), PARAMFLAG_FIN),
+                ("values", lib.ptr(base(VT_VARIANT)), PARAMFLAG_FOUT),
+                ("fetched", lib.ptr(base(VT_UI4)), PARAMFLAG_FOUT),
+            ]),
+            func("Flags", 0x60010003, 48, hresult, [
+                ("flags", lib.ptr(lib.typedesc(VT_USERDEFINED, flags)),
+                 PARAMFLAG_FOUT | PARAMFLAG_FRETVAL),
+            ]),
+            func("Reset", 0x60010004, 56, base(VT_VOID), []),
+        ])
+    lib.typeinfos[-1]["impls"] = 1
+
+    alarm_ptr = lib.ptr(lib.typedesc(VT_USERDEFINED, alarm))
+    typeinfo(
+        lib, TKIND_DISPATCH, "IClock", "50c83a1c-e072-4c48-87b0-3630fa36a6d0",
+        flags=TYPEFLAG_FDUAL | TYPEFLAG_FOLEAUTOMATION | TYPEFLAG_FDISPATCHABLE,
+        vft=13 * 8, align=8, funcs=[
+            func("Time", 1, 56, hresult,
+                 [("time", lib.ptr(base(VT_DATE)), PARAMFLAG_FOUT | PARAMFLAG_FRETVAL)],
+                 INVOKE_PROPERTYGET, FUNC_DISPATCH),
+            func("Time", 1, 64, hresult, [("time", base(VT_DATE), PARAMFLAG_FIN)],
+                 INVOKE_PROPERTYPUT, FUNC_DISPATCH),
+            func("SetAlarm", 2, 72, hresult, [
+                ("alarm", lib.ptr(lib.typedesc(VT_USERDEFINED, alarm_struct)), PARAMFLAG_FIN),
+                ("repeat", base(VT_BOOL), PARAMFLAG_FIN),
+                ("id", lib.ptr(lib.typedesc(VT_USERDEFINED, clock_id)),
+                 PARAMFLAG_FOUT | PARAMFLAG_FRETVAL),
+            ], funckind=FUNC_DISPATCH),
+            func("Name", 3, 80, hresult, [
+                ("prefix", base(VT_BSTR), PARAMFLAG_FIN),
+                ("name", lib.ptr(base(VT_BSTR)), PARAMFLAG_FOUT | PARAMFLAG_FRETVAL),
+            ], funckind=FUNC_DISPATCH),
+            func("Alarms", 4, 88, hresult, [
+                ("alarms", lib.ptr(lib.typedesc(VT_SAFEARRAY, alarm_ptr)),
+                 PARAMFLAG_FOUT | PARAMFLAG_FRETVAL),
+            ], funckind=FUNC_DISPATCH),
+            func("Alarm", 5, 96, hresult, [
+                ("index", base(VT_I4), PARAMFLAG_FIN),
+                ("alarm", lib.ptr(alarm_ptr), PARAMFLAG_FOUT | PARAMFLAG_FRETVAL),
+            ], funckind=FUNC_DISPATCH),
+        ])
+    lib.typeinfos[-1]["impls"] = 1
+    clock_interface = (len(lib.typeinfos) - 1) * TYPEINFO_SIZE
+
+    typeinfo(lib, TKIND_COCLASS, "Clock", "c5f45cbc-4439-418c-a9f9-05ac67525e43",
+             flags=TYPEFLAG_FCANCREATE, impls=[(clock_interface, 1), (alarm, 0)])
+
+    write(lib, "clock.tlb", "ClockLib", "bb12d362-daee-4b9a-aa1d-14ba401cfa1f", "Clock library")
+
+
+if __name__ == "__main__":
+    clock()
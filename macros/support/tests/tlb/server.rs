@@ -0,0 +1,86 @@
+//! The declarations of a COM server, from which the tests of `tlb::build` build a
+//! type library
+use com::interfaces::{IDispatch, IUnknown};
+use com::sys::HRESULT;
+use com::{BStr, SafeArray, Variant};
+
+com::interfaces! {
+    /// An animal
+    ///
+    /// Only the first paragraph becomes the help string.
+    #[uuid("eff8970e-c50f-45e0-9284-291ce5a6f771")]
+    pub unsafe interface IAnimal: IUnknown {
+        /// Make the animal eat
+        fn Eat(&self) -> HRESULT;
+        fn Name(&self, #[retval] name: *mut BStr) -> HRESULT;
+    }
+
+    #[uuid("32a48b9b-e8cc-4bc5-a4a4-c7fdb0a8c1d6")]
+    pub unsafe interface ICat: IAnimal {
+        fn IgnoreHumans(&self, count: u32, ignored: *mut u32) -> HRESULT;
+        fn Friend(&self, #[retval] friend: *mut Option<ICat>) -> HRESULT;
+    }
+
+    /// A scriptable object
+    #[uuid("2d39c8a3-86f5-4f7a-8f6e-2a2b2f4b7c06")]
+    pub unsafe interface IScript: IDispatch {
+        fn Run(&self, source: BStr, args: SafeArray<Variant>, #[retval] result: *mut Variant) -> HRESULT;
+        fn Lines(&self, #[retval] lines: *mut i32) -> HRESULT;
+    }
+}
+
+com::class! {
+    /// A cat which runs scripts
+    #[dispatch]
+    pub class ScriptedCat: ICat(IAnimal), IScript(IDispatch) {}
+
+    impl IAnimal for ScriptedCat {
+        fn Eat(&self) -> HRESULT {
+            com::sys::S_OK
+        }
+
+        fn Name(&self) -> Result<BStr, com::Error> {
+            Ok(BStr::from("Tom"))
+        }
+    }
+
+    impl ICat for ScriptedCat {
+        fn IgnoreHumans(&self, count: u32, ignored: *mut u32) -> HRESULT {
+            unsafe { *ignored = count };
+            com::sys::S_OK
+        }
+
+        fn Friend(&self) -> Result<Option<ICat>, com::Error> {
+            Ok(None)
+        }
+    }
+
+    impl IScript for ScriptedCat {
+        fn Run(&self, _source: BStr, _args: SafeArray<Variant>) -> Result<Variant, com::Error> {
+            Ok(Variant::default())
+        }
+
+        fn Lines(&self) -> Result<i32, com::Error> {
+            Ok(0)
+        }
+    }
+}
+
+// Classes without a CLSID are not part of the type library
+com::class! {
+    pub class Kitten: IAnimal {}
+
+    impl IAnimal for Kitten {
+        fn Eat(&self) -> HRESULT {
+            com::sys::S_OK
+        }
+
+        fn Name(&self) -> Result<BStr, com::Error> {
+            Ok(BStr::from("Kitty"))
+        }
+    }
+}
+
+pub const CLSID_SCRIPTED_CAT: com::CLSID = com::guid!("c5f45cbc-4439-418c-a9f9-05ac67525e43");
+
+com::inproc_dll_module![(CLSID_SCRIPTED_CAT, ScriptedCat),];
@@ -39,6 +39,15 @@ pub unsafe trait AbiTransferable: Sized {
         core::slice::from_raw_parts_mut(core::mem::transmute_copy(&abi), len)
     }
 
+    /// Convert an `[in]` parameter received by a method implemented with `class!`
+    ///
+    /// The caller keeps ownership of `[in]` parameters, so types which free a resource
+    /// when they are dropped must return a copy instead of taking ownership of `abi`.
+    /// By default, this is the same as [`AbiTransferable::from_abi`].
+    fn from_in_param(abi: Self::Abi) -> Self {
+        Self::from_abi(abi)
+    }
+
     /// Converts and consumes the ABI transferable type into its ABI representation.
     fn into_abi(self) -> Self::Abi {
         // This must be safe for the implementing type to
@@ -74,7 +83,8 @@ primitive_transferable_type! {
     f64,
     usize,
     isize,
-    crate::sys::GUID
+    crate::sys::GUID,
+    crate::HResult
 }
 
 unsafe impl<T> AbiTransferable for *mut T {
@@ -96,6 +106,13 @@ unsafe impl<T: crate::Interface> AbiTransferable for T {
     fn get_abi(&self) -> Self::Abi {
         self.as_raw()
     }
+
+    /// The caller keeps its reference, so this adds a new one
+    fn from_in_param(abi: Self::Abi) -> Self {
+        let this = Self::from_abi(abi);
+        unsafe { this.as_iunknown().AddRef() };
+        this
+    }
 }
 
 unsafe impl<T: crate::Interface> AbiTransferable for Option<T> {
@@ -105,4 +122,13 @@ unsafe impl<T: crate::Interface> AbiTransferable for Option<T> {
             .map(|p| p.as_raw().as_ptr())
             .unwrap_or(::core::ptr::null_mut())
     }
+
+    /// The caller keeps its reference, so this adds a new one
+    fn from_in_param(abi: Self::Abi) -> Self {
+        let this = Self::from_abi(abi);
+        if let Some(this) = &this {
+            unsafe { this.as_iunknown().AddRef() };
+        }
+        this
+    }
 }
@@ -0,0 +1,195 @@
+//! Interface pointers which can be used from any apartment
+//!
+//! Interface pointers belong to the apartment they were created in: they are
+//! neither `Send` nor `Sync`, and the objects of a single-threaded apartment (STA)
+//! must only be called from its thread. The global interface table of the process
+//! turns an interface pointer into a [`Cookie`], which can be sent to other threads
+//! and turned back into an interface pointer for their apartment. Threads of other
+//! apartments get a proxy, whose calls run on the thread of the object.
+//! [`Agile`] wraps a cookie for an interface of a known type, and revokes it when it
+//! is dropped.
+//!
+//! ```rust
+//! # com::interfaces! {
+//! #     #[uuid("EFF8970E-C50F-45E0-9284-291CE5A6F771")]
+//! #     #[remote]
+//! #     pub unsafe interface IAnimal: com::interfaces::IUnknown {
+//! #         fn Eat(&self) -> com::sys::HRESULT;
+//! #     }
+//! # }
+//! # com::class! {
+//! #     pub class BritishShortHairCat: IAnimal {}
+//! #     impl IAnimal for BritishShortHairCat {
+//! #         fn Eat(&self) -> com::sys::HRESULT { com::sys::NOERROR }
+//! #     }
+//! # }
+//! # #[cfg(not(windows))]
+//! # fn main() -> Result<(), com::Error> {
+//! use com::runtime::{spawn, ApartmentType, StaThread};
+//! use com::Agile;
+//!
+//! let sta = StaThread::spawn()?;
+//! let cat = sta.dispatcher().invoke(|| {
+//!     let cat = BritishShortHairCat::allocate();
+//!     Agile::new(&cat.query_interface::<IAnimal>().unwrap())
+//! })??;
+//!
+//! // The calls of the other thread run on the thread of the STA
+//! spawn(ApartmentType::Multithreaded, move || -> Result<(), com::Error> {
+//!     let cat = cat.resolve()?;
+//!     com::HResult(unsafe { cat.Eat() }).ok()
+//! })
+//! .join()
+//! .unwrap()??;
+//! # Ok(())
+//! # }
+//! # #[cfg(windows)]
+//! # fn main() {}
+//! ```
+//!
+//! On Windows, this is the `IGlobalInterfaceTable` of the COM runtime, which uses the
+//! proxies and stubs registered with the system. Elsewhere, the objects are called
+//! through the [`Dispatcher`](crate::runtime::Dispatcher) of their apartment with
+//! the proxies and stubs generated for `#[remote]` interfaces, so the thread which
+//! registers an object in a single-threaded apartment must run a
+//! [`MessageLoop`](crate::runtime::MessageLoop). Objects registered from the
+//! multithreaded apartment are expected to be thread safe, and all threads get the
+//! object itself.
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::remote::Remote;
+use crate::Error;
+
+#[cfg(windows)]
+use crate::interfaces::iglobal_interface_table::{
+    IGlobalInterfaceTable, CLSID_STD_GLOBAL_INTERFACE_TABLE,
+};
+#[cfg(not(windows))]
+use crate::AbiTransferable;
+#[cfg(windows)]
+use crate::{HResult, Interface};
+
+/// The value of a cookie
+#[cfg(windows)]
+type RawCookie = u32;
+#[cfg(not(windows))]
+type RawCookie = u64;
+
+/// Identifies an interface pointer registered in the global interface table
+///
+/// Cookies can be sent to other threads, and stay valid until they are revoked with
+/// [`revoke_interface`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Cookie(RawCookie);
+
+#[cfg(windows)]
+fn global_interface_table() -> Result<IGlobalInterfaceTable, Error> {
+    crate::runtime::create_instance(&CLSID_STD_GLOBAL_INTERFACE_TABLE)
+}
+
+/// Register an interface pointer of the current apartment in the global interface
+/// table
+///
+/// Fails with `CO_E_NOTINITIALIZED` if the current thread is not in an apartment.
+pub fn register_interface<I: Remote>(interface: &I) -> Result<Cookie, Error> {
+    #[cfg(windows)]
+    {
+        let unknown = interface.as_iunknown().as_raw().as_ptr();
+        let cookie =
+            unsafe { global_interface_table()?.RegisterInterfaceInGlobal(unknown, &I::IID)? };
+        Ok(Cookie(cookie))
+    }
+    #[cfg(not(windows))]
+    {
+        I::register();
+        crate::remote::apartment::register(interface.as_iunknown(), &I::IID).map(Cookie)
+    }
+}
+
+/// Get the interface pointer registered with `cookie` for use in the current
+/// apartment
+///
+/// This is the object itself in its apartment, and a proxy in other apartments.
+/// `I` must be the interface the pointer was registered as.
+pub fn get_interface<I: Remote>(cookie: Cookie) -> Result<I, Error> {
+    #[cfg(windows)]
+    {
+        let mut interface = None::<I>;
+        let hr = unsafe {
+            global_interface_table()?.GetInterfaceFromGlobal(
+                cookie.0,
+                &I::IID,
+                &mut interface as *mut Option<I> as *mut *mut core::ffi::c_void,
+            )
+        };
+        HResult(hr).ok()?;
+        Ok(interface.unwrap())
+    }
+    #[cfg(not(windows))]
+    {
+        I::register();
+        let interface = crate::remote::apartment::get(cookie.0, &I::IID)?;
+        // The table returns a pointer to the interface `I`
+        Ok(I::from_abi(interface.into_abi().cast()))
+    }
+}
+
+/// Remove an interface pointer from the global interface table
+///
+/// The object is released once the interface pointers returned by [`get_interface`]
+/// are released as well.
+pub fn revoke_interface(cookie: Cookie) -> Result<(), Error> {
+    #[cfg(windows)]
+    {
+        HResult(unsafe { global_interface_table()?.RevokeInterfaceFromGlobal(cookie.0) }).ok()
+    }
+    #[cfg(not(windows))]
+    {
+        crate::remote::apartment::revoke(cookie.0)
+    }
+}
+
+/// An interface pointer which can be sent to other threads and used from any
+/// apartment
+///
+/// The interface is registered in the global interface table until the `Agile` is
+/// dropped. See the [module documentation](self) for an example.
+pub struct Agile<I> {
+    cookie: Cookie,
+    interface: PhantomData<fn() -> I>,
+}
+
+impl<I: Remote> Agile<I> {
+    /// Register `interface`, which belongs to the current apartment
+    pub fn new(interface: &I) -> Result<Self, Error> {
+        Ok(Self {
+            cookie: register_interface(interface)?,
+            interface: PhantomData,
+        })
+    }
+
+    /// Get the interface pointer for use in the current apartment
+    pub fn resolve(&self) -> Result<I, Error> {
+        get_interface(self.cookie)
+    }
+}
+
+impl<I> Agile<I> {
+    /// The cookie of the interface in the global interface table
+    pub fn cookie(&self) -> Cookie {
+        self.cookie
+    }
+}
+
+impl<I> Drop for Agile<I> {
+    fn drop(&mut self) {
+        let _ = revoke_interface(self.cookie);
+    }
+}
+
+impl<I> fmt::Debug for Agile<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Agile").field(&self.cookie).finish()
+    }
+}
@@ -0,0 +1,327 @@
+use alloc::string::String;
+use core::convert::TryFrom;
+use core::fmt;
+
+use crate::sys::BSTR;
+use crate::{AbiTransferable, Param};
+
+/// An owned [BSTR](https://docs.microsoft.com/en-us/previous-versions/windows/desktop/automat/bstr)
+///
+/// A `BSTR` is a length-prefixed, null-terminated UTF-16 string which is allocated
+/// with `SysAllocString`. It is the string type used by automation interfaces. A null
+/// `BSTR` is equivalent to an empty string.
+///
+/// `BStr` can be used directly as a parameter type in `interfaces!` and `class!`
+/// methods:
+///
+/// ```rust
+/// # use com::{interfaces::IUnknown, sys::HRESULT, BStr};
+/// com::interfaces! {
+///     #[uuid("2d39c8a3-86f5-4f7a-8f6e-2a2b2f4b7c01")]
+///     pub unsafe interface INamed: IUnknown {
+///         fn SetName(&self, name: BStr) -> HRESULT;
+///         fn GetName(&self, #[retval] name: *mut BStr) -> HRESULT;
+///     }
+/// }
+/// ```
+///
+/// On Windows, strings are allocated with `SysAllocStringLen` and freed with
+/// `SysFreeString`. On other platforms, the same memory layout is reproduced with the
+/// Rust allocator.
+#[repr(transparent)]
+pub struct BStr(BSTR);
+
+impl BStr {
+    /// Create an empty string
+    pub const fn new() -> Self {
+        Self(core::ptr::null_mut())
+    }
+
+    /// Create a string from UTF-16 code units
+    pub fn from_wide(s: &[u16]) -> Self {
+        if s.is_empty() {
+            return Self::new();
+        }
+        Self(unsafe { alloc_string(s) })
+    }
+
+    /// Take ownership of a raw `BSTR`
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be null or a `BSTR` allocated with `SysAllocString` (or, on other
+    /// platforms than Windows, by this type), which is not owned by anyone else.
+    pub unsafe fn from_raw(raw: BSTR) -> Self {
+        Self(raw)
+    }
+
+    /// Give up ownership of the raw `BSTR`
+    ///
+    /// The caller is responsible for freeing the string, e.g. by passing it back to
+    /// [`BStr::from_raw`].
+    pub fn into_raw(self) -> BSTR {
+        let raw = self.0;
+        core::mem::forget(self);
+        raw
+    }
+
+    /// The raw `BSTR`, which may be null for empty strings
+    pub fn as_ptr(&self) -> BSTR {
+        self.0
+    }
+
+    /// The length of the string in UTF-16 code units, not including the terminating null
+    pub fn len(&self) -> usize {
+        if self.0.is_null() {
+            0
+        } else {
+            unsafe { string_len(self.0) as usize }
+        }
+    }
+
+    /// Whether the string is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The UTF-16 code units of the string, not including the terminating null
+    pub fn as_wide(&self) -> &[u16] {
+        if self.0.is_null() {
+            &[]
+        } else {
+            unsafe { core::slice::from_raw_parts(self.0, self.len()) }
+        }
+    }
+
+    /// Decode the string, replacing invalid UTF-16 with `U+FFFD`
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(self.as_wide())
+    }
+}
+
+impl Default for BStr {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for BStr {
+    fn clone(&self) -> Self {
+        Self::from_wide(self.as_wide())
+    }
+}
+
+impl Drop for BStr {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { free_string(self.0) }
+        }
+    }
+}
+
+/// [`BStr`] is [`Send`] and [`Sync`] because it uniquely owns its allocation, which
+/// is only mutated through `&mut self`.
+unsafe impl Send for BStr {}
+unsafe impl Sync for BStr {}
+
+impl From<&str> for BStr {
+    fn from(s: &str) -> Self {
+        let wide = s.encode_utf16().collect::<alloc::vec::Vec<_>>();
+        Self::from_wide(&wide)
+    }
+}
+
+impl From<&String> for BStr {
+    fn from(s: &String) -> Self {
+        Self::from(s.as_str())
+    }
+}
+
+impl From<String> for BStr {
+    fn from(s: String) -> Self {
+        Self::from(s.as_str())
+    }
+}
+
+impl TryFrom<&BStr> for String {
+    type Error = alloc::string::FromUtf16Error;
+
+    fn try_from(s: &BStr) -> Result<Self, Self::Error> {
+        String::from_utf16(s.as_wide())
+    }
+}
+
+impl TryFrom<BStr> for String {
+    type Error = alloc::string::FromUtf16Error;
+
+    fn try_from(s: BStr) -> Result<Self, Self::Error> {
+        String::try_from(&s)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<&std::ffi::OsStr> for BStr {
+    fn from(s: &std::ffi::OsStr) -> Self {
+        #[cfg(windows)]
+        {
+            use std::os::windows::ffi::OsStrExt;
+            let wide = s.encode_wide().collect::<alloc::vec::Vec<_>>();
+            Self::from_wide(&wide)
+        }
+        #[cfg(not(windows))]
+        {
+            Self::from(&*s.to_string_lossy())
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::ffi::OsString> for BStr {
+    fn from(s: std::ffi::OsString) -> Self {
+        Self::from(s.as_os_str())
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<&BStr> for std::ffi::OsString {
+    fn from(s: &BStr) -> Self {
+        #[cfg(windows)]
+        {
+            use std::os::windows::ffi::OsStringExt;
+            std::ffi::OsString::from_wide(s.as_wide())
+        }
+        #[cfg(not(windows))]
+        {
+            std::ffi::OsString::from(s.to_string_lossy())
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<BStr> for std::ffi::OsString {
+    fn from(s: BStr) -> Self {
+        std::ffi::OsString::from(&s)
+    }
+}
+
+impl<'a> From<&'a str> for Param<'a, BStr> {
+    fn from(s: &'a str) -> Self {
+        Param::Owned(BStr::from(s))
+    }
+}
+
+impl PartialEq for BStr {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_wide() == other.as_wide()
+    }
+}
+
+impl Eq for BStr {}
+
+impl PartialEq<str> for BStr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_wide().iter().copied().eq(other.encode_utf16())
+    }
+}
+
+impl PartialEq<&str> for BStr {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl core::hash::Hash for BStr {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.as_wide().hash(state)
+    }
+}
+
+impl fmt::Display for BStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in core::char::decode_utf16(self.as_wide().iter().copied()) {
+            fmt::Write::write_char(f, c.unwrap_or(core::char::REPLACEMENT_CHARACTER))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string_lossy(), f)
+    }
+}
+
+unsafe impl AbiTransferable for BStr {
+    type Abi = BSTR;
+
+    fn get_abi(&self) -> Self::Abi {
+        self.0
+    }
+
+    fn from_in_param(abi: Self::Abi) -> Self {
+        // The caller keeps ownership of the string
+        let borrowed = core::mem::ManuallyDrop::new(BStr(abi));
+        BStr::clone(&borrowed)
+    }
+}
+
+#[cfg(windows)]
+unsafe fn alloc_string(s: &[u16]) -> BSTR {
+    let len = u32::try_from(s.len()).expect("string is too long for a BSTR");
+    let raw = crate::sys::SysAllocStringLen(s.as_ptr(), len);
+    if raw.is_null() {
+        alloc::alloc::handle_alloc_error(core::alloc::Layout::array::<u16>(s.len() + 1).unwrap());
+    }
+    raw
+}
+
+#[cfg(windows)]
+unsafe fn string_len(raw: BSTR) -> u32 {
+    crate::sys::SysStringLen(raw)
+}
+
+#[cfg(windows)]
+unsafe fn free_string(raw: BSTR) {
+    crate::sys::SysFreeString(raw)
+}
+
+// Outside of Windows, strings are laid out like `SysAllocString` does: a `u32` byte
+// length, followed by the UTF-16 code units and a terminating null. The `BSTR`
+// points at the first code unit.
+
+#[cfg(not(windows))]
+const PREFIX_SIZE: usize = core::mem::size_of::<u32>();
+
+#[cfg(not(windows))]
+fn layout(len: usize) -> core::alloc::Layout {
+    let size = PREFIX_SIZE + (len + 1) * core::mem::size_of::<u16>();
+    core::alloc::Layout::from_size_align(size, core::mem::align_of::<u32>()).unwrap()
+}
+
+#[cfg(not(windows))]
+unsafe fn alloc_string(s: &[u16]) -> BSTR {
+    let byte_len = core::mem::size_of_val(s);
+    let byte_len = u32::try_from(byte_len).expect("string is too long for a BSTR");
+    let layout = layout(s.len());
+    let allocation = alloc::alloc::alloc(layout);
+    if allocation.is_null() {
+        alloc::alloc::handle_alloc_error(layout);
+    }
+    (allocation as *mut u32).write(byte_len);
+    let raw = allocation.add(PREFIX_SIZE) as *mut u16;
+    core::ptr::copy_nonoverlapping(s.as_ptr(), raw, s.len());
+    raw.add(s.len()).write(0);
+    raw
+}
+
+#[cfg(not(windows))]
+unsafe fn string_len(raw: BSTR) -> u32 {
+    let byte_len = (raw as *const u8).sub(PREFIX_SIZE) as *const u32;
+    byte_len.read() / core::mem::size_of::<u16>() as u32
+}
+
+#[cfg(not(windows))]
+unsafe fn free_string(raw: BSTR) {
+    let allocation = (raw as *mut u8).sub(PREFIX_SIZE);
+    alloc::alloc::dealloc(allocation, layout(string_len(raw) as usize));
+}
@@ -0,0 +1,102 @@
+//! Tools for debugging the lifetime of the objects allocated by `com::class!`
+//!
+//! # Leaks
+//!
+//! With the `leak-tracking` feature, every [`ClassAllocation`] registers its
+//! object in a table of the process when it is created, and removes it when the
+//! object is freed. Tests can check that they released all the objects they
+//! created:
+//!
+//! ```rust
+//! # com::interfaces! {
+//! #     #[uuid("EFF8970E-C50F-45E0-9284-291CE5A6F771")]
+//! #     pub unsafe interface IAnimal: com::interfaces::IUnknown {
+//! #         fn Eat(&self) -> com::sys::HRESULT;
+//! #     }
+//! # }
+//! # com::class! {
+//! #     pub class BritishShortHairCat: IAnimal {}
+//! #     impl IAnimal for BritishShortHairCat {
+//! #         fn Eat(&self) -> com::sys::HRESULT { com::sys::NOERROR }
+//! #     }
+//! # }
+//! # #[cfg(feature = "leak-tracking")]
+//! # {
+//! let cat = BritishShortHairCat::allocate();
+//! let animal = cat.query_interface::<IAnimal>().unwrap();
+//! assert_eq!(com::debug::live_objects().len(), 1);
+//!
+//! drop(cat);
+//! drop(animal);
+//! com::debug::assert_no_leaks();
+//! # }
+//! ```
+//!
+//! The creation backtraces of the objects are captured with
+//! [`Backtrace::capture`](std::backtrace::Backtrace::capture), so they are only
+//! recorded when the `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` environment variable
+//! enables them. The table is shared by all threads, so tests checking for leaks
+//! should not run concurrently with other tests allocating objects. This feature
+//! requires Rust 1.65.
+//!
+//! # Reference counts
+//!
+//! With the `refcount-tracing` feature, the `AddRef`, `Release` and
+//! `QueryInterface` calls made through the interfaces of the objects are reported
+//! to the hook set with `set_refcount_hook`. `record_history` sets a hook which
+//! keeps the last calls of every object. They are then included in the panic of a
+//! `Release` call on an object whose count was already zero, and can be read with
+//! `history`:
+//!
+//! ```rust
+//! # com::interfaces! {
+//! #     #[uuid("EFF8970E-C50F-45E0-9284-291CE5A6F771")]
+//! #     pub unsafe interface IAnimal: com::interfaces::IUnknown {
+//! #         fn Eat(&self) -> com::sys::HRESULT;
+//! #     }
+//! # }
+//! # com::class! {
+//! #     pub class BritishShortHairCat: IAnimal {}
+//! #     impl IAnimal for BritishShortHairCat {
+//! #         fn Eat(&self) -> com::sys::HRESULT { com::sys::NOERROR }
+//! #     }
+//! # }
+//! # #[cfg(feature = "refcount-tracing")]
+//! # {
+//! use com::debug::{history, record_history, Operation};
+//!
+//! record_history(16);
+//! let cat = BritishShortHairCat::allocate();
+//! let object = &**cat as *const BritishShortHairCat as usize;
+//! let animal = cat.query_interface::<IAnimal>().unwrap();
+//! drop(animal.clone());
+//!
+//! let operations: Vec<_> = history(object).iter().map(|e| (e.operation, e.count)).collect();
+//! assert_eq!(operations[1..], [(Operation::AddRef, 3), (Operation::Release, 2)]);
+//! # }
+//! ```
+//!
+//! Calls made on the [`ClassAllocation`] itself, such as cloning it, are not
+//! reported.
+//!
+//! [`ClassAllocation`]: crate::production::ClassAllocation
+
+#[cfg(feature = "leak-tracking")]
+mod leaks;
+#[cfg(feature = "refcount-tracing")]
+mod trace;
+
+#[cfg(feature = "leak-tracking")]
+pub use leaks::{assert_no_leaks, live_objects, LiveObject};
+#[cfg(feature = "leak-tracking")]
+pub(crate) use leaks::{track, untrack};
+#[cfg(feature = "refcount-tracing")]
+pub use trace::{
+    clear_history, history, record, record_history, set_refcount_hook, RefCountEvent, RefCountHook,
+};
+#[cfg(feature = "refcount-tracing")]
+pub(crate) use trace::{is_tracing, release_underflowed, trace};
+
+#[doc(inline)]
+#[cfg(feature = "refcount-tracing")]
+pub use crate::refcounting::Operation;
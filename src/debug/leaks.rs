@@ -0,0 +1,97 @@
+//! Tracking of the objects allocated by `com::class!`, with the `leak-tracking`
+//! feature
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// An object allocated by `com::class!` which has not been freed yet
+#[derive(Clone, Debug)]
+pub struct LiveObject {
+    /// The type name of the class
+    pub class: &'static str,
+    /// The address of the object
+    pub address: usize,
+    /// Where the object was allocated
+    pub backtrace: Arc<Backtrace>,
+    /// The order in which objects were allocated
+    sequence: u64,
+}
+
+impl fmt::Display for LiveObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {:#x}", self.class, self.address)?;
+        if self.backtrace.status() == BacktraceStatus::Captured {
+            write!(f, ", allocated at:\n{}", self.backtrace)?;
+        }
+        Ok(())
+    }
+}
+
+struct Table {
+    objects: HashMap<usize, LiveObject>,
+    next: u64,
+}
+
+static TABLE: Mutex<Option<Table>> = Mutex::new(None);
+
+fn table() -> MutexGuard<'static, Option<Table>> {
+    // Objects are still tracked after a panic while the table was locked
+    TABLE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Register an object allocated by `ClassAllocation::new`
+pub(crate) fn track(class: &'static str, address: usize) {
+    let backtrace = Arc::new(Backtrace::capture());
+    let mut table = table();
+    let table = table.get_or_insert_with(|| Table {
+        objects: HashMap::new(),
+        next: 0,
+    });
+    let object = LiveObject {
+        class,
+        address,
+        backtrace,
+        sequence: table.next,
+    };
+    table.next += 1;
+    table.objects.insert(address, object);
+}
+
+/// Remove an object freed by `ClassAllocation::drop_inner`
+pub(crate) fn untrack(address: usize) {
+    // The object is dropped after the table is unlocked, since its fields may
+    // free other objects
+    let object = table()
+        .as_mut()
+        .and_then(|table| table.objects.remove(&address));
+    drop(object);
+}
+
+/// The objects which are currently allocated, in the order they were allocated
+pub fn live_objects() -> Vec<LiveObject> {
+    let mut objects: Vec<_> = table()
+        .as_ref()
+        .map(|table| table.objects.values().cloned().collect())
+        .unwrap_or_default();
+    objects.sort_by_key(|object| object.sequence);
+    objects
+}
+
+/// Panic if any object is still allocated
+///
+/// The message lists the class, address and creation backtrace of every live
+/// object.
+#[track_caller]
+pub fn assert_no_leaks() {
+    let objects = live_objects();
+    if objects.is_empty() {
+        return;
+    }
+    let mut message = format!("{} COM object(s) leaked:", objects.len());
+    for object in &objects {
+        message.push_str("\n  ");
+        message.push_str(&object.to_string());
+    }
+    panic!("{}", message);
+}
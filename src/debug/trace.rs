@@ -0,0 +1,214 @@
+//! Hooks for the `IUnknown` calls of the objects allocated by `com::class!`, with
+//! the `refcount-tracing` feature
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::refcounting::Operation;
+use crate::HResult;
+
+/// A call of `AddRef`, `Release` or `QueryInterface` on an object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RefCountEvent {
+    /// The type name of the class
+    pub class: &'static str,
+    /// The address of the object
+    pub object: usize,
+    /// The index of the interface chain the call was made through
+    ///
+    /// This is `None` for the non-delegating `IUnknown` of aggregatable classes,
+    /// and for `ClassAllocation::query_interface`.
+    pub chain: Option<usize>,
+    /// The method which was called
+    pub operation: Operation,
+    /// The reference count after the call
+    ///
+    /// For the interfaces of aggregated objects, `AddRef` and `Release` return the
+    /// count of the controlling object.
+    pub count: u32,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::AddRef => f.write_str("AddRef"),
+            Operation::Release => f.write_str("Release"),
+            Operation::QueryInterface { iid, hr } => {
+                write!(f, "QueryInterface({:?}) = {}", iid, HResult(*hr))
+            }
+        }
+    }
+}
+
+impl fmt::Display for RefCountEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {:#x}", self.class, self.object)?;
+        if let Some(chain) = self.chain {
+            write!(f, " (chain {})", chain)?;
+        }
+        write!(f, ": {}, count {}", self.operation, self.count)
+    }
+}
+
+/// A function called for every [`RefCountEvent`]
+///
+/// Hooks are called on the thread making the call, and must not call the object.
+pub type RefCountHook = fn(&RefCountEvent);
+
+/// The hook, or 0
+static HOOK: AtomicUsize = AtomicUsize::new(0);
+
+/// Set the function called for every `AddRef`, `Release` and `QueryInterface`
+/// call, or remove it
+pub fn set_refcount_hook(hook: Option<RefCountHook>) {
+    HOOK.store(hook.map_or(0, |hook| hook as usize), Ordering::SeqCst);
+}
+
+/// Whether a hook is set
+#[inline]
+pub(crate) fn is_tracing() -> bool {
+    HOOK.load(Ordering::Relaxed) != 0
+}
+
+/// Report `event` to the hook
+#[inline]
+pub(crate) fn trace(event: RefCountEvent) {
+    let hook = HOOK.load(Ordering::SeqCst);
+    if hook != 0 {
+        // SAFETY: `HOOK` only stores `RefCountHook`s
+        let hook: RefCountHook = unsafe { core::mem::transmute(hook) };
+        hook(&event);
+    }
+}
+
+/// The recorded calls of every object
+#[derive(Default)]
+struct Histories {
+    capacity: usize,
+    /// The number of recorded calls, which orders the objects by their last call
+    calls: u64,
+    /// The number of the last call of every object, and its history
+    objects: HashMap<usize, (u64, VecDeque<RefCountEvent>)>,
+}
+
+/// The histories of the process, which are created on first use and never freed
+static HISTORIES: AtomicPtr<Mutex<Histories>> = AtomicPtr::new(ptr::null_mut());
+
+/// The number of calls kept for every object until [`record_history`] is called
+const DEFAULT_CAPACITY: usize = 32;
+
+/// The number of objects whose history is kept
+///
+/// The history of the object whose last call is the oldest is forgotten to record
+/// the history of a new object.
+const MAX_OBJECTS: usize = 4096;
+
+fn histories() -> MutexGuard<'static, Histories> {
+    let mut histories = HISTORIES.load(Ordering::SeqCst);
+    if histories.is_null() {
+        let new = Box::into_raw(Box::new(Mutex::new(Histories {
+            capacity: DEFAULT_CAPACITY,
+            ..Histories::default()
+        })));
+        histories = match HISTORIES.compare_exchange(
+            ptr::null_mut(),
+            new,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        ) {
+            Ok(_) => new,
+            Err(current) => {
+                drop(unsafe { Box::from_raw(new) });
+                current
+            }
+        };
+    }
+    // The histories are still recorded after a panic while they were locked
+    unsafe { &*histories }
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Keep the last `capacity` calls of every object, by setting [`record`] as the
+/// hook
+///
+/// The history of an object is forgotten when `Release` returns 0. Objects
+/// freed by dropping their last `ClassAllocation` keep their history, which is
+/// followed by the history of the next object allocated at the same address. The
+/// histories of at most 4096 objects are kept, forgetting the objects called least
+/// recently.
+pub fn record_history(capacity: usize) {
+    histories().capacity = capacity;
+    set_refcount_hook(Some(record));
+}
+
+/// Add `event` to the history of its object
+///
+/// This is the hook set by [`record_history`], which other hooks can call as well.
+pub fn record(event: &RefCountEvent) {
+    let mut histories = histories();
+    let capacity = histories.capacity;
+    if capacity == 0 {
+        return;
+    }
+    if event.operation == Operation::Release && event.count == 0 {
+        histories.objects.remove(&event.object);
+        return;
+    }
+    if histories.objects.len() == MAX_OBJECTS && !histories.objects.contains_key(&event.object) {
+        let oldest = histories
+            .objects
+            .iter()
+            .min_by_key(|(_, (last, _))| *last)
+            .map(|(object, _)| *object);
+        if let Some(oldest) = oldest {
+            histories.objects.remove(&oldest);
+        }
+    }
+    histories.calls += 1;
+    let call = histories.calls;
+    let (last, history) = histories.objects.entry(event.object).or_default();
+    *last = call;
+    if history.len() == capacity {
+        history.pop_front();
+    }
+    history.push_back(*event);
+}
+
+/// The recorded calls of the object at the address `object`, oldest first
+pub fn history(object: usize) -> Vec<RefCountEvent> {
+    histories()
+        .objects
+        .get(&object)
+        .map(|(_, history)| history.iter().copied().collect())
+        .unwrap_or_default()
+}
+
+/// Forget the recorded calls of all objects
+pub fn clear_history() {
+    histories().objects.clear();
+}
+
+/// Panics, because `Release` was called on `object` when its count was zero
+///
+/// The message includes the recorded history of the object.
+#[inline(never)]
+#[cold]
+pub(crate) fn release_underflowed(class: &str, object: usize) -> ! {
+    let mut message = format!(
+        "IUnknown::Release called, but refcount was zero\n{} at {:#x}",
+        class, object
+    );
+    let history = history(object);
+    if !history.is_empty() {
+        message.push_str(", last calls:");
+        for event in history {
+            message.push_str("\n  ");
+            message.push_str(&event.to_string());
+        }
+    }
+    panic!("{}", message);
+}
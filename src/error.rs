@@ -0,0 +1,309 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+
+use crate::sys;
+use crate::Interface;
+
+/// A strongly typed Windows result code
+///
+/// This is ABI-identical to [`HRESULT`](crate::sys::HRESULT) and can therefore be used
+/// directly in the signatures of methods declared with [`interfaces!`](crate::interfaces!).
+#[repr(transparent)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct HResult(pub sys::HRESULT);
+
+/// The severity of an [`HResult`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    /// The operation succeeded
+    Success,
+    /// The operation failed
+    Failure,
+}
+
+impl HResult {
+    /// No error
+    pub const S_OK: HResult = HResult(sys::S_OK);
+    /// False
+    pub const S_FALSE: HResult = HResult(sys::S_FALSE);
+
+    /// Whether the result code indicates success
+    pub const fn is_ok(self) -> bool {
+        self.0 >= 0
+    }
+
+    /// Whether the result code indicates failure
+    pub const fn is_err(self) -> bool {
+        self.0 < 0
+    }
+
+    /// The severity bit of the result code
+    pub const fn severity(self) -> Severity {
+        if self.is_err() {
+            Severity::Failure
+        } else {
+            Severity::Success
+        }
+    }
+
+    /// The facility responsible for the result code
+    pub const fn facility(self) -> u16 {
+        ((self.0 >> 16) & 0x1FFF) as u16
+    }
+
+    /// The facility specific part of the result code
+    pub const fn code(self) -> u16 {
+        (self.0 & 0xFFFF) as u16
+    }
+
+    /// Convert into a `Result`, mapping all failure codes to an [`Error`]
+    pub fn ok(self) -> Result<(), Error> {
+        self.into()
+    }
+
+    /// The symbolic name of the result code, if it is one of the constants in [`crate::sys`]
+    pub fn name(self) -> Option<&'static str> {
+        let name = match self.0 {
+            sys::S_OK => "S_OK",
+            sys::S_FALSE => "S_FALSE",
+            sys::E_INVALIDARG => "E_INVALIDARG",
+            sys::E_NOINTERFACE => "E_NOINTERFACE",
+            sys::E_POINTER => "E_POINTER",
+            sys::E_NOTIMPL => "E_NOTIMPL",
+            sys::E_FAIL => "E_FAIL",
+            sys::E_UNEXPECTED => "E_UNEXPECTED",
+            sys::E_OUTOFMEMORY => "E_OUTOFMEMORY",
+            sys::CLASS_E_NOAGGREGATION => "CLASS_E_NOAGGREGATION",
+            sys::CLASS_E_CLASSNOTAVAILABLE => "CLASS_E_CLASSNOTAVAILABLE",
+            sys::SELFREG_E_CLASS => "SELFREG_E_CLASS",
+            sys::DISP_E_TYPEMISMATCH => "DISP_E_TYPEMISMATCH",
+            sys::DISP_E_BADVARTYPE => "DISP_E_BADVARTYPE",
+            sys::DISP_E_OVERFLOW => "DISP_E_OVERFLOW",
+            sys::DISP_E_BADINDEX => "DISP_E_BADINDEX",
+            sys::DISP_E_ARRAYISLOCKED => "DISP_E_ARRAYISLOCKED",
+            sys::DISP_E_MEMBERNOTFOUND => "DISP_E_MEMBERNOTFOUND",
+            sys::DISP_E_UNKNOWNNAME => "DISP_E_UNKNOWNNAME",
+            sys::DISP_E_NONAMEDARGS => "DISP_E_NONAMEDARGS",
+            sys::DISP_E_BADPARAMCOUNT => "DISP_E_BADPARAMCOUNT",
+            sys::RPC_E_DISCONNECTED => "RPC_E_DISCONNECTED",
+            sys::RPC_E_INVALIDMETHOD => "RPC_E_INVALIDMETHOD",
+            sys::RPC_E_INVALID_DATA => "RPC_E_INVALID_DATA",
+            sys::RPC_E_VERSION_MISMATCH => "RPC_E_VERSION_MISMATCH",
+            sys::RPC_E_CHANGED_MODE => "RPC_E_CHANGED_MODE",
+            sys::CO_E_NOTINITIALIZED => "CO_E_NOTINITIALIZED",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+impl From<sys::HRESULT> for HResult {
+    fn from(hr: sys::HRESULT) -> Self {
+        HResult(hr)
+    }
+}
+
+impl From<HResult> for sys::HRESULT {
+    fn from(hr: HResult) -> Self {
+        hr.0
+    }
+}
+
+impl From<HResult> for Result<(), Error> {
+    fn from(hr: HResult) -> Self {
+        if hr.is_ok() {
+            Ok(())
+        } else {
+            Err(Error::new(hr))
+        }
+    }
+}
+
+impl From<Result<(), Error>> for HResult {
+    fn from(result: Result<(), Error>) -> Self {
+        match result {
+            Ok(()) => HResult::S_OK,
+            Err(e) => e.code(),
+        }
+    }
+}
+
+impl core::fmt::Debug for HResult {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "HResult({})", self)
+    }
+}
+
+impl core::fmt::Display for HResult {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{} (0x{:08X})", name, self.0),
+            None => write!(f, "0x{:08X}", self.0),
+        }
+    }
+}
+
+/// An error returned from a failing COM call
+///
+/// Besides its result code, an error may carry the [`ErrorInfo`] the object set for
+/// the thread with `SetErrorInfo` when the call failed. The methods of `class!`
+/// servers which return a `Result` set it from their `Error`, and the wrappers of
+/// `#[retval]` methods generated by `interfaces!` read it back if the object
+/// supports error information for the interface.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Error {
+    code: HResult,
+    info: Option<Box<ErrorInfo>>,
+}
+
+impl Error {
+    /// Create an error from a result code
+    ///
+    /// This is meant for failure codes. Success codes such as `S_FALSE` are kept as
+    /// they are, for the methods which report them as errors; use [`HResult::ok`] to
+    /// only get an error for failure codes.
+    pub fn new(code: HResult) -> Self {
+        Self { code, info: None }
+    }
+
+    /// Create an error with a description
+    pub fn with_description(code: HResult, description: impl Into<String>) -> Self {
+        Self::with_info(code, ErrorInfo::new(description))
+    }
+
+    /// Create an error with error information
+    pub fn with_info(code: HResult, info: ErrorInfo) -> Self {
+        Self {
+            info: Some(Box::new(info)),
+            ..Self::new(code)
+        }
+    }
+
+    /// Create an error for a failed call, with the error information of the
+    /// current thread if the callee set it
+    ///
+    /// This takes the error information of the thread like `GetErrorInfo`, so it
+    /// can only be read once.
+    pub fn from_error_info(code: HResult) -> Self {
+        match crate::error_info::take() {
+            Some(info) => Self::with_info(code, crate::error_info::read(&info)),
+            None => Self::new(code),
+        }
+    }
+
+    /// Create an error for a failed call of a method of `interface`
+    ///
+    /// The error information of the current thread is only taken if the object
+    /// reports with `ISupportErrorInfo` that the methods of `I` set it. Otherwise, it
+    /// may have been left by an unrelated call, and it is cleared. The wrappers of
+    /// `#[retval]` methods generated by `interfaces!` use this.
+    pub fn from_failed_call<I: Interface>(interface: &I, code: HResult) -> Self {
+        if crate::error_info::supports(interface.as_iunknown(), &I::IID) {
+            Self::from_error_info(code)
+        } else {
+            drop(crate::error_info::take());
+            Self::new(code)
+        }
+    }
+
+    /// The result code of the error
+    pub fn code(&self) -> HResult {
+        self.code
+    }
+
+    /// The error information, if the error has any
+    pub fn info(&self) -> Option<&ErrorInfo> {
+        self.info.as_deref()
+    }
+
+    /// The description of the error, if it has one
+    pub fn description(&self) -> Option<&str> {
+        self.info
+            .as_ref()
+            .map(|info| info.description.as_str())
+            .filter(|description| !description.is_empty())
+    }
+
+    /// Set the error information of the current thread to that of this error, and
+    /// return its result code
+    ///
+    /// Servers call this before returning the result code of a failed call, so that
+    /// the caller can get the error information with `GetErrorInfo` or
+    /// [`Error::from_error_info`]. The error information of the thread is cleared if
+    /// the error has none. This is done by `class!` for methods returning a
+    /// `Result<T, com::Error>`.
+    pub fn set_error_info(&self) -> HResult {
+        crate::error_info::set(self.info());
+        self.code
+    }
+}
+
+/// The error information of an [`Error`], as provided by `IErrorInfo`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorInfo {
+    /// The description of the error
+    pub description: String,
+    /// The ProgID of the class or application which raised the error
+    pub source: String,
+    /// The path of the help file describing the error
+    pub help_file: String,
+    /// The help context ID of the error in the help file
+    pub help_context: u32,
+    /// The IID of the interface defining the error, or zero if it is not defined
+    /// by an interface
+    pub guid: sys::GUID,
+}
+
+impl ErrorInfo {
+    /// Create error information with a description
+    pub fn new(description: impl Into<String>) -> Self {
+        Self {
+            description: description.into(),
+            source: String::new(),
+            help_file: String::new(),
+            help_context: 0,
+            guid: sys::GUID {
+                data1: 0,
+                data2: 0,
+                data3: 0,
+                data4: [0; 8],
+            },
+        }
+    }
+}
+
+impl From<HResult> for Error {
+    fn from(code: HResult) -> Self {
+        Self::new(code)
+    }
+}
+
+impl From<Error> for HResult {
+    fn from(error: Error) -> Self {
+        error.code
+    }
+}
+
+impl core::fmt::Debug for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut debug = f.debug_struct("Error");
+        debug.field("code", &self.code);
+        if let Some(info) = &self.info {
+            debug.field("info", info);
+        }
+        debug.finish()
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "COM call failed with {}", self.code)?;
+        if let Some(description) = self.description() {
+            write!(f, ": {}", description)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
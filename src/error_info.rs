@@ -0,0 +1,261 @@
+//! Runtime support for the error information of threads.
+//!
+//! This is part of the implementation of `com-rs`, and should not be used
+//! directly by application code. Use [`Error::from_failed_call`],
+//! [`Error::from_error_info`] and [`Error::set_error_info`] instead.
+//!
+//! On Windows, the error information is the one of the COM runtime, set with
+//! `SetErrorInfo` and taken with `GetErrorInfo`. Elsewhere, it is kept in a thread
+//! local slot with the `std` feature, and not kept at all without it.
+
+use alloc::boxed::Box;
+use core::ffi::c_void;
+use core::ptr::NonNull;
+use core::sync::atomic::AtomicU32;
+
+use crate::interfaces::iunknown::IID_IUNKNOWN;
+use crate::interfaces::{IErrorInfo, ISupportErrorInfo, IUnknown};
+use crate::refcounting;
+use crate::sys::{E_NOINTERFACE, E_POINTER, GUID, HRESULT, IID, NOERROR, S_OK};
+use crate::{AbiTransferable, BStr, Error, ErrorInfo, HResult, Interface};
+
+// See https://github.com/rust-lang/rust/issues/86935
+type IUnknownVTable = <IUnknown as Interface>::VTable;
+type IErrorInfoVTable = <IErrorInfo as Interface>::VTable;
+
+/// A result of a method which may carry error information
+pub trait Report {
+    /// Set the error information of the thread if the result carries any, and
+    /// return the result code
+    fn report(self) -> HRESULT;
+}
+
+impl Report for HRESULT {
+    fn report(self) -> HRESULT {
+        self
+    }
+}
+
+impl Report for HResult {
+    fn report(self) -> HRESULT {
+        self.0
+    }
+}
+
+impl Report for Error {
+    fn report(self) -> HRESULT {
+        self.set_error_info().0
+    }
+}
+
+/// Reports the error of a failed method implemented by `class!`
+#[doc(hidden)]
+#[inline]
+pub fn report<E: Report>(error: E) -> HRESULT {
+    error.report()
+}
+
+/// Set the error information of the current thread, or clear it
+pub(crate) fn set(info: Option<&ErrorInfo>) {
+    let info = info.map(create);
+    // `SetErrorInfo` adds its own reference to the object
+    #[cfg(windows)]
+    unsafe {
+        let info = info
+            .as_ref()
+            .map_or(core::ptr::null_mut(), |info| info.get_abi().as_ptr());
+        crate::sys::SetErrorInfo(0, info as *mut c_void);
+    }
+    #[cfg(all(not(windows), feature = "std"))]
+    slot::SLOT.with(|slot| *slot.borrow_mut() = info);
+    #[cfg(all(not(windows), not(feature = "std")))]
+    drop(info);
+}
+
+/// Take the error information of the current thread, leaving it cleared
+pub(crate) fn take() -> Option<IErrorInfo> {
+    #[cfg(windows)]
+    {
+        let mut info = None::<IErrorInfo>;
+        let hr = unsafe {
+            crate::sys::GetErrorInfo(0, &mut info as *mut Option<IErrorInfo> as *mut *mut c_void)
+        };
+        if hr == S_OK {
+            info
+        } else {
+            None
+        }
+    }
+    #[cfg(all(not(windows), feature = "std"))]
+    {
+        slot::SLOT.with(|slot| slot.borrow_mut().take())
+    }
+    #[cfg(all(not(windows), not(feature = "std")))]
+    {
+        None
+    }
+}
+
+/// Whether `object` reports with `ISupportErrorInfo` that the methods of its
+/// interface `iid` set the error information of the thread
+pub(crate) fn supports(object: &IUnknown, iid: &IID) -> bool {
+    match object.query_interface::<ISupportErrorInfo>() {
+        Some(support) => unsafe { support.InterfaceSupportsErrorInfo(iid) == S_OK },
+        None => false,
+    }
+}
+
+/// Take the error information of the current thread, to pass it to the caller of a
+/// method of the interface `iid` of `object` called from another apartment or
+/// process
+///
+/// The information is cleared and not passed if the object does not support it.
+#[cfg(feature = "remote")]
+pub(crate) fn take_info(object: &IUnknown, iid: &IID) -> Option<ErrorInfo> {
+    let supported = supports(object, iid);
+    let info = take()?;
+    if supported {
+        Some(read(&info))
+    } else {
+        None
+    }
+}
+
+/// Read the error information of an `IErrorInfo`
+///
+/// Fields which cannot be read are left empty.
+pub(crate) fn read(info: &IErrorInfo) -> ErrorInfo {
+    let string = |s: Result<BStr, Error>| s.map(|s| s.to_string_lossy()).unwrap_or_default();
+    unsafe {
+        let mut result = ErrorInfo::new(string(info.GetDescription()));
+        result.source = string(info.GetSource());
+        result.help_file = string(info.GetHelpFile());
+        result.help_context = info.GetHelpContext().unwrap_or(0);
+        if let Ok(guid) = info.GetGUID() {
+            result.guid = guid;
+        }
+        result
+    }
+}
+
+#[cfg(all(not(windows), feature = "std"))]
+#[allow(clippy::missing_const_for_thread_local)]
+mod slot {
+    use crate::interfaces::IErrorInfo;
+    use std::cell::RefCell;
+
+    std::thread_local! {
+        // `const` thread local initializers require Rust 1.59
+        pub static SLOT: RefCell<Option<IErrorInfo>> = RefCell::new(None);
+    }
+}
+
+/// An `IErrorInfo` object holding an [`ErrorInfo`]
+#[repr(C)]
+struct ErrorInfoObject {
+    vtable: &'static IErrorInfoVTable,
+    refs: AtomicU32,
+    info: ErrorInfo,
+}
+
+static ERROR_INFO_VTABLE: IErrorInfoVTable = IErrorInfoVTable {
+    parent: IUnknownVTable {
+        QueryInterface: query_interface,
+        AddRef: add_ref,
+        Release: release,
+    },
+    GetGUID: get_guid,
+    GetSource: get_source,
+    GetDescription: get_description,
+    GetHelpFile: get_help_file,
+    GetHelpContext: get_help_context,
+};
+
+/// Create an `IErrorInfo` object for `info`
+fn create(info: &ErrorInfo) -> IErrorInfo {
+    let object = Box::new(ErrorInfoObject {
+        vtable: &ERROR_INFO_VTABLE,
+        refs: AtomicU32::new(1),
+        info: info.clone(),
+    });
+    unsafe { IErrorInfo::from_abi(NonNull::new_unchecked(Box::into_raw(object)).cast()) }
+}
+
+unsafe fn object<'a, T>(this: NonNull<NonNull<T>>) -> &'a ErrorInfoObject {
+    &*(this.as_ptr() as *const ErrorInfoObject)
+}
+
+unsafe extern "system" fn query_interface(
+    this: NonNull<NonNull<IUnknownVTable>>,
+    riid: *const IID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    if riid.is_null() || ppv.is_null() {
+        return E_POINTER;
+    }
+    let riid = &*riid;
+    if riid == &IID_IUNKNOWN || riid == &IErrorInfo::IID {
+        *ppv = this.as_ptr() as *mut c_void;
+        add_ref(this);
+        NOERROR
+    } else {
+        *ppv = core::ptr::null_mut();
+        E_NOINTERFACE
+    }
+}
+
+unsafe extern "system" fn add_ref(this: NonNull<NonNull<IUnknownVTable>>) -> u32 {
+    refcounting::addref(&object(this).refs)
+}
+
+unsafe extern "system" fn release(this: NonNull<NonNull<IUnknownVTable>>) -> u32 {
+    let new_ref_count = refcounting::release(&object(this).refs);
+    if new_ref_count == 0 {
+        drop(Box::from_raw(this.as_ptr() as *mut ErrorInfoObject));
+    }
+    new_ref_count
+}
+
+/// Write an `[out]` parameter of the object
+unsafe fn write<T>(out: *mut T, value: T) -> HRESULT {
+    if out.is_null() {
+        return E_POINTER;
+    }
+    out.write(value);
+    S_OK
+}
+
+unsafe extern "system" fn get_guid(
+    this: NonNull<NonNull<IErrorInfoVTable>>,
+    guid: *mut GUID,
+) -> HRESULT {
+    write(guid, object(this).info.guid)
+}
+
+unsafe extern "system" fn get_source(
+    this: NonNull<NonNull<IErrorInfoVTable>>,
+    source: *mut BStr,
+) -> HRESULT {
+    write(source, BStr::from(&object(this).info.source))
+}
+
+unsafe extern "system" fn get_description(
+    this: NonNull<NonNull<IErrorInfoVTable>>,
+    description: *mut BStr,
+) -> HRESULT {
+    write(description, BStr::from(&object(this).info.description))
+}
+
+unsafe extern "system" fn get_help_file(
+    this: NonNull<NonNull<IErrorInfoVTable>>,
+    help_file: *mut BStr,
+) -> HRESULT {
+    write(help_file, BStr::from(&object(this).info.help_file))
+}
+
+unsafe extern "system" fn get_help_context(
+    this: NonNull<NonNull<IErrorInfoVTable>>,
+    help_context: *mut u32,
+) -> HRESULT {
+    write(help_context, object(this).info.help_context)
+}
@@ -0,0 +1,102 @@
+//! Everything related to the [IDispatch](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-idispatch) COM interface
+use crate::interfaces;
+use crate::interfaces::iunknown::IUnknown;
+use crate::sys::{DISPID, DISPPARAMS, EXCEPINFO, GUID, HRESULT, VARIANT};
+use crate::{Error, HResult, Variant};
+use alloc::vec::Vec;
+use core::ffi::c_void;
+
+interfaces! {
+    /// [IDispatch](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-idispatch) COM interface
+    #[uuid("00020400-0000-0000-C000-000000000046")]
+    pub unsafe interface IDispatch: IUnknown {
+        /// the [GetTypeInfoCount](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-idispatch-gettypeinfocount) COM method
+        pub unsafe fn GetTypeInfoCount(&self, count: *mut u32) -> HRESULT;
+        /// the [GetTypeInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-idispatch-gettypeinfo) COM method
+        pub unsafe fn GetTypeInfo(&self, index: u32, lcid: u32, type_info: *mut *mut c_void) -> HRESULT;
+        /// the [GetIDsOfNames](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-idispatch-getidsofnames) COM method
+        pub unsafe fn GetIDsOfNames(
+            &self,
+            riid: *const GUID,
+            names: *const *const u16,
+            count: u32,
+            lcid: u32,
+            dispids: *mut DISPID,
+        ) -> HRESULT;
+        /// the [Invoke](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-idispatch-invoke) COM method
+        pub unsafe fn Invoke(
+            &self,
+            dispid: DISPID,
+            riid: *const GUID,
+            lcid: u32,
+            flags: u16,
+            params: *mut DISPPARAMS,
+            result: *mut VARIANT,
+            excep_info: *mut EXCEPINFO,
+            arg_err: *mut u32,
+        ) -> HRESULT;
+    }
+}
+
+/// `IID_NULL`, which must be passed as the `riid` of `GetIDsOfNames` and `Invoke`
+const IID_NULL: GUID = GUID {
+    data1: 0,
+    data2: 0,
+    data3: 0,
+    data4: [0; 8],
+};
+
+/// `LOCALE_USER_DEFAULT`
+const LOCALE_USER_DEFAULT: u32 = 0x400;
+
+impl IDispatch {
+    /// Look up the `DISPID` of a member by name
+    ///
+    /// This is a safe wrapper around `GetIDsOfNames`
+    pub fn get_id_of_name(&self, name: &str) -> Result<DISPID, Error> {
+        let name = name.encode_utf16().chain(Some(0)).collect::<Vec<_>>();
+        let names = [name.as_ptr()];
+        let mut dispid = 0;
+        let hr = unsafe {
+            self.GetIDsOfNames(
+                &IID_NULL,
+                names.as_ptr(),
+                1,
+                LOCALE_USER_DEFAULT,
+                &mut dispid,
+            )
+        };
+        HResult(hr).ok()?;
+        Ok(dispid)
+    }
+
+    /// Invoke a member with positional arguments
+    ///
+    /// `flags` is a combination of `DISPATCH_METHOD` and `DISPATCH_PROPERTYGET`. This
+    /// is a safe wrapper around `Invoke`.
+    pub fn invoke(&self, dispid: DISPID, flags: u16, args: &[Variant]) -> Result<Variant, Error> {
+        // The arguments are passed in reverse order
+        let mut args = args.iter().rev().cloned().collect::<Vec<_>>();
+        let mut params = DISPPARAMS {
+            rgvarg: args.as_mut_ptr() as *mut VARIANT,
+            rgdispidNamedArgs: core::ptr::null_mut(),
+            cArgs: args.len() as u32,
+            cNamedArgs: 0,
+        };
+        let mut result = Variant::new();
+        let hr = unsafe {
+            self.Invoke(
+                dispid,
+                &IID_NULL,
+                LOCALE_USER_DEFAULT,
+                flags,
+                &mut params,
+                &mut result as *mut Variant as *mut VARIANT,
+                core::ptr::null_mut(),
+                core::ptr::null_mut(),
+            )
+        };
+        HResult(hr).ok()?;
+        Ok(result)
+    }
+}
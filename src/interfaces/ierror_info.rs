@@ -0,0 +1,33 @@
+//! Everything related to the [IErrorInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-ierrorinfo)
+//! and [ISupportErrorInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-isupporterrorinfo) COM interfaces
+use crate::interfaces;
+use crate::interfaces::iunknown::IUnknown;
+use crate::sys::{GUID, HRESULT, IID};
+use crate::BStr;
+
+interfaces! {
+    /// [IErrorInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-ierrorinfo) COM interface
+    #[uuid("1CF2B120-547D-101B-8E65-08002B2BA117")]
+    pub unsafe interface IErrorInfo: IUnknown {
+        /// the [GetGUID](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-ierrorinfo-getguid) COM method
+        pub unsafe fn GetGUID(&self, #[retval] guid: *mut GUID) -> HRESULT;
+        /// the [GetSource](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-ierrorinfo-getsource) COM method
+        pub unsafe fn GetSource(&self, #[retval] source: *mut BStr) -> HRESULT;
+        /// the [GetDescription](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-ierrorinfo-getdescription) COM method
+        pub unsafe fn GetDescription(&self, #[retval] description: *mut BStr) -> HRESULT;
+        /// the [GetHelpFile](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-ierrorinfo-gethelpfile) COM method
+        pub unsafe fn GetHelpFile(&self, #[retval] help_file: *mut BStr) -> HRESULT;
+        /// the [GetHelpContext](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-ierrorinfo-gethelpcontext) COM method
+        pub unsafe fn GetHelpContext(&self, #[retval] help_context: *mut u32) -> HRESULT;
+    }
+
+    /// [ISupportErrorInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-isupporterrorinfo) COM interface
+    #[uuid("DF0B3D60-548F-101B-8E65-08002B2BA117")]
+    pub unsafe interface ISupportErrorInfo: IUnknown {
+        /// the [InterfaceSupportsErrorInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-isupporterrorinfo-interfacesupportserrorinfo) COM method
+        ///
+        /// Returns `S_OK` if the methods of the interface `riid` set the error
+        /// information of the thread when they fail, and `S_FALSE` otherwise.
+        pub unsafe fn InterfaceSupportsErrorInfo(&self, riid: *const IID) -> HRESULT;
+    }
+}
@@ -0,0 +1,22 @@
+//! Everything related to the [IGlobalInterfaceTable](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-iglobalinterfacetable) COM interface
+use crate::interfaces;
+use crate::interfaces::iunknown::IUnknown;
+use crate::sys::{CLSID, GUID, HRESULT};
+use core::ffi::c_void;
+
+interfaces! {
+    /// [IGlobalInterfaceTable](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-iglobalinterfacetable) COM interface
+    #[uuid("00000146-0000-0000-C000-000000000046")]
+    pub unsafe interface IGlobalInterfaceTable: IUnknown {
+        /// the [RegisterInterfaceInGlobal](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-iglobalinterfacetable-registerinterfaceinglobal) COM method
+        pub unsafe fn RegisterInterfaceInGlobal(&self, unknown: *mut c_void, riid: *const GUID, #[retval] cookie: *mut u32) -> HRESULT;
+        /// the [RevokeInterfaceFromGlobal](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-iglobalinterfacetable-revokeinterfacefromglobal) COM method
+        pub unsafe fn RevokeInterfaceFromGlobal(&self, cookie: u32) -> HRESULT;
+        /// the [GetInterfaceFromGlobal](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-iglobalinterfacetable-getinterfacefromglobal) COM method
+        pub unsafe fn GetInterfaceFromGlobal(&self, cookie: u32, riid: *const GUID, object: *mut *mut c_void) -> HRESULT;
+    }
+}
+
+/// The class id of the global interface table of the process
+pub const CLSID_STD_GLOBAL_INTERFACE_TABLE: CLSID =
+    crate::guid!("00000323-0000-0000-C000-000000000046");
@@ -0,0 +1,24 @@
+//! Everything related to the [IWeakReference](https://docs.microsoft.com/en-us/windows/win32/api/weakreference/nn-weakreference-iweakreference)
+//! and [IWeakReferenceSource](https://docs.microsoft.com/en-us/windows/win32/api/weakreference/nn-weakreference-iweakreferencesource) COM interfaces
+use crate::interfaces;
+use crate::interfaces::iunknown::IUnknown;
+use crate::sys::{GUID, HRESULT};
+use core::ffi::c_void;
+
+interfaces! {
+    /// [IWeakReference](https://docs.microsoft.com/en-us/windows/win32/api/weakreference/nn-weakreference-iweakreference) COM interface
+    #[uuid("00000037-0000-0000-C000-000000000046")]
+    pub unsafe interface IWeakReference: IUnknown {
+        /// the [Resolve](https://docs.microsoft.com/en-us/windows/win32/api/weakreference/nf-weakreference-iweakreference-resolve(refiid_iinspectable)) COM method
+        ///
+        /// Succeeds with a null `object_reference` if the object has already been destroyed.
+        pub unsafe fn Resolve(&self, riid: *const GUID, object_reference: *mut *mut c_void) -> HRESULT;
+    }
+
+    /// [IWeakReferenceSource](https://docs.microsoft.com/en-us/windows/win32/api/weakreference/nn-weakreference-iweakreferencesource) COM interface
+    #[uuid("00000038-0000-0000-C000-000000000046")]
+    pub unsafe interface IWeakReferenceSource: IUnknown {
+        /// the [GetWeakReference](https://docs.microsoft.com/en-us/windows/win32/api/weakreference/nf-weakreference-iweakreferencesource-getweakreference) COM method
+        pub unsafe fn GetWeakReference(&self, #[retval] weak_reference: *mut IWeakReference) -> HRESULT;
+    }
+}
@@ -1,9 +1,22 @@
-//! Common COM interfaces including IUknown and IClassFactory
+//! Common COM interfaces including IUknown, IClassFactory, IDispatch, IErrorInfo,
+//! IWeakReference and IGlobalInterfaceTable
 
 pub mod iclass_factory;
+pub mod idispatch;
+pub mod ierror_info;
+pub mod iglobal_interface_table;
 pub mod iunknown;
+pub mod iweak_reference;
 
 #[doc(inline)]
 pub use iclass_factory::IClassFactory;
 #[doc(inline)]
+pub use idispatch::IDispatch;
+#[doc(inline)]
+pub use ierror_info::{IErrorInfo, ISupportErrorInfo};
+#[doc(inline)]
+pub use iglobal_interface_table::IGlobalInterfaceTable;
+#[doc(inline)]
 pub use iunknown::IUnknown;
+#[doc(inline)]
+pub use iweak_reference::{IWeakReference, IWeakReferenceSource};
@@ -47,27 +47,59 @@
 #![deny(missing_docs)]
 
 mod abi_transferable;
+#[cfg(feature = "remote")]
+pub mod agile;
+mod bstr;
+#[cfg(any(feature = "leak-tracking", feature = "refcount-tracing"))]
+pub mod debug;
+mod error;
+#[doc(hidden)]
+pub mod error_info;
 mod interface;
 pub mod interfaces;
+pub mod marshal;
+#[cfg(feature = "production")]
+pub mod mock;
 mod param;
 #[doc(hidden)]
 pub mod refcounting;
-#[cfg(windows)]
+#[cfg(any(windows, feature = "std"))]
 pub mod runtime;
+mod safe_array;
 pub mod sys;
+#[doc(hidden)]
+pub mod unwind;
+mod variant;
+mod weak_ref;
 
 #[cfg(feature = "production")]
 /// Functionality for producing COM classes
 pub mod production;
 
+#[cfg(feature = "remote")]
+pub mod remote;
+
 #[doc(inline)]
 pub use abi_transferable::AbiTransferable;
+#[cfg(feature = "remote")]
+#[doc(inline)]
+pub use agile::Agile;
+#[doc(inline)]
+pub use bstr::BStr;
+#[doc(inline)]
+pub use error::{Error, ErrorInfo, HResult, Severity};
 #[doc(inline)]
 pub use interface::Interface;
 #[doc(inline)]
 pub use param::Param;
 #[doc(inline)]
+pub use safe_array::{SafeArray, SafeArrayElement, SafeArrayGuard, SafeArrayGuardMut};
+#[doc(inline)]
 pub use sys::{CLSID, IID};
+#[doc(inline)]
+pub use variant::{PropVariant, PropVariantValue, Variant, VariantValue};
+#[doc(inline)]
+pub use weak_ref::WeakRef;
 
 /// Declare COM interfaces
 ///
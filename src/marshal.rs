@@ -0,0 +1,572 @@
+//! Serialization of method parameters for calls to objects in other processes
+//!
+//! Values are written by [`Marshal::marshal`] to an [`Encoder`] and read back by
+//! [`Marshal::unmarshal`] from a [`Decoder`]. `#[repr(C)]` structs implement
+//! [`Marshal`] with `#[derive(Marshal)]`, which writes their fields in order:
+//!
+//! ```rust
+//! use com::marshal::{Decoder, Encoder, Marshal};
+//!
+//! #[repr(C)]
+//! #[derive(Marshal, Debug, PartialEq)]
+//! struct Point {
+//!     x: i32,
+//!     y: i32,
+//! }
+//!
+//! let mut encoder = Encoder::new();
+//! Point { x: 1, y: -1 }.marshal(&mut encoder).unwrap();
+//! assert_eq!(encoder.as_bytes(), [1, 0, 0, 0, 255, 255, 255, 255]);
+//!
+//! let mut decoder = Decoder::new(encoder.as_bytes());
+//! assert_eq!(Point::unmarshal(&mut decoder).unwrap(), Point { x: 1, y: -1 });
+//! ```
+//!
+//! # Format
+//!
+//! The format is identified by [`VERSION`], which connections exchange when they are
+//! established. Values are written without any padding or alignment, and numbers in
+//! little-endian byte order:
+//!
+//! | Type | Representation |
+//! |------|----------------|
+//! | `i8` to `u64`, `f32`, `f64` | The bytes of the number |
+//! | `usize`, `isize` | A `u64` or an `i64`, so that they can be passed between 32-bit and 64-bit processes |
+//! | `bool` | A single byte which is either `0` or `1` |
+//! | [`GUID`] | `data1`, `data2`, `data3` and the 8 bytes of `data4` |
+//! | [`HResult`] | An `i32` |
+//! | [`BStr`] | The number of UTF-16 code units as a `u32`, followed by the code units |
+//! | [`ErrorInfo`] | The description, source and help file like [`BStr`]s, followed by the help context and the GUID |
+//! | `Option<T>` | A `bool` which is `true` for `Some`, followed by the value if there is one |
+//! | `[T; N]` | The `N` elements |
+//! | `Vec<T>` | The number of elements as a `u32`, followed by the elements |
+//! | [`SafeArray<T>`](SafeArray) | The number of dimensions as a `u16`, the `cElements` (`u32`) and `lLbound` (`i32`) of each dimension in the order of [`SafeArray::bounds`], and the elements in the order of the locked slice |
+//! | [`Variant`] | The `VARTYPE` as a `u16`, followed by the value |
+//! | [`PropVariant`] | The `VARTYPE` as a `u16`, followed by the value |
+//! | Structs | The fields in order |
+//! | Interface pointers | A reference to the object |
+//!
+//! The values of variants are written as the type they hold, with `VT_BOOL` as a
+//! `bool`, `VT_CY` as an `i64`, `VT_DATE` as an `f64`, `VT_UNKNOWN` as an interface
+//! pointer which may be null, and `VT_LPWSTR` like a [`BStr`]. `VT_EMPTY` and
+//! `VT_NULL` have no value. Other types, such as arrays and `VT_DISPATCH`, cannot be
+//! marshalled and fail with `DISP_E_BADVARTYPE`.
+//!
+//! Interface pointers are written as references to the object, which are resolved
+//! by the [`ObjectTable`] of the connection the message is sent over.
+//!
+//! Decoding never trusts the message: lengths are checked against the size of the
+//! message before anything is allocated, and invalid data is reported as an
+//! `RPC_E_INVALID_DATA` error.
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::interfaces::IUnknown;
+use crate::sys::{
+    self, DISP_E_BADVARTYPE, E_NOTIMPL, GUID, IID, RPC_E_INVALID_DATA, SAFEARRAYBOUND, VARTYPE,
+};
+use crate::{
+    BStr, Error, ErrorInfo, HResult, Interface, PropVariant, PropVariantValue, SafeArray,
+    SafeArrayElement, Variant, VariantValue,
+};
+
+#[doc(inline)]
+pub use com_macros::Marshal;
+
+/// The version of the format described in the [module documentation](self)
+///
+/// The version is incremented whenever the representation of a type changes.
+pub const VERSION: u32 = 2;
+
+/// A type which can be sent to another process as a method parameter
+pub trait Marshal: Sized {
+    /// Write the value to `encoder`
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error>;
+
+    /// Read a value written by [`Marshal::marshal`] from `decoder`
+    ///
+    /// Returns an `RPC_E_INVALID_DATA` error if the data is not a valid value.
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error>;
+}
+
+/// The objects which can be referenced by the interface pointers in a message
+///
+/// This is implemented by connections to other processes, which export the objects
+/// they send and create proxies for the objects they receive.
+pub trait ObjectTable {
+    /// Write a reference to `object`, which is an interface pointer for `iid`
+    fn export(&self, object: &IUnknown, iid: &IID, encoder: &mut Encoder<'_>) -> Result<(), Error>;
+
+    /// Read a reference written by [`ObjectTable::export`] on the other side of
+    /// the connection
+    ///
+    /// The returned interface pointer is a pointer to the interface `iid`.
+    fn import(&self, iid: &IID, decoder: &mut Decoder<'_>) -> Result<IUnknown, Error>;
+}
+
+/// A message being written
+#[derive(Default)]
+pub struct Encoder<'a> {
+    bytes: Vec<u8>,
+    objects: Option<&'a dyn ObjectTable>,
+}
+
+impl<'a> Encoder<'a> {
+    /// Create an encoder for a message which cannot contain interface pointers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an encoder for a message whose interface pointers are exported to
+    /// `objects`
+    pub fn with_objects(objects: &'a dyn ObjectTable) -> Self {
+        Self {
+            bytes: Vec::new(),
+            objects: Some(objects),
+        }
+    }
+
+    /// Append raw bytes to the message
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// The table interface pointers are exported to
+    ///
+    /// Returns an `E_NOTIMPL` error if the message cannot contain interface pointers.
+    pub fn objects(&self) -> Result<&'a dyn ObjectTable, Error> {
+        self.objects.ok_or_else(|| HResult(E_NOTIMPL).into())
+    }
+
+    /// The message written so far
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Take the written message
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// A message being read
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+    objects: Option<&'a dyn ObjectTable>,
+}
+
+impl<'a> Decoder<'a> {
+    /// Create a decoder for a message which cannot contain interface pointers
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            objects: None,
+        }
+    }
+
+    /// Create a decoder for a message whose interface pointers are imported from
+    /// `objects`
+    pub fn with_objects(bytes: &'a [u8], objects: &'a dyn ObjectTable) -> Self {
+        Self {
+            bytes,
+            objects: Some(objects),
+        }
+    }
+
+    /// Read `len` raw bytes from the message
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.bytes.len() {
+            return Err(invalid_data());
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    /// The table interface pointers are imported from
+    ///
+    /// Returns an `E_NOTIMPL` error if the message cannot contain interface pointers.
+    pub fn objects(&self) -> Result<&'a dyn ObjectTable, Error> {
+        self.objects.ok_or_else(|| HResult(E_NOTIMPL).into())
+    }
+
+    /// The part of the message which has not been read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+/// The error returned when a message is not valid
+pub(crate) fn invalid_data() -> Error {
+    HResult(RPC_E_INVALID_DATA).into()
+}
+
+macro_rules! marshal_number {
+    ($($t:ty),+) => {
+        $(impl Marshal for $t {
+            fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+                encoder.write_bytes(&self.to_le_bytes());
+                Ok(())
+            }
+
+            fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+                let bytes = decoder.read_bytes(core::mem::size_of::<$t>())?;
+                Ok(<$t>::from_le_bytes(TryFrom::try_from(bytes).unwrap()))
+            }
+        })*
+    };
+}
+
+marshal_number! { i8, u8, i16, u16, i32, u32, i64, u64, f32, f64 }
+
+/// `usize` is written as a `u64`, so that it can be passed between 32-bit and
+/// 64-bit processes
+impl Marshal for usize {
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        (*self as u64).marshal(encoder)
+    }
+
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+        usize::try_from(u64::unmarshal(decoder)?).map_err(|_| invalid_data())
+    }
+}
+
+/// `isize` is written as an `i64`, so that it can be passed between 32-bit and
+/// 64-bit processes
+impl Marshal for isize {
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        (*self as i64).marshal(encoder)
+    }
+
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+        isize::try_from(i64::unmarshal(decoder)?).map_err(|_| invalid_data())
+    }
+}
+
+impl Marshal for bool {
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        (*self as u8).marshal(encoder)
+    }
+
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+        match u8::unmarshal(decoder)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid_data()),
+        }
+    }
+}
+
+impl Marshal for GUID {
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        self.data1.marshal(encoder)?;
+        self.data2.marshal(encoder)?;
+        self.data3.marshal(encoder)?;
+        encoder.write_bytes(&self.data4);
+        Ok(())
+    }
+
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+        Ok(GUID {
+            data1: u32::unmarshal(decoder)?,
+            data2: u16::unmarshal(decoder)?,
+            data3: u16::unmarshal(decoder)?,
+            data4: TryFrom::try_from(decoder.read_bytes(8)?).unwrap(),
+        })
+    }
+}
+
+impl Marshal for HResult {
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        self.0.marshal(encoder)
+    }
+
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+        i32::unmarshal(decoder).map(HResult)
+    }
+}
+
+impl<T: Marshal> Marshal for Option<T> {
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        self.is_some().marshal(encoder)?;
+        match self {
+            Some(value) => value.marshal(encoder),
+            None => Ok(()),
+        }
+    }
+
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+        if bool::unmarshal(decoder)? {
+            T::unmarshal(decoder).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<T: Marshal, const N: usize> Marshal for [T; N] {
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        self.iter().try_for_each(|element| element.marshal(encoder))
+    }
+
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+        let elements = unmarshal_elements(N, decoder)?;
+        match <[T; N]>::try_from(elements) {
+            Ok(array) => Ok(array),
+            Err(_) => unreachable!("exactly N elements are read"),
+        }
+    }
+}
+
+impl<T: Marshal> Marshal for Vec<T> {
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        marshal_slice(self, encoder)
+    }
+
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+        let len = u32::unmarshal(decoder)?;
+        unmarshal_elements(len as usize, decoder)
+    }
+}
+
+/// Write a slice like a `Vec`
+pub(crate) fn marshal_slice<T: Marshal>(
+    elements: &[T],
+    encoder: &mut Encoder<'_>,
+) -> Result<(), Error> {
+    marshal_len(elements.len(), encoder)?;
+    elements
+        .iter()
+        .try_for_each(|element| element.marshal(encoder))
+}
+
+/// Write the length of a sequence as a `u32`
+fn marshal_len(len: usize, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+    u32::try_from(len)
+        .map_err(|_| invalid_data())?
+        .marshal(encoder)
+}
+
+/// Read `len` elements
+fn unmarshal_elements<T: Marshal>(len: usize, decoder: &mut Decoder<'_>) -> Result<Vec<T>, Error> {
+    // The length has not been checked yet, but the elements cannot be smaller than
+    // a byte unless the message is invalid
+    let mut elements = Vec::with_capacity(len.min(decoder.remaining().len()));
+    for _ in 0..len {
+        elements.push(T::unmarshal(decoder)?);
+    }
+    Ok(elements)
+}
+
+impl Marshal for BStr {
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        marshal_wide(self.as_wide(), encoder)
+    }
+
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+        unmarshal_wide(decoder).map(|s| BStr::from_wide(&s))
+    }
+}
+
+impl Marshal for ErrorInfo {
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        for s in [&self.description, &self.source, &self.help_file] {
+            marshal_wide(&s.encode_utf16().collect::<Vec<_>>(), encoder)?;
+        }
+        self.help_context.marshal(encoder)?;
+        self.guid.marshal(encoder)
+    }
+
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+        let mut string = || Ok::<_, Error>(String::from_utf16_lossy(&unmarshal_wide(decoder)?));
+        let mut info = ErrorInfo::new(string()?);
+        info.source = string()?;
+        info.help_file = string()?;
+        info.help_context = u32::unmarshal(decoder)?;
+        info.guid = GUID::unmarshal(decoder)?;
+        Ok(info)
+    }
+}
+
+/// Write UTF-16 code units like a [`BStr`]
+fn marshal_wide(s: &[u16], encoder: &mut Encoder<'_>) -> Result<(), Error> {
+    marshal_len(s.len(), encoder)?;
+    for unit in s {
+        encoder.write_bytes(&unit.to_le_bytes());
+    }
+    Ok(())
+}
+
+/// Read UTF-16 code units written by [`marshal_wide`]
+fn unmarshal_wide(decoder: &mut Decoder<'_>) -> Result<Vec<u16>, Error> {
+    let len = u32::unmarshal(decoder)? as usize;
+    let bytes = decoder.read_bytes(len.checked_mul(2).ok_or_else(invalid_data)?)?;
+    Ok(bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect())
+}
+
+impl<T: SafeArrayElement + Marshal> Marshal for SafeArray<T> {
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        let bounds = self.bounds();
+        // `cDims` is a `u16`
+        (bounds.len() as u16).marshal(encoder)?;
+        for bound in &bounds {
+            bound.cElements.marshal(encoder)?;
+            bound.lLbound.marshal(encoder)?;
+        }
+        self.lock()?
+            .iter()
+            .try_for_each(|element| element.marshal(encoder))
+    }
+
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+        let dims = u16::unmarshal(decoder)?;
+        if dims == 0 {
+            return Err(invalid_data());
+        }
+        let mut bounds = Vec::new();
+        let mut len = 1usize;
+        for _ in 0..dims {
+            let bound = SAFEARRAYBOUND {
+                cElements: u32::unmarshal(decoder)?,
+                lLbound: i32::unmarshal(decoder)?,
+            };
+            len = len
+                .checked_mul(bound.cElements as usize)
+                .ok_or_else(invalid_data)?;
+            bounds.push(bound);
+        }
+        // The elements which can be stored in arrays take at least a byte, so the
+        // array is not allocated if the message is too short
+        if len > decoder.remaining().len() {
+            return Err(invalid_data());
+        }
+        let mut array = SafeArray::with_bounds(&bounds);
+        {
+            let mut elements = array.lock_mut()?;
+            for element in elements.iter_mut() {
+                *element = T::unmarshal(decoder)?;
+            }
+        }
+        Ok(array)
+    }
+}
+
+impl Marshal for Variant {
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        marshal_variant_value(&self.value(), encoder)
+    }
+
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+        let vt = VARTYPE::unmarshal(decoder)?;
+        unmarshal_variant_value(vt, decoder).map(Variant::from)
+    }
+}
+
+impl Marshal for PropVariant {
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        match self.value() {
+            PropVariantValue::Variant(value) => marshal_variant_value(&value, encoder),
+            PropVariantValue::LpWStr(s) => {
+                sys::VT_LPWSTR.marshal(encoder)?;
+                marshal_wide(&s.encode_utf16().collect::<Vec<_>>(), encoder)
+            }
+            PropVariantValue::FileTime(time) => {
+                sys::VT_FILETIME.marshal(encoder)?;
+                time.marshal(encoder)
+            }
+            PropVariantValue::Clsid(guid) => {
+                sys::VT_CLSID.marshal(encoder)?;
+                guid.marshal(encoder)
+            }
+            PropVariantValue::Other(_) => Err(HResult(DISP_E_BADVARTYPE).into()),
+        }
+    }
+
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+        let value = match VARTYPE::unmarshal(decoder)? {
+            sys::VT_LPWSTR => {
+                let s = unmarshal_wide(decoder)?;
+                PropVariantValue::LpWStr(String::from_utf16(&s).map_err(|_| invalid_data())?)
+            }
+            sys::VT_FILETIME => PropVariantValue::FileTime(u64::unmarshal(decoder)?),
+            sys::VT_CLSID => PropVariantValue::Clsid(GUID::unmarshal(decoder)?),
+            vt => PropVariantValue::Variant(unmarshal_variant_value(vt, decoder)?),
+        };
+        Ok(PropVariant::from(value))
+    }
+}
+
+macro_rules! variant_values {
+    ($($arm:ident => $vt:ident),* $(,)?) => {
+        /// Write the `VARTYPE` and the value of a variant
+        fn marshal_variant_value(
+            value: &VariantValue,
+            encoder: &mut Encoder<'_>,
+        ) -> Result<(), Error> {
+            match value {
+                VariantValue::Empty => sys::VT_EMPTY.marshal(encoder),
+                VariantValue::Null => sys::VT_NULL.marshal(encoder),
+                $(VariantValue::$arm(value) => {
+                    sys::$vt.marshal(encoder)?;
+                    value.marshal(encoder)
+                })*
+                VariantValue::Unknown(value) => {
+                    sys::VT_UNKNOWN.marshal(encoder)?;
+                    value.is_some().marshal(encoder)?;
+                    match value {
+                        Some(value) => encoder.objects()?.export(value, &IUnknown::IID, encoder),
+                        None => Ok(()),
+                    }
+                }
+                VariantValue::Dispatch(_) | VariantValue::Other(_) => {
+                    Err(HResult(DISP_E_BADVARTYPE).into())
+                }
+            }
+        }
+
+        /// Read the value of a variant of type `vt`
+        fn unmarshal_variant_value(
+            vt: VARTYPE,
+            decoder: &mut Decoder<'_>,
+        ) -> Result<VariantValue, Error> {
+            match vt {
+                sys::VT_EMPTY => Ok(VariantValue::Empty),
+                sys::VT_NULL => Ok(VariantValue::Null),
+                $(sys::$vt => Marshal::unmarshal(decoder).map(VariantValue::$arm),)*
+                sys::VT_UNKNOWN => {
+                    if bool::unmarshal(decoder)? {
+                        let object = decoder.objects()?.import(&IUnknown::IID, decoder)?;
+                        Ok(VariantValue::Unknown(Some(object)))
+                    } else {
+                        Ok(VariantValue::Unknown(None))
+                    }
+                }
+                _ => Err(invalid_data()),
+            }
+        }
+    };
+}
+
+variant_values! {
+    I1 => VT_I1,
+    I2 => VT_I2,
+    I4 => VT_I4,
+    I8 => VT_I8,
+    UI1 => VT_UI1,
+    UI2 => VT_UI2,
+    UI4 => VT_UI4,
+    UI8 => VT_UI8,
+    Int => VT_INT,
+    UInt => VT_UINT,
+    R4 => VT_R4,
+    R8 => VT_R8,
+    Currency => VT_CY,
+    Date => VT_DATE,
+    Bool => VT_BOOL,
+    Error => VT_ERROR,
+    BStr => VT_BSTR,
+}
@@ -0,0 +1,287 @@
+//! Mock objects for unit testing clients of COM interfaces
+//!
+//! The `interfaces!` macro generates a mock class for the interfaces marked
+//! `#[mock]`, named after the interface with a `Mock` prefix. Every method of the
+//! interface has an `expect_` method setting the closure which runs when the
+//! method is called. The closure takes the parameters of the method and returns
+//! its result, which is a `Result<T, com::Error>` for methods with a `#[retval]`
+//! parameter. The [`Expectation`] it returns can also check the number of calls:
+//!
+//! ```rust
+//! use com::sys::{HRESULT, S_OK};
+//! use std::cell::RefCell;
+//! use std::rc::Rc;
+//!
+//! com::interfaces! {
+//!     #[uuid("EFF8970E-C50F-45E0-9284-291CE5A6F771")]
+//!     #[mock]
+//!     pub unsafe interface IAnimal: com::interfaces::IUnknown {
+//!         fn Eat(&self, food: u32) -> HRESULT;
+//!         fn Weight(&self, #[retval] weight: *mut u32) -> HRESULT;
+//!     }
+//! }
+//!
+//! /// The client code under test
+//! fn feed(animal: &IAnimal) -> Result<u32, com::Error> {
+//!     com::HResult(unsafe { animal.Eat(3) }).ok()?;
+//!     unsafe { animal.Weight() }
+//! }
+//!
+//! let mock = MockIAnimal::new();
+//! let eaten = Rc::new(RefCell::new(Vec::new()));
+//! let log = eaten.clone();
+//! mock.expect_eat(move |food| {
+//!     log.borrow_mut().push(food);
+//!     S_OK
+//! })
+//! .times(1);
+//! mock.expect_weight(|| Ok(5));
+//!
+//! assert_eq!(feed(&mock.interface()), Ok(5));
+//! assert_eq!(*eaten.borrow(), [3]);
+//! // The expectations are verified when the mock is dropped
+//! ```
+//!
+//! Calls of methods without a closure, and calls beyond the number set with
+//! [`Expectation::times`], fail. The failure is reported when the [`Mock`] is
+//! dropped, or by [`Mock::verify`]. Methods returning an `HRESULT` panic, which is
+//! turned into the `HRESULT` of the `#[on_panic]` behavior of classes,
+//! `E_UNEXPECTED`. Other methods return the default value of their return type, or
+//! a null pointer, and abort the process if their type has no default value.
+//!
+//! The mock implements the methods of the base interfaces too, which must be
+//! declared in the same `interfaces!` block, unless they are `IUnknown` or
+//! `IDispatch`. The mocks of interfaces deriving from `IDispatch` implement it
+//! with `#[dispatch]`. Mocks are classes declared with `#[refcount(local)]`, so
+//! they cannot be sent to other threads.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::production::{Class, ClassAllocation};
+
+/// The closure and the expected number of calls of a method of a mock
+///
+/// `F` is the `dyn FnMut` type of the closure.
+pub struct Expectation<F: ?Sized> {
+    method: &'static str,
+    returning: RefCell<Option<Box<F>>>,
+    times: Cell<Option<usize>>,
+    calls: Cell<usize>,
+    failures: RefCell<Vec<String>>,
+}
+
+impl<F: ?Sized> Expectation<F> {
+    /// Create an expectation for `method`, which is not expected to be called
+    #[doc(hidden)]
+    pub fn new(method: &'static str) -> Self {
+        Self {
+            method,
+            returning: RefCell::new(None),
+            times: Cell::new(None),
+            calls: Cell::new(0),
+            failures: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Set the closure called by the method
+    #[doc(hidden)]
+    pub fn returning(&self, returning: Box<F>) -> &Self {
+        *self.returning.borrow_mut() = Some(returning);
+        self
+    }
+
+    /// Expect the method to be called exactly `times` times
+    ///
+    /// By default, the method can be called any number of times once it has a
+    /// closure.
+    pub fn times(&self, times: usize) -> &Self {
+        self.times.set(Some(times));
+        self
+    }
+
+    /// The number of times the method was called
+    pub fn calls(&self) -> usize {
+        self.calls.get()
+    }
+
+    /// Call the closure of the method with `call`, panicking if the call fails
+    #[doc(hidden)]
+    pub fn call<R>(&self, call: impl FnOnce(&mut F) -> R) -> R {
+        match self.try_call(call) {
+            Ok(result) => result,
+            Err(failure) => panic!("{}", failure),
+        }
+    }
+
+    /// Call the closure of the method with `call`, returning `fallback` if the call
+    /// fails
+    ///
+    /// This is used for the methods which do not return an `HRESULT`, since their
+    /// panics abort the process. They only panic if there is no fallback value.
+    #[doc(hidden)]
+    pub fn call_or<R>(&self, call: impl FnOnce(&mut F) -> R, fallback: Option<R>) -> R {
+        match (self.try_call(call), fallback) {
+            (Ok(result), _) => result,
+            (Err(_), Some(fallback)) => fallback,
+            (Err(failure), None) => panic!("{}", failure),
+        }
+    }
+
+    /// Call the closure of the method with `call`, or record and return the failure
+    fn try_call<R>(&self, call: impl FnOnce(&mut F) -> R) -> Result<R, String> {
+        let calls = self.calls.get() + 1;
+        self.calls.set(calls);
+        if matches!(self.times.get(), Some(times) if calls > times) {
+            return Err(self.fail(format!(
+                "{} was called {} times, but {} calls were expected",
+                self.method,
+                calls,
+                self.times.get().unwrap()
+            )));
+        }
+        // The closure is taken out while it runs, so that it can call other
+        // methods of the mock
+        let returning = self.returning.borrow_mut().take();
+        let mut returning = match returning {
+            Some(returning) => returning,
+            None => return Err(self.fail(format!("unexpected call to {}", self.method))),
+        };
+        let result = call(&mut returning);
+        *self.returning.borrow_mut() = Some(returning);
+        Ok(result)
+    }
+
+    /// Record a failure
+    fn fail(&self, failure: String) -> String {
+        self.failures.borrow_mut().push(failure.clone());
+        failure
+    }
+
+    /// Add the failures of the expectation to `failures`
+    #[doc(hidden)]
+    pub fn verify(&self, failures: &mut Vec<String>) {
+        failures.extend(self.failures.borrow().iter().cloned());
+        let calls = self.calls.get();
+        match self.times.get() {
+            Some(times) if calls < times => failures.push(format!(
+                "{} was called {} times, but {} calls were expected",
+                self.method, calls, times
+            )),
+            _ => {}
+        }
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for Expectation<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Expectation")
+            .field("method", &self.method)
+            .field("times", &self.times.get())
+            .field("calls", &self.calls.get())
+            .finish()
+    }
+}
+
+/// The return type of a method of a mock, for which the generated code calls
+/// `fallback` on a reference
+///
+/// This resolves to [`DefaultFallback`] when the type implements `Default`, and
+/// otherwise to [`NoFallback`].
+#[doc(hidden)]
+pub struct Fallback<T>(pub PhantomData<T>);
+
+/// Chosen for the return types implementing `Default`
+#[doc(hidden)]
+pub trait DefaultFallback<T> {
+    /// The default value
+    fn fallback(&self) -> Option<T>;
+}
+
+impl<T: Default> DefaultFallback<T> for Fallback<T> {
+    fn fallback(&self) -> Option<T> {
+        Some(T::default())
+    }
+}
+
+/// Chosen for the other return types
+#[doc(hidden)]
+pub trait NoFallback<T> {
+    /// No value
+    fn fallback(&self) -> Option<T>;
+}
+
+impl<T> NoFallback<T> for &Fallback<T> {
+    fn fallback(&self) -> Option<T> {
+        None
+    }
+}
+
+/// A mock class generated for a `#[mock]` interface
+pub trait Verify: Class {
+    /// The failed expectations of the mock
+    fn failures(&self) -> Vec<String>;
+}
+
+/// A mock object, which verifies its expectations when it is dropped
+///
+/// This dereferences to the [`ClassAllocation`] of the mock, so the `expect_`
+/// methods of the mock, `interface` and `query_interface` can be called on it.
+pub struct Mock<T: Verify> {
+    object: ClassAllocation<T>,
+}
+
+impl<T: Verify> Mock<T> {
+    /// Wrap a mock object
+    #[doc(hidden)]
+    pub fn new(object: ClassAllocation<T>) -> Self {
+        Self { object }
+    }
+
+    /// Panic if an expectation of the mock has failed
+    ///
+    /// The number of calls is only checked against [`Expectation::times`] once the
+    /// mock is dropped, or when this is called.
+    #[track_caller]
+    pub fn verify(&self) {
+        let failures = self.object.failures();
+        if !failures.is_empty() {
+            panic!(
+                "{} expectation(s) of {} failed:\n  {}",
+                failures.len(),
+                core::any::type_name::<T>(),
+                failures.join("\n  ")
+            );
+        }
+    }
+}
+
+impl<T: Verify> core::ops::Deref for Mock<T> {
+    type Target = ClassAllocation<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.object
+    }
+}
+
+impl<T: Verify> Drop for Mock<T> {
+    fn drop(&mut self) {
+        // A failed test is already panicking, and may have failed because of the
+        // mock
+        if !std::thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+impl<T: Verify> fmt::Debug for Mock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Mock")
+            .field(&core::any::type_name::<T>())
+            .finish()
+    }
+}
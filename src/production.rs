@@ -1,8 +1,19 @@
+mod aggregation;
 mod class;
+#[doc(hidden)]
+pub mod dispatch;
 #[cfg(windows)]
 #[doc(hidden)]
 #[cfg(windows)]
 pub mod registration;
+mod registry;
+mod weak_ref;
 
 #[doc(inline)]
-pub use class::{Class, ClassAllocation};
+pub use aggregation::Aggregate;
+#[doc(inline)]
+pub use class::{Class, ClassAllocation, ClassFactory};
+#[doc(inline)]
+pub use registry::ClassRegistry;
+#[doc(hidden)]
+pub use weak_ref::WeakRefCount;
@@ -0,0 +1,57 @@
+use core::cell::{Cell, UnsafeCell};
+
+use crate::interfaces::IUnknown;
+
+/// The inner object of a COM aggregate
+///
+/// A `com::class!` field of this type which is marked with `#[aggregate]` makes
+/// the class expose all interfaces of the inner object through its own
+/// `QueryInterface`.
+///
+/// The inner object is created after the class itself, because it needs the
+/// `IUnknown` of the class as its controlling (outer) object:
+///
+/// ```rust,ignore
+/// let outer = Outer::allocate(Aggregate::new());
+/// let outer_unknown = outer.query_interface::<IUnknown>().unwrap();
+/// let inner = unsafe { Inner::allocate_aggregated(&outer_unknown) };
+/// outer.inner.set(inner).unwrap();
+/// ```
+#[derive(Default)]
+pub struct Aggregate {
+    inner: UnsafeCell<Option<IUnknown>>,
+    initialized: Cell<bool>,
+}
+
+impl Aggregate {
+    /// Create an empty aggregate
+    pub const fn new() -> Self {
+        Self {
+            inner: UnsafeCell::new(None),
+            initialized: Cell::new(false),
+        }
+    }
+
+    /// Set the non-delegating `IUnknown` of the inner object
+    ///
+    /// The inner object can only be set once. If it has already been set, the
+    /// supplied `IUnknown` is returned as an error.
+    pub fn set(&self, inner: IUnknown) -> Result<(), IUnknown> {
+        if self.initialized.get() {
+            return Err(inner);
+        }
+        // SAFETY: No references into `inner` can exist before it is initialized
+        unsafe { *self.inner.get() = Some(inner) };
+        self.initialized.set(true);
+        Ok(())
+    }
+
+    /// Get the non-delegating `IUnknown` of the inner object, if it has been set
+    pub fn get(&self) -> Option<&IUnknown> {
+        if !self.initialized.get() {
+            return None;
+        }
+        // SAFETY: `inner` is never modified once it has been initialized
+        unsafe { (*self.inner.get()).as_ref() }
+    }
+}
@@ -3,8 +3,8 @@
 use alloc::boxed::Box;
 use core::mem::ManuallyDrop;
 
-#[cfg(doc)]
 use crate::interfaces::IUnknown;
+use crate::{Interface, WeakRef};
 
 /// A COM compliant class
 ///
@@ -13,11 +13,16 @@ use crate::interfaces::IUnknown;
 /// The implementing struct must have the following properties:
 /// * it is `#[repr(C)]`
 /// * The first fields of the struct are pointers to the backing VTables for
-/// each of the COM Interfaces the class implements
+///   each of the COM Interfaces the class implements
 pub unsafe trait Class {
     /// The factory object associated with this class
     type Factory;
 
+    /// The type of the reference count of this class
+    ///
+    /// This is an `AtomicU32` unless the class was declared with `#[refcount(local)]`.
+    type RefCount: crate::refcounting::RefCount;
+
     /// Decrement the current reference count and return the new count
     ///
     /// # Safety
@@ -55,6 +60,15 @@ pub unsafe trait Class {
     unsafe fn add_ref(&self) -> u32;
 }
 
+/// A class factory generated by `com::class!`
+///
+/// This is implemented for the [`Class::Factory`] type of every class that does
+/// not opt out of having a class factory.
+pub trait ClassFactory: Class {
+    /// Allocate a new instance of the class factory
+    fn create() -> crate::interfaces::IClassFactory;
+}
+
 /// An allocated COM class
 ///
 /// The class must be heap allocated and not be moved in memory.
@@ -71,6 +85,8 @@ impl<T: Class> ClassAllocation<T> {
     ///
     /// This is not normally used by users of the COM crate but by the code generator
     pub fn new(inner: core::pin::Pin<Box<T>>) -> Self {
+        #[cfg(feature = "leak-tracking")]
+        crate::debug::track(core::any::type_name::<T>(), &*inner as *const T as usize);
         Self {
             inner: core::mem::ManuallyDrop::new(inner),
         }
@@ -94,18 +110,37 @@ impl<T: Class> ClassAllocation<T> {
     #[doc(hidden)]
     #[inline(never)]
     pub unsafe fn drop_inner(&mut self) {
+        #[cfg(feature = "leak-tracking")]
+        crate::debug::untrack(&**self.inner as *const T as usize);
         ManuallyDrop::drop(&mut self.inner);
     }
+
+    /// Create a weak reference to the class
+    ///
+    /// Returns `None` if the class does not support weak references, that is if it
+    /// was not declared with `#[weak_ref]`.
+    pub fn downgrade<I: Interface>(&self) -> Option<WeakRef<I>> {
+        // The first field of the class is the vpointer of its first interface
+        // chain, so a pointer to the class is also a valid `IUnknown` pointer.
+        let unknown = unsafe { &*(self as *const Self as *const IUnknown) };
+        WeakRef::from_unknown(unknown)
+    }
 }
 
 /// [`ClassAllocation<T>`] is [`Send`] because it represents an owned reference to
 /// a heap allocation, and the changes to that reference count are atomic.
-unsafe impl<T: Class> Send for ClassAllocation<T> {}
+///
+/// Classes with a non-atomic reference count (`#[refcount(local)]`) are not [`Send`],
+/// because clones of the allocation may be released concurrently on other threads.
+unsafe impl<T: Class> Send for ClassAllocation<T> where T::RefCount: Sync {}
 
 /// [`ClassAllocation<T>`] is [`Sync`] because it represents an aliased (shared)
 /// reference to a heap-allocated object, and the only way you can gain access
 /// to that heap object is to acquire a `&self` (shared) reference.
-unsafe impl<T: Class> Sync for ClassAllocation<T> {}
+///
+/// Classes with a non-atomic reference count (`#[refcount(local)]`) are not [`Sync`],
+/// because an allocation can be cloned through a shared reference.
+unsafe impl<T: Class> Sync for ClassAllocation<T> where T::RefCount: Sync {}
 
 impl<T: Class> core::ops::Deref for ClassAllocation<T> {
     type Target = core::pin::Pin<Box<T>>;
@@ -120,7 +155,7 @@ impl<T: Class> Drop for ClassAllocation<T> {
         unsafe {
             if self.inner.dec_ref_count() == 0 {
                 // SAFETY: This is safe because the inner value is not accessible by anyone else
-                core::mem::ManuallyDrop::drop(&mut self.inner);
+                self.drop_inner();
             }
         }
     }
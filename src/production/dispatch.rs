@@ -0,0 +1,209 @@
+//! Support for the `IDispatch` implementations generated by `class!` for
+//! `#[dispatch]` classes
+//!
+//! Members are identified by their position in the list of exposed methods, starting
+//! at `DISPID` 1. Only positional arguments are supported.
+//!
+//! Whether the arguments and result of a member can be converted is decided with
+//! [`Convert`]: the generated code calls `argument_fn` and `result_fn` on a
+//! reference to it, which resolve to [`FromVariant`] and [`IntoVariant`] when the
+//! conversion exists, and otherwise to the fallbacks, which make the member fail
+//! with `DISP_E_MEMBERNOTFOUND`.
+use core::convert::TryFrom;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+
+use crate::sys::{
+    DISPATCH_METHOD, DISPATCH_PROPERTYGET, DISPID, DISPID_UNKNOWN, DISPPARAMS, DISP_E_BADINDEX,
+    DISP_E_BADPARAMCOUNT, DISP_E_MEMBERNOTFOUND, DISP_E_NONAMEDARGS, DISP_E_UNKNOWNNAME, E_POINTER,
+    HRESULT, S_OK, VARIANT,
+};
+use crate::{Error, HResult, Variant};
+
+/// The implementation of `GetTypeInfoCount`, reporting that no type information is
+/// available
+pub unsafe fn get_type_info_count(count: *mut u32) -> HRESULT {
+    if count.is_null() {
+        return E_POINTER;
+    }
+    *count = 0;
+    S_OK
+}
+
+/// The implementation of `GetTypeInfo`
+pub unsafe fn get_type_info(type_info: *mut *mut c_void) -> HRESULT {
+    if type_info.is_null() {
+        return E_POINTER;
+    }
+    *type_info = core::ptr::null_mut();
+    DISP_E_BADINDEX
+}
+
+/// The implementation of `GetIDsOfNames`
+///
+/// Names are compared case-insensitively. Since named arguments are not supported,
+/// any name after the member name is unknown.
+pub unsafe fn get_ids_of_names(
+    members: &[&str],
+    names: *const *const u16,
+    count: u32,
+    dispids: *mut DISPID,
+) -> HRESULT {
+    if count == 0 {
+        return S_OK;
+    }
+    if names.is_null() || dispids.is_null() {
+        return E_POINTER;
+    }
+    let names = core::slice::from_raw_parts(names, count as usize);
+    let dispids = core::slice::from_raw_parts_mut(dispids, count as usize);
+    for dispid in dispids.iter_mut() {
+        *dispid = DISPID_UNKNOWN;
+    }
+
+    let member = members
+        .iter()
+        .position(|member| !names[0].is_null() && eq_ignore_case(member, names[0]));
+    match member {
+        Some(index) => dispids[0] = index as DISPID + 1,
+        None => return DISP_E_UNKNOWNNAME,
+    }
+    if count > 1 {
+        return DISP_E_UNKNOWNNAME;
+    }
+    S_OK
+}
+
+/// Compare a name with a null-terminated UTF-16 string, ignoring case
+unsafe fn eq_ignore_case(name: &str, other: *const u16) -> bool {
+    let mut other_len = 0;
+    while *other.add(other_len) != 0 {
+        other_len += 1;
+    }
+    let other = core::slice::from_raw_parts(other, other_len);
+    let lowercase = |c: char| c.to_lowercase();
+    name.chars()
+        .flat_map(lowercase)
+        .eq(core::char::decode_utf16(other.iter().copied())
+            .map(|c| c.unwrap_or(core::char::REPLACEMENT_CHARACTER))
+            .flat_map(lowercase))
+}
+
+/// The positional arguments of an `Invoke` call, in reverse order
+///
+/// Fails if the member is not invoked as a method or property getter, or if the
+/// number of arguments is not `expected`.
+pub unsafe fn arguments<'a>(
+    flags: u16,
+    params: *const DISPPARAMS,
+    expected: usize,
+) -> Result<&'a [Variant], HRESULT> {
+    if flags & (DISPATCH_METHOD | DISPATCH_PROPERTYGET) == 0 {
+        return Err(DISP_E_MEMBERNOTFOUND);
+    }
+    let params = match params.as_ref() {
+        Some(params) => params,
+        None if expected == 0 => return Ok(&[]),
+        None => return Err(DISP_E_BADPARAMCOUNT),
+    };
+    if params.cNamedArgs != 0 {
+        return Err(DISP_E_NONAMEDARGS);
+    }
+    if params.cArgs as usize != expected {
+        return Err(DISP_E_BADPARAMCOUNT);
+    }
+    if expected == 0 {
+        return Ok(&[]);
+    }
+    if params.rgvarg.is_null() {
+        return Err(E_POINTER);
+    }
+    Ok(core::slice::from_raw_parts(
+        params.rgvarg as *const Variant,
+        expected,
+    ))
+}
+
+/// Convert the argument at position `index` of an `Invoke` call
+///
+/// On failure, the position of the argument in `args` is written to `arg_err`.
+pub unsafe fn argument<T>(args: &[Variant], index: usize, arg_err: *mut u32) -> Result<T, HRESULT>
+where
+    T: for<'a> TryFrom<&'a Variant, Error = Error>,
+{
+    let position = args.len() - 1 - index;
+    T::try_from(&args[position]).map_err(|e| {
+        if !arg_err.is_null() {
+            *arg_err = position as u32;
+        }
+        HResult::from(e).0
+    })
+}
+
+/// The type of an argument or of the result of a member, see the module
+/// documentation
+pub struct Convert<T>(pub PhantomData<T>);
+
+/// The conversion of the arguments at a position of an `Invoke` call
+pub type ArgumentFn<T> = unsafe fn(&[Variant], usize, *mut u32) -> Result<T, HRESULT>;
+
+/// Chosen for the arguments which can be converted from a `Variant`
+pub trait FromVariant<T> {
+    /// The conversion of the argument
+    fn argument_fn(&self) -> Result<ArgumentFn<T>, HRESULT>;
+}
+
+impl<T> FromVariant<T> for Convert<T>
+where
+    T: for<'a> TryFrom<&'a Variant, Error = Error>,
+{
+    fn argument_fn(&self) -> Result<ArgumentFn<T>, HRESULT> {
+        Ok(argument::<T>)
+    }
+}
+
+/// Chosen for the arguments which cannot be converted from a `Variant`
+pub trait FromVariantFallback<T> {
+    /// Fails with `DISP_E_MEMBERNOTFOUND`
+    fn argument_fn(&self) -> Result<ArgumentFn<T>, HRESULT>;
+}
+
+impl<T> FromVariantFallback<T> for &Convert<T> {
+    fn argument_fn(&self) -> Result<ArgumentFn<T>, HRESULT> {
+        Err(DISP_E_MEMBERNOTFOUND)
+    }
+}
+
+/// Chosen for the results which can be converted into a `Variant`
+pub trait IntoVariant<T> {
+    /// The conversion of the result
+    fn result_fn(&self) -> Result<fn(T) -> Variant, HRESULT>;
+}
+
+impl<T> IntoVariant<T> for Convert<T>
+where
+    Variant: From<T>,
+{
+    fn result_fn(&self) -> Result<fn(T) -> Variant, HRESULT> {
+        Ok(Variant::from)
+    }
+}
+
+/// Chosen for the results which cannot be converted into a `Variant`
+pub trait IntoVariantFallback<T> {
+    /// Fails with `DISP_E_MEMBERNOTFOUND`
+    fn result_fn(&self) -> Result<fn(T) -> Variant, HRESULT>;
+}
+
+impl<T> IntoVariantFallback<T> for &Convert<T> {
+    fn result_fn(&self) -> Result<fn(T) -> Variant, HRESULT> {
+        Err(DISP_E_MEMBERNOTFOUND)
+    }
+}
+
+/// Write the result of an `Invoke` call, if the caller asked for it
+pub unsafe fn set_result(result: *mut VARIANT, value: Variant) {
+    if !result.is_null() {
+        result.write(value.into_raw());
+    }
+}
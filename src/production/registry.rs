@@ -0,0 +1,173 @@
+use alloc::vec::Vec;
+use core::ffi::c_void;
+
+use super::{Class, ClassFactory};
+use crate::interfaces::{IClassFactory, IUnknown};
+use crate::sys::{CLASS_E_CLASSNOTAVAILABLE, CLSID, E_NOINTERFACE};
+use crate::{Error, HResult, Interface};
+
+/// A registry of in-process COM classes
+///
+/// This provides the same activation functionality as [`CoCreateInstance`] and
+/// [`CoGetClassObject`] for classes implemented with `com::class!`, without going
+/// through the operating system. This means it can be used on platforms other than
+/// Windows, such as for testing activation logic.
+///
+/// ```rust
+/// # com::interfaces! {
+/// #     #[uuid("EFF8970E-C50F-45E0-9284-291CE5A6F771")]
+/// #     pub unsafe interface IAnimal: com::interfaces::IUnknown {
+/// #         fn Eat(&self) -> com::sys::HRESULT;
+/// #     }
+/// # }
+/// # com::class! {
+/// #     pub class BritishShortHairCat: IAnimal {}
+/// #     impl IAnimal for BritishShortHairCat {
+/// #         fn Eat(&self) -> com::sys::HRESULT { com::sys::NOERROR }
+/// #     }
+/// # }
+/// const CLSID_CAT_CLASS: com::CLSID = com::guid!("C5F45CBC-4439-418C-A9F9-05AC67525E43");
+///
+/// let mut registry = com::production::ClassRegistry::new();
+/// registry.register::<BritishShortHairCat>(CLSID_CAT_CLASS);
+///
+/// let animal = registry.create_instance::<IAnimal>(&CLSID_CAT_CLASS).unwrap();
+/// unsafe { animal.Eat() };
+/// ```
+///
+/// [`CoCreateInstance`]: https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-cocreateinstance
+/// [`CoGetClassObject`]: https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-cogetclassobject
+#[derive(Default)]
+pub struct ClassRegistry {
+    classes: Vec<(CLSID, fn() -> IClassFactory)>,
+    #[cfg(windows)]
+    fall_through: bool,
+}
+
+impl ClassRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fall through to the COM runtime (`CoCreateInstance` and `CoGetClassObject`)
+    /// for classes which are not registered.
+    #[cfg(windows)]
+    pub fn fall_through_to_system(mut self) -> Self {
+        self.fall_through = true;
+        self
+    }
+
+    /// Register the class `T` with the associated class id
+    ///
+    /// This replaces any class previously registered with the same class id.
+    pub fn register<T: Class>(&mut self, class_id: CLSID)
+    where
+        T::Factory: ClassFactory,
+    {
+        self.register_factory(class_id, <T::Factory as ClassFactory>::create)
+    }
+
+    /// Register a function creating the class factory for the associated class id
+    ///
+    /// This replaces any class previously registered with the same class id.
+    pub fn register_factory(&mut self, class_id: CLSID, factory: fn() -> IClassFactory) {
+        match self.classes.iter_mut().find(|(id, _)| id == &class_id) {
+            Some(entry) => entry.1 = factory,
+            None => self.classes.push((class_id, factory)),
+        }
+    }
+
+    /// Remove the class with the associated class id from the registry
+    ///
+    /// Returns whether a class was registered with the class id.
+    pub fn unregister(&mut self, class_id: &CLSID) -> bool {
+        let len = self.classes.len();
+        self.classes.retain(|(id, _)| id != class_id);
+        len != self.classes.len()
+    }
+
+    /// Whether a class is registered with the associated class id
+    pub fn contains(&self, class_id: &CLSID) -> bool {
+        self.factory(class_id).is_some()
+    }
+
+    /// Get the class object with the associated [`CLSID`]
+    ///
+    /// This is the equivalent of `runtime::get_class_object` on Windows.
+    pub fn get_class_object<T: Interface>(&self, class_id: &CLSID) -> Result<T, Error> {
+        let factory = match self.factory(class_id) {
+            Some(factory) => factory(),
+            #[cfg(windows)]
+            None if self.fall_through => return crate::runtime::get_class_object(class_id),
+            None => return Err(HResult(CLASS_E_CLASSNOTAVAILABLE).into()),
+        };
+
+        factory
+            .query_interface::<T>()
+            .ok_or_else(|| HResult(E_NOINTERFACE).into())
+    }
+
+    /// Create an instance of the class with the associated class id
+    ///
+    /// This is the equivalent of `runtime::create_instance` on Windows.
+    pub fn create_instance<T: Interface>(&self, class_id: &CLSID) -> Result<T, Error> {
+        let factory = match self.factory(class_id) {
+            Some(factory) => factory(),
+            #[cfg(windows)]
+            None if self.fall_through => return crate::runtime::create_instance(class_id),
+            None => return Err(HResult(CLASS_E_CLASSNOTAVAILABLE).into()),
+        };
+
+        let mut instance = None;
+        let hr = HResult(unsafe {
+            factory.CreateInstance(None, &T::IID, &mut instance as *mut _ as *mut *mut c_void)
+        });
+        hr.ok()?;
+
+        Ok(instance.unwrap())
+    }
+
+    /// Create an instance of the class with the associated class id as the inner
+    /// object of an aggregate
+    ///
+    /// Returns the non-delegating `IUnknown` of the new object. This is the
+    /// equivalent of `runtime::create_aggregated_instance` on Windows.
+    ///
+    /// # Safety
+    ///
+    /// `outer` must be the controlling `IUnknown` of the aggregate and must outlive
+    /// the new object. The returned `IUnknown` must not be handed out to anyone but
+    /// the controlling object.
+    pub unsafe fn create_aggregated_instance(
+        &self,
+        class_id: &CLSID,
+        outer: &IUnknown,
+    ) -> Result<IUnknown, Error> {
+        let factory = match self.factory(class_id) {
+            Some(factory) => factory(),
+            #[cfg(windows)]
+            None if self.fall_through => {
+                return crate::runtime::create_aggregated_instance(class_id, outer)
+            }
+            None => return Err(HResult(CLASS_E_CLASSNOTAVAILABLE).into()),
+        };
+
+        let mut instance = None;
+        let hr = HResult(factory.CreateInstance(
+            Some(outer.clone()),
+            &IUnknown::IID,
+            &mut instance as *mut _ as *mut *mut c_void,
+        ));
+        hr.ok()?;
+
+        Ok(instance.unwrap())
+    }
+
+    fn factory(&self, class_id: &CLSID) -> Option<fn() -> IClassFactory> {
+        self.classes
+            .iter()
+            .find(|(id, _)| id == class_id)
+            .map(|(_, factory)| *factory)
+    }
+}
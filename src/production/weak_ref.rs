@@ -0,0 +1,172 @@
+use alloc::boxed::Box;
+use core::ffi::c_void;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
+
+use crate::interfaces::iunknown::IID_IUNKNOWN;
+use crate::interfaces::{IUnknown, IWeakReference};
+use crate::refcounting::{self, RefCount};
+use crate::sys::{E_NOINTERFACE, E_POINTER, GUID, HRESULT, NOERROR, S_OK};
+use crate::{AbiTransferable, Interface};
+
+// See https://github.com/rust-lang/rust/issues/86935
+type IUnknownVTable = <IUnknown as Interface>::VTable;
+type IWeakReferenceVTable = <IWeakReference as Interface>::VTable;
+
+/// The reference count of a class declared with `#[weak_ref]`
+///
+/// The strong reference count of such classes lives in a separately allocated
+/// control block, which also implements `IWeakReference`. The control block
+/// outlives the object for as long as weak references to it exist.
+///
+/// This is used by code generated by `com::class!` and should not be used directly.
+#[doc(hidden)]
+pub struct WeakRefCount {
+    block: NonNull<WeakRefBlock>,
+}
+
+#[repr(C)]
+struct WeakRefBlock {
+    vtable: &'static IWeakReferenceVTable,
+    strong: AtomicU32,
+    /// The number of `IWeakReference` pointers, plus one held by the object itself
+    weak: AtomicU32,
+    /// The `IUnknown` of the object, which is only valid while `strong` is not zero
+    object: AtomicPtr<c_void>,
+}
+
+static WEAK_REFERENCE_VTABLE: IWeakReferenceVTable = IWeakReferenceVTable {
+    parent: IUnknownVTable {
+        QueryInterface: query_interface,
+        AddRef: add_ref,
+        Release: release,
+    },
+    Resolve: resolve,
+};
+
+impl WeakRefCount {
+    /// Allocate a new control block with a strong reference count of one
+    pub fn new() -> Self {
+        let block = Box::new(WeakRefBlock {
+            vtable: &WEAK_REFERENCE_VTABLE,
+            strong: AtomicU32::new(1),
+            weak: AtomicU32::new(1),
+            object: AtomicPtr::new(core::ptr::null_mut()),
+        });
+        Self {
+            block: unsafe { NonNull::new_unchecked(Box::into_raw(block)) },
+        }
+    }
+
+    /// Set the `IUnknown` pointer weak references resolve to
+    ///
+    /// # Safety
+    ///
+    /// `object` must be an `IUnknown` pointer to the object owning this reference
+    /// count, and the object must not move for the rest of its lifetime.
+    pub unsafe fn set_object(&self, object: *const c_void) {
+        self.block()
+            .object
+            .store(object as *mut c_void, Ordering::SeqCst);
+    }
+
+    /// Create a new weak reference to the object
+    pub fn weak_reference(&self) -> IWeakReference {
+        refcounting::addref(&self.block().weak);
+        IWeakReference::from_abi(self.block.cast())
+    }
+
+    fn block(&self) -> &WeakRefBlock {
+        unsafe { self.block.as_ref() }
+    }
+}
+
+impl Default for WeakRefCount {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl RefCount for WeakRefCount {
+    #[inline(always)]
+    fn increment(&self) -> u32 {
+        self.block().strong.increment()
+    }
+
+    #[inline(always)]
+    fn decrement(&self) -> u32 {
+        self.block().strong.decrement()
+    }
+
+    #[inline(always)]
+    fn count(&self) -> u32 {
+        self.block().strong.count()
+    }
+}
+
+impl Drop for WeakRefCount {
+    fn drop(&mut self) {
+        unsafe {
+            release(self.block.cast());
+        }
+    }
+}
+
+/// [`WeakRefCount`] is [`Send`] and [`Sync`] because the control block is only
+/// modified through atomic operations.
+unsafe impl Send for WeakRefCount {}
+unsafe impl Sync for WeakRefCount {}
+
+unsafe fn block<'a, T>(this: NonNull<NonNull<T>>) -> &'a WeakRefBlock {
+    &*(this.as_ptr() as *const WeakRefBlock)
+}
+
+unsafe extern "system" fn query_interface(
+    this: NonNull<NonNull<IUnknownVTable>>,
+    riid: *const GUID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    let riid = &*riid;
+    if riid == &IID_IUNKNOWN || riid == &IWeakReference::IID {
+        *ppv = this.as_ptr() as *mut c_void;
+        add_ref(this);
+        NOERROR
+    } else {
+        *ppv = core::ptr::null_mut();
+        E_NOINTERFACE
+    }
+}
+
+unsafe extern "system" fn add_ref(this: NonNull<NonNull<IUnknownVTable>>) -> u32 {
+    refcounting::addref(&block(this).weak)
+}
+
+unsafe extern "system" fn release(this: NonNull<NonNull<IUnknownVTable>>) -> u32 {
+    let new_ref_count = refcounting::release(&block(this).weak);
+    if new_ref_count == 0 {
+        drop(Box::from_raw(this.as_ptr() as *mut WeakRefBlock));
+    }
+    new_ref_count
+}
+
+unsafe extern "system" fn resolve(
+    this: NonNull<NonNull<IWeakReferenceVTable>>,
+    riid: *const GUID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    if ppv.is_null() {
+        return E_POINTER;
+    }
+    *ppv = core::ptr::null_mut();
+
+    let block = block(this);
+    if refcounting::try_addref(&block.strong).is_none() {
+        // The object has already been destroyed
+        return S_OK;
+    }
+    // The strong reference we just took keeps the object alive until `object`
+    // is dropped.
+    let object = block.object.load(Ordering::SeqCst);
+    let object = IUnknown::from_abi(NonNull::new_unchecked(object as *mut _));
+    object.QueryInterface(riid, ppv)
+}
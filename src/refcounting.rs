@@ -4,11 +4,82 @@
 //! directly by application code. It is used by code generated by the
 //! `com::class!` macro.
 
+use core::cell::Cell;
 use core::sync::atomic::{AtomicU32, Ordering};
 
+use crate::sys::{HRESULT, IID};
+
 // We check for u32::MAX / 2, instead of u32::MAX, to guard against AddRef attacks.
 const REFCOUNT_OVERFLOW_MAX: u32 = u32::MAX / 2;
 
+/// A reference count of a COM server
+///
+/// # Safety
+///
+/// `increment` and `decrement` must return the value of the count before the
+/// operation, and must wrap around on overflow.
+pub unsafe trait RefCount {
+    /// Increment the count and return the previous count
+    fn increment(&self) -> u32;
+    /// Decrement the count and return the previous count
+    fn decrement(&self) -> u32;
+    /// The current count
+    fn count(&self) -> u32;
+}
+
+unsafe impl RefCount for AtomicU32 {
+    #[inline(always)]
+    fn increment(&self) -> u32 {
+        self.fetch_add(1, Ordering::SeqCst)
+    }
+
+    #[inline(always)]
+    fn decrement(&self) -> u32 {
+        self.fetch_sub(1, Ordering::SeqCst)
+    }
+
+    #[inline(always)]
+    fn count(&self) -> u32 {
+        self.load(Ordering::SeqCst)
+    }
+}
+
+/// A reference count for classes that are only used from a single thread
+///
+/// This is used by classes declared with `#[refcount(local)]`. Since the count
+/// is not atomic, it is not [`Sync`], which makes the `ClassAllocation` of such
+/// classes neither [`Send`] nor [`Sync`].
+#[derive(Debug)]
+pub struct LocalRefCount(Cell<u32>);
+
+impl LocalRefCount {
+    /// Create a reference count with the given initial count
+    pub const fn new(count: u32) -> Self {
+        Self(Cell::new(count))
+    }
+}
+
+unsafe impl RefCount for LocalRefCount {
+    #[inline(always)]
+    fn increment(&self) -> u32 {
+        let old_refcount = self.0.get();
+        self.0.set(old_refcount.wrapping_add(1));
+        old_refcount
+    }
+
+    #[inline(always)]
+    fn decrement(&self) -> u32 {
+        let old_refcount = self.0.get();
+        self.0.set(old_refcount.wrapping_sub(1));
+        old_refcount
+    }
+
+    #[inline(always)]
+    fn count(&self) -> u32 {
+        self.0.get()
+    }
+}
+
 /// Implements `IUnknown::AddRef` for COM servers.
 ///
 /// Increments the reference count and returns the new reference count.
@@ -19,17 +90,45 @@ const REFCOUNT_OVERFLOW_MAX: u32 = u32::MAX / 2;
 /// still outstanding.
 #[doc(hidden)]
 #[inline(always)]
-pub fn addref(refcount: &AtomicU32) -> u32 {
-    let old_refcount = refcount.fetch_add(1, Ordering::SeqCst);
+pub fn addref<R: RefCount + ?Sized>(refcount: &R) -> u32 {
+    let old_refcount = refcount.increment();
     if old_refcount >= REFCOUNT_OVERFLOW_MAX {
         // Undo the increment that we just performed.
-        let _ = refcount.fetch_sub(1, Ordering::SeqCst);
+        let _ = refcount.decrement();
         addref_overflowed();
     } else {
         old_refcount + 1
     }
 }
 
+/// Increments the reference count unless it has already dropped to zero.
+///
+/// This is used to resolve weak references: once the last strong reference
+/// has been released, the object is being destroyed and must not be revived.
+/// Returns the new reference count, or `None` if the count was zero.
+#[doc(hidden)]
+#[inline]
+pub fn try_addref(refcount: &AtomicU32) -> Option<u32> {
+    let mut old_refcount = refcount.load(Ordering::SeqCst);
+    loop {
+        if old_refcount == 0 {
+            return None;
+        }
+        if old_refcount >= REFCOUNT_OVERFLOW_MAX {
+            addref_overflowed();
+        }
+        match refcount.compare_exchange_weak(
+            old_refcount,
+            old_refcount + 1,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        ) {
+            Ok(_) => return Some(old_refcount + 1),
+            Err(current) => old_refcount = current,
+        }
+    }
+}
+
 /// Implements `IUnknown::Release` for COM servers.
 ///
 /// Decrements the reference count and returns the new reference count. The
@@ -37,16 +136,108 @@ pub fn addref(refcount: &AtomicU32) -> u32 {
 /// destroy the COM server.
 #[doc(hidden)]
 #[inline(always)]
-pub fn release(refcount: &AtomicU32) -> u32 {
-    let old_refcount = refcount.fetch_sub(1, Ordering::SeqCst);
+pub fn release<R: RefCount + ?Sized>(refcount: &R) -> u32 {
+    decrement(refcount, || release_underflowed())
+}
+
+/// Implements `IUnknown::Release` for the interface chain `chain` of a COM server.
+///
+/// This is [`release`], followed by a call to [`trace`]. With the
+/// `refcount-tracing` feature, the panic of an underflow includes the recorded
+/// history of `object`.
+#[doc(hidden)]
+#[inline(always)]
+pub fn release_object<C, R: RefCount + ?Sized>(
+    object: *const C,
+    refcount: &R,
+    chain: Option<usize>,
+) -> u32 {
+    let new_refcount = decrement(refcount, || {
+        #[cfg(feature = "refcount-tracing")]
+        crate::debug::release_underflowed(core::any::type_name::<C>(), object as usize);
+        #[cfg(not(feature = "refcount-tracing"))]
+        release_underflowed();
+    });
+    trace(object, chain, Operation::Release, new_refcount);
+    new_refcount
+}
+
+/// Decrements the reference count and returns the new reference count, calling
+/// `underflowed`, which must panic, if the count was already zero
+#[inline(always)]
+fn decrement<R: RefCount + ?Sized>(refcount: &R, underflowed: impl FnOnce()) -> u32 {
+    let old_refcount = refcount.decrement();
     if old_refcount == 0 {
         // The reference count was invalid.
         // In safe Rust, this should be impossible.
         // Of course, other clients outside of safe Rust can use COM.
-        release_underflowed();
-    } else {
-        old_refcount - 1
+        underflowed();
+        unreachable!("the underflow handler returned");
+    }
+    old_refcount - 1
+}
+
+/// A call of an `IUnknown` method of a COM server
+///
+/// See `com::debug::set_refcount_hook`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    /// `IUnknown::AddRef`
+    AddRef,
+    /// `IUnknown::Release`
+    Release,
+    /// `IUnknown::QueryInterface` for `iid`, which returned `hr`
+    QueryInterface {
+        /// The requested interface
+        iid: IID,
+        /// The result of the call
+        hr: HRESULT,
+    },
+}
+
+/// Reports a call of `AddRef` or `Release` on the interface chain `chain` of
+/// `object`, which returned `count`
+///
+/// This does nothing without the `refcount-tracing` feature.
+#[doc(hidden)]
+#[inline(always)]
+pub fn trace<C>(object: *const C, chain: Option<usize>, operation: Operation, count: u32) {
+    #[cfg(feature = "refcount-tracing")]
+    crate::debug::trace(crate::debug::RefCountEvent {
+        class: core::any::type_name::<C>(),
+        object: object as usize,
+        chain,
+        operation,
+        count,
+    });
+    #[cfg(not(feature = "refcount-tracing"))]
+    let _ = (object, chain, operation, count);
+}
+
+/// Reports a call of `QueryInterface` for `riid` on the interface chain `chain` of
+/// `object`, which returned `hr`
+///
+/// This does nothing without the `refcount-tracing` feature.
+///
+/// # Safety
+///
+/// `riid` must be a valid pointer.
+#[doc(hidden)]
+#[inline(always)]
+pub unsafe fn trace_query_interface<C, R: RefCount + ?Sized>(
+    object: *const C,
+    refcount: &R,
+    chain: Option<usize>,
+    riid: *const IID,
+    hr: HRESULT,
+) {
+    #[cfg(feature = "refcount-tracing")]
+    if crate::debug::is_tracing() {
+        let operation = Operation::QueryInterface { iid: *riid, hr };
+        trace(object, chain, operation, refcount.count());
     }
+    #[cfg(not(feature = "refcount-tracing"))]
+    let _ = (object, refcount, chain, riid, hr);
 }
 
 /// Panics, because an `IUnknown::AddRef()` call has overflowed.
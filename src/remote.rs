@@ -0,0 +1,230 @@
+//! Calls to COM objects hosted in other processes
+//!
+//! A [`LocalServer`] hosts the classes of a [`ClassRegistry`](crate::production::ClassRegistry)
+//! for clients in other processes, which reach them through a [`Connection`]. Calls
+//! are forwarded over a [`Transport`], such as a Unix domain socket or a named pipe,
+//! by proxies on the client side and stubs on the server side.
+//!
+//! The proxies and stubs of an interface are generated by `interfaces!` for the
+//! interfaces marked `#[remote]`, which implement the [`Remote`] trait. All the
+//! methods of such interfaces return an `HRESULT`, and their parameters are passed
+//! with [`Marshal`]:
+//!
+//! * parameters passed by value are `[in]` parameters,
+//! * `*const T` parameters are `[in]` parameters which may be null,
+//! * `*mut T` parameters, including the `#[retval]` parameter, are `[out]` parameters
+//!   which are only written when the call succeeds,
+//! * `#[size_is(len)]` pointers are arrays of `len` elements, where `len` is another
+//!   parameter passed by value.
+//!
+//! Interface pointers are passed as references to the object. The receiving side gets
+//! a proxy which forwards calls back to the object, so objects can be passed in both
+//! directions, for example to register callbacks. Calls from the other side are
+//! processed while waiting for the result of a call, like in a single-threaded
+//! apartment, or by [`Connection::serve`].
+//!
+//! Both sides must know the interfaces used through the connection. They are
+//! registered when an interface is passed as a parameter or created with
+//! [`Connection::create_instance`]. Other interfaces, such as the interfaces a
+//! server creates instances for or the interfaces a client queries for, must be
+//! registered with [`register`].
+use core::ffi::c_void;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::interfaces::IUnknown;
+use crate::marshal::{Decoder, Encoder, Marshal};
+use crate::sys::{E_NOINTERFACE, HRESULT, IID, RPC_E_INVALIDMETHOD};
+use crate::{AbiTransferable, Error, HResult, Interface};
+
+#[cfg(not(windows))]
+pub(crate) mod apartment;
+mod connection;
+#[doc(hidden)]
+pub mod proxy;
+mod server;
+mod transport;
+
+#[doc(inline)]
+pub use connection::Connection;
+#[doc(inline)]
+pub use server::LocalServer;
+#[doc(inline)]
+#[cfg(windows)]
+pub use transport::{connect_named_pipe, NamedPipeListener};
+#[doc(inline)]
+pub use transport::{Listener, Transport};
+
+/// An interface which can be called from other processes
+///
+/// This is implemented by `interfaces!` for the interfaces marked `#[remote]`.
+///
+/// # Safety
+///
+/// [`Remote::PROXY_VTABLE`] must be a valid vtable for the interface, whose methods
+/// forward calls to the object behind a proxy created by this module, and
+/// [`Remote::invoke`] must call the methods of the interface with the parameters
+/// written by the proxy.
+pub unsafe trait Remote: Interface {
+    /// The vtable of the proxies for the interface
+    #[doc(hidden)]
+    const PROXY_VTABLE: Self::VTable;
+
+    /// A static copy of [`Remote::PROXY_VTABLE`]
+    #[doc(hidden)]
+    fn proxy_vtable() -> &'static Self::VTable;
+
+    /// Register the interface and its parents for use by connections
+    fn register();
+
+    /// Call the method at `index` in the interface, reading its parameters from `args`
+    /// and writing the `[out]` parameters to `results` if the call succeeds
+    ///
+    /// # Safety
+    ///
+    /// This calls a method of the object through its vtable.
+    #[doc(hidden)]
+    unsafe fn invoke(
+        &self,
+        index: u32,
+        args: &mut Decoder<'_>,
+        results: &mut Encoder<'_>,
+    ) -> Result<HRESULT, Error>;
+}
+
+/// Register the interface `I` and its parents for use by connections
+///
+/// Interfaces are registered automatically when they are passed as parameters or
+/// created, but need to be registered before querying for them.
+pub fn register<I: Remote>() {
+    I::register()
+}
+
+/// The proxy and stub of a registered interface
+#[derive(Clone, Copy)]
+pub(crate) struct RemoteInterface {
+    pub iid: IID,
+    pub proxy_vtable: *const c_void,
+    pub invoke:
+        unsafe fn(&IUnknown, u32, &mut Decoder<'_>, &mut Encoder<'_>) -> Result<HRESULT, Error>,
+}
+
+/// A node of the list of registered interfaces
+struct Registration {
+    interface: RemoteInterface,
+    next: *mut Registration,
+}
+
+/// The registered interfaces, which are never removed
+static INTERFACES: AtomicPtr<Registration> = AtomicPtr::new(ptr::null_mut());
+
+/// Register the proxy and stub of `I`, without its parents
+///
+/// This is called by the [`Remote::register`] implementations generated by `interfaces!`.
+#[doc(hidden)]
+pub fn register_interface<I: Remote>() {
+    if find(&I::IID).is_some() {
+        return;
+    }
+    let registration = alloc::boxed::Box::into_raw(alloc::boxed::Box::new(Registration {
+        interface: RemoteInterface {
+            iid: I::IID,
+            proxy_vtable: I::proxy_vtable() as *const I::VTable as *const c_void,
+            invoke: invoke::<I>,
+        },
+        next: ptr::null_mut(),
+    }));
+    let mut head = INTERFACES.load(Ordering::SeqCst);
+    loop {
+        unsafe { (*registration).next = head };
+        match INTERFACES.compare_exchange_weak(
+            head,
+            registration,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        ) {
+            Ok(_) => return,
+            Err(current) => head = current,
+        }
+    }
+}
+
+/// Find the registered interface with the given IID
+pub(crate) fn find(iid: &IID) -> Option<RemoteInterface> {
+    let mut node = INTERFACES.load(Ordering::SeqCst);
+    while let Some(registration) = unsafe { node.as_ref() } {
+        if &registration.interface.iid == iid {
+            return Some(registration.interface);
+        }
+        node = registration.next;
+    }
+    None
+}
+
+/// Call [`Remote::invoke`] through an `IUnknown` pointing to the interface `I`
+unsafe fn invoke<I: Remote>(
+    this: &IUnknown,
+    index: u32,
+    args: &mut Decoder<'_>,
+    results: &mut Encoder<'_>,
+) -> Result<HRESULT, Error> {
+    // All interface pointers have the same representation
+    let this = &*(this as *const IUnknown as *const I);
+    this.invoke(index, args, results)
+}
+
+/// The methods of `IUnknown` are implemented by the proxies themselves, and are
+/// never invoked through the connection.
+unsafe impl Remote for IUnknown {
+    const PROXY_VTABLE: Self::VTable = proxy::IUNKNOWN_VTABLE;
+
+    fn proxy_vtable() -> &'static Self::VTable {
+        &proxy::IUNKNOWN_VTABLE
+    }
+
+    fn register() {}
+
+    unsafe fn invoke(
+        &self,
+        _index: u32,
+        _args: &mut Decoder<'_>,
+        _results: &mut Encoder<'_>,
+    ) -> Result<HRESULT, Error> {
+        Err(HResult(RPC_E_INVALIDMETHOD).into())
+    }
+}
+
+/// Interface pointers are written as a reference to the object, see [`ObjectTable`](crate::marshal::ObjectTable)
+impl<I: Remote> Marshal for I {
+    fn marshal(&self, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        I::register();
+        encoder
+            .objects()?
+            .export(self.as_iunknown(), &I::IID, encoder)
+    }
+
+    fn unmarshal(decoder: &mut Decoder<'_>) -> Result<Self, Error> {
+        I::register();
+        let object = decoder.objects()?.import(&I::IID, decoder)?;
+        // The object table returns a pointer to the interface `I`
+        Ok(I::from_abi(object.into_abi().cast()))
+    }
+}
+
+/// Query `object` for the interface `iid`
+pub(crate) fn query(object: &IUnknown, iid: &IID) -> Result<IUnknown, Error> {
+    let mut interface = None::<IUnknown>;
+    let hr = unsafe {
+        object.QueryInterface(
+            iid,
+            &mut interface as *mut Option<IUnknown> as *mut *mut c_void,
+        )
+    };
+    HResult(hr).ok()?;
+    interface.ok_or_else(|| HResult(E_NOINTERFACE).into())
+}
+
+/// The address identifying an object
+pub(crate) fn address(identity: &IUnknown) -> usize {
+    identity.get_abi().as_ptr() as usize
+}
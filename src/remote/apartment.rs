@@ -0,0 +1,361 @@
+//! Calls between the apartments of the process, used by [`Agile`](crate::Agile) and
+//! the global interface table on platforms without a COM runtime
+//!
+//! Objects passed to another apartment are recorded in a table shared by all the
+//! threads. The threads of other single-threaded apartments get proxies, which
+//! marshal their calls like the proxies of a connection and run them on the
+//! thread of the object through its [`Dispatcher`]. Objects of the multithreaded
+//! apartment are expected to be thread safe, and are used directly by all threads.
+use core::mem::ManuallyDrop;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicPtr, Ordering};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use super::proxy::{self, Channel, ProxyManager};
+use super::{address, query};
+use crate::interfaces::IUnknown;
+use crate::marshal::{invalid_data, Decoder, Encoder, Marshal, ObjectTable};
+use crate::runtime::{current_apartment, ApartmentType, Dispatcher};
+use crate::sys::{
+    CO_E_NOTINITIALIZED, E_INVALIDARG, E_NOINTERFACE, FAILED, HRESULT, IID, RPC_E_INVALIDMETHOD,
+};
+use crate::{error_info, Error, HResult, Interface};
+
+/// An interface pointer which is only used on the threads of its apartment
+struct Owned(IUnknown);
+
+// The table only clones, queries and releases the objects on their threads
+unsafe impl Send for Owned {}
+
+/// The apartment an object lives in
+#[derive(Clone)]
+enum Home {
+    /// A single-threaded apartment, whose message loop runs the calls
+    Sta(Dispatcher),
+    /// The multithreaded apartment, whose objects are thread safe
+    Mta,
+}
+
+impl Home {
+    /// The apartment of the current thread
+    ///
+    /// The objects of single-threaded apartments can only be called from other
+    /// threads if the apartment has a message loop.
+    fn current() -> Result<Self, Error> {
+        match current_apartment() {
+            Some(ApartmentType::SingleThreaded) => Dispatcher::current()
+                .map(Home::Sta)
+                .ok_or_else(|| HResult(CO_E_NOTINITIALIZED).into()),
+            Some(_) => Ok(Home::Mta),
+            None => Err(HResult(CO_E_NOTINITIALIZED).into()),
+        }
+    }
+}
+
+/// An object passed to another apartment
+struct Entry {
+    identity: Owned,
+    home: Home,
+    /// The references held by cookies, proxies and messages
+    refs: u32,
+    /// The interfaces of the object used by other apartments
+    interfaces: Vec<(IID, Owned)>,
+}
+
+#[derive(Default)]
+struct Table {
+    next_id: u64,
+    entries: HashMap<u64, Entry>,
+    /// The ids of the objects, by the address of their `IUnknown`
+    ids: HashMap<usize, u64>,
+    next_cookie: u64,
+    /// The object and the interface registered with each cookie
+    cookies: HashMap<u64, (u64, IID)>,
+}
+
+/// The table of the process, which is created on first use and never freed
+static TABLE: AtomicPtr<Mutex<Table>> = AtomicPtr::new(ptr::null_mut());
+
+/// Lock the table
+///
+/// The objects are not called while the table is locked, except to add references,
+/// since they may pass objects to other apartments themselves.
+fn table() -> MutexGuard<'static, Table> {
+    let mut table = TABLE.load(Ordering::SeqCst);
+    if table.is_null() {
+        let new = Box::into_raw(Box::new(Mutex::new(Table::default())));
+        table = match TABLE.compare_exchange(
+            ptr::null_mut(),
+            new,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        ) {
+            Ok(_) => new,
+            Err(current) => {
+                drop(unsafe { Box::from_raw(new) });
+                current
+            }
+        };
+    }
+    unsafe { &*table }.lock().unwrap()
+}
+
+std::thread_local! {
+    /// The proxy managers of the objects of other apartments used by the current
+    /// thread, which remove themselves when they are released
+    static IMPORTS: RefCell<HashMap<u64, NonNull<ProxyManager>>> = RefCell::new(HashMap::new());
+}
+
+/// Add a reference to an object of the current apartment, or to the object behind
+/// a proxy for another apartment, and return its id
+fn export(object: &IUnknown, iid: &IID) -> Result<u64, Error> {
+    if let Some(manager) = proxy::proxy_manager(object) {
+        if let Channel::Apartment(_) = manager.channel() {
+            let mut table = table();
+            let entry = table
+                .entries
+                .get_mut(&manager.object())
+                .ok_or_else(invalid_data)?;
+            entry.refs += 1;
+            return Ok(manager.object());
+        }
+    }
+    let home = Home::current()?;
+    let identity = query(object, &IUnknown::IID)?;
+    let mut table = table();
+    let table = &mut *table;
+    let id = match table.ids.get(&address(&identity)) {
+        Some(id) => *id,
+        None => {
+            let id = table.next_id;
+            table.next_id += 1;
+            table.ids.insert(address(&identity), id);
+            table.entries.insert(
+                id,
+                Entry {
+                    identity: Owned(identity.clone()),
+                    home,
+                    refs: 0,
+                    interfaces: Vec::new(),
+                },
+            );
+            id
+        }
+    };
+    let entry = table.entries.get_mut(&id).unwrap();
+    entry.refs += 1;
+    if !entry.interfaces.iter().any(|(i, _)| i == iid) {
+        entry.interfaces.push((*iid, Owned(object.clone())));
+    }
+    Ok(id)
+}
+
+/// Get an interface pointer for `iid` to an object, taking over one of its
+/// references
+///
+/// The object itself is returned on the threads of its apartment, and a proxy
+/// elsewhere.
+fn import(object: u64, iid: &IID) -> Result<IUnknown, Error> {
+    let home = match table().entries.get(&object) {
+        Some(entry) => entry.home.clone(),
+        None => return Err(invalid_data()),
+    };
+    let dispatcher = match home {
+        Home::Sta(dispatcher) if !dispatcher.is_current() => dispatcher,
+        _ => {
+            let interface = interface(object, iid);
+            release(object, 1);
+            return interface;
+        }
+    };
+    let remote = super::find(iid);
+    if remote.is_none() && iid != &IUnknown::IID {
+        release(object, 1);
+        return Err(HResult(E_NOINTERFACE).into());
+    }
+    let manager = IMPORTS.with(|imports| {
+        *imports
+            .borrow_mut()
+            .entry(object)
+            .or_insert_with(|| ProxyManager::new(Channel::Apartment(dispatcher), object))
+    });
+    let manager = unsafe { manager.as_ref() };
+    // Take the reference before any proxy is created, so that it is released with
+    // the proxy on errors
+    manager.add_remote_ref();
+    match (manager.find(iid), remote) {
+        (Some(proxy), _) => Ok(proxy),
+        (None, Some(remote)) => Ok(manager.insert(iid, remote.proxy_vtable)),
+        (None, None) => unreachable!("the proxy for IUnknown always exists"),
+    }
+}
+
+/// Get an interface of an object, on the threads of its apartment
+fn interface(object: u64, iid: &IID) -> Result<IUnknown, Error> {
+    let identity = {
+        let table = table();
+        let entry = table.entries.get(&object).ok_or_else(invalid_data)?;
+        if let Some((_, interface)) = entry.interfaces.iter().find(|(i, _)| i == iid) {
+            return Ok(interface.0.clone());
+        }
+        entry.identity.0.clone()
+    };
+    let interface = query(&identity, iid)?;
+    if let Some(entry) = table().entries.get_mut(&object) {
+        if !entry.interfaces.iter().any(|(i, _)| i == iid) {
+            entry.interfaces.push((*iid, Owned(interface.clone())));
+        }
+    }
+    Ok(interface)
+}
+
+/// Release references to an object, which is released on the thread of its
+/// apartment once the last reference is released
+fn release(object: u64, refs: u32) {
+    let entry = {
+        let mut table = table();
+        let entry = match table.entries.get_mut(&object) {
+            Some(entry) => entry,
+            None => return,
+        };
+        entry.refs = entry.refs.saturating_sub(refs);
+        if entry.refs > 0 {
+            return;
+        }
+        let entry = table.entries.remove(&object).unwrap();
+        table.ids.remove(&address(&entry.identity.0));
+        entry
+    };
+    match entry.home.clone() {
+        Home::Sta(dispatcher) if !dispatcher.is_current() => {
+            // The object is leaked rather than released on another thread if the
+            // message loop of its apartment is gone
+            let entry = ManuallyDrop::new(entry);
+            let _ = dispatcher.post(move || drop(ManuallyDrop::into_inner(entry)));
+        }
+        _ => drop(entry),
+    }
+}
+
+/// The object table of the messages between apartments, which passes the ids of
+/// the objects
+struct Objects;
+
+impl ObjectTable for Objects {
+    fn export(&self, object: &IUnknown, iid: &IID, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        export(object, iid)?.marshal(encoder)
+    }
+
+    fn import(&self, iid: &IID, decoder: &mut Decoder<'_>) -> Result<IUnknown, Error> {
+        import(u64::unmarshal(decoder)?, iid)
+    }
+}
+
+/// Call a method of an object of another apartment, on the thread of the apartment
+pub(crate) fn call<A, R>(
+    dispatcher: &Dispatcher,
+    object: u64,
+    iid: &IID,
+    method: u32,
+    args: A,
+    results: R,
+) -> Result<HRESULT, Error>
+where
+    A: FnOnce(&mut Encoder<'_>) -> Result<(), Error>,
+    R: FnOnce(&mut Decoder<'_>) -> Result<(), Error>,
+{
+    let mut request = Encoder::with_objects(&Objects);
+    args(&mut request)?;
+    let request = request.into_bytes();
+    let iid = *iid;
+    let (hr, reply, info) = dispatcher.invoke(move || {
+        let interface = interface(object, &iid)?;
+        let remote = super::find(&iid).ok_or_else(|| Error::from(HResult(RPC_E_INVALIDMETHOD)))?;
+        let mut args = Decoder::with_objects(&request, &Objects);
+        let mut results = Encoder::with_objects(&Objects);
+        let hr = unsafe { (remote.invoke)(&interface, method, &mut args, &mut results) }?;
+        // The error information is moved to the thread of the caller
+        let info = if FAILED(hr) {
+            error_info::take_info(&interface, &iid)
+        } else {
+            None
+        };
+        Ok::<_, Error>((hr, results.into_bytes(), info))
+    })??;
+    if FAILED(hr) {
+        error_info::set(info.as_ref());
+    } else {
+        results(&mut Decoder::with_objects(&reply, &Objects))?;
+    }
+    Ok(hr)
+}
+
+/// Query an object of another apartment for an interface
+pub(crate) fn query_interface(
+    dispatcher: &Dispatcher,
+    manager: &ProxyManager,
+    iid: &IID,
+) -> Result<IUnknown, Error> {
+    if let Some(interface) = manager.find(iid) {
+        return Ok(interface);
+    }
+    let remote = super::find(iid).ok_or_else(|| Error::from(HResult(E_NOINTERFACE)))?;
+    let (object, queried) = (manager.object(), *iid);
+    dispatcher.invoke(move || interface(object, &queried).map(drop))??;
+    Ok(manager.insert(iid, remote.proxy_vtable))
+}
+
+/// Release the references to an object of another apartment held by its proxies
+pub(crate) fn release_proxy(object: u64, refs: u32) {
+    // The proxies may be released while the thread exits
+    let _ = IMPORTS.try_with(|imports| imports.borrow_mut().remove(&object));
+    release(object, refs);
+}
+
+/// Register an interface pointer for use by other apartments
+pub(crate) fn register(object: &IUnknown, iid: &IID) -> Result<u64, Error> {
+    let id = export(object, iid)?;
+    let mut table = table();
+    let cookie = table.next_cookie;
+    table.next_cookie += 1;
+    table.cookies.insert(cookie, (id, *iid));
+    Ok(cookie)
+}
+
+/// Get the interface pointer registered with `cookie` for the current apartment
+pub(crate) fn get(cookie: u64, iid: &IID) -> Result<IUnknown, Error> {
+    if current_apartment().is_none() {
+        return Err(HResult(CO_E_NOTINITIALIZED).into());
+    }
+    let object = {
+        let mut table = table();
+        let (object, registered) = *table
+            .cookies
+            .get(&cookie)
+            .ok_or_else(|| Error::from(HResult(E_INVALIDARG)))?;
+        if &registered != iid {
+            return Err(HResult(E_NOINTERFACE).into());
+        }
+        table
+            .entries
+            .get_mut(&object)
+            .ok_or_else(invalid_data)?
+            .refs += 1;
+        object
+    };
+    import(object, iid)
+}
+
+/// Revoke a cookie returned by [`register`]
+pub(crate) fn revoke(cookie: u64) -> Result<(), Error> {
+    let object = table().cookies.remove(&cookie);
+    match object {
+        Some((object, _)) => {
+            release(object, 1);
+            Ok(())
+        }
+        None => Err(HResult(E_INVALIDARG).into()),
+    }
+}
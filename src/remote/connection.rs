@@ -0,0 +1,564 @@
+use core::cell::{Cell, RefCell};
+use core::ptr::NonNull;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use super::proxy::{self, Channel, ProxyManager};
+use super::transport::{read_message, write_message, Transport};
+use super::{address, query, Remote};
+use crate::interfaces::IUnknown;
+use crate::marshal::{self, invalid_data, Decoder, Encoder, Marshal, ObjectTable};
+use crate::production::ClassRegistry;
+use crate::sys::{
+    CLASS_E_CLASSNOTAVAILABLE, CLSID, E_NOINTERFACE, FAILED, HRESULT, IID, NOERROR,
+    RPC_E_DISCONNECTED, RPC_E_INVALIDMETHOD, RPC_E_VERSION_MISMATCH,
+};
+use crate::{error_info, Error, ErrorInfo, HResult, Interface};
+
+/// The first message sent by each side: version of the format `u32`
+const HELLO: u8 = 0;
+/// Call a method: object `u64`, interface `IID`, method `u32`, parameters
+const CALL: u8 = 1;
+/// Query an object for an interface: object `u64`, interface `IID`
+const QUERY: u8 = 2;
+/// Create an instance of a class: class `CLSID`, interface `IID`
+const CREATE: u8 = 3;
+/// Release references to an object, without a reply: object `u64`, references `u32`
+const RELEASE: u8 = 4;
+/// The result of a request: `HRESULT`, results, or the error information
+/// `Option<ErrorInfo>` of failed calls
+const REPLY: u8 = 5;
+
+/// A reference to an object owned by the side writing the reference
+const SENDER: u8 = 0;
+/// A reference to an object owned by the side reading the reference
+const RECEIVER: u8 = 1;
+
+/// A connection to another process
+///
+/// Calls through the proxies created by the connection are sent to the other side,
+/// which calls the object and sends the results back. While waiting for the results,
+/// the connection processes the calls made by the other side to the objects passed
+/// to it, such as callbacks. All calls are processed on the thread owning the
+/// connection, which is why neither the connection nor its proxies can be sent to
+/// other threads.
+///
+/// The connection is closed by [`Connection::close`], when it is dropped and all
+/// its proxies are released, or when the other side closes the connection. The
+/// objects passed to the other side are then released, and calls through the
+/// proxies fail with `RPC_E_DISCONNECTED`.
+///
+/// Both sides send the [`VERSION`](crate::marshal::VERSION) of the message format
+/// they use when the connection is created. If the versions differ, the connection
+/// is closed and the first call fails with `RPC_E_VERSION_MISMATCH`.
+///
+/// ```rust,no_run
+/// # com::interfaces! {
+/// #     #[uuid("EFF8970E-C50F-45E0-9284-291CE5A6F771")]
+/// #     #[remote]
+/// #     pub unsafe interface IAnimal: com::interfaces::IUnknown {
+/// #         fn Eat(&self) -> com::sys::HRESULT;
+/// #     }
+/// # }
+/// # #[cfg(unix)]
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// const CLSID_CAT_CLASS: com::CLSID = com::guid!("C5F45CBC-4439-418C-A9F9-05AC67525E43");
+///
+/// let stream = std::os::unix::net::UnixStream::connect("/tmp/animals")?;
+/// let connection = com::remote::Connection::new(stream);
+/// let animal = connection.create_instance::<IAnimal>(&CLSID_CAT_CLASS)?;
+/// unsafe { animal.Eat() };
+/// # Ok(())
+/// # }
+/// # #[cfg(not(unix))]
+/// # fn main() {}
+/// ```
+#[derive(Clone)]
+pub struct Connection {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    transport: RefCell<Option<Box<dyn Transport>>>,
+    classes: Option<Arc<ClassRegistry>>,
+    exports: RefCell<Exports>,
+    /// The proxy managers of the objects of the other side, which remove themselves
+    /// when they are released
+    imports: RefCell<HashMap<u64, NonNull<ProxyManager>>>,
+    /// Whether the `HELLO` of the other side has been received
+    greeted: Cell<bool>,
+    /// The number of requests from the other side being processed
+    dispatching: Cell<u32>,
+    /// The references to release once the requests being processed are answered
+    releases: RefCell<Vec<(u64, u32)>>,
+}
+
+/// The objects passed to the other side
+#[derive(Default)]
+struct Exports {
+    next_id: u64,
+    objects: HashMap<u64, Export>,
+    /// The ids of the objects, by the address of their `IUnknown`
+    ids: HashMap<usize, u64>,
+}
+
+struct Export {
+    identity: IUnknown,
+    /// The references held by the other side
+    refs: u32,
+    /// The interfaces of the object used by the other side
+    interfaces: Vec<(IID, IUnknown)>,
+}
+
+impl Export {
+    fn interface(&mut self, iid: &IID) -> Result<IUnknown, Error> {
+        if let Some((_, interface)) = self.interfaces.iter().find(|(i, _)| i == iid) {
+            return Ok(interface.clone());
+        }
+        let interface = query(&self.identity, iid)?;
+        self.interfaces.push((*iid, interface.clone()));
+        Ok(interface)
+    }
+}
+
+impl Connection {
+    /// Create a connection over the given transport, which is connected to a
+    /// [`LocalServer`](super::LocalServer) or to another connection
+    pub fn new<T: Transport + 'static>(transport: T) -> Self {
+        Self::with_classes(Box::new(transport), None)
+    }
+
+    /// Create a connection whose classes can be created by the other side
+    pub(crate) fn with_classes(
+        transport: Box<dyn Transport>,
+        classes: Option<Arc<ClassRegistry>>,
+    ) -> Self {
+        let connection = Self {
+            inner: Rc::new(Inner {
+                transport: RefCell::new(Some(transport)),
+                classes,
+                exports: RefCell::new(Exports::default()),
+                imports: RefCell::new(HashMap::new()),
+                greeted: Cell::new(false),
+                dispatching: Cell::new(0),
+                releases: RefCell::new(Vec::new()),
+            }),
+        };
+        // Both sides send their version before anything else. Failing to send it
+        // closes the connection, which is reported by the first call.
+        let mut hello = Encoder::new();
+        let _ = HELLO.marshal(&mut hello);
+        let _ = marshal::VERSION.marshal(&mut hello);
+        let _ = connection.send(hello.as_bytes());
+        connection
+    }
+
+    /// Create an instance of the class with the associated class id on the other
+    /// side of the connection
+    ///
+    /// This is the equivalent of `runtime::create_local_instance` on Windows.
+    pub fn create_instance<I: Remote>(&self, class_id: &CLSID) -> Result<I, Error> {
+        I::register();
+        let mut request = Encoder::with_objects(self);
+        CREATE.marshal(&mut request)?;
+        class_id.marshal(&mut request)?;
+        I::IID.marshal(&mut request)?;
+        let reply = self.request(&request.into_bytes())?;
+        let mut reply = Decoder::with_objects(&reply, self);
+        HResult::unmarshal(&mut reply)?.ok()?;
+        I::unmarshal(&mut reply)
+    }
+
+    /// Process the requests of the other side until it closes the connection
+    pub fn serve(&self) -> Result<(), Error> {
+        loop {
+            match self.receive() {
+                Ok(message) => {
+                    if message.first() == Some(&REPLY) {
+                        self.disconnect();
+                        return Err(invalid_data());
+                    }
+                    self.dispatch(&message)?;
+                }
+                Err(e) if e.code().0 == RPC_E_DISCONNECTED => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Whether the connection is still open
+    pub fn is_connected(&self) -> bool {
+        self.inner.transport.borrow().is_some()
+    }
+
+    /// Close the connection
+    ///
+    /// The objects passed to the other side are released, and calls through the
+    /// proxies of the connection fail with `RPC_E_DISCONNECTED`.
+    pub fn close(&self) {
+        self.disconnect()
+    }
+
+    /// Call a method of an object of the other side
+    pub(crate) fn call<A, R>(
+        &self,
+        object: u64,
+        iid: &IID,
+        method: u32,
+        args: A,
+        results: R,
+    ) -> Result<HRESULT, Error>
+    where
+        A: FnOnce(&mut Encoder<'_>) -> Result<(), Error>,
+        R: FnOnce(&mut Decoder<'_>) -> Result<(), Error>,
+    {
+        let mut request = Encoder::with_objects(self);
+        CALL.marshal(&mut request)?;
+        object.marshal(&mut request)?;
+        iid.marshal(&mut request)?;
+        method.marshal(&mut request)?;
+        args(&mut request)?;
+        let reply = self.request(&request.into_bytes())?;
+        let mut reply = Decoder::with_objects(&reply, self);
+        let hr = HRESULT::unmarshal(&mut reply)?;
+        if FAILED(hr) {
+            error_info::set(Option::<ErrorInfo>::unmarshal(&mut reply)?.as_ref());
+        } else {
+            results(&mut reply)?;
+        }
+        Ok(hr)
+    }
+
+    /// Query an object of the other side for an interface
+    pub(crate) fn query_interface(
+        &self,
+        manager: &ProxyManager,
+        iid: &IID,
+    ) -> Result<IUnknown, Error> {
+        if let Some(interface) = manager.find(iid) {
+            return Ok(interface);
+        }
+        if super::find(iid).is_none() {
+            return Err(HResult(E_NOINTERFACE).into());
+        }
+        let mut request = Encoder::new();
+        QUERY.marshal(&mut request)?;
+        manager.object().marshal(&mut request)?;
+        iid.marshal(&mut request)?;
+        let reply = self.request(&request.into_bytes())?;
+        let mut reply = Decoder::with_objects(&reply, self);
+        HResult::unmarshal(&mut reply)?.ok()?;
+        self.import(iid, &mut reply)
+    }
+
+    /// Release the references to an object of the other side held by its proxies
+    pub(crate) fn release_proxy(&self, object: u64, refs: u32) {
+        self.inner.imports.borrow_mut().remove(&object);
+        self.release_remote(object, refs);
+    }
+
+    /// Release references to an object of the other side
+    fn release_remote(&self, object: u64, refs: u32) {
+        self.inner.releases.borrow_mut().push((object, refs));
+        // A reply being written may reference the object, so the references are only
+        // released once it is sent
+        if self.inner.dispatching.get() == 0 {
+            self.send_releases();
+        }
+    }
+
+    fn send_releases(&self) {
+        let releases = core::mem::take(&mut *self.inner.releases.borrow_mut());
+        for (object, refs) in releases {
+            let mut message = Encoder::new();
+            let _ = RELEASE.marshal(&mut message);
+            let _ = object.marshal(&mut message);
+            let _ = refs.marshal(&mut message);
+            // The references are released anyway when the connection is closed
+            let _ = self.send(message.as_bytes());
+        }
+    }
+
+    /// Send a request and wait for its reply, processing the requests of the other
+    /// side in the meantime
+    fn request(&self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        self.send(request)?;
+        loop {
+            let mut message = self.receive()?;
+            if message.first() == Some(&REPLY) {
+                message.remove(0);
+                return Ok(message);
+            }
+            self.dispatch(&message)?;
+        }
+    }
+
+    fn send(&self, message: &[u8]) -> Result<(), Error> {
+        let result = match &mut *self.inner.transport.borrow_mut() {
+            Some(transport) => write_message(&mut **transport, message),
+            None => return Err(HResult(RPC_E_DISCONNECTED).into()),
+        };
+        if result.is_err() {
+            self.disconnect();
+            return Err(HResult(RPC_E_DISCONNECTED).into());
+        }
+        Ok(())
+    }
+
+    /// Receive the next message, after checking that the other side uses the same
+    /// version of the format
+    fn receive(&self) -> Result<Vec<u8>, Error> {
+        if !self.inner.greeted.get() {
+            let hello = self.read()?;
+            self.inner.greeted.set(true);
+            let mut hello = Decoder::new(&hello);
+            let version = match u8::unmarshal(&mut hello) {
+                Ok(HELLO) => u32::unmarshal(&mut hello).ok(),
+                _ => None,
+            };
+            if version != Some(marshal::VERSION) || !hello.remaining().is_empty() {
+                self.disconnect();
+                return Err(HResult(RPC_E_VERSION_MISMATCH).into());
+            }
+        }
+        self.read()
+    }
+
+    fn read(&self) -> Result<Vec<u8>, Error> {
+        let result = match &mut *self.inner.transport.borrow_mut() {
+            Some(transport) => read_message(&mut **transport),
+            None => return Err(HResult(RPC_E_DISCONNECTED).into()),
+        };
+        match result {
+            Ok(Some(message)) => Ok(message),
+            Ok(None) | Err(_) => {
+                self.disconnect();
+                Err(HResult(RPC_E_DISCONNECTED).into())
+            }
+        }
+    }
+
+    /// Close the connection and release the objects passed to the other side
+    fn disconnect(&self) {
+        let transport = self.inner.transport.borrow_mut().take();
+        let exports = core::mem::take(&mut *self.inner.exports.borrow_mut());
+        self.inner.releases.borrow_mut().clear();
+        // The objects may release proxies of this connection when they are dropped
+        drop(exports);
+        drop(transport);
+    }
+
+    /// Process a request of the other side
+    ///
+    /// The other side cannot be trusted to send valid requests, so invalid requests
+    /// close the connection.
+    fn dispatch(&self, message: &[u8]) -> Result<(), Error> {
+        self.inner.dispatching.set(self.inner.dispatching.get() + 1);
+        let result = self.process(message);
+        self.inner.dispatching.set(self.inner.dispatching.get() - 1);
+        if result.is_err() {
+            self.disconnect();
+        } else if self.inner.dispatching.get() == 0 {
+            self.send_releases();
+        }
+        result
+    }
+
+    fn process(&self, message: &[u8]) -> Result<(), Error> {
+        let mut request = Decoder::with_objects(message, self);
+        let mut reply = Encoder::with_objects(self);
+        REPLY.marshal(&mut reply)?;
+        match u8::unmarshal(&mut request)? {
+            CALL => {
+                let object = u64::unmarshal(&mut request)?;
+                let iid = IID::unmarshal(&mut request)?;
+                let method = u32::unmarshal(&mut request)?;
+                let mut results = Encoder::with_objects(self);
+                let (hr, info) = match self.invoke(object, &iid, method, &mut request, &mut results)
+                {
+                    Ok(result) => result,
+                    Err(e) => (e.code().0, e.info().cloned()),
+                };
+                hr.marshal(&mut reply)?;
+                if FAILED(hr) {
+                    info.marshal(&mut reply)?;
+                } else {
+                    reply.write_bytes(results.as_bytes());
+                }
+            }
+            QUERY => {
+                let object = u64::unmarshal(&mut request)?;
+                let iid = IID::unmarshal(&mut request)?;
+                let interface = self.export_interface(object, &iid);
+                self.reply_object(interface, &iid, &mut reply)?;
+            }
+            CREATE => {
+                let class_id = CLSID::unmarshal(&mut request)?;
+                let iid = IID::unmarshal(&mut request)?;
+                let interface = match &self.inner.classes {
+                    Some(classes) => classes
+                        .create_instance::<IUnknown>(&class_id)
+                        .and_then(|object| query(&object, &iid)),
+                    None => Err(HResult(CLASS_E_CLASSNOTAVAILABLE).into()),
+                };
+                self.reply_object(interface, &iid, &mut reply)?;
+            }
+            RELEASE => {
+                let object = u64::unmarshal(&mut request)?;
+                let refs = u32::unmarshal(&mut request)?;
+                return self.release_export(object, refs);
+            }
+            _ => return Err(invalid_data()),
+        }
+        self.send(reply.as_bytes())
+    }
+
+    fn reply_object(
+        &self,
+        interface: Result<IUnknown, Error>,
+        iid: &IID,
+        reply: &mut Encoder<'_>,
+    ) -> Result<(), Error> {
+        match interface {
+            Ok(interface) => {
+                NOERROR.marshal(reply)?;
+                self.export(&interface, iid, reply)
+            }
+            Err(e) => e.code().marshal(reply),
+        }
+    }
+
+    /// Call a method of an object passed to the other side, and take the error
+    /// information it set if the call failed
+    fn invoke(
+        &self,
+        object: u64,
+        iid: &IID,
+        method: u32,
+        args: &mut Decoder<'_>,
+        results: &mut Encoder<'_>,
+    ) -> Result<(HRESULT, Option<ErrorInfo>), Error> {
+        let interface = self.export_interface(object, iid)?;
+        let remote = super::find(iid).ok_or_else(|| Error::from(HResult(RPC_E_INVALIDMETHOD)))?;
+        // The exports are not borrowed during the call, which may pass objects or
+        // make calls through the connection
+        let hr = unsafe { (remote.invoke)(&interface, method, args, results) }?;
+        let info = if FAILED(hr) {
+            error_info::take_info(&interface, iid)
+        } else {
+            None
+        };
+        Ok((hr, info))
+    }
+
+    /// Get an interface of an object passed to the other side
+    fn export_interface(&self, object: u64, iid: &IID) -> Result<IUnknown, Error> {
+        let mut exports = self.inner.exports.borrow_mut();
+        let export = exports.objects.get_mut(&object).ok_or_else(invalid_data)?;
+        export.interface(iid)
+    }
+
+    fn release_export(&self, object: u64, refs: u32) -> Result<(), Error> {
+        let released = {
+            let mut exports = self.inner.exports.borrow_mut();
+            let export = exports.objects.get_mut(&object).ok_or_else(invalid_data)?;
+            export.refs = export.refs.checked_sub(refs).ok_or_else(invalid_data)?;
+            if export.refs > 0 {
+                return Ok(());
+            }
+            let export = exports.objects.remove(&object).unwrap();
+            exports.ids.remove(&address(&export.identity));
+            export
+        };
+        // The object may release proxies of this connection when it is dropped
+        drop(released);
+        Ok(())
+    }
+}
+
+impl ObjectTable for Connection {
+    fn export(&self, object: &IUnknown, iid: &IID, encoder: &mut Encoder<'_>) -> Result<(), Error> {
+        if let Some(manager) = self.proxy_manager(object) {
+            RECEIVER.marshal(encoder)?;
+            return manager.object().marshal(encoder);
+        }
+        let identity = query(object, &IUnknown::IID)?;
+        let id = {
+            let mut exports = self.inner.exports.borrow_mut();
+            let exports = &mut *exports;
+            let id = match exports.ids.get(&address(&identity)) {
+                Some(id) => *id,
+                None => {
+                    let id = exports.next_id;
+                    exports.next_id += 1;
+                    exports.ids.insert(address(&identity), id);
+                    exports.objects.insert(
+                        id,
+                        Export {
+                            identity,
+                            refs: 0,
+                            interfaces: Vec::new(),
+                        },
+                    );
+                    id
+                }
+            };
+            let export = exports.objects.get_mut(&id).unwrap();
+            export.refs += 1;
+            if !export.interfaces.iter().any(|(i, _)| i == iid) {
+                export.interfaces.push((*iid, object.clone()));
+            }
+            id
+        };
+        SENDER.marshal(encoder)?;
+        id.marshal(encoder)
+    }
+
+    fn import(&self, iid: &IID, decoder: &mut Decoder<'_>) -> Result<IUnknown, Error> {
+        let owner = u8::unmarshal(decoder)?;
+        let object = u64::unmarshal(decoder)?;
+        match owner {
+            SENDER => {
+                let remote = super::find(iid);
+                if remote.is_none() && iid != &IUnknown::IID {
+                    // The reference passed with the object is owned by this side
+                    self.release_remote(object, 1);
+                    return Err(HResult(E_NOINTERFACE).into());
+                }
+                let manager = *self
+                    .inner
+                    .imports
+                    .borrow_mut()
+                    .entry(object)
+                    .or_insert_with(|| {
+                        ProxyManager::new(Channel::Connection(self.clone()), object)
+                    });
+                let manager = unsafe { manager.as_ref() };
+                // Take the reference before any proxy is created, so that it is
+                // released with the proxy on errors
+                manager.add_remote_ref();
+                match (manager.find(iid), remote) {
+                    (Some(proxy), _) => Ok(proxy),
+                    (None, Some(remote)) => Ok(manager.insert(iid, remote.proxy_vtable)),
+                    (None, None) => unreachable!("the proxy for IUnknown always exists"),
+                }
+            }
+            RECEIVER => self.export_interface(object, iid),
+            _ => Err(invalid_data()),
+        }
+    }
+}
+
+impl Connection {
+    /// The proxy manager of `object`, if it is a proxy created by this connection
+    fn proxy_manager<'a>(&self, object: &'a IUnknown) -> Option<&'a ProxyManager> {
+        let manager = proxy::proxy_manager(object)?;
+        match manager.channel() {
+            Channel::Connection(connection) if Rc::ptr_eq(&connection.inner, &self.inner) => {
+                Some(manager)
+            }
+            _ => None,
+        }
+    }
+}
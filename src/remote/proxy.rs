@@ -0,0 +1,492 @@
+//! Proxies for objects on the other side of a connection or in another apartment
+//!
+//! This is part of the implementation of `com-rs`, and should not be used directly
+//! by application code. It is used by code generated by `com::interfaces!` for
+//! interfaces marked `#[remote]`.
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::convert::TryInto;
+use core::ffi::c_void;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::ptr::NonNull;
+
+#[cfg(not(windows))]
+use super::apartment;
+use super::connection::Connection;
+use super::transport::MAX_MESSAGE_LEN;
+use crate::interfaces::iunknown::IID_IUNKNOWN;
+use crate::interfaces::{ISupportErrorInfo, IUnknown};
+use crate::marshal::{invalid_data, marshal_slice, Decoder, Encoder, Marshal};
+use crate::refcounting::{self, LocalRefCount};
+#[cfg(not(windows))]
+use crate::runtime::Dispatcher;
+use crate::sys::{
+    E_INVALIDARG, E_NOINTERFACE, E_POINTER, FAILED, GUID, HRESULT, IID, NOERROR, S_OK,
+};
+use crate::{AbiTransferable, Error, Interface};
+
+// See https://github.com/rust-lang/rust/issues/86935
+type IUnknownVTable = <IUnknown as Interface>::VTable;
+type ISupportErrorInfoVTable = <ISupportErrorInfo as Interface>::VTable;
+
+/// The vtable of the `IUnknown` methods of all proxies
+pub const IUNKNOWN_VTABLE: IUnknownVTable = IUnknownVTable {
+    QueryInterface: query_interface,
+    AddRef: add_ref,
+    Release: release,
+};
+
+static IUNKNOWN_PROXY_VTABLE: IUnknownVTable = IUNKNOWN_VTABLE;
+
+/// The proxies answer `ISupportErrorInfo` themselves, since the channel passes the
+/// error information of a failed call only if the object supports it
+static SUPPORT_ERROR_INFO_PROXY_VTABLE: ISupportErrorInfoVTable = ISupportErrorInfoVTable {
+    parent: IUNKNOWN_VTABLE,
+    InterfaceSupportsErrorInfo: interface_supports_error_info,
+};
+
+/// A private IID for which the proxies return their [`ProxyManager`]
+///
+/// This is how a connection recognizes the proxies it created, to pass the
+/// original object back to the other side instead of a proxy of a proxy.
+pub(crate) const IID_PROXY_MANAGER: IID = crate::guid!("5c0f4a0e-1b77-4d6a-9f0b-7f3f6a2de2c1");
+
+/// How the calls of a proxy reach its object
+#[derive(Clone)]
+pub(crate) enum Channel {
+    /// The object is on the other side of a connection
+    Connection(Connection),
+    /// The object lives in the single-threaded apartment of another thread, which
+    /// runs the calls queued by the dispatcher
+    #[cfg(not(windows))]
+    Apartment(Dispatcher),
+}
+
+impl Channel {
+    fn call<A, R>(
+        &self,
+        object: u64,
+        iid: &IID,
+        index: u32,
+        args: A,
+        results: R,
+    ) -> Result<HRESULT, Error>
+    where
+        A: FnOnce(&mut Encoder<'_>) -> Result<(), Error>,
+        R: FnOnce(&mut Decoder<'_>) -> Result<(), Error>,
+    {
+        match self {
+            Channel::Connection(connection) => connection.call(object, iid, index, args, results),
+            #[cfg(not(windows))]
+            Channel::Apartment(dispatcher) => {
+                apartment::call(dispatcher, object, iid, index, args, results)
+            }
+        }
+    }
+
+    fn query_interface(&self, manager: &ProxyManager, iid: &IID) -> Result<IUnknown, Error> {
+        match self {
+            Channel::Connection(connection) => connection.query_interface(manager, iid),
+            #[cfg(not(windows))]
+            Channel::Apartment(dispatcher) => apartment::query_interface(dispatcher, manager, iid),
+        }
+    }
+
+    fn release_proxy(&self, object: u64, refs: u32) {
+        match self {
+            Channel::Connection(connection) => connection.release_proxy(object, refs),
+            #[cfg(not(windows))]
+            Channel::Apartment(_) => apartment::release_proxy(object, refs),
+        }
+    }
+}
+
+/// A proxy for one interface of an object on the other side of a connection
+///
+/// The proxies of an object share the reference count of their [`ProxyManager`].
+#[repr(C)]
+struct InterfaceProxy {
+    vtable: *const c_void,
+    iid: IID,
+    manager: NonNull<ProxyManager>,
+}
+
+/// The proxies for an object on the other side of a connection or in another
+/// apartment
+///
+/// The manager holds the references to the object which were passed to this side of
+/// the channel. They are released when the last proxy is released.
+pub(crate) struct ProxyManager {
+    channel: Channel,
+    object: u64,
+    refs: LocalRefCount,
+    remote_refs: Cell<u32>,
+    /// The proxies for each interface, starting with `IUnknown`
+    ///
+    /// The proxies are boxed so that their addresses do not change.
+    #[allow(clippy::vec_box)]
+    interfaces: RefCell<Vec<Box<InterfaceProxy>>>,
+}
+
+impl ProxyManager {
+    /// Create a manager for the object with the given id, without any references
+    pub(crate) fn new(channel: Channel, object: u64) -> NonNull<ProxyManager> {
+        let manager = Box::new(ProxyManager {
+            channel,
+            object,
+            refs: LocalRefCount::new(0),
+            remote_refs: Cell::new(0),
+            interfaces: RefCell::new(Vec::new()),
+        });
+        let manager = unsafe { NonNull::new_unchecked(Box::into_raw(manager)) };
+        let proxy = InterfaceProxy::new(
+            &IUNKNOWN_PROXY_VTABLE as *const IUnknownVTable as *const c_void,
+            IID_IUNKNOWN,
+            manager,
+        );
+        unsafe { manager.as_ref() }
+            .interfaces
+            .borrow_mut()
+            .push(proxy);
+        manager
+    }
+
+    /// The channel the object is reached through
+    pub(crate) fn channel(&self) -> &Channel {
+        &self.channel
+    }
+
+    /// The id of the object on the other side of the channel
+    pub(crate) fn object(&self) -> u64 {
+        self.object
+    }
+
+    /// Take ownership of a reference to the object which was passed to this side
+    pub(crate) fn add_remote_ref(&self) {
+        self.remote_refs.set(self.remote_refs.get() + 1);
+    }
+
+    /// Get a new reference to the proxy for `iid`, if it was already created
+    pub(crate) fn find(&self, iid: &IID) -> Option<IUnknown> {
+        let interfaces = self.interfaces.borrow();
+        let proxy = interfaces.iter().find(|p| &p.iid == iid)?;
+        let proxy = NonNull::from(&**proxy).cast();
+        self.add_ref();
+        Some(IUnknown::from_abi(proxy))
+    }
+
+    /// Create the proxy for `iid` with the given vtable, and return a new reference
+    /// to it
+    pub(crate) fn insert(&self, iid: &IID, vtable: *const c_void) -> IUnknown {
+        let this = NonNull::from(self);
+        let proxy = InterfaceProxy::new(vtable, *iid, this);
+        let pointer = NonNull::from(&*proxy).cast();
+        self.interfaces.borrow_mut().push(proxy);
+        self.add_ref();
+        IUnknown::from_abi(pointer)
+    }
+
+    fn add_ref(&self) -> u32 {
+        refcounting::addref(&self.refs)
+    }
+
+    unsafe fn release(this: NonNull<ProxyManager>) -> u32 {
+        let manager = this.as_ref();
+        let refs = refcounting::release(&manager.refs);
+        if refs == 0 {
+            let manager = Box::from_raw(this.as_ptr());
+            manager
+                .channel
+                .release_proxy(manager.object, manager.remote_refs.get());
+        }
+        refs
+    }
+}
+
+impl InterfaceProxy {
+    fn new(vtable: *const c_void, iid: IID, manager: NonNull<ProxyManager>) -> Box<Self> {
+        Box::new(InterfaceProxy {
+            vtable,
+            iid,
+            manager,
+        })
+    }
+}
+
+/// The manager of the proxy `this` points to
+///
+/// # Safety
+///
+/// `this` must point to a proxy created by a [`ProxyManager`].
+unsafe fn manager<'a, T>(this: NonNull<NonNull<T>>) -> &'a ProxyManager {
+    (*(this.as_ptr() as *const InterfaceProxy)).manager.as_ref()
+}
+
+/// The proxy manager of `object`, if it is a proxy created by this module
+pub(crate) fn proxy_manager(object: &IUnknown) -> Option<&ProxyManager> {
+    let mut manager = core::ptr::null_mut::<c_void>();
+    let hr = unsafe { object.QueryInterface(&IID_PROXY_MANAGER, &mut manager) };
+    if FAILED(hr) || manager.is_null() {
+        return None;
+    }
+    // The proxy does not add a reference for this interface
+    Some(unsafe { &*(manager as *const ProxyManager) })
+}
+
+unsafe extern "system" fn query_interface(
+    this: NonNull<NonNull<IUnknownVTable>>,
+    riid: *const GUID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    if riid.is_null() || ppv.is_null() {
+        return E_POINTER;
+    }
+    let manager = manager(this);
+    if *riid == IID_PROXY_MANAGER {
+        // This does not add a reference
+        *ppv = manager as *const ProxyManager as *mut c_void;
+        return NOERROR;
+    }
+    if *riid == ISupportErrorInfo::IID {
+        let proxy = manager.find(&*riid).unwrap_or_else(|| {
+            let vtable = &SUPPORT_ERROR_INFO_PROXY_VTABLE as *const ISupportErrorInfoVTable;
+            manager.insert(&*riid, vtable as *const c_void)
+        });
+        *ppv = proxy.into_abi().as_ptr() as *mut c_void;
+        return NOERROR;
+    }
+    match manager.channel.query_interface(manager, &*riid) {
+        Ok(interface) => {
+            *ppv = interface.into_abi().as_ptr() as *mut c_void;
+            NOERROR
+        }
+        Err(_) => {
+            *ppv = core::ptr::null_mut();
+            // Failures of the channel are reported as a missing interface, since
+            // callers of `QueryInterface` usually only expect `E_NOINTERFACE`
+            E_NOINTERFACE
+        }
+    }
+}
+
+unsafe extern "system" fn add_ref(this: NonNull<NonNull<IUnknownVTable>>) -> u32 {
+    manager(this).add_ref()
+}
+
+unsafe extern "system" fn release(this: NonNull<NonNull<IUnknownVTable>>) -> u32 {
+    let manager = (*(this.as_ptr() as *const InterfaceProxy)).manager;
+    ProxyManager::release(manager)
+}
+
+unsafe extern "system" fn interface_supports_error_info(
+    _this: NonNull<NonNull<ISupportErrorInfoVTable>>,
+    _riid: *const IID,
+) -> HRESULT {
+    S_OK
+}
+
+/// Call the method at `index` in the interface `iid` of the object behind the proxy
+/// `this`
+///
+/// `args` writes the `[in]` parameters of the call, and `results` reads the `[out]`
+/// parameters if the call succeeds.
+///
+/// # Safety
+///
+/// `this` must be a proxy created by a connection or for another apartment.
+pub unsafe fn call<T, A, R>(
+    this: NonNull<NonNull<T>>,
+    iid: &IID,
+    index: u32,
+    args: A,
+    results: R,
+) -> HRESULT
+where
+    A: FnOnce(&mut Encoder<'_>) -> Result<(), Error>,
+    R: FnOnce(&mut Decoder<'_>) -> Result<(), Error>,
+{
+    let manager = manager(this);
+    match manager
+        .channel
+        .call(manager.object, iid, index, args, results)
+    {
+        Ok(hr) => hr,
+        Err(e) => e.code().0,
+    }
+}
+
+/// Write an `[in]` parameter passed by value
+///
+/// # Safety
+///
+/// `abi` must be a valid value of `T`, which is still owned by the caller.
+pub unsafe fn marshal_in<T: AbiTransferable + Marshal>(
+    abi: T::Abi,
+    encoder: &mut Encoder<'_>,
+) -> Result<(), Error> {
+    let value = ManuallyDrop::new(T::from_abi(abi));
+    value.marshal(encoder)
+}
+
+/// Write an `[in]` parameter passed as a pointer, which may be null
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a valid value of `T`.
+pub unsafe fn marshal_in_ptr<T: Marshal>(
+    ptr: *const T,
+    encoder: &mut Encoder<'_>,
+) -> Result<(), Error> {
+    match ptr.as_ref() {
+        Some(value) => {
+            true.marshal(encoder)?;
+            value.marshal(encoder)
+        }
+        None => false.marshal(encoder),
+    }
+}
+
+/// Read an `[in]` parameter written by [`marshal_in_ptr`]
+pub fn unmarshal_in_ptr<T: Marshal>(decoder: &mut Decoder<'_>) -> Result<Option<T>, Error> {
+    if bool::unmarshal(decoder)? {
+        T::unmarshal(decoder).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// The pointer passed for an `[in]` parameter read by [`unmarshal_in_ptr`]
+pub fn in_ptr<T>(value: &Option<T>) -> *const T {
+    match value {
+        Some(value) => value,
+        None => core::ptr::null(),
+    }
+}
+
+/// Read an `[out]` parameter and write it to `ptr`
+///
+/// # Safety
+///
+/// `ptr` must be valid for writes. The previous value is not dropped.
+pub unsafe fn unmarshal_out<T: Marshal>(
+    ptr: *mut T,
+    decoder: &mut Decoder<'_>,
+) -> Result<(), Error> {
+    ptr.write(T::unmarshal(decoder)?);
+    Ok(())
+}
+
+/// Write an `[out]` parameter set by a successful call
+///
+/// # Safety
+///
+/// The callee must have initialized `value`.
+pub unsafe fn marshal_out<T: Marshal>(
+    value: core::mem::MaybeUninit<T>,
+    encoder: &mut Encoder<'_>,
+) -> Result<(), Error> {
+    value.assume_init().marshal(encoder)
+}
+
+/// The number of elements of a `#[size_is(len)]` array
+///
+/// Returns an `E_INVALIDARG` error if `len` is negative.
+pub fn array_len<N: TryInto<usize>>(len: N) -> Result<usize, Error> {
+    len.try_into()
+        .map_err(|_| crate::HResult(E_INVALIDARG).into())
+}
+
+/// Write an `[in]` array of `len` elements
+///
+/// # Safety
+///
+/// `ptr` must point to `len` valid values of `T`, or be null if `len` is zero.
+pub unsafe fn marshal_in_array<T: Marshal, N: TryInto<usize>>(
+    ptr: *const T,
+    len: N,
+    encoder: &mut Encoder<'_>,
+) -> Result<(), Error> {
+    let len = array_len(len)?;
+    if len == 0 {
+        return marshal_slice::<T>(&[], encoder);
+    }
+    if ptr.is_null() {
+        return Err(crate::HResult(E_POINTER).into());
+    }
+    marshal_slice(core::slice::from_raw_parts(ptr, len), encoder)
+}
+
+/// Read an `[in]` array written by [`marshal_in_array`]
+pub fn unmarshal_in_array<T: Marshal>(decoder: &mut Decoder<'_>) -> Result<Vec<T>, Error> {
+    Vec::unmarshal(decoder)
+}
+
+/// Check that an `[in]` array read by [`unmarshal_in_array`] has the number of
+/// elements given by its length parameter
+pub fn check_in_array<T, N: TryInto<usize>>(elements: &[T], len: N) -> Result<(), Error> {
+    if elements.len() != array_len(len)? {
+        return Err(invalid_data());
+    }
+    Ok(())
+}
+
+/// Check that an `[out]` array of `len` elements can be written
+pub fn check_out_array<T, N: TryInto<usize>>(ptr: *mut T, len: N) -> Result<(), Error> {
+    if ptr.is_null() && array_len(len)? > 0 {
+        return Err(crate::HResult(E_POINTER).into());
+    }
+    Ok(())
+}
+
+/// Read an `[out]` array of `len` elements and write it to `ptr`
+///
+/// # Safety
+///
+/// `ptr` must be valid for writes of `len` elements. The previous values are not
+/// dropped.
+pub unsafe fn unmarshal_out_array<T: Marshal, N: TryInto<usize>>(
+    ptr: *mut T,
+    len: N,
+    decoder: &mut Decoder<'_>,
+) -> Result<(), Error> {
+    let elements = Vec::<T>::unmarshal(decoder)?;
+    if elements.len() != array_len(len)? {
+        return Err(invalid_data());
+    }
+    for (index, element) in elements.into_iter().enumerate() {
+        ptr.add(index).write(element);
+    }
+    Ok(())
+}
+
+/// Allocate the zeroed elements of an `[out]` array for the callee to write
+///
+/// The length comes from the other side, so arrays which cannot be sent back in a
+/// message are rejected before they are allocated.
+pub fn out_array<T, N: TryInto<usize>>(len: N) -> Result<Vec<MaybeUninit<T>>, Error> {
+    let len = array_len(len)?;
+    match len.checked_mul(core::mem::size_of::<T>().max(1)) {
+        Some(size) if size <= MAX_MESSAGE_LEN => {}
+        _ => return Err(crate::HResult(E_INVALIDARG).into()),
+    }
+    let mut elements = Vec::with_capacity(len);
+    elements.resize_with(len, MaybeUninit::zeroed);
+    Ok(elements)
+}
+
+/// Write an `[out]` array set by a successful call
+///
+/// # Safety
+///
+/// The callee must have initialized all the elements.
+pub unsafe fn marshal_out_array<T: Marshal>(
+    elements: Vec<MaybeUninit<T>>,
+    encoder: &mut Encoder<'_>,
+) -> Result<(), Error> {
+    let elements = elements
+        .into_iter()
+        .map(|element| element.assume_init())
+        .collect::<Vec<_>>();
+    marshal_slice(&elements, encoder)
+}
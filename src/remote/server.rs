@@ -0,0 +1,74 @@
+use std::io;
+use std::sync::Arc;
+
+use super::{Connection, Listener, Transport};
+use crate::production::ClassRegistry;
+use crate::Error;
+
+/// A server hosting classes for clients in other processes
+///
+/// This is the equivalent of registering class objects with `CLSCTX_LOCAL_SERVER`.
+/// Each client gets its own thread, on which the objects created by the client are
+/// called.
+///
+/// ```rust,no_run
+/// # com::interfaces! {
+/// #     #[uuid("EFF8970E-C50F-45E0-9284-291CE5A6F771")]
+/// #     #[remote]
+/// #     pub unsafe interface IAnimal: com::interfaces::IUnknown {
+/// #         fn Eat(&self) -> com::sys::HRESULT;
+/// #     }
+/// # }
+/// # com::class! {
+/// #     pub class BritishShortHairCat: IAnimal {}
+/// #     impl IAnimal for BritishShortHairCat {
+/// #         fn Eat(&self) -> com::sys::HRESULT { com::sys::NOERROR }
+/// #     }
+/// # }
+/// # #[cfg(unix)]
+/// # fn main() -> std::io::Result<()> {
+/// const CLSID_CAT_CLASS: com::CLSID = com::guid!("C5F45CBC-4439-418C-A9F9-05AC67525E43");
+///
+/// com::remote::register::<IAnimal>();
+/// let mut classes = com::production::ClassRegistry::new();
+/// classes.register::<BritishShortHairCat>(CLSID_CAT_CLASS);
+///
+/// let listener = std::os::unix::net::UnixListener::bind("/tmp/animals")?;
+/// com::remote::LocalServer::new(classes).run(listener)
+/// # }
+/// # #[cfg(not(unix))]
+/// # fn main() {}
+/// ```
+///
+/// The interfaces of the objects must be registered with [`register`](super::register)
+/// before clients call them.
+pub struct LocalServer {
+    classes: Arc<ClassRegistry>,
+}
+
+impl LocalServer {
+    /// Create a server for the classes of `classes`
+    pub fn new(classes: ClassRegistry) -> Self {
+        Self {
+            classes: Arc::new(classes),
+        }
+    }
+
+    /// Process the requests of a client on the current thread until it closes the
+    /// connection
+    pub fn serve_client<T: Transport + 'static>(&self, transport: T) -> Result<(), Error> {
+        Connection::with_classes(Box::new(transport), Some(self.classes.clone())).serve()
+    }
+
+    /// Accept clients from `listener` until accepting a client fails, serving each
+    /// client on its own thread
+    pub fn run<L: Listener>(self, listener: L) -> io::Result<()> {
+        loop {
+            let stream = listener.accept()?;
+            let classes = self.classes.clone();
+            std::thread::spawn(move || {
+                let _ = Connection::with_classes(Box::new(stream), Some(classes)).serve();
+            });
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+/// The largest message accepted from the other side of a connection
+pub(crate) const MAX_MESSAGE_LEN: usize = 64 * 1024 * 1024;
+
+/// A byte stream to another process, such as a Unix domain socket or a named pipe
+///
+/// Messages are written as a little-endian `u32` length followed by the message.
+pub trait Transport: Read + Write {}
+
+impl<T: Read + Write> Transport for T {}
+
+/// A source of connections from clients, used by [`LocalServer::run`](super::LocalServer::run)
+pub trait Listener {
+    /// The stream to a client
+    type Stream: Transport + Send + 'static;
+
+    /// Wait for the next client to connect
+    fn accept(&self) -> io::Result<Self::Stream>;
+}
+
+#[cfg(unix)]
+impl Listener for std::os::unix::net::UnixListener {
+    type Stream = std::os::unix::net::UnixStream;
+
+    fn accept(&self) -> io::Result<Self::Stream> {
+        std::os::unix::net::UnixListener::accept(self).map(|(stream, _)| stream)
+    }
+}
+
+/// Write a message
+pub(crate) fn write_message(transport: &mut dyn Transport, message: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(message.len())
+        .ok()
+        .filter(|&len| len as usize <= MAX_MESSAGE_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "message too long"))?;
+    transport.write_all(&len.to_le_bytes())?;
+    transport.write_all(message)?;
+    transport.flush()
+}
+
+/// Read a message
+///
+/// Returns `None` if the other side closed the stream between two messages.
+pub(crate) fn read_message(transport: &mut dyn Transport) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    let mut read = 0;
+    while read < len.len() {
+        match transport.read(&mut len[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message too long",
+        ));
+    }
+    let mut message = vec![0; len];
+    transport.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+#[cfg(windows)]
+pub use self::windows::{connect_named_pipe, NamedPipeListener};
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::OsStr;
+    use std::fs::File;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::{AsRawHandle, FromRawHandle};
+    use std::sync::Mutex;
+
+    use crate::sys::{
+        ConnectNamedPipe, CreateFileW, CreateNamedPipeW, WaitNamedPipeW, ERROR_PIPE_BUSY,
+        ERROR_PIPE_CONNECTED, GENERIC_READ, GENERIC_WRITE, INVALID_HANDLE_VALUE,
+        NMPWAIT_WAIT_FOREVER, OPEN_EXISTING, PIPE_ACCESS_DUPLEX, PIPE_TYPE_BYTE,
+        PIPE_UNLIMITED_INSTANCES,
+    };
+
+    /// The size of the buffers of the pipes
+    const BUFFER_SIZE: u32 = 64 * 1024;
+
+    /// A listener for clients connecting to a named pipe, such as `\\.\pipe\my-server`
+    pub struct NamedPipeListener {
+        name: Vec<u16>,
+        /// The instance of the pipe the next client connects to
+        next: Mutex<Option<File>>,
+    }
+
+    impl NamedPipeListener {
+        /// Create the pipe with the given name
+        ///
+        /// Clients can connect as soon as this returns.
+        pub fn bind(name: impl AsRef<OsStr>) -> io::Result<Self> {
+            let name = wide(name);
+            let next = create_instance(&name)?;
+            Ok(Self {
+                name,
+                next: Mutex::new(Some(next)),
+            })
+        }
+    }
+
+    impl super::Listener for NamedPipeListener {
+        type Stream = File;
+
+        fn accept(&self) -> io::Result<File> {
+            let mut next = self.next.lock().unwrap();
+            let pipe = match next.take() {
+                Some(pipe) => pipe,
+                None => create_instance(&self.name)?,
+            };
+            if unsafe { ConnectNamedPipe(pipe.as_raw_handle(), core::ptr::null_mut()) } == 0 {
+                let error = io::Error::last_os_error();
+                if error.raw_os_error() != Some(ERROR_PIPE_CONNECTED) {
+                    return Err(error);
+                }
+            }
+            // Create the instance for the next client right away, so that clients
+            // do not fail to find the pipe while the server is busy
+            *next = create_instance(&self.name).ok();
+            Ok(pipe)
+        }
+    }
+
+    /// Create a new instance of the pipe
+    fn create_instance(name: &[u16]) -> io::Result<File> {
+        let handle = unsafe {
+            CreateNamedPipeW(
+                name.as_ptr(),
+                PIPE_ACCESS_DUPLEX,
+                PIPE_TYPE_BYTE,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                core::ptr::null_mut(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(unsafe { File::from_raw_handle(handle) })
+    }
+
+    /// Connect to the named pipe of a server listening with a [`NamedPipeListener`]
+    pub fn connect_named_pipe(name: impl AsRef<OsStr>) -> io::Result<File> {
+        let name = wide(name);
+        loop {
+            let handle = unsafe {
+                CreateFileW(
+                    name.as_ptr(),
+                    GENERIC_READ | GENERIC_WRITE,
+                    0,
+                    core::ptr::null_mut(),
+                    OPEN_EXISTING,
+                    0,
+                    core::ptr::null_mut(),
+                )
+            };
+            if handle != INVALID_HANDLE_VALUE {
+                return Ok(unsafe { File::from_raw_handle(handle) });
+            }
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() != Some(ERROR_PIPE_BUSY) {
+                return Err(error);
+            }
+            if unsafe { WaitNamedPipeW(name.as_ptr(), NMPWAIT_WAIT_FOREVER) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+
+    /// A null-terminated UTF-16 string
+    fn wide(name: impl AsRef<OsStr>) -> Vec<u16> {
+        name.as_ref().encode_wide().chain(Some(0)).collect()
+    }
+}
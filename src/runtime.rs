@@ -1,14 +1,35 @@
 //! COM runtime facilities
 //!
-//! This includes initializing the COM runtime as well as creating instances of COM classes
+//! This includes initializing the COM runtime as well as creating instances of COM classes.
+//!
+//! Threads with a specific apartment type are started with [`spawn`], and
+//! single-threaded apartments process calls from other threads with a
+//! [`MessageLoop`]. On platforms other than Windows, apartments are emulated per
+//! thread and the message loop is a portable event loop, so code relying on STA
+//! semantics can be run and tested anywhere.
+#[cfg(windows)]
 use crate::sys::{
-    CoCreateInstance, CoGetClassObject, CoIncrementMTAUsage, CoInitializeEx, CoUninitialize,
-    CLSCTX_INPROC_SERVER, CLSID, COINIT_APARTMENTTHREADED, COINIT_MULTITHREADED, FAILED, HRESULT,
-    IID, S_FALSE, S_OK,
+    CoCreateInstance, CoGetApartmentType, CoGetClassObject, CoIncrementMTAUsage, CoInitializeEx,
+    CoUninitialize, APTTYPE_MAINSTA, APTTYPE_MTA, APTTYPE_STA, CLSCTX_INPROC_SERVER,
+    CLSCTX_LOCAL_SERVER, CLSID, IID, S_FALSE, S_OK,
 };
+use crate::sys::{COINIT_APARTMENTTHREADED, COINIT_MULTITHREADED};
+#[cfg(windows)]
 use core::ffi::c_void;
 
-use crate::Interface;
+#[cfg(windows)]
+use crate::interfaces::IUnknown;
+use crate::Error;
+#[cfg(windows)]
+use crate::{HResult, Interface};
+
+// `const` thread local initializers require Rust 1.59
+#[cfg(feature = "std")]
+#[allow(clippy::missing_const_for_thread_local)]
+mod dispatcher;
+
+#[cfg(feature = "std")]
+pub use dispatcher::{Dispatcher, MessageLoop, StaThread};
 
 /// Initialize a new multithreaded apartment (MTA) runtime. This will ensure
 /// that an MTA is running for the process. Every new thread will implicitly
@@ -17,19 +38,21 @@ use crate::Interface;
 /// This calls `CoIncrementMTAUsage`
 ///
 /// This function only needs to be called once per process.
-pub fn init_runtime() -> Result<(), HRESULT> {
+#[cfg(windows)]
+pub fn init_runtime() -> Result<(), Error> {
     let mut _cookie = core::ptr::null_mut::<c_void>();
     match unsafe { CoIncrementMTAUsage(&mut _cookie as *mut _ as *mut _) } {
         // S_OK indicates the runtime was initialized
         S_OK => Ok(()),
         // Any other result is considered an error here.
-        hr => Err(hr),
+        hr => Err(HResult(hr).into()),
     }
 }
 
 /// The threading model of the current thread's apartment
 #[repr(u32)]
 #[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ApartmentType {
     /// A single-threaded apartment (COINIT_APARTMENTTHREADED)
     SingleThreaded = COINIT_APARTMENTTHREADED,
@@ -39,22 +62,32 @@ pub enum ApartmentType {
 
 /// Establish an apartment type for the current thread.
 ///
-/// This can only be called once per thread and will return an error if
-/// it is called more than once.
+/// This can be called more than once per thread with the same apartment type, and
+/// each successful call must be balanced by a call to [`deinit_apartment`]. Calling
+/// it with a different apartment type returns `RPC_E_CHANGED_MODE`.
 ///
-/// In  general this should only be called on threads created by the user.
+/// In  general this should only be called on threads created by the user. Use
+/// [`spawn`] to start a thread in a new apartment.
 ///
 /// This wraps `CoInitializeEx`. The user is still responsible for establishing
-/// a message pump in the case of an STA
-// TODO: create a special `spawn` function for spawning a thread
-// with a specific apartment type.
-// TODO: add helpers for establishing a message pump
-pub fn init_apartment(apartment_type: ApartmentType) -> Result<(), HRESULT> {
-    match unsafe { CoInitializeEx(core::ptr::null_mut::<c_void>(), apartment_type as u32) } {
-        // S_OK indicates the runtime was initialized
-        S_OK | S_FALSE => Ok(()),
-        // Any other result is considered an error here.
-        hr => Err(hr),
+/// a message pump in the case of an STA, for example with a [`MessageLoop`].
+///
+/// On platforms other than Windows, the apartment is only recorded for the
+/// current thread, which lets [`current_apartment`] and the message loop helpers
+/// behave like they do on Windows.
+pub fn init_apartment(apartment_type: ApartmentType) -> Result<(), Error> {
+    #[cfg(windows)]
+    {
+        match unsafe { CoInitializeEx(core::ptr::null_mut::<c_void>(), apartment_type as u32) } {
+            // S_OK indicates the runtime was initialized
+            S_OK | S_FALSE => Ok(()),
+            // Any other result is considered an error here.
+            hr => Err(HResult(hr).into()),
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        emulated::init(apartment_type)
     }
 }
 
@@ -66,7 +99,108 @@ pub fn init_apartment(apartment_type: ApartmentType) -> Result<(), HRESULT> {
 /// (usually started through [`init_apartment`]).
 /// <https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-couninitialize>
 pub fn deinit_apartment() {
-    unsafe { CoUninitialize() }
+    #[cfg(windows)]
+    unsafe {
+        CoUninitialize()
+    }
+    #[cfg(not(windows))]
+    {
+        emulated::deinit()
+    }
+}
+
+/// The apartment type of the current thread, or `None` if the thread has not
+/// entered an apartment
+///
+/// On Windows this calls `CoGetApartmentType`. Threads which are implicitly part
+/// of the multithreaded apartment because another thread initialized it are
+/// reported as [`ApartmentType::Multithreaded`].
+pub fn current_apartment() -> Option<ApartmentType> {
+    #[cfg(windows)]
+    {
+        let (mut apartment, mut qualifier) = (0, 0);
+        if unsafe { CoGetApartmentType(&mut apartment, &mut qualifier) } != S_OK {
+            return None;
+        }
+        match apartment {
+            APTTYPE_STA | APTTYPE_MAINSTA => Some(ApartmentType::SingleThreaded),
+            APTTYPE_MTA => Some(ApartmentType::Multithreaded),
+            _ => None,
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        emulated::current()
+    }
+}
+
+/// Spawn a thread which runs `f` in a new apartment of type `apartment_type`
+///
+/// The apartment is initialized before `f` is called and uninitialized when it
+/// returns. The result of `f` is returned by [`JoinHandle::join`](std::thread::JoinHandle::join),
+/// or the error if the apartment could not be initialized.
+///
+/// ```rust
+/// use com::runtime::{current_apartment, spawn, ApartmentType};
+///
+/// let thread = spawn(ApartmentType::Multithreaded, || current_apartment());
+/// let apartment = thread.join().unwrap()?;
+/// assert_eq!(apartment, Some(ApartmentType::Multithreaded));
+/// # Ok::<(), com::Error>(())
+/// ```
+///
+/// A single-threaded apartment must process the calls made from other threads.
+/// [`StaThread`] spawns a thread which does so until it is joined.
+#[cfg(feature = "std")]
+pub fn spawn<F, T>(apartment_type: ApartmentType, f: F) -> std::thread::JoinHandle<Result<T, Error>>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    std::thread::spawn(move || {
+        let _runtime = ApartmentRuntime::new(apartment_type)?;
+        Ok(f())
+    })
+}
+
+/// Apartments recorded per thread on platforms without a COM runtime
+#[cfg(not(windows))]
+#[allow(clippy::missing_const_for_thread_local)]
+mod emulated {
+    use super::ApartmentType;
+    use crate::sys::RPC_E_CHANGED_MODE;
+    use crate::{Error, HResult};
+    use std::cell::Cell;
+
+    thread_local! {
+        /// The apartment of the thread and the number of times it was initialized
+        static APARTMENT: Cell<Option<(ApartmentType, usize)>> = Cell::new(None);
+    }
+
+    pub fn init(apartment_type: ApartmentType) -> Result<(), Error> {
+        APARTMENT.with(|apartment| match apartment.get() {
+            None => {
+                apartment.set(Some((apartment_type, 1)));
+                Ok(())
+            }
+            Some((current, count)) if current == apartment_type => {
+                apartment.set(Some((current, count + 1)));
+                Ok(())
+            }
+            Some(_) => Err(HResult(RPC_E_CHANGED_MODE).into()),
+        })
+    }
+
+    pub fn deinit() {
+        APARTMENT.with(|apartment| match apartment.get() {
+            Some((current, count)) if count > 1 => apartment.set(Some((current, count - 1))),
+            _ => apartment.set(None),
+        })
+    }
+
+    pub fn current() -> Option<ApartmentType> {
+        APARTMENT.with(|apartment| apartment.get().map(|(apartment_type, _)| apartment_type))
+    }
 }
 
 /// An apartment runtime configuration.
@@ -78,7 +212,7 @@ pub struct ApartmentRuntime {
 
 impl ApartmentRuntime {
     /// Initialize the thread as an [`ApartmentType`]
-    pub fn new(apartment_type: ApartmentType) -> Result<Self, HRESULT> {
+    pub fn new(apartment_type: ApartmentType) -> Result<Self, Error> {
         init_apartment(apartment_type)?;
         Ok(Self {
             _priv: core::ptr::null(),
@@ -95,9 +229,10 @@ impl Drop for ApartmentRuntime {
 /// Get the class object with the associated [`CLSID`]
 ///
 /// Calls `CoGetClassObject` internally
-pub fn get_class_object<T: Interface>(class_id: &CLSID) -> Result<T, HRESULT> {
+#[cfg(windows)]
+pub fn get_class_object<T: Interface>(class_id: &CLSID) -> Result<T, Error> {
     let mut class = None;
-    let hr = unsafe {
+    let hr = HResult(unsafe {
         CoGetClassObject(
             class_id as *const CLSID,
             CLSCTX_INPROC_SERVER,
@@ -105,10 +240,8 @@ pub fn get_class_object<T: Interface>(class_id: &CLSID) -> Result<T, HRESULT> {
             &T::IID as *const IID,
             &mut class as *mut _ as _,
         )
-    };
-    if FAILED(hr) {
-        return Err(hr);
-    }
+    });
+    hr.ok()?;
 
     Ok(class.unwrap())
 }
@@ -116,26 +249,62 @@ pub fn get_class_object<T: Interface>(class_id: &CLSID) -> Result<T, HRESULT> {
 /// Create an instance of a COM class with the associated class id
 ///
 /// Calls `CoCreateInstance` internally
-pub fn create_instance<T: Interface>(class_id: &CLSID) -> Result<T, HRESULT> {
-    unsafe { create_raw_instance::<T>(class_id, core::ptr::null_mut()) }
+#[cfg(windows)]
+pub fn create_instance<T: Interface>(class_id: &CLSID) -> Result<T, Error> {
+    unsafe { create_raw_instance::<T>(class_id, core::ptr::null_mut(), CLSCTX_INPROC_SERVER) }
+}
+
+/// Create an instance of a COM class with the associated class id, hosted by a
+/// server running in a separate process
+///
+/// Calls `CoCreateInstance` internally with `CLSCTX_LOCAL_SERVER`. Calls are
+/// marshalled by the proxies and stubs registered with the system. To host or call
+/// classes implemented with `com-rs` without registering them, see `com::remote`.
+#[cfg(windows)]
+pub fn create_local_instance<T: Interface>(class_id: &CLSID) -> Result<T, Error> {
+    unsafe { create_raw_instance::<T>(class_id, core::ptr::null_mut(), CLSCTX_LOCAL_SERVER) }
 }
 
-/// A helper for creating both regular and aggregated instances
+/// Create an instance of a COM class with the associated class id as the inner
+/// object of an aggregate
+///
+/// Returns the non-delegating `IUnknown` of the new object.
+///
+/// Calls `CoCreateInstance` internally
+///
+/// # Safety
+///
+/// `outer` must be the controlling `IUnknown` of the aggregate and must outlive
+/// the new object. The returned `IUnknown` must not be handed out to anyone but
+/// the controlling object.
+#[cfg(windows)]
+pub unsafe fn create_aggregated_instance(
+    class_id: &CLSID,
+    outer: &IUnknown,
+) -> Result<IUnknown, Error> {
+    create_raw_instance::<IUnknown>(
+        class_id,
+        outer.as_raw().as_ptr() as *mut c_void,
+        CLSCTX_INPROC_SERVER,
+    )
+}
+
+/// A helper for creating regular, aggregated and out-of-process instances
+#[cfg(windows)]
 unsafe fn create_raw_instance<T: Interface>(
     class_id: &CLSID,
     outer: *mut c_void,
-) -> Result<T, HRESULT> {
+    context: u32,
+) -> Result<T, Error> {
     let mut instance = None;
-    let hr = CoCreateInstance(
+    let hr = HResult(CoCreateInstance(
         class_id as *const CLSID,
         outer,
-        CLSCTX_INPROC_SERVER,
+        context,
         &T::IID as *const IID,
         &mut instance as *mut _ as _,
-    );
-    if FAILED(hr) {
-        return Err(hr);
-    }
+    ));
+    hr.ok()?;
 
     Ok(instance.unwrap())
 }
@@ -0,0 +1,464 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use super::ApartmentType;
+use crate::sys::RPC_E_DISCONNECTED;
+use crate::{Error, HResult};
+
+#[cfg(windows)]
+use crate::sys::{
+    DispatchMessageW, GetCurrentThreadId, GetMessageW, PeekMessageW, PostThreadMessageW,
+    TranslateMessage, MSG, PM_NOREMOVE, PM_REMOVE, WM_NULL, WM_QUIT,
+};
+
+/// A closure queued on a message loop
+type Task = Box<dyn FnOnce() + Send>;
+
+thread_local! {
+    /// The message loop of the current thread
+    static CURRENT: RefCell<Option<Arc<Shared>>> = RefCell::new(None);
+}
+
+fn current() -> Option<Arc<Shared>> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+struct State {
+    tasks: VecDeque<Task>,
+    /// Whether `run` should return
+    quit: bool,
+    /// Whether the message loop was dropped
+    closed: bool,
+}
+
+/// The state of a message loop shared with its dispatchers
+struct Shared {
+    state: Mutex<State>,
+    #[cfg(not(windows))]
+    wake: Condvar,
+    #[cfg(windows)]
+    thread_id: u32,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    fn post(&self, task: Task) -> Result<(), Error> {
+        let mut state = self.lock();
+        if state.closed {
+            return Err(HResult(RPC_E_DISCONNECTED).into());
+        }
+        state.tasks.push_back(task);
+        self.wake(state);
+        Ok(())
+    }
+
+    /// Wake up the thread of the message loop if it is waiting
+    ///
+    /// The state is locked while waking the thread so that a change made before
+    /// waking it is seen by the thread, even if it was about to wait.
+    #[cfg(not(windows))]
+    fn wake(&self, _state: MutexGuard<'_, State>) {
+        self.wake.notify_all();
+    }
+
+    #[cfg(windows)]
+    fn wake(&self, state: MutexGuard<'_, State>) {
+        drop(state);
+        // Messages posted to the thread are kept until they are retrieved
+        unsafe { PostThreadMessageW(self.thread_id, WM_NULL, 0, 0) };
+    }
+
+    /// Wait until the message loop is woken up, processing the window messages of
+    /// the thread on Windows
+    #[cfg(not(windows))]
+    fn wait<'a>(&'a self, state: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        self.wake.wait(state).unwrap()
+    }
+
+    #[cfg(windows)]
+    fn wait<'a>(&'a self, state: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        drop(state);
+        let mut msg = core::mem::MaybeUninit::<MSG>::zeroed();
+        match unsafe { GetMessageW(msg.as_mut_ptr(), core::ptr::null_mut(), 0, 0) } {
+            // GetMessageW fails for invalid parameters only, which would fail again
+            0 | -1 => self.lock().quit = true,
+            _ => unsafe {
+                TranslateMessage(msg.as_ptr());
+                DispatchMessageW(msg.as_ptr());
+            },
+        }
+        self.lock()
+    }
+
+    /// Process the window messages which are already in the queue of the thread
+    #[cfg(windows)]
+    fn dispatch_messages(&self) {
+        let mut msg = core::mem::MaybeUninit::<MSG>::zeroed();
+        while unsafe { PeekMessageW(msg.as_mut_ptr(), core::ptr::null_mut(), 0, 0, PM_REMOVE) } != 0
+        {
+            if unsafe { (*msg.as_ptr()).message } == WM_QUIT {
+                self.lock().quit = true;
+            } else {
+                unsafe {
+                    TranslateMessage(msg.as_ptr());
+                    DispatchMessageW(msg.as_ptr());
+                }
+            }
+        }
+    }
+
+    /// Run the queued tasks until `done` returns `true`, waiting for new tasks when
+    /// the queue is empty
+    ///
+    /// This must only be called on the thread of the message loop.
+    fn pump_until(&self, mut done: impl FnMut(&State) -> bool) {
+        loop {
+            let task = {
+                let mut state = self.lock();
+                loop {
+                    if done(&state) {
+                        return;
+                    }
+                    if let Some(task) = state.tasks.pop_front() {
+                        break task;
+                    }
+                    state = self.wait(state);
+                }
+            };
+            task();
+        }
+    }
+}
+
+/// A message loop running the closures queued on the current thread by its
+/// [`Dispatcher`]s
+///
+/// This is the message pump of a single-threaded apartment (STA): objects living
+/// in the apartment are only called from its thread, and other threads call them
+/// by queuing closures with [`Dispatcher::invoke`] or [`Dispatcher::post`]. On
+/// Windows, the loop also dispatches the window messages of the thread, which
+/// includes the calls made by the COM runtime to objects of the apartment.
+/// Elsewhere it only runs the queued closures.
+///
+/// A thread has at most one message loop. The closures which were not run when
+/// it is dropped are dropped, and queuing more closures fails with
+/// `RPC_E_DISCONNECTED`.
+///
+/// ```rust
+/// use com::runtime::{spawn, ApartmentType, MessageLoop};
+///
+/// let (sender, receiver) = std::sync::mpsc::channel();
+/// let thread = spawn(ApartmentType::SingleThreaded, move || {
+///     let message_loop = MessageLoop::new();
+///     sender.send(message_loop.dispatcher()).unwrap();
+///     message_loop.run();
+/// });
+///
+/// let dispatcher = receiver.recv().unwrap();
+/// assert_eq!(dispatcher.invoke(|| 1 + 1)?, 2);
+/// dispatcher.quit();
+/// thread.join().unwrap()?;
+/// # Ok::<(), com::Error>(())
+/// ```
+pub struct MessageLoop {
+    shared: Arc<Shared>,
+    /// The message loop belongs to the thread which created it
+    _not_send: PhantomData<*const ()>,
+}
+
+impl MessageLoop {
+    /// Create the message loop of the current thread
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread already has a message loop.
+    pub fn new() -> Self {
+        #[cfg(windows)]
+        unsafe {
+            // Create the message queue of the thread, so that messages can be
+            // posted to it before the loop runs
+            let mut msg = core::mem::MaybeUninit::<MSG>::zeroed();
+            PeekMessageW(msg.as_mut_ptr(), core::ptr::null_mut(), 0, 0, PM_NOREMOVE);
+        }
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                tasks: VecDeque::new(),
+                quit: false,
+                closed: false,
+            }),
+            #[cfg(not(windows))]
+            wake: Condvar::new(),
+            #[cfg(windows)]
+            thread_id: unsafe { GetCurrentThreadId() },
+        });
+        CURRENT.with(|current| {
+            let mut current = current.borrow_mut();
+            assert!(current.is_none(), "the thread already has a message loop");
+            *current = Some(shared.clone());
+        });
+        Self {
+            shared,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// A dispatcher queuing closures on this message loop
+    pub fn dispatcher(&self) -> Dispatcher {
+        Dispatcher {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Run the queued closures, waiting for more, until [`Dispatcher::quit`] is called
+    ///
+    /// A panic in a closure queued with [`Dispatcher::post`] is propagated.
+    pub fn run(&self) {
+        self.shared.pump_until(|state| state.quit);
+        self.shared.lock().quit = false;
+    }
+
+    /// Run the closures which are already queued without waiting for more, and
+    /// return how many were run
+    pub fn run_pending(&self) -> usize {
+        #[cfg(windows)]
+        self.shared.dispatch_messages();
+        let pending = self.shared.lock().tasks.len();
+        let mut count = 0;
+        while count < pending {
+            let task = match self.shared.lock().tasks.pop_front() {
+                Some(task) => task,
+                None => break,
+            };
+            task();
+            count += 1;
+        }
+        count
+    }
+}
+
+impl Default for MessageLoop {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for MessageLoop {
+    fn drop(&mut self) {
+        CURRENT.with(|current| current.borrow_mut().take());
+        let tasks = {
+            let mut state = self.shared.lock();
+            state.closed = true;
+            core::mem::take(&mut state.tasks)
+        };
+        // Dropping the tasks fails the pending `invoke` calls
+        drop(tasks);
+    }
+}
+
+/// The result of a closure queued by [`Dispatcher::invoke`]
+struct Completion<T> {
+    result: Mutex<Option<Result<thread::Result<T>, Error>>>,
+    ready: Condvar,
+}
+
+/// Sends the result of a closure to the thread which invoked it, or a
+/// `RPC_E_DISCONNECTED` error if the closure is dropped without being run
+struct Reply<T> {
+    completion: Arc<Completion<T>>,
+    /// The message loop of the invoking thread, which is pumped while it waits
+    caller: Option<Arc<Shared>>,
+}
+
+impl<T> Reply<T> {
+    fn send(&self, result: Result<thread::Result<T>, Error>) {
+        let mut slot = self.completion.result.lock().unwrap();
+        if slot.is_none() {
+            *slot = Some(result);
+        }
+        drop(slot);
+        self.completion.ready.notify_all();
+        if let Some(caller) = &self.caller {
+            caller.wake(caller.lock());
+        }
+    }
+}
+
+impl<T> Drop for Reply<T> {
+    fn drop(&mut self) {
+        self.send(Err(HResult(RPC_E_DISCONNECTED).into()));
+    }
+}
+
+/// Queues closures on the [`MessageLoop`] of another thread
+///
+/// Dispatchers can be cloned and sent to other threads. Closures are run in the
+/// order they were queued.
+#[derive(Clone)]
+pub struct Dispatcher {
+    shared: Arc<Shared>,
+}
+
+impl Dispatcher {
+    /// The dispatcher of the message loop of the current thread, if it has one
+    pub fn current() -> Option<Self> {
+        current().map(|shared| Self { shared })
+    }
+
+    /// Queue `f` on the message loop without waiting for it to run
+    ///
+    /// Fails with `RPC_E_DISCONNECTED` if the message loop was dropped.
+    pub fn post<F>(&self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.shared.post(Box::new(f))
+    }
+
+    /// Run `f` on the thread of the message loop and return its result
+    ///
+    /// `f` is called directly when the current thread is the thread of the message
+    /// loop. Otherwise, the current thread waits for `f` to run, and a panic in `f`
+    /// is propagated to it. If the current thread has a message loop as well, it
+    /// keeps running the closures queued on it while waiting, so that the thread of
+    /// `f` can call back into it like calls between apartments.
+    ///
+    /// Fails with `RPC_E_DISCONNECTED` if the message loop is dropped before `f` runs.
+    pub fn invoke<F, T>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        if self.is_current() {
+            return Ok(f());
+        }
+        let completion = Arc::new(Completion {
+            result: Mutex::new(None),
+            ready: Condvar::new(),
+        });
+        let caller = current();
+        let reply = Reply {
+            completion: completion.clone(),
+            caller: caller.clone(),
+        };
+        self.post(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+            reply.send(Ok(result));
+        })?;
+
+        let result = match caller {
+            Some(caller) => {
+                caller.pump_until(|_| completion.result.lock().unwrap().is_some());
+                completion.result.lock().unwrap().take()
+            }
+            None => {
+                let mut result = completion.result.lock().unwrap();
+                while result.is_none() {
+                    result = completion.ready.wait(result).unwrap();
+                }
+                result.take()
+            }
+        };
+        match result.expect("the result of the closure has been sent") {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(payload)) => panic::resume_unwind(payload),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Make [`MessageLoop::run`] return once the closures queued before are run
+    pub fn quit(&self) {
+        let shared = self.shared.clone();
+        // Nothing is left to quit once the message loop is dropped
+        let _ = self.post(move || shared.lock().quit = true);
+    }
+
+    /// Whether the current thread is the thread of the message loop
+    pub fn is_current(&self) -> bool {
+        CURRENT.with(|current| match &*current.borrow() {
+            Some(shared) => Arc::ptr_eq(shared, &self.shared),
+            None => false,
+        })
+    }
+}
+
+/// A thread in a single-threaded apartment running a [`MessageLoop`]
+///
+/// The thread runs the closures queued by its [`dispatcher`](StaThread::dispatcher)
+/// until it is joined or dropped.
+///
+/// ```rust
+/// use com::runtime::{current_apartment, ApartmentType, StaThread};
+///
+/// let thread = StaThread::spawn()?;
+/// let apartment = thread.dispatcher().invoke(current_apartment)?;
+/// assert_eq!(apartment, Some(ApartmentType::SingleThreaded));
+/// thread.join()?;
+/// # Ok::<(), com::Error>(())
+/// ```
+pub struct StaThread {
+    dispatcher: Dispatcher,
+    thread: Option<JoinHandle<Result<(), Error>>>,
+}
+
+impl StaThread {
+    /// Spawn a thread in a new single-threaded apartment
+    pub fn spawn() -> Result<Self, Error> {
+        let (sender, receiver) = mpsc::channel();
+        let thread = super::spawn(ApartmentType::SingleThreaded, move || {
+            let message_loop = MessageLoop::new();
+            let _ = sender.send(message_loop.dispatcher());
+            message_loop.run();
+        });
+        match receiver.recv() {
+            Ok(dispatcher) => Ok(Self {
+                dispatcher,
+                thread: Some(thread),
+            }),
+            // The apartment could not be initialized
+            Err(_) => match thread.join() {
+                Ok(result) => result.map(|()| unreachable!()),
+                Err(payload) => panic::resume_unwind(payload),
+            },
+        }
+    }
+
+    /// A dispatcher queuing closures on the thread
+    pub fn dispatcher(&self) -> &Dispatcher {
+        &self.dispatcher
+    }
+
+    /// Wait for the closures already queued to run and stop the thread
+    ///
+    /// A panic in a closure queued with [`Dispatcher::post`] is propagated.
+    pub fn join(mut self) -> Result<(), Error> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> Result<(), Error> {
+        let thread = match self.thread.take() {
+            Some(thread) => thread,
+            None => return Ok(()),
+        };
+        self.dispatcher.quit();
+        match thread.join() {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+impl Drop for StaThread {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.dispatcher.quit();
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,646 @@
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+#[cfg(not(windows))]
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::interfaces::{IDispatch, IUnknown};
+use crate::sys::{self, SAFEARRAY, SAFEARRAYBOUND, VARTYPE};
+use crate::{AbiTransferable, BStr, Error, HResult, PropVariant, Variant};
+
+/// Types which can be stored in a [`SafeArray`]
+///
+/// # Safety
+///
+/// `VT` must be the `VARTYPE` of a `SAFEARRAY` holding elements of this type, the
+/// type must have the same layout as such elements, and a value with all bits set to
+/// zero must be valid.
+pub unsafe trait SafeArrayElement: Clone {
+    /// The `VARTYPE` of the elements
+    const VT: VARTYPE;
+}
+
+macro_rules! safe_array_element {
+    ($($t:ty => $vt:ident),* $(,)?) => {
+        $(unsafe impl SafeArrayElement for $t {
+            const VT: VARTYPE = sys::$vt;
+        })*
+    };
+}
+
+safe_array_element! {
+    i8 => VT_I1,
+    i16 => VT_I2,
+    i32 => VT_I4,
+    i64 => VT_I8,
+    u8 => VT_UI1,
+    u16 => VT_UI2,
+    u32 => VT_UI4,
+    u64 => VT_UI8,
+    f32 => VT_R4,
+    f64 => VT_R8,
+    BStr => VT_BSTR,
+    Variant => VT_VARIANT,
+    Option<IUnknown> => VT_UNKNOWN,
+    Option<IDispatch> => VT_DISPATCH,
+}
+
+/// An owned [SAFEARRAY](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/ns-oaidl-safearray)
+///
+/// A `SAFEARRAY` is a self-describing, possibly multi-dimensional array used by
+/// automation interfaces. The elements are accessed by locking the array, which
+/// returns a guard that dereferences to a slice:
+///
+/// ```rust
+/// use com::SafeArray;
+///
+/// let mut array = SafeArray::from(vec![1, 2, 3]);
+/// array.lock_mut().unwrap()[0] = 10;
+/// assert_eq!(*array.lock().unwrap(), [10, 2, 3]);
+/// ```
+///
+/// The elements of multi-dimensional arrays are stored in column-major order, see
+/// [`SafeArray::offset`].
+///
+/// On Windows, arrays are allocated with `SafeArrayCreate`. On other platforms, the
+/// same memory layout is reproduced with the Rust allocator.
+#[repr(transparent)]
+pub struct SafeArray<T: SafeArrayElement> {
+    ptr: NonNull<SAFEARRAY>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: SafeArrayElement> SafeArray<T> {
+    /// Create a one-dimensional array of `len` zeroed elements, indexed from 0
+    pub fn new(len: usize) -> Self {
+        let len = u32::try_from(len).expect("too many elements for a SAFEARRAY");
+        Self::with_bounds(&[SAFEARRAYBOUND {
+            cElements: len,
+            lLbound: 0,
+        }])
+    }
+
+    /// Create an array of zeroed elements with the given bounds for each dimension
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bounds` is empty.
+    pub fn with_bounds(bounds: &[SAFEARRAYBOUND]) -> Self {
+        assert!(
+            !bounds.is_empty(),
+            "a SAFEARRAY needs at least one dimension"
+        );
+        let ptr = unsafe { create(T::VT, bounds) };
+        Self {
+            ptr: NonNull::new(ptr).expect("SafeArrayCreate failed"),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Take ownership of a raw `SAFEARRAY`
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be a valid `SAFEARRAY` with elements of type `T`, which is not owned
+    /// by anyone else. On platforms other than Windows, it must have been allocated by
+    /// this type.
+    pub unsafe fn from_raw(raw: NonNull<SAFEARRAY>) -> Self {
+        Self {
+            ptr: raw,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Give up ownership of the raw `SAFEARRAY`
+    pub fn into_raw(self) -> NonNull<SAFEARRAY> {
+        let ptr = self.ptr;
+        core::mem::forget(self);
+        ptr
+    }
+
+    /// Copy the array and its elements, failing if the copy cannot be allocated
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        let ptr = unsafe { copy(self.as_ptr()) }?;
+        Ok(unsafe { Self::from_raw(ptr) })
+    }
+
+    /// The raw `SAFEARRAY`
+    pub fn as_ptr(&self) -> *mut SAFEARRAY {
+        self.ptr.as_ptr()
+    }
+
+    /// The number of dimensions
+    pub fn dims(&self) -> usize {
+        unsafe { self.ptr.as_ref().cDims as usize }
+    }
+
+    /// The bounds of each dimension, in the order they were passed to
+    /// [`SafeArray::with_bounds`]
+    pub fn bounds(&self) -> Vec<SAFEARRAYBOUND> {
+        unsafe { raw_bounds(self.as_ptr()) }
+            .iter()
+            .rev()
+            .copied()
+            .collect()
+    }
+
+    /// The total number of elements
+    pub fn len(&self) -> usize {
+        unsafe { element_count(self.as_ptr()) }
+    }
+
+    /// Whether the array has no elements
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The position in the locked slice of the element at `indices`
+    ///
+    /// There must be one index per dimension, and the first index varies fastest.
+    /// Returns `None` if an index is out of bounds.
+    pub fn offset(&self, indices: &[i32]) -> Option<usize> {
+        let bounds = self.bounds();
+        if indices.len() != bounds.len() {
+            return None;
+        }
+        let mut offset = 0;
+        let mut stride = 1;
+        for (&index, bound) in indices.iter().zip(&bounds) {
+            let index = i64::from(index) - i64::from(bound.lLbound);
+            if index < 0 || index >= i64::from(bound.cElements) {
+                return None;
+            }
+            offset += index as usize * stride;
+            stride *= bound.cElements as usize;
+        }
+        Some(offset)
+    }
+
+    /// Lock the array for reading its elements
+    pub fn lock(&self) -> Result<SafeArrayGuard<'_, T>, Error> {
+        unsafe { lock(self.as_ptr()) }.ok()?;
+        Ok(SafeArrayGuard { array: self })
+    }
+
+    /// Lock the array for modifying its elements
+    pub fn lock_mut(&mut self) -> Result<SafeArrayGuardMut<'_, T>, Error> {
+        unsafe { lock(self.as_ptr()) }.ok()?;
+        Ok(SafeArrayGuardMut { array: self })
+    }
+
+    /// Copy the elements into a `Vec`, in the order of the locked slice
+    pub fn to_vec(&self) -> Result<Vec<T>, Error> {
+        Ok(self.lock()?.to_vec())
+    }
+
+    /// The elements of the array
+    ///
+    /// # Safety
+    ///
+    /// The array must be locked, and no mutable reference to the elements may exist.
+    unsafe fn elements(&self) -> &[T] {
+        let data = self.ptr.as_ref().pvData as *const T;
+        if data.is_null() {
+            return &[];
+        }
+        core::slice::from_raw_parts(data, self.len())
+    }
+
+    /// The elements of the array
+    ///
+    /// # Safety
+    ///
+    /// The array must be locked, and no other reference to the elements may exist.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn elements_mut(&self) -> &mut [T] {
+        let data = self.ptr.as_ref().pvData as *mut T;
+        if data.is_null() {
+            return &mut [];
+        }
+        core::slice::from_raw_parts_mut(data, self.len())
+    }
+}
+
+impl<T: SafeArrayElement> From<Vec<T>> for SafeArray<T> {
+    /// Create a one-dimensional array, indexed from 0
+    fn from(elements: Vec<T>) -> Self {
+        let mut array = Self::new(elements.len());
+        {
+            let mut guard = array.lock_mut().expect("a new SAFEARRAY can be locked");
+            for (element, value) in guard.iter_mut().zip(elements) {
+                *element = value;
+            }
+        }
+        array
+    }
+}
+
+impl<T: SafeArrayElement> Clone for SafeArray<T> {
+    /// Copies the array
+    ///
+    /// This panics if the array cannot be copied, see [`SafeArray::try_clone`].
+    fn clone(&self) -> Self {
+        self.try_clone().expect("copying the SAFEARRAY failed")
+    }
+}
+
+impl<T: SafeArrayElement> Drop for SafeArray<T> {
+    fn drop(&mut self) {
+        // The guards borrow the array, so it cannot be locked here
+        unsafe { destroy(self.as_ptr()) };
+    }
+}
+
+impl<T: SafeArrayElement + PartialEq> PartialEq for SafeArray<T> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.lock(), other.lock()) {
+            (Ok(a), Ok(b)) => self.bounds() == other.bounds() && *a == *b,
+            _ => false,
+        }
+    }
+}
+
+impl<T: SafeArrayElement + fmt::Debug> fmt::Debug for SafeArray<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("SafeArray");
+        debug.field("bounds", &self.bounds());
+        match self.lock() {
+            Ok(elements) => debug.field("elements", &&*elements),
+            Err(e) => debug.field("elements", &e),
+        };
+        debug.finish()
+    }
+}
+
+/// [`SafeArray`] uniquely owns its allocation, so it is [`Send`] and [`Sync`] when its
+/// elements are. The lock count is updated atomically by [`SafeArray::lock`].
+unsafe impl<T: SafeArrayElement + Send> Send for SafeArray<T> {}
+unsafe impl<T: SafeArrayElement + Sync> Sync for SafeArray<T> {}
+
+/// Null `SAFEARRAY` pointers received from COM are converted into empty arrays.
+unsafe impl<T: SafeArrayElement> AbiTransferable for SafeArray<T> {
+    type Abi = *mut SAFEARRAY;
+
+    fn get_abi(&self) -> Self::Abi {
+        self.as_ptr()
+    }
+
+    fn from_abi(abi: Self::Abi) -> Self {
+        match NonNull::new(abi) {
+            Some(ptr) => unsafe { Self::from_raw(ptr) },
+            None => Self::new(0),
+        }
+    }
+
+    /// Copies the array, since the caller keeps ownership of it
+    ///
+    /// This is called by the `class!` shims, which cannot panic, so an array which
+    /// cannot be copied is received as an empty array.
+    fn from_in_param(abi: Self::Abi) -> Self {
+        match NonNull::new(abi) {
+            Some(ptr) => {
+                let borrowed = core::mem::ManuallyDrop::new(unsafe { Self::from_raw(ptr) });
+                borrowed.try_clone().unwrap_or_else(|_| Self::new(0))
+            }
+            None => Self::new(0),
+        }
+    }
+
+    fn into_abi(self) -> Self::Abi {
+        self.into_raw().as_ptr()
+    }
+}
+
+/// A lock on a [`SafeArray`], giving read access to its elements
+///
+/// The array is unlocked when the guard is dropped.
+pub struct SafeArrayGuard<'a, T: SafeArrayElement> {
+    array: &'a SafeArray<T>,
+}
+
+impl<T: SafeArrayElement> Deref for SafeArrayGuard<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { self.array.elements() }
+    }
+}
+
+impl<T: SafeArrayElement> Drop for SafeArrayGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe { unlock(self.array.as_ptr()) };
+    }
+}
+
+/// A lock on a [`SafeArray`], giving write access to its elements
+///
+/// The array is unlocked when the guard is dropped.
+pub struct SafeArrayGuardMut<'a, T: SafeArrayElement> {
+    array: &'a mut SafeArray<T>,
+}
+
+impl<T: SafeArrayElement> Deref for SafeArrayGuardMut<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { self.array.elements() }
+    }
+}
+
+impl<T: SafeArrayElement> DerefMut for SafeArrayGuardMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { self.array.elements_mut() }
+    }
+}
+
+impl<T: SafeArrayElement> Drop for SafeArrayGuardMut<'_, T> {
+    fn drop(&mut self) {
+        unsafe { unlock(self.array.as_ptr()) };
+    }
+}
+
+impl<T: SafeArrayElement> From<SafeArray<T>> for Variant {
+    fn from(array: SafeArray<T>) -> Self {
+        let mut raw = Variant::new().into_raw();
+        raw.vt = sys::VT_ARRAY | T::VT;
+        raw.data.parray = array.into_raw().as_ptr() as *mut _;
+        unsafe { Variant::from_raw(raw) }
+    }
+}
+
+impl<T: SafeArrayElement> TryFrom<&Variant> for SafeArray<T> {
+    type Error = Error;
+
+    /// Copies the array, failing with `DISP_E_TYPEMISMATCH` if the variant doesn't
+    /// hold an array of `T`
+    fn try_from(variant: &Variant) -> Result<Self, Error> {
+        let raw = variant.as_raw();
+        let ptr = unsafe {
+            if raw.vt == sys::VT_ARRAY | T::VT {
+                raw.data.parray as *mut SAFEARRAY
+            } else if raw.vt == sys::VT_BYREF | sys::VT_ARRAY | T::VT && !raw.data.byref.is_null() {
+                *(raw.data.byref as *const *mut SAFEARRAY)
+            } else {
+                core::ptr::null_mut()
+            }
+        };
+        let ptr = NonNull::new(ptr).ok_or_else(|| Error::new(HResult(sys::DISP_E_TYPEMISMATCH)))?;
+        Ok(unsafe { Self::from_raw(copy(ptr.as_ptr())?) })
+    }
+}
+
+impl<T: SafeArrayElement> From<SafeArray<T>> for PropVariant {
+    fn from(array: SafeArray<T>) -> Self {
+        PropVariant::from(Variant::from(array))
+    }
+}
+
+impl<T: SafeArrayElement> TryFrom<&PropVariant> for SafeArray<T> {
+    type Error = Error;
+
+    fn try_from(variant: &PropVariant) -> Result<Self, Error> {
+        SafeArray::try_from(&Variant::try_from(variant.clone())?)
+    }
+}
+
+/// The bounds stored in the array, in reverse order of the dimensions
+pub(crate) unsafe fn raw_bounds<'a>(psa: *const SAFEARRAY) -> &'a [SAFEARRAYBOUND] {
+    let bounds = core::ptr::addr_of!((*psa).rgsabound) as *const SAFEARRAYBOUND;
+    core::slice::from_raw_parts(bounds, (*psa).cDims as usize)
+}
+
+unsafe fn element_count(psa: *const SAFEARRAY) -> usize {
+    raw_bounds(psa)
+        .iter()
+        .map(|bound| bound.cElements as usize)
+        .product()
+}
+
+#[cfg(windows)]
+unsafe fn create(vt: VARTYPE, bounds: &[SAFEARRAYBOUND]) -> *mut SAFEARRAY {
+    sys::SafeArrayCreate(vt, bounds.len() as u32, bounds.as_ptr())
+}
+
+/// Destroy an array and its elements
+#[cfg(windows)]
+pub(crate) unsafe fn destroy(psa: *mut SAFEARRAY) -> HResult {
+    HResult(sys::SafeArrayDestroy(psa))
+}
+
+/// Copy an array and its elements
+#[cfg(windows)]
+pub(crate) unsafe fn copy(psa: *mut SAFEARRAY) -> Result<NonNull<SAFEARRAY>, Error> {
+    let mut copy = core::ptr::null_mut();
+    HResult(sys::SafeArrayCopy(psa, &mut copy)).ok()?;
+    NonNull::new(copy).ok_or_else(|| Error::new(HResult(sys::E_OUTOFMEMORY)))
+}
+
+#[cfg(windows)]
+unsafe fn lock(psa: *mut SAFEARRAY) -> HResult {
+    HResult(sys::SafeArrayLock(psa))
+}
+
+#[cfg(windows)]
+unsafe fn unlock(psa: *mut SAFEARRAY) -> HResult {
+    HResult(sys::SafeArrayUnlock(psa))
+}
+
+// Outside of Windows, arrays are laid out like `SafeArrayCreate` does: the `VARTYPE`
+// of the elements is stored right before the header, which is followed by the bounds
+// of each dimension. The elements are allocated separately and zeroed.
+
+#[cfg(not(windows))]
+const PREFIX_SIZE: usize = 16;
+
+#[cfg(not(windows))]
+fn header_layout(dims: usize) -> core::alloc::Layout {
+    let size = PREFIX_SIZE + core::mem::size_of::<SAFEARRAY>()
+        - core::mem::size_of::<SAFEARRAYBOUND>()
+        + dims * core::mem::size_of::<SAFEARRAYBOUND>();
+    core::alloc::Layout::from_size_align(size, PREFIX_SIZE).unwrap()
+}
+
+#[cfg(not(windows))]
+fn data_layout(element_size: usize, len: usize) -> core::alloc::Layout {
+    let size = element_size
+        .checked_mul(len)
+        .expect("too many elements for a SAFEARRAY");
+    core::alloc::Layout::from_size_align(size, 8).unwrap()
+}
+
+/// The size of the elements and the features of an array of `vt`
+#[cfg(not(windows))]
+fn element_info(vt: VARTYPE) -> Option<(usize, u16)> {
+    let pointer = core::mem::size_of::<*mut core::ffi::c_void>();
+    let info = match vt {
+        sys::VT_I1 | sys::VT_UI1 => (1, 0),
+        sys::VT_I2 | sys::VT_UI2 | sys::VT_BOOL => (2, 0),
+        sys::VT_I4 | sys::VT_UI4 | sys::VT_INT | sys::VT_UINT | sys::VT_R4 | sys::VT_ERROR => {
+            (4, 0)
+        }
+        sys::VT_I8 | sys::VT_UI8 | sys::VT_R8 | sys::VT_CY | sys::VT_DATE => (8, 0),
+        sys::VT_BSTR => (pointer, sys::FADF_BSTR),
+        sys::VT_UNKNOWN => (pointer, sys::FADF_UNKNOWN),
+        sys::VT_DISPATCH => (pointer, sys::FADF_DISPATCH),
+        sys::VT_VARIANT => (core::mem::size_of::<sys::VARIANT>(), sys::FADF_VARIANT),
+        _ => return None,
+    };
+    Some(info)
+}
+
+#[cfg(not(windows))]
+unsafe fn create(vt: VARTYPE, bounds: &[SAFEARRAYBOUND]) -> *mut SAFEARRAY {
+    let (element_size, features) = match element_info(vt) {
+        Some(info) if !bounds.is_empty() => info,
+        _ => return core::ptr::null_mut(),
+    };
+    let len = bounds
+        .iter()
+        .try_fold(1usize, |len, bound| {
+            len.checked_mul(bound.cElements as usize)
+        })
+        .expect("too many elements for a SAFEARRAY");
+
+    let data = if len == 0 {
+        core::ptr::null_mut()
+    } else {
+        let layout = data_layout(element_size, len);
+        let data = alloc::alloc::alloc_zeroed(layout);
+        if data.is_null() {
+            alloc::alloc::handle_alloc_error(layout);
+        }
+        data
+    };
+
+    let layout = header_layout(bounds.len());
+    let allocation = alloc::alloc::alloc_zeroed(layout);
+    if allocation.is_null() {
+        alloc::alloc::handle_alloc_error(layout);
+    }
+    (allocation.add(PREFIX_SIZE - 4) as *mut u32).write(vt.into());
+    let psa = allocation.add(PREFIX_SIZE) as *mut SAFEARRAY;
+    (*psa).cDims = bounds.len() as u16;
+    (*psa).fFeatures = sys::FADF_HAVEVARTYPE | features;
+    (*psa).cbElements = element_size as u32;
+    (*psa).cLocks = 0;
+    (*psa).pvData = data as *mut _;
+    let raw_bounds = core::ptr::addr_of_mut!((*psa).rgsabound) as *mut SAFEARRAYBOUND;
+    for (i, bound) in bounds.iter().rev().enumerate() {
+        raw_bounds.add(i).write(*bound);
+    }
+    psa
+}
+
+#[cfg(not(windows))]
+unsafe fn vartype(psa: *mut SAFEARRAY) -> VARTYPE {
+    (psa as *const u8).sub(4).cast::<u32>().read() as VARTYPE
+}
+
+/// Destroy an array and its elements
+#[cfg(not(windows))]
+pub(crate) unsafe fn destroy(psa: *mut SAFEARRAY) -> HResult {
+    if psa.is_null() {
+        return HResult(sys::S_OK);
+    }
+    if (*psa).cLocks != 0 {
+        return HResult(sys::DISP_E_ARRAYISLOCKED);
+    }
+    let len = element_count(psa);
+    let data = (*psa).pvData;
+    if !data.is_null() {
+        let features = (*psa).fFeatures;
+        if features & sys::FADF_BSTR != 0 {
+            core::ptr::drop_in_place(core::ptr::slice_from_raw_parts_mut(data as *mut BStr, len));
+        } else if features & (sys::FADF_UNKNOWN | sys::FADF_DISPATCH) != 0 {
+            core::ptr::drop_in_place(core::ptr::slice_from_raw_parts_mut(
+                data as *mut Option<IUnknown>,
+                len,
+            ));
+        } else if features & sys::FADF_VARIANT != 0 {
+            core::ptr::drop_in_place(core::ptr::slice_from_raw_parts_mut(
+                data as *mut Variant,
+                len,
+            ));
+        }
+        alloc::alloc::dealloc(
+            data as *mut u8,
+            data_layout((*psa).cbElements as usize, len),
+        );
+    }
+    let allocation = (psa as *mut u8).sub(PREFIX_SIZE);
+    alloc::alloc::dealloc(allocation, header_layout((*psa).cDims as usize));
+    HResult(sys::S_OK)
+}
+
+/// Copy an array and its elements
+#[cfg(not(windows))]
+pub(crate) unsafe fn copy(psa: *mut SAFEARRAY) -> Result<NonNull<SAFEARRAY>, Error> {
+    let bounds = raw_bounds(psa).iter().rev().copied().collect::<Vec<_>>();
+    let copy = NonNull::new(create(vartype(psa), &bounds))
+        .ok_or_else(|| Error::new(HResult(sys::DISP_E_BADVARTYPE)))?;
+    let len = element_count(psa);
+    let (src, dst) = ((*psa).pvData, copy.as_ref().pvData);
+    if len == 0 {
+        return Ok(copy);
+    }
+    let features = (*psa).fFeatures;
+    if features & sys::FADF_BSTR != 0 {
+        clone_elements::<BStr>(src, dst, len);
+    } else if features & (sys::FADF_UNKNOWN | sys::FADF_DISPATCH) != 0 {
+        clone_elements::<Option<IUnknown>>(src, dst, len);
+    } else if features & sys::FADF_VARIANT != 0 {
+        clone_elements::<Variant>(src, dst, len);
+    } else {
+        let size = (*psa).cbElements as usize * len;
+        core::ptr::copy_nonoverlapping(src as *const u8, dst as *mut u8, size);
+    }
+    Ok(copy)
+}
+
+#[cfg(not(windows))]
+unsafe fn clone_elements<T: Clone>(
+    src: *mut core::ffi::c_void,
+    dst: *mut core::ffi::c_void,
+    len: usize,
+) {
+    let src = core::slice::from_raw_parts(src as *const T, len);
+    let dst = dst as *mut T;
+    for (i, element) in src.iter().enumerate() {
+        // The destination is zeroed, so there is nothing to drop
+        dst.add(i).write(element.clone());
+    }
+}
+
+/// The lock count of `psa`, which is shared by the threads locking the array
+///
+/// # Safety
+///
+/// `psa` must be a valid pointer, for the lifetime `'a`.
+#[cfg(not(windows))]
+unsafe fn lock_count<'a>(psa: *mut SAFEARRAY) -> &'a AtomicU32 {
+    // SAFETY: `AtomicU32` has the same size and alignment as `u32`
+    &*(core::ptr::addr_of_mut!((*psa).cLocks) as *const AtomicU32)
+}
+
+#[cfg(not(windows))]
+unsafe fn lock(psa: *mut SAFEARRAY) -> HResult {
+    match lock_count(psa).fetch_update(Ordering::Acquire, Ordering::Relaxed, |locks| {
+        locks.checked_add(1)
+    }) {
+        Ok(_) => HResult(sys::S_OK),
+        Err(_) => HResult(sys::E_UNEXPECTED),
+    }
+}
+
+#[cfg(not(windows))]
+unsafe fn unlock(psa: *mut SAFEARRAY) -> HResult {
+    match lock_count(psa).fetch_update(Ordering::Release, Ordering::Relaxed, |locks| {
+        locks.checked_sub(1)
+    }) {
+        Ok(_) => HResult(sys::S_OK),
+        Err(_) => HResult(sys::E_UNEXPECTED),
+    }
+}
@@ -16,6 +16,8 @@ pub type BOOL = i32;
 pub type LSTATUS = i32;
 /// HKEY type
 pub type HKEY = *mut c_void;
+/// BSTR type, see [`BStr`](crate::BStr) for an owned version
+pub type BSTR = *mut u16;
 
 /// No error
 pub const S_OK: HRESULT = 0;
@@ -30,28 +32,280 @@ pub const E_INVALIDARG: HRESULT = -0x7FF8_FFA9;
 pub const E_NOINTERFACE: HRESULT = -0x7FFF_BFFE;
 /// Invalid pointer
 pub const E_POINTER: HRESULT = -0x7FFF_BFFD;
+/// Not implemented
+pub const E_NOTIMPL: HRESULT = -0x7FFF_BFFF;
+/// Unspecified failure
+pub const E_FAIL: HRESULT = -0x7FFF_BFFB;
+/// Catastrophic failure
+pub const E_UNEXPECTED: HRESULT = -0x7FFF_0001;
+/// Failed to allocate necessary memory
+pub const E_OUTOFMEMORY: HRESULT = -0x7FF8_FFF2;
 
 /// No aggregation for class
 pub const CLASS_E_NOAGGREGATION: HRESULT = -0x7FFB_FEF0;
 /// Class is not available
 pub const CLASS_E_CLASSNOTAVAILABLE: HRESULT = -0x7FFB_FEEF;
 
+/// Type mismatch
+pub const DISP_E_TYPEMISMATCH: HRESULT = -0x7FFD_FFFB;
+/// Bad variable type
+pub const DISP_E_BADVARTYPE: HRESULT = -0x7FFD_FFF8;
+/// Out of present range
+pub const DISP_E_OVERFLOW: HRESULT = -0x7FFD_FFF6;
+/// Invalid index
+pub const DISP_E_BADINDEX: HRESULT = -0x7FFD_FFF5;
+/// Memory is locked
+pub const DISP_E_ARRAYISLOCKED: HRESULT = -0x7FFD_FFF3;
+/// Member not found
+pub const DISP_E_MEMBERNOTFOUND: HRESULT = -0x7FFD_FFFD;
+/// Unknown name
+pub const DISP_E_UNKNOWNNAME: HRESULT = -0x7FFD_FFFA;
+/// Does not support named arguments
+pub const DISP_E_NONAMEDARGS: HRESULT = -0x7FFD_FFF9;
+/// Invalid number of parameters
+pub const DISP_E_BADPARAMCOUNT: HRESULT = -0x7FFD_FFF2;
+
+/// The object invoked has disconnected from its clients
+pub const RPC_E_DISCONNECTED: HRESULT = -0x7FFE_FEF8;
+/// The method called does not exist on the server
+pub const RPC_E_INVALIDMETHOD: HRESULT = -0x7FFE_FEFC;
+/// The data of a remote call is invalid
+pub const RPC_E_INVALID_DATA: HRESULT = -0x7FFE_FEF1;
+/// The version of the other side of a remote connection does not match
+pub const RPC_E_VERSION_MISMATCH: HRESULT = -0x7FFE_FEF0;
+/// The apartment of a thread cannot be changed once it is initialized
+pub const RPC_E_CHANGED_MODE: HRESULT = -0x7FFE_FEFA;
+/// The thread has not entered an apartment
+pub const CO_E_NOTINITIALIZED: HRESULT = -0x7FFB_FE10;
+
 /// No error
 pub const ERROR_SUCCESS: u32 = 0;
 /// Registration error
 pub const SELFREG_E_CLASS: HRESULT = -0x7FFB_FDFF;
 /// A in process server
 pub const CLSCTX_INPROC_SERVER: u32 = 0x1;
+/// A server running in a separate process on the same machine
+pub const CLSCTX_LOCAL_SERVER: u32 = 0x4;
 
 /// An single threaded apartment (STA)
 pub const COINIT_APARTMENTTHREADED: u32 = 0x2;
 /// An multi threaded apartment (STA)
 pub const COINIT_MULTITHREADED: u32 = 0x0;
 
+/// APTTYPE of a single threaded apartment
+pub const APTTYPE_STA: i32 = 0;
+/// APTTYPE of the multi threaded apartment
+pub const APTTYPE_MTA: i32 = 1;
+/// APTTYPE of the neutral apartment
+pub const APTTYPE_NA: i32 = 2;
+/// APTTYPE of the main single threaded apartment
+pub const APTTYPE_MAINSTA: i32 = 3;
+
+/// VARTYPE type, the type tag of a [`VARIANT`] or [`PROPVARIANT`]
+pub type VARTYPE = u16;
+/// VARIANT_BOOL type
+#[allow(non_camel_case_types)]
+pub type VARIANT_BOOL = i16;
+/// `true` as a [`VARIANT_BOOL`]
+pub const VARIANT_TRUE: VARIANT_BOOL = -1;
+/// `false` as a [`VARIANT_BOOL`]
+pub const VARIANT_FALSE: VARIANT_BOOL = 0;
+
+#[allow(missing_docs)]
+mod vartype {
+    use super::VARTYPE;
+
+    pub const VT_EMPTY: VARTYPE = 0;
+    pub const VT_NULL: VARTYPE = 1;
+    pub const VT_I2: VARTYPE = 2;
+    pub const VT_I4: VARTYPE = 3;
+    pub const VT_R4: VARTYPE = 4;
+    pub const VT_R8: VARTYPE = 5;
+    pub const VT_CY: VARTYPE = 6;
+    pub const VT_DATE: VARTYPE = 7;
+    pub const VT_BSTR: VARTYPE = 8;
+    pub const VT_DISPATCH: VARTYPE = 9;
+    pub const VT_ERROR: VARTYPE = 10;
+    pub const VT_BOOL: VARTYPE = 11;
+    pub const VT_VARIANT: VARTYPE = 12;
+    pub const VT_UNKNOWN: VARTYPE = 13;
+    pub const VT_DECIMAL: VARTYPE = 14;
+    pub const VT_I1: VARTYPE = 16;
+    pub const VT_UI1: VARTYPE = 17;
+    pub const VT_UI2: VARTYPE = 18;
+    pub const VT_UI4: VARTYPE = 19;
+    pub const VT_I8: VARTYPE = 20;
+    pub const VT_UI8: VARTYPE = 21;
+    pub const VT_INT: VARTYPE = 22;
+    pub const VT_UINT: VARTYPE = 23;
+    pub const VT_LPSTR: VARTYPE = 30;
+    pub const VT_LPWSTR: VARTYPE = 31;
+    pub const VT_RECORD: VARTYPE = 36;
+    pub const VT_FILETIME: VARTYPE = 64;
+    pub const VT_CLSID: VARTYPE = 72;
+    pub const VT_VECTOR: VARTYPE = 0x1000;
+    pub const VT_ARRAY: VARTYPE = 0x2000;
+    pub const VT_BYREF: VARTYPE = 0x4000;
+    pub const VT_TYPEMASK: VARTYPE = 0x0FFF;
+}
+#[doc(inline)]
+pub use vartype::*;
+
+/// The [VARIANT](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/ns-oaidl-variant)
+/// type, see [`Variant`](crate::Variant) for an owned version
+#[allow(missing_docs, non_snake_case)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct VARIANT {
+    pub vt: VARTYPE,
+    pub wReserved1: u16,
+    pub wReserved2: u16,
+    pub wReserved3: u16,
+    pub data: VARIANT_DATA,
+}
+
+/// The [PROPVARIANT](https://docs.microsoft.com/en-us/windows/win32/api/propidlbase/ns-propidlbase-propvariant)
+/// type, see [`PropVariant`](crate::PropVariant) for an owned version
+///
+/// This has the same layout as a [`VARIANT`], but supports additional types.
+#[allow(missing_docs, non_snake_case)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PROPVARIANT {
+    pub vt: VARTYPE,
+    pub wReserved1: u16,
+    pub wReserved2: u16,
+    pub wReserved3: u16,
+    pub data: VARIANT_DATA,
+}
+
+/// The value of a [`VARIANT`] or [`PROPVARIANT`], which is interpreted according to
+/// its `vt` field
+#[allow(missing_docs, non_snake_case, non_camel_case_types)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union VARIANT_DATA {
+    pub llVal: i64,
+    pub lVal: i32,
+    pub bVal: u8,
+    pub iVal: i16,
+    pub fltVal: f32,
+    pub dblVal: f64,
+    pub boolVal: VARIANT_BOOL,
+    pub scode: HRESULT,
+    pub cyVal: i64,
+    pub date: f64,
+    pub bstrVal: BSTR,
+    pub punkVal: *mut c_void,
+    pub pdispVal: *mut c_void,
+    pub parray: *mut c_void,
+    pub byref: *mut c_void,
+    pub cVal: i8,
+    pub uiVal: u16,
+    pub ulVal: u32,
+    pub ullVal: u64,
+    pub intVal: i32,
+    pub uintVal: u32,
+    pub pwszVal: *mut u16,
+    pub pszVal: *mut u8,
+    pub puuid: *mut GUID,
+    pub filetime: u64,
+    /// The largest member, a pointer to a record and its `IRecordInfo`
+    pub record: [*mut c_void; 2],
+}
+
+/// DISPID type, the identifier of a member of an `IDispatch` interface
+pub type DISPID = i32;
+/// The [`DISPID`] of the default member
+pub const DISPID_VALUE: DISPID = 0;
+/// The [`DISPID`] returned for unknown names
+pub const DISPID_UNKNOWN: DISPID = -1;
+/// The [`DISPID`] of the named argument holding the new value of a property
+pub const DISPID_PROPERTYPUT: DISPID = -3;
+
+/// `IDispatch::Invoke` flag for calling a method
+pub const DISPATCH_METHOD: u16 = 0x1;
+/// `IDispatch::Invoke` flag for getting a property
+pub const DISPATCH_PROPERTYGET: u16 = 0x2;
+/// `IDispatch::Invoke` flag for setting a property
+pub const DISPATCH_PROPERTYPUT: u16 = 0x4;
+/// `IDispatch::Invoke` flag for setting a property by reference
+pub const DISPATCH_PROPERTYPUTREF: u16 = 0x8;
+
+/// The arguments of `IDispatch::Invoke`, in reverse order
+#[allow(missing_docs, non_snake_case)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct DISPPARAMS {
+    pub rgvarg: *mut VARIANT,
+    pub rgdispidNamedArgs: *mut DISPID,
+    pub cArgs: u32,
+    pub cNamedArgs: u32,
+}
+
+/// The description of an exception raised by `IDispatch::Invoke`
+#[allow(missing_docs, non_snake_case)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct EXCEPINFO {
+    pub wCode: u16,
+    pub wReserved: u16,
+    pub bstrSource: BSTR,
+    pub bstrDescription: BSTR,
+    pub bstrHelpFile: BSTR,
+    pub dwHelpContext: u32,
+    pub pvReserved: *mut c_void,
+    pub pfnDeferredFillIn: Option<unsafe extern "system" fn(*mut EXCEPINFO) -> HRESULT>,
+    pub scode: HRESULT,
+}
+
+/// The bounds of one dimension of a [`SAFEARRAY`]
+#[allow(missing_docs, non_snake_case)]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SAFEARRAYBOUND {
+    pub cElements: u32,
+    pub lLbound: i32,
+}
+
+/// The [SAFEARRAY](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/ns-oaidl-safearray)
+/// type, see [`SafeArray`](crate::SafeArray) for an owned version
+///
+/// `rgsabound` holds `cDims` bounds, in the reverse order of the dimensions passed to
+/// `SafeArrayCreate`.
+#[allow(missing_docs, non_snake_case)]
+#[repr(C)]
+pub struct SAFEARRAY {
+    pub cDims: u16,
+    pub fFeatures: u16,
+    pub cbElements: u32,
+    pub cLocks: u32,
+    pub pvData: *mut c_void,
+    pub rgsabound: [SAFEARRAYBOUND; 1],
+}
+
+/// Features of a [`SAFEARRAY`]
+#[allow(missing_docs)]
+mod fadf {
+    pub const FADF_AUTO: u16 = 0x1;
+    pub const FADF_STATIC: u16 = 0x2;
+    pub const FADF_EMBEDDED: u16 = 0x4;
+    pub const FADF_FIXEDSIZE: u16 = 0x10;
+    pub const FADF_RECORD: u16 = 0x20;
+    pub const FADF_HAVEIID: u16 = 0x40;
+    pub const FADF_HAVEVARTYPE: u16 = 0x80;
+    pub const FADF_BSTR: u16 = 0x100;
+    pub const FADF_UNKNOWN: u16 = 0x200;
+    pub const FADF_DISPATCH: u16 = 0x400;
+    pub const FADF_VARIANT: u16 = 0x800;
+}
+#[doc(inline)]
+pub use fadf::*;
+
 /// A globally unique identifier
 #[allow(missing_docs)]
 #[repr(C)]
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub struct GUID {
     pub data1: u32,
     pub data2: u16,
@@ -89,6 +343,274 @@ impl core::fmt::Display for GUID {
     }
 }
 
+impl GUID {
+    /// Parse a GUID from its textual representation.
+    ///
+    /// Both the braced (`{EFF8970E-C50F-45E0-9284-291CE5A6F771}`) and the bare
+    /// (`EFF8970E-C50F-45E0-9284-291CE5A6F771`) forms are accepted. Hex digits
+    /// may be upper or lower case.
+    ///
+    /// This is a `const fn` so it can be used to build constants. See the
+    /// [`guid!`](crate::guid) macro for a convenient way of doing so.
+    pub const fn parse(s: &str) -> Result<GUID, GuidParseError> {
+        let bytes = s.as_bytes();
+        let mut start = 0;
+        let mut end = bytes.len();
+        if end > 0 && bytes[0] == b'{' {
+            if bytes[end - 1] != b'}' {
+                return Err(GuidParseError::UnmatchedBrace);
+            }
+            start = 1;
+            end -= 1;
+        } else if end > 0 && bytes[end - 1] == b'}' {
+            return Err(GuidParseError::UnmatchedBrace);
+        }
+
+        // The number of hex digits in each of the `-` delimited parts
+        const PART_LENGTHS: [usize; 5] = [8, 4, 4, 4, 12];
+        let mut values = [0u64; 5];
+        let mut index = 0;
+        let mut position = start;
+        while index < PART_LENGTHS.len() {
+            if position > end {
+                return Err(GuidParseError::MissingPart { index });
+            }
+            let mut part_end = position;
+            while part_end < end && bytes[part_end] != b'-' {
+                part_end += 1;
+            }
+            let length = part_end - position;
+            if length != PART_LENGTHS[index] {
+                return Err(GuidParseError::InvalidLength {
+                    index,
+                    expected: PART_LENGTHS[index],
+                    actual: length,
+                });
+            }
+            let mut value = 0u64;
+            let mut offset = 0;
+            while offset < length {
+                let digit = match hex_digit(bytes[position + offset]) {
+                    Some(digit) => digit,
+                    None => return Err(GuidParseError::InvalidHexDigit { index, offset }),
+                };
+                value = (value << 4) | digit as u64;
+                offset += 1;
+            }
+            values[index] = value;
+            index += 1;
+            // Skip over the `-` delimiter
+            position = part_end + 1;
+        }
+        if position <= end {
+            return Err(GuidParseError::TrailingCharacters);
+        }
+
+        let data4 = (values[3] << 48) | values[4];
+        Ok(GUID {
+            data1: values[0] as u32,
+            data2: values[1] as u16,
+            data3: values[2] as u16,
+            data4: [
+                (data4 >> 56) as u8,
+                (data4 >> 48) as u8,
+                (data4 >> 40) as u8,
+                (data4 >> 32) as u8,
+                (data4 >> 24) as u8,
+                (data4 >> 16) as u8,
+                (data4 >> 8) as u8,
+                data4 as u8,
+            ],
+        })
+    }
+}
+
+const fn hex_digit(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+impl core::str::FromStr for GUID {
+    type Err = GuidParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GUID::parse(s)
+    }
+}
+
+/// An error encountered while parsing a [`GUID`]
+///
+/// Part indices refer to the `-` delimited groups of hex digits, starting at 0.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GuidParseError {
+    /// The GUID starts with `{` but does not end with `}` or vice versa
+    UnmatchedBrace,
+    /// The part at `index` is missing
+    MissingPart {
+        /// The index of the missing part
+        index: usize,
+    },
+    /// The part at `index` does not have the expected number of hex digits
+    InvalidLength {
+        /// The index of the offending part
+        index: usize,
+        /// The number of characters the part must have
+        expected: usize,
+        /// The number of characters the part actually has
+        actual: usize,
+    },
+    /// The character at `offset` within the part at `index` is not a hex digit
+    InvalidHexDigit {
+        /// The index of the offending part
+        index: usize,
+        /// The offset of the offending character within the part
+        offset: usize,
+    },
+    /// There are more than five parts
+    TrailingCharacters,
+}
+
+impl GuidParseError {
+    /// A short description of the error which can be used in `const` contexts
+    pub const fn description(&self) -> &'static str {
+        match self {
+            GuidParseError::UnmatchedBrace => "The GUID has unmatched braces",
+            GuidParseError::MissingPart { .. } => "The GUID is missing a part",
+            GuidParseError::InvalidLength { .. } => "The GUID has a part of invalid length",
+            GuidParseError::InvalidHexDigit { .. } => "The GUID contains an invalid hex digit",
+            GuidParseError::TrailingCharacters => "The GUID has trailing characters",
+        }
+    }
+
+    /// The full message of the error, including the position of the offending
+    /// part, which can be built in `const` contexts
+    #[doc(hidden)]
+    pub const fn message(&self) -> ConstMessage {
+        let message = ConstMessage::new();
+        match *self {
+            GuidParseError::MissingPart { index } => message
+                .push("The GUID is missing the part at index ")
+                .push_number(index),
+            GuidParseError::InvalidLength {
+                index,
+                expected,
+                actual,
+            } => message
+                .push("The GUID part at index ")
+                .push_number(index)
+                .push(" must be ")
+                .push_number(expected)
+                .push(" characters long but was ")
+                .push_number(actual)
+                .push(" characters"),
+            GuidParseError::InvalidHexDigit { index, offset } => message
+                .push("The GUID part at index ")
+                .push_number(index)
+                .push(" has an invalid hex digit at offset ")
+                .push_number(offset),
+            _ => message.push(self.description()),
+        }
+    }
+}
+
+/// An ASCII string built in a `const` context, where `format!` is not available
+///
+/// Text beyond the capacity of the buffer is dropped.
+#[doc(hidden)]
+pub struct ConstMessage {
+    bytes: [u8; 128],
+    len: usize,
+}
+
+impl ConstMessage {
+    const fn new() -> Self {
+        Self {
+            bytes: [0; 128],
+            len: 0,
+        }
+    }
+
+    const fn push(mut self, s: &str) -> Self {
+        let s = s.as_bytes();
+        let mut i = 0;
+        while i < s.len() && self.len < self.bytes.len() {
+            self.bytes[self.len] = s[i];
+            self.len += 1;
+            i += 1;
+        }
+        self
+    }
+
+    const fn push_number(self, n: usize) -> Self {
+        let mut digits = [0; 20];
+        let mut count = 0;
+        let mut n = n;
+        loop {
+            digits[digits.len() - 1 - count] = b'0' + (n % 10) as u8;
+            count += 1;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        let mut digits: &[u8] = &digits;
+        while digits.len() > count {
+            if let [_, rest @ ..] = digits {
+                digits = rest;
+            }
+        }
+        // SAFETY: the digits are ASCII
+        self.push(unsafe { core::str::from_utf8_unchecked(digits) })
+    }
+
+    /// The message
+    pub const fn as_str(&self) -> &str {
+        let mut bytes: &[u8] = &self.bytes;
+        while bytes.len() > self.len {
+            if let [rest @ .., _] = bytes {
+                bytes = rest;
+            }
+        }
+        // SAFETY: the messages of `GuidParseError` are ASCII, so truncating them
+        // cannot split a UTF-8 sequence
+        unsafe { core::str::from_utf8_unchecked(bytes) }
+    }
+}
+
+impl core::fmt::Display for GuidParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.message().as_str())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for GuidParseError {}
+
+/// Create a [`CLSID`] (or any other [`GUID`]) constant from a string literal
+///
+/// The GUID is parsed at compile time with [`GUID::parse`] so an invalid GUID
+/// results in a compile error.
+///
+/// # Example
+/// ```rust
+/// const CLSID_CAT_CLASS: com::CLSID = com::guid!("C5F45CBC-4439-418C-A9F9-05AC67525E43");
+/// assert_eq!(CLSID_CAT_CLASS.data1, 0xC5F45CBC);
+/// ```
+#[macro_export]
+macro_rules! guid {
+    ($guid:literal) => {{
+        const GUID: $crate::sys::CLSID = match $crate::sys::GUID::parse($guid) {
+            ::core::result::Result::Ok(guid) => guid,
+            ::core::result::Result::Err(e) => ::core::panic!("{}", e.message().as_str()),
+        };
+        GUID
+    }};
+}
+
 #[cfg(windows)]
 #[link(name = "ole32")]
 #[allow(missing_docs)]
@@ -133,4 +655,158 @@ extern "system" {
         ppv: *mut *mut c_void,
     ) -> HRESULT;
     pub fn CoUninitialize();
+    pub fn CoGetApartmentType(pAptType: *mut i32, pAptQualifier: *mut i32) -> HRESULT;
+}
+
+#[cfg(windows)]
+#[link(name = "oleaut32")]
+#[allow(missing_docs)]
+extern "system" {
+    pub fn SysAllocStringLen(strIn: *const u16, ui: u32) -> BSTR;
+    pub fn SysFreeString(bstrString: BSTR);
+    pub fn SysStringLen(pbstr: BSTR) -> u32;
+    pub fn SetErrorInfo(dwReserved: u32, perrinfo: *mut c_void) -> HRESULT;
+    pub fn GetErrorInfo(dwReserved: u32, pperrinfo: *mut *mut c_void) -> HRESULT;
+}
+
+#[cfg(windows)]
+#[link(name = "oleaut32")]
+#[allow(missing_docs)]
+extern "system" {
+    pub fn VariantClear(pvarg: *mut VARIANT) -> HRESULT;
+    pub fn VariantCopy(pvargDest: *mut VARIANT, pvargSrc: *const VARIANT) -> HRESULT;
+    pub fn SafeArrayCreate(
+        vt: VARTYPE,
+        cDims: u32,
+        rgsabound: *const SAFEARRAYBOUND,
+    ) -> *mut SAFEARRAY;
+    pub fn SafeArrayDestroy(psa: *mut SAFEARRAY) -> HRESULT;
+    pub fn SafeArrayCopy(psa: *mut SAFEARRAY, ppsaOut: *mut *mut SAFEARRAY) -> HRESULT;
+    pub fn SafeArrayLock(psa: *mut SAFEARRAY) -> HRESULT;
+    pub fn SafeArrayUnlock(psa: *mut SAFEARRAY) -> HRESULT;
+    pub fn SafeArrayGetVartype(psa: *mut SAFEARRAY, pvt: *mut VARTYPE) -> HRESULT;
+}
+
+#[cfg(windows)]
+#[link(name = "ole32")]
+#[allow(missing_docs)]
+extern "system" {
+    pub fn PropVariantClear(pvar: *mut PROPVARIANT) -> HRESULT;
+    pub fn PropVariantCopy(pvarDest: *mut PROPVARIANT, pvarSrc: *const PROPVARIANT) -> HRESULT;
+    pub fn CoTaskMemAlloc(cb: usize) -> *mut c_void;
+    pub fn CoTaskMemFree(pv: *mut c_void);
+}
+
+/// HANDLE type
+#[cfg(windows)]
+pub type HANDLE = *mut c_void;
+/// An invalid handle returned by functions such as [`CreateFileW`]
+#[cfg(windows)]
+pub const INVALID_HANDLE_VALUE: HANDLE = -1isize as HANDLE;
+/// Read access
+pub const GENERIC_READ: u32 = 0x8000_0000;
+/// Write access
+pub const GENERIC_WRITE: u32 = 0x4000_0000;
+/// Open a file only if it exists
+pub const OPEN_EXISTING: u32 = 3;
+/// A pipe which can be read from and written to by both ends
+pub const PIPE_ACCESS_DUPLEX: u32 = 0x3;
+/// A byte stream pipe which blocks on reads and writes
+pub const PIPE_TYPE_BYTE: u32 = 0x0;
+/// No limit on the number of instances of a pipe
+pub const PIPE_UNLIMITED_INSTANCES: u32 = 255;
+/// Wait for a pipe until an instance is available
+pub const NMPWAIT_WAIT_FOREVER: u32 = 0xFFFF_FFFF;
+/// All instances of a pipe are busy
+pub const ERROR_PIPE_BUSY: i32 = 231;
+/// A client connected to a pipe before `ConnectNamedPipe` was called
+pub const ERROR_PIPE_CONNECTED: i32 = 535;
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+#[allow(missing_docs)]
+extern "system" {
+    pub fn CreateNamedPipeW(
+        lpName: *const u16,
+        dwOpenMode: u32,
+        dwPipeMode: u32,
+        nMaxInstances: u32,
+        nOutBufferSize: u32,
+        nInBufferSize: u32,
+        nDefaultTimeOut: u32,
+        lpSecurityAttributes: *mut c_void,
+    ) -> HANDLE;
+    pub fn ConnectNamedPipe(hNamedPipe: HANDLE, lpOverlapped: *mut c_void) -> BOOL;
+    pub fn WaitNamedPipeW(lpNamedPipeName: *const u16, nTimeOut: u32) -> BOOL;
+    pub fn CreateFileW(
+        lpFileName: *const u16,
+        dwDesiredAccess: u32,
+        dwShareMode: u32,
+        lpSecurityAttributes: *mut c_void,
+        dwCreationDisposition: u32,
+        dwFlagsAndAttributes: u32,
+        hTemplateFile: HANDLE,
+    ) -> HANDLE;
+}
+
+/// HWND type
+#[cfg(windows)]
+pub type HWND = *mut c_void;
+
+/// POINT type
+#[cfg(windows)]
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+#[allow(missing_docs)]
+pub struct POINT {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// MSG type, a message from the message queue of a thread
+#[cfg(windows)]
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+#[allow(missing_docs, non_snake_case)]
+pub struct MSG {
+    pub hwnd: HWND,
+    pub message: u32,
+    pub wParam: usize,
+    pub lParam: isize,
+    pub time: u32,
+    pub pt: POINT,
+}
+
+/// A message which does nothing, used to wake up a message loop
+pub const WM_NULL: u32 = 0x0000;
+/// A message asking a message loop to exit
+pub const WM_QUIT: u32 = 0x0012;
+/// Leave messages in the queue after `PeekMessageW`
+pub const PM_NOREMOVE: u32 = 0x0000;
+/// Remove messages from the queue with `PeekMessageW`
+pub const PM_REMOVE: u32 = 0x0001;
+
+#[cfg(windows)]
+#[link(name = "user32")]
+#[allow(missing_docs)]
+extern "system" {
+    pub fn GetMessageW(lpMsg: *mut MSG, hWnd: HWND, wMsgFilterMin: u32, wMsgFilterMax: u32)
+        -> BOOL;
+    pub fn PeekMessageW(
+        lpMsg: *mut MSG,
+        hWnd: HWND,
+        wMsgFilterMin: u32,
+        wMsgFilterMax: u32,
+        wRemoveMsg: u32,
+    ) -> BOOL;
+    pub fn TranslateMessage(lpMsg: *const MSG) -> BOOL;
+    pub fn DispatchMessageW(lpMsg: *const MSG) -> isize;
+    pub fn PostThreadMessageW(idThread: u32, Msg: u32, wParam: usize, lParam: isize) -> BOOL;
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+#[allow(missing_docs)]
+extern "system" {
+    pub fn GetCurrentThreadId() -> u32;
 }
@@ -0,0 +1,82 @@
+//! Runtime support for keeping panics from unwinding out of COM methods.
+//!
+//! This is part of the implementation of `com-rs`, and should not be used
+//! directly by application code. It is used by the `extern "system"` functions
+//! generated by the `com::class!` macro, since unwinding into a caller that is
+//! not Rust code is undefined behavior.
+//!
+//! Without the `std` feature panics cannot be caught, and the functions call
+//! the method directly. `no_std` servers are expected to abort on panic.
+
+use crate::sys::HRESULT;
+use crate::HResult;
+
+/// A return type which can report that a method panicked
+pub trait FromPanic {
+    /// The value returned when the method panics instead of returning
+    fn from_panic(hr: HRESULT) -> Self;
+}
+
+impl FromPanic for HRESULT {
+    fn from_panic(hr: HRESULT) -> Self {
+        hr
+    }
+}
+
+impl FromPanic for HResult {
+    fn from_panic(hr: HRESULT) -> Self {
+        HResult(hr)
+    }
+}
+
+/// Calls a method returning an `HRESULT`, and returns `hr` if it panics
+#[doc(hidden)]
+#[inline(always)]
+pub fn catch<R: FromPanic, F: FnOnce() -> R>(hr: HRESULT, method: F) -> R {
+    #[cfg(feature = "std")]
+    {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(method))
+            .unwrap_or_else(|_| R::from_panic(hr))
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        let _ = hr;
+        method()
+    }
+}
+
+/// Calls a method, and aborts the process if it panics
+///
+/// This is used for methods which cannot report errors, such as
+/// `IUnknown::AddRef`, and for classes declared with `#[on_panic(abort)]`.
+/// `name` is the name of the method, used in the diagnostic.
+#[doc(hidden)]
+#[inline(always)]
+pub fn abort_on_panic<R, F: FnOnce() -> R>(name: &'static str, method: F) -> R {
+    #[cfg(feature = "std")]
+    {
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(method)) {
+            Ok(result) => result,
+            Err(_) => aborted(name),
+        }
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        let _ = name;
+        method()
+    }
+}
+
+/// Aborts the process after a panic in the COM method `name`
+///
+/// This function is never inlined, to keep it out of the generated methods.
+#[cfg(feature = "std")]
+#[inline(never)]
+#[cold]
+fn aborted(name: &str) -> ! {
+    eprintln!(
+        "{} panicked and cannot return an error to its caller, aborting",
+        name
+    );
+    std::process::abort()
+}
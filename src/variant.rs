@@ -0,0 +1,1140 @@
+use alloc::string::String;
+use core::alloc::Layout;
+use core::convert::TryFrom;
+use core::ffi::c_void;
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::ptr::NonNull;
+
+use crate::interfaces::{IDispatch, IUnknown};
+use crate::sys::{self, GUID, PROPVARIANT, VARIANT, VARIANT_DATA, VARTYPE};
+use crate::{safe_array, AbiTransferable, BStr, Error, HResult, Interface};
+
+/// An owned [VARIANT](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/ns-oaidl-variant)
+///
+/// A `VARIANT` is a tagged union used by automation interfaces such as `IDispatch`.
+/// Dropping a `Variant` frees the strings and releases the interface pointers it
+/// contains, and cloning it copies them.
+///
+/// The contents of a `Variant` can be inspected through the [`VariantValue`] enum:
+///
+/// ```rust
+/// use com::{Variant, VariantValue};
+/// use std::convert::TryFrom;
+///
+/// let variant = Variant::from(42);
+/// assert_eq!(variant.value(), VariantValue::I4(42));
+/// assert_eq!(i64::try_from(&variant).unwrap(), 42);
+/// ```
+///
+/// `Variant` can be used directly as a parameter type in `interfaces!` and `class!`
+/// methods.
+#[repr(transparent)]
+pub struct Variant(VARIANT);
+
+/// The contents of a [`Variant`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariantValue {
+    /// `VT_EMPTY`
+    Empty,
+    /// `VT_NULL`
+    Null,
+    /// `VT_I1`
+    I1(i8),
+    /// `VT_I2`
+    I2(i16),
+    /// `VT_I4`
+    I4(i32),
+    /// `VT_I8`
+    I8(i64),
+    /// `VT_UI1`
+    UI1(u8),
+    /// `VT_UI2`
+    UI2(u16),
+    /// `VT_UI4`
+    UI4(u32),
+    /// `VT_UI8`
+    UI8(u64),
+    /// `VT_INT`
+    Int(i32),
+    /// `VT_UINT`
+    UInt(u32),
+    /// `VT_R4`
+    R4(f32),
+    /// `VT_R8`
+    R8(f64),
+    /// `VT_CY`, a fixed point number scaled by 10,000
+    Currency(i64),
+    /// `VT_DATE`, the number of days since 30 December 1899
+    Date(f64),
+    /// `VT_BOOL`
+    Bool(bool),
+    /// `VT_ERROR`
+    Error(HResult),
+    /// `VT_BSTR`
+    BStr(BStr),
+    /// `VT_UNKNOWN`
+    Unknown(Option<IUnknown>),
+    /// `VT_DISPATCH`
+    Dispatch(Option<IDispatch>),
+    /// Any other type, such as arrays and records
+    ///
+    /// Arrays can be converted to a [`SafeArray`](crate::SafeArray) with `TryFrom`.
+    Other(Variant),
+}
+
+impl Variant {
+    /// Create an empty (`VT_EMPTY`) variant
+    pub const fn new() -> Self {
+        Self(VARIANT {
+            vt: sys::VT_EMPTY,
+            wReserved1: 0,
+            wReserved2: 0,
+            wReserved3: 0,
+            data: VARIANT_DATA { llVal: 0 },
+        })
+    }
+
+    /// Take ownership of a raw `VARIANT`
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be a valid `VARIANT` which is not owned by anyone else. On platforms
+    /// other than Windows, only the types modelled by [`VariantValue`] are freed when
+    /// the `Variant` is dropped, and other types are copied bitwise when it is cloned.
+    pub unsafe fn from_raw(raw: VARIANT) -> Self {
+        Self(raw)
+    }
+
+    /// Give up ownership of the raw `VARIANT`
+    pub fn into_raw(self) -> VARIANT {
+        let raw = self.0;
+        core::mem::forget(self);
+        raw
+    }
+
+    /// The raw `VARIANT`
+    pub fn as_raw(&self) -> &VARIANT {
+        &self.0
+    }
+
+    /// The type tag of the variant
+    pub fn vt(&self) -> VARTYPE {
+        self.0.vt
+    }
+
+    /// Whether the variant is `VT_EMPTY`
+    pub fn is_empty(&self) -> bool {
+        self.0.vt == sys::VT_EMPTY
+    }
+
+    /// A copy of the contents of the variant
+    ///
+    /// References (`VT_BYREF`) are followed, so the result never borrows from the
+    /// referenced value.
+    pub fn value(&self) -> VariantValue {
+        if self.0.vt & sys::VT_BYREF != 0 {
+            return unsafe { by_ref_value(self.0.vt & !sys::VT_BYREF, self.0.data.byref) }
+                .unwrap_or_else(|| VariantValue::Other(self.clone()));
+        }
+        self.known_value()
+            .unwrap_or_else(|| VariantValue::Other(self.clone()))
+    }
+
+    /// A copy of the contents of the variant, or `None` if its type is not modelled
+    /// by [`VariantValue`]
+    ///
+    /// Unlike [`Variant::value`], this never copies the variant itself.
+    fn known_value(&self) -> Option<VariantValue> {
+        let data = &self.0.data;
+        unsafe {
+            match self.0.vt {
+                vt if vt == sys::VT_BYREF | sys::VT_VARIANT && !data.byref.is_null() => {
+                    (*(data.byref as *const Variant)).known_value()
+                }
+                vt if vt & sys::VT_BYREF != 0 => by_ref_value(vt & !sys::VT_BYREF, data.byref),
+                sys::VT_BSTR => Some(VariantValue::BStr(clone_bstr(data.bstrVal))),
+                sys::VT_UNKNOWN => Some(VariantValue::Unknown(clone_interface(data.punkVal))),
+                sys::VT_DISPATCH => Some(VariantValue::Dispatch(clone_interface(data.pdispVal))),
+                vt => plain_value(vt, data),
+            }
+        }
+    }
+
+    /// Copy the variant, failing if its contents cannot be copied
+    ///
+    /// This fails when an array or, on Windows, a type which is not modelled by
+    /// [`VariantValue`] cannot be copied. On other platforms, such types are copied
+    /// bitwise.
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        let mut raw = self.0;
+        unsafe {
+            match raw.vt {
+                vt if is_plain(vt) => {}
+                sys::VT_BSTR => raw.data.bstrVal = clone_bstr(raw.data.bstrVal).into_raw(),
+                sys::VT_UNKNOWN | sys::VT_DISPATCH => {
+                    raw.data.punkVal =
+                        interface_into_raw(clone_interface::<IUnknown>(raw.data.punkVal))
+                }
+                vt if vt & sys::VT_ARRAY != 0 && !raw.data.parray.is_null() => {
+                    raw.data.parray =
+                        safe_array::copy(raw.data.parray as *mut _)?.as_ptr() as *mut _
+                }
+                #[cfg(windows)]
+                _ => {
+                    let mut copy = Variant::new();
+                    HResult(sys::VariantCopy(&mut copy.0, &self.0)).ok()?;
+                    return Ok(copy);
+                }
+                #[cfg(not(windows))]
+                _ => {}
+            }
+        }
+        Ok(Self(raw))
+    }
+
+    /// The contents of the variant
+    pub fn into_value(self) -> VariantValue {
+        if self.0.vt & sys::VT_BYREF != 0 {
+            return self.value();
+        }
+        let data = self.0.data;
+        let value = unsafe {
+            match self.0.vt {
+                sys::VT_BSTR => VariantValue::BStr(BStr::from_raw(data.bstrVal)),
+                sys::VT_UNKNOWN => VariantValue::Unknown(interface_from_raw(data.punkVal)),
+                sys::VT_DISPATCH => VariantValue::Dispatch(interface_from_raw(data.pdispVal)),
+                vt => match plain_value(vt, &data) {
+                    Some(value) => value,
+                    None => return VariantValue::Other(self),
+                },
+            }
+        };
+        // Ownership of the contents has been transferred to `value`
+        core::mem::forget(self);
+        value
+    }
+}
+
+/// The types without any owned resources, which can be copied bitwise
+pub(crate) fn is_plain(vt: VARTYPE) -> bool {
+    vt & sys::VT_BYREF != 0
+        || matches!(
+            vt,
+            sys::VT_EMPTY
+                | sys::VT_NULL
+                | sys::VT_I1
+                | sys::VT_I2
+                | sys::VT_I4
+                | sys::VT_I8
+                | sys::VT_UI1
+                | sys::VT_UI2
+                | sys::VT_UI4
+                | sys::VT_UI8
+                | sys::VT_INT
+                | sys::VT_UINT
+                | sys::VT_R4
+                | sys::VT_R8
+                | sys::VT_CY
+                | sys::VT_DATE
+                | sys::VT_BOOL
+                | sys::VT_ERROR
+        )
+}
+
+/// Read the value of a type without owned resources
+unsafe fn plain_value(vt: VARTYPE, data: &VARIANT_DATA) -> Option<VariantValue> {
+    let value = match vt {
+        sys::VT_EMPTY => VariantValue::Empty,
+        sys::VT_NULL => VariantValue::Null,
+        sys::VT_I1 => VariantValue::I1(data.cVal),
+        sys::VT_I2 => VariantValue::I2(data.iVal),
+        sys::VT_I4 => VariantValue::I4(data.lVal),
+        sys::VT_I8 => VariantValue::I8(data.llVal),
+        sys::VT_UI1 => VariantValue::UI1(data.bVal),
+        sys::VT_UI2 => VariantValue::UI2(data.uiVal),
+        sys::VT_UI4 => VariantValue::UI4(data.ulVal),
+        sys::VT_UI8 => VariantValue::UI8(data.ullVal),
+        sys::VT_INT => VariantValue::Int(data.intVal),
+        sys::VT_UINT => VariantValue::UInt(data.uintVal),
+        sys::VT_R4 => VariantValue::R4(data.fltVal),
+        sys::VT_R8 => VariantValue::R8(data.dblVal),
+        sys::VT_CY => VariantValue::Currency(data.cyVal),
+        sys::VT_DATE => VariantValue::Date(data.date),
+        sys::VT_BOOL => VariantValue::Bool(data.boolVal != sys::VARIANT_FALSE),
+        sys::VT_ERROR => VariantValue::Error(HResult(data.scode)),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Read the value referenced by a `VT_BYREF` variant
+unsafe fn by_ref_value(vt: VARTYPE, ptr: *mut c_void) -> Option<VariantValue> {
+    if ptr.is_null() {
+        return None;
+    }
+    let value = match vt {
+        sys::VT_VARIANT => (*(ptr as *const Variant)).value(),
+        sys::VT_BSTR => VariantValue::BStr(clone_bstr(*(ptr as *const sys::BSTR))),
+        sys::VT_UNKNOWN => VariantValue::Unknown(clone_interface(*(ptr as *const *mut c_void))),
+        sys::VT_DISPATCH => VariantValue::Dispatch(clone_interface(*(ptr as *const *mut c_void))),
+        // Read the referenced value as if it was stored in the variant itself. All plain
+        // types are at the start of the data, so only the size of the type is read.
+        vt => {
+            let mut data = VARIANT_DATA { llVal: 0 };
+            let size = plain_size(vt)?;
+            core::ptr::copy_nonoverlapping(ptr as *const u8, &mut data as *mut _ as *mut u8, size);
+            plain_value(vt, &data)?
+        }
+    };
+    Some(value)
+}
+
+fn plain_size(vt: VARTYPE) -> Option<usize> {
+    let size = match vt {
+        sys::VT_I1 | sys::VT_UI1 => 1,
+        sys::VT_I2 | sys::VT_UI2 | sys::VT_BOOL => 2,
+        sys::VT_I4 | sys::VT_UI4 | sys::VT_INT | sys::VT_UINT | sys::VT_R4 | sys::VT_ERROR => 4,
+        sys::VT_I8 | sys::VT_UI8 | sys::VT_R8 | sys::VT_CY | sys::VT_DATE => 8,
+        _ => return None,
+    };
+    Some(size)
+}
+
+unsafe fn clone_bstr(raw: sys::BSTR) -> BStr {
+    BStr::clone(&ManuallyDrop::new(BStr::from_raw(raw)))
+}
+
+pub(crate) unsafe fn interface_from_raw<I: Interface>(ptr: *mut c_void) -> Option<I> {
+    NonNull::new(ptr as *mut _).map(I::from_abi)
+}
+
+pub(crate) unsafe fn clone_interface<I: Interface + Clone>(ptr: *mut c_void) -> Option<I> {
+    ManuallyDrop::new(interface_from_raw(ptr)).as_ref().cloned()
+}
+
+pub(crate) fn interface_into_raw<I: Interface>(interface: Option<I>) -> *mut c_void {
+    match interface {
+        Some(interface) => interface.into_abi().as_ptr() as *mut c_void,
+        None => core::ptr::null_mut(),
+    }
+}
+
+/// Build the raw contents of a variant holding `value`
+pub(crate) fn raw_value(value: VariantValue) -> (VARTYPE, VARIANT_DATA) {
+    let mut data = VARIANT_DATA { llVal: 0 };
+    let vt = match value {
+        VariantValue::Empty => sys::VT_EMPTY,
+        VariantValue::Null => sys::VT_NULL,
+        VariantValue::I1(v) => {
+            data.cVal = v;
+            sys::VT_I1
+        }
+        VariantValue::I2(v) => {
+            data.iVal = v;
+            sys::VT_I2
+        }
+        VariantValue::I4(v) => {
+            data.lVal = v;
+            sys::VT_I4
+        }
+        VariantValue::I8(v) => {
+            data.llVal = v;
+            sys::VT_I8
+        }
+        VariantValue::UI1(v) => {
+            data.bVal = v;
+            sys::VT_UI1
+        }
+        VariantValue::UI2(v) => {
+            data.uiVal = v;
+            sys::VT_UI2
+        }
+        VariantValue::UI4(v) => {
+            data.ulVal = v;
+            sys::VT_UI4
+        }
+        VariantValue::UI8(v) => {
+            data.ullVal = v;
+            sys::VT_UI8
+        }
+        VariantValue::Int(v) => {
+            data.intVal = v;
+            sys::VT_INT
+        }
+        VariantValue::UInt(v) => {
+            data.uintVal = v;
+            sys::VT_UINT
+        }
+        VariantValue::R4(v) => {
+            data.fltVal = v;
+            sys::VT_R4
+        }
+        VariantValue::R8(v) => {
+            data.dblVal = v;
+            sys::VT_R8
+        }
+        VariantValue::Currency(v) => {
+            data.cyVal = v;
+            sys::VT_CY
+        }
+        VariantValue::Date(v) => {
+            data.date = v;
+            sys::VT_DATE
+        }
+        VariantValue::Bool(v) => {
+            data.boolVal = if v {
+                sys::VARIANT_TRUE
+            } else {
+                sys::VARIANT_FALSE
+            };
+            sys::VT_BOOL
+        }
+        VariantValue::Error(v) => {
+            data.scode = v.0;
+            sys::VT_ERROR
+        }
+        VariantValue::BStr(v) => {
+            data.bstrVal = v.into_raw();
+            sys::VT_BSTR
+        }
+        VariantValue::Unknown(v) => {
+            data.punkVal = interface_into_raw(v);
+            sys::VT_UNKNOWN
+        }
+        VariantValue::Dispatch(v) => {
+            data.pdispVal = interface_into_raw(v);
+            sys::VT_DISPATCH
+        }
+        VariantValue::Other(v) => {
+            let raw = v.into_raw();
+            return (raw.vt, raw.data);
+        }
+    };
+    (vt, data)
+}
+
+impl Default for Variant {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for Variant {
+    /// Copies the variant
+    ///
+    /// This panics if the contents cannot be copied, see [`Variant::try_clone`].
+    fn clone(&self) -> Self {
+        self.try_clone().expect("copying the VARIANT failed")
+    }
+}
+
+impl Drop for Variant {
+    fn drop(&mut self) {
+        unsafe {
+            match self.0.vt {
+                vt if is_plain(vt) => {}
+                sys::VT_BSTR => drop(BStr::from_raw(self.0.data.bstrVal)),
+                sys::VT_UNKNOWN | sys::VT_DISPATCH => {
+                    drop(interface_from_raw::<IUnknown>(self.0.data.punkVal))
+                }
+                vt if vt & sys::VT_ARRAY != 0 => {
+                    safe_array::destroy(self.0.data.parray as *mut _);
+                }
+                #[cfg(windows)]
+                _ => {
+                    sys::VariantClear(&mut self.0);
+                }
+                #[cfg(not(windows))]
+                _ => {}
+            }
+        }
+    }
+}
+
+impl PartialEq for Variant {
+    /// Compares the contents of the variants, which are never equal if their type is
+    /// not modelled by [`VariantValue`]
+    fn eq(&self, other: &Self) -> bool {
+        match (self.known_value(), other.known_value()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.known_value() {
+            Some(value) => f.debug_tuple("Variant").field(&value).finish(),
+            None => f.debug_tuple("Variant").field(&self.vt()).finish(),
+        }
+    }
+}
+
+impl From<VariantValue> for Variant {
+    fn from(value: VariantValue) -> Self {
+        let (vt, data) = raw_value(value);
+        let mut variant = Variant::new();
+        variant.0.vt = vt;
+        variant.0.data = data;
+        variant
+    }
+}
+
+macro_rules! variant_from {
+    ($($t:ty => $arm:ident),* $(,)?) => {
+        $(impl From<$t> for Variant {
+            fn from(value: $t) -> Self {
+                Variant::from(VariantValue::$arm(value.into()))
+            }
+        })*
+    };
+}
+
+variant_from! {
+    i8 => I1,
+    i16 => I2,
+    i32 => I4,
+    i64 => I8,
+    u8 => UI1,
+    u16 => UI2,
+    u32 => UI4,
+    u64 => UI8,
+    f32 => R4,
+    f64 => R8,
+    bool => Bool,
+    HResult => Error,
+    BStr => BStr,
+    &str => BStr,
+    String => BStr,
+    Option<IUnknown> => Unknown,
+    Option<IDispatch> => Dispatch,
+}
+
+impl From<IUnknown> for Variant {
+    fn from(value: IUnknown) -> Self {
+        Variant::from(VariantValue::Unknown(Some(value)))
+    }
+}
+
+impl From<IDispatch> for Variant {
+    fn from(value: IDispatch) -> Self {
+        Variant::from(VariantValue::Dispatch(Some(value)))
+    }
+}
+
+fn type_mismatch() -> Error {
+    Error::new(HResult(sys::DISP_E_TYPEMISMATCH))
+}
+
+fn overflow() -> Error {
+    Error::new(HResult(sys::DISP_E_OVERFLOW))
+}
+
+impl VariantValue {
+    /// The value as a signed integer, if it is any kind of integer
+    fn as_i128(&self) -> Option<i128> {
+        let value = match *self {
+            VariantValue::I1(v) => v.into(),
+            VariantValue::I2(v) => v.into(),
+            VariantValue::I4(v) | VariantValue::Int(v) => v.into(),
+            VariantValue::I8(v) => v.into(),
+            VariantValue::UI1(v) => v.into(),
+            VariantValue::UI2(v) => v.into(),
+            VariantValue::UI4(v) | VariantValue::UInt(v) => v.into(),
+            VariantValue::UI8(v) => v.into(),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+impl TryFrom<&Variant> for Variant {
+    type Error = Error;
+
+    /// Copies the variant, failing if its contents cannot be copied, see
+    /// [`Variant::try_clone`]
+    fn try_from(variant: &Variant) -> Result<Self, Error> {
+        variant.try_clone()
+    }
+}
+
+/// Conversion out of the contents of a [`Variant`] or [`PropVariant`]
+trait FromValue: Sized {
+    fn from_value(value: VariantValue) -> Result<Self, Error>;
+}
+
+macro_rules! try_from_variant {
+    ($($t:ty),*) => {
+        $(impl TryFrom<&Variant> for $t {
+            type Error = Error;
+
+            fn try_from(variant: &Variant) -> Result<Self, Error> {
+                <$t>::from_value(variant.value())
+            }
+        }
+
+        impl TryFrom<&PropVariant> for $t {
+            type Error = Error;
+
+            fn try_from(variant: &PropVariant) -> Result<Self, Error> {
+                let value = variant.value().into_variant_value().ok_or_else(type_mismatch)?;
+                <$t>::from_value(value)
+            }
+        })*
+    };
+}
+
+try_from_variant!(
+    i8,
+    i16,
+    i32,
+    i64,
+    u8,
+    u16,
+    u32,
+    u64,
+    f32,
+    f64,
+    bool,
+    HResult,
+    BStr,
+    String,
+    Option<IUnknown>,
+    Option<IDispatch>
+);
+
+macro_rules! integer_from_value {
+    ($($t:ty),*) => {
+        $(impl FromValue for $t {
+            // Any integer type is accepted, as long as the value is in range
+            fn from_value(value: VariantValue) -> Result<Self, Error> {
+                let value = value.as_i128().ok_or_else(type_mismatch)?;
+                <$t>::try_from(value).map_err(|_| overflow())
+            }
+        })*
+    };
+}
+
+integer_from_value!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+impl FromValue for f64 {
+    fn from_value(value: VariantValue) -> Result<Self, Error> {
+        match value {
+            VariantValue::R4(v) => Ok(v.into()),
+            VariantValue::R8(v) => Ok(v),
+            _ => value.as_i128().map(|v| v as f64).ok_or_else(type_mismatch),
+        }
+    }
+}
+
+impl FromValue for f32 {
+    // Only integer types which can be represented exactly are accepted
+    fn from_value(value: VariantValue) -> Result<Self, Error> {
+        match value {
+            VariantValue::R4(v) => Ok(v),
+            VariantValue::I1(v) => Ok(v.into()),
+            VariantValue::I2(v) => Ok(v.into()),
+            VariantValue::UI1(v) => Ok(v.into()),
+            VariantValue::UI2(v) => Ok(v.into()),
+            _ => Err(type_mismatch()),
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: VariantValue) -> Result<Self, Error> {
+        match value {
+            VariantValue::Bool(v) => Ok(v),
+            _ => Err(type_mismatch()),
+        }
+    }
+}
+
+impl FromValue for HResult {
+    fn from_value(value: VariantValue) -> Result<Self, Error> {
+        match value {
+            VariantValue::Error(v) => Ok(v),
+            _ => Err(type_mismatch()),
+        }
+    }
+}
+
+impl FromValue for BStr {
+    fn from_value(value: VariantValue) -> Result<Self, Error> {
+        match value {
+            VariantValue::BStr(v) => Ok(v),
+            _ => Err(type_mismatch()),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: VariantValue) -> Result<Self, Error> {
+        let s = BStr::from_value(value)?;
+        String::try_from(&s).map_err(|_| type_mismatch())
+    }
+}
+
+impl FromValue for Option<IUnknown> {
+    fn from_value(value: VariantValue) -> Result<Self, Error> {
+        match value {
+            VariantValue::Unknown(v) => Ok(v),
+            VariantValue::Dispatch(v) => Ok(v.map(IUnknown::from)),
+            _ => Err(type_mismatch()),
+        }
+    }
+}
+
+impl FromValue for Option<IDispatch> {
+    // Objects stored as `VT_UNKNOWN` are queried for `IDispatch`
+    fn from_value(value: VariantValue) -> Result<Self, Error> {
+        match value {
+            VariantValue::Dispatch(v) => Ok(v),
+            VariantValue::Unknown(None) => Ok(None),
+            VariantValue::Unknown(Some(v)) => {
+                v.query_interface().map(Some).ok_or_else(type_mismatch)
+            }
+            _ => Err(type_mismatch()),
+        }
+    }
+}
+
+unsafe impl AbiTransferable for Variant {
+    type Abi = VARIANT;
+
+    fn get_abi(&self) -> Self::Abi {
+        self.0
+    }
+
+    /// Copies the variant, since the caller keeps ownership of it
+    ///
+    /// This is called by the `class!` shims, which cannot panic, so a variant which
+    /// cannot be copied is received as an empty variant.
+    fn from_in_param(abi: Self::Abi) -> Self {
+        let borrowed = ManuallyDrop::new(Variant(abi));
+        borrowed.try_clone().unwrap_or_default()
+    }
+}
+
+/// An owned [PROPVARIANT](https://docs.microsoft.com/en-us/windows/win32/api/propidlbase/ns-propidlbase-propvariant)
+///
+/// A `PROPVARIANT` is used by property stores and has the same layout as a `VARIANT`,
+/// but supports additional types such as `VT_LPWSTR` strings. Strings converted into a
+/// `PropVariant` are stored as `VT_LPWSTR`:
+///
+/// ```rust
+/// use com::{PropVariant, PropVariantValue};
+/// use std::convert::TryFrom;
+///
+/// let variant = PropVariant::from("hello");
+/// assert_eq!(variant.value(), PropVariantValue::LpWStr("hello".into()));
+/// assert_eq!(String::try_from(&variant).unwrap(), "hello");
+/// ```
+///
+/// On Windows, memory for the additional types is allocated with `CoTaskMemAlloc`. On
+/// other platforms, the Rust allocator is used instead.
+#[repr(transparent)]
+pub struct PropVariant(PROPVARIANT);
+
+/// The contents of a [`PropVariant`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropVariantValue {
+    /// Any type which can also be stored in a [`Variant`]
+    Variant(VariantValue),
+    /// `VT_LPWSTR`
+    LpWStr(String),
+    /// `VT_FILETIME`, the number of 100 nanosecond intervals since 1 January 1601
+    FileTime(u64),
+    /// `VT_CLSID`
+    Clsid(GUID),
+    /// Any other type, such as vectors
+    Other(PropVariant),
+}
+
+impl PropVariantValue {
+    /// The equivalent `VARIANT` value, converting `VT_LPWSTR` strings to `VT_BSTR`
+    fn into_variant_value(self) -> Option<VariantValue> {
+        match self {
+            PropVariantValue::Variant(value) => Some(value),
+            PropVariantValue::LpWStr(s) => Some(VariantValue::BStr(s.into())),
+            _ => None,
+        }
+    }
+}
+
+/// The types which are handled by [`Variant`]
+fn is_variant_type(vt: VARTYPE) -> bool {
+    is_plain(vt)
+        || vt & sys::VT_ARRAY != 0
+        || matches!(vt, sys::VT_BSTR | sys::VT_UNKNOWN | sys::VT_DISPATCH)
+}
+
+impl PropVariant {
+    /// Create an empty (`VT_EMPTY`) variant
+    pub const fn new() -> Self {
+        Self(PROPVARIANT {
+            vt: sys::VT_EMPTY,
+            wReserved1: 0,
+            wReserved2: 0,
+            wReserved3: 0,
+            data: VARIANT_DATA { llVal: 0 },
+        })
+    }
+
+    /// Take ownership of a raw `PROPVARIANT`
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be a valid `PROPVARIANT` which is not owned by anyone else. On
+    /// platforms other than Windows, only the types modelled by [`PropVariantValue`]
+    /// are freed when the `PropVariant` is dropped, and they must have been allocated
+    /// by this type. Other types are copied bitwise when the `PropVariant` is cloned.
+    pub unsafe fn from_raw(raw: PROPVARIANT) -> Self {
+        Self(raw)
+    }
+
+    /// Give up ownership of the raw `PROPVARIANT`
+    pub fn into_raw(self) -> PROPVARIANT {
+        let raw = self.0;
+        core::mem::forget(self);
+        raw
+    }
+
+    /// The raw `PROPVARIANT`
+    pub fn as_raw(&self) -> &PROPVARIANT {
+        &self.0
+    }
+
+    /// The type tag of the variant
+    pub fn vt(&self) -> VARTYPE {
+        self.0.vt
+    }
+
+    /// Whether the variant is `VT_EMPTY`
+    pub fn is_empty(&self) -> bool {
+        self.0.vt == sys::VT_EMPTY
+    }
+
+    /// A copy of the contents of the variant
+    pub fn value(&self) -> PropVariantValue {
+        match self.0.vt {
+            vt if is_variant_type(vt) => {
+                PropVariantValue::Variant(unsafe { self.as_variant() }.value())
+            }
+            _ => self
+                .known_value()
+                .unwrap_or_else(|| PropVariantValue::Other(self.clone())),
+        }
+    }
+
+    /// A copy of the contents of the variant, or `None` if its type is not modelled
+    /// by [`PropVariantValue`]
+    ///
+    /// Unlike [`PropVariant::value`], this never copies the variant itself.
+    fn known_value(&self) -> Option<PropVariantValue> {
+        let value = unsafe {
+            match self.0.vt {
+                sys::VT_LPWSTR => PropVariantValue::LpWStr(String::from_utf16_lossy(wide_str(
+                    self.0.data.pwszVal,
+                ))),
+                sys::VT_FILETIME => PropVariantValue::FileTime(self.0.data.filetime),
+                sys::VT_CLSID => PropVariantValue::Clsid(*self.0.data.puuid),
+                vt if is_variant_type(vt) => {
+                    PropVariantValue::Variant(self.as_variant().known_value()?)
+                }
+                _ => return None,
+            }
+        };
+        Some(value)
+    }
+
+    /// Copy the variant, failing if its contents cannot be copied
+    ///
+    /// This fails when an array or, on Windows, a type which is not modelled by
+    /// [`PropVariantValue`] cannot be copied. On other platforms, such types are
+    /// copied bitwise.
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        unsafe {
+            match self.0.vt {
+                sys::VT_LPWSTR | sys::VT_CLSID => Ok(PropVariant::from(self.value())),
+                vt if vt == sys::VT_FILETIME || is_plain(vt) => Ok(Self(self.0)),
+                vt if is_variant_type(vt) => Ok(PropVariant::from(self.as_variant().try_clone()?)),
+                #[cfg(windows)]
+                _ => {
+                    let mut copy = PropVariant::new();
+                    HResult(sys::PropVariantCopy(&mut copy.0, &self.0)).ok()?;
+                    Ok(copy)
+                }
+                #[cfg(not(windows))]
+                _ => Ok(Self(self.0)),
+            }
+        }
+    }
+
+    /// The contents of the variant
+    pub fn into_value(self) -> PropVariantValue {
+        match self.value() {
+            PropVariantValue::Other(_) => PropVariantValue::Other(self),
+            value => value,
+        }
+    }
+
+    /// View a variant of a type shared with `VARIANT` as a [`Variant`]
+    ///
+    /// # Safety
+    ///
+    /// The type of the variant must be handled by [`Variant`].
+    unsafe fn as_variant(&self) -> &Variant {
+        // `PROPVARIANT` and `VARIANT` have the same layout
+        &*(self as *const PropVariant as *const Variant)
+    }
+}
+
+impl Default for PropVariant {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for PropVariant {
+    /// Copies the variant
+    ///
+    /// This panics if the contents cannot be copied, see [`PropVariant::try_clone`].
+    fn clone(&self) -> Self {
+        self.try_clone().expect("copying the PROPVARIANT failed")
+    }
+}
+
+impl Drop for PropVariant {
+    fn drop(&mut self) {
+        unsafe {
+            match self.0.vt {
+                sys::VT_LPWSTR => {
+                    let s = self.0.data.pwszVal;
+                    if !s.is_null() {
+                        let len = wide_str(s).len() + 1;
+                        task_free(s as *mut u8, Layout::array::<u16>(len).unwrap());
+                    }
+                }
+                sys::VT_CLSID => {
+                    let guid = self.0.data.puuid;
+                    if !guid.is_null() {
+                        task_free(guid as *mut u8, Layout::new::<GUID>());
+                    }
+                }
+                sys::VT_FILETIME => {}
+                vt if is_variant_type(vt) => {
+                    drop(Variant::from_raw(VARIANT {
+                        vt,
+                        wReserved1: 0,
+                        wReserved2: 0,
+                        wReserved3: 0,
+                        data: self.0.data,
+                    }));
+                }
+                #[cfg(windows)]
+                _ => {
+                    sys::PropVariantClear(&mut self.0);
+                }
+                #[cfg(not(windows))]
+                _ => {}
+            }
+        }
+    }
+}
+
+impl PartialEq for PropVariant {
+    /// Compares the contents of the variants, which are never equal if their type is
+    /// not modelled by [`PropVariantValue`]
+    fn eq(&self, other: &Self) -> bool {
+        match (self.known_value(), other.known_value()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for PropVariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.known_value() {
+            Some(value) => f.debug_tuple("PropVariant").field(&value).finish(),
+            None => f.debug_tuple("PropVariant").field(&self.vt()).finish(),
+        }
+    }
+}
+
+impl From<PropVariantValue> for PropVariant {
+    fn from(value: PropVariantValue) -> Self {
+        let mut variant = PropVariant::new();
+        let data = &mut variant.0.data;
+        variant.0.vt = match value {
+            PropVariantValue::Variant(value) => return PropVariant::from(Variant::from(value)),
+            PropVariantValue::LpWStr(s) => {
+                let wide = s
+                    .encode_utf16()
+                    .chain(Some(0))
+                    .collect::<alloc::vec::Vec<_>>();
+                unsafe {
+                    let raw = task_alloc(Layout::array::<u16>(wide.len()).unwrap()) as *mut u16;
+                    core::ptr::copy_nonoverlapping(wide.as_ptr(), raw, wide.len());
+                    data.pwszVal = raw;
+                }
+                sys::VT_LPWSTR
+            }
+            PropVariantValue::FileTime(time) => {
+                data.filetime = time;
+                sys::VT_FILETIME
+            }
+            PropVariantValue::Clsid(guid) => {
+                unsafe {
+                    let raw = task_alloc(Layout::new::<GUID>()) as *mut GUID;
+                    raw.write(guid);
+                    data.puuid = raw;
+                }
+                sys::VT_CLSID
+            }
+            PropVariantValue::Other(variant) => return variant,
+        };
+        variant
+    }
+}
+
+impl From<Variant> for PropVariant {
+    fn from(variant: Variant) -> Self {
+        let raw = variant.into_raw();
+        Self(PROPVARIANT {
+            vt: raw.vt,
+            wReserved1: raw.wReserved1,
+            wReserved2: raw.wReserved2,
+            wReserved3: raw.wReserved3,
+            data: raw.data,
+        })
+    }
+}
+
+impl TryFrom<PropVariant> for Variant {
+    type Error = Error;
+
+    /// Converts the types shared by `VARIANT` and `PROPVARIANT`, failing with
+    /// `DISP_E_TYPEMISMATCH` for other types
+    fn try_from(variant: PropVariant) -> Result<Self, Error> {
+        if !is_variant_type(variant.vt()) {
+            return Err(type_mismatch());
+        }
+        let raw = variant.into_raw();
+        Ok(Variant(VARIANT {
+            vt: raw.vt,
+            wReserved1: raw.wReserved1,
+            wReserved2: raw.wReserved2,
+            wReserved3: raw.wReserved3,
+            data: raw.data,
+        }))
+    }
+}
+
+macro_rules! prop_variant_from {
+    ($($t:ty),* $(,)?) => {
+        $(impl From<$t> for PropVariant {
+            fn from(value: $t) -> Self {
+                PropVariant::from(Variant::from(value))
+            }
+        })*
+    };
+}
+
+prop_variant_from!(
+    i8,
+    i16,
+    i32,
+    i64,
+    u8,
+    u16,
+    u32,
+    u64,
+    f32,
+    f64,
+    bool,
+    HResult,
+    BStr,
+    IUnknown,
+    Option<IUnknown>,
+    IDispatch,
+    Option<IDispatch>
+);
+
+impl From<&str> for PropVariant {
+    fn from(s: &str) -> Self {
+        PropVariant::from(PropVariantValue::LpWStr(s.into()))
+    }
+}
+
+impl From<String> for PropVariant {
+    fn from(s: String) -> Self {
+        PropVariant::from(PropVariantValue::LpWStr(s))
+    }
+}
+
+impl From<GUID> for PropVariant {
+    fn from(guid: GUID) -> Self {
+        PropVariant::from(PropVariantValue::Clsid(guid))
+    }
+}
+
+unsafe impl AbiTransferable for PropVariant {
+    type Abi = PROPVARIANT;
+
+    fn get_abi(&self) -> Self::Abi {
+        self.0
+    }
+
+    /// Copies the variant, since the caller keeps ownership of it
+    ///
+    /// This is called by the `class!` shims, which cannot panic, so a variant which
+    /// cannot be copied is received as an empty variant.
+    fn from_in_param(abi: Self::Abi) -> Self {
+        let borrowed = ManuallyDrop::new(PropVariant(abi));
+        borrowed.try_clone().unwrap_or_default()
+    }
+}
+
+/// The code units of a null-terminated UTF-16 string, not including the null
+unsafe fn wide_str<'a>(s: *const u16) -> &'a [u16] {
+    if s.is_null() {
+        return &[];
+    }
+    let mut len = 0;
+    while *s.add(len) != 0 {
+        len += 1;
+    }
+    core::slice::from_raw_parts(s, len)
+}
+
+#[cfg(windows)]
+unsafe fn task_alloc(layout: Layout) -> *mut u8 {
+    let ptr = sys::CoTaskMemAlloc(layout.size()) as *mut u8;
+    if ptr.is_null() {
+        alloc::alloc::handle_alloc_error(layout);
+    }
+    ptr
+}
+
+#[cfg(windows)]
+unsafe fn task_free(ptr: *mut u8, _layout: Layout) {
+    sys::CoTaskMemFree(ptr as *mut c_void)
+}
+
+#[cfg(not(windows))]
+unsafe fn task_alloc(layout: Layout) -> *mut u8 {
+    let ptr = alloc::alloc::alloc(layout);
+    if ptr.is_null() {
+        alloc::alloc::handle_alloc_error(layout);
+    }
+    ptr
+}
+
+#[cfg(not(windows))]
+unsafe fn task_free(ptr: *mut u8, layout: Layout) {
+    alloc::alloc::dealloc(ptr, layout)
+}
@@ -0,0 +1,81 @@
+use core::ffi::c_void;
+use core::marker::PhantomData;
+
+use crate::interfaces::{IUnknown, IWeakReference, IWeakReferenceSource};
+use crate::sys::FAILED;
+use crate::Interface;
+
+/// A weak reference to a COM object
+///
+/// A `WeakRef` does not keep the object alive. [`WeakRef::upgrade`] returns a new
+/// interface pointer for as long as the object has not been destroyed, which makes
+/// it possible to break reference cycles such as the ones between an object and the
+/// event handlers it registers.
+///
+/// Only objects implementing [`IWeakReferenceSource`] support weak references. Classes
+/// declared with `com::class!` implement it when they are marked with `#[weak_ref]`.
+pub struct WeakRef<I> {
+    reference: IWeakReference,
+    _marker: PhantomData<fn() -> I>,
+}
+
+impl<I: Interface> WeakRef<I> {
+    /// Create a weak reference to the object behind `interface`
+    ///
+    /// `interface` may be any interface of the object. Returns `None` if the object
+    /// does not support weak references.
+    pub fn new<S: Interface>(interface: &S) -> Option<Self> {
+        Self::from_unknown(interface.as_iunknown())
+    }
+
+    pub(crate) fn from_unknown(unknown: &IUnknown) -> Option<Self> {
+        let source = unknown.query_interface::<IWeakReferenceSource>()?;
+        let reference = unsafe { source.GetWeakReference() }.ok()?;
+        Some(Self::from_weak_reference(reference))
+    }
+
+    /// Wrap an existing `IWeakReference`
+    pub fn from_weak_reference(reference: IWeakReference) -> Self {
+        Self {
+            reference,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get a strong reference to the object
+    ///
+    /// Returns `None` if the object has been destroyed or does not implement `I`.
+    pub fn upgrade(&self) -> Option<I> {
+        let mut result = None;
+        let hr = unsafe {
+            self.reference
+                .Resolve(&I::IID, &mut result as *mut _ as *mut *mut c_void)
+        };
+        if FAILED(hr) {
+            return None;
+        }
+        result
+    }
+
+    /// The underlying `IWeakReference`
+    pub fn as_weak_reference(&self) -> &IWeakReference {
+        &self.reference
+    }
+}
+
+impl<I> Clone for WeakRef<I> {
+    fn clone(&self) -> Self {
+        Self {
+            reference: self.reference.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<I> core::fmt::Debug for WeakRef<I> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("WeakRef")
+            .field(&self.reference.as_raw())
+            .finish()
+    }
+}
@@ -0,0 +1,78 @@
+//! The objects of `class!` are tracked with the `leak-tracking` feature
+
+use com::debug::{assert_no_leaks, live_objects};
+use com::interfaces::IUnknown;
+use com::sys::{HRESULT, NOERROR};
+use com::Interface;
+
+com::interfaces! {
+    #[uuid("2f6d8a41-93c7-4b5e-a0d2-6e1f7c9b3a01")]
+    pub unsafe interface IHolder : IUnknown {
+        fn Hold(&self, object: IUnknown) -> HRESULT;
+    }
+}
+
+com::class! {
+    pub class Holder : IHolder {
+        held: std::cell::RefCell<Vec<IUnknown>>,
+    }
+
+    impl IHolder for Holder {
+        fn Hold(&self, object: IUnknown) -> HRESULT {
+            self.held.borrow_mut().push(object);
+            NOERROR
+        }
+    }
+}
+
+com::class! {
+    #[no_class_factory]
+    pub class Leaf : IHolder {}
+
+    impl IHolder for Leaf {
+        fn Hold(&self, _object: IUnknown) -> HRESULT {
+            NOERROR
+        }
+    }
+}
+
+fn address<I: Interface>(interface: &I) -> usize {
+    interface.as_iunknown().as_raw().as_ptr() as usize
+}
+
+#[test]
+fn leak_tracking() {
+    assert_no_leaks();
+
+    // Objects are tracked from their allocation until they are freed
+    let holder = Holder::allocate(Default::default())
+        .query_interface::<IHolder>()
+        .unwrap();
+    let leaf = Leaf::allocate().query_interface::<IUnknown>().unwrap();
+    let objects = live_objects();
+    assert_eq!(objects.len(), 2);
+    assert!(objects[0].class.ends_with("::Holder"));
+    assert_eq!(objects[0].address, address(&holder));
+    assert!(objects[1].class.ends_with("::Leaf"));
+    assert_eq!(objects[1].address, address(&leaf));
+
+    // Objects freed by other objects are removed as well
+    unsafe { holder.Hold(leaf.clone()) };
+    drop(leaf);
+    assert_eq!(live_objects().len(), 2);
+    drop(holder);
+    assert!(live_objects().is_empty());
+    assert_no_leaks();
+
+    // Leaked objects are reported with their class and address
+    let leaked = Leaf::allocate();
+    let message = std::panic::catch_unwind(assert_no_leaks).unwrap_err();
+    let message = message.downcast::<String>().unwrap();
+    let expected = format!(
+        "1 COM object(s) leaked:\n  leak_tracking::Leaf at {:#x}",
+        &**leaked as *const Leaf as usize
+    );
+    assert!(message.starts_with(&expected), "{}", message);
+    drop(leaked);
+    assert_no_leaks();
+}
@@ -0,0 +1,153 @@
+//! The `IUnknown` calls of `class!` objects are reported with the
+//! `refcount-tracing` feature
+
+use com::debug::{history, record_history, set_refcount_hook, Operation, RefCountEvent};
+use com::interfaces::IUnknown;
+use com::production::Class;
+use com::sys::{E_NOINTERFACE, HRESULT, NOERROR, S_OK};
+use com::Interface;
+use std::process::Command;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+com::interfaces! {
+    #[uuid("8c2e4f61-5a3b-4d7e-9f10-3b6a2d8e1c01")]
+    pub unsafe interface IAnimal : IUnknown {
+        fn Eat(&self) -> HRESULT;
+    }
+
+    #[uuid("8c2e4f61-5a3b-4d7e-9f10-3b6a2d8e1c02")]
+    pub unsafe interface IDomestic : IUnknown {
+        fn Train(&self) -> HRESULT;
+    }
+
+    #[uuid("8c2e4f61-5a3b-4d7e-9f10-3b6a2d8e1c03")]
+    pub unsafe interface IWild : IUnknown {
+        fn Hunt(&self) -> HRESULT;
+    }
+}
+
+com::class! {
+    #[no_class_factory]
+    pub class Cat : IAnimal, IDomestic {}
+
+    impl IAnimal for Cat {
+        fn Eat(&self) -> HRESULT {
+            NOERROR
+        }
+    }
+
+    impl IDomestic for Cat {
+        fn Train(&self) -> HRESULT {
+            NOERROR
+        }
+    }
+}
+
+fn address(cat: &com::production::ClassAllocation<Cat>) -> usize {
+    &***cat as *const Cat as usize
+}
+
+static EVENTS: AtomicU32 = AtomicU32::new(0);
+
+fn count_events(event: &RefCountEvent) {
+    assert!(event.class.ends_with("::Cat"));
+    EVENTS.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+fn refcount_tracing() {
+    // Hooks are called for every call
+    set_refcount_hook(Some(count_events));
+    let cat = Cat::allocate();
+    let animal = cat.query_interface::<IAnimal>().unwrap();
+    drop(animal.clone());
+    drop(animal);
+    assert_eq!(EVENTS.load(Ordering::SeqCst), 4);
+    set_refcount_hook(None);
+    drop(cat.query_interface::<IAnimal>());
+    assert_eq!(EVENTS.load(Ordering::SeqCst), 4);
+
+    // The history records the chain and the count of every call
+    record_history(4);
+    let object = address(&cat);
+    let animal = cat.query_interface::<IAnimal>().unwrap();
+    let domestic = animal.query_interface::<IDomestic>().unwrap();
+    assert!(domestic.query_interface::<IWild>().is_none());
+    drop(animal);
+    let events = history(object);
+    let calls: Vec<_> = events
+        .iter()
+        .map(|e| (e.chain, e.operation, e.count))
+        .collect();
+    let query = |iid, hr| Operation::QueryInterface { iid, hr };
+    assert_eq!(
+        calls,
+        [
+            (None, query(IAnimal::IID, S_OK), 2),
+            (Some(0), query(IDomestic::IID, S_OK), 3),
+            (Some(1), query(IWild::IID, E_NOINTERFACE), 3),
+            (Some(0), Operation::Release, 2),
+        ]
+    );
+    assert!(events.iter().all(|e| e.object == object));
+    assert_eq!(
+        events[3].to_string(),
+        format!(
+            "{} at {:#x} (chain 0): Release, count 2",
+            events[3].class, object
+        )
+    );
+
+    // The history is bounded
+    drop(domestic);
+    let events = history(object);
+    assert_eq!(events.len(), 4);
+    assert_eq!(events[3].operation, Operation::Release);
+    assert_eq!(events[3].count, 1);
+
+    // The history is forgotten when the object is released
+    let animal = cat.query_interface::<IAnimal>().unwrap();
+    drop(cat);
+    assert_eq!(history(object).len(), 4);
+    drop(animal);
+    assert!(history(object).is_empty());
+    set_refcount_hook(None);
+}
+
+/// A `Release` on an object whose count is zero panics with its history, and
+/// aborts since `Release` cannot return an error
+#[test]
+fn release_underflow() {
+    if std::env::var_os("COM_RS_RELEASE_UNDERFLOW").is_some() {
+        record_history(8);
+        let cat = Cat::allocate();
+        let animal = cat.query_interface::<IAnimal>().unwrap();
+        unsafe {
+            cat.dec_ref_count();
+            cat.dec_ref_count();
+        }
+        drop(animal);
+        unreachable!();
+    }
+
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["release_underflow", "--exact", "--nocapture"])
+        .env("COM_RS_RELEASE_UNDERFLOW", "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("IUnknown::Release called, but refcount was zero"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("::Cat at 0x"), "{}", stderr);
+    assert!(stderr.contains(", last calls:\n"), "{}", stderr);
+    assert!(
+        stderr.contains(": QueryInterface(8C2E4F61-5A3B-4D7E-9F10-3B6A2D8E1C01) = S_OK"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("Cat::Release panicked"), "{}", stderr);
+}
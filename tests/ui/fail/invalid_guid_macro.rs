@@ -0,0 +1,3 @@
+const CLSID: com::CLSID = com::guid!("C5F45CBC-4439-418C-A9F9-05AC67525EXX");
+
+fn main() {}
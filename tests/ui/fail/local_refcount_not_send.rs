@@ -0,0 +1,19 @@
+use com::interfaces::IUnknown;
+
+com::interfaces! {
+    #[uuid("4d3f6b2a-0c1e-4f7b-9a55-3e2f1c0d9b02")]
+    pub unsafe interface ICounter : IUnknown {}
+}
+
+com::class! {
+    #[refcount(local)]
+    pub class LocalCounter : ICounter {}
+
+    impl ICounter for LocalCounter {}
+}
+
+fn assert_send<T: Send>(_: T) {}
+
+fn main() {
+    assert_send(LocalCounter::allocate());
+}
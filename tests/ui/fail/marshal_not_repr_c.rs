@@ -0,0 +1,7 @@
+#[derive(com::marshal::Marshal)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+fn main() {}
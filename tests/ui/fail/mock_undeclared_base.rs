@@ -0,0 +1,16 @@
+com::interfaces! {
+    #[uuid("7b4e2d19-3c8a-4f61-9e05-1d6a3b7c2e02")]
+    pub unsafe interface IAnimal : com::interfaces::IUnknown {
+        fn Eat(&self) -> com::sys::HRESULT;
+    }
+}
+
+com::interfaces! {
+    #[uuid("7b4e2d19-3c8a-4f61-9e05-1d6a3b7c2e03")]
+    #[mock]
+    pub unsafe interface ICat : IAnimal {
+        fn IgnoreHumans(&self) -> com::sys::HRESULT;
+    }
+}
+
+fn main() {}
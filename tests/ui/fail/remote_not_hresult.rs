@@ -0,0 +1,9 @@
+com::interfaces! {
+    #[uuid("3f1c9a2e-6d4b-4e8a-b5c7-2a9d0e1f3b01")]
+    #[remote]
+    pub unsafe interface ICounter : com::interfaces::IUnknown {
+        fn Count(&self) -> u32;
+    }
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+com::interfaces! {
+    #[uuid("3f1c9a2e-6d4b-4e8a-b5c7-2a9d0e1f3b02")]
+    #[remote]
+    pub unsafe interface IBuffer : com::interfaces::IUnknown {
+        fn Data(&self, data: *mut *mut u8) -> com::sys::HRESULT;
+    }
+}
+
+fn main() {}
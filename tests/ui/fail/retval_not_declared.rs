@@ -0,0 +1,20 @@
+use com::sys::HRESULT;
+
+com::interfaces! {
+    #[uuid("12345678-1234-1234-1234-12345678ABD1")]
+    pub unsafe interface ICounter: com::interfaces::IUnknown {
+        fn Count(&self, count: *mut u32) -> HRESULT;
+    }
+}
+
+com::class! {
+    pub class Counter: ICounter {}
+
+    impl ICounter for Counter {
+        fn Count(&self) -> Result<u32, com::Error> {
+            Ok(1)
+        }
+    }
+}
+
+fn main() {}
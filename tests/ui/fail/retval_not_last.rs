@@ -0,0 +1,8 @@
+com::interfaces! {
+    #[uuid("4ad1a0d1-6bd2-4d8c-a1c6-2d6a4bb0d6a2")]
+    pub unsafe interface IMisplaced : com::interfaces::IUnknown {
+        fn Get(&self, #[retval] value: *mut i32, flags: u32) -> com::sys::HRESULT;
+    }
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+com::interfaces! {
+    #[uuid("12345678-1234-1234-1234-12345678ABD2")]
+    pub unsafe interface ICounter: com::interfaces::IUnknown {
+        fn Count(&self, #[retval] #[pass_through] count: *mut u32) -> com::sys::HRESULT;
+    }
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+com::interfaces! {
+    #[uuid("0d6b2f4a-8c1e-4b7d-a3f5-9e2c4d6b8a04")]
+    pub unsafe interface INamed : com::interfaces::IUnknown {
+        #[safe]
+        fn SetName(&self, name: com::sys::BSTR) -> com::sys::HRESULT;
+    }
+}
+
+fn main() {}
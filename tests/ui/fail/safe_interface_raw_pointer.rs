@@ -0,0 +1,10 @@
+com::interfaces! {
+    #[safe]
+    #[uuid("0d6b2f4a-8c1e-4b7d-a3f5-9e2c4d6b8a02")]
+    pub unsafe interface IReader : com::interfaces::IUnknown {
+        fn Length(&self, #[retval] len: *mut u32) -> com::sys::HRESULT;
+        fn Read(&self, data: Option<*mut u8>) -> com::sys::HRESULT;
+    }
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+com::interfaces! {
+    #[uuid("0d6b2f4a-8c1e-4b7d-a3f5-9e2c4d6b8a01")]
+    pub unsafe interface IBuffer : com::interfaces::IUnknown {
+        #[safe]
+        fn Write(&self, data: *const u8, len: u32) -> com::sys::HRESULT;
+    }
+}
+
+fn main() {}
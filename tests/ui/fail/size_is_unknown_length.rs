@@ -0,0 +1,8 @@
+com::interfaces! {
+    #[uuid("3f1c9a2e-6d4b-4e8a-b5c7-2a9d0e1f3b03")]
+    pub unsafe interface IBuffer : com::interfaces::IUnknown {
+        fn Write(&self, #[size_is(len)] bytes: *const u8, count: u32) -> com::sys::HRESULT;
+    }
+}
+
+fn main() {}
@@ -0,0 +1,176 @@
+use com::interfaces::{IClassFactory, IUnknown};
+use com::production::{Aggregate, ClassRegistry};
+use com::sys::{CLASS_E_NOAGGREGATION, E_NOINTERFACE};
+use com::{HResult, Interface};
+use std::cell::Cell;
+use std::rc::Rc;
+
+com::interfaces! {
+    #[uuid("9bb0a8c3-5d44-4a52-9c4b-0fe4c4b2f001")]
+    pub unsafe interface IInner : IUnknown {
+        fn Value(&self) -> u32;
+    }
+
+    #[uuid("9bb0a8c3-5d44-4a52-9c4b-0fe4c4b2f002")]
+    pub unsafe interface IOuter : IUnknown {
+        fn Name(&self) -> u32;
+    }
+
+    #[uuid("9bb0a8c3-5d44-4a52-9c4b-0fe4c4b2f003")]
+    pub unsafe interface IUnrelated : IUnknown {}
+}
+
+com::class! {
+    #[aggregatable]
+    pub class Inner : IInner {
+        drops: Rc<Cell<u32>>,
+    }
+
+    impl IInner for Inner {
+        fn Value(&self) -> u32 {
+            42
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.drops.set(self.drops.get() + 1);
+    }
+}
+
+com::class! {
+    pub class Outer : IOuter {
+        #[aggregate]
+        inner: Aggregate,
+        drops: Rc<Cell<u32>>,
+    }
+
+    impl IOuter for Outer {
+        fn Name(&self) -> u32 {
+            7
+        }
+    }
+}
+
+impl Drop for Outer {
+    fn drop(&mut self) {
+        self.drops.set(self.drops.get() + 10);
+    }
+}
+
+com::class! {
+    pub class NotAggregatable : IUnrelated {}
+
+    impl IUnrelated for NotAggregatable {}
+}
+
+fn get_refcount(unknown: &IUnknown) -> u32 {
+    unsafe {
+        unknown.AddRef();
+        unknown.Release()
+    }
+}
+
+fn identity<I: Interface>(interface: &I) -> IUnknown {
+    interface.as_iunknown().query_interface::<IUnknown>().unwrap()
+}
+
+const CLSID_INNER: com::CLSID = com::guid!("9bb0a8c3-5d44-4a52-9c4b-0fe4c4b2f0c1");
+const CLSID_NOT_AGGREGATABLE: com::CLSID = com::guid!("9bb0a8c3-5d44-4a52-9c4b-0fe4c4b2f0c2");
+
+fn main() {
+    // An aggregatable class can still be used on its own
+    let drops = Rc::new(Cell::new(0));
+    let inner = Inner::allocate(drops.clone());
+    let inner_interface = inner.query_interface::<IInner>().unwrap();
+    assert_eq!(unsafe { inner_interface.Value() }, 42);
+    assert!(inner_interface.query_interface::<IOuter>().is_none());
+    drop(inner);
+    drop(inner_interface);
+    assert_eq!(drops.get(), 1);
+
+    // Aggregate an inner object created directly
+    let drops = Rc::new(Cell::new(0));
+    let outer = Outer::allocate(Aggregate::new(), drops.clone());
+    let outer_unknown = outer.query_interface::<IUnknown>().unwrap();
+    let non_delegating = unsafe { Inner::allocate_aggregated(&outer_unknown, drops.clone()) };
+    assert_eq!(get_refcount(&non_delegating), 1);
+    assert!(outer.inner.set(non_delegating).is_ok());
+
+    let outer_interface = IOuter::from(&**outer);
+    drop(outer);
+    let before = get_refcount(&outer_unknown);
+
+    // The outer object exposes the interfaces of the inner object
+    let inner_interface = outer_interface.query_interface::<IInner>().unwrap();
+    assert_eq!(unsafe { inner_interface.Value() }, 42);
+    // References to the inner interfaces are counted by the outer object
+    assert_eq!(get_refcount(&outer_unknown), before + 1);
+    let cloned = inner_interface.clone();
+    assert_eq!(get_refcount(&outer_unknown), before + 2);
+    drop(cloned);
+    assert_eq!(get_refcount(&outer_unknown), before + 1);
+
+    // QueryInterface from the inner interface goes through the outer object
+    let back = inner_interface.query_interface::<IOuter>().unwrap();
+    assert_eq!(unsafe { back.Name() }, 7);
+    assert_eq!(back, outer_interface);
+    drop(back);
+    assert!(inner_interface.query_interface::<IUnrelated>().is_none());
+
+    // Identity: every interface reports the same IUnknown
+    assert_eq!(identity(&inner_interface), outer_unknown);
+    assert_eq!(identity(&outer_interface), outer_unknown);
+
+    // Destroying the outer object destroys the inner object
+    drop(outer_unknown);
+    drop(outer_interface);
+    assert_eq!(drops.get(), 0);
+    drop(inner_interface);
+    assert_eq!(drops.get(), 11);
+
+    // Aggregation through a class factory
+    let mut registry = ClassRegistry::new();
+    registry.register::<Inner>(CLSID_INNER);
+    registry.register::<NotAggregatable>(CLSID_NOT_AGGREGATABLE);
+    let drops = Rc::new(Cell::new(0));
+    let outer = Outer::allocate(Aggregate::new(), drops.clone());
+    let outer_unknown = outer.query_interface::<IUnknown>().unwrap();
+    let error = unsafe {
+        registry.create_aggregated_instance(&CLSID_NOT_AGGREGATABLE, &outer_unknown)
+    }
+    .unwrap_err();
+    assert_eq!(error.code(), HResult(CLASS_E_NOAGGREGATION));
+
+    let factory = registry
+        .get_class_object::<IClassFactory>(&CLSID_INNER)
+        .unwrap();
+    let mut ppv = std::ptr::null_mut();
+    let hr = unsafe { factory.CreateInstance(Some(outer_unknown.clone()), &IInner::IID, &mut ppv) };
+    assert_eq!(hr, CLASS_E_NOAGGREGATION);
+    assert!(ppv.is_null());
+
+    let non_delegating =
+        unsafe { registry.create_aggregated_instance(&CLSID_INNER, &outer_unknown) }.unwrap();
+    // The non-delegating IUnknown does not delegate QueryInterface for IUnknown
+    let non_delegating_again = non_delegating.query_interface::<IUnknown>().unwrap();
+    assert_eq!(non_delegating_again, non_delegating);
+    assert_ne!(non_delegating, outer_unknown);
+    drop(non_delegating_again);
+    assert!(non_delegating.query_interface::<IOuter>().is_none());
+    outer.inner.set(non_delegating).unwrap();
+
+    let inner_interface = outer_unknown.query_interface::<IInner>().unwrap();
+    assert_eq!(identity(&inner_interface), outer_unknown);
+    assert!(inner_interface.query_interface::<IUnrelated>().is_none());
+    let mut ppv = std::ptr::null_mut();
+    let hr = unsafe { inner_interface.QueryInterface(&IUnrelated::IID, &mut ppv) };
+    assert_eq!(hr, E_NOINTERFACE);
+    drop(inner_interface);
+    drop(outer_unknown);
+    drop(factory);
+    assert_eq!(drops.get(), 0);
+    drop(outer);
+    assert_eq!(drops.get(), 10);
+}
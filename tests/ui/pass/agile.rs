@@ -0,0 +1,233 @@
+// Interfaces declared with `interfaces!` have no proxies registered with the COM
+// runtime, so they are only called across apartments on other platforms
+#![cfg_attr(windows, allow(dead_code, unused_imports))]
+
+use com::agile::{get_interface, register_interface, revoke_interface};
+use com::interfaces::IUnknown;
+use com::runtime::{spawn, ApartmentType, StaThread};
+use com::sys::{CO_E_NOTINITIALIZED, E_INVALIDARG, HRESULT, RPC_E_DISCONNECTED};
+use com::{Agile, HResult};
+use std::cell::Cell;
+use std::sync::{Arc, Mutex};
+use std::thread::ThreadId;
+
+com::interfaces! {
+    #[uuid("7d3c5e2a-0f4b-4c1e-9b8a-2e6f1d0c3a01")]
+    #[remote]
+    pub unsafe interface ICounter : IUnknown {
+        fn Increment(&self, #[retval] value: *mut u32) -> HRESULT;
+        fn IsHome(&self, #[retval] home: *mut bool) -> HRESULT;
+        fn Apply(&self, callback: ICallback, value: i32, #[retval] result: *mut i32) -> HRESULT;
+        fn Create(&self, #[retval] counter: *mut Option<ICounter>) -> HRESULT;
+    }
+
+    #[uuid("7d3c5e2a-0f4b-4c1e-9b8a-2e6f1d0c3a02")]
+    #[remote]
+    pub unsafe interface INamed : IUnknown {
+        fn Name(&self, #[retval] name: *mut u32) -> HRESULT;
+    }
+
+    #[uuid("7d3c5e2a-0f4b-4c1e-9b8a-2e6f1d0c3a03")]
+    #[remote]
+    pub unsafe interface ICallback : IUnknown {
+        fn Call(&self, value: i32, #[retval] result: *mut i32) -> HRESULT;
+    }
+}
+
+/// Records the thread an object is dropped on
+struct DropThread(Arc<Mutex<Option<ThreadId>>>);
+
+impl Drop for DropThread {
+    fn drop(&mut self) {
+        *self.0.lock().unwrap() = Some(std::thread::current().id());
+    }
+}
+
+com::class! {
+    #[no_class_factory]
+    pub class Counter : ICounter, INamed {
+        count: Cell<u32>,
+        home: ThreadId,
+        dropped: DropThread,
+    }
+
+    impl ICounter for Counter {
+        fn Increment(&self) -> Result<u32, HRESULT> {
+            self.count.set(self.count.get() + 1);
+            Ok(self.count.get())
+        }
+
+        fn IsHome(&self) -> Result<bool, HRESULT> {
+            Ok(std::thread::current().id() == self.home)
+        }
+
+        fn Apply(&self, callback: ICallback, value: i32) -> Result<i32, com::Error> {
+            unsafe { callback.Call(value) }
+        }
+
+        fn Create(&self) -> Result<Option<ICounter>, HRESULT> {
+            Ok(new_counter(Default::default()).query_interface())
+        }
+    }
+
+    impl INamed for Counter {
+        fn Name(&self) -> Result<u32, HRESULT> {
+            Ok(7)
+        }
+    }
+}
+
+com::class! {
+    pub class Doubler : ICallback {}
+
+    impl ICallback for Doubler {
+        fn Call(&self, value: i32) -> Result<i32, HRESULT> {
+            Ok(value * 2)
+        }
+    }
+}
+
+fn new_counter(dropped: Arc<Mutex<Option<ThreadId>>>) -> ICounter {
+    let counter = Counter::allocate(Cell::new(0), std::thread::current().id(), DropThread(dropped));
+    counter.query_interface().unwrap()
+}
+
+fn new_doubler() -> ICallback {
+    Doubler::allocate().query_interface().unwrap()
+}
+
+#[cfg(windows)]
+fn main() {}
+
+#[cfg(not(windows))]
+fn main() {
+    let sta = StaThread::spawn().unwrap();
+    let dispatcher = sta.dispatcher().clone();
+    let dropped = Arc::new(Mutex::new(None));
+    let sta_thread = dispatcher.invoke(|| std::thread::current().id()).unwrap();
+    let agile = {
+        let dropped = dropped.clone();
+        dispatcher
+            .invoke(move || {
+                let counter = new_counter(dropped);
+                let agile = Agile::new(&counter).unwrap();
+                // The apartment of the object gets the object itself
+                assert_eq!(agile.resolve().unwrap(), counter);
+                agile
+            })
+            .unwrap()
+    };
+    let agile = Arc::new(agile);
+
+    // Other threads get proxies whose calls run on the thread of the object
+    let mta = {
+        let agile = agile.clone();
+        spawn(ApartmentType::Multithreaded, move || {
+            let counter = agile.resolve().unwrap();
+            assert_eq!(agile.resolve().unwrap(), counter);
+            assert_eq!(unsafe { counter.Increment() }, Ok(1));
+            assert_eq!(unsafe { counter.IsHome() }, Ok(true));
+
+            // Objects of the multithreaded apartment are passed as is
+            let doubler = new_doubler();
+            assert_eq!(unsafe { counter.Apply(&doubler, 21) }, Ok(42));
+
+            // Objects created by the calls are returned as proxies
+            let other = unsafe { counter.Create() }.unwrap().unwrap();
+            assert_eq!(unsafe { other.IsHome() }, Ok(true));
+            assert_eq!(unsafe { other.Increment() }, Ok(1));
+            assert_ne!(other, counter);
+
+            // Proxies are only queried for registered interfaces
+            assert!(counter.query_interface::<INamed>().is_none());
+            com::remote::register::<INamed>();
+            let named = counter.query_interface::<INamed>().unwrap();
+            assert_eq!(unsafe { named.Name() }, Ok(7));
+            assert_eq!(named.query_interface::<ICounter>(), Some(counter.clone()));
+            assert!(counter.query_interface::<ICallback>().is_none());
+        })
+    };
+    mta.join().unwrap().unwrap();
+
+    // Objects of single-threaded apartments are called on their thread, so two
+    // apartments can call each other
+    let other = StaThread::spawn().unwrap();
+    let result = {
+        let agile = agile.clone();
+        other
+            .dispatcher()
+            .invoke(move || {
+                let counter = agile.resolve().unwrap();
+                assert_eq!(unsafe { counter.Increment() }, Ok(2));
+                unsafe { counter.Apply(&new_doubler(), -4) }
+            })
+            .unwrap()
+    };
+    assert_eq!(result, Ok(-8));
+    other.join().unwrap();
+
+    // The global interface table works with cookies as well
+    let cookie = dispatcher
+        .invoke(|| {
+            let doubler = new_doubler();
+            register_interface(&doubler).unwrap()
+        })
+        .unwrap();
+    let thread = spawn(ApartmentType::Multithreaded, move || {
+        let doubler = get_interface::<ICallback>(cookie).unwrap();
+        let result = unsafe { doubler.Call(5) };
+        revoke_interface(cookie).unwrap();
+        assert_eq!(
+            get_interface::<ICallback>(cookie).unwrap_err().code(),
+            HResult(E_INVALIDARG)
+        );
+        assert_eq!(
+            revoke_interface(cookie).unwrap_err().code(),
+            HResult(E_INVALIDARG)
+        );
+        // The proxy keeps the object alive
+        assert_eq!(unsafe { doubler.Call(6) }, Ok(12));
+        result
+    });
+    assert_eq!(thread.join().unwrap().unwrap(), Ok(10));
+
+    // Threads must be in an apartment
+    let error = std::thread::spawn(move || {
+        let unknown = new_doubler().query_interface::<IUnknown>().unwrap();
+        let error = register_interface(&unknown).unwrap_err();
+        (error.code(), get_interface::<ICallback>(cookie).unwrap_err().code())
+    })
+    .join()
+    .unwrap();
+    assert_eq!(
+        error,
+        (HResult(CO_E_NOTINITIALIZED), HResult(CO_E_NOTINITIALIZED))
+    );
+
+    // The object is released on its thread once the cookie and the proxies are
+    let counter = spawn(ApartmentType::Multithreaded, {
+        let agile = agile.clone();
+        move || agile.resolve().map(|counter| Agile::new(&counter).unwrap())
+    })
+    .join()
+    .unwrap()
+    .unwrap()
+    .unwrap();
+    drop(agile);
+    dispatcher.invoke(|| ()).unwrap();
+    assert_eq!(*dropped.lock().unwrap(), None);
+    drop(counter);
+    dispatcher.invoke(|| ()).unwrap();
+    assert_eq!(*dropped.lock().unwrap(), Some(sta_thread));
+
+    // Calls fail once the apartment is gone
+    let agile = dispatcher
+        .invoke(|| Agile::new(&new_counter(Default::default())).unwrap())
+        .unwrap();
+    let thread = spawn(ApartmentType::Multithreaded, move || {
+        let counter = agile.resolve().unwrap();
+        sta.join().unwrap();
+        unsafe { counter.Increment() }.unwrap_err().code()
+    });
+    assert_eq!(thread.join().unwrap().unwrap(), HResult(RPC_E_DISCONNECTED));
+}
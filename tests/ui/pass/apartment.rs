@@ -0,0 +1,171 @@
+use com::runtime::{
+    current_apartment, deinit_apartment, init_apartment, spawn, ApartmentType, Dispatcher,
+    MessageLoop, StaThread,
+};
+use com::sys::{RPC_E_CHANGED_MODE, RPC_E_DISCONNECTED};
+use com::HResult;
+use std::cell::{Cell, RefCell};
+use std::sync::{mpsc, Arc, Mutex};
+
+com::interfaces! {
+    #[uuid("2f0b8a36-6f0c-4d5e-9a0e-3f1d5c7b9a01")]
+    pub unsafe interface ICounter : com::interfaces::IUnknown {
+        fn Increment(&self) -> u32;
+    }
+}
+
+com::class! {
+    pub class Counter : ICounter {
+        count: Cell<u32>,
+    }
+
+    impl ICounter for Counter {
+        fn Increment(&self) -> u32 {
+            self.count.set(self.count.get() + 1);
+            self.count.get()
+        }
+    }
+}
+
+thread_local! {
+    // An object living in the single-threaded apartment of a thread
+    static COUNTER: RefCell<Option<ICounter>> = RefCell::new(None);
+}
+
+fn main() {
+    // Threads start in the apartment they are spawned with
+    assert_eq!(current_apartment(), None);
+    for apartment in [ApartmentType::SingleThreaded, ApartmentType::Multithreaded] {
+        let thread = spawn(apartment, current_apartment);
+        assert_eq!(thread.join().unwrap().unwrap(), Some(apartment));
+    }
+
+    // The apartment of a thread cannot change until it is uninitialized
+    let thread = spawn(ApartmentType::Multithreaded, || {
+        init_apartment(ApartmentType::Multithreaded).unwrap();
+        deinit_apartment();
+        assert_eq!(current_apartment(), Some(ApartmentType::Multithreaded));
+        init_apartment(ApartmentType::SingleThreaded)
+            .unwrap_err()
+            .code()
+    });
+    assert_eq!(thread.join().unwrap().unwrap(), HResult(RPC_E_CHANGED_MODE));
+    let thread = spawn(ApartmentType::SingleThreaded, || {
+        deinit_apartment();
+        assert_eq!(current_apartment(), None);
+        init_apartment(ApartmentType::Multithreaded).unwrap();
+        current_apartment()
+    });
+    assert_eq!(
+        thread.join().unwrap().unwrap(),
+        Some(ApartmentType::Multithreaded)
+    );
+
+    // Objects of the apartment are only used on its thread
+    let sta = StaThread::spawn().unwrap();
+    let dispatcher = sta.dispatcher().clone();
+    assert!(!dispatcher.is_current());
+    let sta_thread = dispatcher.invoke(|| std::thread::current().id()).unwrap();
+    assert_ne!(sta_thread, std::thread::current().id());
+    assert_eq!(
+        dispatcher.invoke(current_apartment).unwrap(),
+        Some(ApartmentType::SingleThreaded)
+    );
+    dispatcher
+        .invoke(|| {
+            let counter = Counter::allocate(Cell::new(0));
+            COUNTER.with(|c| *c.borrow_mut() = counter.query_interface());
+        })
+        .unwrap();
+    let increment = || COUNTER.with(|c| unsafe { c.borrow().as_ref().unwrap().Increment() });
+    assert_eq!(dispatcher.invoke(increment).unwrap(), 1);
+    assert_eq!(dispatcher.invoke(increment).unwrap(), 2);
+
+    // Closures run in the order they are queued, from any thread
+    let order = Arc::new(Mutex::new(Vec::new()));
+    for i in 0..10 {
+        let order = order.clone();
+        dispatcher.post(move || order.lock().unwrap().push(i)).unwrap();
+    }
+    let posters: Vec<_> = (0..4)
+        .map(|_| {
+            let dispatcher = dispatcher.clone();
+            std::thread::spawn(move || dispatcher.invoke(increment).unwrap())
+        })
+        .collect();
+    let mut counts: Vec<_> = posters.into_iter().map(|t| t.join().unwrap()).collect();
+    counts.sort_unstable();
+    assert_eq!(counts, [3, 4, 5, 6]);
+    assert_eq!(*order.lock().unwrap(), (0..10).collect::<Vec<_>>());
+
+    // Invoking the dispatcher of the current thread calls the closure directly
+    let inner = dispatcher.clone();
+    assert_eq!(
+        dispatcher
+            .invoke(move || (inner.is_current(), inner.invoke(|| 42).unwrap()))
+            .unwrap(),
+        (true, 42)
+    );
+
+    // A thread with a message loop runs its closures while waiting for another
+    // apartment, which can call back into it
+    let other = StaThread::spawn().unwrap();
+    let other_dispatcher = other.dispatcher().clone();
+    let total = dispatcher
+        .invoke(move || {
+            let back = Dispatcher::current().unwrap();
+            other_dispatcher
+                .invoke(move || back.invoke(increment).unwrap() * 10)
+                .unwrap()
+        })
+        .unwrap();
+    assert_eq!(total, 70);
+    other.join().unwrap();
+
+    // Panics are propagated to the invoking thread, and the loop keeps running
+    let panic = std::panic::catch_unwind(|| dispatcher.invoke(|| panic!("invoked")));
+    assert_eq!(*panic.unwrap_err().downcast::<&str>().unwrap(), "invoked");
+    assert_eq!(dispatcher.invoke(increment).unwrap(), 8);
+
+    // Closures can no longer be queued once the thread is joined
+    let (sender, receiver) = mpsc::channel::<()>();
+    dispatcher.post(move || drop(sender)).unwrap();
+    sta.join().unwrap();
+    assert!(receiver.recv().is_err());
+    let error = dispatcher.post(|| ()).unwrap_err();
+    assert_eq!(error.code(), HResult(RPC_E_DISCONNECTED));
+    let error = dispatcher.invoke(|| ()).unwrap_err();
+    assert_eq!(error.code(), HResult(RPC_E_DISCONNECTED));
+
+    // A message loop run by hand
+    let message_loop = MessageLoop::new();
+    let dispatcher = message_loop.dispatcher();
+    assert!(dispatcher.is_current());
+    let ran = Arc::new(Mutex::new(0));
+    for _ in 0..3 {
+        let ran = ran.clone();
+        dispatcher.post(move || *ran.lock().unwrap() += 1).unwrap();
+    }
+    assert_eq!(message_loop.run_pending(), 3);
+    assert_eq!(message_loop.run_pending(), 0);
+    let waker = {
+        let (dispatcher, ran) = (dispatcher.clone(), ran.clone());
+        std::thread::spawn(move || {
+            dispatcher.post(move || *ran.lock().unwrap() += 1).unwrap();
+            dispatcher.quit();
+        })
+    };
+    message_loop.run();
+    waker.join().unwrap();
+    assert_eq!(*ran.lock().unwrap(), 4);
+    assert!(Dispatcher::current().unwrap().is_current());
+
+    // Closures left in a dropped message loop are dropped, and queuing more fails
+    let (sender, receiver) = mpsc::channel::<()>();
+    dispatcher.post(move || drop(sender)).unwrap();
+    drop(message_loop);
+    assert!(receiver.recv().is_err());
+    let error = dispatcher.post(|| ()).unwrap_err();
+    assert_eq!(error.code(), HResult(RPC_E_DISCONNECTED));
+    assert!(Dispatcher::current().is_none());
+}
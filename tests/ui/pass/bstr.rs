@@ -0,0 +1,87 @@
+use com::interfaces::IUnknown;
+use com::sys::{HRESULT, S_OK};
+use com::BStr;
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::ffi::OsString;
+
+com::interfaces! {
+    #[uuid("2d39c8a3-86f5-4f7a-8f6e-2a2b2f4b7c02")]
+    pub unsafe interface INamed : IUnknown {
+        fn SetName(&self, name: BStr) -> HRESULT;
+        fn GetName(&self, #[retval] name: *mut BStr) -> HRESULT;
+    }
+}
+
+com::class! {
+    pub class Named : INamed {
+        name: RefCell<BStr>,
+    }
+
+    impl INamed for Named {
+        fn SetName(&self, name: BStr) -> HRESULT {
+            *self.name.borrow_mut() = name;
+            S_OK
+        }
+
+        fn GetName(&self) -> Result<BStr, com::Error> {
+            Ok(self.name.borrow().clone())
+        }
+    }
+}
+
+fn main() {
+    // Conversions
+    let s = BStr::from("Hello, wörld! 🦀");
+    assert_eq!(s, "Hello, wörld! 🦀");
+    assert_eq!(s.len(), "Hello, wörld! 🦀".encode_utf16().count());
+    assert_eq!(s.to_string(), "Hello, wörld! 🦀");
+    assert_eq!(format!("{:?}", s), "\"Hello, wörld! 🦀\"");
+    assert_eq!(String::try_from(&s).unwrap(), "Hello, wörld! 🦀");
+    assert_eq!(BStr::from(String::from("abc")), BStr::from("abc"));
+    assert_eq!(BStr::from(OsString::from("abc")), "abc");
+    assert_eq!(OsString::from(&BStr::from("abc")), OsString::from("abc"));
+
+    // Invalid UTF-16 is only accepted by the lossy conversions
+    let invalid = BStr::from_wide(&[0x61, 0xD800]);
+    assert!(String::try_from(&invalid).is_err());
+    assert_eq!(invalid.to_string_lossy(), "a\u{FFFD}");
+
+    // The empty string is a null BSTR
+    let empty = BStr::new();
+    assert!(empty.is_empty());
+    assert!(empty.as_ptr().is_null());
+    assert_eq!(empty, BStr::from(""));
+    assert_eq!(empty.as_wide(), &[] as &[u16]);
+
+    // Strings are null-terminated and length-prefixed, like `SysAllocString`
+    let s = BStr::from("abc");
+    unsafe {
+        assert_eq!(*s.as_ptr().add(3), 0);
+        assert_eq!(*(s.as_ptr() as *const u32).sub(1), 6);
+    }
+
+    // Clones are independent allocations
+    let clone = s.clone();
+    assert_ne!(clone.as_ptr(), s.as_ptr());
+    assert_eq!(clone, s);
+
+    // Raw round trip
+    let raw = clone.into_raw();
+    let clone = unsafe { BStr::from_raw(raw) };
+    assert_eq!(clone, "abc");
+
+    // Passing strings through COM methods
+    let named = Named::allocate(RefCell::new(BStr::new()));
+    let named = named.query_interface::<INamed>().unwrap();
+    assert_eq!(unsafe { named.GetName() }.unwrap(), "");
+    let name = BStr::from("first");
+    assert_eq!(unsafe { named.SetName(&name) }, S_OK);
+    // The caller keeps ownership of `[in]` strings
+    assert_eq!(name, "first");
+    drop(name);
+    assert_eq!(unsafe { named.GetName() }.unwrap(), "first");
+    assert_eq!(unsafe { named.SetName("second") }, S_OK);
+    assert_eq!(unsafe { named.SetName(BStr::from("third")) }, S_OK);
+    assert_eq!(unsafe { named.GetName() }.unwrap(), "third");
+}
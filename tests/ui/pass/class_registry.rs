@@ -0,0 +1,79 @@
+use com::interfaces::{IClassFactory, IUnknown};
+use com::production::ClassRegistry;
+use com::sys::{CLASS_E_CLASSNOTAVAILABLE, E_NOINTERFACE};
+use com::HResult;
+
+com::interfaces! {
+    #[uuid("a1b2c3d4-0000-4000-8000-000000000001")]
+    pub unsafe interface IAnimal : IUnknown {
+        fn Legs(&self) -> u32;
+    }
+
+    #[uuid("a1b2c3d4-0000-4000-8000-000000000002")]
+    pub unsafe interface IUnimplemented : IUnknown {}
+}
+
+com::class! {
+    pub class Cat : IAnimal {}
+
+    impl IAnimal for Cat {
+        fn Legs(&self) -> u32 {
+            4
+        }
+    }
+}
+
+com::class! {
+    pub class Bird : IAnimal {}
+
+    impl IAnimal for Bird {
+        fn Legs(&self) -> u32 {
+            2
+        }
+    }
+}
+
+const CLSID_CAT: com::CLSID = com::guid!("a1b2c3d4-0000-4000-8000-0000000000ca");
+const CLSID_BIRD: com::CLSID = com::guid!("a1b2c3d4-0000-4000-8000-0000000000b1");
+
+fn main() {
+    let mut registry = ClassRegistry::new();
+    registry.register::<Cat>(CLSID_CAT);
+    assert!(registry.contains(&CLSID_CAT));
+    assert!(!registry.contains(&CLSID_BIRD));
+
+    let cat = registry.create_instance::<IAnimal>(&CLSID_CAT).unwrap();
+    assert_eq!(unsafe { cat.Legs() }, 4);
+    let error = registry
+        .create_instance::<IUnimplemented>(&CLSID_CAT)
+        .unwrap_err();
+    assert_eq!(error.code(), HResult(E_NOINTERFACE));
+
+    let factory = registry
+        .get_class_object::<IClassFactory>(&CLSID_CAT)
+        .unwrap();
+    let cat = factory.create_instance::<IAnimal>().unwrap();
+    assert_eq!(unsafe { cat.Legs() }, 4);
+    let error = registry
+        .get_class_object::<IAnimal>(&CLSID_CAT)
+        .unwrap_err();
+    assert_eq!(error.code(), HResult(E_NOINTERFACE));
+
+    let error = registry
+        .create_instance::<IAnimal>(&CLSID_BIRD)
+        .unwrap_err();
+    assert_eq!(error.code(), HResult(CLASS_E_CLASSNOTAVAILABLE));
+    let error = registry
+        .get_class_object::<IClassFactory>(&CLSID_BIRD)
+        .unwrap_err();
+    assert_eq!(error.code(), HResult(CLASS_E_CLASSNOTAVAILABLE));
+
+    // Registering again replaces the previous registration
+    registry.register::<Bird>(CLSID_CAT);
+    let bird = registry.create_instance::<IAnimal>(&CLSID_CAT).unwrap();
+    assert_eq!(unsafe { bird.Legs() }, 2);
+
+    assert!(registry.unregister(&CLSID_CAT));
+    assert!(!registry.unregister(&CLSID_CAT));
+    assert!(registry.create_instance::<IAnimal>(&CLSID_CAT).is_err());
+}
@@ -0,0 +1,219 @@
+use com::interfaces::{IDispatch, IUnknown};
+use com::sys::{
+    DISPATCH_METHOD, DISPATCH_PROPERTYGET, DISPATCH_PROPERTYPUT, DISP_E_BADPARAMCOUNT,
+    DISP_E_MEMBERNOTFOUND, DISP_E_OVERFLOW, DISP_E_TYPEMISMATCH, DISP_E_UNKNOWNNAME, E_FAIL, GUID,
+    HRESULT, S_OK,
+};
+use com::{BStr, HResult, Variant, VariantValue};
+use std::cell::Cell;
+use std::convert::TryFrom;
+
+com::interfaces! {
+    #[uuid("2d39c8a3-86f5-4f7a-8f6e-2a2b2f4b7c05")]
+    pub unsafe interface ICalculator : IUnknown {
+        fn Add(&self, a: i32, b: i32, #[retval] sum: *mut i32) -> HRESULT;
+        fn Greet(&self, name: BStr, #[retval] greeting: *mut BStr) -> HRESULT;
+        fn Reset(&self) -> HRESULT;
+    }
+
+    #[uuid("2d39c8a3-86f5-4f7a-8f6e-2a2b2f4b7c06")]
+    pub unsafe interface ICounter : IDispatch {
+        fn Increment(&self, #[retval] value: *mut u32) -> HRESULT;
+    }
+
+    #[uuid("2d39c8a3-86f5-4f7a-8f6e-2a2b2f4b7c07")]
+    pub unsafe interface IAnimal : IUnknown {
+        fn Legs(&self) -> u32;
+        fn Ping(&self, hr: HResult) -> HResult;
+        fn Fill(&self, buffer: *mut u8, len: u32) -> HRESULT;
+        fn Feed(&self, food: u32);
+        fn Adopt(&self, owner: IUnknown) -> HRESULT;
+        fn Tag(&self, id: GUID) -> HRESULT;
+        fn Id(&self, #[retval] id: *mut GUID) -> HRESULT;
+    }
+}
+
+com::class! {
+    #[dispatch]
+    pub class Calculator : ICalculator {
+        resets: Cell<u32>,
+    }
+
+    impl ICalculator for Calculator {
+        fn Add(&self, a: i32, b: i32) -> Result<i32, com::Error> {
+            Ok(a + b)
+        }
+
+        fn Greet(&self, name: BStr) -> Result<BStr, com::Error> {
+            Ok(BStr::from(format!("Hello, {}!", name)))
+        }
+
+        fn Reset(&self) -> HRESULT {
+            self.resets.set(self.resets.get() + 1);
+            S_OK
+        }
+    }
+}
+
+// A dual interface, which can be called through its vtable or through `IDispatch`
+com::class! {
+    #[dispatch]
+    pub class Counter : ICounter(IDispatch) {
+        count: Cell<u32>,
+    }
+
+    impl ICounter for Counter {
+        fn Increment(&self) -> Result<u32, com::Error> {
+            self.count.set(self.count.get() + 1);
+            Ok(self.count.get())
+        }
+    }
+}
+
+// Methods which do not return an `HRESULT`, and methods taking pointers or other
+// types which cannot be converted from and to variants
+com::class! {
+    #[dispatch]
+    pub class Dog : IAnimal {
+        food: Cell<u32>,
+    }
+
+    impl IAnimal for Dog {
+        fn Legs(&self) -> u32 {
+            4
+        }
+
+        fn Ping(&self, hr: HResult) -> HResult {
+            hr
+        }
+
+        fn Fill(&self, buffer: *mut u8, len: u32) -> HRESULT {
+            unsafe { std::ptr::write_bytes(buffer, 0, len as usize) };
+            S_OK
+        }
+
+        fn Feed(&self, food: u32) {
+            self.food.set(self.food.get() + food);
+        }
+
+        fn Adopt(&self, _owner: IUnknown) -> HRESULT {
+            S_OK
+        }
+
+        fn Tag(&self, _id: GUID) -> HRESULT {
+            S_OK
+        }
+
+        fn Id(&self) -> Result<GUID, HRESULT> {
+            Ok(com::guid!("2d39c8a3-86f5-4f7a-8f6e-2a2b2f4b7c08"))
+        }
+    }
+}
+
+fn main() {
+    let calculator = Calculator::allocate(Cell::new(0));
+    let dispatch = calculator.query_interface::<IDispatch>().unwrap();
+
+    // Type information is not available
+    let mut count = 1;
+    assert_eq!(unsafe { dispatch.GetTypeInfoCount(&mut count) }, S_OK);
+    assert_eq!(count, 0);
+
+    // Members are numbered in declaration order and looked up ignoring case
+    assert_eq!(dispatch.get_id_of_name("Add").unwrap(), 1);
+    assert_eq!(dispatch.get_id_of_name("greet").unwrap(), 2);
+    assert_eq!(dispatch.get_id_of_name("RESET").unwrap(), 3);
+    let err = dispatch.get_id_of_name("Subtract").unwrap_err();
+    assert_eq!(err.code(), HResult(DISP_E_UNKNOWNNAME));
+
+    // Arguments are converted from variants
+    let sum = dispatch
+        .invoke(1, DISPATCH_METHOD, &[Variant::from(2), Variant::from(3i16)])
+        .unwrap();
+    assert_eq!(sum.value(), VariantValue::I4(5));
+    let greeting = dispatch
+        .invoke(2, DISPATCH_METHOD, &[Variant::from("world")])
+        .unwrap();
+    assert_eq!(greeting.value(), VariantValue::BStr("Hello, world!".into()));
+
+    // Methods without a `#[retval]` return an empty variant
+    let result = dispatch.invoke(3, DISPATCH_METHOD, &[]).unwrap();
+    assert!(result.is_empty());
+    assert_eq!(calculator.resets.get(), 1);
+
+    // Errors
+    let err = dispatch.invoke(1, DISPATCH_METHOD, &[Variant::from(2)]).unwrap_err();
+    assert_eq!(err.code(), HResult(DISP_E_BADPARAMCOUNT));
+    let err = dispatch
+        .invoke(1, DISPATCH_METHOD, &[Variant::from(2), Variant::from("three")])
+        .unwrap_err();
+    assert_eq!(err.code(), HResult(DISP_E_TYPEMISMATCH));
+    let err = dispatch
+        .invoke(1, DISPATCH_METHOD, &[Variant::from(2), Variant::from(u64::MAX)])
+        .unwrap_err();
+    assert_eq!(err.code(), HResult(DISP_E_OVERFLOW));
+    let err = dispatch.invoke(4, DISPATCH_METHOD, &[]).unwrap_err();
+    assert_eq!(err.code(), HResult(DISP_E_MEMBERNOTFOUND));
+    let err = dispatch.invoke(3, DISPATCH_PROPERTYPUT, &[]).unwrap_err();
+    assert_eq!(err.code(), HResult(DISP_E_MEMBERNOTFOUND));
+
+    // Dual interfaces
+    let counter = Counter::allocate(Cell::new(0));
+    let icounter = counter.query_interface::<ICounter>().unwrap();
+    assert_eq!(unsafe { icounter.Increment() }.unwrap(), 1);
+    let dispatch = counter.query_interface::<IDispatch>().unwrap();
+    let increment = dispatch.get_id_of_name("Increment").unwrap();
+    let value = dispatch.invoke(increment, DISPATCH_PROPERTYGET, &[]).unwrap();
+    assert_eq!(value.value(), VariantValue::UI4(2));
+    // `ICounter` derives from `IDispatch`, so it can be used as an `IDispatch` directly
+    let value = icounter.invoke(increment, DISPATCH_METHOD, &[]).unwrap();
+    assert_eq!(u32::try_from(&value).unwrap(), 3);
+
+    // Values returned by methods are converted into the result, and `HResult`s are
+    // returned by `Invoke`
+    let dog = Dog::allocate(Cell::new(0));
+    let dispatch = dog.query_interface::<IDispatch>().unwrap();
+    let legs = dispatch.invoke(1, DISPATCH_PROPERTYGET, &[]).unwrap();
+    assert_eq!(legs.value(), VariantValue::UI4(4));
+    let pong = dispatch
+        .invoke(2, DISPATCH_METHOD, &[Variant::from(HResult(S_OK))])
+        .unwrap();
+    assert!(pong.is_empty());
+    let err = dispatch
+        .invoke(2, DISPATCH_METHOD, &[Variant::from(HResult(E_FAIL))])
+        .unwrap_err();
+    assert_eq!(err.code(), HResult(E_FAIL));
+    let fed = dispatch.invoke(4, DISPATCH_METHOD, &[Variant::from(3)]).unwrap();
+    assert!(fed.is_empty());
+    assert_eq!(dog.food.get(), 3);
+    // Methods taking pointers keep their `DISPID`, but cannot be invoked
+    assert_eq!(dispatch.get_id_of_name("Fill").unwrap(), 3);
+    let err = dispatch
+        .invoke(3, DISPATCH_METHOD, &[Variant::from(0), Variant::from(0)])
+        .unwrap_err();
+    assert_eq!(err.code(), HResult(DISP_E_MEMBERNOTFOUND));
+    // So do methods taking interfaces which are not in an `Option`, or GUIDs
+    assert_eq!(dispatch.get_id_of_name("Adopt").unwrap(), 5);
+    let err = dispatch
+        .invoke(5, DISPATCH_METHOD, &[Variant::from(dispatch.clone())])
+        .unwrap_err();
+    assert_eq!(err.code(), HResult(DISP_E_MEMBERNOTFOUND));
+    let err = dispatch
+        .invoke(6, DISPATCH_METHOD, &[Variant::from(0)])
+        .unwrap_err();
+    assert_eq!(err.code(), HResult(DISP_E_MEMBERNOTFOUND));
+    let err = dispatch.invoke(7, DISPATCH_PROPERTYGET, &[]).unwrap_err();
+    assert_eq!(err.code(), HResult(DISP_E_MEMBERNOTFOUND));
+    // The check is made before the arguments are counted
+    let err = dispatch.invoke(6, DISPATCH_METHOD, &[]).unwrap_err();
+    assert_eq!(err.code(), HResult(DISP_E_MEMBERNOTFOUND));
+
+    // Dispatch interfaces in variants
+    let variant = Variant::from(dispatch.clone());
+    assert_eq!(variant.value(), VariantValue::Dispatch(Some(dispatch.clone())));
+    let unknown = Option::<IUnknown>::try_from(&variant).unwrap();
+    assert_eq!(
+        Option::<IDispatch>::try_from(&Variant::from(unknown)).unwrap(),
+        Some(dispatch)
+    );
+}
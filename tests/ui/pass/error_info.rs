@@ -0,0 +1,142 @@
+use com::interfaces::{IErrorInfo, ISupportErrorInfo, IUnknown};
+use com::sys::{E_FAIL, E_INVALIDARG, E_POINTER, HRESULT, S_FALSE, S_OK};
+use com::{Error, ErrorInfo, HResult, Interface};
+
+com::interfaces! {
+    #[uuid("5a1f3c7e-2b9d-4e60-8c4f-7d2e9b1a0c01")]
+    #[remote]
+    pub unsafe interface IParser : IUnknown {
+        fn Parse(&self, value: i32, #[retval] result: *mut u32) -> HRESULT;
+        fn Validate(&self, value: i32) -> HRESULT;
+    }
+
+    #[uuid("5a1f3c7e-2b9d-4e60-8c4f-7d2e9b1a0c02")]
+    pub unsafe interface ICounter : IUnknown {
+        fn Count(&self, #[retval] count: *mut u32) -> HRESULT;
+        fn Reset(&self, #[retval] count: *mut u32) -> HRESULT;
+    }
+}
+
+com::class! {
+    pub class Parser : IParser, ICounter {}
+
+    impl IParser for Parser {
+        fn Parse(&self, value: i32) -> Result<u32, Error> {
+            match value {
+                v if v < 0 => {
+                    let mut info = ErrorInfo::new("negative values cannot be parsed");
+                    info.source = "Parser".into();
+                    info.help_context = 7;
+                    info.guid = IParser::IID;
+                    Err(Error::with_info(HResult(E_INVALIDARG), info))
+                }
+                0 => Err(Error::new(HResult(E_FAIL))),
+                v => Ok(v as u32),
+            }
+        }
+
+        fn Validate(&self, value: i32) -> HRESULT {
+            if value < 0 {
+                Error::with_description(HResult(E_INVALIDARG), "negative").set_error_info().0
+            } else {
+                S_OK
+            }
+        }
+    }
+
+    impl ICounter for Parser {
+        fn Count(&self) -> Result<u32, HRESULT> {
+            Ok(1)
+        }
+
+        fn Reset(&self) -> Result<u32, HRESULT> {
+            Err(E_FAIL)
+        }
+    }
+}
+
+fn main() {
+    let parser = Parser::allocate().query_interface::<IParser>().unwrap();
+
+    // The error information set by the server is captured by the client wrapper
+    let error = unsafe { parser.Parse(-1) }.unwrap_err();
+    assert_eq!(error.code(), HResult(E_INVALIDARG));
+    assert_eq!(error.description(), Some("negative values cannot be parsed"));
+    let info = error.info().unwrap();
+    assert_eq!(info.source, "Parser");
+    assert_eq!(info.help_context, 7);
+    assert_eq!(info.guid, IParser::IID);
+    assert_eq!(
+        error.to_string(),
+        format!("{}: negative values cannot be parsed", Error::new(HResult(E_INVALIDARG)))
+    );
+    assert_eq!(unsafe { parser.Parse(3) }.unwrap(), 3);
+
+    // Errors without information clear the information of the thread
+    Error::with_description(HResult(E_FAIL), "stale").set_error_info();
+    let error = unsafe { parser.Parse(0) }.unwrap_err();
+    assert_eq!(error.code(), HResult(E_FAIL));
+    assert!(error.info().is_none());
+
+    // Methods returning an HRESULT set the information themselves
+    let hr = unsafe { parser.Validate(-1) };
+    assert_eq!(hr, E_INVALIDARG);
+    let error = Error::from_error_info(HResult(hr));
+    assert_eq!(error.description(), Some("negative"));
+    assert!(Error::from_error_info(HResult(hr)).info().is_none());
+
+    // The class supports error information for the interfaces returning `com::Error`
+    let support = parser.query_interface::<ISupportErrorInfo>().unwrap();
+    unsafe {
+        assert_eq!(support.InterfaceSupportsErrorInfo(&IParser::IID), S_OK);
+        assert_eq!(support.InterfaceSupportsErrorInfo(&ICounter::IID), S_FALSE);
+        assert_eq!(
+            support.InterfaceSupportsErrorInfo(std::ptr::null()),
+            E_POINTER
+        );
+    }
+    assert!(parser.query_interface::<IErrorInfo>().is_none());
+
+    // The information left by another call is not used for the interfaces which
+    // don't support it, and it is cleared
+    let counter = parser.query_interface::<ICounter>().unwrap();
+    Error::with_description(HResult(E_FAIL), "stale").set_error_info();
+    let error = unsafe { counter.Reset() }.unwrap_err();
+    assert_eq!(error.code(), HResult(E_FAIL));
+    assert!(error.info().is_none());
+    assert!(Error::from_error_info(HResult(E_FAIL)).info().is_none());
+
+    cross_apartment();
+}
+
+#[cfg(windows)]
+fn cross_apartment() {}
+
+/// Error information is passed to callers in other apartments
+#[cfg(not(windows))]
+fn cross_apartment() {
+    use com::runtime::{spawn, ApartmentType, StaThread};
+    use com::Agile;
+
+    let sta = StaThread::spawn().unwrap();
+    let agile = sta
+        .dispatcher()
+        .invoke(|| Agile::new(&Parser::allocate().query_interface::<IParser>().unwrap()))
+        .unwrap()
+        .unwrap();
+    spawn(ApartmentType::Multithreaded, move || {
+        let parser = agile.resolve().unwrap();
+        let error = unsafe { parser.Parse(-1) }.unwrap_err();
+        assert_eq!(error.code(), HResult(E_INVALIDARG));
+        assert_eq!(error.description(), Some("negative values cannot be parsed"));
+        assert_eq!(error.info().unwrap().guid, IParser::IID);
+        assert!(unsafe { parser.Parse(0) }.unwrap_err().info().is_none());
+        assert_eq!(unsafe { parser.Validate(-1) }, E_INVALIDARG);
+        let error = Error::from_error_info(HResult(E_INVALIDARG));
+        assert_eq!(error.description(), Some("negative"));
+    })
+    .join()
+    .unwrap()
+    .unwrap();
+    sta.join().unwrap();
+}
@@ -0,0 +1,69 @@
+use com::sys::{GuidParseError, GUID};
+
+const CLSID_BARE: com::CLSID = com::guid!("C5F45CBC-4439-418C-A9F9-05AC67525E43");
+const CLSID_BRACED: com::CLSID = com::guid!("{c5f45cbc-4439-418c-a9f9-05ac67525e43}");
+
+fn main() {
+    let expected = GUID {
+        data1: 0xC5F45CBC,
+        data2: 0x4439,
+        data3: 0x418C,
+        data4: [0xA9, 0xF9, 0x05, 0xAC, 0x67, 0x52, 0x5E, 0x43],
+    };
+    assert_eq!(CLSID_BARE, expected);
+    assert_eq!(CLSID_BRACED, expected);
+    assert_eq!(
+        "C5F45CBC-4439-418C-A9F9-05AC67525E43".parse::<GUID>(),
+        Ok(expected)
+    );
+    // Round trip through `Display`
+    assert_eq!(expected.to_string().parse::<GUID>(), Ok(expected));
+
+    assert_eq!(
+        GUID::parse("C5F45CBC-4439-418C-A9F9"),
+        Err(GuidParseError::MissingPart { index: 4 })
+    );
+    assert_eq!(
+        GUID::parse("C5F45CBC-4439-418C-A9F9-05AC67525E4"),
+        Err(GuidParseError::InvalidLength {
+            index: 4,
+            expected: 12,
+            actual: 11
+        })
+    );
+    assert_eq!(
+        GUID::parse("C5F45CBC-4439-418G-A9F9-05AC67525E43"),
+        Err(GuidParseError::InvalidHexDigit {
+            index: 2,
+            offset: 3
+        })
+    );
+    assert_eq!(
+        GUID::parse("{C5F45CBC-4439-418C-A9F9-05AC67525E43"),
+        Err(GuidParseError::UnmatchedBrace)
+    );
+    assert_eq!(
+        GUID::parse("C5F45CBC-4439-418C-A9F9-05AC67525E43-00"),
+        Err(GuidParseError::TrailingCharacters)
+    );
+    assert_eq!(
+        GUID::parse("").unwrap_err().to_string(),
+        "The GUID part at index 0 must be 8 characters long but was 0 characters"
+    );
+    assert_eq!(
+        GUID::parse("C5F45CBC-4439-418C-A9F9-05AC67525EXX")
+            .unwrap_err()
+            .to_string(),
+        "The GUID part at index 4 has an invalid hex digit at offset 10"
+    );
+    assert_eq!(
+        GUID::parse("C5F45CBC-4439").unwrap_err().to_string(),
+        "The GUID is missing the part at index 2"
+    );
+    assert_eq!(
+        GUID::parse("{C5F45CBC-4439-418C-A9F9-05AC67525E43")
+            .unwrap_err()
+            .to_string(),
+        "The GUID has unmatched braces"
+    );
+}
@@ -0,0 +1,76 @@
+use com::interfaces::IUnknown;
+use com::sys::{E_FAIL, E_NOINTERFACE, S_FALSE};
+use com::{Error, HResult, Severity};
+
+com::interfaces! {
+    #[uuid("5a1c6dbe-0a53-4f3e-bb0d-a4c4a1d0f1d7")]
+    pub unsafe interface ICounter : IUnknown {
+        fn Increment(&self, by: u32) -> HResult;
+        fn Check(&self, expected: HResult) -> HResult;
+    }
+}
+
+com::class! {
+    pub class Counter : ICounter {
+        count: std::cell::Cell<u32>,
+    }
+
+    impl ICounter for Counter {
+        fn Increment(&self, by: u32) -> HResult {
+            self.increment(by).into()
+        }
+
+        fn Check(&self, expected: HResult) -> HResult {
+            expected
+        }
+    }
+}
+
+impl Counter {
+    fn increment(&self, by: u32) -> Result<(), Error> {
+        if by == 0 {
+            return Err(HResult(E_FAIL).into());
+        }
+        self.count.set(self.count.get() + by);
+        Ok(())
+    }
+}
+
+fn increment_twice(counter: &ICounter, by: u32) -> Result<(), Error> {
+    unsafe {
+        counter.Increment(by).ok()?;
+        counter.Increment(by).ok()?;
+    }
+    Ok(())
+}
+
+fn main() {
+    assert_eq!(
+        std::mem::size_of::<HResult>(),
+        std::mem::size_of::<com::sys::HRESULT>()
+    );
+
+    let hr = HResult(E_NOINTERFACE);
+    assert!(hr.is_err());
+    assert_eq!(hr.severity(), Severity::Failure);
+    assert_eq!(hr.facility(), 0);
+    assert_eq!(hr.code(), 0x4002);
+    assert_eq!(hr.to_string(), "E_NOINTERFACE (0x80004002)");
+    assert_eq!(HResult(-0x7FF8_FFFB).facility(), 7);
+    assert_eq!(HResult(-0x7FF8_FFFB).to_string(), "0x80070005");
+    assert!(HResult(S_FALSE).is_ok());
+    assert_eq!(Result::<(), Error>::from(HResult::S_OK), Ok(()));
+    // Success codes are kept by the infallible conversion
+    assert_eq!(Error::from(HResult(S_FALSE)).code(), HResult(S_FALSE));
+
+    let counter = Counter::allocate(std::cell::Cell::new(0));
+    let interface = counter.query_interface::<ICounter>().unwrap();
+    assert!(increment_twice(&interface, 2).is_ok());
+    assert_eq!(counter.count.get(), 4);
+    let error = increment_twice(&interface, 0).unwrap_err();
+    assert_eq!(error.code(), HResult(E_FAIL));
+    assert_eq!(unsafe { interface.Check(HResult(E_FAIL)) }, HResult(E_FAIL));
+
+    let error: Box<dyn std::error::Error> = Box::new(error);
+    assert_eq!(error.to_string(), "COM call failed with E_FAIL (0x80004005)");
+}
@@ -0,0 +1,45 @@
+// The Rust generated by `com-idl` from `macros/support/tests/idl/clock.idl`
+#[allow(dead_code)]
+#[path = "../../../macros/support/tests/idl/clock.rs"]
+mod clock;
+
+use clock::*;
+use com::Interface;
+use std::cell::Cell;
+
+com::class! {
+    pub class Device: ID2D1Device {
+        memory: Cell<u64>,
+    }
+
+    impl ID2D1Device for Device {
+        fn SetMaximumTextureMemory(&self, maximum: u64) {
+            self.memory.set(maximum);
+        }
+
+        fn GetMaximumTextureMemory(&self) -> u64 {
+            self.memory.get()
+        }
+    }
+}
+
+fn main() {
+    assert_eq!(
+        ID2D1Factory::IID,
+        com::guid!("06152247-6f50-465a-9245-118bfd3b6007")
+    );
+    assert_eq!(IClock::IID, com::guid!("50c83a1c-e072-4c48-87b0-3630fa36a6d0"));
+    assert_eq!(CLSID_Clock, com::guid!("C5F45CBC-4439-418C-A9F9-05AC67525E43"));
+
+    assert_eq!(D2D1_UNIT_MODE_FORCE_DWORD, -1);
+    assert_eq!(CLOCK_FLAGS_ALL, 3);
+    assert_eq!(CLOCK_FLAGS_INVALID, -1);
+    assert_eq!(MAX_ALARMS, 16u32);
+    assert_eq!(std::mem::size_of::<D2D_MATRIX_3X2_F>(), 24);
+
+    let device = Device::allocate(Cell::new(0))
+        .query_interface::<ID2D1Device>()
+        .unwrap();
+    unsafe { device.SetMaximumTextureMemory(1 << 40) };
+    assert_eq!(unsafe { device.GetMaximumTextureMemory() }, 1 << 40);
+}
@@ -0,0 +1,76 @@
+use com::interfaces::IUnknown;
+use std::cell::Cell;
+use std::rc::Rc;
+
+com::interfaces! {
+    #[uuid("4d3f6b2a-0c1e-4f7b-9a55-3e2f1c0d9b01")]
+    pub unsafe interface ICounter : IUnknown {
+        fn Increment(&self) -> u32;
+    }
+}
+
+com::class! {
+    #[refcount(local)]
+    pub class LocalCounter : ICounter {
+        // Local classes may hold data which is not thread safe
+        value: Cell<u32>,
+        drops: Rc<Cell<u32>>,
+    }
+
+    impl ICounter for LocalCounter {
+        fn Increment(&self) -> u32 {
+            self.value.set(self.value.get() + 1);
+            self.value.get()
+        }
+    }
+}
+
+impl Drop for LocalCounter {
+    fn drop(&mut self) {
+        self.drops.set(self.drops.get() + 1);
+    }
+}
+
+com::class! {
+    #[refcount(atomic)]
+    pub class AtomicCounter : ICounter {}
+
+    impl ICounter for AtomicCounter {
+        fn Increment(&self) -> u32 {
+            0
+        }
+    }
+}
+
+fn get_refcount(unknown: &IUnknown) -> u32 {
+    unsafe {
+        unknown.AddRef();
+        unknown.Release()
+    }
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+fn main() {
+    assert_send_sync::<com::production::ClassAllocation<AtomicCounter>>();
+
+    let drops = Rc::new(Cell::new(0));
+    let counter = LocalCounter::allocate(Cell::new(0), drops.clone());
+    let unknown = counter.query_interface::<IUnknown>().unwrap();
+    assert_eq!(get_refcount(&unknown), 2);
+
+    let clone = counter.clone();
+    assert_eq!(get_refcount(&unknown), 3);
+    drop(clone);
+
+    let interface = unknown.query_interface::<ICounter>().unwrap();
+    assert_eq!(unsafe { interface.Increment() }, 1);
+    assert_eq!(unsafe { interface.clone().Increment() }, 2);
+    assert_eq!(get_refcount(&unknown), 3);
+
+    drop(counter);
+    drop(unknown);
+    assert_eq!(drops.get(), 0);
+    drop(interface);
+    assert_eq!(drops.get(), 1);
+}
@@ -0,0 +1,274 @@
+use com::interfaces::IUnknown;
+use com::marshal::{Decoder, Encoder, Marshal, ObjectTable};
+use com::sys::{
+    DISP_E_BADVARTYPE, E_NOTIMPL, GUID, HRESULT, IID, RPC_E_INVALID_DATA, SAFEARRAYBOUND, S_OK,
+};
+use com::{BStr, HResult, Interface, PropVariant, PropVariantValue, SafeArray, Variant, VariantValue};
+use std::cell::RefCell;
+use std::fmt::Debug;
+
+#[repr(C)]
+#[derive(Marshal, Clone, Copy, Debug, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[repr(C)]
+#[derive(Marshal, Clone, Debug, PartialEq)]
+struct Labelled<T> {
+    label: BStr,
+    value: T,
+    corners: [Point; 2],
+}
+
+#[repr(C, align(8))]
+#[derive(Marshal, Clone, Debug, PartialEq)]
+struct Pair(u8, Option<u16>);
+
+/// An object table which references the objects by their position in a list
+#[derive(Default)]
+struct Objects(RefCell<Vec<IUnknown>>);
+
+impl ObjectTable for Objects {
+    fn export(&self, object: &IUnknown, _iid: &IID, encoder: &mut Encoder<'_>) -> Result<(), com::Error> {
+        let mut objects = self.0.borrow_mut();
+        objects.push(object.clone());
+        (objects.len() as u32 - 1).marshal(encoder)
+    }
+
+    fn import(&self, _iid: &IID, decoder: &mut Decoder<'_>) -> Result<IUnknown, com::Error> {
+        let index = u32::unmarshal(decoder)? as usize;
+        self.0
+            .borrow()
+            .get(index)
+            .cloned()
+            .ok_or_else(|| HResult(RPC_E_INVALID_DATA).into())
+    }
+}
+
+com::interfaces! {
+    #[uuid("3f1c9a2e-6d4b-4e8a-b5c7-2a9d0e1f3b04")]
+    pub unsafe interface IPing : IUnknown {
+        fn Ping(&self) -> HRESULT;
+    }
+}
+
+com::class! {
+    pub class Object: IPing {}
+
+    impl IPing for Object {
+        fn Ping(&self) -> HRESULT {
+            S_OK
+        }
+    }
+}
+
+fn encode<T: Marshal>(value: &T) -> Vec<u8> {
+    let mut encoder = Encoder::new();
+    value.marshal(&mut encoder).unwrap();
+    encoder.into_bytes()
+}
+
+fn decode<T: Marshal>(bytes: &[u8]) -> Result<T, com::Error> {
+    let mut decoder = Decoder::new(bytes);
+    let value = T::unmarshal(&mut decoder)?;
+    assert!(decoder.remaining().is_empty());
+    Ok(value)
+}
+
+fn round_trip<T: Marshal + PartialEq + Debug>(value: T) {
+    let bytes = encode(&value);
+    assert_eq!(decode::<T>(&bytes).unwrap(), value);
+    // Truncated messages are rejected
+    for len in 0..bytes.len() {
+        let mut decoder = Decoder::new(&bytes[..len]);
+        assert!(T::unmarshal(&mut decoder).is_err(), "{:?} truncated to {}", value, len);
+    }
+}
+
+/// A deterministic xorshift generator for the fuzzer
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// Decode random mutations of `valid` messages as `T`, which must never panic
+fn fuzz<T: Marshal>(rng: &mut Rng, valid: &[Vec<u8>]) {
+    for _ in 0..2000 {
+        let mut bytes = valid[rng.below(valid.len())].clone();
+        for _ in 0..=rng.below(4) {
+            match rng.below(4) {
+                0 if !bytes.is_empty() => {
+                    let i = rng.below(bytes.len());
+                    bytes[i] = rng.next() as u8;
+                }
+                1 if !bytes.is_empty() => {
+                    let i = rng.below(bytes.len());
+                    bytes.truncate(i);
+                }
+                2 => bytes.push(rng.next() as u8),
+                _ => bytes.extend_from_slice(&(rng.next() as u32 | 0x8000_0000).to_le_bytes()),
+            }
+        }
+        let objects = Objects::default();
+        let mut decoder = Decoder::with_objects(&bytes, &objects);
+        let _ = T::unmarshal(&mut decoder);
+    }
+}
+
+fn main() {
+    // Numbers are little-endian without padding
+    assert_eq!(encode(&0x0102_0304u32), [4, 3, 2, 1]);
+    assert_eq!(encode(&-2i16), [0xFE, 0xFF]);
+    assert_eq!(encode(&1usize), [1, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(encode(&true), [1]);
+    assert_eq!(decode::<bool>(&[2]).unwrap_err().code(), HResult(RPC_E_INVALID_DATA));
+    assert_eq!(encode(&Some(7u8)), [1, 7]);
+    assert_eq!(encode(&None::<u8>), [0]);
+    assert_eq!(encode(&vec![1u16, 2]), [2, 0, 0, 0, 1, 0, 2, 0]);
+    assert_eq!(encode(&[1u8, 2, 3]), [1, 2, 3]);
+    assert_eq!(encode(&BStr::from("hé")), [2, 0, 0, 0, b'h', 0, 0xE9, 0]);
+    assert_eq!(encode(&Point { x: 1, y: 2 }), [1, 0, 0, 0, 2, 0, 0, 0]);
+    assert_eq!(encode(&Variant::from(1i16)), [2, 0, 1, 0]);
+
+    // Round trips
+    round_trip(-5i8);
+    round_trip(u64::MAX);
+    round_trip(1.5f32);
+    round_trip(-0.25f64);
+    round_trip(isize::MIN);
+    round_trip(false);
+    round_trip(GUID {
+        data1: 0x0102_0304,
+        data2: 0x0506,
+        data3: 0x0708,
+        data4: [9, 10, 11, 12, 13, 14, 15, 16],
+    });
+    round_trip(HResult(E_NOTIMPL));
+    round_trip(BStr::new());
+    round_trip(BStr::from("Hello, wörld! 🦀"));
+    round_trip(Some(BStr::from("x")));
+    round_trip(vec![vec![1u8], vec![], vec![2, 3]]);
+    round_trip(Pair(1, Some(2)));
+    round_trip(Pair(3, None));
+    round_trip(Labelled {
+        label: BStr::from("square"),
+        value: 4.5f64,
+        corners: [Point { x: 0, y: 0 }, Point { x: 2, y: 2 }],
+    });
+    for value in vec![
+        Variant::new(),
+        Variant::from(VariantValue::Null),
+        Variant::from(-3i8),
+        Variant::from(7u64),
+        Variant::from(VariantValue::Int(-1)),
+        Variant::from(VariantValue::UInt(1)),
+        Variant::from(2.5f32),
+        Variant::from(VariantValue::Currency(12_3400)),
+        Variant::from(VariantValue::Date(36526.5)),
+        Variant::from(true),
+        Variant::from(HResult(E_NOTIMPL)),
+        Variant::from("text"),
+        Variant::from(None::<IUnknown>),
+    ] {
+        round_trip(value);
+    }
+    for value in vec![
+        PropVariant::from(1u8),
+        PropVariant::from("wide string"),
+        PropVariant::from(PropVariantValue::FileTime(132_000_000_000_000_000)),
+        PropVariant::from(IUnknown::IID),
+    ] {
+        round_trip(value);
+    }
+    let array = SafeArray::<i32>::with_bounds(&[
+        SAFEARRAYBOUND {
+            cElements: 2,
+            lLbound: -1,
+        },
+        SAFEARRAYBOUND {
+            cElements: 3,
+            lLbound: 1,
+        },
+    ]);
+    let bytes = encode(&array);
+    let decoded = decode::<SafeArray<i32>>(&bytes).unwrap();
+    assert_eq!(decoded.bounds(), array.bounds());
+    round_trip(SafeArray::from(vec![BStr::from("a"), BStr::from("bc")]));
+
+    // Arrays and strings longer than the message are rejected before allocating
+    let mut huge = u32::MAX.to_le_bytes().to_vec();
+    huge.push(0);
+    assert!(decode::<Vec<u64>>(&huge).is_err());
+    assert!(decode::<BStr>(&huge).is_err());
+    let mut huge = vec![2, 0];
+    huge.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0]);
+    huge.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0]);
+    assert!(decode::<SafeArray<u8>>(&huge).is_err());
+
+    // Variants of other types cannot be marshalled
+    let array = Variant::from(SafeArray::from(vec![1i32]));
+    let error = array.marshal(&mut Encoder::new()).unwrap_err();
+    assert_eq!(error.code(), HResult(DISP_E_BADVARTYPE));
+    assert!(decode::<Variant>(&[0x03, 0x20, 1, 0, 0, 0]).is_err());
+
+    // Interface pointers need an object table
+    let object = Object::allocate().query_interface::<IUnknown>().unwrap();
+    let variant = Variant::from(object.clone());
+    let error = variant.marshal(&mut Encoder::new()).unwrap_err();
+    assert_eq!(error.code(), HResult(E_NOTIMPL));
+
+    let objects = Objects::default();
+    let mut encoder = Encoder::with_objects(&objects);
+    variant.marshal(&mut encoder).unwrap();
+    assert_eq!(encoder.as_bytes(), [13, 0, 1, 0, 0, 0, 0]);
+    let mut decoder = Decoder::with_objects(encoder.as_bytes(), &objects);
+    match Variant::unmarshal(&mut decoder).unwrap().into_value() {
+        VariantValue::Unknown(Some(decoded)) => assert_eq!(decoded, object),
+        value => panic!("unexpected value {:?}", value),
+    }
+
+    // Decoding arbitrary data fails without panicking
+    let mut rng = Rng(0x5EED_1234_ABCD_0001);
+    fuzz::<u32>(&mut rng, &[encode(&7u32)]);
+    fuzz::<bool>(&mut rng, &[encode(&true)]);
+    fuzz::<GUID>(&mut rng, &[encode(&IUnknown::IID)]);
+    fuzz::<BStr>(&mut rng, &[encode(&BStr::from("fuzz")), vec![]]);
+    fuzz::<Vec<Option<u16>>>(&mut rng, &[encode(&vec![Some(1u16), None])]);
+    fuzz::<[Point; 2]>(&mut rng, &[encode(&[Point { x: 1, y: 2 }; 2])]);
+    fuzz::<Labelled<Vec<u8>>>(
+        &mut rng,
+        &[encode(&Labelled {
+            label: BStr::from("l"),
+            value: vec![1u8, 2],
+            corners: [Point { x: 3, y: 4 }; 2],
+        })],
+    );
+    fuzz::<SafeArray<BStr>>(&mut rng, &[encode(&SafeArray::from(vec![BStr::from("a")]))]);
+    fuzz::<SafeArray<Variant>>(&mut rng, &[encode(&SafeArray::from(vec![Variant::from(1)]))]);
+    let variants = vec![
+        encode(&Variant::from(1.0)),
+        encode(&Variant::from("v")),
+        encode(&Variant::from(VariantValue::Currency(1))),
+        vec![13, 0, 1, 0, 0, 0, 0],
+    ];
+    fuzz::<Variant>(&mut rng, &variants);
+    fuzz::<PropVariant>(
+        &mut rng,
+        &[
+            encode(&PropVariant::from("p")),
+            encode(&PropVariant::from(IUnknown::IID)),
+        ],
+    );
+}
@@ -0,0 +1,195 @@
+use com::interfaces::{IDispatch, IUnknown};
+use com::sys::{DISPATCH_METHOD, E_FAIL, E_UNEXPECTED, HRESULT, S_FALSE, S_OK};
+use com::{Error, HResult, VariantValue};
+use std::cell::{Cell, RefCell};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+
+com::interfaces! {
+    #[uuid("7b4e2d19-3c8a-4f61-9e05-1d6a3b7c2e01")]
+    #[mock]
+    pub unsafe interface IStore : IUnknown {
+        fn Put(&self, key: u32, value: *const u8, len: u32) -> HRESULT;
+        fn Get(&self, key: u32, #[retval] value: *mut u8) -> HRESULT;
+        fn Len(&self) -> u32;
+        fn Flush(&self);
+        fn Attach(&self, owner: IUnknown) -> HRESULT;
+    }
+
+    #[uuid("7b4e2d19-3c8a-4f61-9e05-1d6a3b7c2e02")]
+    pub unsafe interface IAnimal : IUnknown {
+        fn Eat(&self, food: u32) -> HRESULT;
+        fn Legs(&self) -> u32;
+    }
+
+    #[uuid("7b4e2d19-3c8a-4f61-9e05-1d6a3b7c2e03")]
+    #[mock]
+    pub unsafe interface ICat : IAnimal {
+        fn Purr(&self, #[retval] volume: *mut u32) -> HRESULT;
+        fn Name(&self) -> *const u16;
+    }
+
+    #[uuid("7b4e2d19-3c8a-4f61-9e05-1d6a3b7c2e04")]
+    #[mock]
+    pub unsafe interface ICounter : IDispatch {
+        fn Increment(&self, #[retval] value: *mut u32) -> HRESULT;
+    }
+}
+
+/// Client code under test
+fn copy(store: &IStore, from: u32, to: u32) -> Result<(), Error> {
+    let value = unsafe { store.Get(from) }?;
+    HResult(unsafe { store.Put(to, &value, 1) }).ok()?;
+    unsafe { store.Flush() };
+    Ok(())
+}
+
+fn main() {
+    // Closures return values and capture arguments
+    let mock = MockIStore::new();
+    let puts = Rc::new(RefCell::new(Vec::new()));
+    let log = puts.clone();
+    mock.expect_put(move |key, value, len| {
+        let value = unsafe { std::slice::from_raw_parts(value, len as usize) };
+        log.borrow_mut().push((key, value.to_vec()));
+        S_OK
+    })
+    .times(1);
+    mock.expect_get(|key| match key {
+        1 => Ok(42),
+        _ => Err(Error::with_description(HResult(E_FAIL), "no such key")),
+    });
+    let flushes = Rc::new(Cell::new(0));
+    let count = flushes.clone();
+    mock.expect_flush(move || count.set(count.get() + 1));
+
+    let store = mock.interface();
+    copy(&store, 1, 2).unwrap();
+    assert_eq!(*puts.borrow(), [(2, vec![42])]);
+    assert_eq!(flushes.get(), 1);
+
+    // Errors of `#[retval]` methods are returned to the caller
+    let error = copy(&store, 3, 4).unwrap_err();
+    assert_eq!(error.code(), HResult(E_FAIL));
+    assert_eq!(error.description(), Some("no such key"));
+    assert_eq!(mock.expect_get(|_| Ok(0)).calls(), 2);
+    drop(store);
+    mock.verify();
+    drop(mock);
+
+    // Interfaces can be passed to the closures
+    let mock = MockIStore::new();
+    let owner = MockIStore::new();
+    let attached = Rc::new(RefCell::new(None));
+    let slot = attached.clone();
+    mock.expect_attach(move |owner| {
+        *slot.borrow_mut() = Some(owner);
+        S_FALSE
+    });
+    owner.expect_len(|| 7);
+    let unknown = owner.interface().query_interface::<IUnknown>().unwrap();
+    assert_eq!(unsafe { mock.interface().Attach(unknown) }, S_FALSE);
+    let attached = attached.borrow_mut().take().unwrap();
+    let attached = attached.query_interface::<IStore>().unwrap();
+    assert_eq!(unsafe { attached.Len() }, 7);
+    drop(attached);
+
+    // Closures can call other methods of the mock
+    let store = mock.interface();
+    let inner = store.clone();
+    mock.expect_len(move || unsafe { inner.Get(0) }.unwrap() as u32 + 1);
+    mock.expect_get(|_| Ok(2));
+    assert_eq!(unsafe { store.Len() }, 3);
+    assert_eq!(unsafe { store.Len() }, 3);
+    drop(store);
+    drop(mock);
+
+    // Unexpected calls fail, and the failures are reported when the mock is dropped,
+    // in the order of the methods
+    let mock = MockIStore::new();
+    mock.expect_put(|_, _, _| S_OK).times(2);
+    let store = mock.interface();
+    assert_eq!(unsafe { store.Put(1, std::ptr::null(), 0) }, S_OK);
+    assert_eq!(unsafe { store.Attach(&store) }, E_UNEXPECTED);
+    let error = unsafe { store.Get(0) }.unwrap_err();
+    assert_eq!(error.code(), HResult(E_UNEXPECTED));
+    let failures = catch_unwind(AssertUnwindSafe(|| drop(mock))).unwrap_err();
+    let failures = failures.downcast::<String>().unwrap();
+    assert!(failures.starts_with("3 expectation(s) of "), "{}", failures);
+    assert!(
+        failures.ends_with(
+            ":\n  IStore::Put was called 1 times, but 2 calls were expected\
+             \n  unexpected call to IStore::Get\
+             \n  unexpected call to IStore::Attach"
+        ),
+        "{}",
+        failures
+    );
+
+    drop(store);
+
+    // Calls beyond the expected count fail
+    let mock = MockIStore::new();
+    mock.expect_put(|_, _, _| S_OK).times(0);
+    assert_eq!(
+        unsafe { mock.interface().Put(1, std::ptr::null(), 0) },
+        E_UNEXPECTED
+    );
+    let failures = catch_unwind(AssertUnwindSafe(|| mock.verify())).unwrap_err();
+    let failures = failures.downcast::<String>().unwrap();
+    assert!(
+        failures.ends_with(":\n  IStore::Put was called 1 times, but 0 calls were expected"),
+        "{}",
+        failures
+    );
+    assert!(catch_unwind(AssertUnwindSafe(|| drop(mock))).is_err());
+
+    // Methods which do not return an `HRESULT` return a default value when they fail
+    let mock = MockIStore::new();
+    let store = mock.interface();
+    assert_eq!(unsafe { store.Len() }, 0);
+    unsafe { store.Flush() };
+    let failures = catch_unwind(AssertUnwindSafe(|| drop(mock))).unwrap_err();
+    let failures = failures.downcast::<String>().unwrap();
+    assert!(
+        failures.ends_with(
+            ":\n  unexpected call to IStore::Len\
+             \n  unexpected call to IStore::Flush"
+        ),
+        "{}",
+        failures
+    );
+    drop(store);
+
+    // The methods of the base interfaces are mocked too
+    let mock = MockICat::new();
+    mock.expect_eat(|_| S_OK).times(1);
+    mock.expect_purr(|| Ok(3));
+    let cat = mock.interface();
+    assert_eq!(unsafe { cat.Purr() }, Ok(3));
+    let animal = cat.query_interface::<IAnimal>().unwrap();
+    assert_eq!(unsafe { animal.Eat(1) }, S_OK);
+    assert_eq!(unsafe { animal.Legs() }, 0);
+    assert!(unsafe { cat.Name() }.is_null());
+    drop((cat, animal));
+    let failures = catch_unwind(AssertUnwindSafe(|| drop(mock))).unwrap_err();
+    let failures = failures.downcast::<String>().unwrap();
+    assert!(
+        failures.ends_with(
+            ":\n  unexpected call to IAnimal::Legs\
+             \n  unexpected call to ICat::Name"
+        ),
+        "{}",
+        failures
+    );
+
+    // Mocks of interfaces deriving from `IDispatch` implement it
+    let mock = MockICounter::new();
+    mock.expect_increment(|| Ok(1));
+    let counter = mock.interface();
+    assert_eq!(unsafe { counter.Increment() }, Ok(1));
+    let increment = counter.get_id_of_name("Increment").unwrap();
+    let value = counter.invoke(increment, DISPATCH_METHOD, &[]).unwrap();
+    assert_eq!(value.value(), VariantValue::UI4(1));
+    assert_eq!(mock.expect_increment(|| Ok(2)).calls(), 2);
+}
@@ -0,0 +1,160 @@
+use com::interfaces::IUnknown;
+use com::sys::{E_FAIL, E_UNEXPECTED, HRESULT, S_OK};
+use com::{HResult, Interface};
+use std::process::Command;
+
+com::interfaces! {
+    #[uuid("3b8e7a52-9d61-4f0c-a2e4-5c7d1f9b0a01")]
+    pub unsafe interface IFallible : IUnknown {
+        fn Run(&self, panic: bool) -> HRESULT;
+        fn Check(&self, panic: bool) -> HResult;
+        fn Get(&self, panic: bool, #[retval] value: *mut u32) -> HRESULT;
+        fn Count(&self, panic: bool) -> u32;
+    }
+}
+
+com::class! {
+    #[no_class_factory]
+    pub class Unexpected : IFallible {}
+
+    impl IFallible for Unexpected {
+        fn Run(&self, panic: bool) -> HRESULT {
+            if panic {
+                panic!("Run");
+            }
+            S_OK
+        }
+
+        fn Check(&self, panic: bool) -> HResult {
+            if panic {
+                panic!("Check");
+            }
+            HResult(S_OK)
+        }
+
+        fn Get(&self, panic: bool) -> Result<u32, HRESULT> {
+            if panic {
+                panic!("Get");
+            }
+            Ok(42)
+        }
+
+        fn Count(&self, panic: bool) -> u32 {
+            if panic {
+                panic!("Count");
+            }
+            1
+        }
+    }
+}
+
+com::class! {
+    #[no_class_factory]
+    #[on_panic(E_FAIL)]
+    pub class Failing : IFallible {}
+
+    impl IFallible for Failing {
+        fn Run(&self, _panic: bool) -> HRESULT {
+            panic!("Run")
+        }
+
+        fn Check(&self, _panic: bool) -> HResult {
+            panic!("Check")
+        }
+
+        fn Get(&self, _panic: bool) -> Result<u32, HRESULT> {
+            panic!("Get")
+        }
+
+        fn Count(&self, _panic: bool) -> u32 {
+            1
+        }
+    }
+}
+
+com::class! {
+    #[no_class_factory]
+    #[on_panic(abort)]
+    pub class Aborting : IFallible {}
+
+    impl IFallible for Aborting {
+        fn Run(&self, _panic: bool) -> HRESULT {
+            panic!("Run")
+        }
+
+        fn Check(&self, _panic: bool) -> HResult {
+            HResult(S_OK)
+        }
+
+        fn Get(&self, _panic: bool) -> Result<u32, HRESULT> {
+            Ok(0)
+        }
+
+        fn Count(&self, _panic: bool) -> u32 {
+            1
+        }
+    }
+}
+
+/// Runs this test in a child process calling `method`, which is expected to abort
+/// with a diagnostic naming it
+fn assert_aborts(method: &str) {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .arg(method)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        assert_eq!(output.status.signal(), Some(6));
+    }
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let diagnostic = format!(
+        "{} panicked and cannot return an error to its caller, aborting",
+        method
+    );
+    assert!(stderr.contains(&diagnostic), "{}", stderr);
+}
+
+fn main() {
+    let unexpected = Unexpected::allocate().query_interface::<IFallible>().unwrap();
+    let aborting = Aborting::allocate().query_interface::<IFallible>().unwrap();
+    if let Some(method) = std::env::args().nth(1) {
+        match method.as_str() {
+            "Unexpected::Count" => unsafe {
+                unexpected.Count(true);
+            },
+            "Aborting::Run" => unsafe {
+                aborting.Run(true);
+            },
+            _ => unreachable!(),
+        }
+        return;
+    }
+    std::panic::set_hook(Box::new(|_| {}));
+
+    // Panics in methods returning an HRESULT become E_UNEXPECTED
+    unsafe {
+        assert_eq!(unexpected.Run(false), S_OK);
+        assert_eq!(unexpected.Run(true), E_UNEXPECTED);
+        assert_eq!(unexpected.Check(true), HResult(E_UNEXPECTED));
+        assert_eq!(unexpected.Get(false), Ok(42));
+        assert_eq!(unexpected.Get(true).unwrap_err().code(), HResult(E_UNEXPECTED));
+        assert_eq!(unexpected.Count(false), 1);
+    }
+
+    // The HRESULT is set by #[on_panic]
+    let failing = Failing::allocate().query_interface::<IFallible>().unwrap();
+    unsafe {
+        assert_eq!(failing.Run(false), E_FAIL);
+        assert_eq!(failing.Check(false), HResult(E_FAIL));
+        assert_eq!(failing.Get(false).unwrap_err().code(), HResult(E_FAIL));
+    }
+    assert!(failing.query_interface::<IUnknown>().is_some());
+
+    // Other methods, and all methods of #[on_panic(abort)] classes, abort
+    assert_aborts("Unexpected::Count");
+    assert_aborts("Aborting::Run");
+    assert!(aborting.as_iunknown().query_interface::<IFallible>().is_some());
+}
@@ -0,0 +1,51 @@
+use com::interfaces::IUnknown;
+use com::sys::{E_INVALIDARG, HRESULT};
+use com::{Error, HResult};
+
+com::interfaces! {
+    #[uuid("0b4d9b07-3f83-4d53-9d0e-5a25c0c0b7a1")]
+    pub unsafe interface ICalculator : IUnknown {
+        fn Divide(&self, a: i32, b: i32, #[retval] result: *mut i32) -> HRESULT;
+        fn Spawn(&self, #[retval] child: *mut Option<ICalculator>) -> HResult;
+    }
+}
+
+com::class! {
+    pub class Calculator : ICalculator {}
+
+    impl ICalculator for Calculator {
+        fn Divide(&self, a: i32, b: i32) -> Result<i32, HRESULT> {
+            if b == 0 {
+                return Err(E_INVALIDARG);
+            }
+            Ok(a / b)
+        }
+
+        fn Spawn(&self) -> Result<Option<ICalculator>, Error> {
+            Ok(Calculator::allocate().query_interface::<ICalculator>())
+        }
+    }
+}
+
+fn main() {
+    let calculator = Calculator::allocate()
+        .query_interface::<ICalculator>()
+        .unwrap();
+
+    assert_eq!(unsafe { calculator.Divide(10, 2) }, Ok(5));
+    let error = unsafe { calculator.Divide(10, 0) }.unwrap_err();
+    assert_eq!(error.code(), HResult(E_INVALIDARG));
+
+    let child = unsafe { calculator.Spawn() }.unwrap().unwrap();
+    assert_eq!(unsafe { child.Divide(9, 3) }, Ok(3));
+    assert_ne!(child, calculator);
+
+    // The raw vtable still takes the out parameter
+    let mut result = 0;
+    let hr = unsafe {
+        let this = <ICalculator as com::AbiTransferable>::get_abi(&calculator);
+        (this.as_ref().as_ref().Divide)(this, 8, 4, &mut result)
+    };
+    assert_eq!(hr, com::sys::S_OK);
+    assert_eq!(result, 2);
+}
@@ -0,0 +1,79 @@
+#![deny(unused_unsafe)]
+
+use com::interfaces::IUnknown;
+use com::sys::{HRESULT, S_OK};
+use com::BStr;
+use std::cell::{Cell, RefCell};
+
+com::interfaces! {
+    #[uuid("6f0b3c2e-5d7a-4f1b-9a8e-3c2d1b0a9f81")]
+    pub unsafe interface ICounter : IUnknown {
+        #[safe]
+        fn Add(&self, amount: u32) -> HRESULT;
+        #[safe]
+        fn Count(&self, #[retval] count: *mut u32) -> HRESULT;
+        fn CopyTo(&self, count: *mut u32) -> HRESULT;
+    }
+
+    #[safe]
+    #[uuid("6f0b3c2e-5d7a-4f1b-9a8e-3c2d1b0a9f82")]
+    pub unsafe interface ILabel : IUnknown {
+        fn SetText(&self, text: BStr) -> HRESULT;
+        fn Text(&self, #[retval] text: *mut BStr) -> HRESULT;
+    }
+}
+
+com::class! {
+    pub class Counter : ICounter, ILabel {
+        count: Cell<u32>,
+        text: RefCell<BStr>,
+    }
+
+    impl ICounter for Counter {
+        fn Add(&self, amount: u32) -> HRESULT {
+            self.count.set(self.count.get() + amount);
+            S_OK
+        }
+
+        fn Count(&self) -> Result<u32, com::Error> {
+            Ok(self.count.get())
+        }
+
+        fn CopyTo(&self, count: *mut u32) -> HRESULT {
+            unsafe { *count = self.count.get() };
+            S_OK
+        }
+    }
+
+    impl ILabel for Counter {
+        fn SetText(&self, text: BStr) -> HRESULT {
+            *self.text.borrow_mut() = text;
+            S_OK
+        }
+
+        fn Text(&self) -> Result<BStr, com::Error> {
+            Ok(self.text.borrow().clone())
+        }
+    }
+}
+
+fn main() {
+    let counter = Counter::allocate(Cell::new(0), RefCell::new(BStr::new()))
+        .query_interface::<ICounter>()
+        .unwrap();
+
+    // `#[safe]` methods are called without `unsafe`
+    assert_eq!(counter.Add(2), S_OK);
+    assert_eq!(counter.Add(3), S_OK);
+    assert_eq!(counter.Count(), Ok(5));
+
+    // Other methods of the interface are still unsafe
+    let mut count = 0;
+    assert_eq!(unsafe { counter.CopyTo(&mut count) }, S_OK);
+    assert_eq!(count, 5);
+
+    // `#[safe]` on an interface applies to all of its methods
+    let label = counter.query_interface::<ILabel>().unwrap();
+    assert_eq!(label.SetText("five"), S_OK);
+    assert_eq!(label.Text().unwrap(), "five");
+}
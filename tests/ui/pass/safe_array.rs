@@ -0,0 +1,143 @@
+use com::interfaces::IUnknown;
+use com::sys::{SAFEARRAYBOUND, HRESULT, S_OK, VT_ARRAY, VT_BSTR, DISP_E_TYPEMISMATCH};
+use com::{BStr, HResult, SafeArray, Variant, VariantValue};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+
+com::interfaces! {
+    #[uuid("2d39c8a3-86f5-4f7a-8f6e-2a2b2f4b7c04")]
+    pub unsafe interface INames : IUnknown {
+        fn SetNames(&self, names: SafeArray<BStr>) -> HRESULT;
+        fn GetNames(&self, #[retval] names: *mut SafeArray<BStr>) -> HRESULT;
+    }
+}
+
+com::class! {
+    pub class Names : INames {
+        names: RefCell<Vec<BStr>>,
+    }
+
+    impl INames for Names {
+        fn SetNames(&self, names: SafeArray<BStr>) -> HRESULT {
+            *self.names.borrow_mut() = names.to_vec().unwrap();
+            S_OK
+        }
+
+        fn GetNames(&self) -> Result<SafeArray<BStr>, com::Error> {
+            Ok(SafeArray::from(self.names.borrow().clone()))
+        }
+    }
+}
+
+fn main() {
+    // One-dimensional arrays
+    let mut array = SafeArray::from(vec![1i32, 2, 3]);
+    assert_eq!(array.dims(), 1);
+    assert_eq!(array.len(), 3);
+    {
+        let mut elements = array.lock_mut().unwrap();
+        elements[1] = 20;
+    }
+    assert_eq!(array.to_vec().unwrap(), [1, 20, 3]);
+    unsafe {
+        // The array is unlocked once the guards are dropped
+        assert_eq!((*array.as_ptr()).cLocks, 0);
+        let guard = array.lock().unwrap();
+        let other = array.lock().unwrap();
+        assert_eq!((*array.as_ptr()).cLocks, 2);
+        drop((guard, other));
+        assert_eq!((*array.as_ptr()).cLocks, 0);
+    }
+
+    // Arrays can be locked by several threads at once
+    let shared = std::sync::Arc::new(array);
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let array = shared.clone();
+            std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    assert_eq!(array.lock().unwrap()[2], 3);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let array = std::sync::Arc::try_unwrap(shared).unwrap();
+    assert_eq!(unsafe { (*array.as_ptr()).cLocks }, 0);
+
+    // New arrays are zeroed
+    let empty = SafeArray::<f64>::new(0);
+    assert!(empty.is_empty());
+    assert!(empty.lock().unwrap().is_empty());
+    assert_eq!(SafeArray::<u8>::new(4).to_vec().unwrap(), [0; 4]);
+    assert_eq!(SafeArray::<Variant>::new(1).to_vec().unwrap(), [Variant::new()]);
+
+    // Multi-dimensional arrays are stored in column-major order
+    let bounds = [
+        SAFEARRAYBOUND { cElements: 2, lLbound: 1 },
+        SAFEARRAYBOUND { cElements: 3, lLbound: -1 },
+    ];
+    let mut matrix = SafeArray::<i16>::with_bounds(&bounds);
+    assert_eq!(matrix.bounds(), bounds);
+    assert_eq!(matrix.len(), 6);
+    // The bounds are stored in reverse order, like `SafeArrayCreate` does
+    unsafe { assert_eq!((*matrix.as_ptr()).rgsabound[0], bounds[1]) };
+    assert_eq!(matrix.offset(&[1, -1]), Some(0));
+    assert_eq!(matrix.offset(&[2, -1]), Some(1));
+    assert_eq!(matrix.offset(&[1, 0]), Some(2));
+    assert_eq!(matrix.offset(&[2, 1]), Some(5));
+    assert_eq!(matrix.offset(&[0, 0]), None);
+    assert_eq!(matrix.offset(&[1, 2]), None);
+    assert_eq!(matrix.offset(&[1]), None);
+    let offset = matrix.offset(&[2, 0]).unwrap();
+    matrix.lock_mut().unwrap()[offset] = 7;
+    assert_eq!(matrix.to_vec().unwrap(), [0, 0, 0, 7, 0, 0]);
+    let copy = matrix.clone();
+    assert_eq!(copy, matrix);
+    assert_eq!(copy.bounds(), bounds);
+
+    // Strings and variants are copied with the array
+    let strings = SafeArray::from(vec![BStr::from("a"), BStr::from("b")]);
+    let copy = strings.clone();
+    assert_ne!(copy.lock().unwrap()[0].as_ptr(), strings.lock().unwrap()[0].as_ptr());
+    assert_eq!(copy, strings);
+    let variants = SafeArray::from(vec![Variant::from(1), Variant::from("two")]);
+    assert_eq!(variants.clone().to_vec().unwrap()[1].value(), VariantValue::BStr("two".into()));
+
+    // Interface pointers are reference counted
+    let names = Names::allocate(RefCell::new(Vec::new()));
+    let unknown = names.query_interface::<IUnknown>().unwrap();
+    let interfaces = SafeArray::from(vec![Some(unknown.clone()), None]);
+    let copy = interfaces.clone();
+    drop(interfaces);
+    assert_eq!(copy.to_vec().unwrap(), [Some(unknown.clone()), None]);
+    drop(copy);
+
+    // Arrays in variants
+    let variant = Variant::from(strings.clone());
+    assert_eq!(variant.vt(), VT_ARRAY | VT_BSTR);
+    assert_eq!(SafeArray::<BStr>::try_from(&variant).unwrap(), strings);
+    assert_eq!(SafeArray::<BStr>::try_from(&variant.clone()).unwrap(), strings);
+    let err = SafeArray::<i32>::try_from(&variant).unwrap_err();
+    assert_eq!(err.code(), HResult(DISP_E_TYPEMISMATCH));
+    assert!(SafeArray::<BStr>::try_from(&Variant::from(1)).is_err());
+
+    // Passing arrays through COM methods
+    let names = names.query_interface::<INames>().unwrap();
+    assert!(unsafe { names.GetNames() }.unwrap().is_empty());
+    assert_eq!(unsafe { names.SetNames(&strings) }, S_OK);
+    // The caller keeps ownership of `[in]` arrays
+    assert_eq!(strings.len(), 2);
+    drop(strings);
+    let result = unsafe { names.GetNames() }.unwrap();
+    assert_eq!(result.to_vec().unwrap(), [BStr::from("a"), BStr::from("b")]);
+
+    // Null arrays are received as empty arrays
+    let raw = com::Interface::as_raw(&names);
+    let vtable = unsafe { raw.as_ref().as_ref() };
+    let hr = unsafe { (vtable.SetNames)(raw, std::ptr::null_mut()) };
+    assert_eq!(hr, S_OK);
+    assert!(unsafe { names.GetNames() }.unwrap().is_empty());
+}
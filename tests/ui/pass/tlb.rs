@@ -0,0 +1,61 @@
+// The Rust generated by `com-tlb` from `macros/support/tests/tlb/clock.tlb`
+#[allow(dead_code)]
+#[path = "../../../macros/support/tests/tlb/clock.rs"]
+mod clock;
+
+use clock::*;
+use com::Interface;
+
+com::class! {
+    pub class Alarm: IAlarm {
+        count: std::cell::Cell<i32>,
+    }
+
+    impl IAlarm for Alarm {
+        fn Ring(&self, count: i32) -> com::sys::HRESULT {
+            self.count.set(self.count.get() + count);
+            com::sys::NOERROR
+        }
+
+        fn GetTime(&self) -> Result<f64, com::Error> {
+            Ok(0.5)
+        }
+
+        fn Next(&self, _count: u32, _values: *mut com::sys::VARIANT, fetched: *mut u32) -> com::sys::HRESULT {
+            unsafe { *fetched = 0 };
+            com::sys::S_FALSE
+        }
+
+        fn Flags(&self) -> Result<CLOCK_FLAGS, com::Error> {
+            Ok(CLOCK_FLAGS_SECONDS | CLOCK_FLAGS_DATE)
+        }
+
+        fn Reset(&self) {
+            self.count.set(0);
+        }
+    }
+}
+
+fn main() {
+    assert_eq!(
+        IAlarm::IID,
+        com::guid!("4a1bd7a8-61c4-4d3b-9f5e-2f1c0d9a7e13")
+    );
+    assert_eq!(
+        IClock::IID,
+        com::guid!("50c83a1c-e072-4c48-87b0-3630fa36a6d0")
+    );
+    assert_eq!(
+        CLSID_Clock,
+        com::guid!("c5f45cbc-4439-418c-a9f9-05ac67525e43")
+    );
+    assert_eq!(CLOCK_FLAGS_INVALID, -1);
+    assert_eq!(std::mem::size_of::<ALARM>(), 40);
+
+    let alarm = Alarm::allocate(Default::default())
+        .query_interface::<IAlarm>()
+        .unwrap();
+    assert_eq!(unsafe { alarm.Ring(2) }, com::sys::NOERROR);
+    assert_eq!(unsafe { alarm.GetTime() }, Ok(0.5));
+    assert_eq!(unsafe { alarm.Flags() }, Ok(3));
+}
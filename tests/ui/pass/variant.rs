@@ -0,0 +1,140 @@
+use com::interfaces::IUnknown;
+use com::sys::{HRESULT, DISP_E_OVERFLOW, DISP_E_TYPEMISMATCH, S_OK, VT_BSTR, VT_BYREF, VT_DECIMAL, VT_I4, VT_LPWSTR};
+use com::{BStr, HResult, PropVariant, PropVariantValue, Variant, VariantValue};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+
+com::interfaces! {
+    #[uuid("2d39c8a3-86f5-4f7a-8f6e-2a2b2f4b7c03")]
+    pub unsafe interface IHolder : IUnknown {
+        fn SetValue(&self, value: Variant) -> HRESULT;
+        fn GetValue(&self, #[retval] value: *mut Variant) -> HRESULT;
+    }
+}
+
+com::class! {
+    pub class Holder : IHolder {
+        value: RefCell<Variant>,
+    }
+
+    impl IHolder for Holder {
+        fn SetValue(&self, value: Variant) -> HRESULT {
+            *self.value.borrow_mut() = value;
+            S_OK
+        }
+
+        fn GetValue(&self) -> Result<Variant, com::Error> {
+            Ok(self.value.borrow().clone())
+        }
+    }
+}
+
+fn main() {
+    // Scalars
+    assert!(Variant::new().is_empty());
+    assert_eq!(Variant::default().value(), VariantValue::Empty);
+    let v = Variant::from(42);
+    assert_eq!(v.vt(), VT_I4);
+    assert_eq!(v.value(), VariantValue::I4(42));
+    assert_eq!(Variant::from(true).value(), VariantValue::Bool(true));
+    assert_eq!(Variant::from(1.5).value(), VariantValue::R8(1.5));
+    assert_eq!(Variant::from(VariantValue::Currency(12_3400)).value(), VariantValue::Currency(12_3400));
+    assert_eq!(Variant::from(HResult(DISP_E_OVERFLOW)).value(), VariantValue::Error(HResult(DISP_E_OVERFLOW)));
+
+    // Conversions between integer types are range-checked
+    assert_eq!(i64::try_from(&v).unwrap(), 42);
+    assert_eq!(u8::try_from(&v).unwrap(), 42);
+    assert_eq!(f64::try_from(&v).unwrap(), 42.0);
+    let err = u8::try_from(&Variant::from(-1)).unwrap_err();
+    assert_eq!(err.code(), HResult(DISP_E_OVERFLOW));
+    let err = bool::try_from(&v).unwrap_err();
+    assert_eq!(err.code(), HResult(DISP_E_TYPEMISMATCH));
+
+    // Strings
+    let s = Variant::from("hello");
+    assert_eq!(s.vt(), VT_BSTR);
+    assert_eq!(s.value(), VariantValue::BStr(BStr::from("hello")));
+    assert_eq!(String::try_from(&s).unwrap(), "hello");
+    let clone = s.clone();
+    assert_ne!(
+        unsafe { clone.as_raw().data.bstrVal },
+        unsafe { s.as_raw().data.bstrVal }
+    );
+    assert_eq!(clone, s);
+    assert!(i32::try_from(&s).is_err());
+
+    // Interfaces are reference counted
+    let holder = Holder::allocate(RefCell::new(Variant::new()));
+    let unknown = holder.query_interface::<IUnknown>().unwrap();
+    let v = Variant::from(unknown.clone());
+    let clone = v.clone();
+    assert_eq!(Option::<IUnknown>::try_from(&clone).unwrap(), Some(unknown.clone()));
+    drop(v);
+    drop(clone);
+    assert_eq!(Variant::from(None::<IUnknown>).value(), VariantValue::Unknown(None));
+
+    // References are followed
+    let mut target = Variant::from("target");
+    let mut raw = Variant::new().into_raw();
+    raw.vt = VT_BYREF | com::sys::VT_VARIANT;
+    raw.data.byref = &mut target as *mut Variant as *mut _;
+    let by_ref = unsafe { Variant::from_raw(raw) };
+    assert_eq!(by_ref.value(), VariantValue::BStr(BStr::from("target")));
+    let mut number = 7i16;
+    let mut raw = Variant::new().into_raw();
+    raw.vt = VT_BYREF | com::sys::VT_I2;
+    raw.data.byref = &mut number as *mut i16 as *mut _;
+    let by_ref = unsafe { Variant::from_raw(raw) };
+    assert_eq!(i32::try_from(&by_ref).unwrap(), 7);
+
+    // Other types are copied, and shown by their type
+    let mut raw = Variant::new().into_raw();
+    raw.vt = VT_DECIMAL;
+    raw.data.llVal = 12;
+    let decimal = unsafe { Variant::from_raw(raw) };
+    let copy = decimal.try_clone().unwrap();
+    assert_eq!(copy.vt(), VT_DECIMAL);
+    assert_eq!(unsafe { copy.as_raw().data.llVal }, 12);
+    assert_eq!(format!("{:?}", decimal.clone()), "Variant(14)");
+    assert!(matches!(decimal.value(), VariantValue::Other(v) if v.vt() == VT_DECIMAL));
+    assert_ne!(decimal, copy);
+    let mut raw = PropVariant::new().into_raw();
+    raw.vt = VT_DECIMAL;
+    let decimal_prop = unsafe { PropVariant::from_raw(raw) };
+    assert_eq!(format!("{:?}", decimal_prop.clone()), "PropVariant(14)");
+    assert!(matches!(decimal_prop.value(), PropVariantValue::Other(v) if v.vt() == VT_DECIMAL));
+
+    // Passing variants through COM methods
+    let holder = holder.query_interface::<IHolder>().unwrap();
+    let value = Variant::from("first");
+    assert_eq!(unsafe { holder.SetValue(&value) }, S_OK);
+    // The caller keeps ownership of `[in]` variants
+    assert_eq!(value, Variant::from("first"));
+    drop(value);
+    assert_eq!(unsafe { holder.GetValue() }.unwrap(), Variant::from("first"));
+    assert_eq!(unsafe { holder.SetValue(Variant::from(5u8)) }, S_OK);
+    assert_eq!(unsafe { holder.GetValue() }.unwrap().value(), VariantValue::UI1(5));
+    assert_eq!(unsafe { holder.SetValue(&decimal) }, S_OK);
+    assert_eq!(unsafe { holder.GetValue() }.unwrap().vt(), VT_DECIMAL);
+
+    // PROPVARIANT strings are stored as VT_LPWSTR
+    let p = PropVariant::from("wide");
+    assert_eq!(p.vt(), VT_LPWSTR);
+    assert_eq!(p.value(), PropVariantValue::LpWStr("wide".into()));
+    assert_eq!(String::try_from(&p).unwrap(), "wide");
+    assert_eq!(p.clone(), p);
+    assert!(Variant::try_from(p).is_err());
+
+    let guid = <IUnknown as com::Interface>::IID;
+    assert_eq!(PropVariant::from(guid).value(), PropVariantValue::Clsid(guid));
+    assert_eq!(
+        PropVariant::from(PropVariantValue::FileTime(1234)).value(),
+        PropVariantValue::FileTime(1234)
+    );
+
+    // Types shared with VARIANT convert both ways
+    let p = PropVariant::from(Variant::from("shared"));
+    assert_eq!(p.value(), PropVariantValue::Variant(VariantValue::BStr("shared".into())));
+    assert_eq!(u16::try_from(&PropVariant::from(9u16)).unwrap(), 9);
+    assert_eq!(Variant::try_from(p).unwrap(), Variant::from("shared"));
+}
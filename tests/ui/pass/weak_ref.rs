@@ -0,0 +1,131 @@
+use com::interfaces::{IUnknown, IWeakReference, IWeakReferenceSource};
+use com::{Interface, WeakRef};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+com::interfaces! {
+    #[uuid("1c5b1d0e-95a0-4a8e-8d6b-7d1b9b0e7a01")]
+    pub unsafe interface IWindow : IUnknown {
+        fn Click(&self) -> u32;
+    }
+
+    #[uuid("1c5b1d0e-95a0-4a8e-8d6b-7d1b9b0e7a02")]
+    pub unsafe interface IHandler : IUnknown {
+        fn Invoke(&self) -> u32;
+    }
+
+    #[uuid("1c5b1d0e-95a0-4a8e-8d6b-7d1b9b0e7a03")]
+    pub unsafe interface IUnrelated : IUnknown {}
+}
+
+com::class! {
+    #[weak_ref]
+    pub class Window : IWindow {
+        handler: RefCell<Option<IHandler>>,
+        clicks: Cell<u32>,
+        drops: Rc<Cell<u32>>,
+    }
+
+    impl IWindow for Window {
+        fn Click(&self) -> u32 {
+            self.clicks.set(self.clicks.get() + 1);
+            match &*self.handler.borrow() {
+                Some(handler) => unsafe { handler.Invoke() },
+                None => 0,
+            }
+        }
+    }
+}
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        self.drops.set(self.drops.get() + 1);
+    }
+}
+
+com::class! {
+    #[no_class_factory]
+    pub class Handler : IHandler {
+        window: WeakRef<IWindow>,
+    }
+
+    impl IHandler for Handler {
+        fn Invoke(&self) -> u32 {
+            // The handler only holds a weak reference to the window, so there is
+            // no reference cycle between the two.
+            match self.window.upgrade() {
+                Some(_window) => 1,
+                None => 2,
+            }
+        }
+    }
+}
+
+fn main() {
+    let drops = Rc::new(Cell::new(0));
+    let window = Window::allocate(RefCell::new(None), Cell::new(0), drops.clone());
+
+    let weak = window.downgrade::<IWindow>().unwrap();
+    let handler = Handler::allocate(weak.clone());
+    *window.handler.borrow_mut() = handler.query_interface::<IHandler>();
+    drop(handler);
+
+    let interface = window.query_interface::<IWindow>().unwrap();
+    drop(window);
+    assert_eq!(unsafe { interface.Click() }, 1);
+
+    // Upgrading yields new strong references
+    let upgraded = weak.upgrade().unwrap();
+    assert_eq!(upgraded, interface);
+    drop(upgraded);
+
+    // Weak references can be created from interface pointers, for any interface
+    let weak_unknown = WeakRef::<IUnknown>::new(&interface).unwrap();
+    assert_eq!(
+        weak_unknown.upgrade().unwrap(),
+        interface.query_interface::<IUnknown>().unwrap()
+    );
+    let weak_unrelated = WeakRef::<IUnrelated>::new(&interface).unwrap();
+    assert!(weak_unrelated.upgrade().is_none());
+
+    // The class implements `IWeakReferenceSource`
+    let source = interface.query_interface::<IWeakReferenceSource>().unwrap();
+    let reference = unsafe { source.GetWeakReference() }.unwrap();
+    assert!(reference.query_interface::<IWeakReference>().is_some());
+    assert!(reference.query_interface::<IWindow>().is_none());
+    let from_reference = WeakRef::<IWindow>::from_weak_reference(reference);
+    drop(source);
+
+    // Weak references do not keep the window alive
+    drop(interface);
+    assert_eq!(drops.get(), 1);
+    assert!(weak.upgrade().is_none());
+    assert!(weak_unknown.upgrade().is_none());
+    assert!(from_reference.upgrade().is_none());
+    assert!(weak.clone().upgrade().is_none());
+
+    // Classes without #[weak_ref] do not support weak references
+    let handler = Handler::allocate(weak);
+    assert!(handler.downgrade::<IHandler>().is_none());
+    let handler = handler.query_interface::<IHandler>().unwrap();
+    assert!(WeakRef::<IHandler>::new(&handler).is_none());
+    assert_eq!(unsafe { handler.Invoke() }, 2);
+
+    // Weak references can be shared with other threads
+    let window = Window::allocate(RefCell::new(None), Cell::new(0), drops.clone());
+    let weak = window.downgrade::<IWindow>().unwrap();
+    let reference = weak.as_weak_reference().clone();
+    let raw = reference.as_raw().as_ptr() as usize;
+    std::mem::forget(reference);
+    std::thread::spawn(move || {
+        let reference: IWeakReference =
+            unsafe { std::mem::transmute(raw as *mut std::ffi::c_void) };
+        let weak = WeakRef::<IWindow>::from_weak_reference(reference);
+        assert!(weak.upgrade().is_some());
+    })
+    .join()
+    .unwrap();
+    drop(window);
+    assert_eq!(drops.get(), 2);
+    assert!(weak.upgrade().is_none());
+}